@@ -0,0 +1,372 @@
+use crate::error::Error;
+use crate::model::base::*;
+use crate::model::ir::{Statement, TemplatePiece, VariableId};
+use crate::model::ttg::TreeData;
+use crate::model::types::Type;
+use crate::span::Span;
+
+/// Checks an inline assembly expression once the types of its operands are known, and splits
+/// its template into the pieces that code generation substitutes operand locations into.
+/// `types` holds the type of each operand's expression, in order.
+pub fn check<P: TreeData<P>>(
+    assembly: &Assembly<P>,
+    types: &[Type],
+) -> Result<Vec<TemplatePiece>, Vec<Error>> {
+    let mut errors = Vec::new();
+
+    for (index, operand) in assembly.operands.iter().enumerate() {
+        if let Some(previous) = assembly.operands[..index]
+            .iter()
+            .find(|previous| previous.name.item == operand.name.item)
+        {
+            errors.push(
+                Error::new(
+                    operand.name.span,
+                    format!(
+                        "Assembly operand `{}` is defined more than once",
+                        operand.name.item
+                    ),
+                )
+                .with_note(previous.name.span, String::from("First definition is here")),
+            );
+        }
+    }
+
+    // Operands are only reported as unused by a template that could be split, since one that
+    // is malformed may well have meant to use them. An empty template uses none of them.
+    let template = match pieces(assembly) {
+        Ok(template) => Some(template),
+        Err(error) => {
+            errors.push(error);
+            None
+        }
+    };
+
+    for (index, (operand, ty)) in assembly.operands.iter().zip(types).enumerate() {
+        let unused = template
+            .as_ref()
+            .is_some_and(|template| !template.contains(&TemplatePiece::Operand(index)));
+
+        if unused {
+            errors.push(Error::new(
+                operand.span,
+                format!(
+                    "Assembly operand `{}` is never used by the template",
+                    operand.name.item
+                ),
+            ));
+        }
+
+        if !register_sized(ty) {
+            errors.push(Error::with_details(
+                operand.expression.span,
+                format!("Type `{ty}` cannot be used as an assembly operand"),
+                String::from(
+                    "Operands must fit in a register: integers, booleans, characters, references, or function pointers",
+                ),
+            ));
+        }
+
+        if operand.direction == Direction::Out && !is_place(&operand.expression) {
+            errors.push(Error::new(
+                operand.expression.span,
+                format!(
+                    "Assembly output `{}` must be assignable, such as a variable or field",
+                    operand.name.item
+                ),
+            ));
+        }
+    }
+
+    for (index, clobber) in assembly.clobbers.iter().enumerate() {
+        if clobber.item.is_empty() {
+            errors.push(Error::new(
+                clobber.span,
+                String::from("Clobbered register name cannot be empty"),
+            ));
+        } else if assembly.clobbers[..index]
+            .iter()
+            .any(|previous| previous.item == clobber.item)
+        {
+            errors.push(Error::new(
+                clobber.span,
+                format!("Register `{}` is clobbered more than once", clobber.item),
+            ));
+        }
+    }
+
+    match (template, errors.is_empty()) {
+        (Some(template), true) => Ok(template),
+        _ => Err(errors),
+    }
+}
+
+/// Lowers a checked assembly expression, given the variable holding each operand's value (for
+/// inputs) or receiving it (for outputs).
+pub fn lower<P: TreeData<P>>(
+    assembly: &Assembly<P>,
+    template: Vec<TemplatePiece>,
+    variables: Vec<VariableId>,
+) -> Statement {
+    Statement::Assembly {
+        template,
+        operands: assembly
+            .operands
+            .iter()
+            .map(|operand| operand.direction)
+            .zip(variables)
+            .collect(),
+        clobbers: assembly
+            .clobbers
+            .iter()
+            .map(|clobber| clobber.item.clone())
+            .collect(),
+    }
+}
+
+fn pieces<P: TreeData<P>>(assembly: &Assembly<P>) -> Result<Vec<TemplatePiece>, Error> {
+    // `{name}` refers to an operand, while `{{` and `}}` are escaped braces. Everything else
+    // is copied through untouched, since the assembler is the one to make sense of it.
+
+    let span = assembly.template.span;
+    let mut chars = assembly.template.item.chars().peekable();
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => name.push(ch),
+                        None => {
+                            return Err(Error::with_details(
+                                span,
+                                String::from("Unterminated operand in assembly template"),
+                                String::from("Use `{{` for a literal brace"),
+                            ))
+                        }
+                    }
+                }
+
+                let index = assembly
+                    .operands
+                    .iter()
+                    .position(|operand| operand.name.item == name)
+                    .ok_or_else(|| {
+                        Error::new(
+                            span,
+                            format!("Assembly template refers to undefined operand `{name}`"),
+                        )
+                    })?;
+
+                if !text.is_empty() {
+                    pieces.push(TemplatePiece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(TemplatePiece::Operand(index));
+            }
+            '}' => {
+                return Err(Error::with_details(
+                    span,
+                    String::from("Unmatched `}` in assembly template"),
+                    String::from("Use `}}` for a literal brace"),
+                ))
+            }
+            ch => text.push(ch),
+        }
+    }
+
+    if !text.is_empty() {
+        pieces.push(TemplatePiece::Text(text));
+    }
+
+    Ok(pieces)
+}
+
+/// References to slices and `str` also carry a length, which makes them two registers wide.
+fn register_sized(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(_, inner) => !matches!(**inner, Type::Slice(_) | Type::Str),
+        _ => matches!(
+            ty,
+            Type::U8
+                | Type::I8
+                | Type::U16
+                | Type::I16
+                | Type::U32
+                | Type::I32
+                | Type::Bool
+                | Type::Char
+                | Type::Function(_, _)
+        ),
+    }
+}
+
+/// Whether an expression denotes a place that can be written to, ignoring mutability.
+pub fn is_place<P: TreeData<P>>(expression: &Expression<P>) -> bool {
+    use ExpressionWithoutBlock::*;
+
+    match &expression.item {
+        ExpressionKind::ExpressionWithoutBlock(Path(_, _))
+        | ExpressionKind::ExpressionWithoutBlock(Field(_, _, _))
+        | ExpressionKind::ExpressionWithoutBlock(Index(_, _, _))
+        | ExpressionKind::ExpressionWithoutBlock(Prefix(PrefixOperator::DeReference, _, _)) => true,
+        ExpressionKind::ExpressionWithoutBlock(Grouped(inner, _)) => is_place(inner),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::*;
+    use crate::model::ast::Syntax;
+    use crate::span::Spanned;
+    use crate::testing::variable;
+
+    fn operand(
+        direction: Direction,
+        name: &str,
+        expression: Expression<Syntax>,
+    ) -> Operand<Syntax> {
+        Operand {
+            direction,
+            name: Spanned::new(name.to_owned(), Span::new(0, 1)),
+            expression,
+            span: Span::new(0, 1),
+        }
+    }
+
+    fn assembly(template: &str, operands: Vec<Operand<Syntax>>) -> Assembly<Syntax> {
+        Assembly {
+            template: Spanned::new(template.to_owned(), Span::new(0, 1)),
+            operands,
+            clobbers: vec![Spanned::new("flags".to_owned(), Span::new(0, 1))],
+        }
+    }
+
+    #[test]
+    fn substitute_operands() {
+        let asm = assembly(
+            "in {value}, ({port}) ; {{}}",
+            vec![
+                operand(Direction::In, "port", variable("p", 0)),
+                operand(Direction::Out, "value", variable("v", 0)),
+            ],
+        );
+
+        assert_eq!(
+            check(&asm, &[Type::U16, Type::U8]).unwrap(),
+            vec![
+                TemplatePiece::Text("in ".to_owned()),
+                TemplatePiece::Operand(1),
+                TemplatePiece::Text(", (".to_owned()),
+                TemplatePiece::Operand(0),
+                TemplatePiece::Text(") ; {}".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_operand() {
+        let asm = assembly("out {port}, a", Vec::new());
+
+        assert_eq!(check(&asm, &[]).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn operand_checks() {
+        let literal = Spanned::new(
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+                Literal::Integer(1),
+                (),
+            )),
+            Span::new(0, 1),
+        );
+        let asm = assembly(
+            "ld {x}, {y}",
+            vec![
+                operand(Direction::Out, "x", literal),
+                operand(Direction::In, "y", variable("y", 0)),
+                operand(Direction::In, "z", variable("z", 0)),
+            ],
+        );
+
+        // Output is not a place, `y` is too large, and `z` is unused.
+        let errors = check(
+            &asm,
+            &[Type::U8, Type::Array(Box::new(Type::U8), 4), Type::U8],
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn wide_references() {
+        let asm = assembly(
+            "ld {x}, {y}",
+            vec![
+                operand(Direction::In, "x", variable("x", 0)),
+                operand(Direction::In, "y", variable("y", 0)),
+            ],
+        );
+        let reference = |inner| Type::Reference(Mutability::Immutable, Box::new(inner));
+
+        // A reference to an array is a single pointer, but one to a slice carries its length.
+        assert!(check(
+            &asm,
+            &[reference(Type::Array(Box::new(Type::U8), 4)), Type::U8]
+        )
+        .is_ok());
+
+        for wide in [
+            reference(Type::Slice(Box::new(Type::U8))),
+            reference(Type::Str),
+        ] {
+            let errors = check(&asm, &[wide.clone(), Type::U8]).unwrap_err();
+            assert_eq!(
+                errors
+                    .iter()
+                    .map(|error| error.message.as_str())
+                    .collect::<Vec<_>>(),
+                vec![format!(
+                    "Type `{wide}` cannot be used as an assembly operand"
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn empty_template() {
+        let asm = assembly("", vec![operand(Direction::In, "x", variable("x", 0))]);
+
+        let errors = check(&asm, &[Type::U8]).unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Assembly operand `x` is never used by the template"]
+        );
+
+        // Nothing is reported as unused by a template that cannot be split.
+        let asm = assembly("ld {x", vec![operand(Direction::In, "x", variable("x", 0))]);
+
+        let errors = check(&asm, &[Type::U8]).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Unterminated operand in assembly template"
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(check(&assembly("", Vec::new()), &[]).unwrap(), Vec::new());
+    }
+}
@@ -0,0 +1,570 @@
+use crate::error::Error;
+use crate::lint::{Level, Lint};
+use crate::model::base::*;
+use crate::model::ttg::TreeData;
+use crate::model::types::Type;
+use crate::span::{Span, Spanned};
+
+/// The kinds of declaration that attributes may annotate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Mod,
+    Use,
+    Function,
+    Method, // Function declared within an `impl` or `trait` block
+    TypeAlias,
+    Struct,
+    Enum,
+    Const,
+    Static,
+    Impl,
+    Trait,
+    Field,
+    Variant,
+}
+
+impl Target {
+    pub fn of<P: TreeData<P>>(kind: &ItemKind<P>) -> Self {
+        match kind {
+            ItemKind::Mod(_) => Target::Mod,
+            ItemKind::Use(_) => Target::Use,
+            ItemKind::Function(_) => Target::Function,
+            ItemKind::TypeAlias(_) => Target::TypeAlias,
+            ItemKind::Struct(_) => Target::Struct,
+            ItemKind::Enum(_) => Target::Enum,
+            ItemKind::Const(_) => Target::Const,
+            ItemKind::Static(_) => Target::Static,
+            ItemKind::Impl(_) => Target::Impl,
+            ItemKind::Trait(_) => Target::Trait,
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Target::*;
+
+        match self {
+            Mod => write!(f, "modules"),
+            Use => write!(f, "use items"),
+            Function => write!(f, "functions"),
+            Method => write!(f, "methods"),
+            TypeAlias => write!(f, "type aliases"),
+            Struct => write!(f, "structs"),
+            Enum => write!(f, "enums"),
+            Const => write!(f, "constants"),
+            Static => write!(f, "statics"),
+            Impl => write!(f, "impl blocks"),
+            Trait => write!(f, "traits"),
+            Field => write!(f, "fields"),
+            Variant => write!(f, "variants"),
+        }
+    }
+}
+
+/// A validated attribute, with its arguments extracted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeKind {
+    Inline,
+    NoReturn,
+    Interrupt,
+    Section(String),
+    Packed,
+    Repr(Type), // The integer type of an enum's tag
+    Test,
+    Deprecated(Option<String>),
+    Lint(Level, Lint), // The level of a lint within the declaration, set by `@allow`, `@warn` or `@deny`
+}
+
+enum Arguments {
+    None,
+    String,
+    OptionalString,
+    Strings, // At least one
+}
+
+struct Definition {
+    name: &'static str,
+    targets: &'static [Target],
+    arguments: Arguments,
+}
+
+const DEFINITIONS: &[Definition] = {
+    use Target::*;
+
+    &[
+        Definition {
+            name: "inline",
+            targets: &[Function, Method],
+            arguments: Arguments::None,
+        },
+        Definition {
+            name: "noreturn",
+            targets: &[Function, Method],
+            arguments: Arguments::None,
+        },
+        Definition {
+            name: "interrupt",
+            targets: &[Function],
+            arguments: Arguments::None,
+        },
+        Definition {
+            name: "section",
+            targets: &[Function, Method, Static],
+            arguments: Arguments::String,
+        },
+        Definition {
+            name: "packed",
+            targets: &[Struct],
+            arguments: Arguments::None,
+        },
+        Definition {
+            name: "repr",
+            targets: &[Enum],
+            arguments: Arguments::String,
+        },
+        Definition {
+            name: "test",
+            targets: &[Function],
+            arguments: Arguments::None,
+        },
+        Definition {
+            name: "deprecated",
+            targets: &[
+                Function, Method, TypeAlias, Struct, Enum, Const, Static, Trait, Field, Variant,
+            ],
+            arguments: Arguments::OptionalString,
+        },
+        Definition {
+            name: "allow",
+            targets: LINTED,
+            arguments: Arguments::Strings,
+        },
+        Definition {
+            name: "warn",
+            targets: LINTED,
+            arguments: Arguments::Strings,
+        },
+        Definition {
+            name: "deny",
+            targets: LINTED,
+            arguments: Arguments::Strings,
+        },
+    ]
+};
+
+/// The declarations that lint levels may be set on, which is any of them.
+const LINTED: &[Target] = {
+    use Target::*;
+
+    &[
+        Mod, Use, Function, Method, TypeAlias, Struct, Enum, Const, Static, Impl, Trait, Field,
+        Variant,
+    ]
+};
+
+/// The validated attributes of a single declaration. This is how later passes ask about the
+/// attributes of a declaration, rather than inspecting the attribute syntax directly.
+#[derive(Debug, Clone, Default)]
+pub struct Attributes(Vec<Spanned<AttributeKind>>);
+
+impl Attributes {
+    pub fn contains(&self, kind: &AttributeKind) -> bool {
+        self.0.iter().any(|attribute| attribute.item == *kind)
+    }
+
+    pub fn section(&self) -> Option<&str> {
+        self.0.iter().find_map(|attribute| match &attribute.item {
+            AttributeKind::Section(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// `None` if the declaration is not deprecated, otherwise the deprecation note, if any.
+    pub fn deprecated(&self) -> Option<Option<&str>> {
+        self.0.iter().find_map(|attribute| match &attribute.item {
+            AttributeKind::Deprecated(note) => Some(note.as_deref()),
+            _ => None,
+        })
+    }
+
+    /// The lint levels set on the declaration, in the order they are written.
+    pub fn lints(&self) -> impl Iterator<Item = (Lint, Level)> + '_ {
+        self.0.iter().filter_map(|attribute| match attribute.item {
+            AttributeKind::Lint(level, lint) => Some((lint, level)),
+            _ => None,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Spanned<AttributeKind>> {
+        self.0.iter()
+    }
+}
+
+/// Validates the attributes annotating a single declaration of the given kind. Invalid
+/// attributes are reported and left out of the returned set.
+pub fn validate<P: TreeData<P>>(
+    attributes: &[Attribute<P>],
+    target: Target,
+) -> (Attributes, Vec<Error>) {
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen: Vec<&Spanned<String>> = Vec::new();
+
+    for attribute in attributes {
+        let name = &attribute.name;
+
+        let definition = match DEFINITIONS.iter().find(|def| def.name == name.item) {
+            Some(definition) => definition,
+            None => {
+                errors.push(Error::new(
+                    name.span,
+                    format!("Unknown attribute `@{}`", name.item),
+                ));
+                continue;
+            }
+        };
+
+        if let Some(previous) = seen.iter().find(|prev| prev.item == name.item) {
+            errors.push(
+                Error::new(
+                    attribute.span,
+                    format!("Attribute `@{}` is specified more than once", name.item),
+                )
+                .with_note(previous.span, String::from("First specified here")),
+            );
+            continue;
+        }
+        seen.push(name);
+
+        if !definition.targets.contains(&target) {
+            let allowed = definition
+                .targets
+                .iter()
+                .map(|target| target.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            errors.push(Error::with_details(
+                attribute.span,
+                format!("Attribute `@{}` cannot be applied to {target}", name.item),
+                format!("`@{}` can only be applied to {allowed}", name.item),
+            ));
+            continue;
+        }
+
+        match arguments(attribute, definition) {
+            Ok(arguments) if matches!(definition.arguments, Arguments::Strings) => {
+                let level = Level::named(definition.name)
+                    .expect("Only lint level attributes take several arguments");
+
+                for (argument, name) in attribute.arguments.iter().zip(arguments) {
+                    match Lint::named(&name) {
+                        Some(lint) => valid.push(Spanned::new(
+                            AttributeKind::Lint(level, lint),
+                            attribute.span,
+                        )),
+                        None => {
+                            errors.push(Error::new(argument.span, format!("Unknown lint `{name}`")))
+                        }
+                    }
+                }
+            }
+            Ok(mut arguments) => {
+                let argument = arguments.pop();
+
+                let kind = match (definition.name, argument) {
+                    ("inline", _) => AttributeKind::Inline,
+                    ("noreturn", _) => AttributeKind::NoReturn,
+                    ("interrupt", _) => AttributeKind::Interrupt,
+                    ("section", Some(section)) => AttributeKind::Section(section),
+                    ("packed", _) => AttributeKind::Packed,
+                    ("repr", Some(repr)) => match representation(&repr) {
+                        Some(ty) => AttributeKind::Repr(ty),
+                        None => {
+                            errors.push(Error::with_details(
+                                attribute.arguments[0].span,
+                                format!("Unknown representation `{repr}`"),
+                                String::from(
+                                    "Expected one of `u8`, `i8`, `u16`, `i16`, `u32`, or `i32`",
+                                ),
+                            ));
+                            continue;
+                        }
+                    },
+                    ("test", _) => AttributeKind::Test,
+                    ("deprecated", note) => AttributeKind::Deprecated(note),
+                    _ => unreachable!("Attribute arguments are checked against its definition"),
+                };
+
+                valid.push(Spanned::new(kind, attribute.span));
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+
+    (Attributes(valid), errors)
+}
+
+fn representation(repr: &str) -> Option<Type> {
+    match repr {
+        "u8" => Some(Type::U8),
+        "i8" => Some(Type::I8),
+        "u16" => Some(Type::U16),
+        "i16" => Some(Type::I16),
+        "u32" => Some(Type::U32),
+        "i32" => Some(Type::I32),
+        _ => None,
+    }
+}
+
+fn arguments<P: TreeData<P>>(
+    attribute: &Attribute<P>,
+    definition: &Definition,
+) -> Result<Vec<String>, Error> {
+    let name = &attribute.name.item;
+
+    let string = |argument: &Expression<P>| match &argument.item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+            Literal::String(value),
+            _,
+        )) => Ok(value.clone()),
+        _ => Err(Error::new(
+            argument.span,
+            format!("Expected string literal as argument of attribute `@{name}`"),
+        )),
+    };
+
+    match (&definition.arguments, attribute.arguments.as_slice()) {
+        (Arguments::None, []) | (Arguments::OptionalString, []) => Ok(Vec::new()),
+        (Arguments::String, [argument]) | (Arguments::OptionalString, [argument]) => {
+            string(argument).map(|argument| vec![argument])
+        }
+        (Arguments::Strings, arguments) if !arguments.is_empty() => {
+            arguments.iter().map(string).collect()
+        }
+        (expected, _) => {
+            let expected = match expected {
+                Arguments::None => "no arguments",
+                Arguments::String => "exactly one string argument",
+                Arguments::OptionalString => "at most one string argument",
+                Arguments::Strings => "at least one string argument",
+            };
+
+            Err(Error::new(
+                attribute.span,
+                format!("Attribute `@{name}` takes {expected}"),
+            ))
+        }
+    }
+}
+
+/// Validates the attributes of every declaration within the geode.
+pub fn check<P: TreeData<P>>(geode: &Geode<P>) -> Vec<Error> {
+    let mut errors = Vec::new();
+
+    for module in &geode.modules {
+        for item in &module.items {
+            check_item(item, Target::of(&item.kind), &mut errors);
+        }
+    }
+
+    errors
+}
+
+fn check_item<P: TreeData<P>>(item: &Item<P>, target: Target, errors: &mut Vec<Error>) {
+    errors.extend(validate(&item.attributes, target).1);
+
+    match &item.kind {
+        // Out-of-line modules are checked along with the rest of `Geode::modules`.
+        ItemKind::Mod(ModItem {
+            body: ModBody::Inline(items),
+            ..
+        }) => {
+            for item in items {
+                check_item(item, Target::of(&item.kind), errors);
+            }
+        }
+        ItemKind::Struct(struct_item) => check_fields(&struct_item.fields, errors),
+        ItemKind::Enum(enum_item) => {
+            for variant in &enum_item.variants {
+                errors.extend(validate(&variant.attributes, Target::Variant).1);
+
+                if let VariantKind::Struct(fields) = &variant.kind {
+                    check_fields(fields, errors);
+                }
+            }
+        }
+        ItemKind::Impl(ImplItem { functions, .. })
+        | ItemKind::Trait(TraitItem { functions, .. }) => {
+            for function in functions {
+                check_item(function, Target::Method, errors);
+            }
+        }
+        _ => { /* No nested declarations */ }
+    }
+}
+
+fn check_fields<P: TreeData<P>>(fields: &[Field<P>], errors: &mut Vec<Error>) {
+    for field in fields {
+        errors.extend(validate(&field.attributes, Target::Field).1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::attribute::*;
+    use crate::testing::{messages, notes, parse, span, span_in};
+
+    /// The attributes of the first item in `source`, validated for `target`.
+    fn validated(source: &str, target: Target) -> (Attributes, Vec<Error>) {
+        let geode = parse(source);
+        validate(&geode.modules[0].items[0].attributes, target)
+    }
+
+    #[test]
+    fn unknown_attributes() {
+        let source = r#"@inlined @allow("unused", "dead_code") fn f() {}"#;
+
+        let (valid, errors) = validated(source, Target::Function);
+
+        assert_eq!(
+            messages(&errors),
+            vec![
+                (
+                    "Unknown attribute `@inlined`",
+                    None,
+                    span(source, "inlined")
+                ),
+                ("Unknown lint `unused`", None, span(source, r#""unused""#)),
+            ]
+        );
+        assert_eq!(
+            valid.lints().collect::<Vec<_>>(),
+            vec![(Lint::DeadCode, Level::Allow)]
+        );
+    }
+
+    #[test]
+    fn duplicate_attributes() {
+        let source = "@inline @deprecated @inline fn f() {}";
+
+        let (valid, errors) = validated(source, Target::Function);
+
+        assert_eq!(
+            notes(&errors),
+            vec![(
+                "Attribute `@inline` is specified more than once",
+                span_in(source, "@deprecated", "@inline"),
+                vec![span(source, "inline")]
+            )]
+        );
+        assert_eq!(valid.iter().count(), 2);
+    }
+
+    #[test]
+    fn wrong_targets() {
+        let source = "@packed fn f() {}";
+
+        let (valid, errors) = validated(source, Target::Function);
+
+        assert_eq!(
+            messages(&errors),
+            vec![(
+                "Attribute `@packed` cannot be applied to functions",
+                Some("`@packed` can only be applied to structs"),
+                span(source, "@packed")
+            )]
+        );
+        assert!(!valid.contains(&AttributeKind::Packed));
+
+        let source = "
+            @interrupt fn f() {}
+            enum Shape { @inline Circle(u8), Square }
+            struct P { @deprecated x: u8 }
+            impl P { @section(\".boot\") fn g() {} }
+        ";
+
+        assert_eq!(
+            messages(&check(&parse(source))),
+            vec![(
+                "Attribute `@inline` cannot be applied to variants",
+                Some("`@inline` can only be applied to functions, methods"),
+                span(source, "@inline")
+            )]
+        );
+    }
+
+    #[test]
+    fn attribute_arguments() {
+        let source = r#"
+            @section fn f() {}
+            @section(1) fn g() {}
+            @inline("always") fn h() {}
+            @allow fn i() {}
+            @repr("u64") enum E {}
+        "#;
+
+        assert_eq!(
+            messages(&check(&parse(source))),
+            vec![
+                (
+                    "Attribute `@section` takes exactly one string argument",
+                    None,
+                    span(source, "@section")
+                ),
+                (
+                    "Expected string literal as argument of attribute `@section`",
+                    None,
+                    span_in(source, "@section(", "1")
+                ),
+                (
+                    "Attribute `@inline` takes no arguments",
+                    None,
+                    span(source, r#"@inline("always")"#)
+                ),
+                (
+                    "Attribute `@allow` takes at least one string argument",
+                    None,
+                    span(source, "@allow")
+                ),
+                (
+                    "Unknown representation `u64`",
+                    Some("Expected one of `u8`, `i8`, `u16`, `i16`, `u32`, or `i32`"),
+                    span(source, r#""u64""#)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn queries() {
+        let source = r#"
+            @inline @section(".boot") @deprecated("Use g") @deny("dead_code")
+            @warn("non_snake_case")
+            fn f() {}
+        "#;
+
+        let (valid, errors) = validated(source, Target::Function);
+
+        assert!(errors.is_empty());
+        assert!(valid.contains(&AttributeKind::Inline));
+        assert!(!valid.contains(&AttributeKind::NoReturn));
+        assert_eq!(valid.section(), Some(".boot"));
+        assert_eq!(valid.deprecated(), Some(Some("Use g")));
+        assert_eq!(
+            valid.lints().collect::<Vec<_>>(),
+            vec![
+                (Lint::DeadCode, Level::Deny),
+                (Lint::NonSnakeCase, Level::Warn)
+            ]
+        );
+
+        let (valid, errors) = validated(r#"@deprecated @repr("u16") enum E {}"#, Target::Enum);
+
+        assert!(errors.is_empty());
+        assert!(valid.contains(&AttributeKind::Repr(Type::U16)));
+        assert_eq!(valid.deprecated(), Some(None));
+        assert_eq!(valid.section(), None);
+    }
+}
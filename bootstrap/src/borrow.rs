@@ -0,0 +1,1174 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::model::base::*;
+use crate::model::def::DefKind;
+use crate::model::resolved::{LocalId, Name, Resolution, ResolvedPath};
+use crate::model::typed::{type_of, Typed};
+use crate::model::types::Type;
+use crate::model::visit::{
+    walk_function, walk_pattern, walk_statement, walk_with_block, walk_without_block, PathContext,
+    Visitor,
+};
+use crate::span::{Span, Spanned};
+use crate::typeck::Tables;
+
+/// Checks that values only change through mutable places, and that borrows do not conflict.
+///
+/// A place is mutable if it is part of a variable declared `mut`, or if it is reached through
+/// a `&mut` reference. Assigning to an immutable place, or borrowing it as `&mut`, is an
+/// error. Assigning a whole variable is left to the initialization check, since an immutable
+/// variable declared without a value may still be assigned once.
+///
+/// A borrow held by a `let` binding lasts from that statement to the binding's last use in the
+/// same block, or to the end of the statement making that use if a loop may make it again.
+/// Assigning the binding a new value ends the borrow rather than using it. While the borrow
+/// lasts, the place it borrows cannot be assigned, borrowed again if either borrow is mutable,
+/// or returned from the function if it belongs to a local variable.
+pub fn check(geode: &Geode<Typed>, tables: &Tables) -> Vec<Error> {
+    let mut checker = Checker {
+        tables,
+        bindings: HashMap::new(),
+        receiver: None,
+        scopes: Vec::new(),
+        holders: HashMap::new(),
+        errors: Vec::new(),
+    };
+
+    checker.visit_geode(geode);
+    checker.errors
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    name: String,
+    span: Span,
+    mutable: bool,
+}
+
+/// A memory location named by an expression, such as `x`, `x.a[i]` or `*x.b`.
+#[derive(Debug, Clone)]
+struct Place {
+    root: LocalId,
+    projections: Vec<Projection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Projection {
+    Field(String),
+    Index, // Any element, since indices are not known until run time
+    Deref,
+}
+
+impl Place {
+    fn overlaps(&self, other: &Place) -> bool {
+        self.root == other.root
+            && self
+                .projections
+                .iter()
+                .zip(&other.projections)
+                .all(|(left, right)| match (left, right) {
+                    (Projection::Field(left), Projection::Field(right)) => left == right,
+                    (left, right) => left == right,
+                })
+    }
+
+    /// Whether the place is stored within the function's own variables, rather than behind a
+    /// reference to memory the function does not own.
+    fn is_local(&self) -> bool {
+        !self.projections.contains(&Projection::Deref)
+    }
+}
+
+/// A borrow held by a variable.
+struct Loan {
+    place: Place,
+    description: String, // How the borrowed place is written in the source
+    mutability: Mutability,
+    span: Span, // Of the borrow expression
+    holder: String,
+    last_use: (usize, Span), // Position of the last statement using the holder, and the use
+    repeated: bool,          // Whether a loop may make the last use again later in its statement
+}
+
+impl Loan {
+    /// Whether the loan is still in use at `span`, within the statement at `position`.
+    fn is_live(&self, position: usize, span: Span) -> bool {
+        let (last, use_span) = self.last_use;
+        position < last || (position == last && (self.repeated || span.start <= use_span.start))
+    }
+}
+
+/// The loans taken within a block, along with the position of the statement being checked.
+struct Scope {
+    loans: Vec<Loan>,
+    position: usize,
+}
+
+/// Why a place cannot be changed.
+enum Immutable {
+    Binding(Binding),
+    Static(String),
+    Shared(Span, Type), // The shared reference the place is reached through, and its type
+}
+
+struct Checker<'a> {
+    tables: &'a Tables,
+    bindings: HashMap<LocalId, Binding>,
+    receiver: Option<Spanned<Receiver>>, // Of the function being checked
+    scopes: Vec<Scope>,
+    holders: HashMap<LocalId, (Binding, Span)>, // Variables holding a borrow of a local variable
+    errors: Vec<Error>,
+}
+
+impl Checker<'_> {
+    fn bind(&mut self, name: &Name, mutability: &Mutability) {
+        if let Some(Resolution::Local(local)) = name.resolution {
+            self.bindings.insert(
+                local,
+                Binding {
+                    name: name.ident.item.clone(),
+                    span: name.ident.span,
+                    mutable: *mutability == Mutability::Mutable,
+                },
+            );
+        }
+    }
+
+    /// The variable a path names, if it is a local one. `self` is declared by the receiver,
+    /// which has no binding of its own, and can never be declared mutable.
+    fn binding(&self, path: &ResolvedPath) -> Option<(LocalId, Binding)> {
+        let Resolution::Local(local) = path.resolution else {
+            return None;
+        };
+
+        match (self.bindings.get(&local), &self.receiver) {
+            (Some(binding), _) => Some((local, binding.clone())),
+            (None, Some(receiver)) if path.segments[0].item == "self" => Some((
+                local,
+                Binding {
+                    name: String::from("self"),
+                    span: receiver.span,
+                    mutable: false,
+                },
+            )),
+            (None, _) => None,
+        }
+    }
+
+    fn place(&self, expression: &Expression<Typed>) -> Option<Place> {
+        use ExpressionWithoutBlock::*;
+
+        let ExpressionKind::ExpressionWithoutBlock(without_block) = &expression.item else {
+            return None;
+        };
+
+        match without_block {
+            Path(path, _) => self.binding(path).map(|(root, _)| Place {
+                root,
+                projections: Vec::new(),
+            }),
+            Field(base, _, _) | Index(base, _, _) => {
+                let mut place = self.place(base)?;
+
+                // Fields and elements are reached through any number of references.
+                let mut ty = type_of(base);
+                while let Type::Reference(_, inner) = ty {
+                    place.projections.push(Projection::Deref);
+                    ty = inner;
+                }
+
+                place.projections.push(match without_block {
+                    Field(_, name, _) => Projection::Field(name.ident.item.clone()),
+                    _ => Projection::Index,
+                });
+
+                Some(place)
+            }
+            Prefix(PrefixOperator::DeReference, inner, _) => {
+                let mut place = self.place(inner)?;
+                place.projections.push(Projection::Deref);
+                Some(place)
+            }
+            Grouped(inner, _) => self.place(inner),
+            _ => None,
+        }
+    }
+
+    /// Finds the reason the place named by an expression cannot change, if there is one.
+    /// Expressions that name no place are temporaries, which may always change.
+    fn immutable(&self, expression: &Expression<Typed>) -> Option<Immutable> {
+        use ExpressionWithoutBlock::*;
+
+        let ExpressionKind::ExpressionWithoutBlock(without_block) = &expression.item else {
+            return None;
+        };
+
+        match without_block {
+            Path(path, _) => match path.resolution {
+                Resolution::Def(DefKind::Static, _) => Some(Immutable::Static(name_of(path))),
+                _ => match self.binding(path) {
+                    Some((_, binding)) if !binding.mutable => Some(Immutable::Binding(binding)),
+                    _ => None,
+                },
+            },
+            Field(base, _, _) | Index(base, _, _) => match type_of(base) {
+                Type::Reference(_, _) => shared(type_of(base))
+                    .then(|| Immutable::Shared(base.span, type_of(base).clone())),
+                _ => self.immutable(base),
+            },
+            Prefix(PrefixOperator::DeReference, inner, _) => match type_of(inner) {
+                Type::Reference(Mutability::Immutable, _) => {
+                    Some(Immutable::Shared(inner.span, type_of(inner).clone()))
+                }
+                _ => None,
+            },
+            Grouped(inner, _) => self.immutable(inner),
+            _ => None,
+        }
+    }
+
+    /// Reports `action`, such as "Cannot assign to `x.a`", if the place named by `target`
+    /// cannot change.
+    fn require_mutable(&mut self, target: &Expression<Typed>, action: String) {
+        let error = match self.immutable(target) {
+            None => return,
+            Some(Immutable::Binding(binding)) => {
+                let details = match binding.name.as_str() {
+                    "self" => String::from("Take `&mut self` instead to allow this"),
+                    name => format!("Declare it as `mut {name}` to allow this"),
+                };
+
+                Error::with_details(
+                    target.span,
+                    format!("{action}, as `{}` is immutable", binding.name),
+                    details,
+                )
+                .with_note(binding.span, format!("`{}` is declared here", binding.name))
+            }
+            Some(Immutable::Static(name)) => Error::with_details(
+                target.span,
+                format!("{action}, as `{name}` is a static"),
+                String::from("Statics cannot be changed once initialized"),
+            ),
+            Some(Immutable::Shared(span, ty)) => Error::with_details(
+                target.span,
+                format!("{action}, as it is behind a shared reference"),
+                format!("Only a `&mut` reference allows changes, but this has type `{ty}`"),
+            )
+            .with_note(span, String::from("The shared reference")),
+        };
+
+        self.errors.push(error);
+    }
+
+    /// Reports a loan that is still in use and conflicts with an access to `place`. `access`
+    /// is the mutability of a new borrow, or `None` for an assignment.
+    fn require_unborrowed(
+        &mut self,
+        place: &Place,
+        description: &str,
+        access: Option<&Mutability>,
+        span: Span,
+    ) {
+        let loan = self
+            .scopes
+            .iter()
+            .flat_map(|scope| {
+                let position = scope.position;
+                scope
+                    .loans
+                    .iter()
+                    .filter(move |loan| loan.is_live(position, span))
+            })
+            .find(|loan| {
+                loan.place.overlaps(place)
+                    && (access != Some(&Mutability::Immutable)
+                        || loan.mutability == Mutability::Mutable)
+            });
+
+        let Some(loan) = loan else {
+            return;
+        };
+
+        let message = match (access, &loan.mutability) {
+            (None, _) => format!("Cannot assign to `{description}` while it is borrowed"),
+            (Some(Mutability::Mutable), Mutability::Mutable) => {
+                format!("Cannot borrow `{description}` as mutable more than once at a time")
+            }
+            (Some(Mutability::Mutable), Mutability::Immutable) => format!(
+                "Cannot borrow `{description}` as mutable, as it is also borrowed as shared"
+            ),
+            (Some(Mutability::Immutable), _) => format!(
+                "Cannot borrow `{description}` as shared, as it is also borrowed as mutable"
+            ),
+        };
+
+        let error = Error::with_details(
+            span,
+            message,
+            format!(
+                "`{}` borrows `{}` until its last use",
+                loan.holder, loan.description
+            ),
+        )
+        .with_note(loan.span, String::from("First borrowed here"))
+        .with_note(
+            loan.last_use.1,
+            String::from("The first borrow is used later here"),
+        );
+
+        self.errors.push(error);
+    }
+
+    fn assign(&mut self, target: &Expression<Typed>) {
+        if let Some(place) = self.place(target) {
+            self.require_unborrowed(&place, &describe(target), None, target.span);
+
+            // Whole variables are checked for initialization instead.
+            if place.projections.is_empty() {
+                return;
+            }
+        }
+
+        self.require_mutable(target, format!("Cannot assign to `{}`", describe(target)));
+    }
+
+    fn borrow(&mut self, mutability: &Mutability, target: &Expression<Typed>, span: Span) {
+        if *mutability == Mutability::Mutable {
+            self.require_mutable(
+                target,
+                format!("Cannot borrow `{}` as mutable", describe(target)),
+            );
+        }
+
+        if let Some(place) = self.place(target) {
+            self.require_unborrowed(&place, &describe(target), Some(mutability), span);
+        }
+    }
+
+    /// Reports arguments of the same call that borrow overlapping places, at least one of
+    /// them mutably, since both borrows last for the whole call.
+    fn arguments(&mut self, arguments: &[Expression<Typed>]) {
+        let borrows: Vec<_> = arguments
+            .iter()
+            .filter_map(|argument| match borrowed(argument) {
+                Some((mutability, target)) => self
+                    .place(target)
+                    .map(|place| (mutability, place, describe(target), argument.span)),
+                None => None,
+            })
+            .collect();
+
+        for (index, (mutability, place, description, span)) in borrows.iter().enumerate() {
+            let first = borrows[..index].iter().find(|(other, earlier, _, _)| {
+                earlier.overlaps(place)
+                    && (*mutability == Mutability::Mutable || *other == Mutability::Mutable)
+            });
+
+            if let Some((_, _, _, first)) = first {
+                let message = match mutability {
+                    Mutability::Mutable => format!(
+                        "Cannot borrow `{description}` as mutable, as an earlier argument also borrows it"
+                    ),
+                    Mutability::Immutable => format!(
+                        "Cannot borrow `{description}` as shared, as an earlier argument borrows it as mutable"
+                    ),
+                };
+
+                self.errors.push(
+                    Error::new(*span, message)
+                        .with_note(*first, String::from("First borrowed here")),
+                );
+            }
+        }
+    }
+
+    /// Reports references to local variables among the values an expression may produce as
+    /// the function's result, since those variables no longer exist once it returns.
+    fn returned(&mut self, expression: &Expression<Typed>) {
+        match &expression.item {
+            ExpressionKind::ExpressionWithBlock(with_block) => match with_block {
+                ExpressionWithBlock::Block(body, _) => self.returned_block(body),
+                ExpressionWithBlock::If(_, then, otherwise, _) => {
+                    self.returned_block(then);
+                    if let Some(otherwise) = otherwise {
+                        self.returned(otherwise);
+                    }
+                }
+                ExpressionWithBlock::IfIs(_, _, then, otherwise, _) => {
+                    self.returned(then);
+                    if let Some(otherwise) = otherwise {
+                        self.returned(otherwise);
+                    }
+                }
+                ExpressionWithBlock::Case(_, arms, _) => {
+                    for arm in arms {
+                        self.returned(&arm.expression);
+                    }
+                }
+                ExpressionWithBlock::While(..)
+                | ExpressionWithBlock::Loop(..)
+                | ExpressionWithBlock::For(..) => {}
+            },
+            ExpressionKind::ExpressionWithoutBlock(without_block) => {
+                self.returned_without_block(without_block)
+            }
+        }
+    }
+
+    fn returned_without_block(&mut self, expression: &ExpressionWithoutBlock<Typed>) {
+        match expression {
+            ExpressionWithoutBlock::Grouped(inner, _) => self.returned(inner),
+            ExpressionWithoutBlock::Prefix(
+                PrefixOperator::Borrow | PrefixOperator::MutableBorrow,
+                target,
+                _,
+            ) => {
+                let binding = match self.place(target) {
+                    Some(place) if place.is_local() => self.root(&place),
+                    _ => None,
+                };
+
+                if let Some(binding) = binding {
+                    self.dangling(target.span, binding, None);
+                }
+            }
+            ExpressionWithoutBlock::Path(path, _) => {
+                let holder = match path.resolution {
+                    Resolution::Local(local) => self.holders.get(&local).cloned(),
+                    _ => None,
+                };
+
+                if let Some((binding, borrow)) = holder {
+                    self.dangling(path.segments[0].span, binding, Some(borrow));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn returned_block(&mut self, block: &BlockExpression<Typed>) {
+        let tail = match block.iter().flatten().last() {
+            Some(Statements::Leading(_, _, tail)) => tail.as_ref(),
+            Some(Statements::Block(tail)) => Some(tail),
+            None => None,
+        };
+
+        if let Some(tail) = tail {
+            self.returned(tail);
+        }
+    }
+
+    fn root(&self, place: &Place) -> Option<Binding> {
+        match self.bindings.get(&place.root) {
+            Some(binding) => Some(binding.clone()),
+            None => self.receiver.as_ref().map(|receiver| Binding {
+                name: String::from("self"),
+                span: receiver.span,
+                mutable: false,
+            }),
+        }
+    }
+
+    fn dangling(&mut self, span: Span, binding: Binding, borrow: Option<Span>) {
+        let mut error = Error::with_details(
+            span,
+            format!(
+                "Cannot return a reference to local variable `{}`",
+                binding.name
+            ),
+            format!(
+                "`{}` no longer exists once the function returns",
+                binding.name
+            ),
+        )
+        .with_note(binding.span, format!("`{}` is declared here", binding.name));
+
+        if let Some(borrow) = borrow {
+            error = error.with_note(borrow, String::from("Borrowed here"));
+        }
+
+        self.errors.push(error);
+    }
+
+    /// Records the loan a `let` statement at `position` takes, if the variable it declares
+    /// holds a borrow that is used by a later statement of the block.
+    fn hold(&mut self, statement: &Statement<Typed>, position: usize, steps: &[Step]) {
+        let Statement::Let(Let {
+            name,
+            initializer: Some(initializer),
+            ..
+        }) = statement
+        else {
+            return;
+        };
+
+        let (Some(Resolution::Local(holder)), Some((mutability, target))) =
+            (&name.resolution, borrowed(initializer))
+        else {
+            return;
+        };
+
+        let Some(place) = self.place(target) else {
+            return;
+        };
+
+        if place.is_local() {
+            if let Some(binding) = self.root(&place) {
+                self.holders.insert(*holder, (binding, initializer.span));
+            }
+        }
+
+        let mut last_use = None;
+        for (position, step) in steps.iter().enumerate().skip(position + 1) {
+            let mut uses = Uses {
+                local: *holder,
+                last: None,
+                repeated: false,
+                regions: Vec::new(),
+            };
+            let reassigned = uses.region(false, |uses| step.visit(uses));
+
+            if let Some(span) = uses.last {
+                last_use = Some(((position, span), uses.repeated));
+            }
+            if reassigned {
+                break;
+            }
+        }
+
+        if let (Some((last_use, repeated)), Some(scope)) = (last_use, self.scopes.last_mut()) {
+            scope.loans.push(Loan {
+                place,
+                description: describe(target),
+                mutability,
+                span: initializer.span,
+                holder: name.ident.item.clone(),
+                last_use,
+                repeated,
+            });
+        }
+    }
+}
+
+impl Visitor<Typed> for Checker<'_> {
+    fn visit_function(&mut self, function: &FunctionItem<Typed>) {
+        self.receiver = function.receiver.clone();
+
+        for parameter in &function.parameters {
+            self.bind(&parameter.name, &parameter.mutability);
+        }
+
+        walk_function(self, function);
+
+        if let Some(body) = &function.body {
+            self.returned_block(body);
+        }
+
+        self.receiver = None;
+    }
+
+    fn visit_block(&mut self, block: &BlockExpression<Typed>) {
+        let steps = steps(block);
+
+        self.scopes.push(Scope {
+            loans: Vec::new(),
+            position: 0,
+        });
+
+        for (position, step) in steps.iter().enumerate() {
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.position = position;
+            }
+
+            step.visit(self);
+
+            if let Step::Statement(statement) = step {
+                self.hold(statement, position, &steps);
+            }
+        }
+
+        self.scopes.pop();
+    }
+
+    fn visit_statement(&mut self, statement: &Statement<Typed>) {
+        if let Statement::Let(Let {
+            name, mutability, ..
+        }) = statement
+        {
+            self.bind(name, mutability);
+        }
+
+        walk_statement(self, statement)
+    }
+
+    fn visit_with_block(&mut self, expression: &ExpressionWithBlock<Typed>) {
+        if let ExpressionWithBlock::For(_, name, _, _, _) = expression {
+            self.bind(name, &Mutability::Immutable);
+        }
+
+        walk_with_block(self, expression)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern<Typed>) {
+        if let PatternKind::Binding(mutability, name, _) = &pattern.item {
+            self.bind(name, mutability);
+        }
+
+        walk_pattern(self, pattern)
+    }
+
+    fn visit_without_block(&mut self, expression: &ExpressionWithoutBlock<Typed>) {
+        use ExpressionWithoutBlock::*;
+
+        walk_without_block(self, expression);
+
+        match expression {
+            Binary(left, operator, _, _) if is_assignment(operator) => self.assign(left),
+            Prefix(
+                operator @ (PrefixOperator::Borrow | PrefixOperator::MutableBorrow),
+                target,
+                _,
+            ) => {
+                let mutability = match operator {
+                    PrefixOperator::MutableBorrow => Mutability::Mutable,
+                    _ => Mutability::Immutable,
+                };
+
+                let span = target.span;
+                self.borrow(&mutability, target, span);
+            }
+            Call(_, arguments, _) => self.arguments(arguments),
+            MethodCall(_, _, _, removed) => match *removed {},
+            Asm(assembly, _) => {
+                for operand in &assembly.operands {
+                    if operand.direction == Direction::Out {
+                        self.assign(&operand.expression);
+                    }
+                }
+            }
+            Return(Some(value), _) => self.returned(value),
+            _ => {}
+        }
+    }
+}
+
+/// A statement or tail expression of a block, in the order they run.
+enum Step<'a> {
+    Statement(&'a Statement<Typed>),
+    Tail(&'a Expression<Typed>),
+}
+
+impl Step<'_> {
+    fn visit(&self, visitor: &mut impl Visitor<Typed>) {
+        match self {
+            Step::Statement(statement) => visitor.visit_statement(statement),
+            Step::Tail(tail) => visitor.visit_expression(tail),
+        }
+    }
+}
+
+fn steps(block: &BlockExpression<Typed>) -> Vec<Step<'_>> {
+    let mut steps = Vec::new();
+
+    for statements in block.iter().flatten() {
+        match statements {
+            Statements::Leading(first, rest, tail) => {
+                steps.extend(std::iter::once(first).chain(rest).map(Step::Statement));
+                steps.extend(tail.iter().map(Step::Tail));
+            }
+            Statements::Block(tail) => steps.push(Step::Tail(tail)),
+        }
+    }
+
+    steps
+}
+
+/// Finds the last use of the value a local variable holds before it is next assigned, within
+/// a statement. Uses after a plain assignment to the variable are of the new value, until the
+/// end of the region making the assignment: a branch may not be taken, and a loop may be left
+/// before it reaches the assignment.
+struct Uses {
+    local: LocalId,
+    last: Option<Span>,
+    repeated: bool, // Whether a loop may make the last use again, before the variable is assigned
+    regions: Vec<Region>,
+}
+
+/// The statement, a branch, or a loop body, within which a use or an assignment is made.
+struct Region {
+    looping: bool,
+    used: bool,       // Whether the last use is within this region
+    reassigned: bool, // Whether the variable is assigned a new value on every path so far
+}
+
+impl Uses {
+    /// Visits a region, returning whether the variable is always assigned within it.
+    fn region(&mut self, looping: bool, visit: impl FnOnce(&mut Self)) -> bool {
+        self.regions.push(Region {
+            looping,
+            used: false,
+            reassigned: false,
+        });
+        visit(self);
+        let region = self
+            .regions
+            .pop()
+            .expect("Regions are pushed and popped in pairs");
+
+        if region.looping && region.used && !region.reassigned {
+            self.repeated = true;
+        }
+
+        region.reassigned
+    }
+
+    fn is_local(&self, expression: &Expression<Typed>) -> bool {
+        matches!(
+            &expression.item,
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(path, _))
+                if path.resolution == Resolution::Local(self.local)
+        )
+    }
+}
+
+impl Visitor<Typed> for Uses {
+    fn visit_path(&mut self, path: &ResolvedPath, _context: PathContext) {
+        let reassigned = self.regions.iter().any(|region| region.reassigned);
+
+        if path.resolution == Resolution::Local(self.local) && !reassigned {
+            self.last = Some(path.segments[0].span);
+            self.repeated = false;
+
+            for region in &mut self.regions {
+                region.used = true;
+            }
+        }
+    }
+
+    fn visit_with_block(&mut self, expression: &ExpressionWithBlock<Typed>) {
+        use ExpressionWithBlock::*;
+
+        match expression {
+            If(condition, then, otherwise, _) => {
+                self.visit_expression(condition);
+                self.region(false, |uses| uses.visit_block(then));
+
+                if let Some(otherwise) = otherwise {
+                    self.region(false, |uses| uses.visit_expression(otherwise));
+                }
+            }
+            IfIs(scrutinee, pattern, then, otherwise, _) => {
+                self.visit_expression(scrutinee);
+                self.visit_pattern(pattern);
+                self.region(false, |uses| uses.visit_expression(then));
+
+                if let Some(otherwise) = otherwise {
+                    self.region(false, |uses| uses.visit_expression(otherwise));
+                }
+            }
+            Case(scrutinee, arms, _) => {
+                self.visit_expression(scrutinee);
+
+                for arm in arms {
+                    self.region(false, |uses| uses.visit_arm(arm));
+                }
+            }
+            // The condition of a `while` is evaluated again on every iteration.
+            While(_, condition, body, _) => {
+                self.region(true, |uses| {
+                    uses.visit_expression(condition);
+                    uses.visit_block(body);
+                });
+            }
+            For(_, _, iterable, body, _) => {
+                self.visit_expression(iterable);
+                self.region(true, |uses| uses.visit_block(body));
+            }
+            Loop(_, body, _) => {
+                self.region(true, |uses| uses.visit_block(body));
+            }
+            Block(..) => walk_with_block(self, expression),
+        }
+    }
+
+    fn visit_without_block(&mut self, expression: &ExpressionWithoutBlock<Typed>) {
+        match expression {
+            ExpressionWithoutBlock::Binary(target, BinaryOperator::Equal, value, _)
+                if self.is_local(target) =>
+            {
+                self.visit_expression(value);
+
+                if let Some(region) = self.regions.last_mut() {
+                    region.reassigned = true;
+                }
+            }
+            _ => walk_without_block(self, expression),
+        }
+    }
+}
+
+/// The mutability and operand of an expression that is a borrow.
+fn borrowed(expression: &Expression<Typed>) -> Option<(Mutability, &Expression<Typed>)> {
+    match &expression.item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+            PrefixOperator::Borrow,
+            target,
+            _,
+        )) => Some((Mutability::Immutable, target)),
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+            PrefixOperator::MutableBorrow,
+            target,
+            _,
+        )) => Some((Mutability::Mutable, target)),
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Grouped(inner, _)) => {
+            borrowed(inner)
+        }
+        _ => None,
+    }
+}
+
+fn is_assignment(operator: &BinaryOperator) -> bool {
+    use BinaryOperator::*;
+
+    matches!(
+        operator,
+        Equal
+            | PlusEqual
+            | MinusEqual
+            | TimesEqual
+            | DivideEqual
+            | AndEqual
+            | OrEqual
+            | LShiftEqual
+            | RShiftEqual
+    )
+}
+
+/// Whether any of the references a value of type `ty` is reached through is shared.
+fn shared(mut ty: &Type) -> bool {
+    while let Type::Reference(mutability, inner) = ty {
+        if *mutability == Mutability::Immutable {
+            return true;
+        }
+        ty = inner;
+    }
+
+    false
+}
+
+fn name_of(path: &ResolvedPath) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.item.as_str())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Writes a place expression the way it appears in the source.
+fn describe(expression: &Expression<Typed>) -> String {
+    use ExpressionWithoutBlock::*;
+
+    match &expression.item {
+        ExpressionKind::ExpressionWithoutBlock(without_block) => match without_block {
+            Path(path, _) => name_of(path),
+            Field(base, name, _) => format!("{}.{}", describe(base), name.ident.item),
+            Index(base, _, _) => format!("{}[..]", describe(base)),
+            Prefix(PrefixOperator::DeReference, inner, _) => format!("*{}", describe(inner)),
+            Grouped(inner, _) => format!("({})", describe(inner)),
+            _ => String::from("_"),
+        },
+        ExpressionKind::ExpressionWithBlock(_) => String::from("_"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::borrow::*;
+    use crate::testing::{self, messages, notes, span, span_in};
+
+    fn errors(source: &str) -> Vec<Error> {
+        let (geode, tables, _) = testing::typed(source);
+        check(&geode, &tables)
+    }
+
+    #[test]
+    fn mutability() {
+        let source = "
+            fn f(p: &u8, q: &mut u8) {
+                let a = [1, 2];
+                a[0] = 1;
+                *p = 1;
+                *q = 1;
+                &mut a;
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![
+                (
+                    "Cannot assign to `a[..]`, as `a` is immutable",
+                    span(source, "a[0]"),
+                    vec![span_in(source, "let a", "a")]
+                ),
+                (
+                    "Cannot assign to `*p`, as it is behind a shared reference",
+                    span(source, "*p"),
+                    vec![span_in(source, "*p", "p")]
+                ),
+                (
+                    "Cannot borrow `a` as mutable, as `a` is immutable",
+                    span_in(source, "&mut a", "a"),
+                    vec![span_in(source, "let a", "a")]
+                ),
+            ]
+        );
+
+        let source = "fn f() { let mut a = [1, 2]; a[0] = 1; &mut a; }";
+
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn method_receivers() {
+        let source = "
+            impl u8 { fn bump(&mut self) {} }
+            fn f(r: &mut u8) { let x: u8 = 1; x.bump(); let mut y: u8 = 1; y.bump(); r.bump(); }
+        ";
+
+        // Calling a `&mut self` method borrows the receiver mutably, unless it is already a
+        // mutable reference.
+        assert_eq!(
+            errors(source)
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Cannot borrow `x` as mutable, as `x` is immutable"]
+        );
+    }
+
+    #[test]
+    fn conflicting_borrows() {
+        let source = "
+            fn f() {
+                let mut x: u8 = 1;
+                let r = &mut x;
+                let s = &x;
+                x = 1;
+                *r = 1;
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![
+                (
+                    "Cannot borrow `x` as shared, as it is also borrowed as mutable",
+                    span_in(source, "&x", "x"),
+                    vec![span(source, "&mut x"), span_in(source, "*r", "r")]
+                ),
+                (
+                    "Cannot assign to `x` while it is borrowed",
+                    span_in(source, "x = 1;", "x"),
+                    vec![span(source, "&mut x"), span_in(source, "*r", "r")]
+                ),
+            ]
+        );
+
+        // Without the last use of `r`, its borrow ends immediately.
+        let source = "fn f() { let mut x: u8 = 1; let r = &mut x; let s = &x; x = 1; }";
+
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn reassigned_in_loops() {
+        let source = "
+            fn f(c: bool) -> u8 {
+                let mut a: u8 = 1;
+                let b: u8 = 2;
+                let mut p = &a;
+                let mut n = *p;
+                loop { a += 1; p = &b; n += *p; if n > 10 { break; } }
+                n
+            }
+            fn g(c: bool) -> u8 {
+                let mut a: u8 = 1;
+                let b: u8 = 2;
+                let mut p = &a;
+                loop { if c { break; } p = &b; }
+                a = 3;
+                *p
+            }
+            fn h() {
+                let mut a: u8 = 1;
+                let p = &a;
+                loop { let v = *p; a = v + 1; if v > 5 { break; } }
+            }
+        ";
+
+        // Assigning `p` ends its borrow of `a` in `f`, but in `g` the loop may be left before
+        // `p` is assigned, and in `h` the next iteration uses `p` again.
+        assert_eq!(
+            notes(&errors(source)),
+            vec![
+                (
+                    "Cannot assign to `a` while it is borrowed",
+                    span_in(source, "a = 3", "a"),
+                    vec![span_in(source, "fn g", "&a"), span_in(source, "*p\n", "p")]
+                ),
+                (
+                    "Cannot assign to `a` while it is borrowed",
+                    span_in(source, "a = v", "a"),
+                    vec![span_in(source, "fn h", "&a"), span_in(source, "*p; a", "p")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn returned_references() {
+        let source = "fn f(p: &u8) -> &u8 { let x: u8 = 1; &x }";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Cannot return a reference to local variable `x`",
+                span_in(source, "&x", "x"),
+                vec![span_in(source, "let x", "x")]
+            )]
+        );
+
+        let source = "fn f(p: &u8) -> &u8 { let r = &p; &**r }";
+
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn places() {
+        // Distinct fields do not overlap, but a struct overlaps its fields, and elements
+        // overlap each other since indices are only known at run time.
+        let source = "
+            struct P { a: u8, b: u8 }
+            fn f(mut p: P) { let r = &mut p.a; let s = &mut p.b; *r = 1; *s = 2; }
+            fn g(mut p: P) { let r = &mut p.a; let s = &p; *r = 1; }
+            fn h(mut a: [u8; 2]) { let r = &mut a[0]; let s = &mut a[1]; *r = 1; }
+            fn i(q: &mut u8) { let r = &*q; *q = 1; *r; }
+        ";
+
+        assert_eq!(
+            messages(&errors(source)),
+            vec![
+                (
+                    "Cannot borrow `p` as shared, as it is also borrowed as mutable",
+                    Some("`r` borrows `p.a` until its last use"),
+                    span_in(source, "let s = &p", "p")
+                ),
+                (
+                    "Cannot borrow `a[..]` as mutable more than once at a time",
+                    Some("`r` borrows `a[..]` until its last use"),
+                    span(source, "a[1]")
+                ),
+                (
+                    "Cannot assign to `*q` while it is borrowed",
+                    Some("`r` borrows `*q` until its last use"),
+                    span_in(source, "*q = 1", "*q")
+                ),
+            ]
+        );
+
+        // A borrow taken in an inner block ends with it.
+        let source = "fn f() { let mut x: u8 = 1; { let r = &x; x = 2; *r; } x = 3; }";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Cannot assign to `x` while it is borrowed",
+                span_in(source, "x = 2", "x"),
+                vec![span(source, "&x"), span_in(source, "*r", "r")]
+            )]
+        );
+    }
+
+    #[test]
+    fn call_arguments() {
+        let source = "
+            fn g(a: &mut u8, b: &u8) {}
+            fn h(a: &u8, b: &u8) {}
+            fn f() { let mut x: u8 = 1; g(&mut x, &x); h(&x, &x); }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Cannot borrow `x` as shared, as an earlier argument borrows it as mutable",
+                span_in(source, "&mut x, ", "&x"),
+                vec![span(source, "&mut x")]
+            )]
+        );
+    }
+
+    #[test]
+    fn statics_and_receivers() {
+        let source = "
+            static S: u8 = 1;
+            static T: [u8; 2] = [1, 2];
+            struct P { a: u8 }
+            impl P { fn set(&self) { self.a = 1; } }
+            fn f() { S = 2; T[0] = 2; }
+        ";
+
+        assert_eq!(
+            messages(&errors(source)),
+            vec![
+                (
+                    "Cannot assign to `self.a`, as it is behind a shared reference",
+                    Some("Only a `&mut` reference allows changes, but this has type `&P`"),
+                    span(source, "self.a")
+                ),
+                (
+                    "Cannot assign to `S`, as `S` is a static",
+                    Some("Statics cannot be changed once initialized"),
+                    span_in(source, "S = 2", "S")
+                ),
+                (
+                    "Cannot assign to `T[..]`, as `T` is a static",
+                    Some("Statics cannot be changed once initialized"),
+                    span(source, "T[0]")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn returned_through_bindings_and_branches() {
+        let source = "
+            fn f() -> &u8 { let x: u8 = 1; let r = &x; r }
+            fn g(c: bool, p: &u8) -> &u8 { let y: u8 = 1; if c { &y } else { p } }
+            fn h(c: u8, p: &u8) -> &u8 { let z: u8 = 1; when c { 0 => p, _ => return &z } }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![
+                (
+                    "Cannot return a reference to local variable `x`",
+                    span_in(source, "&x; r", "r"),
+                    vec![span_in(source, "let x", "x"), span(source, "&x")]
+                ),
+                (
+                    "Cannot return a reference to local variable `y`",
+                    span_in(source, "&y", "y"),
+                    vec![span_in(source, "let y", "y")]
+                ),
+                (
+                    "Cannot return a reference to local variable `z`",
+                    span_in(source, "&z", "z"),
+                    vec![span_in(source, "let z", "z")]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::model::base::*;
+use crate::model::ttg::TreeData;
+use crate::span::Span;
+
+/// Evaluates an expression whose value must be known at compile time, such as the count of an
+/// array repeat expression. Only integer arithmetic is supported. `lookup` gives the value of
+/// a named constant, or `None` if the path does not name one.
+pub fn evaluate<P: TreeData<P>>(
+    expression: &Expression<P>,
+    lookup: &impl Fn(&P::PathRepresentation) -> Option<u32>,
+) -> Result<u32, Error> {
+    let span = expression.span;
+
+    let without_block = match &expression.item {
+        ExpressionKind::ExpressionWithoutBlock(without_block) => without_block,
+        ExpressionKind::ExpressionWithBlock(_) => return Err(not_constant(span)),
+    };
+
+    match without_block {
+        ExpressionWithoutBlock::Literal(Literal::Integer(value), _) => Ok(*value),
+        ExpressionWithoutBlock::Grouped(inner, _) => evaluate(inner, lookup),
+        ExpressionWithoutBlock::Path(path, _) => lookup(path).ok_or_else(|| not_constant(span)),
+        ExpressionWithoutBlock::Binary(left, operator, right, _) => {
+            let left = evaluate(left, lookup)?;
+            let right = evaluate(right, lookup)?;
+
+            let result = match operator {
+                BinaryOperator::Plus => left.checked_add(right),
+                BinaryOperator::Minus => left.checked_sub(right),
+                BinaryOperator::Times => left.checked_mul(right),
+                BinaryOperator::Divide if right == 0 => {
+                    return Err(Error::new(
+                        span,
+                        String::from("Division by zero in constant expression"),
+                    ))
+                }
+                BinaryOperator::Divide => left.checked_div(right),
+                BinaryOperator::And => Some(left & right),
+                BinaryOperator::Or => Some(left | right),
+                BinaryOperator::LShift => left.checked_shl(right),
+                BinaryOperator::RShift => left.checked_shr(right),
+                _ => return Err(not_constant(span)),
+            };
+
+            result.ok_or_else(|| {
+                Error::new(
+                    span,
+                    String::from("Arithmetic overflow in constant expression"),
+                )
+            })
+        }
+        _ => Err(not_constant(span)),
+    }
+}
+
+fn not_constant(span: Span) -> Error {
+    Error::with_details(
+        span,
+        String::from("Expression cannot be evaluated at compile time"),
+        String::from("Only integer literals, constants, and arithmetic on them are allowed here"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::consteval::*;
+    use crate::span::Spanned;
+    use crate::testing::{expression, integer, variable};
+
+    #[allow(clippy::ptr_arg)] // Must match `Syntax::PathRepresentation` exactly.
+    fn size(path: &Vec<Spanned<String>>) -> Option<u32> {
+        match path[0].item.as_str() {
+            "SIZE" => Some(16),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let size_path = Box::new(variable("SIZE", 0));
+        // SIZE * 4 + 1
+        let product = expression(
+            ExpressionWithoutBlock::Binary(
+                size_path,
+                BinaryOperator::Times,
+                Box::new(integer(4, 0)),
+                (),
+            ),
+            0,
+        );
+        let sum = expression(
+            ExpressionWithoutBlock::Binary(
+                Box::new(product),
+                BinaryOperator::Plus,
+                Box::new(integer(1, 0)),
+                (),
+            ),
+            0,
+        );
+
+        assert_eq!(evaluate(&sum, &size).unwrap(), 65);
+    }
+
+    #[test]
+    fn overflow() {
+        let difference = expression(
+            ExpressionWithoutBlock::Binary(
+                Box::new(integer(1, 0)),
+                BinaryOperator::Minus,
+                Box::new(integer(2, 0)),
+                (),
+            ),
+            0,
+        );
+
+        assert!(evaluate(&difference, &size).is_err());
+    }
+}
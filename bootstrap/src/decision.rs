@@ -0,0 +1,1418 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::exhaustive;
+use crate::layout::{EnumLayout, Layouts, TagEncoding};
+use crate::model::base::*;
+use crate::model::def::{DefId, DefKind};
+use crate::model::ir::{BinaryOp, LabelId, Statement, VariableId};
+use crate::model::resolved::{LocalId, Name, Resolution, ResolvedPath};
+use crate::model::ttg::TreeData;
+use crate::model::typed::pattern_type;
+use crate::model::types::Type;
+use crate::typeck::{Tables, VariantShape};
+
+/// A step from a value to one of its parts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Projection {
+    Field(usize),          // Of a struct, in declaration order
+    Variant(DefId, usize), // Of the payload of a variant, once the value is known to hold it
+}
+
+/// A part of the scrutinee, reached by following projections from the whole of it.
+pub type Occurrence = Vec<Projection>;
+
+/// A question asked of a part of the scrutinee.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Test {
+    Variant(DefId),
+    Bool(bool),
+    Range(i64, i64), // An inclusive range of integers or of character codes
+    Constant(DefId), // Equality with a constant whose value is only known once it is lowered
+}
+
+pub type DecisionId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Decision {
+    /// Binds the variables of an arm to parts of the scrutinee, then runs its body.
+    Success {
+        arm: usize,
+        bindings: Vec<(LocalId, Occurrence)>,
+    },
+    /// Binds the variables of an arm, then runs its body if its guard holds and continues with
+    /// `otherwise` if not.
+    Guard {
+        arm: usize,
+        bindings: Vec<(LocalId, Occurrence)>,
+        otherwise: DecisionId,
+    },
+    /// Continues with the first case whose test the part of the scrutinee passes, or with
+    /// `default` if it passes none. Without a default, the cases cover every value.
+    Switch {
+        occurrence: Occurrence,
+        cases: Vec<(Test, DecisionId)>,
+        default: Option<DecisionId>,
+    },
+    /// No arm matches. Exhaustiveness checking makes this unreachable.
+    Failure,
+}
+
+/// The decisions made to find the arm of a `when` expression that a value matches. Identical
+/// subtrees are only stored once, so the tree is really a directed acyclic graph.
+#[derive(Debug)]
+pub struct DecisionTree {
+    nodes: Vec<Decision>,
+    root: DecisionId,
+}
+
+impl DecisionTree {
+    pub fn root(&self) -> DecisionId {
+        self.root
+    }
+
+    pub fn get(&self, id: DecisionId) -> &Decision {
+        &self.nodes[id]
+    }
+
+    /// The decisions that continue from the given one.
+    pub fn successors(&self, id: DecisionId) -> Vec<DecisionId> {
+        match &self.nodes[id] {
+            Decision::Success { .. } | Decision::Failure => Vec::new(),
+            Decision::Guard { otherwise, .. } => vec![*otherwise],
+            Decision::Switch { cases, default, .. } => cases
+                .iter()
+                .map(|(_, case)| *case)
+                .chain(*default)
+                .collect(),
+        }
+    }
+}
+
+/// Compiles the arms of a `when` expression into a decision tree, following "Compiling Pattern
+/// Matching to Good Decision Trees" (Maranget, 2008). The arms are a matrix with a row for each
+/// arm and a column for each part of the scrutinee still to be tested. Each switch tests a part
+/// that the first remaining row needs, and the rows are specialized to each outcome, so no path
+/// through the tree tests the same part twice. Arms with guards fall through to the arms after
+/// them when the guard fails.
+///
+/// The arms must have passed exhaustiveness checking, which guarantees that empty range
+/// patterns have been rejected. They may come from any phase after type checking, such as the
+/// desugared tree being lowered.
+pub fn compile<P>(scrutinee: &Type, arms: &[Arm<P>], tables: &Tables) -> DecisionTree
+where
+    P: TreeData<P, Pattern = Type, NameRepresentation = Name, PathRepresentation = ResolvedPath>,
+{
+    let mut compiler = Compiler {
+        tables,
+        nodes: Vec::new(),
+        ids: HashMap::new(),
+    };
+
+    let rows = arms
+        .iter()
+        .enumerate()
+        .map(|(arm, Arm { pattern, guard, .. })| Row {
+            patterns: vec![compiler.lower(pattern)],
+            bindings: Vec::new(),
+            arm,
+            guarded: guard.is_some(),
+        })
+        .collect();
+
+    let columns = vec![Column {
+        occurrence: Vec::new(),
+        ty: scrutinee.clone(),
+    }];
+
+    let failure = compiler.node(Decision::Failure);
+    let root = compiler.compile(&columns, rows, failure);
+
+    DecisionTree {
+        nodes: compiler.nodes,
+        root,
+    }
+}
+
+/// A pattern with the details irrelevant to testing removed, but unlike in exhaustiveness
+/// checking, with the variables it binds kept.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild(Option<LocalId>),
+    Ctor(Ctor, Vec<Pat>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Variant(DefId),
+    Bool(bool),
+    Range(i64, i64),
+    Single, // The only way to construct a struct or `()`, which needs no test
+    Constant(DefId),
+}
+
+impl Ctor {
+    fn covers(&self, other: &Ctor) -> bool {
+        match (self, other) {
+            (Ctor::Range(lo, hi), Ctor::Range(other_lo, other_hi)) => {
+                lo <= other_lo && other_hi <= hi
+            }
+            _ => self == other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Column {
+    occurrence: Occurrence,
+    ty: Type,
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    patterns: Vec<Pat>,
+    bindings: Vec<(LocalId, Occurrence)>,
+    arm: usize,
+    guarded: bool,
+}
+
+struct Compiler<'a> {
+    tables: &'a Tables,
+    nodes: Vec<Decision>,
+    ids: HashMap<Decision, DecisionId>,
+}
+
+impl Compiler<'_> {
+    /// Adds a decision to the tree, reusing an identical one if there is one already.
+    fn node(&mut self, decision: Decision) -> DecisionId {
+        // A switch whose every outcome leads to the same place need not be made.
+        if let Decision::Switch { cases, default, .. } = &decision {
+            let mut targets = cases.iter().map(|(_, case)| *case).chain(*default);
+            let first = targets.next();
+
+            if let Some(first) = first.filter(|first| targets.all(|target| target == *first)) {
+                return first;
+            }
+        }
+
+        if let Some(id) = self.ids.get(&decision) {
+            return *id;
+        }
+
+        self.nodes.push(decision.clone());
+        self.ids.insert(decision, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn lower<P>(&self, pattern: &Pattern<P>) -> Pat
+    where
+        P: TreeData<
+            P,
+            Pattern = Type,
+            NameRepresentation = Name,
+            PathRepresentation = ResolvedPath,
+        >,
+    {
+        use PatternKind::*;
+
+        let ty = pattern_type(&pattern.item);
+
+        match &pattern.item {
+            Wildcard(_) => Pat::Wild(None),
+            Binding(_, name, _) => match name.resolution {
+                Some(Resolution::Local(local)) => Pat::Wild(Some(local)),
+                _ => Pat::Wild(None),
+            },
+            Literal(Constant::Bool(value), _) => Pat::Ctor(Ctor::Bool(*value), Vec::new()),
+            Literal(constant, _) => {
+                let value = exhaustive::value(constant);
+                Pat::Ctor(Ctor::Range(value, value), Vec::new())
+            }
+            Range(start, end, limits, _) => {
+                let domain = exhaustive::domain(ty).expect("Range patterns have integer types");
+                let lo = start.as_ref().map_or(domain[0].0, exhaustive::value);
+                let hi = match (end, limits) {
+                    (Some(end), RangeLimits::Closed) => exhaustive::value(end),
+                    (Some(end), RangeLimits::HalfOpen) => exhaustive::value(end) - 1,
+                    (None, _) => domain[domain.len() - 1].1,
+                };
+
+                Pat::Ctor(Ctor::Range(lo, hi), Vec::new())
+            }
+            Path(path, _) => match path.resolution {
+                Resolution::Def(DefKind::Variant, variant) => {
+                    Pat::Ctor(Ctor::Variant(variant), Vec::new())
+                }
+                // The values of integer constants are known, so they are tested like literals.
+                Resolution::Def(_, def) => match self.tables.constants.get(&def) {
+                    Some(value) => Pat::Ctor(Ctor::Range(*value as i64, *value as i64), Vec::new()),
+                    None => Pat::Ctor(Ctor::Constant(def), Vec::new()),
+                },
+                _ => Pat::Wild(None),
+            },
+            Tuple(path, elements, _) => match path.resolution {
+                Resolution::Def(_, variant) => Pat::Ctor(
+                    Ctor::Variant(variant),
+                    elements.iter().map(|element| self.lower(element)).collect(),
+                ),
+                _ => Pat::Wild(None),
+            },
+            Struct(path, fields, _, _) => {
+                let (ctor, declared) = match path.resolution {
+                    Resolution::Def(DefKind::Variant, variant) => (
+                        Ctor::Variant(variant),
+                        match &self.tables.variants[&variant].shape {
+                            VariantShape::Struct(fields) => fields.as_slice(),
+                            _ => &[],
+                        },
+                    ),
+                    _ => match ty {
+                        Type::Named(def, _, _) => {
+                            (Ctor::Single, self.tables.fields[def].as_slice())
+                        }
+                        _ => (Ctor::Single, [].as_slice()),
+                    },
+                };
+
+                let fields = declared
+                    .iter()
+                    .map(|declared| {
+                        fields
+                            .iter()
+                            .find(|field| field.name.ident.item == declared.name)
+                            .map_or(Pat::Wild(None), |field| self.lower(&field.pattern))
+                    })
+                    .collect();
+
+                Pat::Ctor(ctor, fields)
+            }
+        }
+    }
+
+    /// Compiles the rows of a matrix, continuing with `fallback` if none of them match.
+    fn compile(&mut self, columns: &[Column], rows: Vec<Row>, fallback: DecisionId) -> DecisionId {
+        let Some(first) = rows.first() else {
+            return fallback;
+        };
+
+        // The first row matches if it tests nothing, so its arm is chosen unless its guard
+        // fails, in which case the remaining rows are tried.
+        let Some(index) = first
+            .patterns
+            .iter()
+            .position(|pattern| matches!(pattern, Pat::Ctor(..)))
+        else {
+            let mut bindings = first.bindings.clone();
+            for (pattern, column) in first.patterns.iter().zip(columns) {
+                if let Pat::Wild(Some(local)) = pattern {
+                    bindings.push((*local, column.occurrence.clone()));
+                }
+            }
+
+            let arm = first.arm;
+            return match first.guarded {
+                false => self.node(Decision::Success { arm, bindings }),
+                true => {
+                    let otherwise = self.compile(columns, rows[1..].to_vec(), fallback);
+                    self.node(Decision::Guard {
+                        arm,
+                        bindings,
+                        otherwise,
+                    })
+                }
+            };
+        };
+
+        let column = &columns[index];
+        let Pat::Ctor(head, _) = &first.patterns[index] else {
+            unreachable!("The column was chosen for its constructor");
+        };
+
+        match head {
+            Ctor::Single => {
+                let rows = rows
+                    .iter()
+                    .filter_map(|row| self.specialize(row, index, column, &Ctor::Single))
+                    .collect();
+
+                self.compile(&self.expand(columns, index, &Ctor::Single), rows, fallback)
+            }
+            // An unknown constant may equal any other constructor in the column, so only the
+            // first row learns from the test, and the others are compiled again without it.
+            Ctor::Constant(def) => {
+                let test = Test::Constant(*def);
+                let otherwise = self.compile(columns, rows[1..].to_vec(), fallback);
+
+                let mut matched = first.clone();
+                matched.patterns[index] = Pat::Wild(None);
+                let matched = self.compile(columns, vec![matched], otherwise);
+
+                self.node(Decision::Switch {
+                    occurrence: column.occurrence.clone(),
+                    cases: vec![(test, matched)],
+                    default: Some(otherwise),
+                })
+            }
+            _ => {
+                // Rows after one testing an unknown constant in this column are compiled
+                // separately, as the fallback of the rows before it.
+                let split = rows
+                    .iter()
+                    .position(|row| matches!(row.patterns[index], Pat::Ctor(Ctor::Constant(_), _)))
+                    .unwrap_or(rows.len());
+
+                let fallback = self.compile(columns, rows[split..].to_vec(), fallback);
+                self.switch(columns, index, &rows[..split], fallback)
+            }
+        }
+    }
+
+    fn switch(
+        &mut self,
+        columns: &[Column],
+        index: usize,
+        rows: &[Row],
+        fallback: DecisionId,
+    ) -> DecisionId {
+        let column = &columns[index];
+        let heads = rows
+            .iter()
+            .filter_map(|row| match &row.patterns[index] {
+                Pat::Ctor(ctor, _) => Some(ctor.clone()),
+                Pat::Wild(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let (ctors, complete) = match &column.ty {
+            Type::Bool => {
+                let ctors = [false, true].map(Ctor::Bool).to_vec();
+                let complete = ctors.iter().all(|ctor| heads.contains(ctor));
+                (
+                    ctors
+                        .into_iter()
+                        .filter(|ctor| heads.contains(ctor))
+                        .collect(),
+                    complete,
+                )
+            }
+            Type::Named(def, _, _) => {
+                let ctors = self
+                    .tables
+                    .variants_of(*def)
+                    .into_iter()
+                    .map(Ctor::Variant)
+                    .collect::<Vec<_>>();
+                let complete = ctors.iter().all(|ctor| heads.contains(ctor));
+                (
+                    ctors
+                        .into_iter()
+                        .filter(|ctor| heads.contains(ctor))
+                        .collect(),
+                    complete,
+                )
+            }
+            ty => {
+                let domain = exhaustive::domain(ty).expect("Only integers are tested by ranges");
+                let parts = split(&domain, &heads);
+                let (covered, uncovered): (Vec<_>, Vec<_>) = parts
+                    .into_iter()
+                    .partition(|part| heads.iter().any(|head| head.covers(part)));
+
+                (covered, uncovered.is_empty())
+            }
+        };
+
+        let mut cases: Vec<(Test, DecisionId)> = Vec::new();
+
+        for ctor in ctors {
+            let specialized = rows
+                .iter()
+                .filter_map(|row| self.specialize(row, index, column, &ctor))
+                .collect();
+            let target = self.compile(&self.expand(columns, index, &ctor), specialized, fallback);
+
+            // Adjacent ranges leading to the same place are tested together.
+            match (cases.last_mut(), &ctor) {
+                (Some((Test::Range(_, hi), last)), Ctor::Range(lo, next_hi))
+                    if *hi + 1 == *lo && *last == target =>
+                {
+                    *hi = *next_hi;
+                }
+                _ => cases.push((test(&ctor), target)),
+            }
+        }
+
+        let default = (!complete).then(|| {
+            let defaults = rows
+                .iter()
+                .filter(|row| matches!(row.patterns[index], Pat::Wild(_)))
+                .map(|row| {
+                    let mut row = row.clone();
+                    if let Pat::Wild(Some(local)) = row.patterns.remove(index) {
+                        row.bindings.push((local, column.occurrence.clone()));
+                    }
+                    row
+                })
+                .collect();
+
+            let mut remaining = columns.to_vec();
+            remaining.remove(index);
+            self.compile(&remaining, defaults, fallback)
+        });
+
+        // Cases leading where the default does are left to it.
+        if let Some(default) = default {
+            cases.retain(|(_, case)| *case != default);
+        }
+
+        self.node(Decision::Switch {
+            occurrence: column.occurrence.clone(),
+            cases,
+            default,
+        })
+    }
+
+    /// The row with the pattern in the given column replaced by the fields of `ctor`, or
+    /// `None` if the pattern cannot match values constructed by `ctor`.
+    fn specialize(&self, row: &Row, index: usize, column: &Column, ctor: &Ctor) -> Option<Row> {
+        let mut row = row.clone();
+        let fields = match row.patterns.remove(index) {
+            Pat::Wild(local) => {
+                if let Some(local) = local {
+                    row.bindings.push((local, column.occurrence.clone()));
+                }
+
+                vec![Pat::Wild(None); self.field_types(ctor, &column.ty).len()]
+            }
+            Pat::Ctor(head, fields) if head.covers(ctor) => fields,
+            Pat::Ctor(_, _) => return None,
+        };
+
+        row.patterns.splice(index..index, fields);
+        Some(row)
+    }
+
+    /// The columns with the given one replaced by the fields of `ctor`.
+    fn expand(&self, columns: &[Column], index: usize, ctor: &Ctor) -> Vec<Column> {
+        let column = &columns[index];
+        let fields = self
+            .field_types(ctor, &column.ty)
+            .into_iter()
+            .enumerate()
+            .map(|(field, ty)| {
+                let projection = match ctor {
+                    Ctor::Variant(variant) => Projection::Variant(*variant, field),
+                    _ => Projection::Field(field),
+                };
+
+                let mut occurrence = column.occurrence.clone();
+                occurrence.push(projection);
+                Column { occurrence, ty }
+            });
+
+        let mut columns = columns.to_vec();
+        columns.splice(index..=index, fields);
+        columns
+    }
+
+    fn field_types(&self, ctor: &Ctor, ty: &Type) -> Vec<Type> {
+        match (ctor, ty) {
+            (Ctor::Variant(variant), _) => variant_fields(self.tables, *variant, ty),
+            (Ctor::Single, Type::Named(def, _, _)) => self.tables.fields[def]
+                .iter()
+                .map(|field| field.ty.clone())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn test(ctor: &Ctor) -> Test {
+    match ctor {
+        Ctor::Variant(variant) => Test::Variant(*variant),
+        Ctor::Bool(value) => Test::Bool(*value),
+        Ctor::Range(lo, hi) => Test::Range(*lo, *hi),
+        Ctor::Constant(def) => Test::Constant(*def),
+        Ctor::Single => unreachable!("Structs are never tested"),
+    }
+}
+
+/// The types of the fields of a variant of `ty`, an instance of the enum declaring it.
+fn variant_fields(tables: &Tables, variant: DefId, ty: &Type) -> Vec<Type> {
+    let substitution = tables.substitution(ty);
+
+    match &tables.variants[&variant].shape {
+        VariantShape::Unit => Vec::new(),
+        VariantShape::Tuple(types) => types
+            .iter()
+            .map(|ty| ty.substitute(&substitution))
+            .collect(),
+        VariantShape::Struct(fields) => fields
+            .iter()
+            .map(|field| field.ty.substitute(&substitution))
+            .collect(),
+    }
+}
+
+/// Branches on the variant held by the enum value in `scrutinee`, reading its tag into `tag`
+/// unless the enum has no tag. Each variant goes to `target(variant)`, and `default` is where
+/// `target` sends the variants that need no case of their own.
+pub fn switch_variant(
+    layout: &EnumLayout,
+    scrutinee: VariableId,
+    tag: VariableId,
+    target: impl Fn(DefId) -> LabelId,
+    default: LabelId,
+) -> Vec<Statement> {
+    let (offset, size, values, default) = match &layout.tag {
+        TagEncoding::None => return vec![Statement::Goto(target(layout.variants[0].variant))],
+        TagEncoding::Direct { size } => {
+            let values = layout
+                .variants
+                .iter()
+                .map(|variant| (variant.discriminant, variant.variant))
+                .collect::<Vec<_>>();
+
+            (0, *size, values, default)
+        }
+        // The dataful variant holds every value of the niche's scalar other than those of the
+        // other variants.
+        TagEncoding::Niche { dataful, niche } => {
+            let values = layout
+                .variants
+                .iter()
+                .filter(|variant| variant.variant != *dataful)
+                .enumerate()
+                .map(|(index, variant)| (niche.start + index as u32, variant.variant))
+                .collect::<Vec<_>>();
+
+            (niche.offset, niche.size, values, target(*dataful))
+        }
+    };
+
+    let cases = values
+        .into_iter()
+        .map(|(value, variant)| (value as i64, value as i64, target(variant)))
+        .filter(|(_, _, label)| *label != default)
+        .collect();
+
+    vec![
+        Statement::Project {
+            target: tag,
+            source: scrutinee,
+            offset,
+            size,
+        },
+        Statement::Switch {
+            scrutinee: tag,
+            cases,
+            default,
+        },
+    ]
+}
+
+/// Builds a value of `variant` in `target` from the values of its fields, each with its size,
+/// recording the variant in the enum's tag or niche.
+pub fn construct(
+    layout: &EnumLayout,
+    variant: DefId,
+    target: VariableId,
+    fields: &[(VariableId, u32)],
+) -> Vec<Statement> {
+    let index = layout
+        .variants
+        .iter()
+        .position(|layout| layout.variant == variant)
+        .expect("Every variant of an enum has a layout");
+
+    let mut statements = match &layout.tag {
+        TagEncoding::None => Vec::new(),
+        TagEncoding::Direct { size } => vec![Statement::InsertImmediate {
+            target,
+            offset: 0,
+            value: layout.variants[index].discriminant,
+            size: *size,
+        }],
+        TagEncoding::Niche { dataful, .. } if *dataful == variant => Vec::new(),
+        // The variants other than the dataful one take consecutive values of the niche.
+        TagEncoding::Niche { dataful, niche } => {
+            let position = layout
+                .variants
+                .iter()
+                .filter(|layout| layout.variant != *dataful)
+                .position(|layout| layout.variant == variant)
+                .expect("The variant is not the dataful one");
+
+            vec![Statement::InsertImmediate {
+                target,
+                offset: niche.offset,
+                value: niche.start + position as u32,
+                size: niche.size,
+            }]
+        }
+    };
+
+    let offsets = &layout.variants[index].offsets;
+    statements.extend(fields.iter().zip(offsets).map(|((source, size), offset)| {
+        Statement::Insert {
+            target,
+            offset: *offset,
+            source: *source,
+            size: *size,
+        }
+    }));
+
+    statements
+}
+
+/// Splits ranges into the parts that each of the given constructors either covers wholly or
+/// not at all.
+fn split(ranges: &[(i64, i64)], heads: &[Ctor]) -> Vec<Ctor> {
+    let mut cuts = heads
+        .iter()
+        .filter_map(|head| match head {
+            Ctor::Range(lo, hi) => Some([*lo, hi + 1]),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    cuts.sort();
+    cuts.dedup();
+
+    let mut parts = Vec::new();
+
+    for &(lo, hi) in ranges {
+        let mut start = lo;
+
+        for &cut in cuts.iter().filter(|&&cut| lo < cut && cut <= hi) {
+            parts.push(Ctor::Range(start, cut - 1));
+            start = cut;
+        }
+
+        parts.push(Ctor::Range(start, hi));
+    }
+
+    parts
+}
+
+/// What lowering a decision tree needs from the pass lowering the function around it.
+pub trait Lower {
+    fn variable(&mut self) -> VariableId;
+    fn label(&mut self) -> LabelId;
+    /// The variable a pattern binding declares.
+    fn local(&mut self, local: LocalId) -> VariableId;
+    /// Where the body of an arm starts.
+    fn arm(&mut self, arm: usize) -> LabelId;
+    /// Lowers the guard of an arm, giving the variable that holds its value. A guard is
+    /// lowered once for every place in the tree that checks it.
+    fn guard(&mut self, arm: usize) -> (Vec<Statement>, VariableId);
+    /// Loads the value of a constant that a pattern compares against.
+    fn constant(&mut self, def: DefId) -> (Vec<Statement>, VariableId);
+}
+
+/// Lowers a decision tree to branching IR, given the variable holding the scrutinee. Every
+/// decision starts at a label of its own, so shared subtrees are only lowered once. A part of
+/// the scrutinee is read into the same variable wherever it is needed, and only once along the
+/// way into a decision that is reached from a single place.
+pub fn lower(
+    tree: &DecisionTree,
+    scrutinee: (VariableId, &Type),
+    tables: &Tables,
+    layouts: &Layouts,
+    lower: &mut impl Lower,
+) -> Vec<Statement> {
+    let mut predecessors = HashMap::new();
+    let mut stack = vec![tree.root()];
+    let mut seen = HashSet::new();
+
+    while let Some(id) = stack.pop() {
+        if seen.insert(id) {
+            for successor in tree.successors(id) {
+                *predecessors.entry(successor).or_insert(0) += 1;
+                stack.push(successor);
+            }
+        }
+    }
+
+    let mut lowering = Lowering {
+        tree,
+        tables,
+        layouts,
+        labels: HashMap::new(),
+        variables: HashMap::from([(Vec::new(), scrutinee.0)]),
+        types: HashMap::from([(Vec::new(), scrutinee.1.clone())]),
+        statements: Vec::new(),
+    };
+
+    let mut pending = vec![(tree.root(), HashSet::new())];
+    let mut lowered = HashSet::new();
+
+    while let Some((id, loaded)) = pending.pop() {
+        if !lowered.insert(id) {
+            continue;
+        }
+
+        let successors = lowering.decision(id, loaded, lower);
+
+        // Decisions reached from elsewhere too cannot rely on what this one has read.
+        for (successor, loaded) in successors.into_iter().rev() {
+            let loaded = match predecessors[&successor] {
+                1 => loaded,
+                _ => HashSet::new(),
+            };
+
+            pending.push((successor, loaded));
+        }
+    }
+
+    lowering.statements
+}
+
+struct Lowering<'a> {
+    tree: &'a DecisionTree,
+    tables: &'a Tables,
+    layouts: &'a Layouts,
+    labels: HashMap<DecisionId, LabelId>,
+    variables: HashMap<Occurrence, VariableId>,
+    types: HashMap<Occurrence, Type>,
+    statements: Vec<Statement>,
+}
+
+impl Lowering<'_> {
+    fn label(&mut self, id: DecisionId, lower: &mut impl Lower) -> LabelId {
+        *self.labels.entry(id).or_insert_with(|| lower.label())
+    }
+
+    /// Lowers one decision, giving its successors along with the parts of the scrutinee that
+    /// have been read on the way to each.
+    fn decision(
+        &mut self,
+        id: DecisionId,
+        mut loaded: HashSet<Occurrence>,
+        lower: &mut impl Lower,
+    ) -> Vec<(DecisionId, HashSet<Occurrence>)> {
+        let label = self.label(id, lower);
+        self.statements.push(Statement::Label(label));
+
+        match self.tree.get(id) {
+            Decision::Success { arm, bindings } => {
+                self.bind(bindings, &mut loaded, lower);
+                let arm = lower.arm(*arm);
+                self.statements.push(Statement::Goto(arm));
+                Vec::new()
+            }
+            Decision::Guard {
+                arm,
+                bindings,
+                otherwise,
+            } => {
+                self.bind(bindings, &mut loaded, lower);
+                let (statements, condition) = lower.guard(*arm);
+                let arm = lower.arm(*arm);
+                let otherwise_label = self.label(*otherwise, lower);
+
+                self.statements.extend(statements);
+                self.statements.push(Statement::GotoIf(condition, arm));
+                self.statements.push(Statement::Goto(otherwise_label));
+                vec![(*otherwise, loaded)]
+            }
+            Decision::Switch {
+                occurrence,
+                cases,
+                default,
+            } => {
+                let scrutinee = self.load(occurrence, &mut loaded, lower);
+                let mut targets = cases
+                    .iter()
+                    .map(|(test, case)| (test, self.label(*case, lower)))
+                    .collect::<Vec<_>>();
+
+                // The last case is the default of a switch that covers every value.
+                let default_label = match default {
+                    Some(default) => self.label(*default, lower),
+                    None => targets.pop().expect("Switches have a case or a default").1,
+                };
+
+                self.test(occurrence, scrutinee, &targets, default_label, lower);
+
+                self.tree
+                    .successors(id)
+                    .into_iter()
+                    .map(|successor| (successor, loaded.clone()))
+                    .collect()
+            }
+            Decision::Failure => {
+                self.statements.push(Statement::Unreachable);
+                Vec::new()
+            }
+        }
+    }
+
+    fn bind(
+        &mut self,
+        bindings: &[(LocalId, Occurrence)],
+        loaded: &mut HashSet<Occurrence>,
+        lower: &mut impl Lower,
+    ) {
+        for (local, occurrence) in bindings {
+            let source = self.load(occurrence, loaded, lower);
+            let target = lower.local(*local);
+            self.statements.push(Statement::Assign { target, source });
+        }
+    }
+
+    fn test(
+        &mut self,
+        occurrence: &Occurrence,
+        scrutinee: VariableId,
+        targets: &[(&Test, LabelId)],
+        default: LabelId,
+        lower: &mut impl Lower,
+    ) {
+        let ranges = match targets.first() {
+            None => {
+                self.statements.push(Statement::Goto(default));
+                return;
+            }
+            Some((Test::Constant(def), label)) => {
+                let (statements, constant) = lower.constant(*def);
+                let equal = lower.variable();
+
+                self.statements.extend(statements);
+                self.statements.push(Statement::BinaryOp {
+                    op: BinaryOp::Equal,
+                    target: equal,
+                    left: scrutinee,
+                    right: constant,
+                });
+                self.statements.push(Statement::GotoIf(equal, *label));
+                self.statements.push(Statement::Goto(default));
+                return;
+            }
+            Some((Test::Variant(_), _)) => {
+                return self.tag(occurrence, scrutinee, targets, default, lower)
+            }
+            Some(_) => targets
+                .iter()
+                .map(|(test, label)| match test {
+                    Test::Bool(value) => (*value as i64, *value as i64, *label),
+                    Test::Range(lo, hi) => (*lo, *hi, *label),
+                    _ => unreachable!("A switch tests one kind of constructor"),
+                })
+                .collect(),
+        };
+
+        self.statements.push(Statement::Switch {
+            scrutinee,
+            cases: ranges,
+            default,
+        });
+    }
+
+    /// Tests which variant an enum holds, according to how its layout records it.
+    fn tag(
+        &mut self,
+        occurrence: &Occurrence,
+        scrutinee: VariableId,
+        targets: &[(&Test, LabelId)],
+        default: LabelId,
+        lower: &mut impl Lower,
+    ) {
+        let Type::Named(def, _, arguments) = &self.types[occurrence] else {
+            unreachable!("Variants are only tested on enums");
+        };
+        let layout = self
+            .layouts
+            .of_enum(*def, arguments)
+            .expect("Every enum has a layout");
+
+        let target = |variant: DefId| {
+            targets
+                .iter()
+                .find(|(test, _)| **test == Test::Variant(variant))
+                .map_or(default, |(_, label)| *label)
+        };
+
+        let tag = lower.variable();
+        let statements = switch_variant(layout, scrutinee, tag, target, default);
+        self.statements.extend(statements);
+    }
+
+    /// The variable holding a part of the scrutinee, reading it first if it has not been
+    /// read on the way here.
+    fn load(
+        &mut self,
+        occurrence: &Occurrence,
+        loaded: &mut HashSet<Occurrence>,
+        lower: &mut impl Lower,
+    ) -> VariableId {
+        let Some((projection, parent)) = occurrence.split_last() else {
+            return self.variables[occurrence];
+        };
+
+        let parent = parent.to_vec();
+        let source = self.load(&parent, loaded, lower);
+        let target = *self
+            .variables
+            .entry(occurrence.clone())
+            .or_insert_with(|| lower.variable());
+
+        if loaded.insert(occurrence.clone()) {
+            let (offset, ty) = match (projection, &self.types[&parent]) {
+                (Projection::Field(field), Type::Named(def, _, _)) => (
+                    self.layouts
+                        .of_struct(*def)
+                        .expect("Every struct has a layout")
+                        .offsets[*field],
+                    self.tables.fields[def][*field].ty.clone(),
+                ),
+                (Projection::Variant(variant, field), Type::Named(def, _, arguments)) => (
+                    self.layouts
+                        .of_enum(*def, arguments)
+                        .and_then(|layout| {
+                            layout
+                                .variants
+                                .iter()
+                                .find(|layout| layout.variant == *variant)
+                        })
+                        .expect("Every variant has a layout")
+                        .offsets[*field],
+                    variant_fields(self.tables, *variant, &self.types[&parent])[*field].clone(),
+                ),
+                _ => unreachable!("Only structs and enums have fields"),
+            };
+
+            let size = self.layouts.of(&ty).size;
+            self.types.insert(occurrence.clone(), ty);
+            self.statements.push(Statement::Project {
+                target,
+                source,
+                offset,
+                size,
+            });
+        }
+
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decision::*;
+    use crate::layout::{self, DataModel};
+    use crate::model::base;
+    use crate::model::typed::{type_of, Typed};
+    use crate::testing;
+
+    struct Compiled {
+        tree: DecisionTree,
+        geode: Geode<Typed>,
+        tables: Tables,
+        scrutinee: Type,
+    }
+
+    /// Compiles the `when` expression that makes up the body of the last function in `source`.
+    fn compile_source(source: &str) -> Compiled {
+        let (geode, tables, _) = testing::typed(source);
+
+        let (scrutinee, arms) = match &geode.modules[0].items.last().unwrap().kind {
+            ItemKind::Function(FunctionItem {
+                body: Some(body), ..
+            }) => match body.first() {
+                Some(Some(
+                    Statements::Block(case)
+                    | Statements::Leading(base::Statement::Expression(case), _, _),
+                )) => match &case.item {
+                    ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Case(
+                        scrutinee,
+                        arms,
+                        _,
+                    )) => (type_of(scrutinee).clone(), arms),
+                    _ => panic!("Expected a `when` expression"),
+                },
+                _ => panic!("Expected a `when` expression"),
+            },
+            _ => panic!("Expected a function"),
+        };
+
+        let tree = compile(&scrutinee, arms, &tables);
+
+        Compiled {
+            tree,
+            geode,
+            tables,
+            scrutinee,
+        }
+    }
+
+    fn success(arm: usize) -> Decision {
+        Decision::Success {
+            arm,
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Checks that no path through the tree tests the same part of the scrutinee twice.
+    fn assert_tested_once(tree: &DecisionTree, id: DecisionId, tested: &mut Vec<Occurrence>) {
+        if let Decision::Switch { occurrence, .. } = tree.get(id) {
+            assert!(
+                !tested.contains(occurrence),
+                "{occurrence:?} is tested twice"
+            );
+            tested.push(occurrence.clone());
+        }
+
+        for successor in tree.successors(id) {
+            assert_tested_once(tree, successor, tested);
+        }
+
+        if let Decision::Switch { .. } = tree.get(id) {
+            tested.pop();
+        }
+    }
+
+    #[test]
+    fn nested_patterns() {
+        let compiled = compile_source(
+            "
+            enum Shape { Circle(u8), Square, Line { a: bool, b: u8 } }
+            fn f(x: Shape) {
+                when x {
+                    Shape::Circle(0) => {},
+                    Shape::Circle(r) => {},
+                    Shape::Line { b: 3, .. } => {},
+                    _ => {},
+                }
+            }
+            ",
+        );
+
+        let tree = &compiled.tree;
+        assert_tested_once(tree, tree.root(), &mut Vec::new());
+
+        let Decision::Switch {
+            occurrence,
+            cases,
+            default,
+        } = tree.get(tree.root())
+        else {
+            panic!("Expected a switch on the variant");
+        };
+
+        assert!(occurrence.is_empty());
+        assert_eq!(cases.len(), 2);
+        assert_eq!(
+            tree.get(default.unwrap()),
+            &Decision::Success {
+                arm: 3,
+                bindings: Vec::new()
+            }
+        );
+
+        // The payload of `Circle` is read through the variant, and `r` binds it.
+        let Test::Variant(circle) = cases[0].0 else {
+            panic!("Expected a variant test");
+        };
+        let Decision::Switch {
+            occurrence,
+            cases: circle_cases,
+            default: Some(circle_default),
+        } = tree.get(cases[0].1)
+        else {
+            panic!("Expected a switch on the payload of `Circle`");
+        };
+
+        assert_eq!(occurrence, &vec![Projection::Variant(circle, 0)]);
+        assert_eq!(circle_cases[0].0, Test::Range(0, 0));
+        assert!(matches!(
+            tree.get(*circle_default),
+            Decision::Success { arm: 1, bindings } if bindings[0].1 == *occurrence
+        ));
+
+        // The failed test of `b` leads to the same decision as the default of the variant.
+        let Decision::Switch {
+            default: line_default,
+            ..
+        } = tree.get(cases[1].1)
+        else {
+            panic!("Expected a switch on `b`");
+        };
+
+        assert_eq!(line_default, default);
+    }
+
+    #[test]
+    fn guards_and_ranges() {
+        let compiled = compile_source(
+            "fn f(x: u8) { when x { n if True => {}, 0..=9 => {}, 10..=19 => {}, 20 => {}, _ => {} } }",
+        );
+
+        let tree = &compiled.tree;
+        let Decision::Guard {
+            arm: 0,
+            bindings,
+            otherwise,
+        } = tree.get(tree.root())
+        else {
+            panic!("Expected the guard to be checked first");
+        };
+
+        assert_eq!(bindings[0].1, Vec::new());
+
+        let Decision::Switch { cases, default, .. } = tree.get(*otherwise) else {
+            panic!("Expected a switch on the value");
+        };
+
+        assert_eq!(
+            cases
+                .iter()
+                .map(|(test, case)| (test.clone(), tree.get(*case).clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Test::Range(0, 9), success(1)),
+                (Test::Range(10, 19), success(2)),
+                (Test::Range(20, 20), success(3)),
+            ]
+        );
+        assert_eq!(tree.get(default.unwrap()), &success(4));
+    }
+
+    #[test]
+    fn structs_and_bools() {
+        let compiled = compile_source(
+            "
+            struct P { a: bool, b: bool }
+            fn f(p: P) { when p { P { a: True, b: True } => {}, P { a: False, b } => {}, _ => {} } }
+            ",
+        );
+
+        // A struct has only one constructor, so its fields are tested directly.
+        let tree = &compiled.tree;
+        let Decision::Switch {
+            occurrence,
+            cases,
+            default: None,
+        } = tree.get(tree.root())
+        else {
+            panic!("Expected a complete switch on `a`");
+        };
+
+        assert_eq!(occurrence, &vec![Projection::Field(0)]);
+        assert_eq!(
+            cases.iter().map(|(test, _)| test).collect::<Vec<_>>(),
+            vec![&Test::Bool(false), &Test::Bool(true)]
+        );
+        assert!(matches!(
+            tree.get(cases[0].1),
+            Decision::Success { arm: 1, bindings } if bindings[0].1 == vec![Projection::Field(1)]
+        ));
+
+        let Decision::Switch {
+            occurrence,
+            cases,
+            default: Some(default),
+        } = tree.get(cases[1].1)
+        else {
+            panic!("Expected a switch on `b`");
+        };
+
+        assert_eq!(occurrence, &vec![Projection::Field(1)]);
+        assert_eq!(cases[0].0, Test::Bool(true));
+        assert_eq!(tree.get(cases[0].1), &success(0));
+        assert_eq!(tree.get(*default), &success(2));
+    }
+
+    #[test]
+    fn constants() {
+        // The value of an integer constant is known, so it is tested like a literal.
+        let compiled =
+            compile_source("const C: u8 = 1; fn f(x: u8) { when x { ::C => {}, _ => {} } }");
+
+        let tree = &compiled.tree;
+        let Decision::Switch { cases, .. } = tree.get(tree.root()) else {
+            panic!("Expected a switch on the value");
+        };
+
+        assert_eq!(cases[0].0, Test::Range(1, 1));
+
+        // Other constants may equal any literal, so the arms after one are tried if it differs.
+        let compiled = compile_source(
+            "const C: char = 'a'; fn f(x: char) { when x { ::C => {}, 'b' => {}, _ => {} } }",
+        );
+
+        let tree = &compiled.tree;
+        let Decision::Switch {
+            cases,
+            default: Some(otherwise),
+            ..
+        } = tree.get(tree.root())
+        else {
+            panic!("Expected a switch on the constant");
+        };
+
+        assert!(
+            matches!(cases[..], [(Test::Constant(_), matched)] if tree.get(matched) == &success(0))
+        );
+
+        let Decision::Switch {
+            cases,
+            default: Some(default),
+            ..
+        } = tree.get(*otherwise)
+        else {
+            panic!("Expected a switch on the literal");
+        };
+
+        assert_eq!(cases[0].0, Test::Range('b' as i64, 'b' as i64));
+        assert_eq!(tree.get(cases[0].1), &success(1));
+        assert_eq!(tree.get(*default), &success(2));
+    }
+
+    #[test]
+    fn shared_decisions() {
+        // The parts of `0..=9` split off by `5` lead to the same arm, so they are tested as one.
+        let compiled = compile_source("fn f(x: u8) { when x { 0..=9 => {}, 5 => {}, _ => {} } }");
+
+        let tree = &compiled.tree;
+        let Decision::Switch { cases, default, .. } = tree.get(tree.root()) else {
+            panic!("Expected a switch on the value");
+        };
+
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].0, Test::Range(0, 9));
+        assert_eq!(tree.get(default.unwrap()), &success(2));
+
+        // Both payload tests fall back to the same decision, which is only stored once.
+        let compiled = compile_source(
+            "
+            enum E { A(bool), B(bool) }
+            fn f(x: E) { when x { E::A(True) => {}, E::B(True) => {}, _ => {} } }
+            ",
+        );
+
+        let tree = &compiled.tree;
+        let defaults = tree
+            .successors(tree.root())
+            .into_iter()
+            .map(|payload| match tree.get(payload) {
+                Decision::Switch { default, .. } => default.unwrap(),
+                _ => panic!("Expected a switch on the payload"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(defaults.len(), 2);
+        assert_eq!(defaults[0], defaults[1]);
+        assert_eq!(tree.get(defaults[0]), &success(2));
+    }
+
+    struct Builder {
+        next: usize,
+        arms: HashMap<usize, LabelId>,
+    }
+
+    impl Lower for Builder {
+        fn variable(&mut self) -> VariableId {
+            self.next += 1;
+            self.next
+        }
+
+        fn label(&mut self) -> LabelId {
+            self.next += 1;
+            self.next
+        }
+
+        fn local(&mut self, _: LocalId) -> VariableId {
+            self.variable()
+        }
+
+        fn arm(&mut self, arm: usize) -> LabelId {
+            let next = self.next + 1;
+            *self.arms.entry(arm).or_insert_with(|| {
+                self.next = next;
+                next
+            })
+        }
+
+        fn guard(&mut self, _: usize) -> (Vec<Statement>, VariableId) {
+            (Vec::new(), self.variable())
+        }
+
+        fn constant(&mut self, _: DefId) -> (Vec<Statement>, VariableId) {
+            (Vec::new(), self.variable())
+        }
+    }
+
+    #[test]
+    fn lowering() {
+        let compiled = compile_source(
+            "
+            enum Option { None, Some(&u8) }
+            fn f(x: Option) { when x { Option::Some(r) => {}, Option::None => {} } }
+            ",
+        );
+
+        let layouts = layout::compute(&compiled.geode, DataModel::BITS_32).unwrap();
+        let mut builder = Builder {
+            next: 0,
+            arms: HashMap::new(),
+        };
+
+        let statements = lower(
+            &compiled.tree,
+            (0, &compiled.scrutinee),
+            &compiled.tables,
+            &layouts,
+            &mut builder,
+        );
+
+        // `None` is the null reference, so the tag is the reference itself.
+        let switches = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Switch { cases, default, .. } => Some((cases.clone(), *default)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(switches.len(), 1);
+        assert_eq!(switches[0].0.len(), 1);
+        assert_eq!((switches[0].0[0].0, switches[0].0[0].1), (0, 0));
+
+        // Each decision jumps to the arm it chooses right after its label.
+        let chosen = |label: LabelId| {
+            let start = statements
+                .iter()
+                .position(
+                    |statement| matches!(statement, Statement::Label(other) if *other == label),
+                )
+                .unwrap();
+
+            statements[start..]
+                .iter()
+                .find_map(|statement| match statement {
+                    Statement::Goto(target) => Some(*target),
+                    _ => None,
+                })
+        };
+
+        assert_eq!(chosen(switches[0].0[0].2), Some(builder.arms[&1]));
+        assert_eq!(chosen(switches[0].1), Some(builder.arms[&0]));
+
+        let projections = statements
+            .iter()
+            .filter(|statement| {
+                matches!(
+                    statement,
+                    Statement::Project {
+                        offset: 0,
+                        size: 4,
+                        ..
+                    }
+                )
+            })
+            .count();
+
+        // One read of the tag, and one of the payload to bind `r`.
+        assert_eq!(projections, 2);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error::Error;
+use crate::span::Span;
+
+/// The `defer` statements registered so far in each block enclosing the point being lowered,
+/// innermost block last. `D` is whatever the lowering pass keeps to lower a deferred expression
+/// again, once for every edge that leaves its block.
+///
+/// Cleanup is attached to edges during CFG construction rather than by rewriting the tree,
+/// since a single `return` or `break` may leave several blocks at once, each with its own
+/// deferred expressions. A block's deferred expressions run in the reverse of the order they
+/// were registered in, and those of inner blocks run before those of outer blocks.
+#[derive(Debug)]
+pub struct Scopes<D> {
+    blocks: Vec<Vec<D>>,
+    // Depths at which the deferred expressions currently being lowered were registered.
+    // Control may not leave a deferred expression, as that would skip the rest of the cleanup.
+    boundaries: Vec<usize>,
+}
+
+impl<D> Scopes<D> {
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            boundaries: Vec::new(),
+        }
+    }
+
+    /// The number of enclosing blocks. Loops record this on entry, so that `break` and
+    /// `continue` know which blocks they leave.
+    pub fn depth(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn enter(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    pub fn defer(&mut self, deferred: D) {
+        self.blocks
+            .last_mut()
+            .expect("`defer` statements only appear within blocks")
+            .push(deferred);
+    }
+
+    /// Leaves the innermost block normally, returning its deferred expressions in the order
+    /// they must run.
+    pub fn exit(&mut self) -> Vec<D> {
+        let mut deferred = self
+            .blocks
+            .pop()
+            .expect("Blocks should be exited exactly as many times as they are entered");
+        deferred.reverse();
+        deferred
+    }
+
+    /// The deferred expressions to run on an edge that jumps out to the given depth, in the
+    /// order they must run. This is `0` for `return` and `?`, and the depth recorded by the
+    /// target loop for `break` and `continue`. Nothing is popped, since lowering continues
+    /// within the same blocks after the jump.
+    pub fn unwind(&self, depth: usize, keyword: &str, span: Span) -> Result<Vec<&D>, Error> {
+        if let Some(boundary) = self.boundaries.last() {
+            if depth < *boundary {
+                return Err(Error::new(
+                    span,
+                    format!("`{keyword}` cannot leave a deferred expression"),
+                ));
+            }
+        }
+
+        Ok(self.blocks[depth..]
+            .iter()
+            .rev()
+            .flat_map(|block| block.iter().rev())
+            .collect())
+    }
+
+    /// Marks the start of lowering a deferred expression, which happens once per exit edge.
+    /// The expression was registered in the innermost block, so it may only jump within
+    /// blocks entered after this point.
+    pub fn begin_deferred(&mut self) {
+        self.boundaries.push(self.blocks.len());
+    }
+
+    pub fn end_deferred(&mut self) {
+        self.boundaries.pop();
+    }
+}
+
+impl<D> Default for Scopes<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::defer::*;
+
+    fn span() -> Span {
+        Span::new(0, 1)
+    }
+
+    #[test]
+    fn lifo_order() {
+        let mut scopes = Scopes::new();
+        scopes.enter();
+        scopes.defer("close file");
+        scopes.defer("unlock");
+
+        assert_eq!(scopes.exit(), vec!["unlock", "close file"]);
+    }
+
+    #[test]
+    fn unwind_nested_blocks() {
+        let mut scopes = Scopes::new();
+        scopes.enter();
+        scopes.defer("outer");
+        let loop_depth = scopes.depth();
+        scopes.enter();
+        scopes.defer("inner 1");
+        scopes.defer("inner 2");
+
+        assert_eq!(
+            scopes.unwind(0, "return", span()).unwrap(),
+            vec![&"inner 2", &"inner 1", &"outer"]
+        );
+        assert_eq!(
+            scopes.unwind(loop_depth, "break", span()).unwrap(),
+            vec![&"inner 2", &"inner 1"]
+        );
+    }
+
+    #[test]
+    fn return_from_deferred() {
+        let mut scopes: Scopes<&str> = Scopes::new();
+        scopes.enter();
+        scopes.begin_deferred();
+
+        assert!(scopes.unwind(0, "return", span()).is_err());
+
+        scopes.enter();
+        assert!(scopes.unwind(1, "break", span()).is_ok());
+    }
+}
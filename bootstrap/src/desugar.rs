@@ -0,0 +1,471 @@
+use crate::error::Error;
+use crate::iterate::{self, Protocols};
+use crate::model::base::*;
+use crate::model::desugared::{Desugared, WithoutFor, WithoutIf};
+use crate::model::fold::{walk_expression, Folder};
+use crate::model::resolved::{LocalId, Name, Resolution, ResolvedPath};
+use crate::model::ttg::TreeData;
+use crate::model::typed::{type_of, Typed};
+use crate::model::types::Type;
+use crate::model::visit::{self, NameContext, PathContext, Visitor};
+use crate::span::{Span, Spanned};
+
+/// Replaces `for` loops, `if` expressions and `if ... is` expressions with the constructs they
+/// stand for, one pass at a time:
+///
+/// ```text
+/// for name in iterable { body }   -->  { <setup> while <condition> { <advance> { body } } }
+/// if condition { then } else ...  -->  if condition is true { then } else ...
+/// if scrutinee is pattern { then } else otherwise
+///                                 -->  when scrutinee { pattern => { then }, _ => otherwise }
+/// ```
+///
+/// where the `for` loop follows the protocol `iterate::Protocols` chooses for its iterable, and
+/// a missing `else` becomes `()`. This runs after type checking, so every synthesized node is
+/// given its type, and the hidden variables of `for` loops are numbered after every local of
+/// the geode. Each synthesized node takes the span of the node it replaces, so diagnostics
+/// about it still point at what was written.
+pub fn desugar(geode: Geode<Typed>) -> Result<Geode<Desugared>, Vec<Error>> {
+    let geode = for_loops(geode)?;
+    let geode = if_expressions(geode);
+    Ok(if_is_expressions(geode))
+}
+
+pub fn for_loops(geode: Geode<Typed>) -> Result<Geode<WithoutFor>, Vec<Error>> {
+    let mut locals = Locals(0);
+    locals.visit_geode(&geode);
+
+    let mut loops = Loops {
+        next: locals.0,
+        protocols: Protocols::new(),
+        errors: Vec::new(),
+    };
+    let geode = loops.fold_geode(geode);
+
+    match loops.errors.is_empty() {
+        true => Ok(geode),
+        false => Err(loops.errors),
+    }
+}
+
+pub fn if_expressions(geode: Geode<WithoutFor>) -> Geode<WithoutIf> {
+    Conditions.fold_geode(geode)
+}
+
+pub fn if_is_expressions(geode: Geode<WithoutIf>) -> Geode<Desugared> {
+    Matches.fold_geode(geode)
+}
+
+/// Finds the number of locals in the geode, one past the highest `LocalId` declared in it.
+struct Locals(usize);
+
+impl Visitor<Typed> for Locals {
+    fn visit_name(&mut self, name: &Name, _: NameContext) {
+        if let Some(Resolution::Local(LocalId(local))) = name.resolution {
+            self.0 = self.0.max(local + 1);
+        }
+    }
+}
+
+struct Loops {
+    next: usize, // The next free local
+    protocols: Protocols,
+    errors: Vec<Error>,
+}
+
+impl Folder<Typed, WithoutFor> for Loops {
+    fn fold_name(&mut self, name: Name, _: NameContext) -> Name {
+        name
+    }
+
+    fn fold_path(&mut self, path: ResolvedPath, _: PathContext) -> ResolvedPath {
+        path
+    }
+
+    fn fold_type(&mut self, ty: Type) -> Type {
+        ty
+    }
+
+    fn fold_expression(&mut self, expression: Expression<Typed>) -> Expression<WithoutFor> {
+        let span = expression.span;
+
+        match expression.item {
+            ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::For(
+                label,
+                name,
+                iterable,
+                body,
+                ty,
+            )) => {
+                let next = &mut self.next;
+                let mut fresh = || {
+                    *next += 1;
+                    LocalId(*next - 1)
+                };
+
+                match self.protocols.protocol(&iterable, &mut fresh) {
+                    // The body may hold loops of its own, which are replaced as the result is
+                    // folded.
+                    Ok(protocol) => {
+                        let desugared = iterate::desugar(
+                            protocol.as_ref(),
+                            label,
+                            name,
+                            *iterable,
+                            body,
+                            ty,
+                            span,
+                        );
+                        self.fold_expression(desugared)
+                    }
+                    Err(error) => {
+                        self.errors.push(error);
+                        let body = self.fold_block(body);
+                        with_block(ExpressionWithBlock::Block(body, ty), span)
+                    }
+                }
+            }
+            item => walk_expression(self, Spanned::new(item, span)),
+        }
+    }
+}
+
+struct Conditions;
+
+impl Folder<WithoutFor, WithoutIf> for Conditions {
+    fn fold_name(&mut self, name: Name, _: NameContext) -> Name {
+        name
+    }
+
+    fn fold_path(&mut self, path: ResolvedPath, _: PathContext) -> ResolvedPath {
+        path
+    }
+
+    fn fold_type(&mut self, ty: Type) -> Type {
+        ty
+    }
+
+    fn fold_expression(&mut self, expression: Expression<WithoutFor>) -> Expression<WithoutIf> {
+        let span = expression.span;
+
+        match expression.item {
+            ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::If(
+                condition,
+                then,
+                otherwise,
+                ty,
+            )) => {
+                let pattern = Spanned::new(
+                    PatternKind::Literal(Constant::Bool(true), Type::Bool),
+                    condition.span,
+                );
+                let then = with_block(ExpressionWithBlock::Block(then, ty.clone()), span);
+                let if_is =
+                    ExpressionWithBlock::IfIs(condition, pattern, Box::new(then), otherwise, ty);
+
+                self.fold_expression(with_block(if_is, span))
+            }
+            item => walk_expression(self, Spanned::new(item, span)),
+        }
+    }
+}
+
+struct Matches;
+
+impl Folder<WithoutIf, Desugared> for Matches {
+    fn fold_name(&mut self, name: Name, _: NameContext) -> Name {
+        name
+    }
+
+    fn fold_path(&mut self, path: ResolvedPath, _: PathContext) -> ResolvedPath {
+        path
+    }
+
+    fn fold_type(&mut self, ty: Type) -> Type {
+        ty
+    }
+
+    fn fold_expression(&mut self, expression: Expression<WithoutIf>) -> Expression<Desugared> {
+        let span = expression.span;
+
+        match expression.item {
+            ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::IfIs(
+                scrutinee,
+                pattern,
+                then,
+                otherwise,
+                ty,
+            )) => {
+                let scrutinee_ty = type_of_without_if(&scrutinee);
+                let otherwise = otherwise.map_or_else(|| unit(span), |otherwise| *otherwise);
+                let arms = vec![
+                    Arm {
+                        pattern,
+                        guard: None,
+                        expression: *then,
+                    },
+                    Arm {
+                        pattern: Spanned::new(PatternKind::Wildcard(scrutinee_ty), otherwise.span),
+                        guard: None,
+                        expression: otherwise,
+                    },
+                ];
+
+                self.fold_expression(with_block(
+                    ExpressionWithBlock::Case(scrutinee, arms, ty),
+                    span,
+                ))
+            }
+            item => walk_expression(self, Spanned::new(item, span)),
+        }
+    }
+}
+
+/// The type of an expression of the phase without `if`, whose remaining constructors all
+/// carry their type the way they do in `Typed`.
+fn type_of_without_if(expression: &Expression<WithoutIf>) -> Type {
+    use ExpressionWithBlock::*;
+    use ExpressionWithoutBlock::*;
+
+    match &expression.item {
+        ExpressionKind::ExpressionWithBlock(with_block) => match with_block {
+            Case(_, _, ty) | IfIs(_, _, _, _, ty) | While(_, _, _, ty) | Loop(_, _, ty) => {
+                ty.clone()
+            }
+            Block(_, ty) => ty.clone(),
+            For(_, _, _, _, removed) | If(_, _, _, removed) => match *removed {},
+        },
+        ExpressionKind::ExpressionWithoutBlock(without_block) => match without_block {
+            ErrorPropagation(_, ty)
+            | Return(_, ty)
+            | Break(_, _, ty)
+            | Continue(_, ty)
+            | Grouped(_, ty)
+            | Path(_, ty)
+            | Literal(_, ty)
+            | Array(_, ty)
+            | Repeat(_, _, ty)
+            | Range(_, _, _, ty)
+            | Prefix(_, _, ty)
+            | Binary(_, _, _, ty)
+            | Call(_, _, ty)
+            | Field(_, _, ty)
+            | Index(_, _, ty)
+            | Asm(_, ty)
+            | Other(ty) => ty.clone(),
+            MethodCall(_, _, _, removed) => match *removed {},
+        },
+    }
+}
+
+fn with_block<P: TreeData<P>>(kind: ExpressionWithBlock<P>, span: Span) -> Expression<P> {
+    Spanned::new(ExpressionKind::ExpressionWithBlock(kind), span)
+}
+
+fn unit(span: Span) -> Expression<WithoutIf> {
+    Spanned::new(
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+            Literal::Unit,
+            Type::Unit,
+        )),
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::desugar::*;
+    use crate::model::ttg::Removed;
+    use crate::model::visit::walk_with_block;
+    use crate::testing;
+    use crate::typeck;
+
+    /// Records the spans and types of the branching expressions left after desugaring.
+    #[derive(Default)]
+    struct Branches {
+        cases: Vec<(Span, Vec<Span>)>,
+        whiles: Vec<Span>,
+        units: Vec<Span>,
+        wildcards: Vec<Type>,
+        lets: Vec<(String, Type)>,
+    }
+
+    impl Visitor<Desugared> for Branches {
+        fn visit_expression(&mut self, expression: &Expression<Desugared>) {
+            match &expression.item {
+                ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Case(_, arms, _)) => {
+                    let patterns = arms.iter().map(|arm| arm.pattern.span).collect();
+                    self.cases.push((expression.span, patterns));
+                }
+                ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::While(..)) => {
+                    self.whiles.push(expression.span)
+                }
+                ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+                    Literal::Unit,
+                    _,
+                )) => self.units.push(expression.span),
+                _ => {}
+            }
+
+            visit::walk_expression(self, expression)
+        }
+
+        fn visit_with_block(&mut self, expression: &ExpressionWithBlock<Desugared>) {
+            let removed: Option<&Removed> = match expression {
+                ExpressionWithBlock::For(_, _, _, _, ext) => Some(ext),
+                ExpressionWithBlock::If(_, _, _, ext) => Some(ext),
+                ExpressionWithBlock::IfIs(_, _, _, _, ext) => Some(ext),
+                _ => None,
+            };
+
+            // The phase has no such expressions, which the compiler knows from their extension.
+            if let Some(removed) = removed {
+                match *removed {}
+            }
+
+            walk_with_block(self, expression)
+        }
+
+        fn visit_pattern(&mut self, pattern: &Pattern<Desugared>) {
+            if let PatternKind::Wildcard(ty) = &pattern.item {
+                self.wildcards.push(ty.clone());
+            }
+
+            visit::walk_pattern(self, pattern)
+        }
+
+        fn visit_statement(&mut self, statement: &Statement<Desugared>) {
+            if let Statement::Let(Let {
+                name, ty: Some(ty), ..
+            }) = statement
+            {
+                self.lets.push((name.ident.item.clone(), ty.clone()));
+            }
+
+            visit::walk_statement(self, statement)
+        }
+    }
+
+    fn branches(source: &str) -> Branches {
+        let (geode, _, _) = testing::typed(source);
+        let mut branches = Branches::default();
+        branches.visit_geode(&desugar(geode).unwrap());
+        branches
+    }
+
+    #[test]
+    fn if_expressions() {
+        let source = "fn f(c: bool, d: u8) { if c { } else if d is 5 { } }";
+        let outer = Span::new(23, 50);
+        let inner = Span::new(37, 50);
+
+        let branches = branches(source);
+
+        // The condition becomes the pattern `True` in its place, and the `else` branch the
+        // wildcard arm.
+        assert_eq!(
+            branches.cases,
+            vec![
+                (outer, vec![Span::new(26, 27), inner]),
+                (inner, vec![Span::new(45, 46), inner]),
+            ]
+        );
+        assert_eq!(branches.units, vec![inner]);
+        assert_eq!(branches.wildcards, vec![Type::Bool, Type::U8]);
+    }
+
+    #[test]
+    fn for_loops() {
+        let source = "fn f(n: u16) { for i in 0..n { if i == 2 { } } }";
+
+        let branches = branches(source);
+
+        assert_eq!(branches.whiles, vec![Span::new(15, 46)]);
+        assert_eq!(branches.cases.len(), 1);
+        assert_eq!(branches.cases[0].0, Span::new(31, 44));
+        // The range is held in a hidden variable, and the rest of the hidden variables and the
+        // loop variable take the type of its bounds.
+        assert_eq!(
+            branches.lets,
+            vec![
+                (
+                    String::from("for#range"),
+                    Type::Range(RangeLimits::HalfOpen, Box::new(Type::U16))
+                ),
+                (String::from("for#next"), Type::U16),
+                (String::from("for#end"), Type::U16),
+                (String::from("i"), Type::U16),
+            ]
+        );
+    }
+
+    #[test]
+    fn fresh_locals() {
+        // Two loops in one function, after a local: the hidden variables of each are numbered
+        // after every local of the geode, including the loop variable of the later loop.
+        let source = "fn f(a: [u8; 4]) { for x in a { } for y in 0..2 { } }";
+        let (geode, _, _) = testing::typed(source);
+        let geode = desugar(geode).unwrap();
+
+        #[derive(Default)]
+        struct Declared(Vec<usize>);
+
+        impl Visitor<Desugared> for Declared {
+            fn visit_name(&mut self, name: &Name, _: NameContext) {
+                if let Some(Resolution::Local(LocalId(local))) = name.resolution {
+                    self.0.push(local);
+                }
+            }
+        }
+
+        let mut declared = Declared::default();
+        declared.visit_geode(&geode);
+        let mut sorted = declared.0.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        // `a`, `x`, `y`, then three hidden variables for the array and three for the range.
+        assert_eq!(sorted, (0..9).collect::<Vec<_>>());
+        assert_eq!(declared.0.len(), 9);
+    }
+
+    #[test]
+    fn stored_ranges() {
+        let source = "fn f(n: u16) { let r = 0..=n; for i in r { } }";
+
+        let branches = branches(source);
+
+        assert_eq!(branches.whiles.len(), 1);
+        assert_eq!(
+            branches.lets[..2],
+            [
+                (
+                    String::from("r"),
+                    Type::Range(RangeLimits::Closed, Box::new(Type::U16))
+                ),
+                (
+                    String::from("for#range"),
+                    Type::Range(RangeLimits::Closed, Box::new(Type::U16))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unbounded_for_loop() {
+        let source = "fn f() { for i in 0.. { } }";
+        let (geode, definitions) = testing::resolve(source);
+
+        // The body is not taken for the end of the range, which cannot be left out here.
+        let errors = typeck::check(geode, &definitions, &Type::I32)
+            .err()
+            .unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "Ranges without both a start and an end can only be indices"
+        );
+        assert_eq!(errors[0].span, Span::new(18, 21));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::model::*;
+use crate::model::ast::Syntax;
+use crate::model::base::*;
 
 use crate::diagnostic::graphviz_model::*;
 
@@ -9,54 +10,56 @@ pub struct GraphvizRenderer {
 }
 
 impl GraphvizRenderer {
-    pub fn render(geode: &Geode) -> String {
+    pub fn render(geode: &Geode<Syntax>) -> String {
         GraphvizRenderer {
             lines: Vec::new(),
             counter: 0,
             digraph: Digraph::new("OpalGeode".to_owned(), Vec::new(), Vec::new()),
         }
-        .render_geode(geode.clone())
+        .render_geode(geode)
     }
 
-    fn render_geode(mut self, geode: Geode) -> String {
+    fn render_geode(mut self, geode: &Geode<Syntax>) -> String {
         self.digraph.nodes.push(Node::new(
             "geode".to_owned(),
             vec![
-                NodeAttribute::Label(geode_label(&geode.name)),
+                NodeAttribute::Label(geode_label(&geode.name.item)),
                 NodeAttribute::Shape("Mrecord".to_owned()),
                 NodeAttribute::FillColor("lightpink".to_owned()),
             ],
         ));
 
-        for item in geode.items {
-            match item.item {
+        for item in geode.modules.iter().flat_map(|module| &module.items) {
+            match &item.kind {
                 ItemKind::Mod(mod_item) => self.render_mod(mod_item),
-                ItemKind::Use(use_item) => self.render_use(use_item),
+                ItemKind::Use(use_tree) => self.render_use(use_tree),
                 ItemKind::Function(function_item) => self.render_function(function_item),
                 ItemKind::TypeAlias(type_alias_item) => self.render_type_alias(type_alias_item),
                 ItemKind::Struct(struct_item) => self.render_struct(struct_item),
                 ItemKind::Enum(enum_item) => self.render_enum(enum_item),
                 ItemKind::Const(const_item) => self.render_const(const_item),
                 ItemKind::Static(static_item) => self.render_static(static_item),
+                ItemKind::Impl(impl_item) => self.render_impl(impl_item),
+                ItemKind::Trait(trait_item) => self.render_trait(trait_item),
             }
         }
 
         self.digraph.generate()
     }
 
-    fn render_mod(&mut self, item: ModItem) {
+    fn render_mod(&mut self, item: &ModItem<Syntax>) {
         todo!()
     }
 
-    fn render_use(&mut self, item: UseItem) {
+    fn render_use(&mut self, item: &UseTree<Syntax>) {
         todo!()
     }
 
-    fn render_function(&mut self, item: FunctionItem) {
+    fn render_function(&mut self, item: &FunctionItem<Syntax>) {
         todo!()
     }
 
-    fn render_type_alias(&mut self, item: TypeAliasItem) {
+    fn render_type_alias(&mut self, item: &TypeAliasItem<Syntax>) {
         self.digraph.nodes.push(Node::new(
             format!("type_{}", self.counter),
             vec![
@@ -67,7 +70,7 @@ impl GraphvizRenderer {
         ))
     }
 
-    fn render_struct(&mut self, item: StructItem) {
+    fn render_struct(&mut self, item: &StructItem<Syntax>) {
         self.digraph.nodes.push(Node::new(
             format!("struct_{}", self.counter),
             vec![
@@ -80,7 +83,7 @@ impl GraphvizRenderer {
         self.counter += 1;
     }
 
-    fn render_enum(&mut self, item: EnumItem) {
+    fn render_enum(&mut self, item: &EnumItem<Syntax>) {
         self.digraph.nodes.push(Node::new(
             format!("enum_{}", self.counter),
             vec![
@@ -97,11 +100,19 @@ impl GraphvizRenderer {
         self.counter += 1;
     }
 
-    fn render_const(&mut self, item: ConstItem) {
+    fn render_const(&mut self, item: &ConstItem<Syntax>) {
         todo!()
     }
 
-    fn render_static(&mut self, item: StaticItem) {
+    fn render_static(&mut self, item: &StaticItem<Syntax>) {
+        todo!()
+    }
+
+    fn render_impl(&mut self, item: &ImplItem<Syntax>) {
+        todo!()
+    }
+
+    fn render_trait(&mut self, item: &TraitItem<Syntax>) {
         todo!()
     }
 }
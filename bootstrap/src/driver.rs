@@ -1,3 +1,299 @@
 mod argparse;
 
+use std::path::Path;
+
+use crate::attribute;
+use crate::borrow;
+use crate::desugar;
+use crate::error::Error;
+use crate::exhaustive;
+use crate::initialize;
+use crate::layout::{self, Layouts};
+use crate::lint::{self, Report};
+use crate::load::{self, FileSystem};
+use crate::lower;
+use crate::model::base::Geode;
+use crate::model::def::Definitions;
+use crate::model::desugared::Desugared;
+use crate::model::ir;
+use crate::resolve;
+use crate::source::SourceMap;
+use crate::typeck::{self, Tables};
+
 pub use argparse::*;
+
+/// The root module of the project being built, relative to the project's directory.
+pub const ROOT: &str = "src/main.opal";
+
+/// A geode that has passed every check, desugared and ready to be lowered.
+pub struct Checked {
+    pub geode: Geode<Desugared>,
+    pub definitions: Definitions,
+    pub tables: Tables,
+    pub layouts: Layouts,
+}
+
+/// Loads the geode rooted at `root` and runs every pass up to lowering over it. The passes
+/// after type checking assume a well-typed tree, so each stage only runs once the stages
+/// before it have reported no errors. The checks of a stage are independent of each other, so
+/// they all run and report together.
+///
+/// Lints run alongside the checks of their stage at the levels in `options`. Those reported as
+/// warnings are added to `warnings` whether or not the build fails, and denied ones are errors.
+pub fn check(
+    root: &Path,
+    options: &BuildOptions,
+    fs: &impl FileSystem,
+    sources: &mut SourceMap,
+    warnings: &mut Vec<Error>,
+) -> Result<Checked, Vec<Error>> {
+    let name = root
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+
+    let geode = load::load(name, root, fs, sources)?;
+    let (geode, definitions) = resolve::resolve(geode)?;
+    let mut errors = attribute::check(&geode);
+    let lints = lint::check(&geode, &definitions, &options.lints);
+    report(lints, warnings, &mut errors);
+
+    let (geode, tables) = match typeck::check(geode, &definitions, &options.default_integer) {
+        Ok(checked) if errors.is_empty() => checked,
+        Ok(_) => return Err(errors),
+        Err(found) => {
+            errors.extend(found);
+            return Err(errors);
+        }
+    };
+
+    let lints = lint::check_typed(&geode, &options.lints);
+    report(lints, warnings, &mut errors);
+
+    let (found, unreachable) = exhaustive::check(&geode, &tables, &definitions, &options.lints);
+    errors.extend(found);
+    report(unreachable, warnings, &mut errors);
+    errors.extend(initialize::check(&geode));
+    errors.extend(borrow::check(&geode, &tables));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let layouts = layout::compute(&geode, options.data_model)?;
+    let geode = desugar::desugar(geode)?;
+
+    Ok(Checked {
+        geode,
+        definitions,
+        tables,
+        layouts,
+    })
+}
+
+/// Moves the diagnostics of lints to the warnings or errors of the build.
+fn report(lints: Report, warnings: &mut Vec<Error>, errors: &mut Vec<Error>) {
+    warnings.extend(lints.warnings.into_iter().map(|(_, warning)| warning));
+    errors.extend(lints.errors.into_iter().map(|(_, error)| error));
+}
+
+/// Checks the geode rooted at `root`, then lowers every function reachable from its `main`
+/// function to IR.
+pub fn build(
+    root: &Path,
+    options: &BuildOptions,
+    fs: &impl FileSystem,
+    sources: &mut SourceMap,
+    warnings: &mut Vec<Error>,
+) -> Result<Vec<ir::Function>, Vec<Error>> {
+    let mut checked = check(root, options, fs, sources, warnings)?;
+
+    lower::lower(
+        &checked.geode,
+        &checked.definitions,
+        &checked.tables,
+        &mut checked.layouts,
+        options.bounds_checks,
+    )
+}
+
+/// Formats a diagnostic for the terminal, as `path:line:column: severity: message`, followed
+/// by its details and notes.
+pub fn render(error: &Error, severity: &str, sources: &SourceMap) -> String {
+    let location = |span| match sources.locate(span) {
+        Some((file, line, column)) => format!("{}:{line}:{column}", file.path.display()),
+        None => String::from("<unknown>"),
+    };
+
+    let mut rendered = format!("{}: {severity}: {}", location(error.span), error.message);
+
+    if let Some(details) = &error.details {
+        rendered.push_str(&format!("\n    {details}"));
+    }
+    for note in &error.notes {
+        rendered.push_str(&format!("\n{}: note: {}", location(note.span), note.item));
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::*;
+    use crate::model::base::ExpressionWithBlock;
+    use crate::model::visit::{self, Visitor};
+    use crate::span::Span;
+    use crate::testing::Single;
+
+    fn checked(source: &'static str) -> Result<Checked, Vec<Error>> {
+        checked_with(source, &BuildOptions::default(), &mut Vec::new())
+    }
+
+    fn checked_with(
+        source: &'static str,
+        options: &BuildOptions,
+        warnings: &mut Vec<Error>,
+    ) -> Result<Checked, Vec<Error>> {
+        check(
+            Path::new("main.opal"),
+            options,
+            &Single(source),
+            &mut SourceMap::new(),
+            warnings,
+        )
+    }
+
+    fn options(args: &[&str]) -> BuildOptions {
+        let args = ["opal", "build"].iter().chain(args);
+        match parse_args(args.map(|arg| arg.to_string()).collect()) {
+            Some(Subcommand::Build(options)) => options,
+            _ => panic!("Expected build options"),
+        }
+    }
+
+    fn messages(errors: Vec<Error>) -> Vec<String> {
+        errors.into_iter().map(|error| error.message).collect()
+    }
+
+    #[derive(Default)]
+    struct Loops(usize);
+
+    impl Visitor<Desugared> for Loops {
+        fn visit_with_block(&mut self, expression: &ExpressionWithBlock<Desugared>) {
+            if let ExpressionWithBlock::While(..) = expression {
+                self.0 += 1;
+            }
+
+            visit::walk_with_block(self, expression)
+        }
+    }
+
+    #[test]
+    fn desugared_output() {
+        let source = "fn main() { let mut sum: u16 = 0; for i in 0..10 { sum += i; } }";
+
+        let checked = checked(source).ok().unwrap();
+
+        let mut loops = Loops::default();
+        loops.visit_geode(&checked.geode);
+        assert_eq!(loops.0, 1);
+    }
+
+    #[test]
+    fn stops_after_type_errors() {
+        // The uninitialized read is only found by a later stage, which does not run.
+        let source = "fn main() { let x: u8; let y: bool = x + 1; }";
+
+        let errors = checked(source).err().unwrap();
+
+        assert_eq!(messages(errors), vec!["Mismatched types"]);
+    }
+
+    #[test]
+    fn checks_after_type_checking() {
+        let source = "fn main() { let x: u8; let y = x; when y { 0 => {} } }";
+
+        let errors = checked(source).err().unwrap();
+
+        // Both checks run, though either error alone stops the build.
+        assert_eq!(
+            messages(errors),
+            vec![
+                "Non-exhaustive patterns",
+                "Use of possibly uninitialized variable `x`"
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_levels() {
+        let source = "fn main() { let x: u8 = 1; let y: u8 = 2; when y { _ => {}, 0 => {} } }";
+
+        let mut warnings = Vec::new();
+        assert!(checked_with(source, &options(&[]), &mut warnings).is_ok());
+        assert_eq!(
+            messages(warnings),
+            vec!["Unused variable `x`", "Unreachable pattern"]
+        );
+
+        let mut warnings = Vec::new();
+        let denied = options(&[
+            "--deny",
+            "unreachable_patterns",
+            "--allow",
+            "unused_variables",
+        ]);
+        let errors = checked_with(source, &denied, &mut warnings).err().unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(messages(errors), vec!["Unreachable pattern"]);
+    }
+
+    #[test]
+    fn default_integer() {
+        let source = "fn main() { let x = 300; }";
+
+        assert!(checked_with(source, &options(&[]), &mut Vec::new()).is_ok());
+
+        let narrow = options(&["--default-integer", "u8"]);
+        let errors = checked_with(source, &narrow, &mut Vec::new())
+            .err()
+            .unwrap();
+        assert_eq!(
+            messages(errors),
+            vec!["Integer literal `300` is out of range for `u8`"]
+        );
+    }
+
+    #[test]
+    fn builds_from_main() {
+        let mut sources = SourceMap::new();
+        let errors = build(
+            Path::new("main.opal"),
+            &BuildOptions::default(),
+            &Single("fn start() {}"),
+            &mut sources,
+            &mut Vec::new(),
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            messages(errors),
+            vec!["The root module has no `main` function"]
+        );
+    }
+
+    #[test]
+    fn rendered_location() {
+        let mut sources = SourceMap::new();
+        sources.add("main.opal".into(), String::from("fn main() {\n    x\n}"));
+        let error = Error::with_details(
+            Span::new(16, 17),
+            String::from("Unresolved name `x`"),
+            String::from("No local, function or constant is named `x`"),
+        );
+
+        assert_eq!(
+            render(&error, "error", &sources),
+            "main.opal:2:5: error: Unresolved name `x`\n    No local, function or constant is named `x`"
+        );
+    }
+}
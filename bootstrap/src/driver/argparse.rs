@@ -1,14 +1,45 @@
 /*
 opal new :proj-name:
-opal build
-opal run :args:
+opal build :options:
+opal run :options: :args:
+
+options:
+    --no-bounds-checks      Omit runtime bounds checks on indexing
+    --default-integer :ty:  Type of integer literals that nothing constrains, `i32` by default
+    --pointer-width :bits:  Width of the target's pointers, either 16 or 32, which is the default
+    --allow :lint:          Silence the lint, which may be repeated for several lints
+    --warn :lint:           Report the lint as a warning
+    --deny :lint:           Report the lint as an error, failing the build
 */
 
+use crate::layout::DataModel;
+use crate::lint::{Level, Levels, Lint};
+use crate::model::types::Type;
+
 #[derive(Debug)]
 pub enum Subcommand {
-    New(String),      // Create a new Opal project with the given name
-    Build,            // Build the project without running it
-    Run(Vec<String>), // Build and run the project with the provided args
+    New(String),                    // Create a new Opal project with the given name
+    Build(BuildOptions),            // Build the project without running it
+    Run(BuildOptions, Vec<String>), // Build and run the project with the provided args
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub bounds_checks: bool,
+    pub default_integer: Type,
+    pub data_model: DataModel,
+    pub lints: Levels,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            bounds_checks: true,
+            default_integer: Type::I32,
+            data_model: DataModel::BITS_32,
+            lints: Levels::default(),
+        }
+    }
 }
 
 pub fn parse_args(args: Vec<String>) -> Option<Subcommand> {
@@ -57,10 +88,70 @@ pub fn parse_subcommand_new(program_name: String, args: &mut Vec<String>) -> Opt
     }
 }
 
-pub fn parse_subcommand_build(program_name: String, args: &mut [String]) -> Option<Subcommand> {
-    Some(Subcommand::Build)
+pub fn parse_subcommand_build(program_name: String, args: &mut Vec<String>) -> Option<Subcommand> {
+    parse_build_options(args).map(Subcommand::Build)
 }
 
-pub fn parse_subcommand_run(program_name: String, args: &mut [String]) -> Option<Subcommand> {
-    Some(Subcommand::Run(args.to_vec()))
+pub fn parse_subcommand_run(program_name: String, args: &mut Vec<String>) -> Option<Subcommand> {
+    let options = parse_build_options(args)?;
+
+    // Everything following the options is passed to the program being run.
+    let mut program_args = std::mem::take(args);
+    program_args.reverse();
+
+    Some(Subcommand::Run(options, program_args))
+}
+
+fn parse_build_options(args: &mut Vec<String>) -> Option<BuildOptions> {
+    let mut options = BuildOptions::default();
+
+    while let Some(arg) = args.last().filter(|arg| arg.starts_with("--")) {
+        match arg.as_str() {
+            "--no-bounds-checks" => options.bounds_checks = false,
+            "--default-integer" => {
+                args.pop();
+                options.default_integer = match args.last().map(String::as_str) {
+                    Some("u8") => Type::U8,
+                    Some("i8") => Type::I8,
+                    Some("u16") => Type::U16,
+                    Some("i16") => Type::I16,
+                    Some("u32") => Type::U32,
+                    Some("i32") => Type::I32,
+                    _ => {
+                        eprintln!("Expected an integer type following `--default-integer`");
+                        return None;
+                    }
+                };
+            }
+            "--pointer-width" => {
+                args.pop();
+                options.data_model = match args.last().map(String::as_str) {
+                    Some("16") => DataModel::BITS_16,
+                    Some("32") => DataModel::BITS_32,
+                    _ => {
+                        eprintln!("Expected `16` or `32` following `--pointer-width`");
+                        return None;
+                    }
+                };
+            }
+            "--allow" | "--warn" | "--deny" => {
+                let level = Level::named(&arg[2..]).expect("Each option names a level");
+                let option = args.pop().unwrap();
+                match args.last().and_then(|name| Lint::named(name)) {
+                    Some(lint) => options.lints.set(lint, level),
+                    None => {
+                        eprintln!("Expected the name of a lint following `{option}`");
+                        return None;
+                    }
+                }
+            }
+            unrecognized => {
+                eprintln!("Unrecognized option `{unrecognized}`");
+                return None;
+            }
+        }
+        args.pop();
+    }
+
+    Some(options)
 }
@@ -1,10 +1,11 @@
-use crate::span::Span;
+use crate::span::{Span, Spanned};
 
 #[derive(Debug)]
 pub struct Error {
     pub span: Span,
     pub message: String,
     pub details: Option<String>,
+    pub notes: Vec<Spanned<String>>,
 }
 
 impl Error {
@@ -13,6 +14,7 @@ impl Error {
             span,
             message,
             details: None,
+            notes: Vec::new(),
         }
     }
 
@@ -21,6 +23,13 @@ impl Error {
             span,
             message,
             details: Some(details),
+            notes: Vec::new(),
         }
     }
+
+    /// Attaches a secondary location to the error, such as where a conflicting definition is.
+    pub fn with_note(mut self, span: Span, note: String) -> Self {
+        self.notes.push(Spanned::new(note, span));
+        self
+    }
 }
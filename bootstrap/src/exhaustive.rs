@@ -0,0 +1,867 @@
+use crate::attribute::Target;
+use crate::error::Error;
+use crate::lint::{Levels, Lint, Report, Scopes};
+use crate::model::base::*;
+use crate::model::def::{DefId, DefKind, Definitions};
+use crate::model::resolved::Resolution;
+use crate::model::typed::*;
+use crate::model::types::Type;
+use crate::model::visit::{walk_item, walk_with_block, Visitor};
+use crate::span::{Span, Spanned};
+use crate::typeck::{Tables, VariantShape};
+
+/// How many uncovered patterns a report lists before summarizing the rest.
+const MAX_WITNESSES: usize = 3;
+
+/// Checks that the arms of every `when` expression cover every value of the scrutinee, and
+/// that each arm matches some value no earlier arm does.
+///
+/// Both questions are answered by asking whether a pattern is useful with respect to a list of
+/// earlier patterns, that is, whether some value matches it but none of them, as described in
+/// "Warnings for pattern matching" (Maranget, 2007). An arm is unreachable if its pattern is not
+/// useful with respect to the arms before it, and the arms are exhaustive if a wildcard is not
+/// useful with respect to all of them. Arms with guards may fail to match even when their
+/// pattern does, so they are checked for reachability but do not cover anything.
+///
+/// Missing arms are errors, while unreachable arms are reported through the
+/// `unreachable_patterns` lint at the given levels.
+pub fn check(
+    geode: &Geode<Typed>,
+    tables: &Tables,
+    definitions: &Definitions,
+    levels: &Levels,
+) -> (Vec<Error>, Report) {
+    let mut checker = Checker {
+        tables,
+        definitions,
+        scopes: Scopes::new(levels),
+        errors: Vec::new(),
+        report: Report::default(),
+    };
+
+    checker.visit_geode(geode);
+    (checker.errors, checker.report)
+}
+
+/// A pattern with the details irrelevant to matching removed. Bindings are wildcards, and
+/// fields are listed in declaration order, with the fields a struct pattern leaves out as
+/// wildcards.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+/// A way to construct a value, which patterns either match or not.
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Variant(DefId),
+    Bool(bool),
+    Range(i64, i64), // An inclusive range of integers or of character codes
+    Single,          // The only way to construct a struct or `()`
+    Opaque,          // A constant, whose value is not known here, so it matches nothing else
+}
+
+impl Ctor {
+    /// Whether every value constructed by `other` is also constructed by `self`.
+    fn covers(&self, other: &Ctor) -> bool {
+        match (self, other) {
+            (Ctor::Range(lo, hi), Ctor::Range(other_lo, other_hi)) => {
+                lo <= other_lo && other_hi <= hi
+            }
+            (Ctor::Opaque, _) | (_, Ctor::Opaque) => false,
+            _ => self == other,
+        }
+    }
+}
+
+type Row = Vec<Pat>;
+
+struct Checker<'a> {
+    tables: &'a Tables,
+    definitions: &'a Definitions,
+    scopes: Scopes<'a>,
+    errors: Vec<Error>,
+    report: Report,
+}
+
+impl Checker<'_> {
+    fn case(&mut self, scrutinee: &Expression<Typed>, arms: &[Arm<Typed>]) {
+        let ty = type_of(scrutinee);
+        let mut matrix: Vec<Row> = Vec::new();
+
+        for arm in arms {
+            let row = vec![self.lower(&arm.pattern)];
+
+            if self
+                .witnesses(&matrix, &row, std::slice::from_ref(ty))
+                .is_empty()
+            {
+                let level = self.scopes.level(Lint::UnreachablePatterns);
+                self.report.emit(
+                    Lint::UnreachablePatterns,
+                    level,
+                    Error::with_details(
+                        arm.pattern.span,
+                        String::from("Unreachable pattern"),
+                        String::from("Every value it matches is matched by an earlier arm"),
+                    ),
+                );
+            }
+
+            if arm.guard.is_none() {
+                matrix.push(row);
+            }
+        }
+
+        let witnesses = self.witnesses(&matrix, &vec![Pat::Wild], std::slice::from_ref(ty));
+
+        if !witnesses.is_empty() {
+            let shown = witnesses
+                .iter()
+                .take(MAX_WITNESSES)
+                .map(|witness| format!("`{}`", self.display(&witness[0], ty)))
+                .collect::<Vec<_>>();
+
+            let details = match (witnesses.len(), shown.as_slice()) {
+                (1, [pattern]) => format!("Pattern {pattern} is not covered"),
+                (count, shown) if count > MAX_WITNESSES => format!(
+                    "Patterns {} and {} more are not covered",
+                    shown.join(", "),
+                    count - MAX_WITNESSES
+                ),
+                (_, [init @ .., last]) => {
+                    format!("Patterns {} and {last} are not covered", init.join(", "))
+                }
+                (_, []) => unreachable!("There is at least one witness"),
+            };
+
+            self.errors.push(Error::with_details(
+                scrutinee.span,
+                String::from("Non-exhaustive patterns"),
+                details,
+            ));
+        }
+    }
+
+    /// Removes the details of a pattern that matching does not depend on.
+    fn lower(&mut self, pattern: &Pattern<Typed>) -> Pat {
+        use PatternKind::*;
+
+        let ty = pattern_type(&pattern.item);
+
+        match &pattern.item {
+            Wildcard(_) | Binding(_, _, _) => Pat::Wild,
+            Literal(constant, _) => {
+                let ctor = match constant {
+                    Constant::Bool(value) => Ctor::Bool(*value),
+                    constant => {
+                        let value = value(constant);
+                        Ctor::Range(value, value)
+                    }
+                };
+
+                Pat::Ctor(ctor, Vec::new())
+            }
+            Range(start, end, limits, _) => {
+                let Some(domain) = domain(ty) else {
+                    return Pat::Ctor(Ctor::Opaque, Vec::new());
+                };
+
+                let lo = start.as_ref().map_or(domain[0].0, value);
+                let hi = match (end, limits) {
+                    (Some(end), RangeLimits::Closed) => value(end),
+                    (Some(end), RangeLimits::HalfOpen) => value(end) - 1,
+                    (None, _) => domain[domain.len() - 1].1,
+                };
+
+                if lo > hi {
+                    self.errors.push(Error::new(
+                        pattern.span,
+                        String::from("Range pattern matches no values"),
+                    ));
+                    return Pat::Ctor(Ctor::Opaque, Vec::new());
+                }
+
+                Pat::Ctor(Ctor::Range(lo, hi), Vec::new())
+            }
+            Path(path, _) => match path.resolution {
+                Resolution::Def(DefKind::Variant, variant) => {
+                    Pat::Ctor(Ctor::Variant(variant), Vec::new())
+                }
+                _ => Pat::Ctor(Ctor::Opaque, Vec::new()),
+            },
+            Tuple(path, elements, _) => match path.resolution {
+                Resolution::Def(DefKind::Variant, variant) => Pat::Ctor(
+                    Ctor::Variant(variant),
+                    elements.iter().map(|element| self.lower(element)).collect(),
+                ),
+                _ => Pat::Ctor(Ctor::Opaque, Vec::new()),
+            },
+            Struct(path, fields, _, _) => {
+                let (ctor, declared) = match path.resolution {
+                    Resolution::Def(DefKind::Variant, variant) => (
+                        Ctor::Variant(variant),
+                        self.field_names(&Ctor::Variant(variant), ty),
+                    ),
+                    _ => (Ctor::Single, self.field_names(&Ctor::Single, ty)),
+                };
+
+                let fields = declared
+                    .iter()
+                    .map(|name| {
+                        fields
+                            .iter()
+                            .find(|field| field.name.ident.item == *name)
+                            .map_or(Pat::Wild, |field| self.lower(&field.pattern))
+                    })
+                    .collect();
+
+                Pat::Ctor(ctor, fields)
+            }
+        }
+    }
+
+    /// The rows of values that match `row` but none of the rows of `matrix`, given as
+    /// patterns. There are none exactly when `row` is not useful with respect to `matrix`.
+    fn witnesses(&self, matrix: &[Row], row: &Row, types: &[Type]) -> Vec<Row> {
+        let Some((head, rest)) = row.split_first() else {
+            return match matrix.is_empty() {
+                true => vec![Vec::new()],
+                false => Vec::new(),
+            };
+        };
+
+        let ty = &types[0];
+        let heads = matrix.iter().map(|row| &row[0]).collect::<Vec<_>>();
+
+        match head {
+            // An opaque constant is treated as a wildcard, so that it is only reported as
+            // unreachable when anything in its place would be.
+            Pat::Wild | Pat::Ctor(Ctor::Opaque, _) => {
+                let Some(ctors) = self.constructors(ty, &heads) else {
+                    return self.missing(matrix, rest, types, Vec::new());
+                };
+
+                let missing = ctors
+                    .iter()
+                    .filter(|ctor| !heads.iter().any(|head| covers(head, ctor)))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                if missing.is_empty() {
+                    ctors
+                        .iter()
+                        .flat_map(|ctor| {
+                            let arity = self.field_types(ctor, ty).len();
+                            let row = vec![Pat::Wild; arity]
+                                .into_iter()
+                                .chain(rest.iter().cloned());
+                            self.specialized(matrix, ctor, row.collect(), types)
+                        })
+                        .collect()
+                } else {
+                    // Only the rows starting with wildcards can match the missing constructors.
+                    // When none of the constructors are mentioned, `_` describes them best.
+                    let missing = match heads.iter().any(|head| matches!(head, Pat::Ctor(..))) {
+                        true => merge(missing),
+                        false => Vec::new(),
+                    };
+
+                    self.missing(matrix, rest, types, missing)
+                }
+            }
+            Pat::Ctor(ctor, fields) => {
+                // A range is split into the parts that each row either covers wholly or not at
+                // all, which are then checked one at a time.
+                let parts = match ctor {
+                    Ctor::Range(lo, hi) => split(&[(*lo, *hi)], &heads),
+                    ctor => vec![ctor.clone()],
+                };
+
+                parts
+                    .iter()
+                    .flat_map(|part| {
+                        let row = fields.iter().chain(rest).cloned().collect();
+                        self.specialized(matrix, part, row, types)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The witnesses of `row` with respect to the rows of `matrix` that start with `ctor`,
+    /// where `row` holds the fields of `ctor` followed by the remaining columns.
+    fn specialized(&self, matrix: &[Row], ctor: &Ctor, row: Row, types: &[Type]) -> Vec<Row> {
+        let fields = self.field_types(ctor, &types[0]);
+        let arity = fields.len();
+
+        let matrix = matrix
+            .iter()
+            .filter_map(|matrix_row| match &matrix_row[0] {
+                Pat::Wild => Some(
+                    vec![Pat::Wild; arity]
+                        .into_iter()
+                        .chain(matrix_row[1..].iter().cloned())
+                        .collect::<Row>(),
+                ),
+                Pat::Ctor(head, head_fields) if head.covers(ctor) => Some(
+                    head_fields
+                        .iter()
+                        .chain(&matrix_row[1..])
+                        .cloned()
+                        .collect(),
+                ),
+                Pat::Ctor(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let types = fields
+            .into_iter()
+            .chain(types[1..].iter().cloned())
+            .collect::<Vec<_>>();
+
+        self.witnesses(&matrix, &row, &types)
+            .into_iter()
+            .map(|witness| {
+                let (fields, rest) = witness.split_at(arity);
+                let head = Pat::Ctor(ctor.clone(), fields.to_vec());
+                std::iter::once(head).chain(rest.iter().cloned()).collect()
+            })
+            .collect()
+    }
+
+    /// The witnesses of a row starting with a wildcard, when the rows of `matrix` starting
+    /// with constructors leave out the `missing` constructors, or every constructor when
+    /// `missing` is empty. Only the rows starting with wildcards can match those values.
+    fn missing(
+        &self,
+        matrix: &[Row],
+        rest: &[Pat],
+        types: &[Type],
+        missing: Vec<Ctor>,
+    ) -> Vec<Row> {
+        let defaults = matrix
+            .iter()
+            .filter(|row| matches!(row[0], Pat::Wild))
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<_>>();
+
+        let witnesses = self.witnesses(&defaults, &rest.to_vec(), &types[1..]);
+
+        let heads = match missing.is_empty() {
+            true => vec![Pat::Wild],
+            false => missing
+                .into_iter()
+                .map(|ctor| {
+                    let arity = self.field_types(&ctor, &types[0]).len();
+                    Pat::Ctor(ctor, vec![Pat::Wild; arity])
+                })
+                .collect(),
+        };
+
+        witnesses
+            .iter()
+            .flat_map(|witness| {
+                heads.iter().map(|head| {
+                    std::iter::once(head.clone())
+                        .chain(witness.iter().cloned())
+                        .collect()
+                })
+            })
+            .collect()
+    }
+
+    /// Every constructor of a type, with ranges split into the parts that the given patterns
+    /// cover wholly or not at all. `None` for types whose values only wildcards can match.
+    fn constructors(&self, ty: &Type, heads: &[&Pat]) -> Option<Vec<Ctor>> {
+        match ty {
+            Type::Bool => Some(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+            Type::Unit => Some(vec![Ctor::Single]),
+            Type::Never => Some(Vec::new()),
+            Type::Named(def, _, _) if self.tables.fields.contains_key(def) => {
+                Some(vec![Ctor::Single])
+            }
+            Type::Named(def, _, _) => Some(
+                self.tables
+                    .variants_of(*def)
+                    .into_iter()
+                    .map(Ctor::Variant)
+                    .collect(),
+            ),
+            ty => domain(ty).map(|domain| split(&domain, heads)),
+        }
+    }
+
+    /// The types of the fields of a value constructed by `ctor`, in declaration order.
+    fn field_types(&self, ctor: &Ctor, ty: &Type) -> Vec<Type> {
+        match (ctor, ty) {
+            (Ctor::Variant(variant), _) => {
+                let substitution = self.tables.substitution(ty);
+
+                match &self.tables.variants[variant].shape {
+                    VariantShape::Unit => Vec::new(),
+                    VariantShape::Tuple(types) => types
+                        .iter()
+                        .map(|ty| ty.substitute(&substitution))
+                        .collect(),
+                    VariantShape::Struct(fields) => fields
+                        .iter()
+                        .map(|field| field.ty.substitute(&substitution))
+                        .collect(),
+                }
+            }
+            (Ctor::Single, Type::Named(def, _, _)) => self.tables.fields[def]
+                .iter()
+                .map(|field| field.ty.clone())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The names of the fields of a value constructed by `ctor`, if it has named fields.
+    fn field_names(&self, ctor: &Ctor, ty: &Type) -> Vec<String> {
+        let fields = match (ctor, ty) {
+            (Ctor::Variant(variant), _) => match &self.tables.variants[variant].shape {
+                VariantShape::Struct(fields) => fields,
+                _ => return Vec::new(),
+            },
+            (Ctor::Single, Type::Named(def, _, _)) => &self.tables.fields[def],
+            _ => return Vec::new(),
+        };
+
+        fields.iter().map(|field| field.name.clone()).collect()
+    }
+
+    /// Writes a witness as the pattern it would be in source code.
+    fn display(&self, pattern: &Pat, ty: &Type) -> String {
+        let Pat::Ctor(ctor, fields) = pattern else {
+            return String::from("_");
+        };
+
+        let types = self.field_types(ctor, ty);
+        let fields = fields
+            .iter()
+            .zip(&types)
+            .map(|(field, ty)| self.display(field, ty))
+            .collect::<Vec<_>>();
+
+        let named = |name: String| {
+            let names = self.field_names(ctor, ty);
+
+            match (names.is_empty(), fields.is_empty()) {
+                (_, true) => name,
+                (true, false) => format!("{name}({})", fields.join(", ")),
+                (false, false) => {
+                    let fields = names
+                        .iter()
+                        .zip(&fields)
+                        .map(|(name, field)| format!("{name}: {field}"))
+                        .collect::<Vec<_>>();
+                    format!("{name} {{ {} }}", fields.join(", "))
+                }
+            }
+        };
+
+        match ctor {
+            Ctor::Variant(variant) => {
+                let enum_ = self.tables.variants[variant].enum_;
+                named(format!(
+                    "{}::{}",
+                    self.definitions.get(enum_).name,
+                    self.definitions.get(*variant).name
+                ))
+            }
+            Ctor::Single => match ty {
+                Type::Named(def, _, _) => named(self.definitions.get(*def).name.clone()),
+                _ => String::from("()"),
+            },
+            Ctor::Bool(true) => String::from("True"),
+            Ctor::Bool(false) => String::from("False"),
+            Ctor::Range(lo, hi) if lo == hi => bound(*lo, ty),
+            Ctor::Range(lo, hi) => format!("{}..={}", bound(*lo, ty), bound(*hi, ty)),
+            Ctor::Opaque => String::from("_"),
+        }
+    }
+}
+
+impl Visitor<Typed> for Checker<'_> {
+    fn visit_item(&mut self, item: &Item<Typed>) {
+        let depth = self.scopes.enter(&item.attributes, Target::of(&item.kind));
+        walk_item(self, item);
+        self.scopes.exit(depth);
+    }
+
+    fn visit_with_block(&mut self, expression: &ExpressionWithBlock<Typed>) {
+        if let ExpressionWithBlock::Case(scrutinee, arms, _) = expression {
+            self.case(scrutinee, arms);
+        }
+
+        walk_with_block(self, expression)
+    }
+}
+
+fn covers(head: &Pat, ctor: &Ctor) -> bool {
+    match head {
+        Pat::Wild => true,
+        Pat::Ctor(head, _) => head.covers(ctor),
+    }
+}
+
+/// The value of an integer or character constant.
+pub fn value(constant: &Constant) -> i64 {
+    match constant {
+        Constant::Integer {
+            negative: true,
+            value,
+        } => -(*value as i64),
+        Constant::Integer { value, .. } => *value as i64,
+        Constant::Character(character) => *character as i64,
+        Constant::Bool(value) => *value as i64,
+    }
+}
+
+/// The values of an integer or character type, as inclusive ranges in ascending order.
+pub fn domain(ty: &Type) -> Option<Vec<(i64, i64)>> {
+    let range = match ty {
+        Type::U8 => (0, u8::MAX as i64),
+        Type::I8 => (i8::MIN as i64, i8::MAX as i64),
+        Type::U16 => (0, u16::MAX as i64),
+        Type::I16 => (i16::MIN as i64, i16::MAX as i64),
+        Type::U32 => (0, u32::MAX as i64),
+        Type::I32 => (i32::MIN as i64, i32::MAX as i64),
+        // Surrogate code points are not characters.
+        Type::Char => return Some(vec![(0, 0xD7FF), (0xE000, char::MAX as i64)]),
+        _ => return None,
+    };
+
+    Some(vec![range])
+}
+
+/// Splits ranges into the parts that each of the given patterns either covers wholly or not
+/// at all, by cutting them wherever one of the patterns' ranges starts or ends.
+fn split(ranges: &[(i64, i64)], heads: &[&Pat]) -> Vec<Ctor> {
+    let mut cuts = heads
+        .iter()
+        .filter_map(|head| match head {
+            Pat::Ctor(Ctor::Range(lo, hi), _) => Some([*lo, hi + 1]),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    cuts.sort();
+    cuts.dedup();
+
+    let mut parts = Vec::new();
+
+    for &(lo, hi) in ranges {
+        let mut start = lo;
+
+        for &cut in cuts.iter().filter(|&&cut| lo < cut && cut <= hi) {
+            parts.push(Ctor::Range(start, cut - 1));
+            start = cut;
+        }
+
+        parts.push(Ctor::Range(start, hi));
+    }
+
+    parts
+}
+
+/// Joins adjacent ranges, so that uncovered values are reported as few patterns as possible.
+fn merge(ctors: Vec<Ctor>) -> Vec<Ctor> {
+    let mut merged: Vec<Ctor> = Vec::new();
+
+    for ctor in ctors {
+        match (merged.last_mut(), &ctor) {
+            (Some(Ctor::Range(_, hi)), Ctor::Range(lo, next_hi)) if *hi + 1 == *lo => {
+                *hi = *next_hi;
+            }
+            _ => merged.push(ctor),
+        }
+    }
+
+    merged
+}
+
+/// Writes a bound of a range of the given type, as it would be written in a pattern.
+fn bound(value: i64, ty: &Type) -> String {
+    match (ty, char::from_u32(value as u32)) {
+        (Type::Char, Some(character)) => format!("{character:?}"),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exhaustive::*;
+    use crate::lint::Level;
+    use crate::testing::{self, messages, span, span_in};
+
+    const SHAPE: &str = "enum Shape { Circle(u8), Square, Line { a: bool, b: u8 } }";
+
+    fn with_shape(function: &str) -> String {
+        format!("{SHAPE}\n{function}")
+    }
+
+    /// The unreachable arms followed by the errors.
+    fn check_patterns(source: &str) -> Vec<Error> {
+        let (geode, tables, definitions) = testing::typed(source);
+        let (errors, report) = check(&geode, &tables, &definitions, &Levels::default());
+
+        assert!(report.errors.is_empty());
+        report
+            .warnings
+            .into_iter()
+            .map(|(_, warning)| warning)
+            .chain(errors)
+            .collect()
+    }
+
+    /// The details of the errors, which say which patterns are not covered.
+    fn uncovered(source: &str) -> Vec<String> {
+        check_patterns(source)
+            .into_iter()
+            .filter_map(|error| error.details)
+            .collect()
+    }
+
+    #[test]
+    fn enums() {
+        let source =
+            with_shape("fn f(x: Shape) { when x { Shape::Circle(_) => {}, Shape::Square => {} } }");
+
+        assert_eq!(
+            messages(&check_patterns(&source)),
+            vec![(
+                "Non-exhaustive patterns",
+                Some("Pattern `Shape::Line { a: _, b: _ }` is not covered"),
+                span_in(&source, "when", "x")
+            )]
+        );
+
+        let source = with_shape(
+            "fn f(x: Shape) {
+                when x { Shape::Line { a: True, .. } => {}, _ => {}, Shape::Square => {} }
+            }",
+        );
+
+        assert_eq!(
+            messages(&check_patterns(&source)),
+            vec![(
+                "Unreachable pattern",
+                Some("Every value it matches is matched by an earlier arm"),
+                span_in(&source, "_ =>", "Shape::Square")
+            )]
+        );
+
+        let source = with_shape(
+            "fn f(x: Shape) {
+                when x { Shape::Circle(0..=9) => {}, Shape::Square => {}, Shape::Line { .. } => {} }
+            }",
+        );
+
+        assert_eq!(
+            uncovered(&source),
+            vec!["Pattern `Shape::Circle(10..=255)` is not covered"]
+        );
+    }
+
+    #[test]
+    fn integers_and_bools() {
+        let source = "fn f(x: u8) { when x { 0..=9 => {}, 20.. => {}, 15 => {} } }";
+
+        assert_eq!(
+            uncovered(source),
+            vec!["Patterns `10..=14` and `16..=19` are not covered"]
+        );
+
+        let source = "fn f(x: i8) { when x { 0.. => {} } }";
+
+        assert_eq!(
+            uncovered(source),
+            vec!["Pattern `-128..=-1` is not covered"]
+        );
+
+        let source = "fn f(x: i8) { when x { 0.. => {}, -128..=-1 => {} } }";
+
+        assert!(check_patterns(source).is_empty());
+
+        let source = "fn f(x: bool) { when x { True => {}, False => {}, _ => {} } }";
+
+        assert_eq!(
+            messages(&check_patterns(source)),
+            vec![(
+                "Unreachable pattern",
+                Some("Every value it matches is matched by an earlier arm"),
+                span(source, "_")
+            )]
+        );
+
+        let source = "fn f(x: u16) { when x { 5..=1 => {}, _ => {} } }";
+
+        assert_eq!(
+            messages(&check_patterns(source)),
+            vec![(
+                "Range pattern matches no values",
+                None,
+                span(source, "5..=1")
+            )]
+        );
+    }
+
+    #[test]
+    fn guards() {
+        let source = "fn f(x: bool) { when x { True if True => {}, False => {} } }";
+
+        assert_eq!(
+            messages(&check_patterns(source)),
+            vec![(
+                "Non-exhaustive patterns",
+                Some("Pattern `True` is not covered"),
+                span_in(source, "when", "x")
+            )]
+        );
+
+        let source = "fn f(x: char) { when x { _ => {}, 'a' if True => {} } }";
+        let errors = check_patterns(source);
+
+        assert_eq!(messages(&errors)[0].0, "Unreachable pattern");
+        assert_eq!(errors[0].span, span(source, "'a'"));
+
+        let source = "fn f(x: char) { when x { 'a'..='z' => {} } }";
+
+        assert_eq!(
+            uncovered(source),
+            vec!["Patterns `'\\0'..='`'`, `'{'..='\\u{d7ff}'` and `'\\u{e000}'..='\\u{10ffff}'` are not covered"]
+        );
+    }
+
+    #[test]
+    fn structs_and_generics() {
+        let source = "
+            enum Direction { Up, Down }
+            struct P { d: Direction, b: bool }
+            fn f(p: P) { when p { P { d: Direction::Up, b: True } => {}, P { b: False, .. } => {} } }
+        ";
+
+        assert_eq!(
+            uncovered(source),
+            vec!["Pattern `P { d: Direction::Down, b: True }` is not covered"]
+        );
+
+        // Fields are checked at the types the enum is instantiated with.
+        let source = "
+            enum Maybe[T] { No, Yes(T) }
+            fn f(x: Maybe[bool]) { when x { Maybe::No => {}, Maybe::Yes(True) => {} } }
+            fn g(x: Maybe[Maybe[u8]]) { when x { Maybe::No => {}, Maybe::Yes(Maybe::Yes(_)) => {} } }
+        ";
+
+        assert_eq!(
+            uncovered(source),
+            vec![
+                "Pattern `Maybe::Yes(False)` is not covered",
+                "Pattern `Maybe::Yes(Maybe::No)` is not covered"
+            ]
+        );
+    }
+
+    #[test]
+    fn witness_lists() {
+        let source = "
+            fn f(x: u8) { when x { 1 => {}, 3 => {} } }
+            fn g(x: u8) { when x { 1 => {}, 3 => {}, 5 => {}, 7 => {} } }
+            fn h(x: u8) { when x { 0..10 => {}, 11..=255 => {} } }
+            fn i(x: u8) { when x { 0..10 => {}, 10..=255 => {} } }
+        ";
+
+        assert_eq!(
+            uncovered(source),
+            vec![
+                "Patterns `0`, `2` and `4..=255` are not covered",
+                "Patterns `0`, `2`, `4` and 2 more are not covered",
+                "Pattern `10` is not covered",
+            ]
+        );
+    }
+
+    #[test]
+    fn bindings_and_constants() {
+        // A single name binds the scrutinee, even when it names a constant.
+        let source = "
+            const C: u8 = 1;
+            fn f(x: u8) { when x { C => {}, 0 => {} } }
+        ";
+
+        assert_eq!(
+            messages(&check_patterns(source)),
+            vec![(
+                "Unreachable pattern",
+                Some("Every value it matches is matched by an earlier arm"),
+                span_in(source, "C => {}, ", "0")
+            )]
+        );
+
+        // The value of a constant is not known, so it covers nothing but is only unreachable
+        // where a wildcard would be.
+        let source = "
+            const C: u8 = 1;
+            fn f(x: u8) { when x { ::C => {}, ::C => {}, 1 => {}, _ => {} } }
+            fn g(x: u8) { when x { ::C => {} } }
+            fn h(x: u8) { when x { _ => {}, ::C => {} } }
+        ";
+
+        assert_eq!(
+            messages(&check_patterns(source)),
+            vec![
+                (
+                    "Unreachable pattern",
+                    Some("Every value it matches is matched by an earlier arm"),
+                    span_in(source, "fn h", "::C")
+                ),
+                (
+                    "Non-exhaustive patterns",
+                    Some("Pattern `0..=255` is not covered"),
+                    span_in(source, "when x { ::C => {} }", "x")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_cases() {
+        let source = "fn f(x: bool) { when x { True => when x { True => {} }, False => {} } }";
+
+        assert_eq!(
+            messages(&check_patterns(source)),
+            vec![(
+                "Non-exhaustive patterns",
+                Some("Pattern `False` is not covered"),
+                span_in(source, "True => when", "x")
+            )]
+        );
+    }
+
+    #[test]
+    fn lint_levels() {
+        let unreachable = |source: &str, levels: &Levels| {
+            let (geode, tables, definitions) = testing::typed(source);
+            let (_, report) = check(&geode, &tables, &definitions, levels);
+            (report.warnings.len(), report.errors.len())
+        };
+        let function = "fn f(x: bool) { when x { _ => {}, True => {} } }";
+
+        assert_eq!(unreachable(function, &Levels::default()), (1, 0));
+
+        let mut levels = Levels::default();
+        levels.set(Lint::UnreachablePatterns, Level::Allow);
+        assert_eq!(unreachable(function, &levels), (0, 0));
+
+        // Attributes take precedence over the command line.
+        let allowed = format!(r#"@allow("unreachable_patterns") {function}"#);
+        let denied = format!(r#"@deny("unreachable_patterns") {function}"#);
+        assert_eq!(unreachable(&allowed, &Levels::default()), (0, 0));
+        assert_eq!(unreachable(&denied, &levels), (0, 1));
+    }
+}
@@ -1 +1,32 @@
+use crate::model::ir::TemplatePiece;
 
+/// Emits an inline assembly template verbatim, with each operand replaced by the location
+/// (register or memory operand) allocated to it. `locations` is indexed like the operands of
+/// the IR `Assembly` statement the template belongs to.
+pub fn assembly(template: &[TemplatePiece], locations: &[String]) -> String {
+    template
+        .iter()
+        .map(|piece| match piece {
+            TemplatePiece::Text(text) => text.as_str(),
+            TemplatePiece::Operand(index) => locations[*index].as_str(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generate::emit::*;
+
+    #[test]
+    fn substitute_locations() {
+        let template = vec![
+            TemplatePiece::Text("out (".to_owned()),
+            TemplatePiece::Operand(1),
+            TemplatePiece::Text("), ".to_owned()),
+            TemplatePiece::Operand(0),
+        ];
+        let locations = vec!["a".to_owned(), "c".to_owned()];
+
+        assert_eq!(assembly(&template, &locations), "out (c), a");
+    }
+}
@@ -0,0 +1,259 @@
+use crate::consteval;
+use crate::error::Error;
+use crate::model::base::*;
+use crate::model::ttg::TreeData;
+use crate::model::types::Type;
+use crate::span::Span;
+
+/// The length of an array or slice. An array's length is part of its type, while a slice's is
+/// only known at runtime, carried alongside the pointer by the reference to the slice.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Length {
+    Static(u32),
+    Dynamic,
+}
+
+/// A runtime comparison guarding an index or range expression. Failing any of them traps.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Check {
+    Index,                 // `index < len`
+    RangeStart,            // `start <= end`
+    RangeEnd(RangeLimits), // `end <= len`, or `end < len` for a closed range
+}
+
+/// Finds the array or slice that a value of type `ty` refers to, looking through any number
+/// of references, the same way indexing and `.len` do.
+pub fn indexable(ty: &Type) -> Option<(&Type, Length)> {
+    match ty {
+        Type::Array(element, size) => Some((element, Length::Static(*size))),
+        Type::Slice(element) => Some((element, Length::Dynamic)),
+        Type::Reference(_, inner) => indexable(inner),
+        _ => None,
+    }
+}
+
+/// The type of `base[index]`. Indexing with a range produces a slice, which is unsized, so
+/// type checking only accepts it as a place, as in `&base[a..b]` or `&mut base[a..b]`.
+pub fn index_type<P: TreeData<P>>(
+    base: &Type,
+    index: &Expression<P>,
+    span: Span,
+) -> Result<Type, Error> {
+    let (element, _) = indexable(base).ok_or_else(|| {
+        Error::new(span, format!("Cannot index into a value of type `{base}`"))
+    })?;
+
+    match is_range(index) {
+        true => Ok(Type::Slice(Box::new(element.clone()))),
+        false => Ok(element.clone()),
+    }
+}
+
+/// Indices and range bounds must be unsigned, so that only the upper bound needs checking.
+pub fn check_index_type(ty: &Type, span: Span) -> Result<(), Error> {
+    match ty {
+        Type::U8 | Type::U16 | Type::U32 => Ok(()),
+        _ => Err(Error::new(
+            span,
+            format!("Arrays and slices must be indexed by an unsigned integer, but found `{ty}`"),
+        )),
+    }
+}
+
+/// The type of the `.len` builtin on a value of type `ty`, if it has one. Arrays and slices
+/// have a length, as do references to them.
+pub fn len_type(ty: &Type) -> Option<Type> {
+    indexable(ty).map(|_| Type::U32)
+}
+
+/// The type of the builtin field `name` of a value of type `ty`, if it has one: the `.len` of
+/// an array or slice, or the `.start` and `.end` bounds of a range.
+pub fn builtin_field_type(ty: &Type, name: &str) -> Option<Type> {
+    match (ty, name) {
+        (_, "len") => len_type(ty),
+        (Type::Range(_, bound), "start" | "end") => Some((**bound).clone()),
+        _ => None,
+    }
+}
+
+/// Determines which runtime bounds checks `base[index]` needs. Checks are elided when the
+/// index, or both ends of the range, are constants that are provably in range, and are all
+/// omitted when bounds checking is disabled for the build. A constant index that is provably
+/// out of range is reported whether or not checks are enabled.
+pub fn checks<P: TreeData<P>>(
+    length: Length,
+    index: &Expression<P>,
+    enabled: bool,
+    lookup: &impl Fn(&P::PathRepresentation) -> Option<u32>,
+) -> Result<Vec<Check>, Error> {
+    let constant = |expression: &Expression<P>| consteval::evaluate(expression, lookup).ok();
+    let static_length = match length {
+        Length::Static(size) => Some(size),
+        Length::Dynamic => None,
+    };
+
+    let mut checks = Vec::new();
+
+    match &index.item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Range(
+            start,
+            end,
+            limits,
+            _,
+        )) => {
+            // A missing start is zero, and a missing end is the length of the array or slice.
+            // A closed range always has an end, and must not be empty.
+            let start_value = match start {
+                Some(start) => constant(start),
+                None => Some(0),
+            };
+            let end_value = match end {
+                Some(end) => constant(end),
+                None => static_length,
+            };
+
+            if let (Some(start_value), Some(end_value)) = (start_value, end_value) {
+                if start_value > end_value {
+                    return Err(Error::new(
+                        index.span,
+                        format!("Range starts at {start_value} but ends at {end_value}"),
+                    ));
+                }
+            }
+
+            if let (Some(end_value), Some(size)) = (end_value, static_length) {
+                let past_end = match limits {
+                    RangeLimits::HalfOpen => end_value > size,
+                    RangeLimits::Closed => end_value >= size,
+                };
+                if past_end {
+                    return Err(out_of_range(index.span, end_value, size));
+                }
+            }
+
+            if start.is_some() && (start_value.is_none() || end_value.is_none()) {
+                checks.push(Check::RangeStart);
+            }
+            if end.is_some() && (end_value.is_none() || static_length.is_none()) {
+                checks.push(Check::RangeEnd(*limits));
+            }
+        }
+        _ => match (constant(index), static_length) {
+            (Some(value), Some(size)) if value >= size => {
+                return Err(out_of_range(index.span, value, size))
+            }
+            (Some(_), Some(_)) => { /* Provably in range */ }
+            _ => checks.push(Check::Index),
+        },
+    }
+
+    match enabled {
+        true => Ok(checks),
+        false => Ok(Vec::new()),
+    }
+}
+
+fn is_range<P: TreeData<P>>(expression: &Expression<P>) -> bool {
+    matches!(
+        expression.item,
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Range(_, _, _, _))
+    )
+}
+
+fn out_of_range(span: Span, index: u32, length: u32) -> Error {
+    Error::new(
+        span,
+        format!("Index {index} is out of range for an array of length {length}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::*;
+    use crate::model::ast::Syntax;
+    use crate::span::Spanned;
+    use crate::testing::{expression, integer, variable};
+
+    fn range(
+        start: Option<Expression<Syntax>>,
+        end: Option<Expression<Syntax>>,
+    ) -> Expression<Syntax> {
+        expression(
+            ExpressionWithoutBlock::Range(
+                start.map(Box::new),
+                end.map(Box::new),
+                RangeLimits::HalfOpen,
+                (),
+            ),
+            0,
+        )
+    }
+
+    fn no_constants(_: &Vec<Spanned<String>>) -> Option<u32> {
+        None
+    }
+
+    #[test]
+    fn index_types() {
+        let array = Type::Array(Box::new(Type::U8), 64);
+        let slice = Type::Reference(
+            Mutability::Immutable,
+            Box::new(Type::Slice(Box::new(Type::U8))),
+        );
+
+        assert_eq!(
+            index_type(&array, &integer(0, 0), Span::new(0, 1)).unwrap(),
+            Type::U8
+        );
+        assert_eq!(
+            index_type(
+                &slice,
+                &range(Some(integer(2, 0)), Some(integer(8, 0))),
+                Span::new(0, 1)
+            )
+            .unwrap(),
+            Type::Slice(Box::new(Type::U8))
+        );
+        assert_eq!(len_type(&slice), Some(Type::U32));
+        assert!(index_type(&Type::U8, &integer(0, 0), Span::new(0, 1)).is_err());
+    }
+
+    #[test]
+    fn elide_constant_index() {
+        let length = Length::Static(64);
+
+        assert_eq!(
+            checks(length, &integer(63, 0), true, &no_constants).unwrap(),
+            Vec::new()
+        );
+        assert!(checks(length, &integer(64, 0), true, &no_constants).is_err());
+        assert_eq!(
+            checks(length, &variable("i", 0), true, &no_constants).unwrap(),
+            vec![Check::Index]
+        );
+        assert_eq!(
+            checks(length, &variable("i", 0), false, &no_constants).unwrap(),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn range_checks() {
+        let constant = range(Some(integer(2, 0)), Some(integer(8, 0)));
+        let open = range(Some(variable("i", 0)), None);
+
+        assert_eq!(
+            checks(Length::Static(8), &constant, true, &no_constants).unwrap(),
+            Vec::new()
+        );
+        assert!(checks(Length::Static(4), &constant, true, &no_constants).is_err());
+        assert_eq!(
+            checks(Length::Dynamic, &constant, true, &no_constants).unwrap(),
+            vec![Check::RangeEnd(RangeLimits::HalfOpen)]
+        );
+        assert_eq!(
+            checks(Length::Static(8), &open, true, &no_constants).unwrap(),
+            vec![Check::RangeStart]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use crate::model::types::{InferKind, Type};
+use crate::span::Span;
+
+#[derive(Debug, Clone)]
+struct Variable {
+    kind: InferKind,
+    binding: Option<Type>,
+    origin: Span, // Where the unknown type was introduced, which is where annotations are needed
+}
+
+/// The inference variables of the body being checked. A variable stands for a type that is not
+/// known where it is first needed, as with `let x;`, and is bound once unification learns what
+/// it must be from a later use. Each body has its own variables, so none of them escape it.
+#[derive(Debug, Default)]
+pub struct Inference {
+    variables: Vec<Variable>,
+}
+
+impl Inference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fresh(&mut self, kind: InferKind, origin: Span) -> Type {
+        self.variables.push(Variable {
+            kind,
+            binding: None,
+            origin,
+        });
+
+        Type::Infer(self.variables.len() - 1, kind)
+    }
+
+    /// Moves where the unbound variables within `ty` came from to `origin`, for those that
+    /// were introduced within `within`. A `let` binding without an annotation is where the
+    /// types its initializer leaves unknown are needed, as with `let x = [];`.
+    pub fn relocate(&mut self, ty: &Type, within: Span, origin: Span) {
+        match self.shallow(ty) {
+            Type::Infer(variable, _) => {
+                let variable = &mut self.variables[variable];
+
+                if within.start <= variable.origin.start && variable.origin.stop <= within.stop {
+                    variable.origin = origin;
+                }
+            }
+            Type::Array(element, _)
+            | Type::Slice(element)
+            | Type::Reference(_, element)
+            | Type::Range(_, element) => self.relocate(&element, within, origin),
+            Type::Function(parameters, return_type) => {
+                for parameter in &parameters {
+                    self.relocate(parameter, within, origin);
+                }
+                self.relocate(&return_type, within, origin);
+            }
+            Type::Named(_, _, arguments) => {
+                for argument in &arguments {
+                    self.relocate(argument, within, origin);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces every bound variable within `ty` with what it is bound to.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Infer(variable, _) => match &self.variables[*variable].binding {
+                Some(binding) => self.resolve(binding),
+                None => ty.clone(),
+            },
+            Type::Array(element, size) => Type::Array(Box::new(self.resolve(element)), *size),
+            Type::Slice(element) => Type::Slice(Box::new(self.resolve(element))),
+            Type::Range(limits, bound) => Type::Range(*limits, Box::new(self.resolve(bound))),
+            Type::Reference(mutability, inner) => {
+                Type::Reference(mutability.clone(), Box::new(self.resolve(inner)))
+            }
+            Type::Function(parameters, return_type) => Type::Function(
+                parameters.iter().map(|param| self.resolve(param)).collect(),
+                Box::new(self.resolve(return_type)),
+            ),
+            Type::Named(def, name, arguments) => Type::Named(
+                *def,
+                name.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| self.resolve(argument))
+                    .collect(),
+            ),
+            _ => ty.clone(),
+        }
+    }
+
+    /// Whether `ty` is an integer type, or a variable that can only become one.
+    pub fn is_integer(&self, ty: &Type) -> bool {
+        is_integer(&self.shallow(ty))
+    }
+
+    /// Makes two types equal by binding the variables within them, returning whether that is
+    /// possible. `Never` unifies with every type without binding anything, since a diverging
+    /// expression can stand in for a value of any type. Unification that fails partway keeps
+    /// the bindings made before the failure, which only affects diagnostics.
+    pub fn unify(&mut self, expected: &Type, found: &Type) -> bool {
+        let expected = self.shallow(expected);
+        let found = self.shallow(found);
+
+        match (&expected, &found) {
+            (Type::Never, _) | (_, Type::Never) => true,
+            (Type::Infer(a, _), Type::Infer(b, _)) if a == b => true,
+            (Type::Infer(a, InferKind::Any), ty) | (ty, Type::Infer(a, InferKind::Any)) => {
+                if self.occurs(*a, ty) {
+                    return false;
+                }
+
+                self.variables[*a].binding = Some(ty.clone());
+                true
+            }
+            (Type::Infer(a, InferKind::Integer), ty) | (ty, Type::Infer(a, InferKind::Integer)) => {
+                // Binding to another integer variable keeps both of them integers.
+                if !is_integer(ty) {
+                    return false;
+                }
+
+                self.variables[*a].binding = Some(ty.clone());
+                true
+            }
+            (Type::Array(a, n), Type::Array(b, m)) => n == m && self.unify(a, b),
+            (Type::Slice(a), Type::Slice(b)) => self.unify(a, b),
+            (Type::Range(l, a), Type::Range(k, b)) => l == k && self.unify(a, b),
+            (Type::Reference(m, a), Type::Reference(n, b)) => m == n && self.unify(a, b),
+            (Type::Function(a, r), Type::Function(b, s)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|(a, b)| self.unify(a, b))
+                    && self.unify(r, s)
+            }
+            (Type::Named(a, _, x), Type::Named(b, _, y)) => {
+                a == b && x.len() == y.len() && x.iter().zip(y).all(|(x, y)| self.unify(x, y))
+            }
+            _ => expected == found,
+        }
+    }
+
+    /// Ends inference for the current body. Integer variables that nothing constrained take
+    /// the default integer type, and every other unbound variable is bound to `Never` so that
+    /// no variable remains in the typed tree. Returns where those other variables came from,
+    /// each of which needs a type annotation.
+    pub fn finish(&mut self, default_integer: &Type) -> Vec<Span> {
+        let mut unresolved = Vec::new();
+
+        for variable in &mut self.variables {
+            if variable.binding.is_none() {
+                variable.binding = Some(match variable.kind {
+                    InferKind::Integer => default_integer.clone(),
+                    InferKind::Any => {
+                        unresolved.push(variable.origin);
+                        Type::Never
+                    }
+                });
+            }
+        }
+
+        unresolved
+    }
+
+    /// Follows bindings until reaching a type that is not a bound variable.
+    fn shallow(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Infer(variable, _) => match &self.variables[*variable].binding {
+                Some(binding) => self.shallow(binding),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// Whether binding `variable` to `ty` would make the variable contain itself.
+    fn occurs(&self, variable: usize, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Type::Infer(other, _) => other == variable,
+            Type::Array(element, _)
+            | Type::Slice(element)
+            | Type::Reference(_, element)
+            | Type::Range(_, element) => self.occurs(variable, &element),
+            Type::Function(parameters, return_type) => {
+                parameters.iter().any(|param| self.occurs(variable, param))
+                    || self.occurs(variable, &return_type)
+            }
+            Type::Named(_, _, arguments) => arguments
+                .iter()
+                .any(|argument| self.occurs(variable, argument)),
+            _ => false,
+        }
+    }
+}
+
+fn is_integer(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::U8
+            | Type::I8
+            | Type::U16
+            | Type::I16
+            | Type::U32
+            | Type::I32
+            | Type::Infer(_, InferKind::Integer)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::infer::*;
+    use crate::model::base::Mutability;
+
+    fn span() -> Span {
+        Span::new(0, 1)
+    }
+
+    #[test]
+    fn binds_through_structure() {
+        let mut inference = Inference::new();
+        let element = inference.fresh(InferKind::Any, span());
+        let array = Type::Array(Box::new(element.clone()), 3);
+
+        assert!(inference.unify(&array, &Type::Array(Box::new(Type::Bool), 3)));
+        assert_eq!(inference.resolve(&element), Type::Bool);
+        assert!(!inference.unify(&array, &Type::Array(Box::new(Type::U8), 3)));
+    }
+
+    #[test]
+    fn integer_variables() {
+        let mut inference = Inference::new();
+        let literal = inference.fresh(InferKind::Integer, span());
+        let binding = inference.fresh(InferKind::Any, span());
+
+        assert!(!inference.unify(&literal, &Type::Bool));
+        assert!(inference.unify(&binding, &literal));
+        assert!(inference.is_integer(&binding));
+
+        assert!(inference.unify(&Type::U16, &binding));
+        assert_eq!(inference.resolve(&literal), Type::U16);
+    }
+
+    #[test]
+    fn occurs_check() {
+        let mut inference = Inference::new();
+        let variable = inference.fresh(InferKind::Any, span());
+        let reference = Type::Reference(Mutability::Immutable, Box::new(variable.clone()));
+
+        assert!(!inference.unify(&variable, &reference));
+    }
+
+    #[test]
+    fn finish_defaults_integers() {
+        let mut inference = Inference::new();
+        let literal = inference.fresh(InferKind::Integer, span());
+        let unknown = inference.fresh(InferKind::Any, Span::new(4, 7));
+
+        assert_eq!(inference.finish(&Type::U8), vec![Span::new(4, 7)]);
+        assert_eq!(inference.resolve(&literal), Type::U8);
+        assert_eq!(inference.resolve(&unknown), Type::Never);
+    }
+
+    #[test]
+    fn relocates_unbound_variables() {
+        // A binding at 4..5 whose initializer at 8..15 gives it a type with two unknowns, one
+        // introduced by the initializer and one from before it.
+        let mut inference = Inference::new();
+        let element = inference.fresh(InferKind::Any, Span::new(9, 11));
+        let elsewhere = inference.fresh(InferKind::Any, Span::new(2, 3));
+        let array = Type::Array(Box::new(element), 0);
+        let reference = Type::Reference(Mutability::Immutable, Box::new(elsewhere));
+        let ty = Type::Function(vec![array, reference], Box::new(Type::Unit));
+
+        // Only the variable introduced by the initializer moves to the binding.
+        inference.relocate(&ty, Span::new(8, 15), Span::new(4, 5));
+        assert_eq!(
+            inference.finish(&Type::U8),
+            vec![Span::new(4, 5), Span::new(2, 3)]
+        );
+    }
+}
@@ -0,0 +1,770 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::Error;
+use crate::model::base::*;
+use crate::model::resolved::{LocalId, Name, Resolution};
+use crate::model::typed::Typed;
+use crate::model::visit::{walk_function, Visitor};
+use crate::span::Span;
+
+/// Checks that every variable is initialized before it is read, and that an immutable variable
+/// is only assigned where it cannot have been initialized already, which allows `let x;`
+/// followed by a single assignment on each path.
+///
+/// This is a forward data flow analysis following the control flow of each function body:
+/// branches join at the end of `if` and `when` expressions, loops are repeated until what is
+/// known at their start stops changing, and `break`, `continue` and `return` carry what is
+/// known to where they jump. Deferred expressions are checked both where their block ends
+/// normally and with what is known on every jump out of the block made after they were
+/// registered, including `return` and `?`.
+pub fn check(geode: &Geode<Typed>) -> Vec<Error> {
+    let mut checker = Checker {
+        locals: HashMap::new(),
+        loops: Vec::new(),
+        exits: Vec::new(),
+        errors: Vec::new(),
+        reported: Vec::new(),
+    };
+
+    checker.visit_geode(geode);
+    checker.errors
+}
+
+/// What is known about the variables at a point of a function body, or `None` if the point
+/// cannot be reached.
+type Flow = Option<State>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    definitely: HashSet<LocalId>, // Initialized on every path to this point
+    maybe: HashSet<LocalId>,      // Initialized on some path to this point
+}
+
+/// Combines what is known on two paths meeting at the same point.
+fn join(left: Flow, right: Flow) -> Flow {
+    match (left, right) {
+        (None, flow) | (flow, None) => flow,
+        (Some(left), Some(right)) => Some(State {
+            definitely: left
+                .definitely
+                .intersection(&right.definitely)
+                .copied()
+                .collect(),
+            maybe: left.maybe.union(&right.maybe).copied().collect(),
+        }),
+    }
+}
+
+struct Local {
+    name: String,
+    span: Span, // Of the name at its declaration
+    mutable: bool,
+}
+
+/// The paths leaving a loop that is being analyzed.
+struct Frame {
+    label: Option<String>,
+    depth: usize, // The number of blocks enclosing the loop
+    breaks: Flow,
+    continues: Flow,
+}
+
+struct Checker {
+    locals: HashMap<LocalId, Local>,
+    loops: Vec<Frame>,
+    // For each enclosing block, and each deferred expression registered in it so far, what is
+    // known on the jumps that leave the block after the registration.
+    exits: Vec<Vec<Flow>>,
+    errors: Vec<Error>,
+    // Loop bodies are analyzed repeatedly, so the same error may be found more than once.
+    reported: Vec<Span>,
+}
+
+impl Checker {
+    fn declare(
+        &mut self,
+        name: &Name,
+        mutability: &Mutability,
+        flow: &mut Flow,
+    ) -> Option<LocalId> {
+        let Some(Resolution::Local(local)) = name.resolution else {
+            return None;
+        };
+
+        self.locals.insert(
+            local,
+            Local {
+                name: name.ident.item.clone(),
+                span: name.ident.span,
+                mutable: *mutability == Mutability::Mutable,
+            },
+        );
+
+        // A declaration within a loop introduces a fresh variable on every iteration.
+        if let Some(state) = flow {
+            state.definitely.remove(&local);
+            state.maybe.remove(&local);
+        }
+
+        Some(local)
+    }
+
+    fn initialize(&mut self, local: LocalId, flow: &mut Flow) {
+        if let Some(state) = flow {
+            state.definitely.insert(local);
+            state.maybe.insert(local);
+        }
+    }
+
+    fn report(&mut self, error: Error) {
+        if !self.reported.contains(&error.span) {
+            self.reported.push(error.span);
+            self.errors.push(error);
+        }
+    }
+
+    fn read(&mut self, local: LocalId, span: Span, flow: &Flow) {
+        let (Some(state), Some(declared)) = (flow, self.locals.get(&local)) else {
+            return;
+        };
+
+        if !state.definitely.contains(&local) {
+            let error = Error::with_details(
+                span,
+                format!("Use of possibly uninitialized variable `{}`", declared.name),
+                format!(
+                    "Assign `{}` a value on every path to this use",
+                    declared.name
+                ),
+            )
+            .with_note(
+                declared.span,
+                format!("`{}` is declared here without a value", declared.name),
+            );
+
+            self.report(error);
+        }
+    }
+
+    fn assign(&mut self, local: LocalId, span: Span, flow: &mut Flow) {
+        let (Some(state), Some(declared)) = (flow.as_ref(), self.locals.get(&local)) else {
+            return;
+        };
+
+        if !declared.mutable && state.maybe.contains(&local) {
+            let error = Error::with_details(
+                span,
+                format!(
+                    "Cannot assign twice to immutable variable `{}`",
+                    declared.name
+                ),
+                format!("Declare it with `let mut {}` to allow this", declared.name),
+            )
+            .with_note(
+                declared.span,
+                format!("`{}` is declared here", declared.name),
+            );
+
+            self.report(error);
+        }
+
+        self.initialize(local, flow);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern<Typed>, flow: &mut Flow) {
+        match &pattern.item {
+            PatternKind::Binding(mutability, name, _) => {
+                if let Some(local) = self.declare(name, mutability, flow) {
+                    self.initialize(local, flow);
+                }
+            }
+            PatternKind::Tuple(_, elements, _) => {
+                for element in elements {
+                    self.pattern(element, flow);
+                }
+            }
+            PatternKind::Struct(_, fields, _, _) => {
+                for field in fields {
+                    self.pattern(&field.pattern, flow);
+                }
+            }
+            PatternKind::Wildcard(_)
+            | PatternKind::Literal(_, _)
+            | PatternKind::Range(_, _, _, _)
+            | PatternKind::Path(_, _) => {}
+        }
+    }
+
+    /// Records a jump out of every block from the given depth outwards, which is `0` for
+    /// `return` and `?`, and the depth of the target loop for `break` and `continue`.
+    fn leave(&mut self, depth: usize, flow: &Flow) {
+        for exits in self.exits.iter_mut().skip(depth) {
+            for exit in exits {
+                *exit = join(exit.take(), flow.clone());
+            }
+        }
+    }
+
+    fn block(&mut self, block: &BlockExpression<Typed>, flow: &mut Flow) {
+        let mut deferred = Vec::new();
+        self.exits.push(Vec::new());
+
+        for statements in block.iter().flatten() {
+            match statements {
+                Statements::Leading(first, rest, tail) => {
+                    for statement in std::iter::once(first).chain(rest) {
+                        self.statement(statement, &mut deferred, flow);
+                    }
+
+                    if let Some(tail) = tail {
+                        self.expression(tail, flow);
+                    }
+                }
+                Statements::Block(tail) => self.expression(tail, flow),
+            }
+        }
+
+        let exits = self
+            .exits
+            .pop()
+            .expect("Blocks are entered and exited in pairs");
+
+        for (expression, mut exit) in deferred.into_iter().zip(exits).rev() {
+            self.expression(expression, flow);
+            self.expression(expression, &mut exit);
+        }
+    }
+
+    fn statement<'a>(
+        &mut self,
+        statement: &'a Statement<Typed>,
+        deferred: &mut Vec<&'a Expression<Typed>>,
+        flow: &mut Flow,
+    ) {
+        match statement {
+            Statement::Empty => {}
+            Statement::Let(Let {
+                name,
+                mutability,
+                initializer,
+                ..
+            }) => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer, flow);
+                }
+
+                let local = self.declare(name, mutability, flow);
+
+                if let (Some(local), Some(_)) = (local, initializer) {
+                    self.initialize(local, flow);
+                }
+            }
+            Statement::Expression(expression) => self.expression(expression, flow),
+            Statement::Defer(expression) => {
+                deferred.push(expression);
+                self.exits
+                    .last_mut()
+                    .expect("`defer` statements only appear within blocks")
+                    .push(None);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression<Typed>, flow: &mut Flow) {
+        match &expression.item {
+            ExpressionKind::ExpressionWithBlock(with_block) => self.with_block(with_block, flow),
+            ExpressionKind::ExpressionWithoutBlock(without_block) => {
+                self.without_block(without_block, flow)
+            }
+        }
+    }
+
+    fn with_block(&mut self, expression: &ExpressionWithBlock<Typed>, flow: &mut Flow) {
+        use ExpressionWithBlock::*;
+
+        match expression {
+            Block(body, _) => self.block(body, flow),
+            If(condition, then, otherwise, _) => {
+                self.expression(condition, flow);
+
+                let mut then_flow = flow.clone();
+                self.block(then, &mut then_flow);
+
+                if let Some(otherwise) = otherwise {
+                    self.expression(otherwise, flow);
+                }
+
+                *flow = join(then_flow, flow.take());
+            }
+            IfIs(scrutinee, pattern, then, otherwise, _) => {
+                self.expression(scrutinee, flow);
+
+                let mut then_flow = flow.clone();
+                self.pattern(pattern, &mut then_flow);
+                self.expression(then, &mut then_flow);
+
+                if let Some(otherwise) = otherwise {
+                    self.expression(otherwise, flow);
+                }
+
+                *flow = join(then_flow, flow.take());
+            }
+            Case(scrutinee, arms, _) => {
+                self.expression(scrutinee, flow);
+
+                // Each arm is reached by the scrutinee failing to match the arms before it,
+                // possibly after their guards have run.
+                let mut next = flow.take();
+
+                for arm in arms {
+                    let mut arm_flow = next.clone();
+                    self.pattern(&arm.pattern, &mut arm_flow);
+
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard, &mut arm_flow);
+                        next = join(next, arm_flow.clone());
+                    }
+
+                    self.expression(&arm.expression, &mut arm_flow);
+                    *flow = join(flow.take(), arm_flow);
+                }
+            }
+            While(label, condition, body, _) => {
+                let exit = self.repeat(label, flow, |checker, flow| {
+                    checker.expression(condition, flow);
+                    let exit = flow.clone();
+                    checker.block(body, flow);
+                    exit
+                });
+
+                *flow = exit;
+            }
+            Loop(label, body, _) => {
+                let exit = self.repeat(label, flow, |checker, flow| {
+                    checker.block(body, flow);
+                    None
+                });
+
+                *flow = exit;
+            }
+            For(label, name, iterable, body, _) => {
+                self.expression(iterable, flow);
+
+                let exit = self.repeat(label, flow, |checker, flow| {
+                    let exit = flow.clone();
+                    if let Some(local) = checker.declare(name, &Mutability::Immutable, flow) {
+                        checker.initialize(local, flow);
+                    }
+                    checker.block(body, flow);
+                    exit
+                });
+
+                *flow = exit;
+            }
+        }
+    }
+
+    /// Analyzes a loop until what is known at its start stops changing, which happens since
+    /// that only ever loses variables known to be initialized and gains variables that may
+    /// be. `iteration` analyzes one iteration, returning what is known where the loop ends
+    /// without a `break`. The result is what is known after the loop.
+    fn repeat(
+        &mut self,
+        label: &Option<Label>,
+        flow: &Flow,
+        mut iteration: impl FnMut(&mut Self, &mut Flow) -> Flow,
+    ) -> Flow {
+        let mut start = flow.clone();
+
+        loop {
+            self.loops.push(Frame {
+                label: label.as_ref().map(|label| label.item.clone()),
+                depth: self.exits.len(),
+                breaks: None,
+                continues: None,
+            });
+
+            let mut end = start.clone();
+            let exit = iteration(self, &mut end);
+            let frame = self
+                .loops
+                .pop()
+                .expect("Frames are pushed and popped in pairs");
+
+            let next = join(join(flow.clone(), end), frame.continues);
+
+            if next == start {
+                return join(exit, frame.breaks);
+            }
+
+            start = next;
+        }
+    }
+
+    /// The loop that `break` or `continue` with the given label leaves or resumes.
+    fn frame(&mut self, label: &Option<Label>) -> Option<&mut Frame> {
+        self.loops.iter_mut().rev().find(|frame| match label {
+            Some(label) => frame.label.as_ref() == Some(&label.item),
+            None => true,
+        })
+    }
+
+    fn without_block(&mut self, expression: &ExpressionWithoutBlock<Typed>, flow: &mut Flow) {
+        use ExpressionWithoutBlock::*;
+
+        match expression {
+            Path(path, _) => {
+                if let Resolution::Local(local) = path.resolution {
+                    let span = path.segments[0].span;
+                    self.read(local, span, flow);
+                }
+            }
+            Binary(left, operator, right, _) => match operator {
+                BinaryOperator::Equal => {
+                    self.expression(right, flow);
+
+                    match local(left) {
+                        Some(local) => self.assign(local, left.span, flow),
+                        None => self.expression(left, flow),
+                    }
+                }
+                BinaryOperator::PlusEqual
+                | BinaryOperator::MinusEqual
+                | BinaryOperator::TimesEqual
+                | BinaryOperator::DivideEqual
+                | BinaryOperator::AndEqual
+                | BinaryOperator::OrEqual
+                | BinaryOperator::LShiftEqual
+                | BinaryOperator::RShiftEqual => {
+                    self.expression(right, flow);
+                    self.expression(left, flow);
+
+                    if let Some(local) = local(left) {
+                        self.assign(local, left.span, flow);
+                    }
+                }
+                // The right operand only runs if the left does not decide the result.
+                BinaryOperator::LazyAnd | BinaryOperator::LazyOr => {
+                    self.expression(left, flow);
+
+                    let mut right_flow = flow.clone();
+                    self.expression(right, &mut right_flow);
+                    *flow = join(flow.take(), right_flow);
+                }
+                _ => {
+                    self.expression(left, flow);
+                    self.expression(right, flow);
+                }
+            },
+            Return(value, _) => {
+                if let Some(value) = value {
+                    self.expression(value, flow);
+                }
+
+                self.leave(0, flow);
+                *flow = None;
+            }
+            Break(label, value, _) => {
+                if let Some(value) = value {
+                    self.expression(value, flow);
+                }
+
+                let leaving = flow.take();
+                if let Some(frame) = self.frame(label) {
+                    let depth = frame.depth;
+                    frame.breaks = join(frame.breaks.take(), leaving.clone());
+                    self.leave(depth, &leaving);
+                }
+            }
+            Continue(label, _) => {
+                let leaving = flow.take();
+                if let Some(frame) = self.frame(label) {
+                    let depth = frame.depth;
+                    frame.continues = join(frame.continues.take(), leaving.clone());
+                    self.leave(depth, &leaving);
+                }
+            }
+            Asm(assembly, _) => {
+                let (inputs, outputs): (Vec<_>, Vec<_>) = assembly
+                    .operands
+                    .iter()
+                    .partition(|operand| operand.direction == Direction::In);
+
+                for operand in inputs {
+                    self.expression(&operand.expression, flow);
+                }
+
+                for operand in outputs {
+                    match local(&operand.expression) {
+                        Some(local) => self.assign(local, operand.expression.span, flow),
+                        None => self.expression(&operand.expression, flow),
+                    }
+                }
+            }
+            // The error is returned early, leaving every enclosing block.
+            ErrorPropagation(inner, _) => {
+                self.expression(inner, flow);
+                self.leave(0, flow);
+            }
+            Grouped(inner, _) | Prefix(_, inner, _) => self.expression(inner, flow),
+            Field(inner, _, _) => self.expression(inner, flow),
+            Repeat(value, count, _) => {
+                self.expression(value, flow);
+                self.expression(count, flow);
+            }
+            Index(indexed, index, _) => {
+                self.expression(indexed, flow);
+                self.expression(index, flow);
+            }
+            Range(start, end, _, _) => {
+                for bound in start.iter().chain(end) {
+                    self.expression(bound, flow);
+                }
+            }
+            Array(elements, _) => {
+                for element in elements {
+                    self.expression(element, flow);
+                }
+            }
+            Call(function, arguments, _) => {
+                self.expression(function, flow);
+                for argument in arguments {
+                    self.expression(argument, flow);
+                }
+            }
+            MethodCall(_, _, _, removed) => match *removed {},
+            Literal(_, _) | Other(_) => {}
+        }
+    }
+}
+
+impl Visitor<Typed> for Checker {
+    fn visit_function(&mut self, function: &FunctionItem<Typed>) {
+        if let Some(body) = &function.body {
+            let mut flow = Some(State {
+                definitely: HashSet::new(),
+                maybe: HashSet::new(),
+            });
+
+            for parameter in &function.parameters {
+                if let Some(local) = self.declare(&parameter.name, &parameter.mutability, &mut flow)
+                {
+                    self.initialize(local, &mut flow);
+                }
+            }
+
+            self.loops.clear();
+            self.exits.clear();
+            self.block(body, &mut flow);
+        }
+
+        walk_function(self, function)
+    }
+}
+
+/// The local variable an expression names, if it is nothing more than that.
+fn local(expression: &Expression<Typed>) -> Option<LocalId> {
+    match &expression.item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(path, _)) => {
+            match path.resolution {
+                Resolution::Local(local) => Some(local),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::initialize::*;
+    use crate::testing::{self, notes, span, span_in};
+
+    fn errors(source: &str) -> Vec<Error> {
+        let (geode, _, _) = testing::typed(source);
+        check(&geode)
+    }
+
+    #[test]
+    fn branches() {
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                if c { x = 1; }
+                x
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Use of possibly uninitialized variable `x`",
+                span_in(source, "}\n", "x"),
+                vec![span_in(source, "let", "x")]
+            )]
+        );
+
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                if c { x = 1; } else { x = 2; }
+                x
+            }
+        ";
+
+        assert!(errors(source).is_empty());
+
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                if c { x = 1; } else { return 0; }
+                x
+            }
+        ";
+
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn loops() {
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                loop { x = 1; break; }
+                x
+            }
+        ";
+
+        assert!(errors(source).is_empty());
+
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                while c { x = 1; }
+                x
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![
+                (
+                    "Cannot assign twice to immutable variable `x`",
+                    span_in(source, "while", "x"),
+                    vec![span_in(source, "let", "x")]
+                ),
+                (
+                    "Use of possibly uninitialized variable `x`",
+                    span_in(source, "}\n", "x"),
+                    vec![span_in(source, "let", "x")]
+                )
+            ]
+        );
+
+        let source = "
+            fn f(c: bool) -> u8 {
+                let mut x: u8;
+                while c { x = 1; }
+                x
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Use of possibly uninitialized variable `x`",
+                span_in(source, "}\n", "x"),
+                vec![span_in(source, "let", "x")]
+            )]
+        );
+    }
+
+    #[test]
+    fn deferred_exits() {
+        // The deferred read runs on the early return, before `x` is assigned.
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                defer x;
+                if c { return 0; }
+                x = 1;
+                x
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Use of possibly uninitialized variable `x`",
+                span_in(source, "defer", "x"),
+                vec![span_in(source, "let", "x")]
+            )]
+        );
+
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                if c { return 0; }
+                defer x;
+                x = 1;
+                x
+            }
+        ";
+
+        assert!(errors(source).is_empty());
+
+        let source = "
+            fn f(c: bool) -> u8 {
+                let x: u8;
+                loop {
+                    defer x;
+                    if c { break; }
+                    x = 1;
+                    break;
+                }
+                0
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Use of possibly uninitialized variable `x`",
+                span_in(source, "defer", "x"),
+                vec![span_in(source, "let", "x")]
+            )]
+        );
+    }
+
+    #[test]
+    fn reassignment() {
+        let source = "
+            fn f() -> u8 {
+                let x: u8;
+                x = 1;
+                x = 2;
+                x
+            }
+        ";
+
+        assert_eq!(
+            notes(&errors(source)),
+            vec![(
+                "Cannot assign twice to immutable variable `x`",
+                span_in(source, "x = 2", "x"),
+                vec![span_in(source, "let", "x")]
+            )]
+        );
+
+        let source = "
+            fn f() -> u8 {
+                let mut x: u8;
+                x = 1;
+                x = 2;
+                x
+            }
+        ";
+
+        assert!(errors(source).is_empty());
+    }
+}
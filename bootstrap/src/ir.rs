@@ -1,3 +1,5 @@
+use crate::model::ir::VariableId;
+
 enum Constant {
     U8(u8),
     U16(u16),
@@ -11,8 +13,3 @@ enum Instruction {
     Assign(VariableId, VariableId),
     AssignConstant(VariableId, Constant),
 }
-
-enum Decision {
-    Success(/* Body to be executed? */),
-    
-}
\ No newline at end of file
@@ -0,0 +1,618 @@
+use crate::error::Error;
+use crate::index;
+use crate::model::base::*;
+use crate::model::resolved::{LocalId, Name, Resolution, ResolvedPath};
+use crate::model::typed::{type_of, Typed};
+use crate::model::types::Type;
+use crate::span::{Span, Spanned};
+
+// Names of the hidden variables that drive a desugared loop, for diagnostics. They cannot be
+// written in source, and are referred to by their local, so they never collide with user
+// variables.
+const RANGE: &str = "for#range";
+const NEXT: &str = "for#next";
+const END: &str = "for#end";
+const MORE: &str = "for#more";
+const ITEMS: &str = "for#items";
+const INDEX: &str = "for#index";
+const LEN: &str = "for#len";
+
+/// How a for-loop steps through the value it iterates over. `for name in iterable { body }`
+/// desugars to
+///
+/// ```text
+/// {
+///     <setup>
+///     while <condition> {
+///         <advance>
+///         { body }
+///     }
+/// }
+/// ```
+///
+/// where `advance` binds the loop variable and moves past the current element before the body
+/// runs, so that a `continue` within the body cannot skip it. Every node a protocol builds is
+/// typed, since loops are desugared after type checking.
+pub trait Protocol {
+    fn setup(&self, iterable: Expression<Typed>, span: Span) -> Vec<Statement<Typed>>;
+    fn condition(&self, span: Span) -> Expression<Typed>;
+    fn advance(&self, name: Name, span: Span) -> Vec<Statement<Typed>>;
+}
+
+/// Builds the protocol for iterating over values of type `ty`, if it applies to that type,
+/// taking the locals of its hidden variables from `fresh`.
+pub type Builder = fn(&Type, &mut dyn FnMut() -> LocalId) -> Option<Box<dyn Protocol>>;
+
+/// The protocols that for-loops follow, tried in the order they were registered until one
+/// applies to the type of the iterable. Ranges, arrays, and slices have built-in protocols.
+/// User types are made iterable by registering another, for instance one that calls the
+/// methods of an iteration trait.
+pub struct Protocols(Vec<Builder>);
+
+impl Protocols {
+    pub fn new() -> Self {
+        Self(vec![RangeProtocol::build, IndexedProtocol::build])
+    }
+
+    pub fn register(&mut self, builder: Builder) {
+        self.0.push(builder);
+    }
+
+    /// Chooses the protocol for iterating over `iterable`, by its type.
+    pub fn protocol(
+        &self,
+        iterable: &Expression<Typed>,
+        fresh: &mut dyn FnMut() -> LocalId,
+    ) -> Result<Box<dyn Protocol>, Error> {
+        let ty = type_of(iterable);
+
+        self.0
+            .iter()
+            .find_map(|build| build(ty, fresh))
+            .ok_or_else(|| {
+                Error::with_details(
+                    iterable.span,
+                    format!("Cannot iterate over a value of type `{ty}`"),
+                    String::from("Only ranges, arrays, and slices can be iterated over"),
+                )
+            })
+    }
+}
+
+impl Default for Protocols {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A variable introduced by a protocol, numbered after every local of the geode.
+struct Hidden {
+    name: &'static str,
+    local: LocalId,
+    ty: Type,
+}
+
+/// Counts from the start of a range to its end, which are each read once.
+pub struct RangeProtocol {
+    limits: RangeLimits,
+    range: Hidden,
+    next: Hidden,
+    end: Hidden,
+    more: Hidden, // Only used by closed ranges
+}
+
+/// Walks an array or slice by index. Iterating through a reference binds a reference to each
+/// element, while iterating anything else binds a copy of each element.
+pub struct IndexedProtocol {
+    borrow: Option<Mutability>,
+    element: Type,
+    items: Hidden,
+    index: Hidden,
+    len: Hidden,
+}
+
+impl RangeProtocol {
+    fn build(ty: &Type, fresh: &mut dyn FnMut() -> LocalId) -> Option<Box<dyn Protocol>> {
+        let Type::Range(limits, bound) = ty else {
+            return None;
+        };
+
+        Some(Box::new(RangeProtocol {
+            limits: *limits,
+            range: Hidden::new(RANGE, ty, fresh),
+            next: Hidden::new(NEXT, bound, fresh),
+            end: Hidden::new(END, bound, fresh),
+            more: Hidden::new(MORE, &Type::Bool, fresh),
+        }))
+    }
+
+    /// Reads the bound `name` of the range.
+    fn bound(&self, name: &str, span: Span) -> Expression<Typed> {
+        let Type::Range(_, bound) = &self.range.ty else {
+            unreachable!("The range protocol iterates over ranges");
+        };
+
+        field(self.range.path(span), name, bound, span)
+    }
+}
+
+impl IndexedProtocol {
+    fn build(ty: &Type, fresh: &mut dyn FnMut() -> LocalId) -> Option<Box<dyn Protocol>> {
+        let (element, borrow) = match ty {
+            Type::Reference(mutability, inner) => {
+                (index::indexable(inner)?, Some(mutability.clone()))
+            }
+            _ => (index::indexable(ty)?, None),
+        };
+
+        Some(Box::new(IndexedProtocol {
+            borrow,
+            element: element.0.clone(),
+            items: Hidden::new(ITEMS, ty, fresh),
+            index: Hidden::new(INDEX, &Type::U32, fresh),
+            len: Hidden::new(LEN, &Type::U32, fresh),
+        }))
+    }
+}
+
+/// Desugars `for name in iterable { body }` into a block containing a `while` loop, which has
+/// the type `ty` of the loop. The loop's label moves to the `while` loop, so `break` and
+/// `continue` keep their meaning.
+pub fn desugar(
+    protocol: &dyn Protocol,
+    label: Option<Label>,
+    name: Name,
+    iterable: Expression<Typed>,
+    body: BlockExpression<Typed>,
+    ty: Type,
+    span: Span,
+) -> Expression<Typed> {
+    let mut statements = protocol.advance(name, span);
+    statements.push(Statement::Expression(with_block(
+        ExpressionWithBlock::Block(body, Type::Unit),
+        span,
+    )));
+
+    let mut outer = protocol.setup(iterable, span);
+    outer.push(Statement::Expression(with_block(
+        ExpressionWithBlock::While(
+            label,
+            Box::new(protocol.condition(span)),
+            block(statements),
+            Type::Unit,
+        ),
+        span,
+    )));
+
+    with_block(ExpressionWithBlock::Block(block(outer), ty), span)
+}
+
+impl Protocol for RangeProtocol {
+    fn setup(&self, iterable: Expression<Typed>, span: Span) -> Vec<Statement<Typed>> {
+        let mut statements = vec![
+            self.range.declare(Mutability::Immutable, iterable, span),
+            self.next
+                .declare(Mutability::Mutable, self.bound("start", span), span),
+            self.end
+                .declare(Mutability::Immutable, self.bound("end", span), span),
+        ];
+
+        // A closed range may end at the largest value of its type, so the cursor cannot be
+        // advanced past the end. Instead, a flag records whether the end has been reached.
+        if self.limits == RangeLimits::Closed {
+            statements.push(self.more.declare(
+                Mutability::Mutable,
+                binary(
+                    self.next.path(span),
+                    BinaryOperator::Le,
+                    self.end.path(span),
+                    Type::Bool,
+                ),
+                span,
+            ));
+        }
+
+        statements
+    }
+
+    fn condition(&self, span: Span) -> Expression<Typed> {
+        match self.limits {
+            RangeLimits::HalfOpen => binary(
+                self.next.path(span),
+                BinaryOperator::Lt,
+                self.end.path(span),
+                Type::Bool,
+            ),
+            RangeLimits::Closed => self.more.path(span),
+        }
+    }
+
+    fn advance(&self, name: Name, span: Span) -> Vec<Statement<Typed>> {
+        let bind = Statement::Let(Let {
+            name,
+            mutability: Mutability::Immutable,
+            ty: Some(self.next.ty.clone()),
+            initializer: Some(self.next.path(span)),
+            span,
+        });
+        let increment = Statement::Expression(binary(
+            self.next.path(span),
+            BinaryOperator::PlusEqual,
+            integer(1, &self.next.ty, span),
+            Type::Unit,
+        ));
+
+        match self.limits {
+            RangeLimits::HalfOpen => vec![bind, increment],
+            RangeLimits::Closed => vec![
+                bind,
+                Statement::Expression(binary(
+                    self.more.path(span),
+                    BinaryOperator::Equal,
+                    binary(
+                        self.next.path(span),
+                        BinaryOperator::Lt,
+                        self.end.path(span),
+                        Type::Bool,
+                    ),
+                    Type::Unit,
+                )),
+                Statement::Expression(with_block(
+                    ExpressionWithBlock::If(
+                        Box::new(self.more.path(span)),
+                        block(vec![increment]),
+                        None,
+                        Type::Unit,
+                    ),
+                    span,
+                )),
+            ],
+        }
+    }
+}
+
+impl Protocol for IndexedProtocol {
+    fn setup(&self, iterable: Expression<Typed>, span: Span) -> Vec<Statement<Typed>> {
+        let len = field(self.items.path(span), "len", &Type::U32, span);
+
+        vec![
+            self.items.declare(Mutability::Immutable, iterable, span),
+            self.index
+                .declare(Mutability::Mutable, integer(0, &Type::U32, span), span),
+            self.len.declare(Mutability::Immutable, len, span),
+        ]
+    }
+
+    fn condition(&self, span: Span) -> Expression<Typed> {
+        binary(
+            self.index.path(span),
+            BinaryOperator::Lt,
+            self.len.path(span),
+            Type::Bool,
+        )
+    }
+
+    fn advance(&self, name: Name, span: Span) -> Vec<Statement<Typed>> {
+        let element = without_block(
+            ExpressionWithoutBlock::Index(
+                Box::new(self.items.path(span)),
+                Box::new(self.index.path(span)),
+                self.element.clone(),
+            ),
+            span,
+        );
+
+        let (element, ty) = match &self.borrow {
+            None => (element, self.element.clone()),
+            Some(mutability) => {
+                let operator = match mutability {
+                    Mutability::Immutable => PrefixOperator::Borrow,
+                    Mutability::Mutable => PrefixOperator::MutableBorrow,
+                };
+                let ty = Type::Reference(mutability.clone(), Box::new(self.element.clone()));
+                let borrowed = without_block(
+                    ExpressionWithoutBlock::Prefix(operator, Box::new(element), ty.clone()),
+                    span,
+                );
+                (borrowed, ty)
+            }
+        };
+
+        vec![
+            Statement::Let(Let {
+                name,
+                mutability: Mutability::Immutable,
+                ty: Some(ty),
+                initializer: Some(element),
+                span,
+            }),
+            Statement::Expression(binary(
+                self.index.path(span),
+                BinaryOperator::PlusEqual,
+                integer(1, &Type::U32, span),
+                Type::Unit,
+            )),
+        ]
+    }
+}
+
+impl Hidden {
+    fn new(name: &'static str, ty: &Type, fresh: &mut dyn FnMut() -> LocalId) -> Self {
+        Hidden {
+            name,
+            local: fresh(),
+            ty: ty.clone(),
+        }
+    }
+
+    fn path(&self, span: Span) -> Expression<Typed> {
+        let path = ResolvedPath {
+            segments: vec![Spanned::new(self.name.to_owned(), span)],
+            resolution: Resolution::Local(self.local),
+        };
+        without_block(ExpressionWithoutBlock::Path(path, self.ty.clone()), span)
+    }
+
+    fn declare(
+        &self,
+        mutability: Mutability,
+        initializer: Expression<Typed>,
+        span: Span,
+    ) -> Statement<Typed> {
+        Statement::Let(Let {
+            name: Name {
+                ident: Spanned::new(self.name.to_owned(), span),
+                resolution: Some(Resolution::Local(self.local)),
+            },
+            mutability,
+            ty: Some(self.ty.clone()),
+            initializer: Some(initializer),
+            span,
+        })
+    }
+}
+
+/// Reads the builtin field `name` of type `ty`, such as the `.len` of an array.
+fn field(base: Expression<Typed>, name: &str, ty: &Type, span: Span) -> Expression<Typed> {
+    let name = Name {
+        ident: Spanned::new(name.to_owned(), span),
+        resolution: None,
+    };
+
+    without_block(
+        ExpressionWithoutBlock::Field(Box::new(base), name, ty.clone()),
+        span,
+    )
+}
+
+fn block(statements: Vec<Statement<Typed>>) -> BlockExpression<Typed> {
+    let mut statements = statements.into_iter();
+
+    match statements.next() {
+        None => Vec::new(),
+        Some(first) => vec![Some(Statements::Leading(first, statements.collect(), None))],
+    }
+}
+
+fn integer(value: u32, ty: &Type, span: Span) -> Expression<Typed> {
+    without_block(
+        ExpressionWithoutBlock::Literal(Literal::Integer(value), ty.clone()),
+        span,
+    )
+}
+
+fn binary(
+    left: Expression<Typed>,
+    operator: BinaryOperator,
+    right: Expression<Typed>,
+    ty: Type,
+) -> Expression<Typed> {
+    let span = Span::between(left.span, right.span);
+    without_block(
+        ExpressionWithoutBlock::Binary(Box::new(left), operator, Box::new(right), ty),
+        span,
+    )
+}
+
+fn without_block(kind: ExpressionWithoutBlock<Typed>, span: Span) -> Expression<Typed> {
+    Spanned::new(ExpressionKind::ExpressionWithoutBlock(kind), span)
+}
+
+fn with_block(kind: ExpressionWithBlock<Typed>, span: Span) -> Expression<Typed> {
+    Spanned::new(ExpressionKind::ExpressionWithBlock(kind), span)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::iterate::*;
+
+    fn span() -> Span {
+        Span::new(0, 1)
+    }
+
+    fn range(limits: RangeLimits, end: u32) -> Expression<Typed> {
+        without_block(
+            ExpressionWithoutBlock::Range(
+                Some(Box::new(integer(0, &Type::U8, span()))),
+                Some(Box::new(integer(end, &Type::U8, span()))),
+                limits,
+                Type::Range(limits, Box::new(Type::U8)),
+            ),
+            span(),
+        )
+    }
+
+    fn variable(name: &str, ty: Type) -> Expression<Typed> {
+        let path = ResolvedPath {
+            segments: vec![Spanned::new(name.to_owned(), span())],
+            resolution: Resolution::Local(LocalId(0)),
+        };
+        without_block(ExpressionWithoutBlock::Path(path, ty), span())
+    }
+
+    fn statements(block: &BlockExpression<Typed>) -> Vec<&Statement<Typed>> {
+        match block.as_slice() {
+            [Some(Statements::Leading(first, rest, None))] => {
+                std::iter::once(first).chain(rest.iter()).collect()
+            }
+            _ => panic!(),
+        }
+    }
+
+    fn desugared(iterable: Expression<Typed>) -> Expression<Typed> {
+        let mut next = 1;
+        let protocol = Protocols::new()
+            .protocol(&iterable, &mut || {
+                next += 1;
+                LocalId(next)
+            })
+            .unwrap();
+        let name = Name {
+            ident: Spanned::new("i".to_owned(), span()),
+            resolution: Some(Resolution::Local(LocalId(1))),
+        };
+
+        desugar(
+            protocol.as_ref(),
+            None,
+            name,
+            iterable,
+            Vec::new(),
+            Type::Unit,
+            span(),
+        )
+    }
+
+    // Returns the number of setup statements and the number of statements in the loop body.
+    fn shape(expression: &Expression<Typed>) -> (usize, usize) {
+        let outer = match &expression.item {
+            ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Block(outer, _)) => outer,
+            _ => panic!(),
+        };
+        let outer = statements(outer);
+
+        match outer.last() {
+            Some(Statement::Expression(Spanned {
+                item: ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::While(_, _, body, _)),
+                ..
+            })) => (outer.len() - 1, statements(body).len()),
+            _ => panic!(),
+        }
+    }
+
+    // The type of the loop variable, as bound at the start of the loop body.
+    fn element_type(expression: &Expression<Typed>) -> Type {
+        let ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Block(outer, _)) =
+            &expression.item
+        else {
+            panic!()
+        };
+
+        match statements(outer).last() {
+            Some(Statement::Expression(Spanned {
+                item: ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::While(_, _, body, _)),
+                ..
+            })) => match statements(body)[0] {
+                Statement::Let(Let { ty: Some(ty), .. }) => ty.clone(),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn half_open_range() {
+        // Setup binds the range, then next and end from its bounds; the body binds `i`,
+        // increments, then runs the user's body.
+        let desugared = desugared(range(RangeLimits::HalfOpen, 8));
+
+        assert_eq!(shape(&desugared), (3, 3));
+        assert_eq!(element_type(&desugared), Type::U8);
+    }
+
+    #[test]
+    fn closed_range() {
+        assert_eq!(shape(&desugared(range(RangeLimits::Closed, 255))), (4, 4));
+    }
+
+    #[test]
+    fn array() {
+        let array = Type::Array(Box::new(Type::U16), 4);
+        let reference = Type::Reference(Mutability::Mutable, Box::new(array.clone()));
+        let desugared_reference = desugared(variable("buf", reference));
+        let desugared_value = desugared(variable("buf", array));
+
+        // Iterating through a reference binds references, whether or not it is borrowed in
+        // place.
+        assert_eq!(shape(&desugared_reference), (3, 3));
+        assert_eq!(
+            element_type(&desugared_reference),
+            Type::Reference(Mutability::Mutable, Box::new(Type::U16))
+        );
+        assert_eq!(element_type(&desugared_value), Type::U16);
+    }
+
+    #[test]
+    fn stored_range() {
+        // A range held in a variable is iterated over by its type, like one written in place.
+        let desugared = desugared(variable(
+            "r",
+            Type::Range(RangeLimits::HalfOpen, Box::new(Type::U16)),
+        ));
+
+        assert_eq!(shape(&desugared), (3, 3));
+        assert_eq!(element_type(&desugared), Type::U16);
+    }
+
+    /// Iterates over a `bool` once, binding its value.
+    struct Once(Hidden);
+
+    impl Protocol for Once {
+        fn setup(&self, iterable: Expression<Typed>, span: Span) -> Vec<Statement<Typed>> {
+            vec![self.0.declare(Mutability::Mutable, iterable, span)]
+        }
+
+        fn condition(&self, span: Span) -> Expression<Typed> {
+            self.0.path(span)
+        }
+
+        fn advance(&self, name: Name, span: Span) -> Vec<Statement<Typed>> {
+            vec![Statement::Let(Let {
+                name,
+                mutability: Mutability::Immutable,
+                ty: Some(Type::Bool),
+                initializer: Some(self.0.path(span)),
+                span,
+            })]
+        }
+    }
+
+    #[test]
+    fn registered_protocol() {
+        let iterable = variable("b", Type::Bool);
+        let mut protocols = Protocols::new();
+        assert!(protocols.protocol(&iterable, &mut || LocalId(0)).is_err());
+
+        protocols.register(|ty, fresh| match ty {
+            Type::Bool => Some(Box::new(Once(Hidden::new("once", ty, fresh)))),
+            _ => None,
+        });
+        let protocol = protocols.protocol(&iterable, &mut || LocalId(2)).unwrap();
+        let name = Name {
+            ident: Spanned::new("i".to_owned(), span()),
+            resolution: Some(Resolution::Local(LocalId(1))),
+        };
+        let desugared = desugar(
+            protocol.as_ref(),
+            None,
+            name,
+            iterable,
+            Vec::new(),
+            Type::Unit,
+            span(),
+        );
+
+        assert_eq!(shape(&desugared), (1, 2));
+        assert_eq!(element_type(&desugared), Type::Bool);
+    }
+}
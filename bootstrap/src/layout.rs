@@ -0,0 +1,960 @@
+use std::collections::HashMap;
+
+use crate::attribute::{self, AttributeKind, Target};
+use crate::error::Error;
+use crate::model::base::*;
+use crate::model::def::DefId;
+use crate::model::typed::{pattern_type, type_of, Typed};
+use crate::model::types::Type;
+use crate::model::visit::{
+    walk_expression, walk_item, walk_parameter, walk_pattern, walk_statement, Visitor,
+};
+use crate::span::{Span, Spanned};
+use crate::typeck::def_of;
+
+/// The properties of the target machine that layouts depend on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataModel {
+    pub pointer_size: u32, // In bytes, of references and function pointers alike
+    pub max_align: u32,    // Primitives are aligned to their size, up to this alignment
+}
+
+impl DataModel {
+    pub const BITS_16: DataModel = DataModel {
+        pointer_size: 2,
+        max_align: 2,
+    };
+
+    pub const BITS_32: DataModel = DataModel {
+        pointer_size: 4,
+        max_align: 4,
+    };
+
+    /// The size of the largest value the target can address.
+    pub fn max_size(&self) -> u64 {
+        (1 << (8 * self.pointer_size as u64)) - 1
+    }
+
+    fn scalar(&self, size: u32) -> Layout {
+        Layout {
+            size,
+            align: size.clamp(1, self.max_align),
+            niche: None,
+        }
+    }
+}
+
+impl Default for DataModel {
+    fn default() -> Self {
+        Self::BITS_32
+    }
+}
+
+/// A range of values that a scalar within a type never holds, such as `2..=255` for `bool`.
+/// An enum containing the type can store its other variants as these values, rather than
+/// needing a tag of its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Niche {
+    pub offset: u32,
+    pub size: u32,  // Of the scalar, in bytes
+    pub start: u32, // The first invalid value
+    pub count: u32, // How many consecutive values from `start` are invalid
+}
+
+impl Niche {
+    /// The niche of a scalar of the given size whose only valid values are `0..valid`.
+    fn above(valid: u64, size: u32) -> Option<Niche> {
+        let count = (1u64 << (8 * size as u64)).saturating_sub(valid);
+
+        (count > 0).then(|| Niche {
+            offset: 0,
+            size,
+            start: valid as u32,
+            count: count.min(u32::MAX as u64) as u32,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub size: u32,
+    pub align: u32,
+    pub niche: Option<Niche>,
+}
+
+impl Layout {
+    /// The distance between consecutive values of this layout in an array, which is its size
+    /// rounded up to its alignment.
+    pub fn stride(&self) -> u32 {
+        self.size.next_multiple_of(self.align)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLayout {
+    pub layout: Layout,
+    pub offsets: Vec<u32>, // Of each field, in declaration order
+}
+
+/// How the value of an enum records which of its variants it holds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagEncoding {
+    None,                 // The enum has at most one variant, so there is nothing to record
+    Direct { size: u32 }, // The discriminant is stored at offset 0, before every payload
+    // The variants other than `dataful` are stored in the niche of `dataful`'s payload, as
+    // consecutive values from `niche.start` in declaration order.
+    Niche { dataful: DefId, niche: Niche },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantLayout {
+    pub variant: DefId,
+    pub discriminant: u32, // The index of the variant, in declaration order
+    pub offsets: Vec<u32>, // Of each field of its payload, in declaration order
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumLayout {
+    pub layout: Layout,
+    pub tag: TagEncoding,
+    pub variants: Vec<VariantLayout>,
+}
+
+/// The layouts of every struct and enum in the geode, from which the layout of any other type
+/// follows. Lowering consults these for sizes and offsets, and tooling may query them to
+/// describe memory-mapped structures. The declarations are kept, so that the layouts of generic
+/// enum instances that only monomorphization reveals can be computed when they are found.
+#[derive(Debug)]
+pub struct Layouts {
+    model: DataModel,
+    structs: HashMap<DefId, StructLayout>,
+    enums: HashMap<DefId, Vec<(Vec<Type>, EnumLayout)>>, // Each instance, by its type arguments
+    declarations: HashMap<DefId, Declaration>,
+    names: HashMap<DefId, Spanned<String>>, // Of each struct and enum, for diagnostics
+}
+
+impl Layouts {
+    pub fn model(&self) -> DataModel {
+        self.model
+    }
+
+    pub fn of_struct(&self, def: DefId) -> Option<&StructLayout> {
+        self.structs.get(&def)
+    }
+
+    /// The layout of an enum instantiated with the given type arguments, which are empty
+    /// unless the enum is generic.
+    pub fn of_enum(&self, def: DefId, arguments: &[Type]) -> Option<&EnumLayout> {
+        self.enums
+            .get(&def)?
+            .iter()
+            .find(|(instance, _)| instance == arguments)
+            .map(|(_, layout)| layout)
+    }
+
+    /// Computes the layouts of the generic enum instances within `ty`, a monomorphic type that
+    /// the geode need not mention itself, such as `Option[u8]` within an instance of
+    /// `fn some[T](value: T) -> Option[T]`. Arrays too large for the target are reported at
+    /// `span`.
+    pub fn instantiate(&mut self, ty: &Type, span: Span) -> Vec<Error> {
+        let mut computer = Computer {
+            mentioned: Vec::new(),
+            span,
+            layouts: self,
+            errors: Vec::new(),
+        };
+
+        computer.instances(ty, span);
+        computer.errors
+    }
+
+    /// References and function pointers are never null, which leaves `0` as a niche.
+    fn pointer(&self) -> Layout {
+        let size = self.model.pointer_size;
+
+        Layout {
+            niche: Some(Niche {
+                offset: 0,
+                size,
+                start: 0,
+                count: 1,
+            }),
+            ..self.model.scalar(size)
+        }
+    }
+
+    /// The layout of a value of the given type. Types without a size known at compile time,
+    /// `str` and slices, only exist behind references, and have no layout of their own. Arrays
+    /// too large for the target are reported as layouts are computed, and their sizes saturate.
+    pub fn of(&self, ty: &Type) -> Layout {
+        match ty {
+            Type::U8 | Type::I8 => self.model.scalar(1),
+            Type::U16 | Type::I16 => self.model.scalar(2),
+            Type::U32 | Type::I32 | Type::Char => self.model.scalar(4),
+            Type::Bool => Layout {
+                niche: Niche::above(2, 1),
+                ..self.model.scalar(1)
+            },
+            Type::Unit | Type::Never => self.model.scalar(0),
+            Type::Array(element, length) => {
+                let element = self.of(element);
+                Layout {
+                    size: element.stride().saturating_mul(*length),
+                    niche: element.niche.filter(|_| *length > 0),
+                    ..element
+                }
+            }
+            // The start, followed by the end.
+            Type::Range(_, bound) => {
+                let bound = self.of(bound);
+                Layout {
+                    size: bound.stride() * 2,
+                    ..bound
+                }
+            }
+            // References to unsized types also carry the length of what they refer to.
+            Type::Reference(_, inner) => {
+                let pointer = self.pointer();
+
+                match **inner {
+                    Type::Str | Type::Slice(_) => Layout {
+                        size: 2 * pointer.size,
+                        ..pointer
+                    },
+                    _ => pointer,
+                }
+            }
+            Type::Function(_, _) => self.pointer(),
+            Type::Named(def, _, arguments) => {
+                match (self.structs.get(def), self.of_enum(*def, arguments)) {
+                    (Some(structure), _) => structure.layout.clone(),
+                    (_, Some(enumeration)) => enumeration.layout.clone(),
+                    _ => unreachable!("Layouts are computed for every struct and enum instance"),
+                }
+            }
+            Type::Str | Type::Slice(_) => unreachable!("Unsized types have no layout"),
+            Type::Parameter(_, _) | Type::Infer(_, _) => {
+                unreachable!("Layouts are only computed for monomorphic types")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Declaration {
+    Struct {
+        packed: bool,
+        fields: Vec<(Type, Span)>,
+    },
+    Enum {
+        repr: Option<Spanned<Type>>,
+        parameters: Vec<DefId>, // Replaced by the type arguments of each instance
+        variants: Vec<(DefId, Vec<(Type, Span)>)>,
+    },
+}
+
+/// The fields of a struct or of a variant's payload, laid out one after another.
+struct Fields {
+    offsets: Vec<u32>,
+    end: u64,
+    align: u32,
+    niche: Option<Niche>,
+}
+
+/// Computes the layout of every struct and enum of a well-typed geode.
+///
+/// Fields are laid out in declaration order, each at the next offset suited to its alignment,
+/// unless the struct is `@packed`, in which case there is no padding and the struct is
+/// aligned to a single byte. An enum stores its discriminant as a tag before the payloads of
+/// its variants, in the smallest integer type that fits, unless `@repr` names the type. An
+/// enum whose variants all lack payloads is just its tag, and the values the tag never takes
+/// form a niche. Enums with a single variant carrying a payload store the other variants in
+/// a niche of that payload instead, where one is large enough, so that they need no tag.
+///
+/// A generic enum has a layout for each instance of it with concrete type arguments that the
+/// geode mentions, each computed as if the arguments were written in place of its parameters.
+pub fn compute(geode: &Geode<Typed>, model: DataModel) -> Result<Layouts, Vec<Error>> {
+    let mut layouts = Layouts {
+        model,
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        declarations: HashMap::new(),
+        names: HashMap::new(),
+    };
+    let mut computer = Computer {
+        mentioned: Vec::new(),
+        span: Span::new(0, 0),
+        layouts: &mut layouts,
+        errors: Vec::new(),
+    };
+
+    computer.visit_geode(geode);
+
+    // Computing in declaration order keeps the reports deterministic.
+    let mut definitions = computer
+        .layouts
+        .declarations
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    definitions.sort();
+
+    for def in definitions {
+        if let Some(Declaration::Enum { parameters, .. }) = computer.layouts.declarations.get(&def)
+        {
+            if !parameters.is_empty() {
+                continue;
+            }
+        }
+
+        computer.named(def, &[]);
+    }
+
+    for (ty, span) in std::mem::take(&mut computer.mentioned) {
+        computer.instances(&ty, span);
+    }
+
+    match computer.errors.is_empty() {
+        true => Ok(layouts),
+        false => Err(computer.errors),
+    }
+}
+
+struct Computer<'a> {
+    mentioned: Vec<(Type, Span)>, // The types of the geode, each where it is first mentioned
+    span: Span,                   // The innermost item, parameter or `let` statement being visited
+    layouts: &'a mut Layouts,
+    errors: Vec<Error>,
+}
+
+impl Computer<'_> {
+    /// Computes the layout of a struct or enum instance, unless it is known already. Each
+    /// struct and enum contained within it by value is computed first, which always ends since
+    /// type checking rejects types containing themselves.
+    fn named(&mut self, def: DefId, arguments: &[Type]) {
+        if self.layouts.structs.contains_key(&def) || self.layouts.of_enum(def, arguments).is_some()
+        {
+            return;
+        }
+
+        match self.layouts.declarations.get(&def).cloned() {
+            Some(Declaration::Struct { packed, fields }) => {
+                let fields = self.fields(&fields, 0, packed);
+                let size = self.size(fields.end.next_multiple_of(fields.align as u64), def);
+
+                self.layouts.structs.insert(
+                    def,
+                    StructLayout {
+                        layout: Layout {
+                            size,
+                            align: fields.align,
+                            niche: fields.niche,
+                        },
+                        offsets: fields.offsets,
+                    },
+                );
+            }
+            Some(Declaration::Enum {
+                repr,
+                parameters,
+                variants,
+            }) => {
+                let substitution = parameters
+                    .into_iter()
+                    .zip(arguments.iter().cloned())
+                    .collect::<Vec<_>>();
+
+                let variants = variants
+                    .into_iter()
+                    .map(|(variant, fields)| {
+                        let fields = fields
+                            .into_iter()
+                            .map(|(ty, span)| (ty.substitute(&substitution), span))
+                            .collect();
+                        (variant, fields)
+                    })
+                    .collect();
+
+                let layout = self.enumeration(def, repr, variants);
+                let instances = self.layouts.enums.entry(def).or_default();
+                instances.push((arguments.to_vec(), layout));
+            }
+            None => {}
+        }
+    }
+
+    /// Computes the layouts of the generic enum instances within a monomorphic type, and checks
+    /// that the arrays within it can be stored by value.
+    fn instances(&mut self, ty: &Type, span: Span) {
+        match ty {
+            Type::Named(def, _, arguments) if !arguments.is_empty() => {
+                for argument in arguments {
+                    self.instances(argument, span);
+                }
+
+                self.named(*def, arguments);
+            }
+            Type::Array(_, _) => {
+                self.layout(ty, span);
+            }
+            Type::Slice(inner) | Type::Reference(_, inner) | Type::Range(_, inner) => {
+                self.instances(inner, span)
+            }
+            Type::Function(parameters, return_type) => {
+                for parameter in parameters {
+                    self.instances(parameter, span);
+                }
+
+                self.instances(return_type, span);
+            }
+            _ => {}
+        }
+    }
+
+    fn mention(&mut self, ty: &Type, span: Span) {
+        if !ty.is_generic() && !self.mentioned.iter().any(|(mentioned, _)| mentioned == ty) {
+            self.mentioned.push((ty.clone(), span));
+        }
+    }
+
+    /// Checks that an array of `length` elements of the given layout fits within the target's
+    /// address space.
+    fn fits(&mut self, ty: &Type, element: &Layout, length: u32, span: Span) -> bool {
+        if element.stride() as u64 * length as u64 > self.layouts.model.max_size() {
+            self.errors.push(Error::new(
+                span,
+                format!("Type `{ty}` is too large for the target"),
+            ));
+            return false;
+        }
+
+        true
+    }
+
+    /// The layout of a field or array type, reporting types that cannot be stored by value.
+    fn layout(&mut self, ty: &Type, span: Span) -> Option<Layout> {
+        match ty {
+            Type::Str | Type::Slice(_) => {
+                self.errors.push(Error::with_details(
+                    span,
+                    format!("The size of `{ty}` is not known at compile time"),
+                    format!("Store it behind a reference, such as `&{ty}`"),
+                ));
+                return None;
+            }
+            Type::Named(def, _, arguments) => self.named(*def, arguments),
+            Type::Array(element, length) => {
+                let element = self.layout(element, span)?;
+
+                if !self.fits(ty, &element, *length, span) {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+
+        Some(self.layouts.of(ty))
+    }
+
+    fn fields(&mut self, fields: &[(Type, Span)], start: u64, packed: bool) -> Fields {
+        let mut offsets = Vec::new();
+        let mut end = start;
+        let mut align = 1;
+        let mut niche: Option<Niche> = None;
+
+        for (ty, span) in fields {
+            let Some(layout) = self.layout(ty, *span) else {
+                offsets.push(end as u32);
+                continue;
+            };
+
+            let field_align = if packed { 1 } else { layout.align };
+            let offset = end.next_multiple_of(field_align as u64);
+
+            // The largest niche leaves the most room for enums containing this type.
+            if let Some(field_niche) = layout.niche {
+                if niche.is_none_or(|niche| field_niche.count > niche.count) {
+                    niche = Some(Niche {
+                        offset: offset as u32 + field_niche.offset,
+                        ..field_niche
+                    });
+                }
+            }
+
+            offsets.push(offset as u32);
+            end = offset + layout.size as u64;
+            align = align.max(field_align);
+        }
+
+        Fields {
+            offsets,
+            end,
+            align,
+            niche,
+        }
+    }
+
+    fn enumeration(
+        &mut self,
+        def: DefId,
+        repr: Option<Spanned<Type>>,
+        variants: Vec<(DefId, Vec<(Type, Span)>)>,
+    ) -> EnumLayout {
+        let count = variants.len() as u64;
+
+        // Payloads are first laid out from offset 0, as they would be without a tag.
+        let untagged = variants
+            .iter()
+            .map(|(_, fields)| self.fields(fields, 0, false))
+            .collect::<Vec<_>>();
+
+        let mut dataful = untagged
+            .iter()
+            .enumerate()
+            .filter(|(_, fields)| fields.end > 0);
+
+        let niche = match (&repr, dataful.next(), dataful.next()) {
+            (None, Some((index, fields)), None) if count > 1 => fields
+                .niche
+                .filter(|niche| niche.count as u64 >= count - 1)
+                .map(|niche| (index, niche)),
+            _ => None,
+        };
+
+        let layout = |fields: &[Fields], tag: TagEncoding, niche, computer: &mut Self| {
+            let align = fields.iter().map(|fields| fields.align).max().unwrap_or(1);
+            let end = fields.iter().map(|fields| fields.end).max().unwrap_or(0);
+
+            EnumLayout {
+                layout: Layout {
+                    size: computer.size(end.next_multiple_of(align as u64), def),
+                    align,
+                    niche,
+                },
+                tag,
+                variants: variants
+                    .iter()
+                    .zip(fields)
+                    .enumerate()
+                    .map(|(discriminant, ((variant, _), fields))| VariantLayout {
+                        variant: *variant,
+                        discriminant: discriminant as u32,
+                        offsets: fields.offsets.clone(),
+                    })
+                    .collect(),
+            }
+        };
+
+        if let Some((index, niche)) = niche {
+            let others = count as u32 - 1;
+            let remaining = (niche.count > others).then(|| Niche {
+                start: niche.start + others,
+                count: niche.count - others,
+                ..niche
+            });
+
+            let tag = TagEncoding::Niche {
+                dataful: variants[index].0,
+                niche,
+            };
+
+            return layout(&untagged, tag, remaining, self);
+        }
+
+        if repr.is_none() && count <= 1 {
+            let niche = untagged.first().and_then(|fields| fields.niche);
+            return layout(&untagged, TagEncoding::None, niche, self);
+        }
+
+        let tag = match &repr {
+            Some(repr) => {
+                let tag = self.layouts.of(&repr.item);
+                let max = match repr.item {
+                    Type::I8 | Type::I16 | Type::I32 => (1u64 << (8 * tag.size - 1)) - 1,
+                    _ => (1u64 << (8 * tag.size)) - 1,
+                };
+
+                if count > max + 1 {
+                    self.errors.push(Error::with_details(
+                        repr.span,
+                        format!(
+                            "Enum `{}` has too many variants for its tag",
+                            self.name(def).item
+                        ),
+                        format!(
+                            "`{}` can distinguish at most {} variants, but the enum has {count}",
+                            repr.item,
+                            max + 1
+                        ),
+                    ));
+                }
+
+                tag
+            }
+            None if count <= 1 << 8 => self.layouts.of(&Type::U8),
+            None if count <= 1 << 16 => self.layouts.of(&Type::U16),
+            None => self.layouts.of(&Type::U32),
+        };
+
+        let tagged = variants
+            .iter()
+            .map(|(_, fields)| {
+                let mut fields = self.fields(fields, tag.size as u64, false);
+                fields.align = fields.align.max(tag.align);
+                fields
+            })
+            .collect::<Vec<_>>();
+
+        let niche = Niche::above(count, tag.size);
+        layout(&tagged, TagEncoding::Direct { size: tag.size }, niche, self)
+    }
+
+    /// Checks that a struct or enum of the given size fits within the target's address space.
+    fn size(&mut self, size: u64, def: DefId) -> u32 {
+        let max = self.layouts.model.max_size();
+
+        if size > max {
+            let name = self.name(def);
+            self.errors.push(Error::with_details(
+                name.span,
+                format!("Type `{}` is too large for the target", name.item),
+                format!("Its size of {size} bytes exceeds the maximum of {max}"),
+            ));
+            return 0;
+        }
+
+        size as u32
+    }
+
+    fn name(&self, def: DefId) -> Spanned<String> {
+        self.layouts.names[&def].clone()
+    }
+}
+
+impl Visitor<Typed> for Computer<'_> {
+    fn visit_item(&mut self, item: &Item<Typed>) {
+        match &item.kind {
+            ItemKind::Struct(structure) => {
+                let (attributes, _) = attribute::validate(&item.attributes, Target::Struct);
+                let def = def_of(&structure.name);
+
+                self.layouts.names.insert(def, structure.name.ident.clone());
+                self.layouts.declarations.insert(
+                    def,
+                    Declaration::Struct {
+                        packed: attributes.contains(&AttributeKind::Packed),
+                        fields: fields(&structure.fields),
+                    },
+                );
+            }
+            ItemKind::Enum(enumeration) => {
+                let (attributes, _) = attribute::validate(&item.attributes, Target::Enum);
+                let def = def_of(&enumeration.name);
+
+                let repr = attributes
+                    .iter()
+                    .find_map(|attribute| match &attribute.item {
+                        AttributeKind::Repr(ty) => Some(Spanned::new(ty.clone(), attribute.span)),
+                        _ => None,
+                    });
+
+                let variants = enumeration
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        let fields = match &variant.kind {
+                            VariantKind::Unit => Vec::new(),
+                            VariantKind::Tuple(types) => types
+                                .iter()
+                                .map(|ty| (ty.clone(), variant.name.ident.span))
+                                .collect(),
+                            VariantKind::Struct(struct_fields) => fields(struct_fields),
+                        };
+
+                        (def_of(&variant.name), fields)
+                    })
+                    .collect();
+
+                let parameters = enumeration
+                    .generics
+                    .iter()
+                    .map(|generic| def_of(&generic.name))
+                    .collect();
+
+                self.layouts
+                    .names
+                    .insert(def, enumeration.name.ident.clone());
+                self.layouts.declarations.insert(
+                    def,
+                    Declaration::Enum {
+                        repr,
+                        parameters,
+                        variants,
+                    },
+                );
+            }
+            _ => {
+                let span = std::mem::replace(&mut self.span, item.span);
+                walk_item(self, item);
+                self.span = span;
+            }
+        }
+    }
+
+    fn visit_parameter(&mut self, parameter: &Parameter<Typed>) {
+        let span = std::mem::replace(&mut self.span, parameter.span);
+        walk_parameter(self, parameter);
+        self.span = span;
+    }
+
+    fn visit_statement(&mut self, statement: &Statement<Typed>) {
+        let span = match statement {
+            Statement::Let(Let { span, .. }) => std::mem::replace(&mut self.span, *span),
+            _ => self.span,
+        };
+        walk_statement(self, statement);
+        self.span = span;
+    }
+
+    fn visit_expression(&mut self, expression: &Expression<Typed>) {
+        self.mention(type_of(expression), expression.span);
+        walk_expression(self, expression);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern<Typed>) {
+        self.mention(pattern_type(&pattern.item), pattern.span);
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        self.mention(ty, self.span);
+    }
+}
+
+fn fields(fields: &[Field<Typed>]) -> Vec<(Type, Span)> {
+    fields
+        .iter()
+        .map(|field| (field.ty.clone(), field.name.ident.span))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::*;
+    use crate::model::def::DefKind;
+    use crate::model::resolved::Resolution;
+    use crate::testing::{self, messages, span};
+
+    fn compute_source(source: &str, model: DataModel) -> Result<(Layouts, Vec<DefId>), Vec<Error>> {
+        let (geode, _, _) = testing::typed(source);
+
+        // The definitions of the geode's structs and enums, in declaration order.
+        let types = geode.modules[0]
+            .items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Struct(StructItem { name, .. })
+                | ItemKind::Enum(EnumItem { name, .. }) => match name.resolution {
+                    Some(Resolution::Def(DefKind::Struct | DefKind::Enum, def)) => Some(def),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+
+        compute(&geode, model).map(|layouts| (layouts, types))
+    }
+
+    fn layout(size: u32, align: u32, niche: Option<Niche>) -> Layout {
+        Layout { size, align, niche }
+    }
+
+    #[test]
+    fn structs() {
+        let source = "
+            struct S { a: u8, b: u32, c: u16 }
+            @packed struct P { a: u8, b: u32, c: u16 }
+        ";
+
+        let (layouts, types) = compute_source(source, DataModel::BITS_32).unwrap();
+        let s = layouts.of_struct(types[0]).unwrap();
+        let p = layouts.of_struct(types[1]).unwrap();
+
+        assert_eq!(s.offsets, vec![0, 4, 8]);
+        assert_eq!(s.layout, layout(12, 4, None));
+        assert_eq!(p.offsets, vec![0, 1, 5]);
+        assert_eq!(p.layout, layout(7, 1, None));
+
+        let (layouts, types) = compute_source(source, DataModel::BITS_16).unwrap();
+        let s = layouts.of_struct(types[0]).unwrap();
+
+        assert_eq!(s.offsets, vec![0, 2, 6]);
+        assert_eq!(s.layout, layout(8, 2, None));
+    }
+
+    #[test]
+    fn arrays_and_references() {
+        let layouts = compute_source("", DataModel::BITS_16).unwrap().0;
+        let reference = |inner| Type::Reference(Mutability::Immutable, Box::new(inner));
+        let null = Some(Niche {
+            offset: 0,
+            size: 2,
+            start: 0,
+            count: 1,
+        });
+
+        assert_eq!(
+            layouts.of(&Type::Array(Box::new(Type::U16), 3)),
+            layout(6, 2, None)
+        );
+        assert_eq!(layouts.of(&reference(Type::U32)), layout(2, 2, null));
+        assert_eq!(layouts.of(&reference(Type::Str)), layout(4, 2, null));
+        assert_eq!(
+            layouts.of(&Type::Array(Box::new(Type::Bool), 0)).niche,
+            None
+        );
+
+        let source = "
+            struct Pair { a: u32, b: u8 }
+            struct Pairs { pairs: [Pair; 2] }
+        ";
+
+        let (layouts, types) = compute_source(source, DataModel::BITS_32).unwrap();
+
+        assert_eq!(layouts.of_struct(types[0]).unwrap().layout.stride(), 8);
+        assert_eq!(
+            layouts.of_struct(types[1]).unwrap().layout,
+            layout(16, 4, None)
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let source = "
+            enum Direction { Up, Down, Left }
+            enum Single { Only }
+            enum Maybe { No, Yes(&u32) }
+            enum Choice { A(u8), B(u32) }
+            enum Nested { X, Y(Direction) }
+        ";
+
+        let (layouts, types) = compute_source(source, DataModel::BITS_32).unwrap();
+
+        let direction = layouts.of_enum(types[0], &[]).unwrap();
+        let tag_niche = |start, count| Niche {
+            offset: 0,
+            size: 1,
+            start,
+            count,
+        };
+
+        assert_eq!(direction.layout, layout(1, 1, Some(tag_niche(3, 253))));
+        assert_eq!(direction.tag, TagEncoding::Direct { size: 1 });
+
+        let unit = layouts.of_enum(types[1], &[]).unwrap();
+        assert_eq!(unit.layout, layout(0, 1, None));
+        assert_eq!(unit.tag, TagEncoding::None);
+
+        let maybe = layouts.of_enum(types[2], &[]).unwrap();
+        assert_eq!(maybe.layout, layout(4, 4, None));
+        assert!(matches!(maybe.tag, TagEncoding::Niche { niche, .. } if niche.start == 0));
+
+        let choice = layouts.of_enum(types[3], &[]).unwrap();
+        assert_eq!(choice.layout.size, 8);
+        assert_eq!(choice.tag, TagEncoding::Direct { size: 1 });
+        assert_eq!(
+            choice
+                .variants
+                .iter()
+                .map(|variant| variant.offsets.clone())
+                .collect::<Vec<_>>(),
+            vec![vec![1], vec![4]]
+        );
+
+        // `X` takes the first value `Direction` never holds.
+        let nested = layouts.of_enum(types[4], &[]).unwrap();
+        assert_eq!(nested.layout, layout(1, 1, Some(tag_niche(4, 252))));
+        assert_eq!(
+            nested.tag,
+            TagEncoding::Niche {
+                dataful: nested.variants[1].variant,
+                niche: tag_niche(3, 253),
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let variants = (0..257)
+            .map(|index| format!("V{index}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let source = format!(
+            r#"
+            @repr("u8") enum Many {{ {variants} }}
+            struct Bytes {{ bytes: [u8] }}
+            "#
+        );
+
+        let errors = compute_source(&source, DataModel::BITS_32).err().unwrap();
+
+        assert_eq!(
+            messages(&errors),
+            vec![
+                (
+                    "Enum `Many` has too many variants for its tag",
+                    Some("`u8` can distinguish at most 256 variants, but the enum has 257"),
+                    span(&source, r#"@repr("u8")"#)
+                ),
+                (
+                    "The size of `[u8]` is not known at compile time",
+                    Some("Store it behind a reference, such as `&[u8]`"),
+                    span(&source, "bytes")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn large_arrays() {
+        let source = "
+            fn f(a: [u32; 20000], b: &[str; 2]) {
+                let x: [u8; 70000];
+                let y: [[u16; 40000]; 2];
+            }
+        ";
+
+        let errors = compute_source(source, DataModel::BITS_16).err().unwrap();
+
+        assert_eq!(
+            messages(&errors),
+            vec![
+                (
+                    "Type `[u32; 20000]` is too large for the target",
+                    None,
+                    span(source, "a: [u32; 20000]")
+                ),
+                (
+                    "The size of `str` is not known at compile time",
+                    Some("Store it behind a reference, such as `&str`"),
+                    span(source, "b: &[str; 2]")
+                ),
+                (
+                    "Type `[u8; 70000]` is too large for the target",
+                    None,
+                    span(source, "let x: [u8; 70000];")
+                ),
+                (
+                    "Type `[u16; 40000]` is too large for the target",
+                    None,
+                    span(source, "let y: [[u16; 40000]; 2];")
+                ),
+            ]
+        );
+
+        // Querying the layout of an array too large for the target does not overflow.
+        let (layouts, _) = compute_source("", DataModel::BITS_32).unwrap();
+        let huge = Type::Array(Box::new(Type::U32), 2_000_000_000);
+        assert_eq!(layouts.of(&huge).size, u32::MAX);
+    }
+}
@@ -0,0 +1,1034 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::attribute::{self, AttributeKind, Target};
+use crate::error::Error;
+use crate::model::ast::TypeKind;
+use crate::model::base::*;
+use crate::model::def::{DefId, DefKind, Definitions};
+use crate::model::resolved::{self, LocalId, Name, Resolution, Resolved, ResolvedPath};
+use crate::model::ttg::TreeData;
+use crate::model::typed::{type_of, Typed};
+use crate::model::types::Type;
+use crate::model::visit::{self, NameContext, PathContext, Visitor};
+use crate::span::{Span, Spanned};
+
+/// How a lint is reported. `allow` silences it, `warn` reports it without stopping the build,
+/// and `deny` reports it as an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    /// The level named by an attribute or command line option, as in `@deny` or `--deny`.
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    UnusedVariables,
+    UnusedImports,
+    UnusedFunctions,
+    DeadCode, // Private types, constants and statics that are never used
+    ShadowedBindings,
+    NonSnakeCase,
+    RedundantParentheses,
+    UnreachableCode,
+    UnreachablePatterns, // `when` arms matching nothing that the arms before them do not
+}
+
+/// Every lint, with the name it is referred to by and the level it has unless set otherwise.
+const LINTS: &[(Lint, &str, Level)] = &[
+    (Lint::UnusedVariables, "unused_variables", Level::Warn),
+    (Lint::UnusedImports, "unused_imports", Level::Warn),
+    (Lint::UnusedFunctions, "unused_functions", Level::Warn),
+    (Lint::DeadCode, "dead_code", Level::Warn),
+    // Shadowing is a common way to transform a value under the same name.
+    (Lint::ShadowedBindings, "shadowed_bindings", Level::Allow),
+    (Lint::NonSnakeCase, "non_snake_case", Level::Warn),
+    (
+        Lint::RedundantParentheses,
+        "redundant_parentheses",
+        Level::Warn,
+    ),
+    (Lint::UnreachableCode, "unreachable_code", Level::Warn),
+    (
+        Lint::UnreachablePatterns,
+        "unreachable_patterns",
+        Level::Warn,
+    ),
+];
+
+impl Lint {
+    pub fn named(name: &str) -> Option<Self> {
+        LINTS
+            .iter()
+            .find(|(_, lint_name, _)| *lint_name == name)
+            .map(|(lint, _, _)| *lint)
+    }
+
+    pub fn name(self) -> &'static str {
+        LINTS.iter().find(|(lint, _, _)| *lint == self).unwrap().1
+    }
+
+    pub fn default_level(self) -> Level {
+        LINTS.iter().find(|(lint, _, _)| *lint == self).unwrap().2
+    }
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The lint levels set on the command line. A lint that is not set there has its default level.
+#[derive(Debug, Clone, Default)]
+pub struct Levels(Vec<(Lint, Level)>);
+
+impl Levels {
+    /// Sets the level of a lint, overriding any level it was given before.
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.0.retain(|(set, _)| *set != lint);
+        self.0.push((lint, level));
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.0
+            .iter()
+            .find(|(set, _)| *set == lint)
+            .map_or(lint.default_level(), |(_, level)| *level)
+    }
+}
+
+/// The diagnostics of the lints, split by the level they were reported at. Allowed lints are
+/// left out.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub warnings: Vec<(Lint, Error)>,
+    pub errors: Vec<(Lint, Error)>, // From denied lints, which fail the build
+}
+
+impl Report {
+    pub fn emit(&mut self, lint: Lint, level: Level, error: Error) {
+        match level {
+            Level::Allow => {}
+            Level::Warn => self.warnings.push((lint, error)),
+            Level::Deny => self.errors.push((lint, error)),
+        }
+    }
+
+    pub fn extend(&mut self, other: Report) {
+        self.warnings.extend(other.warnings);
+        self.errors.extend(other.errors);
+    }
+}
+
+/// The line printed at the end of a build, counting the warnings and errors reported during it.
+/// `None` if there was nothing to report.
+pub fn summary(warnings: usize, errors: usize) -> Option<String> {
+    let count = |count: usize, noun: &str| match count {
+        1 => format!("1 {noun}"),
+        _ => format!("{count} {noun}s"),
+    };
+
+    match (warnings, errors) {
+        (0, 0) => None,
+        (warnings, 0) => Some(format!(
+            "Build finished with {}",
+            count(warnings, "warning")
+        )),
+        (0, errors) => Some(format!("Build failed with {}", count(errors, "error"))),
+        (warnings, errors) => Some(format!(
+            "Build failed with {} and {}",
+            count(errors, "error"),
+            count(warnings, "warning")
+        )),
+    }
+}
+
+/// The lint levels in effect at some point of a walk. Levels set by the attributes of the
+/// enclosing declarations take precedence over the command line, the innermost first. Passes
+/// outside this module that report lints of their own track levels with this too.
+pub struct Scopes<'a> {
+    levels: &'a Levels,
+    set: Vec<(Lint, Level)>,
+}
+
+impl<'a> Scopes<'a> {
+    pub fn new(levels: &'a Levels) -> Self {
+        Self {
+            levels,
+            set: Vec::new(),
+        }
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.set
+            .iter()
+            .rev()
+            .find(|(set, _)| *set == lint)
+            .map_or_else(|| self.levels.level(lint), |(_, level)| *level)
+    }
+
+    /// Applies the levels set by a declaration's attributes, until `exit` is given the returned
+    /// depth. Invalid attributes are left to `attribute::check` to report.
+    pub fn enter<P: TreeData<P>>(&mut self, attributes: &[Attribute<P>], target: Target) -> usize {
+        let depth = self.set.len();
+        self.set
+            .extend(attribute::validate(attributes, target).0.lints());
+        depth
+    }
+
+    pub fn exit(&mut self, depth: usize) {
+        self.set.truncate(depth);
+    }
+}
+
+/// Runs the lints over a resolved geode. Levels set on an out-of-line `mod` declaration only
+/// apply to the declaration itself, not to the items within the module's file.
+pub fn check(geode: &Geode<Resolved>, definitions: &Definitions, levels: &Levels) -> Report {
+    let mut linter = Linter {
+        definitions,
+        scopes: Scopes::new(levels),
+        report: Report::default(),
+        bindings: Vec::new(),
+        variables: Vec::new(),
+        locals: HashMap::new(),
+        imports: Vec::new(),
+        items: Vec::new(),
+        used: HashSet::new(),
+        item: None,
+        associated: false,
+        signature_only: false,
+    };
+
+    linter.visit_geode(geode);
+    linter.finish();
+    linter.report
+}
+
+/// Runs the lints that need to know the types of expressions, over a type checked geode.
+pub fn check_typed(geode: &Geode<Typed>, levels: &Levels) -> Report {
+    let mut reachability = Reachability {
+        scopes: Scopes::new(levels),
+        report: Report::default(),
+    };
+
+    reachability.visit_geode(geode);
+    reachability.report
+}
+
+/// A declaration that is reported unless something uses it, along with the level of its lint
+/// where it is declared.
+struct Unused {
+    lint: Lint,
+    level: Level,
+    error: Error,
+    used: bool,
+}
+
+struct Import {
+    name: String,
+    unused: Unused,
+}
+
+struct Linter<'a> {
+    definitions: &'a Definitions,
+    scopes: Scopes<'a>,
+    report: Report,
+    bindings: Vec<Vec<Spanned<String>>>, // The names bound in each enclosing scope, for shadowing
+    variables: Vec<Unused>,
+    locals: HashMap<LocalId, usize>, // Indices into `variables`
+    imports: Vec<Vec<Import>>,       // The private imports of each enclosing module
+    items: Vec<(DefId, Unused)>,
+    used: HashSet<DefId>,
+    item: Option<DefId>, // The outermost item being walked, whose uses of itself do not count
+    associated: bool,    // Whether functions belong to an `impl` or `trait` block
+    signature_only: bool, // Whether the function being walked has no body
+}
+
+impl Linter<'_> {
+    fn emit(&mut self, lint: Lint, error: Error) {
+        let level = self.scopes.level(lint);
+        self.report.emit(lint, level, error);
+    }
+
+    fn unused(&self, lint: Lint, error: Error) -> Unused {
+        Unused {
+            lint,
+            level: self.scopes.level(lint),
+            error,
+            used: false,
+        }
+    }
+
+    /// Reports what was never used, once the whole geode has been walked.
+    fn finish(&mut self) {
+        let used = &self.used;
+        let items = self
+            .items
+            .drain(..)
+            .map(|(def, mut item)| {
+                item.used = used.contains(&def);
+                item
+            })
+            .collect::<Vec<_>>();
+
+        for unused in std::mem::take(&mut self.variables).into_iter().chain(items) {
+            if !unused.used {
+                self.report.emit(unused.lint, unused.level, unused.error);
+            }
+        }
+    }
+
+    fn enter_module(&mut self) {
+        self.imports.push(Vec::new());
+    }
+
+    fn exit_module(&mut self) {
+        let imports = self
+            .imports
+            .pop()
+            .expect("Modules should be exited exactly as many times as they are entered");
+
+        for import in imports.into_iter().filter(|import| !import.unused.used) {
+            let Unused {
+                lint, level, error, ..
+            } = import.unused;
+            self.report.emit(lint, level, error);
+        }
+    }
+
+    /// Records the names a private `use` tree imports. Glob imports are never reported.
+    fn import(&mut self, tree: &UseTree<Resolved>) {
+        let name = match &tree.kind {
+            UseTreeKind::Simple(Some(alias)) => &alias.ident,
+            UseTreeKind::Simple(None) => match tree.prefix.segments.last() {
+                Some(last) => last,
+                None => return,
+            },
+            UseTreeKind::Glob => return,
+            UseTreeKind::Nested(trees, _) => {
+                for tree in trees {
+                    self.import(tree);
+                }
+                return;
+            }
+        };
+
+        let unused = self.unused(
+            Lint::UnusedImports,
+            Error::new(tree.prefix.span(), format!("Unused import `{}`", name.item)),
+        );
+
+        if let Some(imports) = self.imports.last_mut() {
+            imports.push(Import {
+                name: name.item.clone(),
+                unused,
+            });
+        }
+    }
+
+    /// Records a private item, to be reported if nothing outside of it uses it. Associated
+    /// functions are left out, since their uses through values are only known once types are.
+    fn declare(&mut self, item: &Item<Resolved>) {
+        let (name, lint) = match &item.kind {
+            ItemKind::Function(function) if !self.associated => {
+                let attributes = attribute::validate(&item.attributes, Target::Function).0;
+                let entry = function.name.ident.item == "main"
+                    || attributes.contains(&AttributeKind::Test)
+                    || attributes.contains(&AttributeKind::Interrupt);
+
+                if entry {
+                    return;
+                }
+                (&function.name, Lint::UnusedFunctions)
+            }
+            ItemKind::Struct(StructItem { name, .. })
+            | ItemKind::Enum(EnumItem { name, .. })
+            | ItemKind::TypeAlias(TypeAliasItem { name, .. })
+            | ItemKind::Const(ConstItem { name, .. })
+            | ItemKind::Static(StaticItem { name, .. }) => (name, Lint::DeadCode),
+            _ => return,
+        };
+
+        if let Some(Resolution::Def(kind, def)) = name.resolution {
+            let unused = self.unused(
+                lint,
+                Error::new(
+                    name.ident.span,
+                    format!("Unused {kind} `{}`", name.ident.item),
+                ),
+            );
+            self.items.push((def, unused));
+        }
+    }
+
+    fn path(&mut self, path: &ResolvedPath) {
+        let def = match &path.resolution {
+            Resolution::Local(local) => {
+                if let Some(&index) = self.locals.get(local) {
+                    self.variables[index].used = true;
+                }
+                return;
+            }
+            Resolution::Def(_, def) | Resolution::Relative(def, _) => *def,
+        };
+
+        if let (Some(first), Some(imports)) = (path.segments.first(), self.imports.last_mut()) {
+            for import in imports
+                .iter_mut()
+                .filter(|import| import.name == first.item)
+            {
+                import.unused.used = true;
+            }
+        }
+
+        if Some(def) != self.item {
+            self.used.insert(def);
+
+            // Using a variant uses its enum.
+            let definition = self.definitions.get(def);
+            if let (DefKind::Variant, Some(parent)) = (definition.kind, definition.parent) {
+                self.used.insert(parent);
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &resolved::Type) {
+        match &ty.item {
+            TypeKind::Array(inner, _) | TypeKind::Slice(inner) | TypeKind::Reference(_, inner) => {
+                self.ty(inner)
+            }
+            TypeKind::Function(parameters, return_type) => {
+                for parameter in parameters {
+                    self.ty(parameter);
+                }
+                if let Some(return_type) = return_type {
+                    self.ty(return_type);
+                }
+            }
+            TypeKind::Parenthesized(inner) => {
+                // Only a function type can need parentheses, as in `&(fn() -> u8)`.
+                if !matches!(inner.item, TypeKind::Function(_, _)) {
+                    self.emit(
+                        Lint::RedundantParentheses,
+                        Error::new(ty.span, String::from("Unnecessary parentheses around type")),
+                    );
+                }
+                self.ty(inner);
+            }
+            TypeKind::Path(path) => self.path(path),
+            TypeKind::Generic(path, arguments) => {
+                self.path(path);
+
+                for argument in arguments {
+                    self.ty(argument);
+                }
+            }
+            TypeKind::U8
+            | TypeKind::I8
+            | TypeKind::U16
+            | TypeKind::I16
+            | TypeKind::U32
+            | TypeKind::I32
+            | TypeKind::Bool
+            | TypeKind::Char
+            | TypeKind::Str
+            | TypeKind::Unit => {}
+        }
+    }
+
+    /// Reports parentheses around an expression in a position where no operator can bind to
+    /// it, like a condition or an argument. Parentheses around expressions that need none
+    /// anywhere are reported by `visit_expression` instead.
+    fn parenthesized(&mut self, expression: &Expression<Resolved>) {
+        if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Grouped(inner, _)) =
+            &expression.item
+        {
+            if !atomic(inner) {
+                self.emit(
+                    Lint::RedundantParentheses,
+                    Error::new(
+                        expression.span,
+                        String::from("Unnecessary parentheses around expression"),
+                    ),
+                );
+            }
+        }
+    }
+
+    fn snake_case(&mut self, what: &str, name: &Spanned<String>) {
+        if name.item.chars().any(char::is_uppercase) {
+            self.emit(
+                Lint::NonSnakeCase,
+                Error::with_details(
+                    name.span,
+                    format!("{what} `{}` should have a snake case name", name.item),
+                    format!("Rename it to `{}`", snake_case(&name.item)),
+                ),
+            );
+        }
+    }
+
+    /// Records a local binding, which is reported if it shadows another binding that is still
+    /// in scope, or if it is never used.
+    fn bind(&mut self, name: &Name, context: NameContext) {
+        let Some(Resolution::Local(local)) = name.resolution else {
+            return;
+        };
+        let ident = &name.ident;
+
+        self.snake_case(
+            if context == NameContext::Parameter {
+                "Parameter"
+            } else {
+                "Variable"
+            },
+            ident,
+        );
+
+        if ident.item.starts_with('_') {
+            return;
+        }
+
+        let shadowed = self
+            .bindings
+            .iter()
+            .rev()
+            .flatten()
+            .find(|binding| binding.item == ident.item)
+            .map(|binding| binding.span);
+
+        if let Some(shadowed) = shadowed {
+            self.emit(
+                Lint::ShadowedBindings,
+                Error::new(
+                    ident.span,
+                    format!("Binding `{}` shadows an earlier binding", ident.item),
+                )
+                .with_note(shadowed, String::from("Earlier binding is here")),
+            );
+        }
+        if let Some(bindings) = self.bindings.last_mut() {
+            bindings.push(ident.clone());
+        }
+
+        if !self.signature_only {
+            let unused = self.unused(
+                Lint::UnusedVariables,
+                Error::with_details(
+                    ident.span,
+                    format!("Unused variable `{}`", ident.item),
+                    format!(
+                        "Prefix it with an underscore, as in `_{}`, if this is intentional",
+                        ident.item
+                    ),
+                ),
+            );
+            self.locals.insert(local, self.variables.len());
+            self.variables.push(unused);
+        }
+    }
+}
+
+impl Visitor<Resolved> for Linter<'_> {
+    fn visit_module(&mut self, id: ModuleId, module: &Module<Resolved>) {
+        self.enter_module();
+        visit::walk_module(self, id, module);
+        self.exit_module();
+    }
+
+    fn visit_item(&mut self, item: &Item<Resolved>) {
+        let depth = self.scopes.enter(&item.attributes, Target::of(&item.kind));
+
+        if item.visibility == Visibility::Private {
+            self.declare(item);
+        }
+
+        let outermost = self.item.is_none();
+        if outermost {
+            self.item = match &item.kind {
+                ItemKind::Function(FunctionItem { name, .. })
+                | ItemKind::Struct(StructItem { name, .. })
+                | ItemKind::Enum(EnumItem { name, .. })
+                | ItemKind::TypeAlias(TypeAliasItem { name, .. })
+                | ItemKind::Const(ConstItem { name, .. })
+                | ItemKind::Static(StaticItem { name, .. }) => match name.resolution {
+                    Some(Resolution::Def(_, def)) => Some(def),
+                    _ => None,
+                },
+                _ => None,
+            };
+        }
+
+        match &item.kind {
+            ItemKind::Use(tree) if item.visibility == Visibility::Private => self.import(tree),
+            ItemKind::Use(_) => {}
+            ItemKind::Mod(ModItem {
+                body: ModBody::Inline(_),
+                ..
+            }) => {
+                self.enter_module();
+                visit::walk_item(self, item);
+                self.exit_module();
+            }
+            ItemKind::Impl(_) | ItemKind::Trait(_) => {
+                self.associated = true;
+                visit::walk_item(self, item);
+                self.associated = false;
+            }
+            _ => visit::walk_item(self, item),
+        }
+
+        if outermost {
+            self.item = None;
+        }
+        self.scopes.exit(depth);
+    }
+
+    fn visit_function(&mut self, function: &FunctionItem<Resolved>) {
+        let signature_only = std::mem::replace(&mut self.signature_only, function.body.is_none());
+        visit::walk_function(self, function);
+        self.signature_only = signature_only;
+    }
+
+    fn visit_statement(&mut self, statement: &Statement<Resolved>) {
+        match statement {
+            Statement::Let(Let {
+                initializer: Some(expression),
+                ..
+            })
+            | Statement::Expression(expression) => self.parenthesized(expression),
+            _ => {}
+        }
+
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression<Resolved>) {
+        if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Grouped(inner, _)) =
+            &expression.item
+        {
+            if atomic(inner) {
+                self.emit(
+                    Lint::RedundantParentheses,
+                    Error::new(
+                        expression.span,
+                        String::from("Unnecessary parentheses around expression"),
+                    ),
+                );
+            }
+        }
+
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_with_block(&mut self, expression: &ExpressionWithBlock<Resolved>) {
+        use ExpressionWithBlock::*;
+
+        match expression {
+            Case(scrutinee, _, _) | IfIs(scrutinee, _, _, _, _) => self.parenthesized(scrutinee),
+            While(_, condition, _, _) | If(condition, _, _, _) => self.parenthesized(condition),
+            For(_, _, _, _, _) | Loop(_, _, _) | Block(_, _) => {}
+        }
+
+        visit::walk_with_block(self, expression);
+    }
+
+    fn visit_without_block(&mut self, expression: &ExpressionWithoutBlock<Resolved>) {
+        use ExpressionWithoutBlock::*;
+
+        match expression {
+            Return(Some(value), _) | Break(_, Some(value), _) => self.parenthesized(value),
+            Call(_, arguments, _) | MethodCall(_, _, arguments, _) | Array(arguments, _) => {
+                for argument in arguments {
+                    self.parenthesized(argument);
+                }
+            }
+            _ => {}
+        }
+
+        // `(s.f)(1)` calls the function held by the field `f`, where `s.f(1)` would call a method
+        // named `f`, so parentheses around a called field are needed.
+        if let Call(callee, arguments, _) = expression {
+            if let ExpressionKind::ExpressionWithoutBlock(Grouped(inner, _)) = &callee.item {
+                if matches!(
+                    inner.item,
+                    ExpressionKind::ExpressionWithoutBlock(Field(..))
+                ) {
+                    self.visit_expression(inner);
+
+                    for argument in arguments {
+                        self.visit_expression(argument);
+                    }
+                    return;
+                }
+            }
+        }
+
+        visit::walk_without_block(self, expression);
+    }
+
+    fn visit_name(&mut self, name: &Name, context: NameContext) {
+        match (context, &name.resolution) {
+            (
+                NameContext::Declaration,
+                Some(Resolution::Def(DefKind::Fn | DefKind::AssocFn, _)),
+            ) => self.snake_case("Function", &name.ident),
+            (NameContext::Declaration, Some(Resolution::Def(DefKind::Mod, _))) => {
+                self.snake_case("Module", &name.ident)
+            }
+            (NameContext::Parameter | NameContext::Binding, _) => self.bind(name, context),
+            _ => {}
+        }
+    }
+
+    fn visit_path(&mut self, path: &ResolvedPath, context: PathContext) {
+        // An import is not a use of what it imports, only the paths naming it are.
+        if context != PathContext::Import {
+            self.path(path);
+        }
+    }
+
+    fn visit_type(&mut self, ty: &resolved::Type) {
+        self.ty(ty);
+    }
+
+    fn enter_scope(&mut self) {
+        self.bindings.push(Vec::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.bindings.pop();
+    }
+}
+
+/// Whether an expression binds tighter than any operator, so that parentheses around it are
+/// never needed.
+fn atomic(expression: &Expression<Resolved>) -> bool {
+    use ExpressionWithoutBlock::*;
+
+    matches!(
+        &expression.item,
+        ExpressionKind::ExpressionWithoutBlock(
+            Path(_, _)
+                | Literal(_, _)
+                | Grouped(_, _)
+                | Array(_, _)
+                | Call(_, _, _)
+                | MethodCall(_, _, _, _)
+                | Field(_, _, _)
+                | Index(_, _, _)
+        )
+    )
+}
+
+/// Converts a name to snake case, as in `parseHeader` to `parse_header`.
+fn snake_case(name: &str) -> String {
+    let mut converted = String::new();
+
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if index > 0 && !converted.ends_with('_') {
+                converted.push('_');
+            }
+            converted.extend(c.to_lowercase());
+        } else {
+            converted.push(c);
+        }
+    }
+
+    converted
+}
+
+/// Reports code following an expression that diverges within the same block. Only the first
+/// unreachable piece of code in a block is reported.
+struct Reachability<'a> {
+    scopes: Scopes<'a>,
+    report: Report,
+}
+
+impl Reachability<'_> {
+    fn reachable(&mut self, span: Span, diverges: Option<Span>, reported: &mut bool) {
+        if let (Some(at), false) = (diverges, *reported) {
+            *reported = true;
+
+            let level = self.scopes.level(Lint::UnreachableCode);
+            self.report.emit(
+                Lint::UnreachableCode,
+                level,
+                Error::new(span, String::from("Unreachable code")).with_note(
+                    at,
+                    String::from("Any code following this expression is unreachable"),
+                ),
+            );
+        }
+    }
+}
+
+impl Visitor<Typed> for Reachability<'_> {
+    fn visit_item(&mut self, item: &Item<Typed>) {
+        let depth = self.scopes.enter(&item.attributes, Target::of(&item.kind));
+        visit::walk_item(self, item);
+        self.scopes.exit(depth);
+    }
+
+    fn visit_block(&mut self, block: &BlockExpression<Typed>) {
+        let mut diverges = None;
+        let mut reported = false;
+
+        for statements in block.iter().flatten() {
+            let (leading, last) = match statements {
+                Statements::Leading(first, rest, last) => {
+                    (std::iter::once(first).chain(rest).collect(), last.as_ref())
+                }
+                Statements::Block(last) => (Vec::new(), Some(last)),
+            };
+
+            for statement in leading {
+                let (span, diverging) = match statement {
+                    Statement::Empty => continue,
+                    Statement::Let(Let {
+                        initializer, span, ..
+                    }) => (
+                        *span,
+                        initializer
+                            .as_ref()
+                            .is_some_and(|initializer| *type_of(initializer) == Type::Never),
+                    ),
+                    Statement::Expression(expression) => {
+                        (expression.span, *type_of(expression) == Type::Never)
+                    }
+                    Statement::Defer(expression) => (expression.span, false),
+                };
+
+                self.reachable(span, diverges, &mut reported);
+                self.visit_statement(statement);
+
+                if diverging {
+                    diverges = diverges.or(Some(span));
+                }
+            }
+
+            if let Some(last) = last {
+                self.reachable(last.span, diverges, &mut reported);
+                self.visit_expression(last);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::*;
+    use crate::testing::{self, span, span_in};
+
+    fn lint(source: &str, levels: &Levels) -> Report {
+        let (geode, definitions) = testing::resolve(source);
+        check(&geode, &definitions, levels)
+    }
+
+    fn messages(diagnostics: &[(Lint, Error)]) -> Vec<(Lint, &str, Span)> {
+        diagnostics
+            .iter()
+            .map(|(lint, error)| (*lint, error.message.as_str(), error.span))
+            .collect()
+    }
+
+    #[test]
+    fn bindings() {
+        let source = "fn main(a: u8) { let b = a; let b = b; let _c = 1; }";
+
+        let report = lint(source, &Levels::default());
+        assert_eq!(
+            messages(&report.warnings),
+            vec![(
+                Lint::UnusedVariables,
+                "Unused variable `b`",
+                span_in(source, "let b = b", "b")
+            )]
+        );
+
+        let mut levels = Levels::default();
+        levels.set(Lint::ShadowedBindings, Level::Warn);
+
+        let report = lint(source, &levels);
+        assert_eq!(
+            messages(&report.warnings),
+            vec![
+                (
+                    Lint::ShadowedBindings,
+                    "Binding `b` shadows an earlier binding",
+                    span_in(source, "let b = b", "b")
+                ),
+                (
+                    Lint::UnusedVariables,
+                    "Unused variable `b`",
+                    span_in(source, "let b = b", "b")
+                ),
+            ]
+        );
+        assert_eq!(
+            report.warnings[0].1.notes[0].span,
+            span_in(source, "let b = a", "b")
+        );
+    }
+
+    #[test]
+    fn unused_items() {
+        let source = "
+            mod m { pub fn h() {} }
+            use m::h;
+            struct S {}
+            fn main() { used() }
+            fn used() {}
+            fn helperFn() {}
+            fn recursive() { recursive() }
+        ";
+
+        let report = lint(source, &Levels::default());
+        assert_eq!(
+            messages(&report.warnings),
+            vec![
+                (
+                    Lint::NonSnakeCase,
+                    "Function `helperFn` should have a snake case name",
+                    span(source, "helperFn")
+                ),
+                (
+                    Lint::UnusedImports,
+                    "Unused import `h`",
+                    span(source, "m::h")
+                ),
+                (Lint::DeadCode, "Unused struct `S`", span(source, "S")),
+                (
+                    Lint::UnusedFunctions,
+                    "Unused function `helperFn`",
+                    span(source, "helperFn")
+                ),
+                (
+                    Lint::UnusedFunctions,
+                    "Unused function `recursive`",
+                    span(source, "recursive")
+                ),
+            ]
+        );
+        assert_eq!(
+            report.warnings[0].1.details.as_deref(),
+            Some("Rename it to `helper_fn`")
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let main =
+            |level: &str| format!(r#"@{level}("unused_variables") fn main() {{ let x = 1; }}"#);
+
+        let report = lint(&main("allow"), &Levels::default());
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+
+        let source = main("deny");
+        let report = lint(&source, &Levels::default());
+        assert!(report.warnings.is_empty());
+        assert_eq!(
+            messages(&report.errors),
+            vec![(
+                Lint::UnusedVariables,
+                "Unused variable `x`",
+                span(&source, "x")
+            )]
+        );
+
+        // Attributes take precedence over the command line.
+        let mut levels = Levels::default();
+        levels.set(Lint::UnusedVariables, Level::Deny);
+
+        let report = lint(&main("warn"), &levels);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+
+        let geode = testing::parse(r#"@allow("dead_code", "nonsense") fn main() {}"#);
+        let (_, errors) =
+            attribute::validate(&geode.modules[0].items[0].attributes, Target::Function);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unknown lint `nonsense`");
+    }
+
+    #[test]
+    fn redundant_parentheses() {
+        let source = "fn main() { let _x: (u8) = (1); let _y = (1 + 2) * 3; }";
+
+        let report = lint(source, &Levels::default());
+        assert_eq!(
+            messages(&report.warnings),
+            vec![
+                (
+                    Lint::RedundantParentheses,
+                    "Unnecessary parentheses around type",
+                    span(source, "(u8)")
+                ),
+                (
+                    Lint::RedundantParentheses,
+                    "Unnecessary parentheses around expression",
+                    span(source, "(1)")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn called_fields() {
+        let source = "
+            struct S { f: fn(u8) -> u8 }
+            fn main(s: S) -> u8 { let x = (s.f)(1); (s.f)((x)) }
+        ";
+
+        // The parentheses around a called field keep it from being a method call.
+        let report = lint(source, &Levels::default());
+
+        assert_eq!(
+            messages(&report.warnings),
+            vec![(
+                Lint::RedundantParentheses,
+                "Unnecessary parentheses around expression",
+                span(source, "(x)")
+            )]
+        );
+    }
+
+    #[test]
+    fn unreachable_code() {
+        let source = "fn main() -> u8 { return 1; 2 }";
+
+        let (geode, _, _) = testing::typed(source);
+        let report = check_typed(&geode, &Levels::default());
+
+        assert_eq!(
+            messages(&report.warnings),
+            vec![(Lint::UnreachableCode, "Unreachable code", span(source, "2"))]
+        );
+        assert_eq!(report.warnings[0].1.notes[0].span, span(source, "return 1"));
+    }
+
+    #[test]
+    fn summaries() {
+        assert_eq!(summary(0, 0), None);
+        assert_eq!(summary(1, 0).unwrap(), "Build finished with 1 warning");
+        assert_eq!(summary(0, 3).unwrap(), "Build failed with 3 errors");
+        assert_eq!(
+            summary(2, 1).unwrap(),
+            "Build failed with 1 error and 2 warnings"
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::model::ast::Syntax;
+use crate::model::base::*;
+use crate::parse;
+use crate::source::SourceMap;
+use crate::span::{Span, Spanned};
+
+pub const EXTENSION: &str = "opal";
+
+/// Access to the files of a geode, kept separate so that loading can be tested without
+/// touching the disk.
+pub trait FileSystem {
+    fn read(&self, path: &Path) -> Option<String>;
+    fn exists(&self, path: &Path) -> bool;
+    /// The path with all symbolic links resolved, identifying the file however it is reached.
+    fn canonicalize(&self, path: &Path) -> PathBuf;
+}
+
+pub struct Disk;
+
+impl FileSystem for Disk {
+    fn read(&self, path: &Path) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+    }
+}
+
+/// Loads the geode rooted at `root`, along with every module file reachable from it through
+/// `mod foo;` items, registering each file in `sources`. The root module is
+/// `Geode::modules[0]`.
+pub fn load(
+    name: String,
+    root: &Path,
+    fs: &impl FileSystem,
+    sources: &mut SourceMap,
+) -> Result<Geode<Syntax>, Vec<Error>> {
+    let mut loader = Loader {
+        fs,
+        sources,
+        modules: Vec::new(),
+        files: HashMap::new(),
+        stack: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let root_name = Spanned::empty(name.clone());
+    loader.load_file(root, root_name, Span::new(0, 0), true);
+
+    match loader.errors.is_empty() {
+        true => Ok(Geode {
+            name: Spanned::empty(name),
+            modules: loader.modules.into_iter().flatten().collect(),
+        }),
+        false => Err(loader.errors),
+    }
+}
+
+/// Finds the file of the out-of-line module `name`, declared by a module whose submodule files
+/// live in `directory`. The module may be in either `name.opal` or `name/mod.opal`, but not
+/// both.
+pub fn resolve(
+    fs: &impl FileSystem,
+    directory: &Path,
+    name: &str,
+    span: Span,
+) -> Result<PathBuf, Error> {
+    let file = directory.join(format!("{name}.{EXTENSION}"));
+    let mod_file = directory.join(name).join(format!("mod.{EXTENSION}"));
+
+    match (fs.exists(&file), fs.exists(&mod_file)) {
+        (true, false) => Ok(file),
+        (false, true) => Ok(mod_file),
+        (false, false) => Err(Error::with_details(
+            span,
+            format!("Could not find the file for module `{name}`"),
+            format!(
+                "Expected to find `{}` or `{}`",
+                file.display(),
+                mod_file.display()
+            ),
+        )),
+        (true, true) => Err(Error::with_details(
+            span,
+            format!("The file for module `{name}` is ambiguous"),
+            format!(
+                "Both `{}` and `{}` exist, but only one may",
+                file.display(),
+                mod_file.display()
+            ),
+        )),
+    }
+}
+
+/// The directory holding the files of a module's out-of-line submodules. The root file and
+/// `mod.opal` files own the directory they are in, while `foo.opal` owns the directory `foo`
+/// next to it.
+pub fn submodule_directory(path: &Path, is_root: bool) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new("")).to_owned();
+    let is_mod_file = path.file_stem().is_some_and(|stem| stem == "mod");
+
+    match (is_root || is_mod_file, path.file_stem()) {
+        (false, Some(stem)) => parent.join(stem),
+        _ => parent,
+    }
+}
+
+struct Loader<'a, F: FileSystem> {
+    fs: &'a F,
+    sources: &'a mut SourceMap,
+    // Modules are placed once all of their submodules are loaded, but their ids are reserved
+    // beforehand, so that the root module comes first.
+    modules: Vec<Option<Module<Syntax>>>,
+    files: HashMap<PathBuf, Span>, // Canonical paths of loaded files, and where they were declared
+    stack: Vec<(PathBuf, Span)>,   // Files currently being loaded, outermost first
+    errors: Vec<Error>,
+}
+
+impl<'a, F: FileSystem> Loader<'a, F> {
+    fn load_file(
+        &mut self,
+        path: &Path,
+        name: Spanned<String>,
+        declaration: Span,
+        is_root: bool,
+    ) -> Option<ModuleId> {
+        let canonical = self.fs.canonicalize(path);
+
+        // Submodule files are always further down the directory tree than their parents, so
+        // a module can only contain itself through a symbolic link.
+        if let Some(position) = self.stack.iter().position(|(file, _)| *file == canonical) {
+            let error = self.stack[position..].iter().fold(
+                Error::new(
+                    declaration,
+                    format!("Module `{}` contains itself", name.item),
+                ),
+                |error, (file, span)| {
+                    error.with_note(*span, format!("`{}` is declared here", file.display()))
+                },
+            );
+            self.errors.push(error);
+            return None;
+        }
+
+        if let Some(first) = self.files.get(&canonical) {
+            self.errors.push(
+                Error::new(
+                    declaration,
+                    format!(
+                        "File `{}` is already loaded as another module",
+                        path.display()
+                    ),
+                )
+                .with_note(*first, String::from("It is first declared here")),
+            );
+            return None;
+        }
+
+        let text = match self.fs.read(path) {
+            Some(text) => text,
+            None => {
+                self.errors.push(Error::new(
+                    declaration,
+                    format!("Could not read module file `{}`", path.display()),
+                ));
+                return None;
+            }
+        };
+
+        let file = self.sources.add(path.to_owned(), text);
+        let source = self.sources.file(file);
+        let parsed = parse::tokenize_at(&source.text, source.start)
+            .and_then(|mut tokens| parse::items(&mut tokens));
+
+        let mut items = match parsed {
+            Ok(items) => items,
+            Err(error) => {
+                self.errors.push(error);
+                return None;
+            }
+        };
+
+        let id = self.modules.len();
+        self.modules.push(None);
+        self.files.insert(canonical.clone(), declaration);
+        self.stack.push((canonical, declaration));
+
+        self.resolve_items(&mut items, submodule_directory(path, is_root));
+
+        self.stack.pop();
+        self.modules[id] = Some(Module { name, items, file });
+
+        Some(id)
+    }
+
+    fn resolve_items(&mut self, items: &mut [Item<Syntax>], directory: PathBuf) {
+        for item in items {
+            let ItemKind::Mod(ModItem { name, body }) = &mut item.kind else {
+                continue;
+            };
+
+            match body {
+                // Inline modules have no file of their own, but still nest the files of their
+                // out-of-line submodules within a directory.
+                ModBody::Inline(items) => self.resolve_items(items, directory.join(&name.item)),
+                ModBody::File(module) => {
+                    match resolve(self.fs, &directory, &name.item, item.span) {
+                        Ok(path) => *module = self.load_file(&path, name.clone(), item.span, false),
+                        Err(error) => self.errors.push(error),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::load::*;
+
+    /// Files in memory, along with symbolic links from one path to another.
+    #[derive(Default)]
+    struct Memory {
+        files: HashMap<PathBuf, String>,
+        links: HashMap<PathBuf, PathBuf>,
+    }
+
+    impl Memory {
+        fn new(files: &[(&str, &str)]) -> Self {
+            Self {
+                files: files
+                    .iter()
+                    .map(|(path, text)| (PathBuf::from(path), text.to_string()))
+                    .collect(),
+                links: HashMap::new(),
+            }
+        }
+
+        fn target(&self, path: &Path) -> PathBuf {
+            self.links
+                .get(path)
+                .cloned()
+                .unwrap_or_else(|| path.to_owned())
+        }
+    }
+
+    impl FileSystem for Memory {
+        fn read(&self, path: &Path) -> Option<String> {
+            self.files.get(&self.target(path)).cloned()
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files.contains_key(&self.target(path))
+        }
+
+        fn canonicalize(&self, path: &Path) -> PathBuf {
+            self.target(path)
+        }
+    }
+
+    fn module_names(geode: &Geode<Syntax>) -> Vec<&str> {
+        geode
+            .modules
+            .iter()
+            .map(|module| module.name.item.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn submodule_directories() {
+        assert_eq!(
+            submodule_directory(Path::new("src/main.opal"), true),
+            Path::new("src")
+        );
+        assert_eq!(
+            submodule_directory(Path::new("src/foo.opal"), false),
+            Path::new("src/foo")
+        );
+        assert_eq!(
+            submodule_directory(Path::new("src/foo/mod.opal"), false),
+            Path::new("src/foo")
+        );
+    }
+
+    #[test]
+    fn load_nested_modules() {
+        let fs = Memory::new(&[
+            ("src/main.opal", "mod foo;\nmod bar { mod baz; }"),
+            ("src/foo/mod.opal", "mod qux;"),
+            ("src/foo/qux.opal", ""),
+            ("src/bar/baz.opal", ""),
+        ]);
+        let mut sources = SourceMap::new();
+        let geode = load(
+            "main".to_owned(),
+            Path::new("src/main.opal"),
+            &fs,
+            &mut sources,
+        )
+        .unwrap();
+
+        assert_eq!(module_names(&geode), vec!["main", "foo", "qux", "baz"]);
+        assert_eq!(
+            sources.file(geode.modules[2].file).path,
+            Path::new("src/foo/qux.opal")
+        );
+        assert!(matches!(
+            geode.modules[0].items[0].kind,
+            ItemKind::Mod(ModItem {
+                body: ModBody::File(Some(1)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn missing_and_ambiguous_files() {
+        let fs = Memory::new(&[
+            ("src/main.opal", "mod foo;\nmod bar;"),
+            ("src/foo.opal", ""),
+            ("src/foo/mod.opal", ""),
+        ]);
+        let errors = load(
+            "main".to_owned(),
+            Path::new("src/main.opal"),
+            &fs,
+            &mut SourceMap::new(),
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("ambiguous"));
+        assert!(errors[1].message.contains("Could not find"));
+    }
+
+    #[test]
+    fn module_cycle() {
+        let mut fs = Memory::new(&[
+            ("src/main.opal", "mod foo;"),
+            ("src/foo.opal", "mod again;"),
+        ]);
+        fs.links.insert(
+            PathBuf::from("src/foo/again.opal"),
+            PathBuf::from("src/foo.opal"),
+        );
+
+        let errors = load(
+            "main".to_owned(),
+            Path::new("src/main.opal"),
+            &fs,
+            &mut SourceMap::new(),
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("contains itself"));
+    }
+}
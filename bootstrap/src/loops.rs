@@ -0,0 +1,293 @@
+use crate::error::Error;
+use crate::model::base::Label;
+use crate::model::ir::{LabelId, Statement, VariableId};
+use crate::model::types::Type;
+use crate::span::{Span, Spanned};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LoopKind {
+    While,
+    For,
+    Loop,
+}
+
+#[derive(Debug)]
+pub struct Frame<T> {
+    pub label: Option<Label>,
+    pub kind: LoopKind,
+    pub data: T,
+    pub span: Span,
+}
+
+/// The loops enclosing the expression currently being checked or lowered, innermost last.
+/// `break` and `continue` are matched with their loop here, so the same stack serves both
+/// type checking, where `T` tracks the type of the loop's `break` values, and lowering, where
+/// `T` holds the CFG labels to jump to.
+#[derive(Debug)]
+pub struct LoopStack<T> {
+    frames: Vec<Frame<T>>,
+}
+
+impl<T> LoopStack<T> {
+    pub fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    pub fn enter(
+        &mut self,
+        label: Option<Label>,
+        kind: LoopKind,
+        data: T,
+        span: Span,
+    ) -> Result<(), Error> {
+        // Reusing the label of an enclosing loop would make the outer loop unreachable by
+        // name. The loop is entered regardless, so that checking can continue within it.
+
+        let shadowed = label.as_ref().and_then(|label| {
+            self.frames
+                .iter()
+                .filter_map(|frame| frame.label.as_ref())
+                .find(|outer| outer.item == label.item)
+                .map(|outer| (label.span, outer.span, label.item.clone()))
+        });
+
+        self.frames.push(Frame {
+            label,
+            kind,
+            data,
+            span,
+        });
+
+        match shadowed {
+            None => Ok(()),
+            Some((span, outer, name)) => Err(Error::new(
+                span,
+                format!("Label '{name} is already in use by an enclosing loop"),
+            )
+            .with_note(outer, String::from("Enclosing loop's label is here"))),
+        }
+    }
+
+    pub fn exit(&mut self) -> Frame<T> {
+        self.frames
+            .pop()
+            .expect("Loops should be exited exactly as many times as they are entered")
+    }
+
+    /// Finds the loop exited by `break` or resumed by `continue`: the innermost loop when
+    /// there is no label, otherwise the innermost loop with that label.
+    pub fn target(
+        &mut self,
+        label: Option<&Label>,
+        keyword: &str,
+        span: Span,
+    ) -> Result<&mut Frame<T>, Error> {
+        match label {
+            None => self.frames.last_mut().ok_or_else(|| {
+                Error::new(span, format!("`{keyword}` can only be used within a loop"))
+            }),
+            Some(label) => self
+                .frames
+                .iter_mut()
+                .rev()
+                .find(|frame| frame.label.as_ref().map(|l| &l.item) == Some(&label.item))
+                .ok_or_else(|| {
+                    Error::new(
+                        label.span,
+                        format!("No enclosing loop is labeled '{}", label.item),
+                    )
+                }),
+        }
+    }
+}
+
+impl<T> Default for LoopStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records the type of a `break` within the loop it exits. `value` is the type of the break's
+/// value, or `None` for a bare `break`. Only `loop` may be exited with a value, since `while`
+/// and `for` loops can also finish without any `break` at all. Every `break` out of the same
+/// loop must agree on the type, with the first `break` deciding it.
+pub fn check_break(
+    frame: &mut Frame<Option<Spanned<Type>>>,
+    value: Option<Type>,
+    span: Span,
+) -> Result<(), Error> {
+    if frame.kind != LoopKind::Loop && value.is_some() {
+        return Err(Error::with_details(
+            span,
+            String::from("`break` with a value can only exit a `loop`"),
+            String::from("`while` and `for` loops always have type `Unit`"),
+        ));
+    }
+
+    let ty = value.unwrap_or(Type::Unit);
+
+    match &frame.data {
+        // A diverging value, as in `break return`, is compatible with any loop type.
+        _ if ty == Type::Never => Ok(()),
+        None => {
+            frame.data = Some(Spanned::new(ty, span));
+            Ok(())
+        }
+        Some(first) if first.item == ty => Ok(()),
+        Some(first) => Err(Error::with_details(
+            span,
+            String::from("Mismatched types of `break` values"),
+            format!("Expected `{}`, found `{ty}`", first.item),
+        )
+        .with_note(first.span, format!("Type `{}` is decided here", first.item))),
+    }
+}
+
+/// The type of a loop expression once its body has been checked. A `loop` without any `break`
+/// never finishes, so it has type `Never`.
+pub fn loop_type(frame: &Frame<Option<Spanned<Type>>>) -> Type {
+    match (frame.kind, &frame.data) {
+        (LoopKind::Loop, Some(ty)) => ty.item.clone(),
+        (LoopKind::Loop, None) => Type::Never,
+        _ => Type::Unit,
+    }
+}
+
+/// Where control goes when leaving or resuming a loop. `result` receives the values of `break`
+/// expressions, and is only present for `loop`. `scope_depth` is the `defer::Scopes` depth
+/// outside the loop's body, which determines the deferred expressions a jump must run.
+#[derive(Debug, Clone)]
+pub struct Targets {
+    pub break_to: LabelId,
+    pub continue_to: LabelId,
+    pub result: Option<VariableId>,
+    pub scope_depth: usize,
+}
+
+pub fn lower_break(targets: &Targets, value: Option<VariableId>) -> Vec<Statement> {
+    let mut statements = Vec::new();
+
+    if let (Some(target), Some(source)) = (targets.result, value) {
+        statements.push(Statement::Assign { target, source });
+    }
+    statements.push(Statement::Goto(targets.break_to));
+
+    statements
+}
+
+pub fn lower_continue(targets: &Targets) -> Statement {
+    Statement::Goto(targets.continue_to)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::ir;
+    use crate::testing::{self, messages, notes, span, span_in};
+
+    #[test]
+    fn labeled_targets() {
+        let source = "
+            fn f() {
+                'outer: while True {
+                    for i in 0..3 {
+                        continue 'inner;
+                        'outer: loop { break 'outer; }
+                    }
+                }
+            }
+        ";
+
+        // `'outer` names the innermost loop with that label, but reusing it is an error.
+        let errors = testing::type_errors(source);
+
+        assert_eq!(
+            notes(&errors),
+            vec![
+                (
+                    "No enclosing loop is labeled 'inner",
+                    span(source, "'inner"),
+                    Vec::new()
+                ),
+                (
+                    "Label 'outer is already in use by an enclosing loop",
+                    span_in(source, "'outer: loop", "'outer"),
+                    vec![span(source, "'outer")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn break_outside_loop() {
+        let source = "fn f() { break; continue; }";
+
+        let errors = testing::type_errors(source);
+
+        assert_eq!(
+            messages(&errors),
+            vec![
+                (
+                    "`break` can only be used within a loop",
+                    None,
+                    span(source, "break")
+                ),
+                (
+                    "`continue` can only be used within a loop",
+                    None,
+                    span(source, "continue")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn break_types() {
+        // The first `break` decides the type of the loop, a diverging value fits any type, and
+        // a loop without any `break` never finishes.
+        testing::typed(
+            "fn f(x: u8) -> u8 { loop { if x > 1 { break x; } if x > 2 { break return 2; } } }
+            fn g() -> u8 { loop {} }",
+        );
+
+        let source = "fn f(x: u8) -> u8 { loop { if x > 1 { break x; } break; } }";
+
+        let errors = testing::type_errors(source);
+
+        assert_eq!(
+            notes(&errors),
+            vec![(
+                "Mismatched types of `break` values",
+                span_in(source, "break;", "break"),
+                vec![span(source, "break x")]
+            )]
+        );
+    }
+
+    #[test]
+    fn break_value_from_while() {
+        let source = "fn f(x: u8) { while True { break x; } for i in 0..x { break; } }";
+
+        let errors = testing::type_errors(source);
+
+        assert_eq!(
+            messages(&errors),
+            vec![(
+                "`break` with a value can only exit a `loop`",
+                Some("`while` and `for` loops always have type `Unit`"),
+                span(source, "break x")
+            )]
+        );
+    }
+
+    #[test]
+    fn lowering() {
+        let functions = testing::lowered("fn main() { let x: u8 = 1; let y = loop { break x; }; }");
+
+        // The value of `break` is assigned to the result of the loop before jumping out of it.
+        let body = &functions[0].body;
+        let assigned = body
+            .windows(2)
+            .any(|pair| matches!(pair, [ir::Statement::Assign { .. }, ir::Statement::Goto(_)]));
+        assert!(assigned, "{body:?}");
+    }
+}
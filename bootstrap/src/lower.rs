@@ -0,0 +1,2042 @@
+use std::collections::HashMap;
+
+use crate::asm;
+use crate::decision;
+use crate::defer::Scopes;
+use crate::error::Error;
+use crate::index::{self, Check, Length};
+use crate::layout::{Layout, Layouts};
+use crate::loops::{self, LoopKind, LoopStack, Targets};
+use crate::model::base::*;
+use crate::model::def::{DefId, DefKind, Definitions};
+use crate::model::desugared::{type_of, Desugared};
+use crate::model::ir::{self, BinaryOp, FunctionId, LabelId, UnaryOp, VariableId};
+use crate::model::resolved::{LocalId, Name, Resolution, ResolvedPath};
+use crate::model::typed::pattern_type;
+use crate::model::types::Type;
+use crate::model::visit::{self, Visitor};
+use crate::monomorphize::{self, Callee, Instance, Instances};
+use crate::propagate;
+use crate::span::Span;
+use crate::typeck::{def_of, infer, signature_type, Tables};
+
+/// The declarations that lowering looks up by definition: the function items of the geode,
+/// and the initializers of its constants, which are lowered wherever a constant is used.
+#[derive(Default)]
+struct Items<'a> {
+    functions: HashMap<DefId, &'a FunctionItem<Desugared>>,
+    constants: HashMap<DefId, &'a Expression<Desugared>>,
+}
+
+impl<'a> Items<'a> {
+    fn collect(&mut self, items: &'a [Item<Desugared>]) {
+        for item in items {
+            match &item.kind {
+                ItemKind::Function(function) => {
+                    self.functions.insert(def_of(&function.name), function);
+                }
+                ItemKind::Const(constant) => {
+                    self.constants
+                        .insert(def_of(&constant.name), &constant.value);
+                }
+                ItemKind::Mod(ModItem {
+                    body: ModBody::Inline(items),
+                    ..
+                }) => self.collect(items),
+                ItemKind::Impl(ImplItem { functions, .. })
+                | ItemKind::Trait(TraitItem { functions, .. }) => self.collect(functions),
+                _ => {}
+            }
+        }
+    }
+}
+
+struct Context<'a> {
+    definitions: &'a Definitions,
+    tables: &'a Tables,
+    layouts: &'a Layouts,
+    items: &'a Items<'a>,
+    bounds_checks: bool,
+}
+
+/// The types mentioned within a function, whose layouts an instance of it needs.
+#[derive(Default)]
+struct Mentioned(Vec<Type>);
+
+impl Visitor<Desugared> for Mentioned {
+    fn visit_expression(&mut self, expression: &Expression<Desugared>) {
+        self.0.push(type_of(expression).clone());
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern<Desugared>) {
+        self.0.push(pattern_type(&pattern.item).clone());
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        self.0.push(ty.clone());
+    }
+}
+
+/// Lowers a checked and desugared geode to IR, building the control flow graph of each function
+/// out of labels and jumps. Only the function instances reachable from `main` in the root
+/// module are lowered, and `main` is the first of them. Each call names the instance it calls
+/// by its index among the functions returned.
+///
+/// Generic functions are monomorphized: each instance is lowered with its type arguments in
+/// place of its type parameters, and calls through a trait bound are dispatched statically to
+/// the implementation for the type the bound stands for. The layouts of any generic enum
+/// instances that only appear this way are computed as each instance is reached.
+///
+/// Indexing is guarded by runtime bounds checks, unless `bounds_checks` is unset or the index
+/// is a constant that is provably in range.
+pub fn lower(
+    geode: &Geode<Desugared>,
+    definitions: &Definitions,
+    tables: &Tables,
+    layouts: &mut Layouts,
+    bounds_checks: bool,
+) -> Result<Vec<ir::Function>, Vec<Error>> {
+    let mut items = Items::default();
+    for module in &geode.modules {
+        items.collect(&module.items);
+    }
+
+    let entry = geode.modules[0]
+        .items
+        .iter()
+        .find_map(|item| match &item.kind {
+            ItemKind::Function(function) if function.name.ident.item == "main" => {
+                Some(def_of(&function.name))
+            }
+            _ => None,
+        });
+
+    let Some(entry) = entry else {
+        return Err(vec![Error::new(
+            Span::new(0, 0),
+            String::from("The root module has no `main` function"),
+        )]);
+    };
+
+    let mut instances = Instances::new(entry);
+    let mut functions = Vec::new();
+    let mut errors = Vec::new();
+
+    while let Some((id, instance)) = instances.next() {
+        let function = items.functions[&instance.function];
+
+        if !instance.arguments.is_empty() {
+            let mut mentioned = Mentioned::default();
+            mentioned.visit_function(function);
+
+            for ty in mentioned.0 {
+                let ty = ty.substitute(&instance.arguments);
+                errors.extend(layouts.instantiate(&ty, function.name.ident.span));
+            }
+        }
+
+        let context = Context {
+            definitions,
+            tables,
+            layouts,
+            items: &items,
+            bounds_checks,
+        };
+        let lowering = Lowering {
+            context: &context,
+            instances: &mut instances,
+            instance,
+            locals: HashMap::new(),
+            receiver: None,
+            variables: 0,
+            labels: 0,
+            statements: Vec::new(),
+            loops: LoopStack::new(),
+            scopes: Scopes::new(),
+            errors: Vec::new(),
+        };
+
+        match lowering.function(function) {
+            Ok(function) => functions.push((id, function)),
+            Err(found) => errors.extend(found),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    functions.sort_by_key(|(id, _)| *id);
+    Ok(functions
+        .into_iter()
+        .map(|(_, function)| function)
+        .collect())
+}
+
+/// Where a value lives, so that it can be read, written, or borrowed.
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    Variable(VariableId),
+    Part(VariableId, u32), // The bytes at an offset within a variable, such as a field
+    Memory(VariableId, u32), // The bytes at an offset past the address held in a variable
+}
+
+impl Place {
+    fn offset(self, by: u32) -> Place {
+        match self {
+            Place::Variable(variable) => Place::Part(variable, by),
+            Place::Part(variable, offset) => Place::Part(variable, offset + by),
+            Place::Memory(address, offset) => Place::Memory(address, offset + by),
+        }
+    }
+}
+
+/// The lowering of a single function instance.
+struct Lowering<'a, 'b> {
+    context: &'b Context<'a>,
+    instances: &'b mut Instances,
+    instance: Instance,
+    locals: HashMap<LocalId, VariableId>,
+    receiver: Option<VariableId>,
+    variables: usize,
+    labels: usize,
+    statements: Vec<ir::Statement>,
+    loops: LoopStack<Targets>,
+    scopes: Scopes<&'a Expression<Desugared>>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Lowering<'a, '_> {
+    fn function(
+        mut self,
+        function: &'a FunctionItem<Desugared>,
+    ) -> Result<ir::Function, Vec<Error>> {
+        let mut parameters = Vec::new();
+
+        if function.receiver.is_some() {
+            let receiver = self.variable();
+            self.receiver = Some(receiver);
+            parameters.push(receiver);
+        }
+
+        for parameter in &function.parameters {
+            let local = local_of(&parameter.name);
+            parameters.push(self.local(local));
+        }
+
+        let body = function
+            .body
+            .as_ref()
+            .expect("Only functions with bodies are called");
+        let value = self.block(body);
+        self.push(ir::Statement::Return(value));
+
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+
+        // Each instance of a generic function is named after the types it was instantiated
+        // with, as in `id[u8]`, so that instances can be told apart.
+        let name = &self.context.definitions.get(self.instance.function).name;
+        let name = match self.instance.arguments.is_empty() {
+            true => name.clone(),
+            false => {
+                let arguments = self
+                    .instance
+                    .arguments
+                    .iter()
+                    .map(|(_, ty)| ty.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{name}[{arguments}]")
+            }
+        };
+
+        Ok(ir::Function {
+            name,
+            parameters,
+            variables: self.variables,
+            body: self.statements,
+        })
+    }
+
+    fn variable(&mut self) -> VariableId {
+        self.variables += 1;
+        self.variables - 1
+    }
+
+    fn label(&mut self) -> LabelId {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn push(&mut self, statement: ir::Statement) {
+        self.statements.push(statement);
+    }
+
+    fn local(&mut self, local: LocalId) -> VariableId {
+        match self.locals.get(&local) {
+            Some(variable) => *variable,
+            None => {
+                let variable = self.variable();
+                self.locals.insert(local, variable);
+                variable
+            }
+        }
+    }
+
+    /// A type of the function being lowered, with the type arguments of its instance in place
+    /// of its type parameters.
+    fn ty(&self, ty: &Type) -> Type {
+        ty.substitute(&self.instance.arguments)
+    }
+
+    fn type_of(&self, expression: &'a Expression<Desugared>) -> Type {
+        self.ty(type_of(expression))
+    }
+
+    fn layout(&self, ty: &Type) -> Layout {
+        self.context.layouts.of(&self.ty(ty))
+    }
+
+    /// Lowers `lower` on its own, returning the statements it produced rather than adding them
+    /// to the function, along with the variable holding its value.
+    fn capture(
+        &mut self,
+        lower: impl FnOnce(&mut Self) -> VariableId,
+    ) -> (Vec<ir::Statement>, VariableId) {
+        let outer = std::mem::take(&mut self.statements);
+        let value = lower(self);
+        (std::mem::replace(&mut self.statements, outer), value)
+    }
+
+    fn unsupported(&mut self, what: &str, span: Span) -> VariableId {
+        self.errors
+            .push(Error::new(span, format!("{what} cannot be lowered yet")));
+        self.variable()
+    }
+
+    /// Lowers a block, giving the variable holding its value. The block's deferred expressions
+    /// run once its value is computed, and on every jump out of it.
+    fn block(&mut self, block: &'a BlockExpression<Desugared>) -> VariableId {
+        let mut value = None;
+        self.scopes.enter();
+
+        for statements in block.iter().flatten() {
+            let last = match statements {
+                Statements::Leading(first, rest, last) => {
+                    for statement in std::iter::once(first).chain(rest) {
+                        self.statement(statement);
+                    }
+
+                    last.as_ref()
+                }
+                Statements::Block(last) => Some(last),
+            };
+
+            if let Some(last) = last {
+                value = Some(self.expression(last));
+            }
+        }
+
+        // A block that ends by jumping away already ran its deferred expressions on the way out.
+        let deferred = self.scopes.exit();
+        if !matches!(
+            self.statements.last(),
+            Some(ir::Statement::Return(_) | ir::Statement::Goto(_))
+        ) {
+            for deferred in deferred {
+                self.deferred(deferred);
+            }
+        }
+
+        value.unwrap_or_else(|| self.variable())
+    }
+
+    /// Lowers the deferred expressions to run on a jump out to `depth`, as by `keyword`.
+    fn unwind(&mut self, depth: usize, keyword: &str, span: Span) {
+        match self.scopes.unwind(depth, keyword, span) {
+            Ok(deferred) => {
+                let deferred = deferred.into_iter().copied().collect::<Vec<_>>();
+
+                for deferred in deferred {
+                    self.deferred(deferred);
+                }
+            }
+            Err(error) => self.errors.push(error),
+        }
+    }
+
+    fn deferred(&mut self, deferred: &'a Expression<Desugared>) {
+        self.scopes.begin_deferred();
+        self.expression(deferred);
+        self.scopes.end_deferred();
+    }
+
+    fn statement(&mut self, statement: &'a Statement<Desugared>) {
+        match statement {
+            Statement::Empty => {}
+            Statement::Let(Let {
+                name, initializer, ..
+            }) => {
+                let target = self.local(local_of(name));
+
+                if let Some(initializer) = initializer {
+                    let source = self.expression(initializer);
+                    self.push(ir::Statement::Assign { target, source });
+                }
+            }
+            Statement::Expression(expression) => {
+                self.expression(expression);
+            }
+            Statement::Defer(expression) => self.scopes.defer(expression),
+        }
+    }
+
+    fn expression(&mut self, expression: &'a Expression<Desugared>) -> VariableId {
+        match &expression.item {
+            ExpressionKind::ExpressionWithBlock(with_block) => {
+                self.with_block(with_block, expression.span)
+            }
+            ExpressionKind::ExpressionWithoutBlock(without_block) => {
+                self.without_block(without_block, expression.span)
+            }
+        }
+    }
+
+    fn with_block(
+        &mut self,
+        expression: &'a ExpressionWithBlock<Desugared>,
+        span: Span,
+    ) -> VariableId {
+        use ExpressionWithBlock::*;
+
+        match expression {
+            Case(scrutinee, arms, _) => self.case(scrutinee, arms),
+            While(label, condition, body, _) => {
+                let start = self.label();
+                let end = self.label();
+                let enter = self.label();
+
+                self.push(ir::Statement::Label(start));
+                let condition = self.expression(condition);
+                self.push(ir::Statement::GotoIf(condition, enter));
+                self.push(ir::Statement::Goto(end));
+                self.push(ir::Statement::Label(enter));
+
+                let targets = Targets {
+                    break_to: end,
+                    continue_to: start,
+                    result: None,
+                    scope_depth: self.scopes.depth(),
+                };
+                self.loop_body(label, LoopKind::While, targets, body, span);
+
+                self.push(ir::Statement::Goto(start));
+                self.push(ir::Statement::Label(end));
+                self.variable()
+            }
+            Loop(label, body, _) => {
+                let start = self.label();
+                let end = self.label();
+                let result = self.variable();
+
+                self.push(ir::Statement::Label(start));
+
+                let targets = Targets {
+                    break_to: end,
+                    continue_to: start,
+                    result: Some(result),
+                    scope_depth: self.scopes.depth(),
+                };
+                self.loop_body(label, LoopKind::Loop, targets, body, span);
+
+                self.push(ir::Statement::Goto(start));
+                self.push(ir::Statement::Label(end));
+                result
+            }
+            Block(block, _) => self.block(block),
+            IfIs(_, _, _, _, removed) | For(_, _, _, _, removed) | If(_, _, _, removed) => {
+                match *removed {}
+            }
+        }
+    }
+
+    /// Lowers the body of a loop, within which `break` and `continue` jump to `targets`.
+    fn loop_body(
+        &mut self,
+        label: &Option<Label>,
+        kind: LoopKind,
+        targets: Targets,
+        body: &'a BlockExpression<Desugared>,
+        span: Span,
+    ) {
+        self.loops
+            .enter(label.clone(), kind, targets, span)
+            .expect("Loop labels were checked by type checking");
+        self.block(body);
+        self.loops.exit();
+    }
+
+    /// The targets of the loop a `break` or `continue` jumps out of.
+    fn targets(&mut self, label: &Option<Label>, keyword: &str, span: Span) -> Targets {
+        self.loops
+            .target(label.as_ref(), keyword, span)
+            .expect("`break` and `continue` were checked by type checking")
+            .data
+            .clone()
+    }
+
+    /// Lowers `operand?`. How it unwraps its operand was decided by type checking, and is
+    /// worked out again from the operand's type and the function's return type.
+    fn propagate(&mut self, operand: &'a Expression<Desugared>, span: Span) -> VariableId {
+        let context = self.context;
+        let ty = self.type_of(operand);
+        let signature = &context.tables.signatures[&self.instance.function];
+        let return_type = self.ty(&signature.return_type);
+
+        let propagation =
+            propagate::propagation(&ty, &return_type, context.tables, context.definitions, span)
+                .expect("`?` was checked by type checking");
+
+        let value = self.expression(operand);
+        let mut lower = Propagating {
+            lowering: self,
+            span,
+        };
+        let (statements, output) = propagate::lower(
+            &propagation,
+            (value, &ty),
+            &return_type,
+            context.layouts,
+            &mut lower,
+        );
+        self.statements.extend(statements);
+
+        output
+    }
+
+    /// Lowers a `when` expression to its decision tree, followed by the body of each arm.
+    fn case(
+        &mut self,
+        scrutinee: &'a Expression<Desugared>,
+        arms: &'a [Arm<Desugared>],
+    ) -> VariableId {
+        let context = self.context;
+        let ty = self.type_of(scrutinee);
+        let value = self.expression(scrutinee);
+        let tree = decision::compile(&ty, arms, context.tables);
+
+        let labels = arms.iter().map(|_| self.label()).collect::<Vec<_>>();
+        let result = self.variable();
+        let end = self.label();
+
+        let mut lower = Arms {
+            lowering: self,
+            arms,
+            labels: &labels,
+        };
+        let statements = decision::lower(
+            &tree,
+            (value, &ty),
+            context.tables,
+            context.layouts,
+            &mut lower,
+        );
+        self.statements.extend(statements);
+
+        for (arm, label) in arms.iter().zip(&labels) {
+            self.push(ir::Statement::Label(*label));
+            let source = self.expression(&arm.expression);
+            self.push(ir::Statement::Assign {
+                target: result,
+                source,
+            });
+            self.push(ir::Statement::Goto(end));
+        }
+
+        self.push(ir::Statement::Label(end));
+        result
+    }
+
+    fn without_block(
+        &mut self,
+        expression: &'a ExpressionWithoutBlock<Desugared>,
+        span: Span,
+    ) -> VariableId {
+        use ExpressionWithoutBlock::*;
+
+        match expression {
+            ErrorPropagation(operand, _) => self.propagate(operand, span),
+            Return(value, _) => {
+                let value = match value {
+                    Some(value) => self.expression(value),
+                    None => self.variable(),
+                };
+
+                self.unwind(0, "return", span);
+                self.push(ir::Statement::Return(value));
+                self.variable()
+            }
+            Break(label, value, _) => {
+                let value = value.as_ref().map(|value| self.expression(value));
+                let targets = self.targets(label, "break", span);
+                self.unwind(targets.scope_depth, "break", span);
+                self.statements.extend(loops::lower_break(&targets, value));
+                self.variable()
+            }
+            Continue(label, _) => {
+                let targets = self.targets(label, "continue", span);
+                self.unwind(targets.scope_depth, "continue", span);
+                self.push(loops::lower_continue(&targets));
+                self.variable()
+            }
+            Grouped(inner, _) => self.expression(inner),
+            Path(path, ty) => self.path(path, ty, span),
+            Literal(literal, _) => self.literal(literal),
+            Array(elements, ty) => {
+                let Type::Array(element, _) = ty else {
+                    unreachable!("Array expressions have array types");
+                };
+                let layout = self.layout(element);
+                let target = self.variable();
+
+                for (index, element) in elements.iter().enumerate() {
+                    let source = self.expression(element);
+                    self.push(ir::Statement::Insert {
+                        target,
+                        offset: index as u32 * layout.stride(),
+                        source,
+                        size: layout.size,
+                    });
+                }
+
+                target
+            }
+            Repeat(value, _, ty) => {
+                let Type::Array(element, length) = ty else {
+                    unreachable!("Repeat expressions have array types");
+                };
+                let layout = self.layout(element);
+                let source = self.expression(value);
+                let target = self.variable();
+
+                for index in 0..*length {
+                    self.push(ir::Statement::Insert {
+                        target,
+                        offset: index * layout.stride(),
+                        source,
+                        size: layout.size,
+                    });
+                }
+
+                target
+            }
+            Range(Some(start), Some(end), _, ty) => {
+                let Type::Range(_, bound) = ty else {
+                    unreachable!("Range expressions have range types");
+                };
+                let layout = self.layout(bound);
+                let target = self.variable();
+
+                for (index, bound) in [start, end].into_iter().enumerate() {
+                    let source = self.expression(bound);
+                    self.push(ir::Statement::Insert {
+                        target,
+                        offset: index as u32 * layout.stride(),
+                        source,
+                        size: layout.size,
+                    });
+                }
+
+                target
+            }
+            Range(_, _, _, _) => unreachable!("Only indices may leave out the bounds of a range"),
+            Prefix(PrefixOperator::Borrow | PrefixOperator::MutableBorrow, operand, _) => {
+                let place = self.place(operand);
+                self.address(place)
+            }
+            Prefix(operator, operand, ty) => {
+                let right = self.expression(operand);
+                let target = self.variable();
+
+                self.push(match operator {
+                    PrefixOperator::DeReference => ir::Statement::Load {
+                        target,
+                        address: right,
+                        offset: 0,
+                        size: self.layout(ty).size,
+                    },
+                    PrefixOperator::ArithmeticNegate => ir::Statement::UnaryOp {
+                        op: UnaryOp::Negate,
+                        target,
+                        right,
+                    },
+                    // A boolean is `0` or `1`, so flipping every bit would not negate it.
+                    PrefixOperator::LogicalNegate if *ty == Type::Bool => {
+                        ir::Statement::BinaryOpImmediate {
+                            op: BinaryOp::Equal,
+                            target,
+                            left: right,
+                            right: 0,
+                        }
+                    }
+                    PrefixOperator::LogicalNegate => ir::Statement::UnaryOp {
+                        op: UnaryOp::Not,
+                        target,
+                        right,
+                    },
+                    PrefixOperator::Borrow | PrefixOperator::MutableBorrow => {
+                        unreachable!("Borrows are lowered as places")
+                    }
+                });
+
+                target
+            }
+            Binary(left, operator, right, _) => self.binary(left, operator, right),
+            Call(function, arguments, ty) => self.call(function, arguments, ty, span),
+            MethodCall(_, _, _, removed) => match *removed {},
+            Field(base, name, _) if is_len(type_of(base), name) => self.len(base),
+            Field(_, _, ty) | Index(_, _, ty) => {
+                let place = self.place_of(expression, span);
+                self.read(place, ty)
+            }
+            Asm(assembly, _) => self.assembly(assembly),
+            Other(_) => {
+                self.push(ir::Statement::Unreachable);
+                self.variable()
+            }
+        }
+    }
+
+    /// Lowers inline assembly. Its template was split by type checking, and is split again
+    /// the same way. Inputs are evaluated in order, and the places of outputs are found before
+    /// the assembly runs, then written with the values it leaves in their variables.
+    fn assembly(&mut self, assembly: &'a Assembly<Desugared>) -> VariableId {
+        let types = assembly
+            .operands
+            .iter()
+            .map(|operand| self.type_of(&operand.expression))
+            .collect::<Vec<_>>();
+        let template = asm::check(assembly, &types).expect("Assembly was checked by type checking");
+
+        let mut variables = Vec::new();
+        let mut outputs = Vec::new();
+
+        for (operand, ty) in assembly.operands.iter().zip(&types) {
+            match operand.direction {
+                Direction::In => variables.push(self.expression(&operand.expression)),
+                Direction::Out => {
+                    let place = self.place(&operand.expression);
+                    let variable = self.variable();
+                    variables.push(variable);
+                    outputs.push((place, variable, ty));
+                }
+            }
+        }
+
+        self.push(asm::lower(assembly, template, variables));
+
+        for (place, variable, ty) in outputs {
+            self.write(place, variable, ty);
+        }
+
+        self.variable()
+    }
+
+    fn path(&mut self, path: &ResolvedPath, ty: &Type, span: Span) -> VariableId {
+        match &path.resolution {
+            Resolution::Local(local) => {
+                let source = self.local_path(*local, path);
+                self.copy(source)
+            }
+            Resolution::Def(DefKind::Variant, _) if matches!(ty, Type::Function(_, _)) => {
+                self.unsupported("Variant constructors as function pointers", span)
+            }
+            Resolution::Def(DefKind::Variant, variant) => self.construct(*variant, ty, &[]),
+            Resolution::Def(DefKind::Const, constant) => self.constant(*constant),
+            Resolution::Def(DefKind::Static, _) => {
+                let place = self.static_place(path);
+                self.read(place, ty)
+            }
+            Resolution::Def(DefKind::Fn | DefKind::AssocFn, function) => {
+                let target = self.variable();
+
+                if let Some(function) = self.request(*function, ty, &[], span) {
+                    self.push(ir::Statement::FunctionAddress { target, function });
+                }
+
+                target
+            }
+            _ => unreachable!("Type checking only accepts values as expressions"),
+        }
+    }
+
+    /// The variable of a local named by a path. The receiver is the only local named `self`,
+    /// and takes the variable of the first parameter.
+    fn local_path(&mut self, local: LocalId, path: &ResolvedPath) -> VariableId {
+        match (self.receiver, path.segments.as_slice()) {
+            (Some(receiver), [segment]) if segment.item == "self" => {
+                *self.locals.entry(local).or_insert(receiver)
+            }
+            _ => self.local(local),
+        }
+    }
+
+    fn static_place(&mut self, path: &ResolvedPath) -> Place {
+        let Resolution::Def(DefKind::Static, global) = path.resolution else {
+            unreachable!("Only statics have global places");
+        };
+
+        let address = self.variable();
+        self.push(ir::Statement::Global {
+            target: address,
+            global,
+        });
+        Place::Memory(address, 0)
+    }
+
+    /// Lowers the initializer of a constant in place of a use of it.
+    fn constant(&mut self, constant: DefId) -> VariableId {
+        let initializer = self.context.items.constants[&constant];
+        self.expression(initializer)
+    }
+
+    fn literal(&mut self, literal: &Literal) -> VariableId {
+        let target = self.variable();
+
+        let value = match literal {
+            Literal::Integer(value) => *value as i64,
+            Literal::Character(value) => *value as i64,
+            Literal::True => 1,
+            Literal::False => 0,
+            Literal::String(value) => {
+                self.push(ir::Statement::Bytes {
+                    target,
+                    value: value.as_bytes().to_vec(),
+                });
+                return target;
+            }
+            Literal::Unit => return target,
+            Literal::Never => {
+                self.push(ir::Statement::Unreachable);
+                return target;
+            }
+        };
+
+        self.push(ir::Statement::Immediate { target, value });
+        target
+    }
+
+    fn copy(&mut self, source: VariableId) -> VariableId {
+        let target = self.variable();
+        self.push(ir::Statement::Assign { target, source });
+        target
+    }
+
+    /// Builds a value of an enum's variant from the values of its fields.
+    fn construct(&mut self, variant: DefId, ty: &Type, fields: &[(VariableId, u32)]) -> VariableId {
+        let Type::Named(def, _, arguments) = self.ty(ty) else {
+            unreachable!("Variants construct enums");
+        };
+        let layout = self
+            .context
+            .layouts
+            .of_enum(def, &arguments)
+            .expect("Every enum instance has a layout");
+
+        let target = self.variable();
+        self.statements
+            .extend(decision::construct(layout, variant, target, fields));
+        target
+    }
+
+    fn binary(
+        &mut self,
+        left: &'a Expression<Desugared>,
+        operator: &BinaryOperator,
+        right: &'a Expression<Desugared>,
+    ) -> VariableId {
+        use BinaryOperator::*;
+
+        let ty = self.type_of(left);
+
+        match operator {
+            LazyAnd | LazyOr => self.lazy(left, operator, right),
+            Equal => {
+                let place = self.place(left);
+                let source = self.expression(right);
+                self.write(place, source, &ty);
+                self.variable()
+            }
+            PlusEqual | MinusEqual | TimesEqual | DivideEqual | AndEqual | OrEqual
+            | LShiftEqual | RShiftEqual => {
+                let place = self.place(left);
+                let current = self.read(place, &ty);
+                let right = self.expression(right);
+                let source = self.operation(operator, current, right, &ty);
+                self.write(place, source, &ty);
+                self.variable()
+            }
+            _ => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                self.operation(operator, left, right, &ty)
+            }
+        }
+    }
+
+    /// Applies an arithmetic, bitwise, or comparison operator to operands of type `ty`.
+    fn operation(
+        &mut self,
+        operator: &BinaryOperator,
+        left: VariableId,
+        right: VariableId,
+        ty: &Type,
+    ) -> VariableId {
+        use BinaryOperator::*;
+
+        let signed = matches!(ty, Type::I8 | Type::I16 | Type::I32);
+
+        // `a > b` is `b < a`, and `a >= b` is `b <= a`.
+        let (op, left, right) = match operator {
+            Plus | PlusEqual => (BinaryOp::Add, left, right),
+            Minus | MinusEqual => (BinaryOp::Sub, left, right),
+            Times | TimesEqual => (BinaryOp::Mult, left, right),
+            Divide | DivideEqual => (BinaryOp::Divide { signed }, left, right),
+            And | AndEqual => (BinaryOp::And, left, right),
+            Or | OrEqual => (BinaryOp::Or, left, right),
+            LShift | LShiftEqual => (BinaryOp::ShiftLeft, left, right),
+            RShift | RShiftEqual => (BinaryOp::ShiftRight { signed }, left, right),
+            Eq => (BinaryOp::Equal, left, right),
+            Ne => (BinaryOp::NotEqual, left, right),
+            Lt => (BinaryOp::Less { signed }, left, right),
+            Le => (BinaryOp::LessEqual { signed }, left, right),
+            Gt => (BinaryOp::Less { signed }, right, left),
+            Ge => (BinaryOp::LessEqual { signed }, right, left),
+            LazyAnd | LazyOr | Equal => unreachable!("Only arithmetic operators are applied"),
+        };
+
+        let target = self.variable();
+        self.push(ir::Statement::BinaryOp {
+            op,
+            target,
+            left,
+            right,
+        });
+        target
+    }
+
+    /// Lowers `&&` and `||`, which only evaluate their right operand if the left one does not
+    /// decide the result.
+    fn lazy(
+        &mut self,
+        left: &'a Expression<Desugared>,
+        operator: &BinaryOperator,
+        right: &'a Expression<Desugared>,
+    ) -> VariableId {
+        let result = self.variable();
+        let end = self.label();
+
+        let source = self.expression(left);
+        self.push(ir::Statement::Assign {
+            target: result,
+            source,
+        });
+
+        match operator {
+            BinaryOperator::LazyAnd => {
+                let rest = self.label();
+                self.push(ir::Statement::GotoIf(result, rest));
+                self.push(ir::Statement::Goto(end));
+                self.push(ir::Statement::Label(rest));
+            }
+            _ => self.push(ir::Statement::GotoIf(result, end)),
+        }
+
+        let source = self.expression(right);
+        self.push(ir::Statement::Assign {
+            target: result,
+            source,
+        });
+        self.push(ir::Statement::Label(end));
+        result
+    }
+
+    fn call(
+        &mut self,
+        function: &'a Expression<Desugared>,
+        arguments: &'a [Expression<Desugared>],
+        ty: &Type,
+        span: Span,
+    ) -> VariableId {
+        let resolution = match &function.item {
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(path, _)) => {
+                Some(&path.resolution)
+            }
+            _ => None,
+        };
+
+        match resolution {
+            Some(Resolution::Def(DefKind::Variant, variant)) => {
+                let fields = arguments
+                    .iter()
+                    .map(|argument| {
+                        let size = self.layout(&self.type_of(argument)).size;
+                        (self.expression(argument), size)
+                    })
+                    .collect::<Vec<_>>();
+
+                self.construct(*variant, ty, &fields)
+            }
+            Some(Resolution::Def(DefKind::Fn | DefKind::AssocFn, callee)) => {
+                let Some(function) = self.request(*callee, type_of(function), arguments, span)
+                else {
+                    return self.variable();
+                };
+
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                let target = self.variable();
+                self.push(ir::Statement::Call {
+                    target,
+                    function,
+                    arguments,
+                });
+                target
+            }
+            // Anything else callable is a function pointer, such as a local or a field.
+            _ => {
+                let function = self.expression(function);
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                let target = self.variable();
+                self.push(ir::Statement::IndirectCall {
+                    target,
+                    function,
+                    arguments,
+                });
+                target
+            }
+        }
+    }
+
+    /// The instance of `function` named by a path of type `ty`, passed `arguments` if it is
+    /// called, requesting that it be lowered. Gives `None` once any error is reported.
+    fn request(
+        &mut self,
+        function: DefId,
+        ty: &Type,
+        arguments: &'a [Expression<Desugared>],
+        span: Span,
+    ) -> Option<FunctionId> {
+        let callee = match self.callee(function, ty, arguments) {
+            Ok(callee) => callee,
+            Err(what) => {
+                self.unsupported(what, span);
+                return None;
+            }
+        };
+
+        let tables = self.context.tables;
+        match monomorphize::resolve(&callee, &self.instance, &tables.traits, span) {
+            Ok(instance) => Some(self.instances.request(instance)),
+            Err(errors) => {
+                self.errors.extend(errors);
+                None
+            }
+        }
+    }
+
+    /// The function named by a path of type `ty`, in terms of the type parameters of the
+    /// function being lowered. Type checking inferred the generic arguments of a call from its
+    /// arguments, and they are inferred again the same way, which leaves nothing to infer them
+    /// from when the function is not called. A function declared by a trait is named through
+    /// a bound, and the type standing for `Self` is found in the type of the path instead,
+    /// which only works if the function's signature mentions `Self`. Unsupported uses are
+    /// described by the error.
+    fn callee(
+        &self,
+        function: DefId,
+        ty: &Type,
+        arguments: &'a [Expression<Desugared>],
+    ) -> Result<Callee, &'static str> {
+        let tables = self.context.tables;
+        let signature = &tables.signatures[&function];
+        let owner = tables.owners.get(&function);
+        let declared = signature_type(signature, owner);
+        let mut found = Vec::new();
+
+        match owner {
+            Some(Type::Parameter(trait_, _))
+                if self.context.definitions.get(*trait_).kind == DefKind::Trait =>
+            {
+                infer(&declared, ty, &[*trait_], &mut found);
+                let Some((_, self_ty)) = found.pop() else {
+                    return Err("Trait functions whose signatures do not mention `Self`");
+                };
+
+                Ok(Callee::TraitMethod {
+                    trait_: *trait_,
+                    method: signature.name.clone(),
+                    self_ty,
+                })
+            }
+            _ => {
+                let Type::Function(parameters, _) = declared else {
+                    unreachable!("Functions have function types");
+                };
+                let generics = tables.generics.get(&function).cloned().unwrap_or_default();
+
+                for (parameter, argument) in parameters.iter().zip(arguments) {
+                    infer(parameter, type_of(argument), &generics, &mut found);
+                }
+
+                if found.len() < generics.len() {
+                    return Err("Pointers to generic functions");
+                }
+
+                Ok(Callee::Function(function, found))
+            }
+        }
+    }
+
+    /// The length of an array or slice, or of one behind references.
+    fn len(&mut self, base: &'a Expression<Desugared>) -> VariableId {
+        let (place, ty) = self.through_references(base);
+        let target = self.variable();
+
+        match (ty, place) {
+            (Type::Array(_, length), _) => self.push(ir::Statement::Immediate {
+                target,
+                value: length as i64,
+            }),
+            (Type::Slice(_), Place::Memory(slice, 0)) => {
+                self.push(ir::Statement::SliceLength { target, slice })
+            }
+            _ => unreachable!("Only arrays and slices have lengths"),
+        }
+
+        target
+    }
+
+    fn place(&mut self, expression: &'a Expression<Desugared>) -> Place {
+        match &expression.item {
+            ExpressionKind::ExpressionWithoutBlock(without_block) => {
+                self.place_of(without_block, expression.span)
+            }
+            ExpressionKind::ExpressionWithBlock(with_block) => {
+                Place::Variable(self.with_block(with_block, expression.span))
+            }
+        }
+    }
+
+    /// The place an expression denotes. Expressions other than variables, fields, indexed
+    /// elements, and dereferences are evaluated into a variable of their own.
+    fn place_of(&mut self, expression: &'a ExpressionWithoutBlock<Desugared>, span: Span) -> Place {
+        use ExpressionWithoutBlock::*;
+
+        match expression {
+            Grouped(inner, _) => self.place(inner),
+            Path(path, _) => match &path.resolution {
+                Resolution::Local(local) => Place::Variable(self.local_path(*local, path)),
+                Resolution::Def(DefKind::Static, _) => self.static_place(path),
+                _ => Place::Variable(self.without_block(expression, span)),
+            },
+            Field(base, name, _) if !is_len(type_of(base), name) => {
+                let (place, owner) = self.through_references(base);
+                let def = match owner {
+                    Type::Named(def, _, _) => def,
+                    // A range holds its start, followed by its end.
+                    Type::Range(_, bound) => {
+                        return match name.ident.item.as_str() {
+                            "start" => place,
+                            _ => place.offset(self.layout(&bound).stride()),
+                        };
+                    }
+                    _ => unreachable!("Only structs and ranges have fields"),
+                };
+
+                let index = self.context.tables.fields[&def]
+                    .iter()
+                    .position(|field| field.name == name.ident.item)
+                    .expect("Fields were checked by type checking");
+                let offset = self
+                    .context
+                    .layouts
+                    .of_struct(def)
+                    .expect("Every struct has a layout")
+                    .offsets[index];
+
+                place.offset(offset)
+            }
+            Index(base, index, _) => {
+                let address = self.element(base, index);
+                Place::Memory(address, 0)
+            }
+            Prefix(PrefixOperator::DeReference, operand, _) => {
+                Place::Memory(self.expression(operand), 0)
+            }
+            _ => Place::Variable(self.without_block(expression, span)),
+        }
+    }
+
+    /// The place of the value that `base` refers to through any number of references, along
+    /// with its type. Fields and elements are reached through references implicitly. A slice
+    /// is left as the reference to it, which carries its length.
+    fn through_references(&mut self, base: &'a Expression<Desugared>) -> (Place, Type) {
+        let Type::Reference(_, inner) = self.type_of(base) else {
+            return (self.place(base), self.type_of(base));
+        };
+
+        let mut place = Place::Memory(self.expression(base), 0);
+        let mut ty = *inner;
+
+        while let Type::Reference(_, inner) = ty {
+            let address = self.read(
+                place,
+                &Type::Reference(Mutability::Immutable, inner.clone()),
+            );
+            place = Place::Memory(address, 0);
+            ty = *inner;
+        }
+
+        (place, ty)
+    }
+
+    /// The address of the element `base[index]`, or the reference to the slice `base[range]`
+    /// when it is indexed by a range, as the place of a slice is the reference to it.
+    fn element(
+        &mut self,
+        base: &'a Expression<Desugared>,
+        index: &'a Expression<Desugared>,
+    ) -> VariableId {
+        let context = self.context;
+        let (place, ty) = self.through_references(base);
+        let pointer_size = context.layouts.model().pointer_size;
+
+        let (element, length, start) = match (index::indexable(&ty), place) {
+            (Some((element, length @ Length::Static(_))), place) => {
+                (element.clone(), length, self.address(place))
+            }
+            (Some((element, length @ Length::Dynamic)), Place::Memory(slice, 0)) => {
+                let pointer = self.variable();
+                self.push(ir::Statement::Project {
+                    target: pointer,
+                    source: slice,
+                    offset: 0,
+                    size: pointer_size,
+                });
+                (element.clone(), length, pointer)
+            }
+            _ => unreachable!("Only arrays and slices are indexed"),
+        };
+
+        let lookup = |path: &ResolvedPath| match path.resolution {
+            Resolution::Def(DefKind::Const, def) => context.tables.constants.get(&def).copied(),
+            _ => None,
+        };
+        let checks = match index::checks(length, index, context.bounds_checks, &lookup) {
+            Ok(checks) => checks,
+            Err(error) => {
+                self.errors.push(error);
+                return self.variable();
+            }
+        };
+
+        // The length is only read if a check needs it, or a slice ends where its base does.
+        let length = |lowering: &mut Self| {
+            let target = lowering.variable();
+            lowering.push(match (length, place) {
+                (Length::Static(size), _) => ir::Statement::Immediate {
+                    target,
+                    value: size as i64,
+                },
+                (Length::Dynamic, Place::Memory(slice, _)) => {
+                    ir::Statement::SliceLength { target, slice }
+                }
+                (Length::Dynamic, _) => unreachable!("Slices are reached through references"),
+            });
+            target
+        };
+
+        let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Range(
+            first,
+            last,
+            limits,
+            _,
+        )) = &index.item
+        else {
+            let index = self.expression(index);
+
+            if checks.contains(&Check::Index) {
+                let length = length(self);
+                self.push(ir::Statement::BoundsCheck {
+                    index,
+                    length,
+                    inclusive: false,
+                });
+            }
+
+            return self.offset(start, index, &element);
+        };
+
+        // A missing start is zero, and a missing end is the length of the base.
+        let first = match first {
+            Some(first) => self.expression(first),
+            None => self.literal(&Literal::Integer(0)),
+        };
+        let last = match last {
+            Some(last) => self.expression(last),
+            None => length(self),
+        };
+
+        for check in checks {
+            let (index, length, inclusive) = match check {
+                Check::Index => unreachable!("Ranges are checked by their bounds"),
+                Check::RangeStart => (first, last, true),
+                Check::RangeEnd(RangeLimits::HalfOpen) => (last, length(self), true),
+                Check::RangeEnd(RangeLimits::Closed) => (last, length(self), false),
+            };
+
+            self.push(ir::Statement::BoundsCheck {
+                index,
+                length,
+                inclusive,
+            });
+        }
+
+        // The end of a closed range is part of it.
+        let end = match limits {
+            RangeLimits::HalfOpen => last,
+            RangeLimits::Closed => {
+                let end = self.variable();
+                self.push(ir::Statement::BinaryOpImmediate {
+                    op: BinaryOp::Add,
+                    target: end,
+                    left: last,
+                    right: 1,
+                });
+                end
+            }
+        };
+
+        let pointer = self.offset(start, first, &element);
+        let count = self.variable();
+        let slice = self.variable();
+
+        self.push(ir::Statement::BinaryOp {
+            op: BinaryOp::Sub,
+            target: count,
+            left: end,
+            right: first,
+        });
+        self.push(ir::Statement::Insert {
+            target: slice,
+            offset: 0,
+            source: pointer,
+            size: pointer_size,
+        });
+        self.push(ir::Statement::Insert {
+            target: slice,
+            offset: pointer_size,
+            source: count,
+            size: pointer_size,
+        });
+
+        slice
+    }
+
+    /// The address of the element `index` elements of type `element` past `start`.
+    fn offset(&mut self, start: VariableId, index: VariableId, element: &Type) -> VariableId {
+        let scaled = self.variable();
+        let address = self.variable();
+
+        self.push(ir::Statement::BinaryOpImmediate {
+            op: BinaryOp::Mult,
+            target: scaled,
+            left: index,
+            right: self.layout(element).stride() as usize,
+        });
+        self.push(ir::Statement::BinaryOp {
+            op: BinaryOp::Add,
+            target: address,
+            left: start,
+            right: scaled,
+        });
+
+        address
+    }
+
+    fn read(&mut self, place: Place, ty: &Type) -> VariableId {
+        let size = self.layout(ty).size;
+
+        match place {
+            Place::Variable(source) => self.copy(source),
+            Place::Part(source, offset) => {
+                let target = self.variable();
+                self.push(ir::Statement::Project {
+                    target,
+                    source,
+                    offset,
+                    size,
+                });
+                target
+            }
+            Place::Memory(address, offset) => {
+                let target = self.variable();
+                self.push(ir::Statement::Load {
+                    target,
+                    address,
+                    offset,
+                    size,
+                });
+                target
+            }
+        }
+    }
+
+    fn write(&mut self, place: Place, source: VariableId, ty: &Type) {
+        let size = self.layout(ty).size;
+
+        self.push(match place {
+            Place::Variable(target) => ir::Statement::Assign { target, source },
+            Place::Part(target, offset) => ir::Statement::Insert {
+                target,
+                offset,
+                source,
+                size,
+            },
+            Place::Memory(address, offset) => ir::Statement::Store {
+                address,
+                offset,
+                source,
+                size,
+            },
+        });
+    }
+
+    fn address(&mut self, place: Place) -> VariableId {
+        let (base, offset) = match place {
+            Place::Variable(source) => (self.address_of(source), 0),
+            Place::Part(source, offset) => (self.address_of(source), offset),
+            Place::Memory(address, offset) => (address, offset),
+        };
+
+        if offset == 0 {
+            return base;
+        }
+
+        let target = self.variable();
+        self.push(ir::Statement::BinaryOpImmediate {
+            op: BinaryOp::Add,
+            target,
+            left: base,
+            right: offset as usize,
+        });
+        target
+    }
+
+    fn address_of(&mut self, source: VariableId) -> VariableId {
+        let target = self.variable();
+        self.push(ir::Statement::Address { target, source });
+        target
+    }
+}
+
+fn local_of(name: &Name) -> LocalId {
+    match name.resolution {
+        Some(Resolution::Local(local)) => local,
+        _ => unreachable!("Bindings resolve to locals"),
+    }
+}
+
+/// Whether `base.name` is the builtin length of an array or slice rather than a field.
+fn is_len(base: &Type, name: &Name) -> bool {
+    name.ident.item == "len" && index::indexable(base).is_some()
+}
+
+/// What lowering the decision tree of a `when` expression needs, given its arms and the label
+/// starting the body of each.
+struct Arms<'l, 'a, 'b> {
+    lowering: &'l mut Lowering<'a, 'b>,
+    arms: &'a [Arm<Desugared>],
+    labels: &'l [LabelId],
+}
+
+impl decision::Lower for Arms<'_, '_, '_> {
+    fn variable(&mut self) -> VariableId {
+        self.lowering.variable()
+    }
+
+    fn label(&mut self) -> LabelId {
+        self.lowering.label()
+    }
+
+    fn local(&mut self, local: LocalId) -> VariableId {
+        self.lowering.local(local)
+    }
+
+    fn arm(&mut self, arm: usize) -> LabelId {
+        self.labels[arm]
+    }
+
+    fn guard(&mut self, arm: usize) -> (Vec<ir::Statement>, VariableId) {
+        let guard = self.arms[arm]
+            .guard
+            .as_ref()
+            .expect("Only the guards of guarded arms are lowered");
+        self.lowering.capture(|lowering| lowering.expression(guard))
+    }
+
+    fn constant(&mut self, def: DefId) -> (Vec<ir::Statement>, VariableId) {
+        self.lowering.capture(|lowering| lowering.constant(def))
+    }
+}
+
+/// What lowering `?` needs, given where it appears.
+struct Propagating<'l, 'a, 'b> {
+    lowering: &'l mut Lowering<'a, 'b>,
+    span: Span,
+}
+
+impl propagate::Lower for Propagating<'_, '_, '_> {
+    fn variable(&mut self) -> VariableId {
+        self.lowering.variable()
+    }
+
+    fn label(&mut self) -> LabelId {
+        self.lowering.label()
+    }
+
+    fn deferred(&mut self) -> Vec<ir::Statement> {
+        let span = self.span;
+        let (statements, _) = self.lowering.capture(|lowering| {
+            lowering.unwind(0, "?", span);
+            lowering.variable()
+        });
+
+        statements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::BuildOptions;
+    use crate::generate::emit;
+    use crate::lower::*;
+    use crate::model::ir::Statement;
+    use crate::testing;
+
+    fn body(source: &'static str) -> Vec<Statement> {
+        testing::lowered(source).swap_remove(0).body
+    }
+
+    #[test]
+    fn break_value() {
+        let body = body("fn main() { let x: u8 = loop { break 5; }; }");
+
+        // `x` is variable 0, and the loop's result is variable 1.
+        assert!(matches!(
+            body.as_slice(),
+            [
+                Statement::Label(0),
+                Statement::Immediate {
+                    target: 2,
+                    value: 5
+                },
+                Statement::Assign {
+                    target: 1,
+                    source: 2
+                },
+                Statement::Goto(1),
+                Statement::Goto(0),
+                Statement::Label(1),
+                Statement::Assign {
+                    target: 0,
+                    source: 1
+                },
+                Statement::Return(_),
+            ]
+        ));
+    }
+
+    #[test]
+    fn labeled_targets() {
+        let body = body("fn main() { 'outer: loop { while True { continue; } break 'outer; } }");
+
+        // The `loop` starts at label 0 and ends at label 1, and the `while` loop tests its
+        // condition at label 2, ends at label 3, and runs its body from label 4.
+        assert!(matches!(
+            body.as_slice(),
+            [
+                Statement::Label(0),
+                Statement::Label(2),
+                Statement::Immediate { target: 1, .. },
+                Statement::GotoIf(1, 4),
+                Statement::Goto(3),
+                Statement::Label(4),
+                Statement::Goto(2),
+                Statement::Goto(2),
+                Statement::Label(3),
+                Statement::Goto(1),
+                Statement::Goto(0),
+                Statement::Label(1),
+                Statement::Return(_),
+            ]
+        ));
+    }
+
+    #[test]
+    fn case_arms() {
+        let body = body(
+            "fn main() { let x: u8 = 3; let y = when x { 0 => 1, n if n > 5 => n, _ => 2 }; }",
+        );
+
+        // Each arm's body starts at its own label and ends by jumping past the other arms.
+        let labels = body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Label(label) => Some(*label),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let end = *labels.last().unwrap();
+
+        let exits = body
+            .iter()
+            .filter(|statement| matches!(statement, Statement::Goto(label) if *label == end))
+            .count();
+        assert_eq!(exits, 3);
+        assert!(body
+            .iter()
+            .any(|statement| matches!(statement, Statement::Switch { .. })));
+    }
+
+    #[test]
+    fn generic_instances() {
+        let functions = testing::lowered(
+            "fn id[T](value: T) -> T { value }
+            fn main() { let a: u8 = 1; let b = id(a); let c = id(True); let d = id(b); }",
+        );
+
+        // `id` is lowered once for each type it is called with, and named after that type.
+        let names = functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "id[u8]", "id[bool]"]);
+        let called = functions[0]
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Call { function, .. } => Some(*function),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(called, vec![1, 2, 1]);
+    }
+
+    #[test]
+    fn static_dispatch() {
+        let functions = testing::lowered(
+            "trait Size { fn size(self) -> u8; }
+            impl Size for u8 { fn size(self) -> u8 { 1 } }
+            impl Size for bool { fn size(self) -> u8 { 2 } }
+            fn twice[T: Size](value: T) -> u8 { T::size(value) + T::size(value) }
+            fn main() { let x: u8 = 1; twice(x); twice(True); }",
+        );
+
+        // Both instances of `twice` call the implementation for the type `T` stands for.
+        let names = functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["main", "twice[u8]", "twice[bool]", "size", "size"]
+        );
+
+        let called = |function: &ir::Function| {
+            function
+                .body
+                .iter()
+                .filter_map(|statement| match statement {
+                    Statement::Call { function, .. } => Some(*function),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let returned = |function: usize| {
+            functions[function]
+                .body
+                .iter()
+                .find_map(|statement| match statement {
+                    Statement::Immediate { value, .. } => Some(*value),
+                    _ => None,
+                })
+        };
+
+        // The `u8` instance of `twice` comes first, as it is called first.
+        let [first, second] = called(&functions[1])[..] else {
+            panic!("`twice` calls `size` twice");
+        };
+        assert_eq!(first, second);
+        assert_eq!(returned(first), Some(1));
+
+        let [first, second] = called(&functions[2])[..] else {
+            panic!("`twice` calls `size` twice");
+        };
+        assert_eq!(first, second);
+        assert_eq!(returned(first), Some(2));
+    }
+
+    #[test]
+    fn generic_enum_instances() {
+        // `Option[u16]` is only mentioned by the instance of `some`, so the geode itself
+        // never mentions it.
+        let functions = testing::lowered(
+            "fn some[T](value: T) -> bool { let x = Some(value); True }
+            fn main() { let x: u16 = 1; some(x); }",
+        );
+
+        assert_eq!(functions.len(), 2);
+    }
+
+    #[test]
+    fn method_calls() {
+        let functions = testing::lowered(
+            "impl u8 { fn double(self) -> u8 { self + self } fn bump(&mut self) { *self += 1; } }
+            fn main() { let mut x: u8 = 1; x.bump(); let y = x.double(); }",
+        );
+
+        // `bump` borrows `x`, variable 0, while `double` takes a copy of it.
+        let body = &functions[0].body;
+        let borrowed = body
+            .iter()
+            .find_map(|statement| match statement {
+                Statement::Address { target, source: 0 } => Some(*target),
+                _ => None,
+            })
+            .unwrap();
+        let calls = body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Call {
+                    function,
+                    arguments,
+                    ..
+                } => Some((functions[*function].name.as_str(), arguments.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], ("bump", vec![borrowed]));
+        assert_eq!(calls[1].0, "double");
+        assert_ne!(calls[1].1, vec![borrowed]);
+    }
+
+    #[test]
+    fn bound_methods() {
+        let functions = testing::lowered(
+            "trait Size { fn size(&self) -> u8; }
+            impl Size for u8 { fn size(&self) -> u8 { *self } }
+            fn total[T: Size](values: &[T; 2]) -> u8 { values[0].size() + values[1].size() }
+            fn main() { let values: [u8; 2] = [1, 2]; total(&values); }",
+        );
+
+        let names = functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "total[u8]", "size"]);
+    }
+
+    #[test]
+    fn calls() {
+        let functions =
+            testing::lowered("fn double(x: u8) -> u8 { x + x } fn main() { double(double(1)); }");
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[1].name, "double");
+        assert_eq!(functions[1].parameters, vec![0]);
+
+        let calls = functions[0]
+            .body
+            .iter()
+            .filter(|statement| matches!(statement, Statement::Call { function: 1, .. }))
+            .count();
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn field_function_pointers() {
+        let functions = testing::lowered(
+            "struct Ops { apply: fn(u8) -> u8 }
+            fn main(ops: &Ops) -> u8 { (ops.apply)(1) }",
+        );
+
+        // The pointer is loaded out of the struct behind `ops` and called with the argument.
+        let body = &functions[0].body;
+        let pointer = body
+            .iter()
+            .find_map(|statement| match statement {
+                Statement::Load { target, .. } => Some(*target),
+                _ => None,
+            })
+            .unwrap();
+        let called = body
+            .iter()
+            .find_map(|statement| match statement {
+                Statement::IndirectCall {
+                    function,
+                    arguments,
+                    ..
+                } => Some((*function, arguments.len())),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(called, (pointer, 1));
+        assert_eq!(functions.len(), 1);
+    }
+
+    #[test]
+    fn array_function_pointers() {
+        let functions = testing::lowered(
+            "fn double(x: u8) -> u8 { x + x } fn negate(x: u8) -> u8 { 0 - x }
+            fn main() { let table: [fn(u8) -> u8; 2] = [double, negate]; table[1](3); }",
+        );
+
+        let names = functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "double", "negate"]);
+
+        let body = &functions[0].body;
+        let mut addresses = body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::FunctionAddress { function, .. } => Some(*function),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        addresses.sort();
+        assert_eq!(addresses, vec![1, 2]);
+        assert!(body
+            .iter()
+            .any(|statement| matches!(statement, Statement::IndirectCall { .. })));
+        assert!(!body
+            .iter()
+            .any(|statement| matches!(statement, Statement::Call { .. })));
+    }
+
+    /// The calls and jumps of the function lowered `index`th, in order, with calls named by
+    /// their function.
+    fn trace(source: &'static str, index: usize) -> Vec<String> {
+        let functions = testing::lowered(source);
+
+        functions[index]
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Call { function, .. } => Some(functions[*function].name.clone()),
+                Statement::Goto(_) => Some(String::from("goto")),
+                Statement::Return(_) => Some(String::from("return")),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deferred_on_return() {
+        let trace = trace(
+            "fn first() {} fn second() {} fn third() {}
+            fn main() { defer first(); { defer second(); defer third(); return; } }",
+            0,
+        );
+
+        assert_eq!(trace, vec!["third", "second", "first", "return", "return"]);
+    }
+
+    #[test]
+    fn deferred_on_break() {
+        let trace = trace(
+            "fn outer() {} fn first() {} fn second() {}
+            fn main() { defer outer(); loop { defer first(); defer second(); break; } }",
+            0,
+        );
+
+        // Only the blocks within the loop are left by `break`.
+        assert_eq!(
+            trace,
+            vec!["second", "first", "goto", "goto", "outer", "return"]
+        );
+    }
+
+    #[test]
+    fn deferred_on_continue() {
+        let trace = trace(
+            "fn outer() {} fn first() {} fn second() {}
+            fn main() {
+                defer outer();
+                let mut i: u8 = 0;
+                while i < 3 { defer first(); i += 1; defer second(); continue; }
+            }",
+            0,
+        );
+
+        // The loop's condition jumps past the body before it is entered.
+        assert_eq!(
+            trace,
+            vec!["goto", "second", "first", "goto", "goto", "outer", "return"]
+        );
+    }
+
+    #[test]
+    fn deferred_on_fall_through() {
+        let trace = trace(
+            "fn first() {} fn second() {}
+            fn main() -> u8 { defer first(); defer second(); 1 }",
+            0,
+        );
+
+        assert_eq!(trace, vec!["second", "first", "return"]);
+    }
+
+    #[test]
+    fn deferred_on_propagation() {
+        let trace = trace(
+            "fn first() {} fn second() {}
+            fn parse(x: Option[u8]) -> Option[u8] { defer first(); defer second(); Some(x? + 1) }
+            fn main() { parse(None); }",
+            1,
+        );
+
+        // `None` returns early through the deferred expressions, and `Some` falls through to
+        // the end of the body, running them again.
+        assert_eq!(
+            trace,
+            vec!["second", "first", "return", "second", "first", "return"]
+        );
+    }
+
+    #[test]
+    fn bounds_checks() {
+        let source = "fn main() { let mut a: [u8; 4] = [1, 2, 3, 4]; let i: u8 = 2; a[i] = a[3]; }";
+
+        // The constant index is provably in range.
+        let checks = |functions: Vec<ir::Function>| {
+            functions[0]
+                .body
+                .iter()
+                .filter(|statement| matches!(statement, Statement::BoundsCheck { .. }))
+                .count()
+        };
+        assert_eq!(checks(testing::lowered(source)), 1);
+
+        let options = BuildOptions {
+            bounds_checks: false,
+            ..BuildOptions::default()
+        };
+        assert_eq!(checks(testing::built(source, &options).ok().unwrap()), 0);
+
+        let errors = testing::built(
+            "fn main() { let a: [u8; 4] = [1, 2, 3, 4]; let x = a[4]; }",
+            &options,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors[0].message,
+            "Index 4 is out of range for an array of length 4"
+        );
+    }
+
+    #[test]
+    fn slices() {
+        let functions = testing::lowered(
+            "fn first(values: &[u8]) -> u8 { values[0] }
+            fn main() { let a: [u8; 4] = [1, 2, 3, 4]; let i: u32 = 1; first(&a[i..]); }",
+        );
+
+        // The start of the slice is checked against the end of the array, and the slice holds
+        // the address of its first element and its length.
+        let body = &functions[0].body;
+        assert!(body.iter().any(|statement| matches!(
+            statement,
+            Statement::BoundsCheck {
+                inclusive: true,
+                ..
+            }
+        )));
+        let inserts = body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Insert { offset, size, .. } => Some((*offset, *size)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(&inserts[inserts.len() - 2..], [(0, 4), (4, 4)]);
+
+        // Indexing the slice checks against the length it carries.
+        let body = &functions[1].body;
+        let length = body
+            .iter()
+            .find_map(|statement| match statement {
+                Statement::SliceLength { target, .. } => Some(*target),
+                _ => None,
+            })
+            .unwrap();
+        assert!(body.iter().any(|statement| matches!(
+            statement,
+            Statement::BoundsCheck {
+                length: l,
+                inclusive: false,
+                ..
+            } if *l == length
+        )));
+    }
+
+    #[test]
+    fn assembly() {
+        let functions = testing::lowered(
+            "fn main() { let port: u8 = 1; let mut value: u8 = 0;
+            asm { \"in {value}, ({port})\", in port = port, out value = value, clobber \"flags\" }; }",
+        );
+
+        let body = &functions[0].body;
+        let position = body
+            .iter()
+            .position(|statement| matches!(statement, Statement::Assembly { .. }))
+            .unwrap();
+        let Statement::Assembly {
+            template,
+            operands,
+            clobbers,
+        } = &body[position]
+        else {
+            unreachable!();
+        };
+
+        let locations = vec![String::from("c"), String::from("a")];
+        assert_eq!(emit::assembly(template, &locations), "in a, (c)");
+        assert_eq!(operands[0].0, Direction::In);
+        assert_eq!(operands[1].0, Direction::Out);
+        assert_eq!(clobbers, &vec![String::from("flags")]);
+
+        // The output is written back to `value` once the assembly has run.
+        let (_, output) = operands[1];
+        assert!(matches!(
+            body[position + 1],
+            Statement::Assign { source, .. } if source == output
+        ));
+    }
+}
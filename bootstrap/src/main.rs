@@ -2,30 +2,75 @@
 #![allow(unused_variables)]
 #![allow(unused)]
 
-use std::io::Write;
-
-use diagnostic::GraphvizRenderer;
-
+mod asm;
+mod at_least;
+mod attribute;
+mod borrow;
+mod consteval;
+mod decision;
+mod defer;
+mod desugar;
 mod diagnostic;
 mod driver;
 mod error;
+mod exhaustive;
 mod generate;
+mod index;
+mod infer;
+mod initialize;
+mod ir;
+mod iterate;
+mod layout;
+mod lint;
+mod load;
+mod loops;
+mod lower;
+mod method;
 mod model;
+mod monomorphize;
 mod optimize;
 mod parse;
-mod scope;
+mod prelude;
+mod privacy;
+mod propagate;
+mod resolve;
+mod source;
 mod span;
 mod stream;
-mod at_least;
-mod ir;
+#[cfg(test)]
+mod testing;
+mod traits;
+mod typeck;
 
 fn main() {
-    let source = std::fs::read_to_string("opal_tests/enums.opal").unwrap();
-    let mut tokens = parse::tokenize(&source).unwrap();
-    let geode = parse::geode("DUMMY_NAME".to_owned(), &mut tokens).unwrap();
-    // let graph = GraphvizRenderer::render(&geode);
-    // let mut file = std::fs::File::create("test_output.gv").unwrap();
-    // file.write(graph.as_bytes());
-    println!("{:#?}", geode);
-    // println!("{graph}");
+    let options = match driver::parse_args(std::env::args().collect()) {
+        Some(driver::Subcommand::Build(options) | driver::Subcommand::Run(options, _)) => options,
+        Some(driver::Subcommand::New(name)) => {
+            eprintln!("Creating the project `{name}` is not supported yet");
+            std::process::exit(1);
+        }
+        None => std::process::exit(1),
+    };
+
+    let mut sources = source::SourceMap::new();
+    let root = std::path::Path::new(driver::ROOT);
+
+    let mut warnings = Vec::new();
+    let errors = driver::build(root, &options, &load::Disk, &mut sources, &mut warnings)
+        .err()
+        .unwrap_or_default();
+
+    for warning in &warnings {
+        eprintln!("{}", driver::render(warning, "warning", &sources));
+    }
+    for error in &errors {
+        eprintln!("{}", driver::render(error, "error", &sources));
+    }
+    if let Some(summary) = lint::summary(warnings.len(), errors.len()) {
+        eprintln!("{summary}");
+    }
+
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,296 @@
+use crate::model::base::{
+    Expression, ExpressionKind, ExpressionWithoutBlock, Mutability, PrefixOperator, Receiver,
+};
+use crate::model::ttg::TreeData;
+use crate::model::types::Type;
+use crate::span::{Span, Spanned};
+
+#[derive(Debug, PartialEq)]
+pub enum MethodError {
+    Undefined,
+    MultiplyDefined(Span),   // Span of the previous definition
+    NotAMethod(Span),        // The function exists, but takes no receiver
+    ImmutableReceiver(Span), // `&mut self` method called through a `&T`
+}
+
+/// A function declared within an `impl` block. The function handle `F` is whatever the
+/// calling pass uses to refer to the function item the method desugars to.
+#[derive(Debug, Clone)]
+pub struct Method<F> {
+    pub name: String,
+    pub receiver: Option<Receiver>,
+    pub function: F,
+    pub span: Span,
+}
+
+/// How a method call's receiver must be adjusted so that it matches the method's receiver.
+/// The receiver is first dereferenced `derefs` times, then borrowed if `borrow` is present.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Adjustment {
+    pub derefs: usize,
+    pub borrow: Option<Mutability>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodTable<F> {
+    // `Type` is not hashable, and there are few enough impls that a scan is fine.
+    impls: Vec<(Type, Vec<Method<F>>)>,
+}
+
+impl<F> MethodTable<F> {
+    pub fn new() -> Self {
+        Self { impls: Vec::new() }
+    }
+
+    pub fn insert(&mut self, self_ty: Type, method: Method<F>) -> Result<(), MethodError> {
+        // Impls for the same type may be split across several blocks, so collisions are
+        // checked against every method of the type rather than just those in one block.
+
+        match self.impls.iter_mut().find(|(ty, _)| *ty == self_ty) {
+            Some((_, methods)) => match methods.iter().find(|m| m.name == method.name) {
+                Some(previous) => Err(MethodError::MultiplyDefined(previous.span)),
+                None => {
+                    methods.push(method);
+                    Ok(())
+                }
+            },
+            None => {
+                self.impls.push((self_ty, vec![method]));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn associated(&self, self_ty: &Type, name: &str) -> Option<&Method<F>> {
+        self.impls
+            .iter()
+            .find(|(ty, _)| ty == self_ty)
+            .and_then(|(_, methods)| methods.iter().find(|m| m.name == name))
+    }
+
+    pub fn lookup(
+        &self,
+        receiver_ty: &Type,
+        name: &str,
+    ) -> Result<(&Method<F>, Adjustment), MethodError> {
+        // Look for the method on the receiver's type. If the type has no such method and is a
+        // reference, look through the reference and try again. The first type that has the
+        // method wins, which means that methods are never ambiguous.
+
+        let mut ty = receiver_ty;
+        let mut derefs = 0;
+
+        loop {
+            if let Some(method) = self.associated(ty, name) {
+                let adjustment = adjust(receiver_ty, derefs, &method.receiver, method.span)?;
+                return Ok((method, adjustment));
+            }
+
+            match ty {
+                Type::Reference(_, inner) => {
+                    ty = inner;
+                    derefs += 1;
+                }
+                _ => return Err(MethodError::Undefined),
+            }
+        }
+    }
+}
+
+/// How a receiver of type `receiver_ty` must be adjusted to call a method taking `receiver`,
+/// which was found on the type `derefs` references into it. `declaration` is the method's.
+pub fn adjust(
+    receiver_ty: &Type,
+    derefs: usize,
+    receiver: &Option<Receiver>,
+    declaration: Span,
+) -> Result<Adjustment, MethodError> {
+    // Mutability of the reference most recently looked through, and whether any reference
+    // looked through was shared.
+    let mut through = None;
+    let mut shared = false;
+
+    let mut ty = receiver_ty;
+    for _ in 0..derefs {
+        let Type::Reference(mutability, inner) = ty else {
+            unreachable!("Methods are only found through references");
+        };
+
+        ty = inner;
+        shared |= *mutability == Mutability::Immutable;
+        through = Some(mutability.clone());
+    }
+
+    Ok(match receiver {
+        None => return Err(MethodError::NotAMethod(declaration)),
+        Some(Receiver::Value) => Adjustment {
+            derefs,
+            borrow: None,
+        },
+        // Calling a `&mut self` method through a shared reference can never be made valid by
+        // re-borrowing.
+        Some(Receiver::Reference(Mutability::Mutable)) if shared => {
+            return Err(MethodError::ImmutableReceiver(declaration))
+        }
+        // `&*r` and `&mut *r` are just `r` when `r` is already a reference of the right
+        // mutability, so the borrow and the dereference cancel out.
+        Some(Receiver::Reference(mutability))
+            if derefs == 1 && through.as_ref() == Some(mutability) =>
+        {
+            Adjustment {
+                derefs: 0,
+                borrow: None,
+            }
+        }
+        Some(Receiver::Reference(mutability)) => Adjustment {
+            derefs,
+            borrow: Some(mutability.clone()),
+        },
+    })
+}
+
+impl Adjustment {
+    /// Wraps the receiver in the dereferences and borrow described by the adjustment. The
+    /// `prefix` callback supplies the phase's extension value for each synthesized prefix
+    /// expression, innermost first.
+    pub fn apply<P: TreeData<P>>(
+        &self,
+        receiver: Expression<P>,
+        mut prefix: impl FnMut(&PrefixOperator) -> P::Prefix,
+    ) -> Expression<P> {
+        let span = receiver.span;
+        let mut expression = receiver;
+
+        let derefs = (0..self.derefs).map(|_| PrefixOperator::DeReference);
+        let borrow = self.borrow.iter().map(|mutability| match mutability {
+            Mutability::Mutable => PrefixOperator::MutableBorrow,
+            Mutability::Immutable => PrefixOperator::Borrow,
+        });
+
+        for operator in derefs.chain(borrow) {
+            let extension = prefix(&operator);
+            expression = Spanned::new(
+                ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+                    operator,
+                    Box::new(expression),
+                    extension,
+                )),
+                span,
+            );
+        }
+
+        expression
+    }
+}
+
+/// Desugars `receiver.method(args)` into the plain call `function(receiver, args)`, where
+/// `function` is an expression naming the method's function item. The receiver is adjusted
+/// first, and the synthesized prefix expressions keep its span.
+pub fn desugar<P: TreeData<P>>(
+    function: Expression<P>,
+    receiver: Expression<P>,
+    adjustment: &Adjustment,
+    arguments: Vec<Expression<P>>,
+    call: P::Call,
+    prefix: impl FnMut(&PrefixOperator) -> P::Prefix,
+) -> ExpressionWithoutBlock<P> {
+    let mut all_arguments = vec![adjustment.apply(receiver, prefix)];
+    all_arguments.extend(arguments);
+
+    ExpressionWithoutBlock::Call(Box::new(function), all_arguments, call)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::method::*;
+
+    fn table(receiver: Option<Receiver>) -> MethodTable<()> {
+        let mut table = MethodTable::new();
+        let method = Method {
+            name: "frob".to_owned(),
+            receiver,
+            function: (),
+            span: Span::new(0, 1),
+        };
+        table.insert(Type::U8, method).ok().unwrap();
+        table
+    }
+
+    fn reference(mutability: Mutability, ty: Type) -> Type {
+        Type::Reference(mutability, Box::new(ty))
+    }
+
+    #[test]
+    fn borrow_value_receiver() {
+        let table = table(Some(Receiver::Reference(Mutability::Immutable)));
+        let (_, adjustment) = table.lookup(&Type::U8, "frob").ok().unwrap();
+
+        assert_eq!(
+            adjustment,
+            Adjustment {
+                derefs: 0,
+                borrow: Some(Mutability::Immutable)
+            }
+        );
+    }
+
+    #[test]
+    fn reference_receiver_passed_through() {
+        let table = table(Some(Receiver::Reference(Mutability::Mutable)));
+        let ty = reference(Mutability::Mutable, Type::U8);
+        let (_, adjustment) = table.lookup(&ty, "frob").ok().unwrap();
+
+        assert_eq!(
+            adjustment,
+            Adjustment {
+                derefs: 0,
+                borrow: None
+            }
+        );
+    }
+
+    #[test]
+    fn deref_for_value_receiver() {
+        let table = table(Some(Receiver::Value));
+        let ty = reference(
+            Mutability::Immutable,
+            reference(Mutability::Mutable, Type::U8),
+        );
+        let (_, adjustment) = table.lookup(&ty, "frob").ok().unwrap();
+
+        assert_eq!(
+            adjustment,
+            Adjustment {
+                derefs: 2,
+                borrow: None
+            }
+        );
+    }
+
+    #[test]
+    fn mutable_method_through_shared_reference() {
+        let table = table(Some(Receiver::Reference(Mutability::Mutable)));
+        let ty = reference(Mutability::Immutable, Type::U8);
+
+        assert_eq!(
+            table.lookup(&ty, "frob").err(),
+            Some(MethodError::ImmutableReceiver(Span::new(0, 1)))
+        );
+    }
+
+    #[test]
+    fn duplicate_method() {
+        let mut table = table(None);
+        let method = Method {
+            name: "frob".to_owned(),
+            receiver: None,
+            function: (),
+            span: Span::new(4, 5),
+        };
+
+        assert_eq!(
+            table.insert(Type::U8, method),
+            Err(MethodError::MultiplyDefined(Span::new(0, 1)))
+        );
+    }
+}
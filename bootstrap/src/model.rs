@@ -1,10 +1,16 @@
 pub mod ast;
 pub mod cfg;
+pub mod def;
+pub mod desugared;
+pub mod fold;
 pub mod ir;
+pub mod resolved;
 pub mod symbol;
 pub mod token;
 pub mod types;
 pub mod ttg;
+pub mod typed;
+pub mod visit;
 pub mod base;
 
 pub use ast::*;
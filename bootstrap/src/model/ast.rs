@@ -8,6 +8,9 @@ impl TreeData<Syntax> for Syntax {
     type Case = ();
     type IfIs = ();
     type For = ();
+    type While = ();
+    type Loop = ();
+    type If = ();
     type ErrorPropagation = ();
     type Return = ();
     type Break = ();
@@ -17,12 +20,17 @@ impl TreeData<Syntax> for Syntax {
     type Path = ();
     type Literal = ();
     type Array = ();
+    type Repeat = ();
+    type Range = ();
     type Prefix = ();
     type Binary = ();
     type Call = ();
+    type MethodCall = ();
     type Field = ();
     type Index = ();
+    type Asm = ();
     type Other = ();
+    type Pattern = ();
 
     type NameRepresentation = Spanned<String>;
     type PathRepresentation = Vec<Self::NameRepresentation>;
@@ -31,7 +39,9 @@ impl TreeData<Syntax> for Syntax {
 
 pub type Type = Spanned<TypeKind>;
 
-pub enum TypeKind {
+/// A type as written. Only paths differ between phases, so later phases reuse this with their
+/// own `PathRepresentation`, as in `resolved::Type`.
+pub enum TypeKind<P: TreeData<P> = Syntax> {
     U8,
     I8,
     U16,
@@ -42,8 +52,11 @@ pub enum TypeKind {
     Char,
     Str,
     Unit,
-    Array(Box<Type>, u32),
-    Reference(Mutability, Box<Type>),
-    Parenthesized(Box<Type>),
-    Path(<Syntax as TreeData<Syntax>>::PathRepresentation),
+    Array(Box<Spanned<TypeKind<P>>>, u32),
+    Slice(Box<Spanned<TypeKind<P>>>),  // `[T]`, which only makes sense behind a reference
+    Reference(Mutability, Box<Spanned<TypeKind<P>>>),
+    Function(Vec<Spanned<TypeKind<P>>>, Option<Box<Spanned<TypeKind<P>>>>),  // `fn(A, B) -> C`, where the return type defaults to `Unit`
+    Parenthesized(Box<Spanned<TypeKind<P>>>),
+    Path(P::PathRepresentation),
+    Generic(P::PathRepresentation, Vec<Spanned<TypeKind<P>>>),  // `Option[T]`, a generic type given its type arguments
 }
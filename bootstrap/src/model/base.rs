@@ -6,13 +6,13 @@
 /// hopefully going to be useful.
 /// 
 /// 1. This model can be parameterized with a marker-type `T` satisfying `TreeData<T>` in order to
-///     attach resolved types to each expression node. This allows for semantic analysis and parsing
-///     to use distinct types without redefining the entire language model to provide only minor
-///     variations in the fields.
+///    attach resolved types to each expression node. This allows for semantic analysis and parsing
+///    to use distinct types without redefining the entire language model to provide only minor
+///    variations in the fields.
 /// 
 /// 2. The model can be desugared to remove specific forms during different passes. For example,
-///     IfIs expressions can be desugared to Case expressions prior to semantic analysis. Additionally,
-///     Case expressions can be replaced with Decision trees prior to lowering.
+///    IfIs expressions can be desugared to Case expressions prior to semantic analysis. Additionally,
+///    Case expressions can be replaced with Decision trees prior to lowering.
 /// 
 /// There are other tricks that I am hoping to employ here. There are some nodes that are not at the
 /// expression level that store some type representation in them; for example, the type annotation of
@@ -23,22 +23,44 @@
 /// 
 /// Both of these things can hopefully be achieved by parameterizing the tree over new type parameters
 /// for "type representation" and "name representation", then allowing them to flow down to their use sites.
+use crate::source::FileId;
 use crate::span::{Spanned, Span};
 use crate::model::ttg::TreeData;
 
 pub struct Geode<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    modules: Vec<Module<P>>,
+    pub name: P::NameRepresentation,
+    pub modules: Vec<Module<P>>,
 }
 
+/// A module loaded from its own file. The geode's root module comes first, and out-of-line
+/// `mod` items refer to the others by their index in `Geode::modules`.
 pub struct Module<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    items: Vec<Item<P>>,
+    pub name: P::NameRepresentation,
+    pub items: Vec<Item<P>>,
+    pub file: FileId,
 }
 
-pub type Item<P> = Spanned<ItemKind<P>>;
+pub type ModuleId = usize;
+
+pub struct Item<P> where P: TreeData<P> {
+    pub kind: ItemKind<P>,
+    pub attributes: Vec<Attribute<P>>,
+    pub visibility: Visibility,
+    pub span: Span,
+}
+
+/// How far outside of its module a declaration may be named. Items and fields are private
+/// unless marked otherwise, and a private declaration is visible within its module and that
+/// module's submodules. See `privacy` for the checks.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Visibility {
+    Private,
+    Geode,   // `pub(geode)`, visible anywhere within the same geode
+    Public,  // `pub`
+}
 
 pub enum ItemKind<P> where P: TreeData<P> {
+    Mod(ModItem<P>),
     Use(UseTree<P>),
     Function(FunctionItem<P>),
     TypeAlias(TypeAliasItem<P>),
@@ -46,11 +68,23 @@ pub enum ItemKind<P> where P: TreeData<P> {
     Enum(EnumItem<P>),
     Const(ConstItem<P>),
     Static(StaticItem<P>),
+    Impl(ImplItem<P>),
+    Trait(TraitItem<P>),
+}
+
+pub struct ModItem<P> where P: TreeData<P> {
+    pub name: P::NameRepresentation,
+    pub body: ModBody<P>,
+}
+
+pub enum ModBody<P> where P: TreeData<P> {
+    Inline(Vec<Item<P>>),       // `mod foo { ... }`
+    File(Option<ModuleId>),     // `mod foo;`, which is `None` until `load` finds its file
 }
 
 pub struct UseTree<P> where P: TreeData<P> {
-    prefix: P::PathRepresentation,
-    kind: UseTreeKind<P>,
+    pub prefix: P::PathRepresentation,
+    pub kind: UseTreeKind<P>,
 }
 
 pub enum UseTreeKind<P> where P: TreeData<P> {
@@ -60,59 +94,120 @@ pub enum UseTreeKind<P> where P: TreeData<P> {
 }
 
 pub struct FunctionItem<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    parameters: Vec<Parameter<P>>,
-    return_type: Option<P::TypeRepresentation>,
-    body: BlockExpression<P>,
+    pub name: P::NameRepresentation,
+    pub generics: Vec<GenericParameter<P>>,
+    pub receiver: Option<Spanned<Receiver>>,  // Only present on functions declared within an `impl` or `trait` block
+    pub parameters: Vec<Parameter<P>>,
+    pub return_type: Option<P::TypeRepresentation>,
+    pub body: Option<BlockExpression<P>>,  // Only absent on method signatures declared within a `trait` block
+}
+
+/// A generic type parameter, `T: Bound + Bound`. Every type the parameter is instantiated with
+/// must implement each of the bound traits. Since generic functions are monomorphized, method
+/// calls through a bound are dispatched statically to the implementing type's method.
+pub struct GenericParameter<P> where P: TreeData<P> {
+    pub name: P::NameRepresentation,
+    pub bounds: Vec<P::PathRepresentation>,
+    pub span: Span,
 }
 
 pub struct TypeAliasItem<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    ty: P::TypeRepresentation,
+    pub name: P::NameRepresentation,
+    pub ty: P::TypeRepresentation,
 }
 
 pub struct StructItem<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    fields: Vec<Field<P>>,
+    pub name: P::NameRepresentation,
+    pub fields: Vec<Field<P>>,
 }
 
 pub struct EnumItem<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    variants: Vec<Variant<P>>
+    pub name: P::NameRepresentation,
+    pub generics: Vec<GenericParameter<P>>,
+    pub variants: Vec<Variant<P>>
 }
 
 pub struct ConstItem<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    ty: P::TypeRepresentation,
-    value: Expression<P>,
+    pub name: P::NameRepresentation,
+    pub ty: P::TypeRepresentation,
+    pub value: Expression<P>,
 }
 
 pub struct StaticItem<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    ty: P::TypeRepresentation,
-    value: Expression<P>,
+    pub name: P::NameRepresentation,
+    pub ty: P::TypeRepresentation,
+    pub value: Expression<P>,
+}
+
+/// An `impl Type { ... }` block attaching methods to a type. Each function within the block
+/// may take a `self` receiver, which allows it to be called with method-call syntax,
+/// `value.method(args)`. Method calls are desugared to plain calls of the form
+/// `Type::method(value, args)` during lowering, with the receiver automatically borrowed
+/// or dereferenced as needed to match the method's receiver.
+pub struct ImplItem<P> where P: TreeData<P> {
+    pub trait_: Option<P::PathRepresentation>,  // `impl Trait for Type { ... }`
+    pub ty: P::TypeRepresentation,
+    pub functions: Vec<Item<P>>,  // Always `ItemKind::Function`
+}
+
+/// A `trait Name { ... }` item declaring method signatures. Types implement the trait with an
+/// `impl Name for Type { ... }` block, which must provide every declared method.
+pub struct TraitItem<P> where P: TreeData<P> {
+    pub name: P::NameRepresentation,
+    pub functions: Vec<Item<P>>,  // Always `ItemKind::Function`
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Receiver {
+    Value,                   // `self`
+    Reference(Mutability),   // `&self` or `&mut self`
 }
 
 pub struct Parameter<P> where P: TreeData<P> {
-    mutability: Mutability,
-    ty: P::TypeRepresentation,
-    span: Span
+    pub name: P::NameRepresentation,
+    pub mutability: Mutability,
+    pub ty: P::TypeRepresentation,
+    pub span: Span
 }
 
 pub struct Field<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    ty: P::TypeRepresentation,
-    span: Span,
+    pub name: P::NameRepresentation,
+    pub ty: P::TypeRepresentation,
+    pub attributes: Vec<Attribute<P>>,
+    pub visibility: Visibility,
+    pub span: Span,
+}
+
+pub struct Variant<P> where P: TreeData<P> {
+    pub name: P::NameRepresentation,
+    pub kind: VariantKind<P>,
+    pub attributes: Vec<Attribute<P>>,
+    pub visibility: Visibility, // `Public` unless restricted, making it as visible as its enum
+    pub span: Span,
 }
 
-pub enum Variant<P> where P: TreeData<P> {
-    Unit(P::NameRepresentation),
-    Tuple(P::NameRepresentation, Vec<P::TypeRepresentation>),
-    Struct(P::NameRepresentation, Vec<Field<P>>),
+pub enum VariantKind<P> where P: TreeData<P> {
+    Unit,
+    Tuple(Vec<P::TypeRepresentation>),
+    Struct(Vec<Field<P>>),
+}
+
+/// An attribute annotating an item, field, or variant, such as `@inline` or `@section("boot")`.
+/// Attributes are parsed generally, and are only checked against the set of known attributes
+/// and the kind of declaration they annotate afterwards, by `attribute::validate`. Arguments
+/// are always literal expressions.
+pub struct Attribute<P> where P: TreeData<P> {
+    pub name: Spanned<String>,  // Attribute names are never resolved, so they stay as written
+    pub arguments: Vec<Expression<P>>,
+    pub span: Span,
 }
 
 pub type Expression<Phase> = Spanned<ExpressionKind<Phase>>;
 
+/// A loop label, `'name`, without the quote. Labels are scoped to the loop they name, and are
+/// matched up with `break` and `continue` by `loops::LoopStack` rather than name resolution.
+pub type Label = Spanned<String>;
+
 pub type BlockExpression<Phase> = Vec<Option<Statements<Phase>>>;
 
 pub enum ExpressionKind<P> where P: TreeData<P> {
@@ -122,25 +217,32 @@ pub enum ExpressionKind<P> where P: TreeData<P> {
 
 pub enum ExpressionWithBlock<P> where P: TreeData<P> {
     Case(Box<Expression<P>>, Vec<Arm<P>>, P::Case),
-    IfIs(Box<Expression<P>>, Pattern, Box<Expression<P>>, Option<Box<Expression<P>>>, P::IfIs),
-    For(P::NameRepresentation, Box<Expression<P>>, BlockExpression<P>, P::For),
+    IfIs(Box<Expression<P>>, Pattern<P>, Box<Expression<P>>, Option<Box<Expression<P>>>, P::IfIs),
+    For(Option<Label>, P::NameRepresentation, Box<Expression<P>>, BlockExpression<P>, P::For),
+    While(Option<Label>, Box<Expression<P>>, BlockExpression<P>, P::While),
+    Loop(Option<Label>, BlockExpression<P>, P::Loop),
+    If(Box<Expression<P>>, BlockExpression<P>, Option<Box<Expression<P>>>, P::If),
     Block(BlockExpression<P>, P::Block),
 }
 
 pub enum ExpressionWithoutBlock<P> where P: TreeData<P> {
     ErrorPropagation(Box<Expression<P>>, P::ErrorPropagation),
     Return(Option<Box<Expression<P>>>, P::Return),
-    Break(Option<Box<Expression<P>>>, P::Break),
-    Continue(P::Continue),
+    Break(Option<Label>, Option<Box<Expression<P>>>, P::Break),
+    Continue(Option<Label>, P::Continue),
     Grouped(Box<Expression<P>>, P::Grouped),
     Path(P::PathRepresentation, P::Path),
     Literal(Literal, P::Literal),
     Array(Vec<Expression<P>>, P::Array),
+    Repeat(Box<Expression<P>>, Box<Expression<P>>, P::Repeat),  // `[value; count]`, where `count` is constant
+    Range(Option<Box<Expression<P>>>, Option<Box<Expression<P>>>, RangeLimits, P::Range),  // `start..end`, either bound optional
     Prefix(PrefixOperator, Box<Expression<P>>, P::Prefix),
     Binary(Box<Expression<P>>, BinaryOperator, Box<Expression<P>>, P::Binary),
     Call(Box<Expression<P>>, Vec<Expression<P>>, P::Call),
+    MethodCall(Box<Expression<P>>, P::NameRepresentation, Vec<Expression<P>>, P::MethodCall),
     Field(Box<Expression<P>>, P::NameRepresentation, P::Field),
     Index(Box<Expression<P>>, Box<Expression<P>>, P::Index),
+    Asm(Assembly<P>, P::Asm),
     Other(P::Other),
 }
 
@@ -148,21 +250,51 @@ pub enum Statement<P> where P: TreeData<P> {
     Empty,
     Let(Let<P>),
     Expression(Expression<P>),
+    Defer(Expression<P>),  // Runs when control leaves the enclosing block, see `defer::Scopes`
 }
 
 pub enum Statements<P> where P: TreeData<P> {
-    Leading(Statement<P>, Vec<Statement<P>>, Option<ExpressionWithoutBlock<P>>),
-    Block(ExpressionWithoutBlock<P>)
+    Leading(Statement<P>, Vec<Statement<P>>, Option<Expression<P>>),
+    Block(Expression<P>)
 }
 
 pub struct Let<P> where P: TreeData<P> {
-    name: P::NameRepresentation,
-    mutability: Mutability,
+    pub name: P::NameRepresentation,
+    pub mutability: Mutability,
     // TODO: Some new parameter to force this required after type checking.
     // Or maybe we just won't need this after name resolution and we'll disable the constructor.
-    ty: Option<P::TypeRepresentation>,
-    initializer: Option<Expression<P>>,
-    span: Span,
+    pub ty: Option<P::TypeRepresentation>,
+    pub initializer: Option<Expression<P>>,
+    pub span: Span,
+}
+
+/// An inline assembly expression, `asm { "template", in name = expr, out name = place }`. The
+/// template is emitted verbatim, except that each `{name}` is replaced by the location of the
+/// operand with that name, and `{{` and `}}` stand for literal braces. Registers listed as
+/// clobbered are assumed to hold garbage after the assembly runs.
+pub struct Assembly<P> where P: TreeData<P> {
+    pub template: Spanned<String>,
+    pub operands: Vec<Operand<P>>,
+    pub clobbers: Vec<Spanned<String>>,
+}
+
+pub struct Operand<P> where P: TreeData<P> {
+    pub direction: Direction,
+    pub name: Spanned<String>,  // Operand names are local to the template, and are never resolved
+    pub expression: Expression<P>,  // Must be a place expression for outputs
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RangeLimits {
+    HalfOpen,  // `start..end`, excluding `end`
+    Closed,    // `start..=end`, including `end`
 }
 
 pub enum Literal {
@@ -175,15 +307,43 @@ pub enum Literal {
     Never,
 }
 
-pub struct Pattern;
+/// A pattern, which a value matches if it has the shape the pattern describes. Matching binds
+/// the names within the pattern to the corresponding parts of the value, within the arm or
+/// branch the pattern guards. A lone identifier is always a binding, so unit variants and
+/// constants are matched by a path of at least two segments, as in `Direction::Up`.
+pub type Pattern<Phase> = Spanned<PatternKind<Phase>>;
+
+pub enum PatternKind<P> where P: TreeData<P> {
+    Wildcard(P::Pattern),  // `_`
+    Binding(Mutability, P::NameRepresentation, P::Pattern),  // `name` or `mut name`, matching anything
+    Literal(Constant, P::Pattern),
+    Range(Option<Constant>, Option<Constant>, RangeLimits, P::Pattern),  // `'a'..='z'` or `10..`, either bound optional
+    Path(P::PathRepresentation, P::Pattern),  // A unit variant or a constant
+    Tuple(P::PathRepresentation, Vec<Pattern<P>>, P::Pattern),  // `Variant(a, b)`
+    Struct(P::PathRepresentation, Vec<FieldPattern<P>>, bool, P::Pattern),  // `Name { a, b: pattern }`, ending with `..` if set
+}
+
+pub struct FieldPattern<P> where P: TreeData<P> {
+    pub name: P::NameRepresentation,
+    pub pattern: Pattern<P>,  // A binding of the field's own name for the shorthand `{ name }`
+}
+
+/// A value compared against by a literal or range pattern. Unlike literal expressions, integers
+/// may be negated, as in `-1`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Constant {
+    Integer { negative: bool, value: u32 },
+    Character(char),
+    Bool(bool),
+}
 
 pub struct Arm<P> where P: TreeData<P> {
-    pattern: Spanned<Pattern>,
-    guard: Option<Expression<P>>,
-    expression: Expression<P>,
+    pub pattern: Pattern<P>,
+    pub guard: Option<Expression<P>>,
+    pub expression: Expression<P>,
 }
 
-pub struct Path<P: TreeData<P>>(Vec<P::NameRepresentation>);
+pub struct Path<P: TreeData<P>>(pub Vec<P::NameRepresentation>);
 
 pub enum PrefixOperator {
     Borrow,
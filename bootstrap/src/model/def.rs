@@ -0,0 +1,132 @@
+use crate::model::base::Visibility;
+use crate::span::Span;
+
+/// Identifies a single definition (item, variant, associated function, generic parameter, ...)
+/// within a geode. Definitions are numbered as they are discovered, so a `DefId` is only
+/// meaningful alongside the table that handed it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DefId(pub usize);
+
+/// What kind of definition a `DefId` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Mod,
+    Struct,
+    Enum,
+    Variant,
+    Trait,
+    TypeAlias,
+    TypeParameter,
+    Fn,
+    AssocFn,
+    Const,
+    Static,
+}
+
+impl std::fmt::Display for DefKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            DefKind::Mod => "module",
+            DefKind::Struct => "struct",
+            DefKind::Enum => "enum",
+            DefKind::Variant => "variant",
+            DefKind::Trait => "trait",
+            DefKind::TypeAlias => "type alias",
+            DefKind::TypeParameter => "type parameter",
+            DefKind::Fn | DefKind::AssocFn => "function",
+            DefKind::Const => "constant",
+            DefKind::Static => "static",
+        };
+
+        write!(f, "{description}")
+    }
+}
+
+/// Everything the resolver knows about a definition, independent of where it is named from.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub kind: DefKind,
+    pub name: String,
+    pub parent: Option<DefId>, // The module, enum, trait, or function declaring it
+    pub visibility: Visibility,
+    pub span: Span, // The span of the name at the declaration
+}
+
+/// The table handing out `DefId`s, built by name resolution and consulted by every later pass.
+#[derive(Debug, Default)]
+pub struct Definitions {
+    definitions: Vec<Definition>,
+    core: Option<DefId>, // The module of the items every geode can use, see `prelude`
+}
+
+impl Definitions {
+    pub fn new() -> Self {
+        Self {
+            definitions: Vec::new(),
+            core: None,
+        }
+    }
+
+    pub fn define(&mut self, definition: Definition) -> DefId {
+        self.definitions.push(definition);
+        DefId(self.definitions.len() - 1)
+    }
+
+    pub fn get(&self, def: DefId) -> &Definition {
+        &self.definitions[def.0]
+    }
+
+    pub fn set_core(&mut self, module: DefId) {
+        self.core = Some(module);
+    }
+
+    /// The item of the core module with the given name, such as `prelude::OPTION`.
+    pub fn core(&self, name: &str) -> Option<DefId> {
+        let core = self.core?;
+
+        (0..self.definitions.len()).map(DefId).find(|def| {
+            let definition = self.get(*def);
+            definition.parent == Some(core) && definition.name == name
+        })
+    }
+
+    /// The module a definition is declared in, or the definition itself if it is a module.
+    pub fn module(&self, def: DefId) -> DefId {
+        let mut current = def;
+
+        while self.get(current).kind != DefKind::Mod {
+            current = self
+                .get(current)
+                .parent
+                .expect("Every definition other than the root module has a parent");
+        }
+
+        current
+    }
+
+    /// The type parameters declared by a generic item, in declaration order.
+    pub fn type_parameters(&self, item: DefId) -> Vec<DefId> {
+        (0..self.definitions.len())
+            .map(DefId)
+            .filter(|def| {
+                let definition = self.get(*def);
+                definition.kind == DefKind::TypeParameter && definition.parent == Some(item)
+            })
+            .collect()
+    }
+
+    /// The names of the modules leading from the root module to `module`, which must be a
+    /// module. The root module's own name is not included.
+    pub fn module_path(&self, module: DefId) -> Vec<String> {
+        let mut path = Vec::new();
+        let mut current = module;
+
+        while let Some(parent) = self.get(current).parent {
+            path.push(self.get(current).name.clone());
+            current = parent;
+        }
+
+        path.reverse();
+        path
+    }
+}
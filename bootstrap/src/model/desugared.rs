@@ -0,0 +1,231 @@
+use crate::model::base::*;
+use crate::model::resolved::{Name, ResolvedPath};
+use crate::model::ttg::{Extends, Removed, TreeData};
+use crate::model::typed::Typed;
+use crate::model::types::Type;
+
+// The phases produced by the passes in `desugar`, in the order they run. Each phase removes
+// the constructors its pass replaces by giving them the uninhabited extension `Removed`, and
+// otherwise matches `Typed`, so every expression left keeps its type for lowering.
+
+/// Declares a phase that matches `$previous` but for the constructors listed, which no longer
+/// exist in it. The list is cumulative, naming every constructor removed so far.
+macro_rules! phase {
+    ($(#[$doc:meta])* $phase:ident: $previous:ident without [$($removed:ident),*]) => {
+        $(#[$doc])*
+        pub enum $phase {}
+
+        impl TreeData<$phase> for $phase {
+            type Case = extension!(Case; $($removed),*);
+            type IfIs = extension!(IfIs; $($removed),*);
+            type For = extension!(For; $($removed),*);
+            type While = extension!(While; $($removed),*);
+            type Loop = extension!(Loop; $($removed),*);
+            type If = extension!(If; $($removed),*);
+            type ErrorPropagation = extension!(ErrorPropagation; $($removed),*);
+            type Return = extension!(Return; $($removed),*);
+            type Break = extension!(Break; $($removed),*);
+            type Continue = extension!(Continue; $($removed),*);
+            type Block = extension!(Block; $($removed),*);
+            type Grouped = extension!(Grouped; $($removed),*);
+            type Path = extension!(Path; $($removed),*);
+            type Literal = extension!(Literal; $($removed),*);
+            type Array = extension!(Array; $($removed),*);
+            type Repeat = extension!(Repeat; $($removed),*);
+            type Range = extension!(Range; $($removed),*);
+            type Prefix = extension!(Prefix; $($removed),*);
+            type Binary = extension!(Binary; $($removed),*);
+            type Call = extension!(Call; $($removed),*);
+            type MethodCall = extension!(MethodCall; $($removed),*);
+            type Field = extension!(Field; $($removed),*);
+            type Index = extension!(Index; $($removed),*);
+            type Asm = extension!(Asm; $($removed),*);
+            type Other = extension!(Other; $($removed),*);
+            type Pattern = extension!(Pattern; $($removed),*);
+
+            type NameRepresentation = Name;
+            type PathRepresentation = ResolvedPath;
+            type TypeRepresentation = Type;
+        }
+
+        impl Extends<$previous> for $phase {
+            fn case(ext: <$previous as TreeData<$previous>>::Case) -> Self::Case {
+                ext.carry()
+            }
+            fn if_is(ext: <$previous as TreeData<$previous>>::IfIs) -> Self::IfIs {
+                ext.carry()
+            }
+            fn for_(ext: <$previous as TreeData<$previous>>::For) -> Self::For {
+                ext.carry()
+            }
+            fn while_(ext: <$previous as TreeData<$previous>>::While) -> Self::While {
+                ext.carry()
+            }
+            fn loop_(ext: <$previous as TreeData<$previous>>::Loop) -> Self::Loop {
+                ext.carry()
+            }
+            fn if_(ext: <$previous as TreeData<$previous>>::If) -> Self::If {
+                ext.carry()
+            }
+            fn error_propagation(
+                ext: <$previous as TreeData<$previous>>::ErrorPropagation,
+            ) -> Self::ErrorPropagation {
+                ext.carry()
+            }
+            fn return_(ext: <$previous as TreeData<$previous>>::Return) -> Self::Return {
+                ext.carry()
+            }
+            fn break_(ext: <$previous as TreeData<$previous>>::Break) -> Self::Break {
+                ext.carry()
+            }
+            fn continue_(ext: <$previous as TreeData<$previous>>::Continue) -> Self::Continue {
+                ext.carry()
+            }
+            fn block(ext: <$previous as TreeData<$previous>>::Block) -> Self::Block {
+                ext.carry()
+            }
+            fn grouped(ext: <$previous as TreeData<$previous>>::Grouped) -> Self::Grouped {
+                ext.carry()
+            }
+            fn path(ext: <$previous as TreeData<$previous>>::Path) -> Self::Path {
+                ext.carry()
+            }
+            fn literal(ext: <$previous as TreeData<$previous>>::Literal) -> Self::Literal {
+                ext.carry()
+            }
+            fn array(ext: <$previous as TreeData<$previous>>::Array) -> Self::Array {
+                ext.carry()
+            }
+            fn repeat(ext: <$previous as TreeData<$previous>>::Repeat) -> Self::Repeat {
+                ext.carry()
+            }
+            fn range(ext: <$previous as TreeData<$previous>>::Range) -> Self::Range {
+                ext.carry()
+            }
+            fn prefix(ext: <$previous as TreeData<$previous>>::Prefix) -> Self::Prefix {
+                ext.carry()
+            }
+            fn binary(ext: <$previous as TreeData<$previous>>::Binary) -> Self::Binary {
+                ext.carry()
+            }
+            fn call(ext: <$previous as TreeData<$previous>>::Call) -> Self::Call {
+                ext.carry()
+            }
+            fn method_call(
+                ext: <$previous as TreeData<$previous>>::MethodCall,
+            ) -> Self::MethodCall {
+                ext.carry()
+            }
+            fn field(ext: <$previous as TreeData<$previous>>::Field) -> Self::Field {
+                ext.carry()
+            }
+            fn index(ext: <$previous as TreeData<$previous>>::Index) -> Self::Index {
+                ext.carry()
+            }
+            fn asm(ext: <$previous as TreeData<$previous>>::Asm) -> Self::Asm {
+                ext.carry()
+            }
+            fn other(ext: <$previous as TreeData<$previous>>::Other) -> Self::Other {
+                ext.carry()
+            }
+            fn pattern(ext: <$previous as TreeData<$previous>>::Pattern) -> Self::Pattern {
+                ext.carry()
+            }
+        }
+    };
+}
+
+/// The extension type of `$field` in a phase without the constructors listed after it.
+macro_rules! extension {
+    (MethodCall; $($removed:ident),*) => { Removed }; // Already removed by type checking
+    ($field:ident;) => { Type };
+    (For; For $(, $rest:ident)*) => { Removed };
+    (If; If $(, $rest:ident)*) => { Removed };
+    (IfIs; IfIs $(, $rest:ident)*) => { Removed };
+    ($field:ident; $first:ident $(, $rest:ident)*) => { extension!($field; $($rest),*) };
+}
+
+phase! {
+    /// The phase without `for` loops, which are replaced by `while` loops.
+    WithoutFor: Typed without [For]
+}
+
+phase! {
+    /// The phase without `if` expressions, which are replaced by `if ... is true`.
+    WithoutIf: WithoutFor without [For, If]
+}
+
+phase! {
+    /// The phase without `if ... is` expressions, which are replaced by `when` expressions.
+    /// This leaves `when`, `while` and `loop` as the only expressions that branch.
+    Desugared: WithoutIf without [For, If, IfIs]
+}
+
+/// Carries an extension field over to the next phase. A field keeps its type, stays removed,
+/// or is removed by the pass producing the next phase.
+trait Carry<T> {
+    fn carry(self) -> T;
+}
+
+impl Carry<Type> for Type {
+    fn carry(self) -> Type {
+        self
+    }
+}
+
+impl Carry<Removed> for Removed {
+    fn carry(self) -> Removed {
+        self
+    }
+}
+
+// A pass replaces every node of the constructor it removes without folding it, so this is
+// never reached.
+impl Carry<Removed> for Type {
+    fn carry(self) -> Removed {
+        unreachable!("Removed constructors are replaced before their extension is folded")
+    }
+}
+
+pub fn type_of(expression: &Expression<Desugared>) -> &Type {
+    match &expression.item {
+        ExpressionKind::ExpressionWithBlock(with_block) => with_block_type(with_block),
+        ExpressionKind::ExpressionWithoutBlock(without_block) => without_block_type(without_block),
+    }
+}
+
+pub fn with_block_type(expression: &ExpressionWithBlock<Desugared>) -> &Type {
+    use ExpressionWithBlock::*;
+
+    match expression {
+        Case(_, _, ty) | While(_, _, _, ty) | Loop(_, _, ty) | Block(_, ty) => ty,
+        IfIs(_, _, _, _, removed) | For(_, _, _, _, removed) | If(_, _, _, removed) => {
+            match *removed {}
+        }
+    }
+}
+
+pub fn without_block_type(expression: &ExpressionWithoutBlock<Desugared>) -> &Type {
+    use ExpressionWithoutBlock::*;
+
+    match expression {
+        ErrorPropagation(_, ty)
+        | Return(_, ty)
+        | Break(_, _, ty)
+        | Continue(_, ty)
+        | Grouped(_, ty)
+        | Path(_, ty)
+        | Literal(_, ty)
+        | Array(_, ty)
+        | Repeat(_, _, ty)
+        | Range(_, _, _, ty)
+        | Prefix(_, _, ty)
+        | Binary(_, _, _, ty)
+        | Call(_, _, ty)
+        | Field(_, _, ty)
+        | Index(_, _, ty)
+        | Asm(_, ty)
+        | Other(ty) => ty,
+        MethodCall(_, _, _, removed) => match *removed {},
+    }
+}
@@ -0,0 +1,906 @@
+use crate::model::base::*;
+use crate::model::ttg::{Extends, TreeData};
+use crate::model::visit::{NameContext, PathContext};
+use crate::span::Spanned;
+
+/// Rebuilds a tree of phase `P` as a tree of phase `Q`, node by node, in the same order as
+/// `visit::Visitor` walks it. Every method other than `fold_name`, `fold_path` and `fold_type`
+/// defaults to rebuilding the node from its folded children through the matching `walk_`
+/// function, so a pass only overrides the nodes it cares about.
+///
+/// Extension fields are carried over by `Q`'s `Extends<P>` implementation unless a pass
+/// overrides the hook for them, such as `for_extension`.
+pub trait Folder<P: TreeData<P>, Q: Extends<P>> {
+    fn fold_name(
+        &mut self,
+        name: P::NameRepresentation,
+        context: NameContext,
+    ) -> Q::NameRepresentation;
+
+    fn fold_path(
+        &mut self,
+        path: P::PathRepresentation,
+        context: PathContext,
+    ) -> Q::PathRepresentation;
+
+    fn fold_type(&mut self, ty: P::TypeRepresentation) -> Q::TypeRepresentation;
+
+    fn enter_scope(&mut self) {}
+
+    fn exit_scope(&mut self) {}
+
+    fn fold_geode(&mut self, geode: Geode<P>) -> Geode<Q> {
+        walk_geode(self, geode)
+    }
+
+    fn fold_module(&mut self, id: ModuleId, module: Module<P>) -> Module<Q> {
+        walk_module(self, id, module)
+    }
+
+    fn fold_item(&mut self, item: Item<P>) -> Item<Q> {
+        walk_item(self, item)
+    }
+
+    fn fold_use_tree(&mut self, tree: UseTree<P>) -> UseTree<Q> {
+        walk_use_tree(self, tree)
+    }
+
+    fn fold_function(&mut self, function: FunctionItem<P>) -> FunctionItem<Q> {
+        walk_function(self, function)
+    }
+
+    fn fold_generic(&mut self, generic: GenericParameter<P>) -> GenericParameter<Q> {
+        walk_generic(self, generic)
+    }
+
+    fn fold_receiver(&mut self, receiver: Spanned<Receiver>) -> Spanned<Receiver> {
+        receiver
+    }
+
+    fn fold_parameter(&mut self, parameter: Parameter<P>) -> Parameter<Q> {
+        walk_parameter(self, parameter)
+    }
+
+    fn fold_field(&mut self, field: Field<P>) -> Field<Q> {
+        walk_field(self, field)
+    }
+
+    fn fold_variant(&mut self, variant: Variant<P>) -> Variant<Q> {
+        walk_variant(self, variant)
+    }
+
+    fn fold_attribute(&mut self, attribute: Attribute<P>) -> Attribute<Q> {
+        walk_attribute(self, attribute)
+    }
+
+    fn fold_block(&mut self, block: BlockExpression<P>) -> BlockExpression<Q> {
+        walk_block(self, block)
+    }
+
+    fn fold_statement(&mut self, statement: Statement<P>) -> Statement<Q> {
+        walk_statement(self, statement)
+    }
+
+    fn fold_expression(&mut self, expression: Expression<P>) -> Expression<Q> {
+        walk_expression(self, expression)
+    }
+
+    fn fold_with_block(&mut self, expression: ExpressionWithBlock<P>) -> ExpressionWithBlock<Q> {
+        walk_with_block(self, expression)
+    }
+
+    fn fold_without_block(
+        &mut self,
+        expression: ExpressionWithoutBlock<P>,
+    ) -> ExpressionWithoutBlock<Q> {
+        walk_without_block(self, expression)
+    }
+
+    fn fold_arm(&mut self, arm: Arm<P>) -> Arm<Q> {
+        walk_arm(self, arm)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern<P>) -> Pattern<Q> {
+        walk_pattern(self, pattern)
+    }
+
+    // Extension fields, which are folded after the rest of their node.
+
+    fn case_extension(&mut self, ext: P::Case) -> Q::Case {
+        Q::case(ext)
+    }
+
+    fn if_is_extension(&mut self, ext: P::IfIs) -> Q::IfIs {
+        Q::if_is(ext)
+    }
+
+    fn for_extension(&mut self, ext: P::For) -> Q::For {
+        Q::for_(ext)
+    }
+
+    fn while_extension(&mut self, ext: P::While) -> Q::While {
+        Q::while_(ext)
+    }
+
+    fn loop_extension(&mut self, ext: P::Loop) -> Q::Loop {
+        Q::loop_(ext)
+    }
+
+    fn if_extension(&mut self, ext: P::If) -> Q::If {
+        Q::if_(ext)
+    }
+
+    fn error_propagation_extension(&mut self, ext: P::ErrorPropagation) -> Q::ErrorPropagation {
+        Q::error_propagation(ext)
+    }
+
+    fn return_extension(&mut self, ext: P::Return) -> Q::Return {
+        Q::return_(ext)
+    }
+
+    fn break_extension(&mut self, ext: P::Break) -> Q::Break {
+        Q::break_(ext)
+    }
+
+    fn continue_extension(&mut self, ext: P::Continue) -> Q::Continue {
+        Q::continue_(ext)
+    }
+
+    fn block_extension(&mut self, ext: P::Block) -> Q::Block {
+        Q::block(ext)
+    }
+
+    fn grouped_extension(&mut self, ext: P::Grouped) -> Q::Grouped {
+        Q::grouped(ext)
+    }
+
+    fn path_extension(&mut self, ext: P::Path) -> Q::Path {
+        Q::path(ext)
+    }
+
+    fn literal_extension(&mut self, ext: P::Literal) -> Q::Literal {
+        Q::literal(ext)
+    }
+
+    fn array_extension(&mut self, ext: P::Array) -> Q::Array {
+        Q::array(ext)
+    }
+
+    fn repeat_extension(&mut self, ext: P::Repeat) -> Q::Repeat {
+        Q::repeat(ext)
+    }
+
+    fn range_extension(&mut self, ext: P::Range) -> Q::Range {
+        Q::range(ext)
+    }
+
+    fn prefix_extension(&mut self, ext: P::Prefix) -> Q::Prefix {
+        Q::prefix(ext)
+    }
+
+    fn binary_extension(&mut self, ext: P::Binary) -> Q::Binary {
+        Q::binary(ext)
+    }
+
+    fn call_extension(&mut self, ext: P::Call) -> Q::Call {
+        Q::call(ext)
+    }
+
+    fn method_call_extension(&mut self, ext: P::MethodCall) -> Q::MethodCall {
+        Q::method_call(ext)
+    }
+
+    fn field_extension(&mut self, ext: P::Field) -> Q::Field {
+        Q::field(ext)
+    }
+
+    fn index_extension(&mut self, ext: P::Index) -> Q::Index {
+        Q::index(ext)
+    }
+
+    fn asm_extension(&mut self, ext: P::Asm) -> Q::Asm {
+        Q::asm(ext)
+    }
+
+    fn other_extension(&mut self, ext: P::Other) -> Q::Other {
+        Q::other(ext)
+    }
+
+    fn pattern_extension(&mut self, ext: P::Pattern) -> Q::Pattern {
+        Q::pattern(ext)
+    }
+}
+
+pub fn walk_geode<P, Q, F>(folder: &mut F, geode: Geode<P>) -> Geode<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    Geode {
+        name: folder.fold_name(geode.name, NameContext::Module),
+        modules: geode
+            .modules
+            .into_iter()
+            .enumerate()
+            .map(|(id, module)| folder.fold_module(id, module))
+            .collect(),
+    }
+}
+
+pub fn walk_module<P, Q, F>(folder: &mut F, _id: ModuleId, module: Module<P>) -> Module<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    Module {
+        name: folder.fold_name(module.name, NameContext::Module),
+        items: fold_items(folder, module.items),
+        file: module.file,
+    }
+}
+
+pub fn walk_item<P, Q, F>(folder: &mut F, item: Item<P>) -> Item<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let Item {
+        kind,
+        attributes,
+        visibility,
+        span,
+    } = item;
+
+    let attributes = fold_attributes(folder, attributes);
+
+    let kind = match kind {
+        ItemKind::Mod(ModItem { name, body }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            let body = match body {
+                ModBody::Inline(items) => ModBody::Inline(fold_items(folder, items)),
+                ModBody::File(id) => ModBody::File(id),
+            };
+
+            ItemKind::Mod(ModItem { name, body })
+        }
+        ItemKind::Use(tree) => ItemKind::Use(folder.fold_use_tree(tree)),
+        ItemKind::Function(function) => ItemKind::Function(folder.fold_function(function)),
+        ItemKind::TypeAlias(TypeAliasItem { name, ty }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            ItemKind::TypeAlias(TypeAliasItem {
+                name,
+                ty: folder.fold_type(ty),
+            })
+        }
+        ItemKind::Struct(StructItem { name, fields }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            let fields = fields
+                .into_iter()
+                .map(|field| folder.fold_field(field))
+                .collect();
+
+            ItemKind::Struct(StructItem { name, fields })
+        }
+        ItemKind::Enum(EnumItem {
+            name,
+            generics,
+            variants,
+        }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            folder.enter_scope();
+
+            let generics = generics
+                .into_iter()
+                .map(|generic| folder.fold_generic(generic))
+                .collect();
+            let variants = variants
+                .into_iter()
+                .map(|variant| folder.fold_variant(variant))
+                .collect();
+
+            folder.exit_scope();
+
+            ItemKind::Enum(EnumItem {
+                name,
+                generics,
+                variants,
+            })
+        }
+        ItemKind::Const(ConstItem { name, ty, value }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            let ty = folder.fold_type(ty);
+
+            ItemKind::Const(ConstItem {
+                name,
+                ty,
+                value: folder.fold_expression(value),
+            })
+        }
+        ItemKind::Static(StaticItem { name, ty, value }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            let ty = folder.fold_type(ty);
+
+            ItemKind::Static(StaticItem {
+                name,
+                ty,
+                value: folder.fold_expression(value),
+            })
+        }
+        ItemKind::Impl(ImplItem {
+            trait_,
+            ty,
+            functions,
+        }) => {
+            let trait_ = trait_.map(|path| folder.fold_path(path, PathContext::Trait));
+            let ty = folder.fold_type(ty);
+
+            ItemKind::Impl(ImplItem {
+                trait_,
+                ty,
+                functions: fold_items(folder, functions),
+            })
+        }
+        ItemKind::Trait(TraitItem { name, functions }) => {
+            let name = folder.fold_name(name, NameContext::Declaration);
+            ItemKind::Trait(TraitItem {
+                name,
+                functions: fold_items(folder, functions),
+            })
+        }
+    };
+
+    Item {
+        kind,
+        attributes,
+        visibility,
+        span,
+    }
+}
+
+fn fold_items<P, Q, F>(folder: &mut F, items: Vec<Item<P>>) -> Vec<Item<Q>>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    items
+        .into_iter()
+        .map(|item| folder.fold_item(item))
+        .collect()
+}
+
+pub fn walk_use_tree<P, Q, F>(folder: &mut F, tree: UseTree<P>) -> UseTree<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let prefix = folder.fold_path(tree.prefix, PathContext::Import);
+    let kind = match tree.kind {
+        UseTreeKind::Simple(alias) => {
+            UseTreeKind::Simple(alias.map(|alias| folder.fold_name(alias, NameContext::Import)))
+        }
+        UseTreeKind::Nested(trees, span) => UseTreeKind::Nested(
+            trees
+                .into_iter()
+                .map(|tree| folder.fold_use_tree(tree))
+                .collect(),
+            span,
+        ),
+        UseTreeKind::Glob => UseTreeKind::Glob,
+    };
+
+    UseTree { prefix, kind }
+}
+
+pub fn walk_function<P, Q, F>(folder: &mut F, function: FunctionItem<P>) -> FunctionItem<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let FunctionItem {
+        name,
+        generics,
+        receiver,
+        parameters,
+        return_type,
+        body,
+    } = function;
+
+    let name = folder.fold_name(name, NameContext::Declaration);
+    folder.enter_scope();
+
+    let generics = generics
+        .into_iter()
+        .map(|generic| folder.fold_generic(generic))
+        .collect();
+    let receiver = receiver.map(|receiver| folder.fold_receiver(receiver));
+    let parameters = parameters
+        .into_iter()
+        .map(|parameter| folder.fold_parameter(parameter))
+        .collect();
+    let return_type = return_type.map(|ty| folder.fold_type(ty));
+    let body = body.map(|body| folder.fold_block(body));
+
+    folder.exit_scope();
+
+    FunctionItem {
+        name,
+        generics,
+        receiver,
+        parameters,
+        return_type,
+        body,
+    }
+}
+
+pub fn walk_generic<P, Q, F>(folder: &mut F, generic: GenericParameter<P>) -> GenericParameter<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    GenericParameter {
+        name: folder.fold_name(generic.name, NameContext::TypeParameter),
+        bounds: generic
+            .bounds
+            .into_iter()
+            .map(|bound| folder.fold_path(bound, PathContext::Trait))
+            .collect(),
+        span: generic.span,
+    }
+}
+
+pub fn walk_parameter<P, Q, F>(folder: &mut F, parameter: Parameter<P>) -> Parameter<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let ty = folder.fold_type(parameter.ty);
+
+    Parameter {
+        name: folder.fold_name(parameter.name, NameContext::Parameter),
+        mutability: parameter.mutability,
+        ty,
+        span: parameter.span,
+    }
+}
+
+pub fn walk_field<P, Q, F>(folder: &mut F, field: Field<P>) -> Field<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let attributes = fold_attributes(folder, field.attributes);
+    let name = folder.fold_name(field.name, NameContext::Field);
+
+    Field {
+        name,
+        ty: folder.fold_type(field.ty),
+        attributes,
+        visibility: field.visibility,
+        span: field.span,
+    }
+}
+
+pub fn walk_variant<P, Q, F>(folder: &mut F, variant: Variant<P>) -> Variant<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let attributes = fold_attributes(folder, variant.attributes);
+    let name = folder.fold_name(variant.name, NameContext::Declaration);
+    let kind = match variant.kind {
+        VariantKind::Unit => VariantKind::Unit,
+        VariantKind::Tuple(types) => {
+            VariantKind::Tuple(types.into_iter().map(|ty| folder.fold_type(ty)).collect())
+        }
+        VariantKind::Struct(fields) => VariantKind::Struct(
+            fields
+                .into_iter()
+                .map(|field| folder.fold_field(field))
+                .collect(),
+        ),
+    };
+
+    Variant {
+        name,
+        kind,
+        attributes,
+        visibility: variant.visibility,
+        span: variant.span,
+    }
+}
+
+fn fold_attributes<P, Q, F>(folder: &mut F, attributes: Vec<Attribute<P>>) -> Vec<Attribute<Q>>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    attributes
+        .into_iter()
+        .map(|attribute| folder.fold_attribute(attribute))
+        .collect()
+}
+
+pub fn walk_attribute<P, Q, F>(folder: &mut F, attribute: Attribute<P>) -> Attribute<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    Attribute {
+        name: attribute.name,
+        arguments: fold_expressions(folder, attribute.arguments),
+        span: attribute.span,
+    }
+}
+
+pub fn walk_block<P, Q, F>(folder: &mut F, block: BlockExpression<P>) -> BlockExpression<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    folder.enter_scope();
+
+    let block = block
+        .into_iter()
+        .map(|statements| {
+            statements.map(|statements| match statements {
+                Statements::Leading(first, rest, last) => {
+                    let first = folder.fold_statement(first);
+                    let rest = rest
+                        .into_iter()
+                        .map(|statement| folder.fold_statement(statement))
+                        .collect();
+                    let last = last.map(|last| folder.fold_expression(last));
+
+                    Statements::Leading(first, rest, last)
+                }
+                Statements::Block(expression) => {
+                    Statements::Block(folder.fold_expression(expression))
+                }
+            })
+        })
+        .collect();
+
+    folder.exit_scope();
+    block
+}
+
+pub fn walk_statement<P, Q, F>(folder: &mut F, statement: Statement<P>) -> Statement<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    match statement {
+        Statement::Empty => Statement::Empty,
+        Statement::Let(Let {
+            name,
+            mutability,
+            ty,
+            initializer,
+            span,
+        }) => {
+            let ty = ty.map(|ty| folder.fold_type(ty));
+            let initializer = initializer.map(|initializer| folder.fold_expression(initializer));
+
+            Statement::Let(Let {
+                name: folder.fold_name(name, NameContext::Binding),
+                mutability,
+                ty,
+                initializer,
+                span,
+            })
+        }
+        Statement::Expression(expression) => {
+            Statement::Expression(folder.fold_expression(expression))
+        }
+        Statement::Defer(expression) => Statement::Defer(folder.fold_expression(expression)),
+    }
+}
+
+pub fn walk_expression<P, Q, F>(folder: &mut F, expression: Expression<P>) -> Expression<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    let kind = match expression.item {
+        ExpressionKind::ExpressionWithBlock(with_block) => {
+            ExpressionKind::ExpressionWithBlock(folder.fold_with_block(with_block))
+        }
+        ExpressionKind::ExpressionWithoutBlock(without_block) => {
+            ExpressionKind::ExpressionWithoutBlock(folder.fold_without_block(without_block))
+        }
+    };
+
+    Spanned::new(kind, expression.span)
+}
+
+pub fn walk_with_block<P, Q, F>(
+    folder: &mut F,
+    expression: ExpressionWithBlock<P>,
+) -> ExpressionWithBlock<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    use ExpressionWithBlock::*;
+
+    match expression {
+        Case(scrutinee, arms, ext) => {
+            let scrutinee = boxed(folder, *scrutinee);
+            let arms = arms.into_iter().map(|arm| folder.fold_arm(arm)).collect();
+
+            Case(scrutinee, arms, folder.case_extension(ext))
+        }
+        IfIs(scrutinee, pattern, then, otherwise, ext) => {
+            let scrutinee = boxed(folder, *scrutinee);
+
+            folder.enter_scope();
+            let pattern = folder.fold_pattern(pattern);
+            let then = boxed(folder, *then);
+            folder.exit_scope();
+            let otherwise = otherwise.map(|otherwise| boxed(folder, *otherwise));
+
+            IfIs(
+                scrutinee,
+                pattern,
+                then,
+                otherwise,
+                folder.if_is_extension(ext),
+            )
+        }
+        For(label, name, iterable, body, ext) => {
+            let iterable = boxed(folder, *iterable);
+
+            folder.enter_scope();
+            let name = folder.fold_name(name, NameContext::Binding);
+            let body = folder.fold_block(body);
+            folder.exit_scope();
+
+            For(label, name, iterable, body, folder.for_extension(ext))
+        }
+        While(label, condition, body, ext) => {
+            let condition = boxed(folder, *condition);
+            let body = folder.fold_block(body);
+
+            While(label, condition, body, folder.while_extension(ext))
+        }
+        Loop(label, body, ext) => {
+            let body = folder.fold_block(body);
+            Loop(label, body, folder.loop_extension(ext))
+        }
+        If(condition, then, otherwise, ext) => {
+            let condition = boxed(folder, *condition);
+            let then = folder.fold_block(then);
+            let otherwise = otherwise.map(|otherwise| boxed(folder, *otherwise));
+
+            If(condition, then, otherwise, folder.if_extension(ext))
+        }
+        Block(body, ext) => {
+            let body = folder.fold_block(body);
+            Block(body, folder.block_extension(ext))
+        }
+    }
+}
+
+pub fn walk_without_block<P, Q, F>(
+    folder: &mut F,
+    expression: ExpressionWithoutBlock<P>,
+) -> ExpressionWithoutBlock<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    use ExpressionWithoutBlock::*;
+
+    match expression {
+        ErrorPropagation(inner, ext) => {
+            let inner = boxed(folder, *inner);
+            ErrorPropagation(inner, folder.error_propagation_extension(ext))
+        }
+        Return(value, ext) => {
+            let value = value.map(|value| boxed(folder, *value));
+            Return(value, folder.return_extension(ext))
+        }
+        Break(label, value, ext) => {
+            let value = value.map(|value| boxed(folder, *value));
+            Break(label, value, folder.break_extension(ext))
+        }
+        Continue(label, ext) => Continue(label, folder.continue_extension(ext)),
+        Grouped(inner, ext) => {
+            let inner = boxed(folder, *inner);
+            Grouped(inner, folder.grouped_extension(ext))
+        }
+        Path(path, ext) => {
+            let path = folder.fold_path(path, PathContext::Expression);
+            Path(path, folder.path_extension(ext))
+        }
+        Literal(literal, ext) => Literal(literal, folder.literal_extension(ext)),
+        Array(elements, ext) => {
+            let elements = fold_expressions(folder, elements);
+            Array(elements, folder.array_extension(ext))
+        }
+        Repeat(value, count, ext) => {
+            let value = boxed(folder, *value);
+            let count = boxed(folder, *count);
+
+            Repeat(value, count, folder.repeat_extension(ext))
+        }
+        Range(start, end, limits, ext) => {
+            let start = start.map(|start| boxed(folder, *start));
+            let end = end.map(|end| boxed(folder, *end));
+
+            Range(start, end, limits, folder.range_extension(ext))
+        }
+        Prefix(operator, operand, ext) => {
+            let operand = boxed(folder, *operand);
+            Prefix(operator, operand, folder.prefix_extension(ext))
+        }
+        Binary(left, operator, right, ext) => {
+            let left = boxed(folder, *left);
+            let right = boxed(folder, *right);
+
+            Binary(left, operator, right, folder.binary_extension(ext))
+        }
+        Call(function, arguments, ext) => {
+            let function = boxed(folder, *function);
+            let arguments = fold_expressions(folder, arguments);
+
+            Call(function, arguments, folder.call_extension(ext))
+        }
+        MethodCall(receiver, method, arguments, ext) => {
+            let receiver = boxed(folder, *receiver);
+            let method = folder.fold_name(method, NameContext::Method);
+            let arguments = fold_expressions(folder, arguments);
+
+            MethodCall(
+                receiver,
+                method,
+                arguments,
+                folder.method_call_extension(ext),
+            )
+        }
+        Field(base, field, ext) => {
+            let base = boxed(folder, *base);
+            let field = folder.fold_name(field, NameContext::Field);
+
+            Field(base, field, folder.field_extension(ext))
+        }
+        Index(base, index, ext) => {
+            let base = boxed(folder, *base);
+            let index = boxed(folder, *index);
+
+            Index(base, index, folder.index_extension(ext))
+        }
+        Asm(
+            Assembly {
+                template,
+                operands,
+                clobbers,
+            },
+            ext,
+        ) => {
+            let operands = operands
+                .into_iter()
+                .map(|operand| Operand {
+                    direction: operand.direction,
+                    name: operand.name,
+                    expression: folder.fold_expression(operand.expression),
+                    span: operand.span,
+                })
+                .collect();
+
+            Asm(
+                Assembly {
+                    template,
+                    operands,
+                    clobbers,
+                },
+                folder.asm_extension(ext),
+            )
+        }
+        Other(ext) => Other(folder.other_extension(ext)),
+    }
+}
+
+pub fn walk_arm<P, Q, F>(folder: &mut F, arm: Arm<P>) -> Arm<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    folder.enter_scope();
+
+    let arm = Arm {
+        pattern: folder.fold_pattern(arm.pattern),
+        guard: arm.guard.map(|guard| folder.fold_expression(guard)),
+        expression: folder.fold_expression(arm.expression),
+    };
+
+    folder.exit_scope();
+    arm
+}
+
+pub fn walk_pattern<P, Q, F>(folder: &mut F, pattern: Pattern<P>) -> Pattern<Q>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    use PatternKind::*;
+
+    let kind = match pattern.item {
+        Wildcard(ext) => Wildcard(folder.pattern_extension(ext)),
+        Binding(mutability, name, ext) => {
+            let name = folder.fold_name(name, NameContext::Binding);
+            Binding(mutability, name, folder.pattern_extension(ext))
+        }
+        Literal(constant, ext) => Literal(constant, folder.pattern_extension(ext)),
+        Range(start, end, limits, ext) => {
+            Range(start, end, limits, folder.pattern_extension(ext))
+        }
+        Path(path, ext) => {
+            let path = folder.fold_path(path, PathContext::Pattern);
+            Path(path, folder.pattern_extension(ext))
+        }
+        Tuple(path, elements, ext) => {
+            let path = folder.fold_path(path, PathContext::Pattern);
+            let elements = elements
+                .into_iter()
+                .map(|element| folder.fold_pattern(element))
+                .collect();
+
+            Tuple(path, elements, folder.pattern_extension(ext))
+        }
+        Struct(path, fields, rest, ext) => {
+            let path = folder.fold_path(path, PathContext::StructPattern);
+            let fields = fields
+                .into_iter()
+                .map(|field| FieldPattern {
+                    name: folder.fold_name(field.name, NameContext::Field),
+                    pattern: folder.fold_pattern(field.pattern),
+                })
+                .collect();
+
+            Struct(path, fields, rest, folder.pattern_extension(ext))
+        }
+    };
+
+    Spanned::new(kind, pattern.span)
+}
+
+fn boxed<P, Q, F>(folder: &mut F, expression: Expression<P>) -> Box<Expression<Q>>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    Box::new(folder.fold_expression(expression))
+}
+
+fn fold_expressions<P, Q, F>(folder: &mut F, expressions: Vec<Expression<P>>) -> Vec<Expression<Q>>
+where
+    P: TreeData<P>,
+    Q: Extends<P>,
+    F: Folder<P, Q> + ?Sized,
+{
+    expressions
+        .into_iter()
+        .map(|expression| folder.fold_expression(expression))
+        .collect()
+}
@@ -1,13 +1,49 @@
+use crate::model::base::Direction;
+use crate::model::def::DefId;
+
 pub type VariableId = usize;
 pub type LabelId = usize;
+pub type FunctionId = usize; // Index of a function instance produced by monomorphization
+
+/// A function instance lowered to IR. Variables are numbered from zero within each function,
+/// starting with its parameters, and hold values of any size, from which parts are read and
+/// written with `Project` and `Insert`.
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<VariableId>,
+    pub variables: usize,
+    pub body: Vec<Statement>,
+}
 
+#[derive(Debug)]
 pub enum Statement {
     Assign {
         target: VariableId,
         source: VariableId,
     },
+    // Sets the variable to a constant, such as the value of a literal.
+    Immediate {
+        target: VariableId,
+        value: i64,
+    },
+    // Marks the position that jumps to the label continue from.
+    Label(LabelId),
     Goto(LabelId),
     GotoIf(VariableId, LabelId),
+    // Jumps to the label of the first inclusive range containing the value of `scrutinee`, or
+    // to `default` if there is none. Code generation chooses between a jump table and a chain
+    // of comparisons.
+    Switch {
+        scrutinee: VariableId,
+        cases: Vec<(i64, i64, LabelId)>,
+        default: LabelId,
+    },
+    // Control never reaches here, such as the failure of a decision tree whose arms are known
+    // to be exhaustive.
+    Unreachable,
+    // Returns from the current function with the value in the variable.
+    Return(VariableId),
     BinaryOp {
         op: BinaryOp,
         target: VariableId,
@@ -25,14 +61,126 @@ pub enum Statement {
         target: VariableId,
         right: VariableId,
     },
+    // Takes the address of a variable, which must then live in memory, such as when a local
+    // is borrowed.
+    Address {
+        target: VariableId,
+        source: VariableId,
+    },
+    // Takes the address of a static.
+    Global {
+        target: VariableId,
+        global: DefId,
+    },
+    // Reads the `size` bytes at `offset` past the address in `address`.
+    Load {
+        target: VariableId,
+        address: VariableId,
+        offset: u32,
+        size: u32,
+    },
+    // Writes the value in `source` as the `size` bytes at `offset` past the address in
+    // `address`.
+    Store {
+        address: VariableId,
+        offset: u32,
+        source: VariableId,
+        size: u32,
+    },
+    // Produces a reference to the bytes, which are placed in read-only data, along with their
+    // length, as a reference to a `str` holds them.
+    Bytes {
+        target: VariableId,
+        value: Vec<u8>,
+    },
+    // Loads the address of a function, producing a function pointer.
+    FunctionAddress {
+        target: VariableId,
+        function: FunctionId,
+    },
+    // Calls a function that is known statically, which is every call of a named function item.
+    Call {
+        target: VariableId,
+        function: FunctionId,
+        arguments: Vec<VariableId>,
+    },
+    // Calls through a function pointer, such as an entry of a dispatch table.
+    IndirectCall {
+        target: VariableId,
+        function: VariableId,
+        arguments: Vec<VariableId>,
+    },
+    // Reads the `size` bytes at `offset` within the value in `source`, such as a field of a
+    // struct or the tag of an enum.
+    Project {
+        target: VariableId,
+        source: VariableId,
+        offset: u32,
+        size: u32,
+    },
+    // Writes the value in `source` as the `size` bytes at `offset` within the value in `target`,
+    // such as a field of a variant's payload.
+    Insert {
+        target: VariableId,
+        offset: u32,
+        source: VariableId,
+        size: u32,
+    },
+    // Writes `value` as the `size` bytes at `offset` within the value in `target`, such as the
+    // tag of an enum.
+    InsertImmediate {
+        target: VariableId,
+        offset: u32,
+        value: u32,
+        size: u32,
+    },
+    // Reads the length carried by a reference to a slice.
+    SliceLength {
+        target: VariableId,
+        slice: VariableId,
+    },
+    // Traps unless `index < length`, or `index <= length` when `inclusive` is set, as it is
+    // for the bounds of a range.
+    BoundsCheck {
+        index: VariableId,
+        length: VariableId,
+        inclusive: bool,
+    },
+    // Inline assembly, carried through to code generation verbatim. Operands are referred to
+    // by their index within `operands`.
+    Assembly {
+        template: Vec<TemplatePiece>,
+        operands: Vec<(Direction, VariableId)>,
+        clobbers: Vec<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePiece {
+    Text(String),
+    Operand(usize),
 }
 
+// Operations that depend on whether their operands are signed say so, since variables carry
+// no types of their own.
+#[derive(Debug)]
 pub enum BinaryOp {
     Add,
     Mult,
     Sub,
+    Divide { signed: bool },
+    And,
+    Or,
+    ShiftLeft,
+    ShiftRight { signed: bool }, // Shifts copies of the sign bit in if `signed` is set
+    Equal,                       // Produces `1` if the operands are equal and `0` otherwise
+    NotEqual,
+    Less { signed: bool },
+    LessEqual { signed: bool },
 }
 
+#[derive(Debug)]
 pub enum UnaryOp {
     Negate,
+    Not, // Flips every bit
 }
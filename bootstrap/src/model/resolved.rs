@@ -0,0 +1,109 @@
+use crate::span::{Span, Spanned};
+use crate::model::ast::{Syntax, TypeKind};
+use crate::model::def::{DefId, DefKind};
+use crate::model::ttg::{Extends, TreeData};
+
+/// The phase produced by name resolution. Every path refers to what it resolved to, and every
+/// declared name carries the definition or local binding it introduces.
+pub enum Resolved {}
+
+impl TreeData<Resolved> for Resolved {
+    type Case = ();
+    type IfIs = ();
+    type For = ();
+    type While = ();
+    type Loop = ();
+    type If = ();
+    type ErrorPropagation = ();
+    type Return = ();
+    type Break = ();
+    type Continue = ();
+    type Block = ();
+    type Grouped = ();
+    type Path = ();
+    type Literal = ();
+    type Array = ();
+    type Repeat = ();
+    type Range = ();
+    type Prefix = ();
+    type Binary = ();
+    type Call = ();
+    type MethodCall = ();
+    type Field = ();
+    type Index = ();
+    type Asm = ();
+    type Other = ();
+    type Pattern = ();
+
+    type NameRepresentation = Name;
+    type PathRepresentation = ResolvedPath;
+    type TypeRepresentation = Type;
+}
+
+// No extension field carries any data in either phase.
+impl Extends<Syntax> for Resolved {
+    fn case(_: ()) {}
+    fn if_is(_: ()) {}
+    fn for_(_: ()) {}
+    fn while_(_: ()) {}
+    fn loop_(_: ()) {}
+    fn if_(_: ()) {}
+    fn error_propagation(_: ()) {}
+    fn return_(_: ()) {}
+    fn break_(_: ()) {}
+    fn continue_(_: ()) {}
+    fn block(_: ()) {}
+    fn grouped(_: ()) {}
+    fn path(_: ()) {}
+    fn literal(_: ()) {}
+    fn array(_: ()) {}
+    fn repeat(_: ()) {}
+    fn range(_: ()) {}
+    fn prefix(_: ()) {}
+    fn binary(_: ()) {}
+    fn call(_: ()) {}
+    fn method_call(_: ()) {}
+    fn field(_: ()) {}
+    fn index(_: ()) {}
+    fn asm(_: ()) {}
+    fn other(_: ()) {}
+    fn pattern(_: ()) {}
+}
+
+pub type Type = Spanned<TypeKind<Resolved>>;
+
+/// A local variable, parameter, or `for` binding. Locals are numbered across the whole geode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalId(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    Def(DefKind, DefId),
+    Local(LocalId),
+    // A name looked up relative to a type, as in `Point::new`. Which function it names depends
+    // on the `impl` blocks for that type, so it is left for type checking.
+    Relative(DefId, Spanned<String>),
+}
+
+/// A name as declared. Names that can only be resolved once types are known, such as those of
+/// fields and of methods called through a value, have no resolution.
+#[derive(Debug, Clone)]
+pub struct Name {
+    pub ident: Spanned<String>,
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPath {
+    pub segments: Vec<Spanned<String>>, // As written, for diagnostics
+    pub resolution: Resolution,
+}
+
+impl ResolvedPath {
+    pub fn span(&self) -> Span {
+        match (self.segments.first(), self.segments.last()) {
+            (Some(first), Some(last)) => Span::between(first.span, last.span),
+            _ => Span::new(0, 0),
+        }
+    }
+}
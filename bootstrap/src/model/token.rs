@@ -6,17 +6,23 @@ pub enum KeywordToken {
     Type,
     Struct,
     Enum,
+    Impl,
+    Trait,
     Static,
     Const,
     Let,
+    Defer,
     Continue,
     Break,
     True,
     False,
     Case,
     If,
+    Else,
     Is,
     For,
+    While,
+    Loop,
     Return,
     Mut,
     U8,
@@ -29,12 +35,26 @@ pub enum KeywordToken {
     Char,
     Str,
     Use,
+    Pub,
     As,
+    Mod,
+    Unit,
+    SelfValue,
+    Asm,
+    In,
 }
 
 impl std::fmt::Display for KeywordToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string().to_lowercase())
+        use KeywordToken::*;
+
+        match self {
+            // Literal keywords are capitalized, like the types and variants they stand for.
+            True | False | Unit => write!(f, "{self:?}"),
+            SelfValue => write!(f, "self"),
+            Case => write!(f, "when"),
+            _ => write!(f, "{}", format!("{self:?}").to_lowercase()),
+        }
     }
 }
 
@@ -44,22 +64,29 @@ impl TryFrom<String> for KeywordToken {
     fn try_from(value: String) -> Result<Self, Self::Error> {
         use KeywordToken::*;
 
-        
         match value.as_str() {
             "fn" => Ok(Fn),
             "type" => Ok(Type),
             "struct" => Ok(Struct),
             "enum" => Ok(Enum),
+            "impl" => Ok(Impl),
+            "trait" => Ok(Trait),
             "static" => Ok(Static),
             "const" => Ok(Const),
             "let" => Ok(Let),
+            "defer" => Ok(Defer),
             "continue" => Ok(Continue),
             "break" => Ok(Break),
             "True" => Ok(True),
             "False" => Ok(False),
             "Unit" => Ok(Unit),
-            "when" => Ok(When),
+            "when" => Ok(Case),
+            "if" => Ok(If),
+            "else" => Ok(Else),
+            "is" => Ok(Is),
             "for" => Ok(For),
+            "while" => Ok(While),
+            "loop" => Ok(Loop),
             "return" => Ok(Return),
             "mut" => Ok(Mut),
             "u8" => Ok(U8),
@@ -72,7 +99,12 @@ impl TryFrom<String> for KeywordToken {
             "char" => Ok(Char),
             "str" => Ok(Str),
             "use" => Ok(Use),
+            "pub" => Ok(Pub),
             "as" => Ok(As),
+            "mod" => Ok(Mod),
+            "self" => Ok(SelfValue),
+            "asm" => Ok(Asm),
+            "in" => Ok(In),
             _ => Err(()),
         }
     }
@@ -119,11 +151,15 @@ pub enum BasicToken {
     Asterisk,
 
     Period,
+    Period2,
+    Period2Equal,
     Comma,
     Caret,
     Question,
+    At,
 
     LightRArrow,
+    HeavyRArrow,
     Colon,
     Colon2,
     Hyphen,
@@ -173,11 +209,15 @@ impl std::fmt::Display for BasicToken {
             LAngleEqual => write!(f, "<="),
 
             Period => write!(f, "."),
+            Period2 => write!(f, ".."),
+            Period2Equal => write!(f, "..="),
             Caret => write!(f, "^"),
             Question => write!(f, "?"),
+            At => write!(f, "@"),
 
             Comma => write!(f, ","),
             LightRArrow => write!(f, "->"),
+            HeavyRArrow => write!(f, "=>"),
             Colon => write!(f, ":"),
             Colon2 => write!(f, "::"),
             Asterisk => write!(f, "*"),
@@ -216,6 +256,7 @@ pub struct IntegerLiteralToken;
 pub enum Token {
     Keyword(KeywordToken),
     Identifier(String),
+    Label(String),
     Basic(BasicToken),
     Literal(LiteralToken),
     Eof,
@@ -228,6 +269,7 @@ impl std::fmt::Display for Token {
         match self {
             Keyword(kw) => write!(f, "keyword `{}`", kw),
             Identifier(name) => write!(f, "identifier \"{}\"", name),
+            Label(name) => write!(f, "label '{}", name),
             Basic(basic) => write!(f, "token \"{}\"", basic),
             Literal(lit) => write!(f, "literal \'{}\'", lit),
             Eof => write!(f, "end of file"),
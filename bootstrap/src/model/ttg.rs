@@ -2,6 +2,9 @@ pub trait TreeData<Phase> {
     type Case;
     type IfIs;
     type For;
+    type While;
+    type Loop;
+    type If;
     type ErrorPropagation;
     type Return;
     type Break;
@@ -11,14 +14,55 @@ pub trait TreeData<Phase> {
     type Path;
     type Literal;
     type Array;
+    type Repeat;
+    type Range;
     type Prefix;
     type Binary;
     type Call;
+    type MethodCall;
     type Field;
     type Index;
+    type Asm;
     type Other;
+    type Pattern;
 
     type NameRepresentation;
     type PathRepresentation;
     type TypeRepresentation;
-}
\ No newline at end of file
+}
+/// Derives the extension fields of this phase from those of phase `P`. A `fold::Folder` from
+/// `P` uses this for every extension field that it does not compute itself, so that a pass
+/// only overrides the hooks for the fields its phase changes.
+pub trait Extends<P: TreeData<P>>: TreeData<Self> + Sized {
+    fn case(ext: P::Case) -> Self::Case;
+    fn if_is(ext: P::IfIs) -> Self::IfIs;
+    fn for_(ext: P::For) -> Self::For;
+    fn while_(ext: P::While) -> Self::While;
+    fn loop_(ext: P::Loop) -> Self::Loop;
+    fn if_(ext: P::If) -> Self::If;
+    fn error_propagation(ext: P::ErrorPropagation) -> Self::ErrorPropagation;
+    fn return_(ext: P::Return) -> Self::Return;
+    fn break_(ext: P::Break) -> Self::Break;
+    fn continue_(ext: P::Continue) -> Self::Continue;
+    fn block(ext: P::Block) -> Self::Block;
+    fn grouped(ext: P::Grouped) -> Self::Grouped;
+    fn path(ext: P::Path) -> Self::Path;
+    fn literal(ext: P::Literal) -> Self::Literal;
+    fn array(ext: P::Array) -> Self::Array;
+    fn repeat(ext: P::Repeat) -> Self::Repeat;
+    fn range(ext: P::Range) -> Self::Range;
+    fn prefix(ext: P::Prefix) -> Self::Prefix;
+    fn binary(ext: P::Binary) -> Self::Binary;
+    fn call(ext: P::Call) -> Self::Call;
+    fn method_call(ext: P::MethodCall) -> Self::MethodCall;
+    fn field(ext: P::Field) -> Self::Field;
+    fn index(ext: P::Index) -> Self::Index;
+    fn asm(ext: P::Asm) -> Self::Asm;
+    fn other(ext: P::Other) -> Self::Other;
+    fn pattern(ext: P::Pattern) -> Self::Pattern;
+}
+
+/// The extension type of a constructor that a phase does not have. It has no values, so no
+/// node of that kind can be built in the phase, and passes over it match such nodes with
+/// `match ext {}`.
+pub enum Removed {}
@@ -0,0 +1,156 @@
+use crate::model::base::*;
+use crate::model::resolved::{Name, ResolvedPath};
+use crate::model::ttg::{Removed, TreeData};
+use crate::model::types::Type;
+
+/// The phase produced by type checking. Every expression carries its type in its extension
+/// field, and every type annotation is replaced by the type it denotes. Method calls are gone,
+/// desugared to plain calls of the functions they resolve to.
+pub enum Typed {}
+
+impl TreeData<Typed> for Typed {
+    type Case = Type;
+    type IfIs = Type;
+    type For = Type;
+    type While = Type;
+    type Loop = Type;
+    type If = Type;
+    type ErrorPropagation = Type;
+    type Return = Type;
+    type Break = Type;
+    type Continue = Type;
+    type Block = Type;
+    type Grouped = Type;
+    type Path = Type;
+    type Literal = Type;
+    type Array = Type;
+    type Repeat = Type;
+    type Range = Type;
+    type Prefix = Type;
+    type Binary = Type;
+    type Call = Type;
+    type MethodCall = Removed;
+    type Field = Type;
+    type Index = Type;
+    type Asm = Type;
+    type Other = Type;
+    type Pattern = Type;
+
+    type NameRepresentation = Name;
+    type PathRepresentation = ResolvedPath;
+    type TypeRepresentation = Type;
+}
+
+pub fn type_of(expression: &Expression<Typed>) -> &Type {
+    match &expression.item {
+        ExpressionKind::ExpressionWithBlock(with_block) => with_block_type(with_block),
+        ExpressionKind::ExpressionWithoutBlock(without_block) => without_block_type(without_block),
+    }
+}
+
+pub fn with_block_type(expression: &ExpressionWithBlock<Typed>) -> &Type {
+    use ExpressionWithBlock::*;
+
+    match expression {
+        Case(_, _, ty)
+        | IfIs(_, _, _, _, ty)
+        | For(_, _, _, _, ty)
+        | While(_, _, _, ty)
+        | Loop(_, _, ty)
+        | If(_, _, _, ty)
+        | Block(_, ty) => ty,
+    }
+}
+
+pub fn without_block_type(expression: &ExpressionWithoutBlock<Typed>) -> &Type {
+    use ExpressionWithoutBlock::*;
+
+    match expression {
+        ErrorPropagation(_, ty)
+        | Return(_, ty)
+        | Break(_, _, ty)
+        | Continue(_, ty)
+        | Grouped(_, ty)
+        | Path(_, ty)
+        | Literal(_, ty)
+        | Array(_, ty)
+        | Repeat(_, _, ty)
+        | Range(_, _, _, ty)
+        | Prefix(_, _, ty)
+        | Binary(_, _, _, ty)
+        | Call(_, _, ty)
+        | Field(_, _, ty)
+        | Index(_, _, ty)
+        | Asm(_, ty)
+        | Other(ty) => ty,
+        MethodCall(_, _, _, removed) => match *removed {},
+    }
+}
+
+pub fn with_block_type_mut(expression: &mut ExpressionWithBlock<Typed>) -> &mut Type {
+    use ExpressionWithBlock::*;
+
+    match expression {
+        Case(_, _, ty)
+        | IfIs(_, _, _, _, ty)
+        | For(_, _, _, _, ty)
+        | While(_, _, _, ty)
+        | Loop(_, _, ty)
+        | If(_, _, _, ty)
+        | Block(_, ty) => ty,
+    }
+}
+
+pub fn without_block_type_mut(expression: &mut ExpressionWithoutBlock<Typed>) -> &mut Type {
+    use ExpressionWithoutBlock::*;
+
+    match expression {
+        ErrorPropagation(_, ty)
+        | Return(_, ty)
+        | Break(_, _, ty)
+        | Continue(_, ty)
+        | Grouped(_, ty)
+        | Path(_, ty)
+        | Literal(_, ty)
+        | Array(_, ty)
+        | Repeat(_, _, ty)
+        | Range(_, _, _, ty)
+        | Prefix(_, _, ty)
+        | Binary(_, _, _, ty)
+        | Call(_, _, ty)
+        | Field(_, _, ty)
+        | Index(_, _, ty)
+        | Asm(_, ty)
+        | Other(ty) => ty,
+        MethodCall(_, _, _, removed) => match *removed {},
+    }
+}
+
+/// The type of the value a pattern matches, in this phase or any phase after it.
+pub fn pattern_type<P: TreeData<P, Pattern = Type>>(pattern: &PatternKind<P>) -> &Type {
+    use PatternKind::*;
+
+    match pattern {
+        Wildcard(ty)
+        | Binding(_, _, ty)
+        | Literal(_, ty)
+        | Range(_, _, _, ty)
+        | Path(_, ty)
+        | Tuple(_, _, ty)
+        | Struct(_, _, _, ty) => ty,
+    }
+}
+
+pub fn pattern_type_mut(pattern: &mut PatternKind<Typed>) -> &mut Type {
+    use PatternKind::*;
+
+    match pattern {
+        Wildcard(ty)
+        | Binding(_, _, ty)
+        | Literal(_, ty)
+        | Range(_, _, _, ty)
+        | Path(_, ty)
+        | Tuple(_, _, ty)
+        | Struct(_, _, _, ty) => ty,
+    }
+}
@@ -1,4 +1,5 @@
-use crate::model::base::Mutability;
+use crate::model::base::{Mutability, RangeLimits};
+use crate::model::def::DefId;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Type {
@@ -14,7 +15,117 @@ pub enum Type {
     Unit,
     Never,
     Array(Box<Type>, u32),
+    Slice(Box<Type>), // Unsized, so always behind a reference, which then also carries the length
     Reference(Mutability, Box<Type>),
-    Function,
-    String, // User defined types. WIP.
+    Function(Vec<Type>, Box<Type>), // Function pointer, `fn(params) -> return`
+    Range(RangeLimits, Box<Type>), // `start..end` or `start..=end`, holding both bounds
+    Named(DefId, String, Vec<Type>), // A struct or enum, its name for diagnostics, and its type arguments
+    Parameter(DefId, String), // Generic type parameter, along with its name for diagnostics
+    Infer(usize, InferKind), // Inference variable, which only appears while a body is being checked
+}
+
+/// What an inference variable may stand for. Integer variables are the types of integer
+/// literals whose type is not yet known, and may only become integer types. See `infer`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InferKind {
+    Any,
+    Integer,
+}
+
+impl Type {
+    /// Replaces every generic type parameter with its argument. This is how a generic function
+    /// is monomorphized: its signature and body are re-typed under each distinct set of
+    /// arguments it is called with. Parameters without an argument are left untouched.
+    pub fn substitute(&self, arguments: &[(DefId, Type)]) -> Type {
+        match self {
+            Type::Parameter(def, _) => arguments
+                .iter()
+                .find(|(param, _)| param == def)
+                .map_or_else(|| self.clone(), |(_, arg)| arg.clone()),
+            Type::Array(element, size) => {
+                Type::Array(Box::new(element.substitute(arguments)), *size)
+            }
+            Type::Slice(element) => Type::Slice(Box::new(element.substitute(arguments))),
+            Type::Range(limits, bound) => {
+                Type::Range(*limits, Box::new(bound.substitute(arguments)))
+            }
+            Type::Reference(mutability, inner) => {
+                Type::Reference(mutability.clone(), Box::new(inner.substitute(arguments)))
+            }
+            Type::Function(parameters, return_type) => Type::Function(
+                parameters
+                    .iter()
+                    .map(|param| param.substitute(arguments))
+                    .collect(),
+                Box::new(return_type.substitute(arguments)),
+            ),
+            Type::Named(def, name, types) => Type::Named(
+                *def,
+                name.clone(),
+                types.iter().map(|ty| ty.substitute(arguments)).collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn is_generic(&self) -> bool {
+        match self {
+            Type::Parameter(_, _) => true,
+            Type::Array(element, _) | Type::Slice(element) | Type::Range(_, element) => {
+                element.is_generic()
+            }
+            Type::Reference(_, inner) => inner.is_generic(),
+            Type::Function(parameters, return_type) => {
+                parameters.iter().any(Type::is_generic) || return_type.is_generic()
+            }
+            Type::Named(_, _, arguments) => arguments.iter().any(Type::is_generic),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Type::*;
+
+        match self {
+            U8 => write!(f, "u8"),
+            I8 => write!(f, "i8"),
+            U16 => write!(f, "u16"),
+            I16 => write!(f, "i16"),
+            U32 => write!(f, "u32"),
+            I32 => write!(f, "i32"),
+            Bool => write!(f, "bool"),
+            Char => write!(f, "char"),
+            Str => write!(f, "str"),
+            Unit => write!(f, "Unit"),
+            Never => write!(f, "Never"),
+            Array(element, size) => write!(f, "[{element}; {size}]"),
+            Slice(element) => write!(f, "[{element}]"),
+            Range(RangeLimits::HalfOpen, bound) => write!(f, "Range[{bound}]"),
+            Range(RangeLimits::Closed, bound) => write!(f, "RangeInclusive[{bound}]"),
+            Reference(Mutability::Immutable, inner) => write!(f, "&{inner}"),
+            Reference(Mutability::Mutable, inner) => write!(f, "&mut {inner}"),
+            Function(parameters, return_type) => {
+                let parameters = parameters
+                    .iter()
+                    .map(|param| param.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "fn({parameters}) -> {return_type}")
+            }
+            Named(_, name, arguments) if arguments.is_empty() => write!(f, "{name}"),
+            Named(_, name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{name}[{arguments}]")
+            }
+            Parameter(_, name) => write!(f, "{name}"),
+            Infer(_, InferKind::Any) => write!(f, "_"),
+            Infer(_, InferKind::Integer) => write!(f, "{{integer}}"),
+        }
+    }
 }
@@ -0,0 +1,1022 @@
+use crate::model::base::*;
+use crate::model::ttg::TreeData;
+use crate::span::Spanned;
+
+/// What a name is the name of, for passes that treat names differently depending on what they
+/// name. Names are given to the hooks in the order they come into scope, so a `let` binding is
+/// visited after its initializer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NameContext {
+    Module,      // The geode, or a module file
+    Declaration, // An item, variant, or associated function, where it is declared
+    TypeParameter,
+    Parameter,
+    Binding, // A `let`, `for`, or pattern binding
+    Import,  // The name given to an import with `as`
+    Field,   // A field, where it is declared or accessed through a value
+    Method,  // A method called through a value
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathContext {
+    Expression,
+    Trait,  // A generic bound, or the trait an `impl` implements
+    Import, // The prefix of a `use` tree
+    Pattern, // A unit variant, constant, or tuple variant matched by a pattern
+    StructPattern, // The struct or struct-like variant matched by a struct pattern
+}
+
+/// Walks a tree by reference. Every method defaults to walking the node's children through the
+/// matching `walk_` function, so an implementation only overrides the nodes it cares about and
+/// calls the `walk_` function itself to keep walking below them.
+///
+/// Names, paths and types are opaque to the walk, since their representation depends on the
+/// phase. `enter_scope` and `exit_scope` surround every function signature, enum, block, `when`
+/// arm, `if is` branch and `for` binding, in which bindings are visible.
+pub trait Visitor<P: TreeData<P>> {
+    fn visit_geode(&mut self, geode: &Geode<P>) {
+        walk_geode(self, geode)
+    }
+
+    fn visit_module(&mut self, id: ModuleId, module: &Module<P>) {
+        walk_module(self, id, module)
+    }
+
+    fn visit_item(&mut self, item: &Item<P>) {
+        walk_item(self, item)
+    }
+
+    fn visit_use_tree(&mut self, tree: &UseTree<P>) {
+        walk_use_tree(self, tree)
+    }
+
+    fn visit_function(&mut self, function: &FunctionItem<P>) {
+        walk_function(self, function)
+    }
+
+    fn visit_generic(&mut self, generic: &GenericParameter<P>) {
+        walk_generic(self, generic)
+    }
+
+    fn visit_receiver(&mut self, _receiver: &Spanned<Receiver>) {}
+
+    fn visit_parameter(&mut self, parameter: &Parameter<P>) {
+        walk_parameter(self, parameter)
+    }
+
+    fn visit_field(&mut self, field: &Field<P>) {
+        walk_field(self, field)
+    }
+
+    fn visit_variant(&mut self, variant: &Variant<P>) {
+        walk_variant(self, variant)
+    }
+
+    fn visit_attribute(&mut self, attribute: &Attribute<P>) {
+        walk_attribute(self, attribute)
+    }
+
+    fn visit_block(&mut self, block: &BlockExpression<P>) {
+        walk_block(self, block)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement<P>) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expression(&mut self, expression: &Expression<P>) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_with_block(&mut self, expression: &ExpressionWithBlock<P>) {
+        walk_with_block(self, expression)
+    }
+
+    fn visit_without_block(&mut self, expression: &ExpressionWithoutBlock<P>) {
+        walk_without_block(self, expression)
+    }
+
+    fn visit_arm(&mut self, arm: &Arm<P>) {
+        walk_arm(self, arm)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern<P>) {
+        walk_pattern(self, pattern)
+    }
+
+    fn visit_name(&mut self, _name: &P::NameRepresentation, _context: NameContext) {}
+
+    fn visit_path(&mut self, _path: &P::PathRepresentation, _context: PathContext) {}
+
+    fn visit_type(&mut self, _ty: &P::TypeRepresentation) {}
+
+    fn enter_scope(&mut self) {}
+
+    fn exit_scope(&mut self) {}
+}
+
+pub fn walk_geode<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, geode: &Geode<P>) {
+    visitor.visit_name(&geode.name, NameContext::Module);
+
+    for (id, module) in geode.modules.iter().enumerate() {
+        visitor.visit_module(id, module);
+    }
+}
+
+pub fn walk_module<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    _id: ModuleId,
+    module: &Module<P>,
+) {
+    visitor.visit_name(&module.name, NameContext::Module);
+
+    for item in &module.items {
+        visitor.visit_item(item);
+    }
+}
+
+/// Walks an item. The items of an out-of-line module are walked with its `Module`, not here.
+pub fn walk_item<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, item: &Item<P>) {
+    for attribute in &item.attributes {
+        visitor.visit_attribute(attribute);
+    }
+
+    match &item.kind {
+        ItemKind::Mod(ModItem { name, body }) => {
+            visitor.visit_name(name, NameContext::Declaration);
+
+            if let ModBody::Inline(items) = body {
+                for item in items {
+                    visitor.visit_item(item);
+                }
+            }
+        }
+        ItemKind::Use(tree) => visitor.visit_use_tree(tree),
+        ItemKind::Function(function) => visitor.visit_function(function),
+        ItemKind::TypeAlias(TypeAliasItem { name, ty }) => {
+            visitor.visit_name(name, NameContext::Declaration);
+            visitor.visit_type(ty);
+        }
+        ItemKind::Struct(StructItem { name, fields }) => {
+            visitor.visit_name(name, NameContext::Declaration);
+
+            for field in fields {
+                visitor.visit_field(field);
+            }
+        }
+        ItemKind::Enum(EnumItem {
+            name,
+            generics,
+            variants,
+        }) => {
+            visitor.visit_name(name, NameContext::Declaration);
+            visitor.enter_scope();
+
+            for generic in generics {
+                visitor.visit_generic(generic);
+            }
+            for variant in variants {
+                visitor.visit_variant(variant);
+            }
+
+            visitor.exit_scope();
+        }
+        ItemKind::Const(ConstItem { name, ty, value })
+        | ItemKind::Static(StaticItem { name, ty, value }) => {
+            visitor.visit_name(name, NameContext::Declaration);
+            visitor.visit_type(ty);
+            visitor.visit_expression(value);
+        }
+        ItemKind::Impl(ImplItem {
+            trait_,
+            ty,
+            functions,
+        }) => {
+            if let Some(trait_) = trait_ {
+                visitor.visit_path(trait_, PathContext::Trait);
+            }
+            visitor.visit_type(ty);
+
+            for function in functions {
+                visitor.visit_item(function);
+            }
+        }
+        ItemKind::Trait(TraitItem { name, functions }) => {
+            visitor.visit_name(name, NameContext::Declaration);
+
+            for function in functions {
+                visitor.visit_item(function);
+            }
+        }
+    }
+}
+
+pub fn walk_use_tree<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, tree: &UseTree<P>) {
+    visitor.visit_path(&tree.prefix, PathContext::Import);
+
+    match &tree.kind {
+        UseTreeKind::Simple(Some(alias)) => visitor.visit_name(alias, NameContext::Import),
+        UseTreeKind::Simple(None) | UseTreeKind::Glob => {}
+        UseTreeKind::Nested(trees, _) => {
+            for tree in trees {
+                visitor.visit_use_tree(tree);
+            }
+        }
+    }
+}
+
+/// Walks a function. Its name is declared outside of the scope of its signature, in which its
+/// type parameters, receiver and parameters are bound, in that order.
+pub fn walk_function<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    function: &FunctionItem<P>,
+) {
+    visitor.visit_name(&function.name, NameContext::Declaration);
+    visitor.enter_scope();
+
+    for generic in &function.generics {
+        visitor.visit_generic(generic);
+    }
+    if let Some(receiver) = &function.receiver {
+        visitor.visit_receiver(receiver);
+    }
+    for parameter in &function.parameters {
+        visitor.visit_parameter(parameter);
+    }
+    if let Some(return_type) = &function.return_type {
+        visitor.visit_type(return_type);
+    }
+    if let Some(body) = &function.body {
+        visitor.visit_block(body);
+    }
+
+    visitor.exit_scope();
+}
+
+pub fn walk_generic<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    generic: &GenericParameter<P>,
+) {
+    visitor.visit_name(&generic.name, NameContext::TypeParameter);
+
+    for bound in &generic.bounds {
+        visitor.visit_path(bound, PathContext::Trait);
+    }
+}
+
+pub fn walk_parameter<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    parameter: &Parameter<P>,
+) {
+    visitor.visit_type(&parameter.ty);
+    visitor.visit_name(&parameter.name, NameContext::Parameter);
+}
+
+pub fn walk_field<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, field: &Field<P>) {
+    for attribute in &field.attributes {
+        visitor.visit_attribute(attribute);
+    }
+
+    visitor.visit_name(&field.name, NameContext::Field);
+    visitor.visit_type(&field.ty);
+}
+
+pub fn walk_variant<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, variant: &Variant<P>) {
+    for attribute in &variant.attributes {
+        visitor.visit_attribute(attribute);
+    }
+
+    visitor.visit_name(&variant.name, NameContext::Declaration);
+
+    match &variant.kind {
+        VariantKind::Unit => {}
+        VariantKind::Tuple(types) => {
+            for ty in types {
+                visitor.visit_type(ty);
+            }
+        }
+        VariantKind::Struct(fields) => {
+            for field in fields {
+                visitor.visit_field(field);
+            }
+        }
+    }
+}
+
+pub fn walk_attribute<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    attribute: &Attribute<P>,
+) {
+    for argument in &attribute.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_block<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    block: &BlockExpression<P>,
+) {
+    visitor.enter_scope();
+
+    for statements in block.iter().flatten() {
+        match statements {
+            Statements::Leading(first, rest, last) => {
+                visitor.visit_statement(first);
+
+                for statement in rest {
+                    visitor.visit_statement(statement);
+                }
+                if let Some(last) = last {
+                    visitor.visit_expression(last);
+                }
+            }
+            Statements::Block(expression) => visitor.visit_expression(expression),
+        }
+    }
+
+    visitor.exit_scope();
+}
+
+pub fn walk_statement<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    statement: &Statement<P>,
+) {
+    match statement {
+        Statement::Empty => {}
+        Statement::Let(Let {
+            name,
+            ty,
+            initializer,
+            ..
+        }) => {
+            if let Some(ty) = ty {
+                visitor.visit_type(ty);
+            }
+            if let Some(initializer) = initializer {
+                visitor.visit_expression(initializer);
+            }
+            visitor.visit_name(name, NameContext::Binding);
+        }
+        Statement::Expression(expression) | Statement::Defer(expression) => {
+            visitor.visit_expression(expression)
+        }
+    }
+}
+
+pub fn walk_expression<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    expression: &Expression<P>,
+) {
+    match &expression.item {
+        ExpressionKind::ExpressionWithBlock(with_block) => visitor.visit_with_block(with_block),
+        ExpressionKind::ExpressionWithoutBlock(without_block) => {
+            visitor.visit_without_block(without_block)
+        }
+    }
+}
+
+pub fn walk_with_block<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    expression: &ExpressionWithBlock<P>,
+) {
+    use ExpressionWithBlock::*;
+
+    match expression {
+        Case(scrutinee, arms, _) => {
+            visitor.visit_expression(scrutinee);
+
+            for arm in arms {
+                visitor.visit_arm(arm);
+            }
+        }
+        IfIs(scrutinee, pattern, then, otherwise, _) => {
+            visitor.visit_expression(scrutinee);
+            visitor.enter_scope();
+            visitor.visit_pattern(pattern);
+            visitor.visit_expression(then);
+            visitor.exit_scope();
+
+            if let Some(otherwise) = otherwise {
+                visitor.visit_expression(otherwise);
+            }
+        }
+        For(_, name, iterable, body, _) => {
+            visitor.visit_expression(iterable);
+            visitor.enter_scope();
+            visitor.visit_name(name, NameContext::Binding);
+            visitor.visit_block(body);
+            visitor.exit_scope();
+        }
+        While(_, condition, body, _) => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(body);
+        }
+        Loop(_, body, _) | Block(body, _) => visitor.visit_block(body),
+        If(condition, then, otherwise, _) => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(then);
+
+            if let Some(otherwise) = otherwise {
+                visitor.visit_expression(otherwise);
+            }
+        }
+    }
+}
+
+pub fn walk_without_block<P: TreeData<P>, V: Visitor<P> + ?Sized>(
+    visitor: &mut V,
+    expression: &ExpressionWithoutBlock<P>,
+) {
+    use ExpressionWithoutBlock::*;
+
+    match expression {
+        ErrorPropagation(inner, _) | Grouped(inner, _) | Prefix(_, inner, _) => {
+            visitor.visit_expression(inner)
+        }
+        Return(value, _) | Break(_, value, _) => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+        Continue(_, _) | Literal(_, _) | Other(_) => {}
+        Path(path, _) => visitor.visit_path(path, PathContext::Expression),
+        Array(elements, _) => {
+            for element in elements {
+                visitor.visit_expression(element);
+            }
+        }
+        Repeat(left, right, _) | Binary(left, _, right, _) | Index(left, right, _) => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Range(start, end, _, _) => {
+            for bound in [start, end].into_iter().flatten() {
+                visitor.visit_expression(bound);
+            }
+        }
+        Call(function, arguments, _) => {
+            visitor.visit_expression(function);
+
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        MethodCall(receiver, method, arguments, _) => {
+            visitor.visit_expression(receiver);
+            visitor.visit_name(method, NameContext::Method);
+
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        Field(base, field, _) => {
+            visitor.visit_expression(base);
+            visitor.visit_name(field, NameContext::Field);
+        }
+        Asm(assembly, _) => {
+            for operand in &assembly.operands {
+                visitor.visit_expression(&operand.expression);
+            }
+        }
+    }
+}
+
+/// Walks a `when` arm, whose guard and expression share a scope with the pattern's bindings.
+pub fn walk_arm<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, arm: &Arm<P>) {
+    visitor.enter_scope();
+    visitor.visit_pattern(&arm.pattern);
+
+    if let Some(guard) = &arm.guard {
+        visitor.visit_expression(guard);
+    }
+    visitor.visit_expression(&arm.expression);
+
+    visitor.exit_scope();
+}
+
+pub fn walk_pattern<P: TreeData<P>, V: Visitor<P> + ?Sized>(visitor: &mut V, pattern: &Pattern<P>) {
+    use PatternKind::*;
+
+    match &pattern.item {
+        Wildcard(_) | Literal(_, _) | Range(_, _, _, _) => {}
+        Binding(_, name, _) => visitor.visit_name(name, NameContext::Binding),
+        Path(path, _) => visitor.visit_path(path, PathContext::Pattern),
+        Tuple(path, elements, _) => {
+            visitor.visit_path(path, PathContext::Pattern);
+
+            for element in elements {
+                visitor.visit_pattern(element);
+            }
+        }
+        Struct(path, fields, _, _) => {
+            visitor.visit_path(path, PathContext::StructPattern);
+
+            for field in fields {
+                visitor.visit_name(&field.name, NameContext::Field);
+                visitor.visit_pattern(&field.pattern);
+            }
+        }
+    }
+}
+
+/// Walks a tree by mutable reference, in the same order as `Visitor`, for passes that rewrite
+/// parts of a tree in place without changing its phase.
+pub trait VisitorMut<P: TreeData<P>> {
+    fn visit_geode_mut(&mut self, geode: &mut Geode<P>) {
+        walk_geode_mut(self, geode)
+    }
+
+    fn visit_module_mut(&mut self, id: ModuleId, module: &mut Module<P>) {
+        walk_module_mut(self, id, module)
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item<P>) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_use_tree_mut(&mut self, tree: &mut UseTree<P>) {
+        walk_use_tree_mut(self, tree)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut FunctionItem<P>) {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_generic_mut(&mut self, generic: &mut GenericParameter<P>) {
+        walk_generic_mut(self, generic)
+    }
+
+    fn visit_receiver_mut(&mut self, _receiver: &mut Spanned<Receiver>) {}
+
+    fn visit_parameter_mut(&mut self, parameter: &mut Parameter<P>) {
+        walk_parameter_mut(self, parameter)
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field<P>) {
+        walk_field_mut(self, field)
+    }
+
+    fn visit_variant_mut(&mut self, variant: &mut Variant<P>) {
+        walk_variant_mut(self, variant)
+    }
+
+    fn visit_attribute_mut(&mut self, attribute: &mut Attribute<P>) {
+        walk_attribute_mut(self, attribute)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockExpression<P>) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement<P>) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression<P>) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_with_block_mut(&mut self, expression: &mut ExpressionWithBlock<P>) {
+        walk_with_block_mut(self, expression)
+    }
+
+    fn visit_without_block_mut(&mut self, expression: &mut ExpressionWithoutBlock<P>) {
+        walk_without_block_mut(self, expression)
+    }
+
+    fn visit_arm_mut(&mut self, arm: &mut Arm<P>) {
+        walk_arm_mut(self, arm)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern<P>) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_name_mut(&mut self, _name: &mut P::NameRepresentation, _context: NameContext) {}
+
+    fn visit_path_mut(&mut self, _path: &mut P::PathRepresentation, _context: PathContext) {}
+
+    fn visit_type_mut(&mut self, _ty: &mut P::TypeRepresentation) {}
+
+    fn enter_scope(&mut self) {}
+
+    fn exit_scope(&mut self) {}
+}
+
+pub fn walk_geode_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    geode: &mut Geode<P>,
+) {
+    visitor.visit_name_mut(&mut geode.name, NameContext::Module);
+
+    for (id, module) in geode.modules.iter_mut().enumerate() {
+        visitor.visit_module_mut(id, module);
+    }
+}
+
+pub fn walk_module_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    _id: ModuleId,
+    module: &mut Module<P>,
+) {
+    visitor.visit_name_mut(&mut module.name, NameContext::Module);
+
+    for item in &mut module.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_item_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    item: &mut Item<P>,
+) {
+    for attribute in &mut item.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+
+    match &mut item.kind {
+        ItemKind::Mod(ModItem { name, body }) => {
+            visitor.visit_name_mut(name, NameContext::Declaration);
+
+            if let ModBody::Inline(items) = body {
+                for item in items {
+                    visitor.visit_item_mut(item);
+                }
+            }
+        }
+        ItemKind::Use(tree) => visitor.visit_use_tree_mut(tree),
+        ItemKind::Function(function) => visitor.visit_function_mut(function),
+        ItemKind::TypeAlias(TypeAliasItem { name, ty }) => {
+            visitor.visit_name_mut(name, NameContext::Declaration);
+            visitor.visit_type_mut(ty);
+        }
+        ItemKind::Struct(StructItem { name, fields }) => {
+            visitor.visit_name_mut(name, NameContext::Declaration);
+
+            for field in fields {
+                visitor.visit_field_mut(field);
+            }
+        }
+        ItemKind::Enum(EnumItem {
+            name,
+            generics,
+            variants,
+        }) => {
+            visitor.visit_name_mut(name, NameContext::Declaration);
+            visitor.enter_scope();
+
+            for generic in generics {
+                visitor.visit_generic_mut(generic);
+            }
+            for variant in variants {
+                visitor.visit_variant_mut(variant);
+            }
+
+            visitor.exit_scope();
+        }
+        ItemKind::Const(ConstItem { name, ty, value })
+        | ItemKind::Static(StaticItem { name, ty, value }) => {
+            visitor.visit_name_mut(name, NameContext::Declaration);
+            visitor.visit_type_mut(ty);
+            visitor.visit_expression_mut(value);
+        }
+        ItemKind::Impl(ImplItem {
+            trait_,
+            ty,
+            functions,
+        }) => {
+            if let Some(trait_) = trait_ {
+                visitor.visit_path_mut(trait_, PathContext::Trait);
+            }
+            visitor.visit_type_mut(ty);
+
+            for function in functions {
+                visitor.visit_item_mut(function);
+            }
+        }
+        ItemKind::Trait(TraitItem { name, functions }) => {
+            visitor.visit_name_mut(name, NameContext::Declaration);
+
+            for function in functions {
+                visitor.visit_item_mut(function);
+            }
+        }
+    }
+}
+
+pub fn walk_use_tree_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    tree: &mut UseTree<P>,
+) {
+    visitor.visit_path_mut(&mut tree.prefix, PathContext::Import);
+
+    match &mut tree.kind {
+        UseTreeKind::Simple(Some(alias)) => visitor.visit_name_mut(alias, NameContext::Import),
+        UseTreeKind::Simple(None) | UseTreeKind::Glob => {}
+        UseTreeKind::Nested(trees, _) => {
+            for tree in trees {
+                visitor.visit_use_tree_mut(tree);
+            }
+        }
+    }
+}
+
+pub fn walk_function_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    function: &mut FunctionItem<P>,
+) {
+    visitor.visit_name_mut(&mut function.name, NameContext::Declaration);
+    visitor.enter_scope();
+
+    for generic in &mut function.generics {
+        visitor.visit_generic_mut(generic);
+    }
+    if let Some(receiver) = &mut function.receiver {
+        visitor.visit_receiver_mut(receiver);
+    }
+    for parameter in &mut function.parameters {
+        visitor.visit_parameter_mut(parameter);
+    }
+    if let Some(return_type) = &mut function.return_type {
+        visitor.visit_type_mut(return_type);
+    }
+    if let Some(body) = &mut function.body {
+        visitor.visit_block_mut(body);
+    }
+
+    visitor.exit_scope();
+}
+
+pub fn walk_generic_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    generic: &mut GenericParameter<P>,
+) {
+    visitor.visit_name_mut(&mut generic.name, NameContext::TypeParameter);
+
+    for bound in &mut generic.bounds {
+        visitor.visit_path_mut(bound, PathContext::Trait);
+    }
+}
+
+pub fn walk_parameter_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    parameter: &mut Parameter<P>,
+) {
+    visitor.visit_type_mut(&mut parameter.ty);
+    visitor.visit_name_mut(&mut parameter.name, NameContext::Parameter);
+}
+
+pub fn walk_field_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    field: &mut Field<P>,
+) {
+    for attribute in &mut field.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+
+    visitor.visit_name_mut(&mut field.name, NameContext::Field);
+    visitor.visit_type_mut(&mut field.ty);
+}
+
+pub fn walk_variant_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    variant: &mut Variant<P>,
+) {
+    for attribute in &mut variant.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+
+    visitor.visit_name_mut(&mut variant.name, NameContext::Declaration);
+
+    match &mut variant.kind {
+        VariantKind::Unit => {}
+        VariantKind::Tuple(types) => {
+            for ty in types {
+                visitor.visit_type_mut(ty);
+            }
+        }
+        VariantKind::Struct(fields) => {
+            for field in fields {
+                visitor.visit_field_mut(field);
+            }
+        }
+    }
+}
+
+pub fn walk_attribute_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    attribute: &mut Attribute<P>,
+) {
+    for argument in &mut attribute.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+pub fn walk_block_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    block: &mut BlockExpression<P>,
+) {
+    visitor.enter_scope();
+
+    for statements in block.iter_mut().flatten() {
+        match statements {
+            Statements::Leading(first, rest, last) => {
+                visitor.visit_statement_mut(first);
+
+                for statement in rest {
+                    visitor.visit_statement_mut(statement);
+                }
+                if let Some(last) = last {
+                    visitor.visit_expression_mut(last);
+                }
+            }
+            Statements::Block(expression) => visitor.visit_expression_mut(expression),
+        }
+    }
+
+    visitor.exit_scope();
+}
+
+pub fn walk_statement_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    statement: &mut Statement<P>,
+) {
+    match statement {
+        Statement::Empty => {}
+        Statement::Let(Let {
+            name,
+            ty,
+            initializer,
+            ..
+        }) => {
+            if let Some(ty) = ty {
+                visitor.visit_type_mut(ty);
+            }
+            if let Some(initializer) = initializer {
+                visitor.visit_expression_mut(initializer);
+            }
+            visitor.visit_name_mut(name, NameContext::Binding);
+        }
+        Statement::Expression(expression) | Statement::Defer(expression) => {
+            visitor.visit_expression_mut(expression)
+        }
+    }
+}
+
+pub fn walk_expression_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut Expression<P>,
+) {
+    match &mut expression.item {
+        ExpressionKind::ExpressionWithBlock(with_block) => visitor.visit_with_block_mut(with_block),
+        ExpressionKind::ExpressionWithoutBlock(without_block) => {
+            visitor.visit_without_block_mut(without_block)
+        }
+    }
+}
+
+pub fn walk_with_block_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ExpressionWithBlock<P>,
+) {
+    use ExpressionWithBlock::*;
+
+    match expression {
+        Case(scrutinee, arms, _) => {
+            visitor.visit_expression_mut(scrutinee);
+
+            for arm in arms {
+                visitor.visit_arm_mut(arm);
+            }
+        }
+        IfIs(scrutinee, pattern, then, otherwise, _) => {
+            visitor.visit_expression_mut(scrutinee);
+            visitor.enter_scope();
+            visitor.visit_pattern_mut(pattern);
+            visitor.visit_expression_mut(then);
+            visitor.exit_scope();
+
+            if let Some(otherwise) = otherwise {
+                visitor.visit_expression_mut(otherwise);
+            }
+        }
+        For(_, name, iterable, body, _) => {
+            visitor.visit_expression_mut(iterable);
+            visitor.enter_scope();
+            visitor.visit_name_mut(name, NameContext::Binding);
+            visitor.visit_block_mut(body);
+            visitor.exit_scope();
+        }
+        While(_, condition, body, _) => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_mut(body);
+        }
+        Loop(_, body, _) | Block(body, _) => visitor.visit_block_mut(body),
+        If(condition, then, otherwise, _) => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_mut(then);
+
+            if let Some(otherwise) = otherwise {
+                visitor.visit_expression_mut(otherwise);
+            }
+        }
+    }
+}
+
+pub fn walk_without_block_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ExpressionWithoutBlock<P>,
+) {
+    use ExpressionWithoutBlock::*;
+
+    match expression {
+        ErrorPropagation(inner, _) | Grouped(inner, _) | Prefix(_, inner, _) => {
+            visitor.visit_expression_mut(inner)
+        }
+        Return(value, _) | Break(_, value, _) => {
+            if let Some(value) = value {
+                visitor.visit_expression_mut(value);
+            }
+        }
+        Continue(_, _) | Literal(_, _) | Other(_) => {}
+        Path(path, _) => visitor.visit_path_mut(path, PathContext::Expression),
+        Array(elements, _) => {
+            for element in elements {
+                visitor.visit_expression_mut(element);
+            }
+        }
+        Repeat(left, right, _) | Binary(left, _, right, _) | Index(left, right, _) => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        }
+        Range(start, end, _, _) => {
+            for bound in [start, end].into_iter().flatten() {
+                visitor.visit_expression_mut(bound);
+            }
+        }
+        Call(function, arguments, _) => {
+            visitor.visit_expression_mut(function);
+
+            for argument in arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        MethodCall(receiver, method, arguments, _) => {
+            visitor.visit_expression_mut(receiver);
+            visitor.visit_name_mut(method, NameContext::Method);
+
+            for argument in arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        Field(base, field, _) => {
+            visitor.visit_expression_mut(base);
+            visitor.visit_name_mut(field, NameContext::Field);
+        }
+        Asm(assembly, _) => {
+            for operand in &mut assembly.operands {
+                visitor.visit_expression_mut(&mut operand.expression);
+            }
+        }
+    }
+}
+
+pub fn walk_arm_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(visitor: &mut V, arm: &mut Arm<P>) {
+    visitor.enter_scope();
+    visitor.visit_pattern_mut(&mut arm.pattern);
+
+    if let Some(guard) = &mut arm.guard {
+        visitor.visit_expression_mut(guard);
+    }
+    visitor.visit_expression_mut(&mut arm.expression);
+
+    visitor.exit_scope();
+}
+
+pub fn walk_pattern_mut<P: TreeData<P>, V: VisitorMut<P> + ?Sized>(
+    visitor: &mut V,
+    pattern: &mut Pattern<P>,
+) {
+    use PatternKind::*;
+
+    match &mut pattern.item {
+        Wildcard(_) | Literal(_, _) | Range(_, _, _, _) => {}
+        Binding(_, name, _) => visitor.visit_name_mut(name, NameContext::Binding),
+        Path(path, _) => visitor.visit_path_mut(path, PathContext::Pattern),
+        Tuple(path, elements, _) => {
+            visitor.visit_path_mut(path, PathContext::Pattern);
+
+            for element in elements {
+                visitor.visit_pattern_mut(element);
+            }
+        }
+        Struct(path, fields, _, _) => {
+            visitor.visit_path_mut(path, PathContext::StructPattern);
+
+            for field in fields {
+                visitor.visit_name_mut(&mut field.name, NameContext::Field);
+                visitor.visit_pattern_mut(&mut field.pattern);
+            }
+        }
+    }
+}
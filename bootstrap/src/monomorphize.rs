@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::model::def::DefId;
+use crate::model::ir::FunctionId;
+use crate::model::types::Type;
+use crate::span::Span;
+use crate::traits::TraitEnvironment;
+
+/// A function together with the concrete types its generic parameters are instantiated with.
+/// Non-generic functions have exactly one instance, with no arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub function: DefId,
+    pub arguments: Vec<(DefId, Type)>,
+}
+
+/// The callee of a call expression as seen by the type checker, before monomorphization.
+#[derive(Debug, Clone)]
+pub enum Callee {
+    // A function named directly, possibly with generic arguments in terms of the caller's
+    // own generic parameters.
+    Function(DefId, Vec<(DefId, Type)>),
+    // A method called through a trait bound, `value.method(...)` where `value: T` and `T: Trait`.
+    TraitMethod {
+        trait_: DefId,
+        method: String,
+        self_ty: Type,
+    },
+}
+
+/// The worklist of function instances reachable from the geode's entry point. Lowering takes
+/// instances off the worklist one at a time, and requests an instance for each call it lowers,
+/// so that every instance is lowered exactly once. Each instance is identified by the order it
+/// was first requested in, with the entry point first.
+#[derive(Debug)]
+pub struct Instances {
+    seen: Vec<Instance>,
+    pending: Vec<FunctionId>,
+}
+
+impl Instances {
+    pub fn new(entry: DefId) -> Self {
+        let main = Instance {
+            function: entry,
+            arguments: Vec::new(),
+        };
+
+        Self {
+            seen: vec![main],
+            pending: vec![0],
+        }
+    }
+
+    pub fn request(&mut self, instance: Instance) -> FunctionId {
+        match self.seen.iter().position(|seen| *seen == instance) {
+            Some(id) => id,
+            None => {
+                self.seen.push(instance);
+                self.pending.push(self.seen.len() - 1);
+                self.seen.len() - 1
+            }
+        }
+    }
+
+    pub fn next(&mut self) -> Option<(FunctionId, Instance)> {
+        let id = self.pending.pop()?;
+        Some((id, self.seen[id].clone()))
+    }
+
+    pub fn all(&self) -> &[Instance] {
+        &self.seen
+    }
+}
+
+/// Resolves a callee appearing within the body of `caller` to the concrete instance it calls.
+/// The caller's generic arguments are substituted into the callee first, which turns a call
+/// through a trait bound into a call on a concrete type that can be statically dispatched.
+pub fn resolve(
+    callee: &Callee,
+    caller: &Instance,
+    env: &TraitEnvironment,
+    span: Span,
+) -> Result<Instance, Vec<Error>> {
+    match callee {
+        Callee::Function(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|(param, ty)| (*param, ty.substitute(&caller.arguments)))
+                .collect::<Vec<_>>();
+
+            env.check_bounds(&arguments, span)?;
+
+            Ok(Instance {
+                function: *function,
+                arguments,
+            })
+        }
+        Callee::TraitMethod {
+            trait_,
+            method,
+            self_ty,
+        } => {
+            let self_ty = self_ty.substitute(&caller.arguments);
+
+            match env.dispatch(*trait_, method, &self_ty) {
+                Some(function) => Ok(Instance {
+                    function,
+                    arguments: Vec::new(),
+                }),
+                None => Err(vec![Error::new(
+                    span,
+                    format!(
+                        "The trait `{}` is not implemented for type `{self_ty}`",
+                        env.lookup(*trait_).name
+                    ),
+                )]),
+            }
+        }
+    }
+}
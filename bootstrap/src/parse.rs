@@ -1,6 +1,7 @@
 mod expr;
 mod item;
 mod lex;
+mod pattern;
 mod stmt;
 
 #[cfg(test)]
@@ -11,7 +12,9 @@ pub use crate::parse::item::item;
 pub use crate::parse::lex::*;
 
 use crate::error::Error;
-use crate::model::*;
+use crate::model::ast::Syntax;
+use crate::model::base::Item;
+use crate::model::token::*;
 use crate::span::{Span, Spanned};
 use crate::stream::{PeekFor, Stream};
 
@@ -45,16 +48,16 @@ impl PeekFor<KeywordToken, ParseResult<Spanned<Token>>> for Stream<Token> {
     }
 }
 
-impl PeekFor<IdentifierToken, ParseResult<Identifier>> for Stream<Token> {
+impl PeekFor<IdentifierToken, ParseResult<Spanned<String>>> for Stream<Token> {
     fn peek_for(
         &mut self,
         _kind: IdentifierToken,
         error_message: String,
-    ) -> ParseResult<Identifier> {
+    ) -> ParseResult<Spanned<String>> {
         match self.peek() {
             Token::Identifier(name) => {
                 let spanned = self.pop();
-                Ok(Identifier::new(name, spanned.span))
+                Ok(Spanned::new(name, spanned.span))
             }
             otherwise => Err(Error::new(
                 self.peek_span(),
@@ -68,7 +71,7 @@ impl PeekFor<IntegerLiteralToken, ParseResult<u32>> for Stream<Token> {
     fn peek_for(&mut self, _kind: IntegerLiteralToken, error_message: String) -> ParseResult<u32> {
         match self.peek() {
             Token::Literal(LiteralToken::Integer(val)) => {
-                let spanned = self.pop();
+                self.pop();
                 Ok(val)
             }
             otherwise => Err(Error::new(
@@ -79,45 +82,57 @@ impl PeekFor<IntegerLiteralToken, ParseResult<u32>> for Stream<Token> {
     }
 }
 
-pub fn geode(name: String, tokens: &mut Stream<Token>) -> ParseResult<Geode> {
+/// Parses the items of a whole file, up to its end. Out-of-line modules are left as
+/// `ModBody::File(None)`, to be found and parsed by `load`.
+pub fn items(tokens: &mut Stream<Token>) -> ParseResult<Vec<Item<Syntax>>> {
     let mut items = Vec::new();
 
     loop {
         match tokens.peek() {
-            Token::Eof => break Ok(Geode::new(name, items)),
+            Token::Eof => break Ok(items),
             _ => items.push(item(tokens)?),
         }
     }
 }
 
-pub fn path(tokens: &mut Stream<Token>) -> ParseResult<Spanned<Path>> {
-    let start = tokens.peek_span();
-
-    let is_global = tokens
-        .peek_for(BasicToken::Colon2, String::from(""))
-        .is_ok();
+pub fn path(tokens: &mut Stream<Token>) -> ParseResult<Spanned<Vec<Spanned<String>>>> {
+    // Path |= COLON2? IDENT (COLON2 IDENT)*
+    //
+    // Paths are resolved starting from the current module, falling back to the root module. A
+    // leading `::` starts from the root module alone, and is kept as an empty first segment so
+    // that the path still reads as written when its segments are joined.
 
+    let start = tokens.peek_span();
     let mut segments = Vec::new();
 
+    if let Ok(global) = tokens.peek_for(BasicToken::Colon2, String::from("")) {
+        segments.push(Spanned::new(String::new(), global.span));
+    }
+
     loop {
         match tokens.peek_for(IdentifierToken, String::from("")) {
             Ok(ident) => segments.push(ident),
             Err(_) => {
                 return Err(Error::new(
                     tokens.peek_span(),
-                    String::from("Expected identifier while parsing path expression"),
+                    format!(
+                        "Expected identifier while parsing path, but found {} instead",
+                        tokens.peek()
+                    ),
                 ))
             }
         };
 
-        match tokens.peek_for(BasicToken::Colon2, String::from("")) {
-            Ok(_) => { /* */ }
-            Err(_) => break,
+        // `Name::{...}` and `Name::*` continue a use tree rather than the path.
+        match (tokens.peek(), tokens.peek_nth(1)) {
+            (Token::Basic(BasicToken::Colon2), Token::Identifier(_)) => {
+                tokens.pop();
+            }
+            _ => break,
         };
     }
 
-    Ok(Spanned::new(
-        Path::new(is_global, segments),
-        Span::between(start, tokens.peek_span()),
-    ))
+    let end = segments.last().map_or(start, |last| last.span);
+
+    Ok(Spanned::new(segments, Span::between(start, end)))
 }
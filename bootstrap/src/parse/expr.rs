@@ -1,28 +1,23 @@
-#![allow(unused_variables)]
-
 use crate::error::Error;
-use crate::model::{
-    AssignmentOperator, Expression, ExpressionKind, KeywordToken, LiteralToken, Token,
-};
+use crate::model::ast::Syntax;
+use crate::model::base::{self, *};
+use crate::model::token::*;
 use crate::parse;
-use crate::parse::BasicToken;
-use crate::parse::{ExpressionWithoutBlock, ParseResult};
+use crate::parse::pattern::{pattern, restricted_pattern};
+use crate::parse::stmt;
+use crate::parse::ParseResult;
 use crate::span::{Span, Spanned};
 use crate::stream::{PeekFor, Stream};
 
-use crate::parse::IdentifierToken;
-
-use super::{
-    ArithmeticOrLogicalOperator, BlockExpression, ComparisonOperator, LazyBooleanOperator,
-};
-
-type ParserFunction = dyn Fn(Precedence, Expression, &mut Stream<Token>) -> ParseResult<Expression>;
+type ParserFunction =
+    dyn Fn(Precedence, Expression<Syntax>, &mut Stream<Token>) -> ParseResult<Expression<Syntax>>;
 
 #[derive(PartialEq, PartialOrd, Debug)]
 pub enum Precedence {
     Minimum,
     Return,
     Assignment,
+    Range,
     LazyOr,
     LazyAnd,
     Comparison,
@@ -47,7 +42,8 @@ impl Precedence {
             Minimum => Minimum,
             Return => Minimum,
             Assignment => Return,
-            LazyOr => Assignment,
+            Range => Assignment,
+            LazyOr => Range,
             LazyAnd => LazyOr,
             Comparison => LazyAnd,
             Or => Comparison,
@@ -81,19 +77,20 @@ impl Precedence {
             Basic(LParen) => Some((Self::FunctionCall, &call)),
             Basic(LBrack) => Some((Self::FunctionCall, &index)),
 
-            Basic(Asterisk) | Basic(FSlash) => Some((Self::Multiplicative, &arithmetic_or_logical)),
-            Basic(Plus) | Basic(Hyphen) => Some((Self::Additive, &arithmetic_or_logical)),
-            Basic(LAngle2) | Basic(RAngle2) => Some((Self::Shift, &arithmetic_or_logical)),
+            Basic(Asterisk) | Basic(FSlash) => Some((Self::Multiplicative, &binary)),
+            Basic(Plus) | Basic(Hyphen) => Some((Self::Additive, &binary)),
+            Basic(LAngle2) | Basic(RAngle2) => Some((Self::Shift, &binary)),
 
-            Basic(Ampersand) => Some((Self::And, &arithmetic_or_logical)),
-            Basic(Caret) => Some((Self::Xor, &arithmetic_or_logical)),
-            Basic(Bar) => Some((Self::Or, &arithmetic_or_logical)),
+            Basic(Ampersand) => Some((Self::And, &binary)),
+            Basic(Bar) => Some((Self::Or, &binary)),
 
             Basic(Equal2) | Basic(BangEqual) | Basic(LAngle) | Basic(RAngle)
-            | Basic(LAngleEqual) | Basic(RAngleEqual) => Some((Self::Comparison, &comparison)),
+            | Basic(LAngleEqual) | Basic(RAngleEqual) => Some((Self::Comparison, &binary)),
 
-            Basic(Ampersand2) => Some((Self::LazyAnd, &lazy_boolean)),
-            Basic(Bar2) => Some((Self::LazyOr, &lazy_boolean)),
+            Basic(Ampersand2) => Some((Self::LazyAnd, &binary)),
+            Basic(Bar2) => Some((Self::LazyOr, &binary)),
+
+            Basic(Period2) | Basic(Period2Equal) => Some((Self::Range, &range)),
 
             Basic(Equal)
             | Basic(PlusEqual)
@@ -112,20 +109,42 @@ impl Precedence {
     }
 }
 
-impl From<ArithmeticOrLogicalOperator> for Precedence {
-    fn from(value: ArithmeticOrLogicalOperator) -> Self {
-        use ArithmeticOrLogicalOperator::*;
+/// The binary operator that a token stands for, if any.
+fn binary_operator(token: &Token) -> Option<BinaryOperator> {
+    use BasicToken::*;
+    use BinaryOperator as Op;
+
+    let Token::Basic(basic) = token else {
+        return None;
+    };
 
-        match value {
-            Plus => Self::Additive,
-            Minus => Self::Additive,
-            Times => Self::Multiplicative,
-            Divide => Self::Multiplicative,
-            And => Self::And,
-            Or => Self::Or,
-            LShift => Self::Shift,
-            RShift => Self::Shift,
-        }
+    match basic {
+        Plus => Some(Op::Plus),
+        Hyphen => Some(Op::Minus),
+        Asterisk => Some(Op::Times),
+        FSlash => Some(Op::Divide),
+        Ampersand => Some(Op::And),
+        Bar => Some(Op::Or),
+        LAngle2 => Some(Op::LShift),
+        RAngle2 => Some(Op::RShift),
+        Equal2 => Some(Op::Eq),
+        BangEqual => Some(Op::Ne),
+        RAngle => Some(Op::Gt),
+        LAngle => Some(Op::Lt),
+        RAngleEqual => Some(Op::Ge),
+        LAngleEqual => Some(Op::Le),
+        Bar2 => Some(Op::LazyOr),
+        Ampersand2 => Some(Op::LazyAnd),
+        Equal => Some(Op::Equal),
+        PlusEqual => Some(Op::PlusEqual),
+        HyphenEqual => Some(Op::MinusEqual),
+        AsteriskEqual => Some(Op::TimesEqual),
+        FSlashEqual => Some(Op::DivideEqual),
+        AmpersandEqual => Some(Op::AndEqual),
+        BarEqual => Some(Op::OrEqual),
+        LAngle2Equal => Some(Op::LShiftEqual),
+        RAngle2Equal => Some(Op::RShiftEqual),
+        _ => None,
     }
 }
 
@@ -135,41 +154,69 @@ pub fn peek_expression(tokens: &Stream<Token>) -> bool {
     use LiteralToken::*;
     use Token::*;
 
-    match tokens.peek() {
-        Basic(LParen) => true,
-        Basic(Hyphen) => true,
-        Basic(Bang) => true,
-        Basic(Colon2) => true,
-        Basic(Ampersand) => true, // TODO: Not parsing borrow expressions yet
-        Basic(Asterisk) => true,  // TODO: Not parsing dereference expressions yet
-        Identifier(_) => true,
-        Keyword(Return) => true,
-        Keyword(Break) => true,
-        Keyword(Continue) => true,
-        Keyword(True) => true,
-        Keyword(False) => true,
-        Keyword(Unit) => true,
-        Literal(Integer(_)) => true,
-        Literal(Character(_)) => true,
-        Literal(String(_)) => true,
-        _ => false,
-    }
+    matches!(
+        tokens.peek(),
+        Basic(LParen)
+            | Basic(LBrack)
+            | Basic(Hyphen)
+            | Basic(Bang)
+            | Basic(Period2)
+            | Basic(Period2Equal)
+            | Basic(Ampersand)
+            | Basic(Asterisk)
+            | Basic(Colon2)
+            | Identifier(_)
+            | Keyword(SelfValue)
+            | Keyword(Asm)
+            | Keyword(If)
+            | Keyword(Case)
+            | Keyword(While)
+            | Keyword(For)
+            | Keyword(Loop)
+            | Label(_)
+            | Basic(LBrace)
+            | Keyword(Return)
+            | Keyword(Break)
+            | Keyword(Continue)
+            | Keyword(True)
+            | Keyword(False)
+            | Keyword(Unit)
+            | Literal(Integer(_))
+            | Literal(Character(_))
+            | Literal(String(_))
+    )
 }
 
-pub fn expression(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+pub fn expression(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     pratt(Precedence::Minimum, tokens)
 }
 
-pub fn block_expression(tokens: &mut Stream<Token>) -> ParseResult<Spanned<BlockExpression>> {
-    // <block-expression> |= LBRACE <statements>? RBRACE
-    //
-    // <statements> |= <statement>+
-    //              |  <statement>+ <expr-without-block>
-    //              |  <expr-without-block>
+/// Parses the expression beginning an expression statement. One with a block ends at its
+/// closing brace rather than continuing into operators, so that `if c {} *p = 1;` is two
+/// statements rather than a multiplication.
+pub fn statement_expression(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    match tokens.peek() {
+        Keyword(If) | Keyword(Case) | Keyword(While) | Keyword(For) | Keyword(Loop) | Label(_)
+        | Basic(LBrace) => prefix(tokens),
+        _ => expression(tokens),
+    }
+}
+
+pub fn block_expression(
+    tokens: &mut Stream<Token>,
+) -> ParseResult<Spanned<BlockExpression<Syntax>>> {
+    // <block-expression> |= LBRACE <statement>* <expression>? RBRACE
     //
-    // TODO: For now, just going to parse as LBRACE <statement>* RBRACE
+    // The final expression of a block may omit its semicolon, which makes it the value of the
+    // block. An expression with a block ends at its closing brace, so it is the value of the
+    // block only when nothing but the block's own closing brace follows it.
 
     use BasicToken::*;
+    use KeywordToken::*;
     use Token::*;
 
     let start = tokens.peek_for(
@@ -178,22 +225,60 @@ pub fn block_expression(tokens: &mut Stream<Token>) -> ParseResult<Spanned<Block
     )?;
 
     let mut statements = Vec::new();
+    let mut tail = None;
+
+    let end = loop {
+        if let Ok(rbrace) = tokens.peek_for(RBrace, String::from("")) {
+            break rbrace.span;
+        }
+
+        if tail.is_some() {
+            return Err(Error::new(
+                tokens.peek_span(),
+                format!(
+                    "Expected {Semicolon} or {RBrace} following expression, but found {} instead",
+                    tokens.peek()
+                ),
+            ));
+        }
 
-    loop {
         match tokens.peek() {
-            Basic(RBrace) => {
-                let end = tokens.pop();
-                break Ok(Spanned::new(
-                    statements,
-                    Span::between(start.span, end.span),
-                ));
+            Keyword(Let) | Keyword(Defer) | Basic(Semicolon) => {
+                statements.push(stmt::statement(tokens)?)
+            }
+            _ => {
+                let expression = statement_expression(tokens)?;
+
+                match tokens.peek() {
+                    Basic(RBrace) => tail = Some(expression),
+                    _ => {
+                        stmt::terminator(&expression, tokens)?;
+                        statements.push(Statement::Expression(expression));
+                    }
+                }
             }
-            _ => statements.push(parse::stmt::statement(tokens)?),
         }
-    }
+    };
+
+    let mut statements = statements.into_iter();
+    let block = match (statements.next(), tail) {
+        (None, None) => Vec::new(),
+        (None, Some(tail)) => vec![Some(Statements::Block(tail))],
+        (Some(first), tail) => vec![Some(Statements::Leading(first, statements.collect(), tail))],
+    };
+
+    Ok(Spanned::new(block, Span::between(start.span, end)))
+}
+
+fn without_block(expression: ExpressionWithoutBlock<Syntax>, span: Span) -> Expression<Syntax> {
+    Spanned::new(ExpressionKind::ExpressionWithoutBlock(expression), span)
+}
+
+fn with_block(expression: ExpressionWithBlock<Syntax>, span: Span) -> Expression<Syntax> {
+    Spanned::new(ExpressionKind::ExpressionWithBlock(expression), span)
 }
 
-fn pratt(precedence: Precedence, tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+fn pratt(precedence: Precedence, tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     let mut left = prefix(tokens)?;
 
     while precedence < Precedence::infix_precedence(tokens) {
@@ -203,19 +288,26 @@ fn pratt(precedence: Precedence, tokens: &mut Stream<Token>) -> ParseResult<Expr
     Ok(left)
 }
 
-fn prefix(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+fn prefix(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     use BasicToken::*;
     use KeywordToken as Kw;
     use LiteralToken as Lit;
     use Token::*;
 
-    type ExpressionParser = dyn Fn(&mut Stream<Token>) -> ParseResult<Expression>;
+    type ExpressionParser = dyn Fn(&mut Stream<Token>) -> ParseResult<Expression<Syntax>>;
 
     let maybe_parser: Option<&ExpressionParser> = match tokens.peek() {
         Basic(LParen) => Some(&group),
-        Basic(Hyphen) | Basic(Bang) => Some(&negate_operator),
+        Basic(Hyphen) | Basic(Bang) | Basic(Ampersand) | Basic(Asterisk) => Some(&prefix_operator),
         Basic(LBrack) => Some(&array),
+        Basic(Period2) | Basic(Period2Equal) => Some(&range_to),
         Identifier(_) | Basic(Colon2) => Some(&path),
+        Keyword(Kw::SelfValue) => Some(&self_value),
+        Keyword(Kw::Asm) => Some(&asm),
+        Keyword(Kw::If) => Some(&if_expression),
+        Keyword(Kw::Case) => Some(&case_expression),
+        Keyword(Kw::While) | Keyword(Kw::For) | Keyword(Kw::Loop) | Label(_) => Some(&looping),
+        Basic(LBrace) => Some(&block),
         Keyword(Kw::Return) | Keyword(Kw::Break) | Keyword(Kw::Continue) => Some(&unconditional),
         Keyword(Kw::True)
         | Keyword(Kw::False)
@@ -238,7 +330,7 @@ fn prefix(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
     }
 }
 
-fn infix(left: Expression, tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+fn infix(left: Expression<Syntax>, tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     // TODO: Idk why I need to clone here...
     match Precedence::infix_precedence_and_parser(&tokens.clone()) {
         None => Err(Error::new(
@@ -254,121 +346,69 @@ fn infix(left: Expression, tokens: &mut Stream<Token>) -> ParseResult<Expression
 
 fn assignment(
     precedence: Precedence,
-    left: Expression,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
-    let operator = match AssignmentOperator::try_from(tokens.peek()) {
-        Err(()) => {
-            return Err(Error::new(
-                tokens.peek_span(),
-                "Expected assignment operator".to_owned(),
-            ))
-        }
-        Ok(op) => op,
-    };
-
-    let basic = BasicToken::from(operator);
-    tokens.peek_for(
-        basic.clone(),
-        format!("Expected to find {basic} as assignment operator"),
-    )?;
-
-    let right = pratt(precedence.right_associative(), tokens)?;
-
-    let left_span = left.span;
-    let right_span = right.span;
-
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Assignment(
-            Box::new(left),
-            operator,
-            Box::new(right),
-        )),
-        Span::between(left_span, right_span),
-    ))
+) -> ParseResult<Expression<Syntax>> {
+    // Assignments are right associative, so `a = b = c` assigns `b = c` to `a`.
+    operator(precedence.right_associative(), left, tokens)
 }
 
-fn comparison(
+fn binary(
     precedence: Precedence,
-    left: Expression,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
-    let operator = match ComparisonOperator::try_from(tokens.peek()) {
-        Err(()) => {
-            return Err(Error::new(
-                tokens.peek_span(),
-                "Expected comparison operator".to_owned(),
-            ))
-        }
-        Ok(op) => op,
-    };
-
-    let basic = BasicToken::from(operator.clone());
-    tokens.peek_for(
-        basic.clone(),
-        format!("Expected to find {basic} as binary operator"),
-    )?;
-
-    let right = pratt(precedence, tokens)?;
-
-    let left_span = left.span;
-    let right_span = right.span;
-
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Comparison(
-            Box::new(left),
-            operator,
-            Box::new(right),
-        )),
-        Span::between(left_span, right_span),
-    ))
+) -> ParseResult<Expression<Syntax>> {
+    operator(precedence, left, tokens)
 }
 
-fn lazy_boolean(
-    precedence: Precedence,
-    left: Expression,
+fn operator(
+    right_precedence: Precedence,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
-    let operator = match LazyBooleanOperator::try_from(tokens.peek()) {
-        Err(()) => {
+) -> ParseResult<Expression<Syntax>> {
+    let operator = match binary_operator(&tokens.peek()) {
+        None => {
             return Err(Error::new(
                 tokens.peek_span(),
-                "Expected lazy boolean operator".to_owned(),
+                format!(
+                    "Expected binary operator, but found {} instead",
+                    tokens.peek()
+                ),
             ))
         }
-        Ok(op) => op,
+        Some(op) => op,
     };
+    tokens.pop();
 
-    let basic = BasicToken::from(operator.clone());
-    tokens.peek_for(
-        basic.clone(),
-        format!("Expected to find {basic} as binary operator"),
-    )?;
+    let right = pratt(right_precedence, tokens)?;
+    let span = Span::between(left.span, right.span);
 
-    let right = pratt(precedence, tokens)?;
+    Ok(without_block(
+        ExpressionWithoutBlock::Binary(Box::new(left), operator, Box::new(right), ()),
+        span,
+    ))
+}
 
+fn call(
+    _precedence: Precedence,
+    left: Expression<Syntax>,
+    tokens: &mut Stream<Token>,
+) -> ParseResult<Expression<Syntax>> {
     let left_span = left.span;
-    let right_span = right.span;
+    let (args, end) = arguments(left_span, tokens)?;
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::LazyBoolean(
-            Box::new(left),
-            operator,
-            Box::new(right),
-        )),
-        Span::between(left_span, right_span),
+    Ok(without_block(
+        ExpressionWithoutBlock::Call(Box::new(left), args, ()),
+        Span::between(left_span, end),
     ))
 }
 
-fn call(
-    _precedence: Precedence,
-    left: Expression,
+fn arguments(
+    start: Span,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
+) -> ParseResult<(Vec<Expression<Syntax>>, Span)> {
     use BasicToken::*;
 
-    let left_span = left.span;
-
     tokens.peek_for(
         LParen,
         format!("Expected to find {LParen} to begin call argument list"),
@@ -379,19 +419,11 @@ fn call(
 
     loop {
         match tokens.peek_for(RParen, String::from("")) {
-            Ok(rparen) => {
-                return Ok(Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Call(
-                        Box::new(left),
-                        args,
-                    )),
-                    Span::between(left_span, tokens.peek_span()),
-                ))
-            }
+            Ok(rparen) => return Ok((args, rparen.span)),
             Err(_) => {
                 if expect_rparen {
                     return Err(Error::new(
-                        Span::between(left_span, tokens.peek_span()),
+                        Span::between(start, tokens.peek_span()),
                         format!("Expected to find {RParen} to end call argument list"),
                     ));
                 }
@@ -409,9 +441,9 @@ fn call(
 
 fn index(
     _precedence: Precedence,
-    left: Expression,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
+) -> ParseResult<Expression<Syntax>> {
     use BasicToken::*;
 
     let left_span = left.span;
@@ -428,20 +460,17 @@ fn index(
         format!("Expected to find {RBrack} as part of index expression"),
     )?;
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Index(
-            Box::new(left),
-            Box::new(index),
-        )),
+    Ok(without_block(
+        ExpressionWithoutBlock::Index(Box::new(left), Box::new(index), ()),
         Span::between(left_span, rbrack.span),
     ))
 }
 
 fn field(
     _precedence: Precedence,
-    left: Expression,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
+) -> ParseResult<Expression<Syntax>> {
     use BasicToken::*;
 
     let left_span = left.span;
@@ -455,49 +484,90 @@ fn field(
         "Expected to find identifier as part of field expression".to_string(),
     )?;
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Field(Box::new(left), ident)),
-        Span::between(left_span, tokens.peek_span()),
-    ))
+    // A field immediately followed by an argument list is a method call, `value.method(args)`.
+    // This has to be decided here rather than by the call parser, because the receiver and
+    // the method name must stay together for method resolution during type checking.
+    match tokens.peek() {
+        Token::Basic(LParen) => {
+            let (args, end) = arguments(left_span, tokens)?;
+
+            Ok(without_block(
+                ExpressionWithoutBlock::MethodCall(Box::new(left), ident, args, ()),
+                Span::between(left_span, end),
+            ))
+        }
+        _ => {
+            let end = ident.span;
+
+            Ok(without_block(
+                ExpressionWithoutBlock::Field(Box::new(left), ident, ()),
+                Span::between(left_span, end),
+            ))
+        }
+    }
 }
 
-fn arithmetic_or_logical(
+fn range(
     precedence: Precedence,
-    left: Expression,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
-    let operator = match ArithmeticOrLogicalOperator::try_from(tokens.peek()) {
-        Err(()) => {
-            return Err(Error::new(
-                tokens.peek_span(),
-                "Expected arithmetic or logical binary operator".to_owned(),
-            ))
-        }
-        Ok(op) => op,
-    };
+) -> ParseResult<Expression<Syntax>> {
+    let left_span = left.span;
+    let (end, limits, end_span) = range_end(precedence, tokens)?;
 
-    let basic = BasicToken::from(operator.clone());
-    tokens.peek_for(
-        basic.clone(),
-        format!("Expected to find {basic} as binary operator"),
-    )?;
+    Ok(without_block(
+        ExpressionWithoutBlock::Range(Some(Box::new(left)), end, limits, ()),
+        Span::between(left_span, end_span),
+    ))
+}
 
-    let right = pratt(precedence, tokens)?;
+fn range_to(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    // A range without a start, `..end`, `..=end` or just `..`.
 
-    let left_span = left.span;
-    let right_span = right.span;
+    let start = tokens.peek_span();
+    let (end, limits, end_span) = range_end(Precedence::Range, tokens)?;
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::ArithmeticOrLogical(
-            Box::new(left),
-            operator,
-            Box::new(right),
-        )),
-        Span::between(left_span, right_span),
+    Ok(without_block(
+        ExpressionWithoutBlock::Range(None, end, limits, ()),
+        Span::between(start, end_span),
     ))
 }
 
-fn group(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+type RangeEnd = (Option<Box<Expression<Syntax>>>, RangeLimits, Span);
+
+fn range_end(precedence: Precedence, tokens: &mut Stream<Token>) -> ParseResult<RangeEnd> {
+    use BasicToken::*;
+
+    // The end of a half-open range is optional, as in `buf[2..]`, so it is only parsed when
+    // something that could begin an expression follows the operator. A block never begins the
+    // end, so that the body of `for i in 0.. { }` is not taken for it. A closed range must
+    // have an end, since `start..=` would include everything up to nothing in particular.
+
+    match tokens.peek_for(Period2Equal, String::from("")) {
+        Ok(_) => {
+            let right = pratt(precedence, tokens)?;
+            let right_span = right.span;
+            Ok((Some(Box::new(right)), RangeLimits::Closed, right_span))
+        }
+        Err(_) => {
+            let found = tokens.peek_for(
+                Period2,
+                format!("Expected to find {Period2} or {Period2Equal} as range operator"),
+            )?;
+
+            match peek_expression(tokens) && tokens.peek() != Token::Basic(LBrace) {
+                true => {
+                    let right = pratt(precedence, tokens)?;
+                    let right_span = right.span;
+                    Ok((Some(Box::new(right)), RangeLimits::HalfOpen, right_span))
+                }
+                false => Ok((None, RangeLimits::HalfOpen, found.span)),
+            }
+        }
+    }
+}
+
+fn group(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     use BasicToken::*;
 
     let lparen = tokens.peek_for(
@@ -511,28 +581,363 @@ fn group(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
         format!("Expected {RParen} to close parenthesized expression"),
     )?;
 
-    let expr_span = expr.span;
-
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Grouped(Box::new(expr))),
+    Ok(without_block(
+        ExpressionWithoutBlock::Grouped(Box::new(expr), ()),
         Span::between(lparen.span, rparen.span),
     ))
 }
 
-fn path(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+fn path(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     let spanned_path = parse::path(tokens)?;
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Path(spanned_path.item)),
+    Ok(without_block(
+        ExpressionWithoutBlock::Path(spanned_path.item, ()),
         spanned_path.span,
     ))
 }
 
+fn self_value(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use KeywordToken::*;
+
+    // Within a method, `self` is an ordinary single-segment path naming the receiver.
+    let found = tokens.peek_for(SelfValue, format!("Expected to find {SelfValue}"))?;
+
+    Ok(without_block(
+        ExpressionWithoutBlock::Path(vec![Spanned::new(String::from("self"), found.span)], ()),
+        found.span,
+    ))
+}
+
+fn block(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    let block = block_expression(tokens)?;
+
+    Ok(with_block(
+        ExpressionWithBlock::Block(block.item, ()),
+        block.span,
+    ))
+}
+
+fn if_expression(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use KeywordToken::*;
+
+    // <if-expression> |= IF <expression> (IS <pattern>)? <block-expression> <else-clause>?
+    //
+    // <else-clause> |= ELSE (<block-expression> | <if-expression>)
+
+    let start = tokens.peek_for(If, format!("Expected to find {If} expression"))?;
+    let condition = expression(tokens)?;
+
+    // The block follows the pattern directly, so a path followed by a brace is taken to be a
+    // path pattern followed by the block, rather than the beginning of a struct pattern.
+    let pattern = match tokens.peek_for(Is, String::from("")) {
+        Ok(_) => Some(restricted_pattern(tokens)?),
+        Err(_) => None,
+    };
+
+    let then = block_expression(tokens)?;
+
+    let (otherwise, end) = match tokens.peek_for(Else, String::from("")) {
+        Ok(_) => {
+            let otherwise = match tokens.peek() {
+                Token::Keyword(If) => if_expression(tokens)?,
+                _ => block(tokens)?,
+            };
+            let end = otherwise.span;
+            (Some(Box::new(otherwise)), end)
+        }
+        Err(_) => (None, then.span),
+    };
+
+    let span = Span::between(start.span, end);
+
+    match pattern {
+        None => Ok(with_block(
+            ExpressionWithBlock::If(Box::new(condition), then.item, otherwise, ()),
+            span,
+        )),
+        Some(pattern) => {
+            let then = with_block(ExpressionWithBlock::Block(then.item, ()), then.span);
+
+            Ok(with_block(
+                ExpressionWithBlock::IfIs(
+                    Box::new(condition),
+                    pattern,
+                    Box::new(then),
+                    otherwise,
+                    (),
+                ),
+                span,
+            ))
+        }
+    }
+}
+
+fn case_expression(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+
+    // <when-expression> |= WHEN <expression> LBRACE (<arm> (COMMA <arm>)* COMMA?)? RBRACE
+    //
+    // <arm> |= <pattern> (IF <expression>)? HEAVY_R_ARROW <expression>
+    //
+    // As with statements, the comma following an arm may be omitted when the arm's
+    // expression has a block.
+
+    let start = tokens.peek_for(Case, format!("Expected to find {Case} expression"))?;
+    let scrutinee = expression(tokens)?;
+
+    tokens.peek_for(
+        LBrace,
+        format!("Expected to find {LBrace} to begin {Case} arms"),
+    )?;
+
+    let mut arms = Vec::new();
+    let mut expect_rbrace = false;
+
+    let end = loop {
+        match tokens.peek_for(RBrace, String::from("")) {
+            Ok(rbrace) => break rbrace.span,
+            Err(_) => {
+                if expect_rbrace {
+                    return Err(Error::new(
+                        tokens.peek_span(),
+                        format!(
+                            "Expected to find {Comma} or {RBrace} following {Case} arm, but found {} instead",
+                            tokens.peek()
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let pattern = pattern(tokens)?;
+        let guard = match tokens.peek_for(If, String::from("")) {
+            Ok(_) => Some(expression(tokens)?),
+            Err(_) => None,
+        };
+
+        tokens.peek_for(
+            HeavyRArrow,
+            format!("Expected to find {HeavyRArrow} following {Case} arm pattern"),
+        )?;
+
+        let expression = expression(tokens)?;
+
+        expect_rbrace = tokens.peek_for(Comma, String::from("")).is_err()
+            && matches!(expression.item, ExpressionKind::ExpressionWithoutBlock(_));
+
+        arms.push(Arm {
+            pattern,
+            guard,
+            expression,
+        });
+    };
+
+    Ok(with_block(
+        ExpressionWithBlock::Case(Box::new(scrutinee), arms, ()),
+        Span::between(start.span, end),
+    ))
+}
+
+fn looping(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+
+    // <loop-expression> |= (LABEL COLON)? (<while-loop> | <for-loop> | <loop>)
+
+    let start = tokens.peek_span();
+
+    let label = match tokens.peek() {
+        Token::Label(name) => {
+            let label = Spanned::new(name, tokens.pop().span);
+            tokens.peek_for(
+                Colon,
+                format!("Expected to find {Colon} following loop label"),
+            )?;
+            Some(label)
+        }
+        _ => None,
+    };
+
+    match tokens.peek() {
+        Token::Keyword(While) => while_loop(start, label, tokens),
+        Token::Keyword(For) => for_loop(start, label, tokens),
+        Token::Keyword(Loop) => {
+            tokens.pop();
+            let body = block_expression(tokens)?;
+
+            Ok(with_block(
+                ExpressionWithBlock::Loop(label, body.item, ()),
+                Span::between(start, body.span),
+            ))
+        }
+        otherwise => Err(Error::new(
+            tokens.peek_span(),
+            format!("Expected to find {While}, {For}, or {Loop} following loop label, but found {otherwise} instead"),
+        )),
+    }
+}
+
+fn while_loop(
+    start: Span,
+    label: Option<Label>,
+    tokens: &mut Stream<Token>,
+) -> ParseResult<Expression<Syntax>> {
+    use KeywordToken::*;
+
+    tokens.peek_for(While, format!("Expected to find {While} loop"))?;
+    let condition = expression(tokens)?;
+    let body = block_expression(tokens)?;
+
+    Ok(with_block(
+        ExpressionWithBlock::While(label, Box::new(condition), body.item, ()),
+        Span::between(start, body.span),
+    ))
+}
+
+fn for_loop(
+    start: Span,
+    label: Option<Label>,
+    tokens: &mut Stream<Token>,
+) -> ParseResult<Expression<Syntax>> {
+    use KeywordToken::*;
+
+    tokens.peek_for(For, format!("Expected to find {For} loop"))?;
+    let name = tokens.peek_for(
+        IdentifierToken,
+        format!("Expected to find loop variable following {For}"),
+    )?;
+    tokens.peek_for(
+        In,
+        format!("Expected to find {In} following {For} loop variable"),
+    )?;
+    let iterable = expression(tokens)?;
+    let body = block_expression(tokens)?;
+
+    Ok(with_block(
+        ExpressionWithBlock::For(label, name, Box::new(iterable), body.item, ()),
+        Span::between(start, body.span),
+    ))
+}
+
+fn asm(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use LiteralToken as Lit;
+
+    // <asm-expression> |= ASM LBRACE STRING (COMMA <asm-entry>)* COMMA? RBRACE
+    //
+    // <asm-entry> |= IN IDENT EQUAL <expression>
+    //             |  "out" IDENT EQUAL <expression>
+    //             |  "clobber" STRING
+    //
+    // `out` and `clobber` are only meaningful here, so they are matched as identifiers rather
+    // than reserved as keywords.
+
+    let start = tokens.peek_for(Asm, format!("Expected to find {Asm} expression"))?;
+    tokens.peek_for(
+        LBrace,
+        format!("Expected to find {LBrace} to begin assembly block"),
+    )?;
+
+    let template = match tokens.peek() {
+        Token::Literal(Lit::String(template)) => Spanned::new(template, tokens.pop().span),
+        otherwise => {
+            return Err(Error::new(
+                tokens.peek_span(),
+                format!("Expected to find assembly template string, but found {otherwise} instead"),
+            ))
+        }
+    };
+
+    let mut operands = Vec::new();
+    let mut clobbers = Vec::new();
+
+    let end = loop {
+        if let Ok(rbrace) = tokens.peek_for(RBrace, String::from("")) {
+            break rbrace.span;
+        }
+
+        tokens.peek_for(
+            Comma,
+            format!("Expected to find {Comma} or {RBrace} following assembly template or operand"),
+        )?;
+
+        // Trailing comma
+        if let Ok(rbrace) = tokens.peek_for(RBrace, String::from("")) {
+            break rbrace.span;
+        }
+
+        let entry_start = tokens.peek_span();
+        let direction = match tokens.peek() {
+            Token::Keyword(In) => {
+                tokens.pop();
+                Direction::In
+            }
+            Token::Identifier(word) if word == "out" => {
+                tokens.pop();
+                Direction::Out
+            }
+            Token::Identifier(word) if word == "clobber" => {
+                tokens.pop();
+                match tokens.peek() {
+                    Token::Literal(Lit::String(register)) => {
+                        clobbers.push(Spanned::new(register, tokens.pop().span));
+                    }
+                    otherwise => {
+                        return Err(Error::new(
+                            tokens.peek_span(),
+                            format!("Expected to find clobbered register name as a string, but found {otherwise} instead"),
+                        ))
+                    }
+                }
+                continue;
+            }
+            otherwise => {
+                return Err(Error::new(
+                    tokens.peek_span(),
+                    format!("Expected to find {In}, out, or clobber in assembly block, but found {otherwise} instead"),
+                ))
+            }
+        };
+
+        let name = tokens.peek_for(
+            IdentifierToken,
+            "Expected to find assembly operand name".to_string(),
+        )?;
+        tokens.peek_for(
+            Equal,
+            format!("Expected to find {Equal} following assembly operand name"),
+        )?;
+        let expression = expression(tokens)?;
+        let span = Span::between(entry_start, expression.span);
+
+        operands.push(Operand {
+            direction,
+            name,
+            expression,
+            span,
+        });
+    };
+
+    Ok(without_block(
+        ExpressionWithoutBlock::Asm(
+            Assembly {
+                template,
+                operands,
+                clobbers,
+            },
+            (),
+        ),
+        Span::between(start.span, end),
+    ))
+}
+
 fn error_propagation(
-    precedence: Precedence,
-    left: Expression,
+    _precedence: Precedence,
+    left: Expression<Syntax>,
     tokens: &mut Stream<Token>,
-) -> ParseResult<Expression> {
+) -> ParseResult<Expression<Syntax>> {
     use BasicToken::*;
 
     let found = tokens.peek_for(
@@ -542,72 +947,112 @@ fn error_propagation(
 
     let left_span = left.span;
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::ErrorPropagation(Box::new(left))),
+    Ok(without_block(
+        ExpressionWithoutBlock::ErrorPropagation(Box::new(left), ()),
         Span::between(left_span, found.span),
     ))
 }
 
-fn unconditional(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+fn unconditional(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     use KeywordToken::*;
 
     let start = tokens.peek_span();
 
-    match tokens.peek_for(Return, String::from("")) {
-        Ok(_) => match peek_expression(tokens) {
+    if tokens.peek_for(Return, String::from("")).is_ok() {
+        return match peek_expression(tokens) {
             true => {
                 let expr = pratt(Precedence::Return, tokens)?;
-                return Ok(Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Return(Some(Box::new(
-                        expr,
-                    )))),
-                    Span::between(start, tokens.peek_span()),
-                ));
-            }
-            false => {
-                return Ok(Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Return(None)),
-                    Span::between(start, tokens.peek_span()),
+                let span = Span::between(start, expr.span);
+                Ok(without_block(
+                    ExpressionWithoutBlock::Return(Some(Box::new(expr)), ()),
+                    span,
                 ))
             }
-        },
-        Err(_) => { /* Try next unconditional expressions */ }
-    };
+            false => Ok(without_block(
+                ExpressionWithoutBlock::Return(None, ()),
+                start,
+            )),
+        };
+    }
+
+    // A label directly following `break` or `continue` always names the loop to exit, rather
+    // than beginning a labeled loop expression as the value of the `break`.
 
-    match tokens.peek_for(Break, String::from("")) {
-        Ok(_) => match peek_expression(tokens) {
+    if tokens.peek_for(Break, String::from("")).is_ok() {
+        let label = target_label(tokens);
+        let label_span = label.as_ref().map_or(start, |label| label.span);
+
+        return match peek_expression(tokens) {
             true => {
                 let expr = pratt(Precedence::Return, tokens)?;
-                return Ok(Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Break(Some(Box::new(
-                        expr,
-                    )))),
-                    Span::between(start, tokens.peek_span()),
-                ));
-            }
-            false => {
-                return Ok(Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Break(None)),
-                    Span::between(start, tokens.peek_span()),
-                ));
+                let span = Span::between(start, expr.span);
+                Ok(without_block(
+                    ExpressionWithoutBlock::Break(label, Some(Box::new(expr)), ()),
+                    span,
+                ))
             }
-        },
-        Err(_) => { /* Try next unconditional expressions */ }
-    };
+            false => Ok(without_block(
+                ExpressionWithoutBlock::Break(label, None, ()),
+                Span::between(start, label_span),
+            )),
+        };
+    }
 
     tokens.peek_for(Continue, format!("Expected to find {Continue} expression"))?;
+    let label = target_label(tokens);
+    let end = label.as_ref().map_or(start, |label| label.span);
 
-    Ok(Expression::new(
-        ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Continue),
-        Span::between(start, tokens.peek_span()),
+    Ok(without_block(
+        ExpressionWithoutBlock::Continue(label, ()),
+        Span::between(start, end),
     ))
 }
 
-fn negate_operator(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
-    todo!()
+fn target_label(tokens: &mut Stream<Token>) -> Option<Label> {
+    match tokens.peek() {
+        Token::Label(name) => Some(Spanned::new(name, tokens.pop().span)),
+        _ => None,
+    }
 }
 
-fn array(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+fn prefix_operator(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // <prefix-expression> |= (HYPHEN | BANG | ASTERISK | AMPERSAND MUT?) <expression>
+    //
+    // Prefix operators bind more tightly than any binary operator, so `-a * b` negates `a`,
+    // but less tightly than calls, fields and `?`, so `-a.b` negates the field.
+
+    let start = tokens.peek_span();
+
+    let operator = match tokens.pop().item {
+        Basic(Hyphen) => PrefixOperator::ArithmeticNegate,
+        Basic(Bang) => PrefixOperator::LogicalNegate,
+        Basic(Asterisk) => PrefixOperator::DeReference,
+        Basic(Ampersand) => match tokens.peek_for(Mut, String::from("")) {
+            Ok(_) => PrefixOperator::MutableBorrow,
+            Err(_) => PrefixOperator::Borrow,
+        },
+        otherwise => {
+            return Err(Error::new(
+                start,
+                format!("Expected to find prefix operator, but found {otherwise} instead"),
+            ))
+        }
+    };
+
+    let operand = pratt(Precedence::Unary, tokens)?;
+    let span = Span::between(start, operand.span);
+
+    Ok(without_block(
+        ExpressionWithoutBlock::Prefix(operator, Box::new(operand), ()),
+        span,
+    ))
+}
+
+fn array(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     use BasicToken::*;
 
     let start = tokens.peek_span();
@@ -622,9 +1067,9 @@ fn array(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
     loop {
         match tokens.peek_for(RBrack, String::from("")) {
             Ok(rbrack) => {
-                return Ok(Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Array(elements)),
-                    Span::between(start, tokens.peek_span()),
+                return Ok(without_block(
+                    ExpressionWithoutBlock::Array(elements, ()),
+                    Span::between(start, rbrack.span),
                 ));
             }
             Err(_) => {
@@ -639,6 +1084,24 @@ fn array(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
 
         elements.push(expression(tokens)?);
 
+        // A single element followed by a semicolon is a repeat expression, `[value; count]`.
+        if elements.len() == 1 && tokens.peek_for(Semicolon, String::from("")).is_ok() {
+            let count = expression(tokens)?;
+            let rbrack = tokens.peek_for(
+                RBrack,
+                format!("Expected to find {RBrack} to end array repeat expression"),
+            )?;
+
+            return Ok(without_block(
+                ExpressionWithoutBlock::Repeat(
+                    Box::new(elements.pop().unwrap()),
+                    Box::new(count),
+                    (),
+                ),
+                Span::between(start, rbrack.span),
+            ));
+        }
+
         match tokens.peek_for(Comma, String::from("")) {
             Ok(_) => { /* */ }
             Err(_) => expect_rbrack = true,
@@ -646,65 +1109,30 @@ fn array(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
     }
 }
 
-fn literal(tokens: &mut Stream<Token>) -> ParseResult<Expression> {
+pub fn literal(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
     use KeywordToken::*;
     use LiteralToken as Lit;
     use Token::*;
 
-    match tokens.peek() {
-        Literal(Lit::Integer(val)) => {
-            let start = tokens.pop().span;
-            Ok(Expression::new(
-                ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(val)),
-                Span::between(start, tokens.peek_span())
-            ))
-        },
-        Literal(Lit::Character(ch)) => {
-            let start = tokens.pop().span;
-            Ok(Expression::new(
-                ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Character(ch)),
-                Span::between(start, tokens.peek_span())
-            ))
-        },
-        Literal(Lit::String(st)) => {
-            let start = tokens.pop().span;
-            Ok(Expression::new(
-                ExpressionKind::WithoutBlock(ExpressionWithoutBlock::String(st)),
-                Span::between(start, tokens.peek_span())
+    let literal = match tokens.peek() {
+        Literal(Lit::Integer(val)) => base::Literal::Integer(val),
+        Literal(Lit::Character(ch)) => base::Literal::Character(ch),
+        Literal(Lit::String(st)) => base::Literal::String(st),
+        Keyword(True) => base::Literal::True,
+        Keyword(False) => base::Literal::False,
+        Keyword(Unit) => base::Literal::Unit,
+        otherwise => {
+            return Err(Error::new(
+                tokens.peek_span(),
+                format!("Expected to find integer literal, character literal, or string literal but found {otherwise} instead"),
             ))
-        },
-        Keyword(True) => {
-            let start = tokens.pop().span;
-            Ok(
-                Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::True),
-                    Span::between(start, tokens.peek_span())
-                )
-            )
-        },
-        Keyword(False) => {
-            let start = tokens.pop().span;
-            Ok(
-                Expression::new(
-                    ExpressionKind::WithoutBlock(ExpressionWithoutBlock::False),
-                    Span::between(start, tokens.peek_span())
-                )
-            )
-        },
-        Keyword(Unit) => {
-            let start = tokens.pop().span;
-            Ok(
-                Expression::new(
-                    ExpressionKind::WithoutBlock(
-                        ExpressionWithoutBlock::Unit,
-                    ),
-                    Span::between(start, tokens.peek_span())
-                )
-            )
         }
-        otherwise => Err(Error::new(
-            tokens.peek_span(),
-            format!("Expected to find integer literal, character literal, or string literal but found {otherwise} instead")
-        ))
-    }
+    };
+
+    let span = tokens.pop().span;
+
+    Ok(without_block(
+        ExpressionWithoutBlock::Literal(literal, ()),
+        span,
+    ))
 }
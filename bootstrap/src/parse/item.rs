@@ -1,49 +1,207 @@
-use std::iter;
-
-use crate::model::*;
+use crate::error::Error;
+use crate::model::ast::{Syntax, Type, TypeKind};
+use crate::model::base::*;
+use crate::model::token::*;
 use crate::parse;
-use crate::parse::*;
-use crate::span::Span;
-use crate::stream::Stream;
+use crate::parse::{block_expression, expression, literal, ParseResult};
+use crate::span::{Span, Spanned};
+use crate::stream::{PeekFor, Stream};
 
-pub fn item(tokens: &mut Stream<Token>) -> ParseResult<Item> {
+pub fn item(tokens: &mut Stream<Token>) -> ParseResult<Item<Syntax>> {
     use ItemKind::*;
     use KeywordToken as Kw;
     use Token::*;
 
-    match tokens.peek() {
+    let attributes = attributes(tokens)?;
+    let visibility = visibility(tokens)?;
+
+    let spanned = match tokens.peek() {
         Keyword(Kw::Mod) => {
-            mod_item(tokens).map(|spanned| Item::new(Mod(spanned.item), spanned.span))
+            mod_item(tokens).map(|spanned| Spanned::new(Mod(spanned.item), spanned.span))
         }
         Keyword(Kw::Use) => {
-            use_item(tokens).map(|spanned| Item::new(Use(spanned.item), spanned.span))
-        }
-        Keyword(Kw::Fn) => {
-            function_item(tokens).map(|spanned| Item::new(Function(spanned.item), spanned.span))
-        }
-        Keyword(Kw::Type) => {
-            type_alias_item(tokens).map(|spanned| Item::new(TypeAlias(spanned.item), spanned.span))
+            use_item(tokens).map(|spanned| Spanned::new(Use(spanned.item), spanned.span))
         }
+        Keyword(Kw::Fn) => function_item(tokens, FunctionContext::Free)
+            .map(|spanned| Spanned::new(Function(spanned.item), spanned.span)),
+        Keyword(Kw::Type) => type_alias_item(tokens)
+            .map(|spanned| Spanned::new(TypeAlias(spanned.item), spanned.span)),
         Keyword(Kw::Struct) => {
-            struct_item(tokens).map(|spanned| Item::new(Struct(spanned.item), spanned.span))
+            struct_item(tokens).map(|spanned| Spanned::new(Struct(spanned.item), spanned.span))
         }
         Keyword(Kw::Enum) => {
-            enum_item(tokens).map(|spanned| Item::new(Enum(spanned.item), spanned.span))
+            enum_item(tokens).map(|spanned| Spanned::new(Enum(spanned.item), spanned.span))
         }
         Keyword(Kw::Const) => {
-            const_item(tokens).map(|spanned| Item::new(Const(spanned.item), spanned.span))
+            const_item(tokens).map(|spanned| Spanned::new(Const(spanned.item), spanned.span))
         }
         Keyword(Kw::Static) => {
-            static_item(tokens).map(|spanned| Item::new(Static(spanned.item), spanned.span))
+            static_item(tokens).map(|spanned| Spanned::new(Static(spanned.item), spanned.span))
+        }
+        Keyword(Kw::Impl) => {
+            impl_item(tokens).map(|spanned| Spanned::new(Impl(spanned.item), spanned.span))
+        }
+        Keyword(Kw::Trait) => {
+            trait_item(tokens).map(|spanned| Spanned::new(Trait(spanned.item), spanned.span))
         }
         _ => Err(Error::new(
             tokens.peek_span(),
             "Expected to find item".to_string(),
         )),
+    }?;
+
+    Ok(attributed(attributes, visibility, spanned))
+}
+
+fn attributed(
+    attributes: Vec<Attribute<Syntax>>,
+    visibility: Option<Spanned<Visibility>>,
+    spanned: Spanned<ItemKind<Syntax>>,
+) -> Item<Syntax> {
+    let start = attributes
+        .first()
+        .map(|first| first.span)
+        .or(visibility.as_ref().map(|visibility| visibility.span));
+    let span = match start {
+        Some(start) => Span::between(start, spanned.span),
+        None => spanned.span,
+    };
+    let visibility = visibility.map_or(Visibility::Private, |visibility| visibility.item);
+
+    Item {
+        kind: spanned.item,
+        attributes,
+        visibility,
+        span,
+    }
+}
+
+fn visibility(tokens: &mut Stream<Token>) -> ParseResult<Option<Spanned<Visibility>>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+
+    // Visibility |= PUB (LPAREN "geode" RPAREN)?
+    //
+    // Declarations without a visibility are private to their module. `geode` is only a
+    // keyword in this position, so it is matched as an identifier.
+
+    let start = match tokens.peek_for(Pub, String::from("")) {
+        Ok(start) => start,
+        Err(_) => return Ok(None),
+    };
+
+    if tokens.peek_for(LParen, String::from("")).is_err() {
+        return Ok(Some(Spanned::new(Visibility::Public, start.span)));
+    }
+
+    let scope = tokens.peek_for(
+        IdentifierToken,
+        format!("Expected to find visibility scope following {Pub}{LParen}"),
+    )?;
+    if scope.item != "geode" {
+        return Err(Error::with_details(
+            scope.span,
+            format!("Unknown visibility scope `{}`", scope.item),
+            format!("The only visibility scope is `geode`, as in `{Pub}{LParen}geode{RParen}`"),
+        ));
+    }
+
+    let end = tokens.peek_for(
+        RParen,
+        format!("Expected to find {RParen} to conclude visibility scope"),
+    )?;
+
+    Ok(Some(Spanned::new(
+        Visibility::Geode,
+        Span::between(start.span, end.span),
+    )))
+}
+
+fn attributes(tokens: &mut Stream<Token>) -> ParseResult<Vec<Attribute<Syntax>>> {
+    use BasicToken::*;
+
+    // Attribute |= AT IDENT (LPAREN (Literal (COMMA Literal)* COMMA?)? RPAREN)?
+    //
+    // Attributes are parsed without regard for which attributes exist or what they may be
+    // applied to. That is checked once the whole geode has been parsed.
+
+    let mut attributes = Vec::new();
+
+    while let Ok(at) = tokens.peek_for(At, String::from("")) {
+        let name = tokens.peek_for(
+            IdentifierToken,
+            format!("Expected to find attribute name following {At}"),
+        )?;
+
+        let mut end = name.span;
+        let mut arguments = Vec::new();
+
+        if tokens.peek_for(LParen, String::from("")).is_ok() {
+            let mut expect_rparen = false;
+
+            loop {
+                match tokens.peek_for(RParen, String::from("")) {
+                    Ok(rparen) => {
+                        end = rparen.span;
+                        break;
+                    }
+                    Err(_) => {
+                        if expect_rparen {
+                            return Err(Error::new(
+                                tokens.peek_span(),
+                                format!(
+                                    "Expected to find {RParen} to conclude attribute {}'s argument list",
+                                    name.item
+                                ),
+                            ));
+                        }
+                    }
+                }
+
+                arguments.push(literal(tokens)?);
+
+                match tokens.peek_for(Comma, String::from("")) {
+                    Ok(_) => { /* */ }
+                    Err(_) => expect_rparen = true,
+                }
+            }
+        }
+
+        attributes.push(Attribute {
+            name,
+            arguments,
+            span: Span::between(at.span, end),
+        });
     }
+
+    Ok(attributes)
 }
 
-fn mod_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ModItem>> {
+fn associated_function(
+    tokens: &mut Stream<Token>,
+    context: FunctionContext,
+) -> ParseResult<Item<Syntax>> {
+    let attributes = attributes(tokens)?;
+    let visibility = visibility(tokens)?;
+
+    // A trait's functions are always exactly as visible as the trait itself.
+    if let (FunctionContext::Trait, Some(visibility)) = (&context, &visibility) {
+        return Err(Error::new(
+            visibility.span,
+            String::from("Trait functions cannot have their own visibility"),
+        ));
+    }
+
+    let function = function_item(tokens, context)?;
+
+    Ok(attributed(
+        attributes,
+        visibility,
+        Spanned::new(ItemKind::Function(function.item), function.span),
+    ))
+}
+
+fn mod_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ModItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -56,9 +214,13 @@ fn mod_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ModItem>> {
         format!("Expected module name to follow {Mod}"),
     )?;
 
+    // An out-of-line module's items are in a file of its own, which `load` finds and parses.
     match tokens.peek_for(Semicolon, String::from("")) {
         Ok(spanned) => Ok(Spanned::new(
-            ModItem::new(name, None),
+            ModItem {
+                name,
+                body: ModBody::File(None),
+            },
             Span::between(start.span, spanned.span),
         )),
         Err(_) => {
@@ -72,7 +234,10 @@ fn mod_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ModItem>> {
                 match tokens.peek_for(RBrace, String::from("")) {
                     Ok(spanned) => {
                         break Ok(Spanned::new(
-                            ModItem::new(name, Some(items)),
+                            ModItem {
+                                name,
+                                body: ModBody::Inline(items),
+                            },
                             Span::between(start.span, spanned.span),
                         ))
                     }
@@ -83,28 +248,127 @@ fn mod_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ModItem>> {
     }
 }
 
-fn use_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<UseItem>> {
-    todo!()
+fn use_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<UseTree<Syntax>>> {
+    use BasicToken::*;
+    use KeywordToken::*;
 
-    // use BasicToken::*;
-    // use KeywordToken::*;
+    let start = tokens.peek_for(
+        Use,
+        format!("Expected to find use item beginning with {Use}"),
+    )?;
 
-    // let start = tokens.peek_for(
-    //     Use,
-    //     format!("Expected to find use item beginning with {Use}"),
-    // )?;
+    let tree = use_tree(tokens)?;
 
-    // let tree = use_tree(tokens)?;
+    let end = tokens.peek_for(
+        Semicolon,
+        format!("Expected to find {Semicolon} to conclude use item"),
+    )?;
+
+    Ok(Spanned::new(tree, Span::between(start.span, end.span)))
+}
+
+fn use_tree(tokens: &mut Stream<Token>) -> ParseResult<UseTree<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // UseTree |= Path (AS IDENT)?
+    //         |  (Path COLON2)? ASTERISK
+    //         |  (Path COLON2)? LBRACE (UseTree (COMMA UseTree)* COMMA?)? RBRACE
+    //
+    // The prefix of a nested tree is shared by each of the trees within it, and may be empty
+    // within a nested tree, as in `use foo::{bar, {baz, qux}}`.
+
+    let prefix = match tokens.peek() {
+        Identifier(_) | Basic(Colon2) => {
+            let prefix = parse::path(tokens)?.item;
+
+            match tokens.peek() {
+                Basic(Colon2) => {
+                    tokens.pop();
+                }
+                _ => {
+                    let alias = match tokens.peek_for(As, String::from("")) {
+                        Ok(_) => Some(tokens.peek_for(
+                            IdentifierToken,
+                            format!("Expected to find identifier following {As}"),
+                        )?),
+                        Err(_) => None,
+                    };
+
+                    return Ok(UseTree {
+                        prefix,
+                        kind: UseTreeKind::Simple(alias),
+                    });
+                }
+            }
+
+            prefix
+        }
+        _ => Vec::new(),
+    };
+
+    match tokens.peek() {
+        Basic(Asterisk) => {
+            tokens.pop();
+            Ok(UseTree {
+                prefix,
+                kind: UseTreeKind::Glob,
+            })
+        }
+        Basic(LBrace) => {
+            let start = tokens.pop();
+            let mut trees = Vec::new();
+            let mut expect_rbrace = false;
 
-    // let end = tokens.peek_for(
-    //     Semicolon,
-    //     format!("Expected to find {Semicolon} to conclude use item"),
-    // )?;
+            let end = loop {
+                match tokens.peek_for(RBrace, String::from("")) {
+                    Ok(rbrace) => break rbrace.span,
+                    Err(_) => {
+                        if expect_rbrace {
+                            return Err(Error::new(
+                                tokens.peek_span(),
+                                format!("Expected to find {RBrace} to conclude nested use tree"),
+                            ));
+                        }
+                    }
+                }
+
+                trees.push(use_tree(tokens)?);
+
+                match tokens.peek_for(Comma, String::from("")) {
+                    Ok(_) => { /* */ }
+                    Err(_) => expect_rbrace = true,
+                }
+            };
 
-    // Ok(Spanned::new(tree, Span::between(start.span, end.span)))
+            Ok(UseTree {
+                prefix,
+                kind: UseTreeKind::Nested(trees, Span::between(start.span, end)),
+            })
+        }
+        otherwise => Err(Error::new(
+            tokens.peek_span(),
+            format!(
+                "Expected identifier, {Asterisk}, or {LBrace} as part of use tree, but found {otherwise} instead"
+            ),
+        )),
+    }
 }
 
-fn function_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<FunctionItem>> {
+/// Where a function item is declared, which determines whether it may take a receiver and
+/// whether it has a body.
+#[derive(PartialEq)]
+enum FunctionContext {
+    Free,
+    Impl,
+    Trait,
+}
+
+fn function_item(
+    tokens: &mut Stream<Token>,
+    context: FunctionContext,
+) -> ParseResult<Spanned<FunctionItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -118,6 +382,11 @@ fn function_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<FunctionItem
         format!("Expected to find function item identifier following {Fn}"),
     )?;
 
+    let generics = match tokens.peek() {
+        Token::Basic(LBrack) => generic_parameters(tokens)?,
+        _ => Vec::new(),
+    };
+
     tokens.peek_for(
         LParen,
         format!(
@@ -126,8 +395,27 @@ fn function_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<FunctionItem
         ),
     )?;
 
+    let receiver = match receiver(tokens)? {
+        Some(spanned) if context == FunctionContext::Free => {
+            return Err(Error::with_details(
+                spanned.span,
+                format!(
+                    "Function item {} cannot take a {SelfValue} receiver",
+                    function_name.item
+                ),
+                format!(
+                    "Only functions declared within an {Impl} or {Trait} block may take a receiver"
+                ),
+            ))
+        }
+        maybe_receiver => maybe_receiver,
+    };
+
     let mut parameters = Vec::new();
-    let mut expect_rparen = false;
+    let mut expect_rparen = match receiver {
+        Some(_) => tokens.peek_for(Comma, String::from("")).is_err(),
+        None => false,
+    };
 
     loop {
         match tokens.peek_for(RParen, String::from("")) {
@@ -161,20 +449,244 @@ fn function_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<FunctionItem
         _ => None,
     };
 
-    let body = block_expression(tokens)?;
+    // Functions declared within a trait are signatures only. Their bodies are provided by
+    // each implementation of the trait.
+    let (body, end) = match context {
+        FunctionContext::Trait => {
+            let end = tokens.peek_for(
+                Semicolon,
+                format!(
+                    "Expected to find {Semicolon} to conclude method signature {} within {Trait} item",
+                    function_name.item
+                ),
+            )?;
+            (None, end.span)
+        }
+        _ => {
+            let body = block_expression(tokens)?;
+            (Some(body.item), body.span)
+        }
+    };
 
     Ok(Spanned::new(
-        FunctionItem::new(
-            function_name,
+        FunctionItem {
+            name: function_name,
+            generics,
+            receiver,
             parameters,
             return_type,
-            Some(body.item), // TODO: Make body optional
-        ),
-        Span::between(start.span, body.span),
+            body,
+        },
+        Span::between(start.span, end),
     ))
 }
 
-fn type_alias_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<TypeAliasItem>> {
+fn generic_parameters(tokens: &mut Stream<Token>) -> ParseResult<Vec<GenericParameter<Syntax>>> {
+    use BasicToken::*;
+
+    // GenericParameters |= LBRACK GenericParameter (COMMA GenericParameter)* COMMA? RBRACK
+    //
+    // GenericParameter |= IDENT (COLON Path (PLUS Path)*)?
+
+    tokens.peek_for(
+        LBrack,
+        format!("Expected to find {LBrack} to begin generic parameter list"),
+    )?;
+
+    let mut parameters = Vec::new();
+    let mut expect_rbrack = false;
+
+    loop {
+        match tokens.peek_for(RBrack, String::from("")) {
+            Ok(_) => break Ok(parameters),
+            Err(_) => {
+                if expect_rbrack {
+                    return Err(Error::new(
+                        tokens.peek_span(),
+                        format!("Expected to find {RBrack} to conclude generic parameter list"),
+                    ));
+                }
+            }
+        }
+
+        let name = tokens.peek_for(
+            IdentifierToken,
+            "Expected to find generic parameter name".to_string(),
+        )?;
+        let mut span = name.span;
+        let mut bounds = Vec::new();
+
+        if tokens.peek_for(Colon, String::from("")).is_ok() {
+            loop {
+                let bound = parse::path(tokens)?;
+                span = Span::between(span, bound.span);
+                bounds.push(bound.item);
+
+                match tokens.peek_for(Plus, String::from("")) {
+                    Ok(_) => { /* */ }
+                    Err(_) => break,
+                }
+            }
+        }
+
+        parameters.push(GenericParameter { name, bounds, span });
+
+        match tokens.peek_for(Comma, String::from("")) {
+            Ok(_) => { /* */ }
+            Err(_) => expect_rbrack = true,
+        }
+    }
+}
+
+fn receiver(tokens: &mut Stream<Token>) -> ParseResult<Option<Spanned<Receiver>>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // Receiver |= SELF
+    //          |  AMPERSAND MUT? SELF
+    //
+    // Parameters never begin with an ampersand, so seeing one here is enough to commit to
+    // parsing a by-reference receiver.
+
+    match tokens.peek() {
+        Keyword(SelfValue) => {
+            let span = tokens.pop().span;
+            Ok(Some(Spanned::new(Receiver::Value, span)))
+        }
+        Basic(Ampersand) => {
+            let start = tokens.pop();
+            let mutability = match tokens.peek_for(Mut, String::from("")) {
+                Ok(_) => Mutability::Mutable,
+                Err(_) => Mutability::Immutable,
+            };
+            let end = tokens.peek_for(
+                SelfValue,
+                format!("Expected to find {SelfValue} following {Ampersand} in receiver"),
+            )?;
+
+            Ok(Some(Spanned::new(
+                Receiver::Reference(mutability),
+                Span::between(start.span, end.span),
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn impl_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ImplItem<Syntax>>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    let start = tokens.peek_for(
+        Impl,
+        format!("Expected to find impl item beginning with {Impl}"),
+    )?;
+
+    // Both `impl Type` and `impl Trait for Type` begin with something that parses as a type,
+    // so only once `for` is seen is it known that a trait was named.
+    let first = type_repr(tokens)?;
+
+    let (trait_, ty) = match tokens.peek_for(For, String::from("")) {
+        Ok(_) => match first.item {
+            TypeKind::Path(path) => (Some(path), type_repr(tokens)?),
+            _ => {
+                return Err(Error::new(
+                    first.span,
+                    format!("Expected to find trait path preceding {For} in {Impl} item"),
+                ))
+            }
+        },
+        Err(_) => (None, first),
+    };
+
+    tokens.peek_for(
+        LBrace,
+        format!("Expected to find {LBrace} following {Impl} item's type"),
+    )?;
+
+    let mut functions = Vec::new();
+
+    loop {
+        match tokens.peek() {
+            Basic(RBrace) => {
+                let end = tokens.pop();
+                break Ok(Spanned::new(
+                    ImplItem {
+                        trait_,
+                        ty,
+                        functions,
+                    },
+                    Span::between(start.span, end.span),
+                ));
+            }
+            Keyword(Fn) | Keyword(Pub) | Basic(At) => {
+                functions.push(associated_function(tokens, FunctionContext::Impl)?)
+            }
+            otherwise => {
+                break Err(Error::new(
+                    tokens.peek_span(),
+                    format!(
+                        "Expected to find function item or {RBrace} within {Impl} item, but found {otherwise} instead"
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+fn trait_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<TraitItem<Syntax>>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    let start = tokens.peek_for(
+        Trait,
+        format!("Expected to find trait item beginning with {Trait}"),
+    )?;
+
+    let name = tokens.peek_for(
+        IdentifierToken,
+        format!("Expected to find trait item identifier following {Trait}"),
+    )?;
+
+    tokens.peek_for(
+        LBrace,
+        format!(
+            "Expected to find {LBrace} following trait item identifier {}",
+            name.item
+        ),
+    )?;
+
+    let mut functions = Vec::new();
+
+    loop {
+        match tokens.peek() {
+            Basic(RBrace) => {
+                let end = tokens.pop();
+                break Ok(Spanned::new(
+                    TraitItem { name, functions },
+                    Span::between(start.span, end.span),
+                ));
+            }
+            Keyword(Fn) | Keyword(Pub) | Basic(At) => {
+                functions.push(associated_function(tokens, FunctionContext::Trait)?)
+            }
+            otherwise => {
+                break Err(Error::new(
+                    tokens.peek_span(),
+                    format!(
+                        "Expected to find method signature or {RBrace} within trait item `{}`, but found {otherwise} instead",
+                        name.item
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+fn type_alias_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<TypeAliasItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -207,12 +719,12 @@ fn type_alias_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<TypeAliasI
     )?;
 
     Ok(Spanned::new(
-        TypeAliasItem::new(name, ty),
+        TypeAliasItem { name, ty },
         Span::between(start.span, end.span),
     ))
 }
 
-fn struct_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StructItem>> {
+fn struct_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StructItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -236,8 +748,8 @@ fn struct_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StructItem>> {
 
     let mut fields = Vec::new();
 
-    while let Token::Identifier(_) = tokens.peek() {
-        let fd = field(tokens)?;
+    while let Token::Identifier(_) | Token::Basic(At) | Token::Keyword(Pub) = tokens.peek() {
+        let fd = field(tokens, Visibility::Private)?;
 
         fields.push(fd);
 
@@ -256,12 +768,12 @@ fn struct_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StructItem>> {
     )?;
 
     Ok(Spanned::new(
-        StructItem::new(name, fields),
+        StructItem { name, fields },
         Span::between(start.span, end.span),
     ))
 }
 
-fn enum_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<EnumItem>> {
+fn enum_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<EnumItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -275,6 +787,11 @@ fn enum_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<EnumItem>> {
         format!("Expected to find enum item identifier following {Enum}"),
     )?;
 
+    let generics = match tokens.peek() {
+        Token::Basic(LBrack) => generic_parameters(tokens)?,
+        _ => Vec::new(),
+    };
+
     tokens.peek_for(
         LBrace,
         format!(
@@ -285,7 +802,7 @@ fn enum_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<EnumItem>> {
 
     let mut variants = Vec::new();
 
-    while let Token::Identifier(_) = tokens.peek() {
+    while let Token::Identifier(_) | Token::Basic(At) | Token::Keyword(Pub) = tokens.peek() {
         let vt = variant(tokens)?;
         variants.push(vt);
 
@@ -304,12 +821,16 @@ fn enum_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<EnumItem>> {
     )?;
 
     Ok(Spanned::new(
-        EnumItem::new(name, variants),
+        EnumItem {
+            name,
+            generics,
+            variants,
+        },
         Span::between(start.span, end.span),
     ))
 }
 
-fn const_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ConstItem>> {
+fn const_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ConstItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -320,7 +841,7 @@ fn const_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ConstItem>> {
 
     let name = tokens.peek_for(
         IdentifierToken,
-        format!("Expected to find const item identifier following {Type}"),
+        format!("Expected to find const item identifier following {Const}"),
     )?;
 
     tokens.peek_for(
@@ -352,23 +873,23 @@ fn const_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<ConstItem>> {
     )?;
 
     Ok(Spanned::new(
-        ConstItem::new(name, ty, value),
+        ConstItem { name, ty, value },
         Span::between(start.span, end.span),
     ))
 }
 
-fn static_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StaticItem>> {
+fn static_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StaticItem<Syntax>>> {
     use BasicToken::*;
     use KeywordToken::*;
 
     let start = tokens.peek_for(
         Static,
-        format!("Expected to find static item beginning with {Const}"),
+        format!("Expected to find static item beginning with {Static}"),
     )?;
 
     let name = tokens.peek_for(
         IdentifierToken,
-        format!("Expected to find static item identifier following {Type}"),
+        format!("Expected to find static item identifier following {Static}"),
     )?;
 
     tokens.peek_for(
@@ -400,150 +921,38 @@ fn static_item(tokens: &mut Stream<Token>) -> ParseResult<Spanned<StaticItem>> {
     )?;
 
     Ok(Spanned::new(
-        StaticItem::new(name, ty, value),
+        StaticItem { name, ty, value },
         Span::between(start.span, end.span),
     ))
 }
 
-// fn use_tree(tokens: &mut Stream<Token>) -> ParseResult<UseTree> {
-//     use BasicToken::*;
-//     use KeywordToken::*;
-//     use Token::*;
-
-//     // UseTree |= Path COLON2 ASTERISK
-//     //         |  Path COLON2 LBRACE UseTree (COMMA UseTree)* COMMA? RBRACE
-//     //         |  Path (AS IDENT)?
-//     //
-//     // With some left factoring...
-//     //
-//     // UseTree |= Path UseTreeTail
-//     //
-//     // UseTreeTail |= COLON2 ASTERISK
-//     //             |  COLON2 LBRACE UseTree (COMMA UseTree)* COMMA? RBRACE
-//     //             |  AS IDENT
-//     //             |  EPSILON
-//     //
-
-//     let mut segments = Vec::new();
-//     segments.push(tokens.peek_for(
-//         IdentifierToken,
-//         format!("Expected identifier while parsing use path"),
-//     )?);
-
-//     if let Basic(Colon2) = tokens.peek() {
-//         tokens.pop();
-
-//         loop {
-//             match tokens.peek() {
-//                 Identifier(name) => {
-//                     let popped = tokens.pop();
-//                     segments.push(ast::Identifier::new(name, popped.span));
-//                 }
-//                 Basic(Asterisk) => {
-//                     tokens.pop();
-//                     break Ok(UseTree::Wildcard(UsePath::new(
-//                         segments
-//                             .pop()
-//                             .expect("Use tree parser should always produce at least one name..."),
-//                         segments,
-//                     )));
-//                 }
-//                 Basic(LBrace) => {
-//                     tokens.pop();
-//                     let mut trees = vec![use_tree(tokens)?];
-//                     loop {
-//                         if let Basic(Comma) = tokens.peek() {
-//                             tokens.pop();
-//                             trees.push(use_tree(tokens)?);
-//                         } else {
-//                             break;
-//                         }
-//                     }
-//                     let _ = tokens.peek_for(Comma, String::from(""));
-//                     tokens.peek_for(RBrace, format!("Expected {RBrace} to conclude use tree"))?;
-//                     break Ok(UseTree::Children(
-//                         UsePath::new(
-//                             segments.pop().expect(
-//                                 "Use tree parser should always produce at least one name...",
-//                             ),
-//                             segments,
-//                         ),
-//                         trees,
-//                     ));
-//                 },
-//                 otherwise => break Err(
-//                     Error::new(
-//                         tokens.peek_span(),
-//                         format!(
-//                             "Expected identifier, {Asterisk}, or {LBrace} as part of use tree, but found {otherwise} instead"
-//                         )
-//                     )
-//                 )
-//             }
-
-//             match tokens.peek() {
-//                 Basic(Colon2) => {
-//                     tokens.pop();
-//                 }
-//                 Keyword(As) => {
-//                     tokens.pop();
-//                     let name = tokens.peek_for(
-//                         IdentifierToken,
-//                         format!("Expected identifier following {As}"),
-//                     )?;
-//                     break Ok(UseTree::Rebind(
-//                         UsePath::new(segments.pop().unwrap(), segments),
-//                         name,
-//                     ));
-//                 }
-//                 _epsilon => {
-//                     break Ok(UseTree::Import(UsePath::new(
-//                         segments.pop().unwrap(),
-//                         segments,
-//                     )))
-//                 }
-//             }
-//         }
-//     } else {
-//         match tokens.peek() {
-//             Keyword(As) => {
-//                 tokens.pop();
-//                 let name = tokens.peek_for(
-//                     IdentifierToken,
-//                     format!("Expected identifier following {As}"),
-//                 )?;
-//                 Ok(UseTree::Rebind(
-//                     UsePath::new(segments.pop().unwrap(), segments),
-//                     name,
-//                 ))
-//             }
-//             _epsilon => Ok(UseTree::Import(UsePath::new(
-//                 segments.pop().unwrap(),
-//                 segments,
-//             ))),
-//         }
-//     }
-// }
-
-pub fn type_repr(tokens: &mut Stream<Token>) -> ParseResult<TypeRepr> {
+pub fn type_repr(tokens: &mut Stream<Token>) -> ParseResult<Type> {
     use BasicToken::*;
     use KeywordToken::*;
     use Token::*;
 
     match tokens.peek() {
-        Keyword(U8) => Ok(TypeRepr::new(TypeReprKind::U8, tokens.pop().span)),
-        Keyword(I8) => Ok(TypeRepr::new(TypeReprKind::I8, tokens.pop().span)),
-        Keyword(U16) => Ok(TypeRepr::new(TypeReprKind::U16, tokens.pop().span)),
-        Keyword(I16) => Ok(TypeRepr::new(TypeReprKind::I16, tokens.pop().span)),
-        Keyword(U32) => Ok(TypeRepr::new(TypeReprKind::U32, tokens.pop().span)),
-        Keyword(I32) => Ok(TypeRepr::new(TypeReprKind::I32, tokens.pop().span)),
-        Keyword(Bool) => Ok(TypeRepr::new(TypeReprKind::Bool, tokens.pop().span)),
-        Keyword(Char) => Ok(TypeRepr::new(TypeReprKind::Char, tokens.pop().span)),
-        Keyword(Str) => Ok(TypeRepr::new(TypeReprKind::Str, tokens.pop().span)),
-        Keyword(Unit) => Ok(TypeRepr::new(TypeReprKind::Unit, tokens.pop().span)),
+        Keyword(U8) => Ok(Spanned::new(TypeKind::U8, tokens.pop().span)),
+        Keyword(I8) => Ok(Spanned::new(TypeKind::I8, tokens.pop().span)),
+        Keyword(U16) => Ok(Spanned::new(TypeKind::U16, tokens.pop().span)),
+        Keyword(I16) => Ok(Spanned::new(TypeKind::I16, tokens.pop().span)),
+        Keyword(U32) => Ok(Spanned::new(TypeKind::U32, tokens.pop().span)),
+        Keyword(I32) => Ok(Spanned::new(TypeKind::I32, tokens.pop().span)),
+        Keyword(Bool) => Ok(Spanned::new(TypeKind::Bool, tokens.pop().span)),
+        Keyword(Char) => Ok(Spanned::new(TypeKind::Char, tokens.pop().span)),
+        Keyword(Str) => Ok(Spanned::new(TypeKind::Str, tokens.pop().span)),
+        Keyword(Unit) => Ok(Spanned::new(TypeKind::Unit, tokens.pop().span)),
         Basic(LBrack) => {
             let start = tokens.pop();
             let element_type = type_repr(tokens)?;
+
+            if let Ok(end) = tokens.peek_for(RBrack, String::from("")) {
+                return Ok(Spanned::new(
+                    TypeKind::Slice(Box::new(element_type)),
+                    Span::between(start.span, end.span),
+                ));
+            }
+
             tokens.peek_for(
                 Semicolon,
                 format!("Expected to find {Semicolon} following array type literal's element type"),
@@ -557,8 +966,8 @@ pub fn type_repr(tokens: &mut Stream<Token>) -> ParseResult<TypeRepr> {
                 format!("Expected to find {RBrack} to conclude array type literal"),
             )?;
 
-            Ok(TypeRepr::new(
-                TypeReprKind::Array(Box::new(element_type), size),
+            Ok(Spanned::new(
+                TypeKind::Array(Box::new(element_type), size),
                 Span::between(start.span, end.span),
             ))
         }
@@ -571,11 +980,56 @@ pub fn type_repr(tokens: &mut Stream<Token>) -> ParseResult<TypeRepr> {
             let ty = type_repr(tokens)?;
             let ty_span = ty.span;
 
-            Ok(TypeRepr::new(
-                TypeReprKind::Reference(mutability, Box::new(ty)),
+            Ok(Spanned::new(
+                TypeKind::Reference(mutability, Box::new(ty)),
                 Span::between(start.span, ty_span),
             ))
         }
+        Keyword(Fn) => {
+            let start = tokens.pop();
+            tokens.peek_for(
+                LParen,
+                format!("Expected to find {LParen} following {Fn} in function type literal"),
+            )?;
+
+            let mut parameters = Vec::new();
+            let mut expect_rparen = false;
+
+            let rparen = loop {
+                match tokens.peek_for(RParen, String::from("")) {
+                    Ok(rparen) => break rparen,
+                    Err(_) => {
+                        if expect_rparen {
+                            return Err(Error::new(
+                                tokens.peek_span(),
+                                format!("Expected to find {RParen} to conclude function type literal's parameter list"),
+                            ));
+                        }
+                    }
+                }
+
+                parameters.push(type_repr(tokens)?);
+
+                match tokens.peek_for(Comma, String::from("")) {
+                    Ok(_) => { /* */ }
+                    Err(_) => expect_rparen = true,
+                }
+            };
+
+            let (return_type, end) = match tokens.peek_for(LightRArrow, String::from("")) {
+                Ok(_) => {
+                    let ty = type_repr(tokens)?;
+                    let ty_span = ty.span;
+                    (Some(Box::new(ty)), ty_span)
+                }
+                Err(_) => (None, rparen.span),
+            };
+
+            Ok(Spanned::new(
+                TypeKind::Function(parameters, return_type),
+                Span::between(start.span, end),
+            ))
+        }
         Basic(LParen) => {
             let start = tokens.pop();
             let ty = type_repr(tokens)?;
@@ -584,16 +1038,49 @@ pub fn type_repr(tokens: &mut Stream<Token>) -> ParseResult<TypeRepr> {
                 format!("Expected to find {RParen} to conclude parenthesized type literal"),
             )?;
 
-            Ok(TypeRepr::new(
-                TypeReprKind::Parenthesized(Box::new(ty)),
+            Ok(Spanned::new(
+                TypeKind::Parenthesized(Box::new(ty)),
                 Span::between(start.span, end.span),
             ))
         }
-        Basic(Colon2) | Identifier(_) => {
+        Identifier(_) | Basic(Colon2) => {
             let spanned_path = parse::path(tokens)?;
-            Ok(TypeRepr::new(
-                TypeReprKind::Path(spanned_path.item),
-                spanned_path.span,
+
+            // A generic type is given its type arguments in brackets, as in `Option[u8]`.
+            if tokens.peek_for(LBrack, String::from("")).is_err() {
+                return Ok(Spanned::new(
+                    TypeKind::Path(spanned_path.item),
+                    spanned_path.span,
+                ));
+            }
+
+            let mut arguments = Vec::new();
+            let mut expect_rbrack = false;
+
+            let end = loop {
+                match tokens.peek_for(RBrack, String::from("")) {
+                    Ok(rbrack) => break rbrack.span,
+                    Err(_) => {
+                        if expect_rbrack {
+                            return Err(Error::new(
+                                tokens.peek_span(),
+                                format!("Expected to find {RBrack} to conclude type argument list"),
+                            ));
+                        }
+                    }
+                }
+
+                arguments.push(type_repr(tokens)?);
+
+                match tokens.peek_for(Comma, String::from("")) {
+                    Ok(_) => { /* */ }
+                    Err(_) => expect_rbrack = true,
+                }
+            };
+
+            Ok(Spanned::new(
+                TypeKind::Generic(spanned_path.item, arguments),
+                Span::between(spanned_path.span, end),
             ))
         }
         otherwise => Err(Error::new(
@@ -603,7 +1090,7 @@ pub fn type_repr(tokens: &mut Stream<Token>) -> ParseResult<TypeRepr> {
     }
 }
 
-fn parameter(tokens: &mut Stream<Token>) -> ParseResult<Parameter> {
+fn parameter(tokens: &mut Stream<Token>) -> ParseResult<Parameter<Syntax>> {
     use BasicToken::*;
     use KeywordToken::*;
 
@@ -629,19 +1116,27 @@ fn parameter(tokens: &mut Stream<Token>) -> ParseResult<Parameter> {
     let ty = type_repr(tokens)?;
     let ty_span = ty.span;
 
-    Ok(Parameter::new(
-        mutability,
+    Ok(Parameter {
         name,
+        mutability,
         ty,
-        Span::between(maybe_start.unwrap_or(name_span), ty_span),
-    ))
+        span: Span::between(maybe_start.unwrap_or(name_span), ty_span),
+    })
 }
 
-fn field(tokens: &mut Stream<Token>) -> ParseResult<Field> {
+/// Parses a field, which is `default` visible unless it says otherwise: private within a
+/// struct, and as visible as its variant within an enum.
+fn field(tokens: &mut Stream<Token>, default: Visibility) -> ParseResult<Field<Syntax>> {
     use BasicToken::Colon;
 
+    let attributes = attributes(tokens)?;
+    let visibility = visibility(tokens)?;
     let name = tokens.peek_for(IdentifierToken, "Expected field name".to_string())?;
-    let name_span = name.span;
+    let name_span = attributes
+        .first()
+        .map(|first| first.span)
+        .or(visibility.as_ref().map(|visibility| visibility.span))
+        .unwrap_or(name.span);
 
     tokens.peek_for(
         Colon,
@@ -651,27 +1146,44 @@ fn field(tokens: &mut Stream<Token>) -> ParseResult<Field> {
     let ty = type_repr(tokens)?;
     let ty_span = ty.span;
 
-    Ok(Field::new(name, ty, Span::between(name_span, ty_span)))
+    Ok(Field {
+        name,
+        ty,
+        attributes,
+        visibility: visibility.map_or(default, |visibility| visibility.item),
+        span: Span::between(name_span, ty_span),
+    })
 }
 
-fn variant(tokens: &mut Stream<Token>) -> ParseResult<Variant> {
+fn variant(tokens: &mut Stream<Token>) -> ParseResult<Variant<Syntax>> {
     use BasicToken::*;
     use Token::*;
 
+    let attributes = attributes(tokens)?;
+    let visibility = visibility(tokens)?;
+    // A variant is as visible as its enum unless it is restricted further.
+    let default = visibility
+        .as_ref()
+        .map_or(Visibility::Public, |visibility| visibility.item);
     let name = tokens.peek_for(
         IdentifierToken,
         "Expected to find variant identifier".to_string(),
     )?;
+    let start = attributes
+        .first()
+        .map(|first| first.span)
+        .or(visibility.as_ref().map(|visibility| visibility.span))
+        .unwrap_or(name.span);
 
-    match tokens.peek() {
+    let (kind, end) = match tokens.peek() {
         Basic(LParen) => {
             tokens.pop();
             let mut expect_rparen = false;
             let mut elements = Vec::new();
 
-            loop {
+            let end = loop {
                 match tokens.peek_for(RParen, String::from("")) {
-                    Ok(_) => break,
+                    Ok(rparen) => break rparen.span,
                     _ => {
                         if expect_rparen {
                             return Err(Error::new(
@@ -692,18 +1204,18 @@ fn variant(tokens: &mut Stream<Token>) -> ParseResult<Variant> {
                     Ok(_) => { /* */ }
                     Err(_) => expect_rparen = true,
                 }
-            }
+            };
 
-            Ok(Variant::Tuple(name, elements))
+            (VariantKind::Tuple(elements), end)
         }
         Basic(LBrace) => {
             tokens.pop();
             let mut expect_rbrace = false;
             let mut fields = Vec::new();
 
-            loop {
+            let end = loop {
                 match tokens.peek_for(RBrace, String::from("")) {
-                    Ok(_) => break,
+                    Ok(rbrace) => break rbrace.span,
                     _ => {
                         if expect_rbrace {
                             return Err(Error::new(
@@ -717,19 +1229,27 @@ fn variant(tokens: &mut Stream<Token>) -> ParseResult<Variant> {
                     }
                 }
 
-                let fd = field(tokens)?;
+                let fd = field(tokens, default)?;
                 fields.push(fd);
 
                 match tokens.peek_for(Comma, String::from("")) {
                     Ok(_) => { /* */ }
                     Err(_) => expect_rbrace = true,
                 }
-            }
+            };
 
-            Ok(Variant::Struct(name, fields))
+            (VariantKind::Struct(fields), end)
         }
-        _ => Ok(Variant::Unit(name)),
-    }
+        _ => (VariantKind::Unit, name.span),
+    };
+
+    Ok(Variant {
+        name,
+        kind,
+        attributes,
+        visibility: default,
+        span: Span::between(start, end),
+    })
 }
 
 pub fn peek_type_repr(tokens: &Stream<Token>) -> bool {
@@ -749,6 +1269,7 @@ pub fn peek_type_repr(tokens: &Stream<Token>) -> bool {
             | Keyword(Char)
             | Keyword(Str)
             | Keyword(Unit)
+            | Keyword(Fn)
             | Basic(LBrack)
             | Basic(Ampersand)
             | Basic(LParen)
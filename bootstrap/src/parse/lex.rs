@@ -323,6 +323,21 @@ fn tokenize_char_literal(stream: &mut Stream<char>) -> LexResult<Spanned<Token>>
     }
 }
 
+fn tokenize_label(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
+    use Token::*;
+
+    let start = stream.pop().span;
+    let word = tokenize_word(stream)?;
+
+    match word.item {
+        Identifier(name) => Ok(Spanned::new(Label(name), Span::between(start, word.span))),
+        otherwise => Err(Error::new(
+            Span::between(start, word.span),
+            format!("Expected label name following single quote, but found {otherwise} instead"),
+        )),
+    }
+}
+
 fn tokenize_string_literal(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
     use LiteralToken::*;
     use Token::*;
@@ -361,7 +376,22 @@ fn tokenize_basic(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
         '{' => Ok(Spanned::new(Basic(LBrace), stream.pop().span)),
         '}' => Ok(Spanned::new(Basic(RBrace), stream.pop().span)),
         ',' => Ok(Spanned::new(Basic(Comma), stream.pop().span)),
-        '.' => Ok(Spanned::new(Basic(Period), stream.pop().span)),
+        '.' => {
+            let start = stream.pop().span;
+            match stream.peek() {
+                '.' => {
+                    let second = stream.pop().span;
+                    match stream.peek() {
+                        '=' => Ok(Spanned::new(
+                            Basic(Period2Equal),
+                            Span::between(start, stream.pop().span),
+                        )),
+                        _ => Ok(Spanned::new(Basic(Period2), Span::between(start, second))),
+                    }
+                }
+                _ => Ok(Spanned::new(Basic(Period), start)),
+            }
+        }
         '^' => Ok(Spanned::new(Basic(Caret), stream.pop().span)),
         '(' => Ok(Spanned::new(Basic(LParen), stream.pop().span)),
         ')' => Ok(Spanned::new(Basic(RParen), stream.pop().span)),
@@ -404,13 +434,17 @@ fn tokenize_basic(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
         '!' => {
             let start = stream.pop().span;
             match stream.peek() {
-                '=' => Ok(Spanned::new(Basic(BangEqual), stream.pop().span)),
-                _ => Ok(Spanned::new(Basic(Bang), stream.peek_span())),
+                '=' => Ok(Spanned::new(
+                    Basic(BangEqual),
+                    Span::between(start, stream.pop().span),
+                )),
+                _ => Ok(Spanned::new(Basic(Bang), start)),
             }
         }
         '[' => Ok(Spanned::new(Basic(LBrack), stream.pop().span)),
         ']' => Ok(Spanned::new(Basic(RBrack), stream.pop().span)),
         '?' => Ok(Spanned::new(Basic(Question), stream.pop().span)),
+        '@' => Ok(Spanned::new(Basic(At), stream.pop().span)),
         '=' => {
             let start = stream.pop().span;
             match stream.peek() {
@@ -418,6 +452,10 @@ fn tokenize_basic(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
                     let stop = stream.pop().span;
                     Ok(Spanned::new(Basic(Equal2), Span::between(start, stop)))
                 }
+                '>' => {
+                    let stop = stream.pop().span;
+                    Ok(Spanned::new(Basic(HeavyRArrow), Span::between(start, stop)))
+                }
                 _ => Ok(Spanned::new(
                     Basic(Equal),
                     Span::between(start, stream.peek_span()),
@@ -487,26 +525,29 @@ fn tokenize_basic(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
         '/' => {
             let start = stream.pop().span;
             match stream.peek() {
-                '=' => Ok(Spanned::new(Basic(FSlashEqual), stream.pop().span)),
-                _ => Ok(Spanned::new(Basic(FSlash), stream.pop().span)),
+                '=' => Ok(Spanned::new(
+                    Basic(FSlashEqual),
+                    Span::between(start, stream.pop().span),
+                )),
+                _ => Ok(Spanned::new(Basic(FSlash), start)),
             }
         }
         '<' => {
             let start = stream.pop().span;
             match stream.peek() {
-                '<' => match stream.peek() {
-                    '=' => {
-                        let stop = stream.pop().span;
-                        Ok(Spanned::new(
-                            Basic(LAngle2Equal),
-                            Span::between(start, stop),
-                        ))
-                    }
-                    _ => {
-                        let stop = stream.pop().span;
-                        Ok(Spanned::new(Basic(LAngle2), Span::between(start, stop)))
+                '<' => {
+                    let second = stream.pop().span;
+                    match stream.peek() {
+                        '=' => {
+                            let stop = stream.pop().span;
+                            Ok(Spanned::new(
+                                Basic(LAngle2Equal),
+                                Span::between(start, stop),
+                            ))
+                        }
+                        _ => Ok(Spanned::new(Basic(LAngle2), Span::between(start, second))),
                     }
-                },
+                }
                 '=' => Ok(Spanned::new(
                     Basic(LAngleEqual),
                     Span::between(start, stream.pop().span),
@@ -520,19 +561,19 @@ fn tokenize_basic(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
         '>' => {
             let start = stream.pop().span;
             match stream.peek() {
-                '>' => match stream.peek() {
-                    '=' => {
-                        let stop = stream.pop().span;
-                        Ok(Spanned::new(
-                            Basic(RAngle2Equal),
-                            Span::between(start, stop),
-                        ))
-                    }
-                    _ => {
-                        let stop = stream.pop().span;
-                        Ok(Spanned::new(Basic(RAngle2), Span::between(start, stop)))
+                '>' => {
+                    let second = stream.pop().span;
+                    match stream.peek() {
+                        '=' => {
+                            let stop = stream.pop().span;
+                            Ok(Spanned::new(
+                                Basic(RAngle2Equal),
+                                Span::between(start, stop),
+                            ))
+                        }
+                        _ => Ok(Spanned::new(Basic(RAngle2), Span::between(start, second))),
                     }
-                },
+                }
                 '=' => Ok(Spanned::new(
                     Basic(RAngleEqual),
                     Span::between(start, stream.pop().span),
@@ -555,32 +596,47 @@ fn tokenize_basic(stream: &mut Stream<char>) -> LexResult<Spanned<Token>> {
 }
 
 pub fn tokenize(source: &str) -> LexResult<Stream<Token>> {
-    let mut source = Stream::from(source);
+    tokenize_at(source, 0)
+}
+
+/// Tokenizes a source file whose first character is at position `start`, as assigned by its
+/// `SourceMap`, so that spans from different files never overlap.
+pub fn tokenize_at(source: &str, start: usize) -> LexResult<Stream<Token>> {
+    // The end is marked explicitly, so that an empty file still has a position for its `Eof`.
+    let end = start + source.chars().count();
+    let mut source: Stream<char> = source
+        .chars()
+        .enumerate()
+        .map(|(i, c)| Spanned::from((start + i, c)))
+        .chain(std::iter::once(Spanned::from((end, char::END))))
+        .collect();
     let mut tokens: Vec<Spanned<Token>> = Vec::new();
 
     loop {
         match source.peek() {
             '0'..='9' => tokens.push(tokenize_integer_literal(&mut source)?),
             'a'..='z' | 'A'..='Z' | '_' => tokens.push(tokenize_word(&mut source)?),
-            '\'' => tokens.push(tokenize_char_literal(&mut source)?),
+            // `'a'` is a character literal, but `'a` begins a label.
+            '\'' => match (source.peek_nth(1), source.peek_nth(2)) {
+                ('A'..='Z' | 'a'..='z' | '_', next) if next != '\'' => {
+                    tokens.push(tokenize_label(&mut source)?)
+                }
+                _ => tokens.push(tokenize_char_literal(&mut source)?),
+            },
             '\"' => tokens.push(tokenize_string_literal(&mut source)?),
             '\0' => {
                 tokens.push(Spanned::new(Token::Eof, source.end_span()));
                 break Ok(tokens.into_iter().collect());
             }
-            ' ' | '\n' => {
+            ' ' | '\t' | '\r' | '\n' => {
                 source.pop();
                 continue;
             }
             '#' => {
                 source.pop();
-                loop {
-                    if source.peek() != '\n' {
-                        source.pop();
-                    } else {
-                        source.pop();
-                        break;
-                    }
+                // A comment runs to the end of its line, or of the file.
+                while !matches!(source.peek(), '\n' | '\0') {
+                    source.pop();
                 }
             }
             _ => tokens.push(tokenize_basic(&mut source)?),
@@ -0,0 +1,303 @@
+use crate::error::Error;
+use crate::model::ast::Syntax;
+use crate::model::base::*;
+use crate::model::token::*;
+use crate::parse;
+use crate::parse::ParseResult;
+use crate::span::{Span, Spanned};
+use crate::stream::{PeekFor, Stream};
+
+pub fn pattern(tokens: &mut Stream<Token>) -> ParseResult<Pattern<Syntax>> {
+    pattern_with(tokens, true)
+}
+
+/// Parses a pattern that is directly followed by a block, as in `if value is pattern { ... }`,
+/// where a brace following a path begins the block rather than a struct pattern. Struct
+/// patterns nested within a tuple pattern are still allowed.
+pub fn restricted_pattern(tokens: &mut Stream<Token>) -> ParseResult<Pattern<Syntax>> {
+    pattern_with(tokens, false)
+}
+
+fn pattern_with(tokens: &mut Stream<Token>, allow_struct: bool) -> ParseResult<Pattern<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // <pattern> |= UNDERSCORE
+    //           |  MUT? IDENT
+    //           |  <constant> ((PERIOD2 | PERIOD2_EQUAL) <constant>?)?
+    //           |  (PERIOD2 | PERIOD2_EQUAL) <constant>
+    //           |  <path>
+    //           |  <path> LPAREN (<pattern> (COMMA <pattern>)* COMMA?)? RPAREN
+    //           |  <path> LBRACE (<field-pattern> (COMMA <field-pattern>)* (COMMA PERIOD2)? COMMA?)? RBRACE
+    //
+    // <constant> |= HYPHEN? INTEGER | CHARACTER | TRUE | FALSE
+    //
+    // A lone identifier is always a binding, so a unit variant or constant has to be named by
+    // a path of at least two segments.
+
+    let start = tokens.peek_span();
+
+    match tokens.peek() {
+        Identifier(name) if name == "_" => {
+            Ok(Pattern::new(PatternKind::Wildcard(()), tokens.pop().span))
+        }
+        Keyword(Mut) => {
+            tokens.pop();
+            let name = tokens.peek_for(
+                IdentifierToken,
+                format!("Expected to find binding name following {Mut}"),
+            )?;
+            let span = Span::between(start, name.span);
+
+            Ok(Pattern::new(
+                PatternKind::Binding(Mutability::Mutable, name, ()),
+                span,
+            ))
+        }
+        Identifier(_) | Basic(Colon2) => {
+            let path = parse::path(tokens)?;
+
+            match tokens.peek() {
+                Basic(LParen) => tuple_pattern(path, tokens),
+                Basic(LBrace) if allow_struct => struct_pattern(path, tokens),
+                _ if path.item.len() == 1 => {
+                    let name = path.item.into_iter().next().unwrap();
+                    Ok(Pattern::new(
+                        PatternKind::Binding(Mutability::Immutable, name, ()),
+                        path.span,
+                    ))
+                }
+                _ => Ok(Pattern::new(PatternKind::Path(path.item, ()), path.span)),
+            }
+        }
+        Basic(Period2) | Basic(Period2Equal) => {
+            let (end, limits, end_span) = range_end(tokens)?;
+
+            match end {
+                Some(end) => Ok(Pattern::new(
+                    PatternKind::Range(None, Some(end), limits, ()),
+                    Span::between(start, end_span),
+                )),
+                None => Err(Error::new(
+                    Span::between(start, end_span),
+                    format!("Expected to find the end of a range pattern beginning with {Period2}"),
+                )),
+            }
+        }
+        _ => {
+            let constant = constant(tokens)?;
+
+            match tokens.peek() {
+                Basic(Period2) | Basic(Period2Equal) => {
+                    let (end, limits, end_span) = range_end(tokens)?;
+                    Ok(Pattern::new(
+                        PatternKind::Range(Some(constant.item), end, limits, ()),
+                        Span::between(start, end_span),
+                    ))
+                }
+                _ => Ok(Pattern::new(
+                    PatternKind::Literal(constant.item, ()),
+                    constant.span,
+                )),
+            }
+        }
+    }
+}
+
+fn tuple_pattern(
+    path: Spanned<Vec<Spanned<String>>>,
+    tokens: &mut Stream<Token>,
+) -> ParseResult<Pattern<Syntax>> {
+    use BasicToken::*;
+
+    tokens.peek_for(
+        LParen,
+        format!("Expected to find {LParen} to begin tuple pattern"),
+    )?;
+
+    let mut elements = Vec::new();
+    let mut expect_rparen = false;
+
+    let end = loop {
+        match tokens.peek_for(RParen, String::from("")) {
+            Ok(rparen) => break rparen.span,
+            Err(_) => {
+                if expect_rparen {
+                    return Err(Error::new(
+                        tokens.peek_span(),
+                        format!("Expected to find {RParen} to conclude tuple pattern"),
+                    ));
+                }
+            }
+        }
+
+        elements.push(pattern(tokens)?);
+
+        match tokens.peek_for(Comma, String::from("")) {
+            Ok(_) => { /* */ }
+            Err(_) => expect_rparen = true,
+        }
+    };
+
+    Ok(Pattern::new(
+        PatternKind::Tuple(path.item, elements, ()),
+        Span::between(path.span, end),
+    ))
+}
+
+fn struct_pattern(
+    path: Spanned<Vec<Spanned<String>>>,
+    tokens: &mut Stream<Token>,
+) -> ParseResult<Pattern<Syntax>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+
+    // <field-pattern> |= IDENT COLON <pattern>
+    //                 |  MUT? IDENT
+    //
+    // A field without a pattern binds a variable of the field's own name.
+
+    tokens.peek_for(
+        LBrace,
+        format!("Expected to find {LBrace} to begin struct pattern"),
+    )?;
+
+    let mut fields = Vec::new();
+    let mut rest = false;
+    let mut expect_rbrace = false;
+
+    let end = loop {
+        match tokens.peek_for(RBrace, String::from("")) {
+            Ok(rbrace) => break rbrace.span,
+            Err(_) => {
+                if expect_rbrace {
+                    return Err(Error::new(
+                        tokens.peek_span(),
+                        format!("Expected to find {RBrace} to conclude struct pattern"),
+                    ));
+                }
+            }
+        }
+
+        if tokens.peek_for(Period2, String::from("")).is_ok() {
+            rest = true;
+            let _ = tokens.peek_for(Comma, String::from(""));
+            expect_rbrace = true;
+            continue;
+        }
+
+        let mutable = tokens.peek_for(Mut, String::from("")).ok();
+        let name = tokens.peek_for(
+            IdentifierToken,
+            "Expected to find field name in struct pattern".to_string(),
+        )?;
+
+        let pattern = match (&mutable, tokens.peek_for(Colon, String::from(""))) {
+            (None, Ok(_)) => pattern(tokens)?,
+            (Some(mutable), Ok(_)) => {
+                return Err(Error::new(
+                    mutable.span,
+                    format!("Expected a binding of the field `{}` itself to follow {Mut}, rather than a pattern", name.item),
+                ))
+            }
+            (_, Err(_)) => {
+                let start = mutable.as_ref().map_or(name.span, |mutable| mutable.span);
+                let mutability = match mutable {
+                    Some(_) => Mutability::Mutable,
+                    None => Mutability::Immutable,
+                };
+
+                Pattern::new(
+                    PatternKind::Binding(mutability, name.clone(), ()),
+                    Span::between(start, name.span),
+                )
+            }
+        };
+
+        fields.push(FieldPattern { name, pattern });
+
+        match tokens.peek_for(Comma, String::from("")) {
+            Ok(_) => { /* */ }
+            Err(_) => expect_rbrace = true,
+        }
+    };
+
+    Ok(Pattern::new(
+        PatternKind::Struct(path.item, fields, rest, ()),
+        Span::between(path.span, end),
+    ))
+}
+
+fn range_end(tokens: &mut Stream<Token>) -> ParseResult<(Option<Constant>, RangeLimits, Span)> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use LiteralToken as Lit;
+    use Token::*;
+
+    // As with range expressions, only a half-open range may leave out its end.
+
+    let operator = tokens.pop();
+    let limits = match operator.item {
+        Basic(Period2Equal) => RangeLimits::Closed,
+        _ => RangeLimits::HalfOpen,
+    };
+
+    match (tokens.peek(), limits) {
+        (
+            Basic(Hyphen) | Literal(Lit::Integer(_)) | Literal(Lit::Character(_)) | Keyword(True)
+            | Keyword(False),
+            _,
+        ) => {
+            let end = constant(tokens)?;
+            Ok((Some(end.item), limits, end.span))
+        }
+        (_, RangeLimits::HalfOpen) => Ok((None, limits, operator.span)),
+        (otherwise, RangeLimits::Closed) => Err(Error::new(
+            tokens.peek_span(),
+            format!("Expected to find the end of a range pattern following {Period2Equal}, but found {otherwise} instead"),
+        )),
+    }
+}
+
+fn constant(tokens: &mut Stream<Token>) -> ParseResult<Spanned<Constant>> {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use LiteralToken as Lit;
+    use Token::*;
+
+    let start = tokens.peek_span();
+
+    match tokens.peek() {
+        Basic(Hyphen) => {
+            tokens.pop();
+            let end = tokens.peek_span();
+            let value = tokens.peek_for(
+                IntegerLiteralToken,
+                format!("Expected to find integer literal following {Hyphen} in pattern"),
+            )?;
+
+            Ok(Spanned::new(
+                Constant::Integer {
+                    negative: true,
+                    value,
+                },
+                Span::between(start, end),
+            ))
+        }
+        Literal(Lit::Integer(value)) => Ok(Spanned::new(
+            Constant::Integer {
+                negative: false,
+                value,
+            },
+            tokens.pop().span,
+        )),
+        Literal(Lit::Character(ch)) => Ok(Spanned::new(Constant::Character(ch), tokens.pop().span)),
+        Keyword(True) => Ok(Spanned::new(Constant::Bool(true), tokens.pop().span)),
+        Keyword(False) => Ok(Spanned::new(Constant::Bool(false), tokens.pop().span)),
+        otherwise => Err(Error::new(
+            start,
+            format!("Expected to find pattern, but found {otherwise} instead"),
+        )),
+    }
+}
@@ -1,23 +1,62 @@
-use crate::model::*;
-use crate::parse::expression;
+use crate::model::ast::Syntax;
+use crate::model::base::*;
+use crate::model::token::*;
 use crate::parse::item::type_repr;
 use crate::parse::ParseResult;
+use crate::parse::{expression, statement_expression};
 use crate::span::Span;
 use crate::stream::PeekFor;
 use crate::stream::Stream;
 
-pub fn statement(tokens: &mut Stream<Token>) -> ParseResult<Statement> {
+pub fn statement(tokens: &mut Stream<Token>) -> ParseResult<Statement<Syntax>> {
     use KeywordToken::*;
     use Token::*;
 
     match tokens.peek() {
         Keyword(Let) => let_statement(tokens).map(Statement::Let),
+        Keyword(Defer) => {
+            tokens.pop();
+            expression_statement(tokens).map(Statement::Defer)
+        }
+        Basic(BasicToken::Semicolon) => {
+            tokens.pop();
+            Ok(Statement::Empty)
+        }
+        _ => expression_statement(tokens).map(Statement::Expression),
+    }
+}
+
+pub fn expression_statement(tokens: &mut Stream<Token>) -> ParseResult<Expression<Syntax>> {
+    // <expression-statement> |= <expression-without-block> SEMICOLON
+    //                        |  <expression-with-block> SEMICOLON?
 
-        _ => todo!(),
+    let expr = statement_expression(tokens)?;
+    terminator(&expr, tokens)?;
+
+    Ok(expr)
+}
+
+/// Consumes the semicolon ending an expression statement, which may be left out following an
+/// expression with a block.
+pub fn terminator(expr: &Expression<Syntax>, tokens: &mut Stream<Token>) -> ParseResult<()> {
+    use BasicToken::*;
+
+    match &expr.item {
+        ExpressionKind::ExpressionWithBlock(_) => {
+            let _ = tokens.peek_for(Semicolon, String::from(""));
+        }
+        ExpressionKind::ExpressionWithoutBlock(_) => {
+            tokens.peek_for(
+                Semicolon,
+                format!("Expected {Semicolon} following expression statement"),
+            )?;
+        }
     }
+
+    Ok(())
 }
 
-pub fn let_statement(tokens: &mut Stream<Token>) -> ParseResult<Let> {
+pub fn let_statement(tokens: &mut Stream<Token>) -> ParseResult<Let<Syntax>> {
     // Let Statements are surprisingly syntactically complex...
     //
     // let mut? foo [: Type]? [= init]? ;
@@ -54,11 +93,11 @@ pub fn let_statement(tokens: &mut Stream<Token>) -> ParseResult<Let> {
         ),
     )?;
 
-    Ok(Let::new(
+    Ok(Let {
         name,
         mutability,
-        maybe_type,
-        maybe_init,
-        Span::between(start.span, end.span),
-    ))
+        ty: maybe_type,
+        initializer: maybe_init,
+        span: Span::between(start.span, end.span),
+    })
 }
@@ -1,5 +1,9 @@
-use crate::model::*;
+use crate::model::ast::{Syntax, TypeKind};
+use crate::model::base::*;
+use crate::model::token::*;
+use crate::parse;
 use crate::parse::expr::*;
+use crate::parse::item::{item, type_repr};
 use crate::parse::lex;
 use crate::parse::stmt::*;
 use crate::span::{Span, Spanned};
@@ -155,6 +159,44 @@ fn lex_string() {
     );
 }
 
+#[test]
+fn lex_arrows_and_division() {
+    use BasicToken::*;
+
+    let tokens: Vec<Token> = {
+        let mut tokens = lex::tokenize("a/b => c -> d <<= e").unwrap();
+        std::iter::from_fn(|| match tokens.pop().item {
+            Token::Eof => None,
+            token => Some(token),
+        })
+        .collect()
+    };
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Identifier("a".to_owned()),
+            Token::Basic(FSlash),
+            Token::Identifier("b".to_owned()),
+            Token::Basic(HeavyRArrow),
+            Token::Identifier("c".to_owned()),
+            Token::Basic(LightRArrow),
+            Token::Identifier("d".to_owned()),
+            Token::Basic(LAngle2Equal),
+            Token::Identifier("e".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn lex_trailing_comment() {
+    let mut tokens = lex::tokenize("foo # no newline follows").unwrap();
+
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens.pop().item, Token::Identifier("foo".to_owned()));
+    assert_eq!(tokens.pop().item, Token::Eof);
+}
+
 #[test]
 fn parse_integer_literal() {
     let mut tokens: Stream<Token> = vec![Spanned::empty(Token::Literal(LiteralToken::Integer(4)))]
@@ -163,13 +205,27 @@ fn parse_integer_literal() {
 
     let expr = expression(&mut tokens).unwrap();
 
-    if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(4)) = expr.item {
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+        Literal::Integer(4),
+        (),
+    )) = expr.item
+    {
         // assert!(true);
     } else {
         panic!();
     }
 }
 
+fn integer(expression: &Expression<Syntax>) -> Option<u32> {
+    match &expression.item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+            Literal::Integer(value),
+            (),
+        )) => Some(*value),
+        _ => None,
+    }
+}
+
 #[test]
 fn parse_add_expr() {
     let mut tokens: Stream<Token> = vec![
@@ -182,23 +238,16 @@ fn parse_add_expr() {
 
     let expr = expression(&mut tokens).unwrap();
 
-    if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::ArithmeticOrLogical(
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Binary(
         left,
         op,
         right,
+        (),
     )) = expr.item
     {
-        assert_eq!(op, ArithmeticOrLogicalOperator::Plus);
-
-        match left.item {
-            ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(4)) => { /* */ }
-            _ => panic!(),
-        }
-
-        match right.item {
-            ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(2)) => { /*assert!(true)*/ },
-            _ => panic!(),
-        }
+        assert!(matches!(op, BinaryOperator::Plus));
+        assert_eq!(integer(&left), Some(4));
+        assert_eq!(integer(&right), Some(2));
     } else {
         panic!();
     }
@@ -218,43 +267,27 @@ fn parse_add_assoc_expr() {
 
     let expr = expression(&mut tokens).unwrap();
 
-    if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::ArithmeticOrLogical(
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Binary(
         left,
-        op,
+        BinaryOperator::Plus,
         right,
+        (),
     )) = expr.item
     {
-        assert_eq!(op, ArithmeticOrLogicalOperator::Plus);
-
         match left.item {
-            ExpressionKind::WithoutBlock(ExpressionWithoutBlock::ArithmeticOrLogical(
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Binary(
                 left_inner,
-                op_inner,
+                BinaryOperator::Plus,
                 right_inner,
+                (),
             )) => {
-                assert_eq!(op_inner, ArithmeticOrLogicalOperator::Plus);
-
-                if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(4)) =
-                    left_inner.item
-                {
-                    if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(2)) =
-                        right_inner.item
-                    {
-                        // assert!(true)
-                    } else {
-                        panic!()
-                    }
-                } else {
-                    panic!()
-                }
+                assert_eq!(integer(&left_inner), Some(4));
+                assert_eq!(integer(&right_inner), Some(2));
             }
             _ => panic!(),
         }
 
-        match right.item {
-            ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(1)) => {},
-            _ => panic!(),
-        }
+        assert_eq!(integer(&right), Some(1));
     } else {
         panic!();
     }
@@ -272,24 +305,21 @@ fn parse_assign_expr() {
 
     let expr = expression(&mut tokens).unwrap();
 
-    if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Assignment(left, op, right)) =
-        expr.item
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Binary(
+        left,
+        BinaryOperator::Equal,
+        right,
+        (),
+    )) = expr.item
     {
-        assert_eq!(op, AssignmentOperator::Equal);
-
-        if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Path(Path {
-            is_global: false,
+        if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(
             mut segments,
-        })) = left.item
+            (),
+        )) = left.item
         {
             assert_eq!(segments.len(), 1);
             assert_eq!(segments.pop().unwrap().item, "foo".to_owned());
-
-            if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Integer(4)) = right.item {
-                // assert!(true);
-            } else {
-                panic!();
-            }
+            assert_eq!(integer(&right), Some(4));
         } else {
             panic!()
         }
@@ -298,24 +328,574 @@ fn parse_assign_expr() {
     }
 }
 
-// #[test]
-// fn parse_assign_assoc_expr() {
-//     let mut tokens: Stream<Token> = vec![
-//         Spanned::empty(Token::Identifier("foo".to_owned())),
-//         Spanned::empty(Token::Basic(BasicToken::Equal)),
-//         Spanned::empty(Token::Identifier("bar".to_owned())),
-//         Spanned::empty(Token::Basic(BasicToken::Equal)),
-//         Spanned::empty(Token::Literal(LiteralToken::Integer(4)))
-//     ].into_iter().collect();
+#[test]
+fn parse_prefix_expr() {
+    // -a * *b
+    let mut tokens = lex::tokenize("-a * *b").unwrap();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    match expr.item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Binary(
+            left,
+            BinaryOperator::Times,
+            right,
+            (),
+        )) => {
+            assert!(matches!(
+                left.item,
+                ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+                    PrefixOperator::ArithmeticNegate,
+                    _,
+                    (),
+                ))
+            ));
+            assert!(matches!(
+                right.item,
+                ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+                    PrefixOperator::DeReference,
+                    _,
+                    (),
+                ))
+            ));
+        }
+        _ => panic!(),
+    }
+
+    let mut tokens = lex::tokenize("&mut x.y").unwrap();
+
+    match expression(&mut tokens).unwrap().item {
+        ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+            PrefixOperator::MutableBorrow,
+            operand,
+            (),
+        )) => assert!(matches!(
+            operand.item,
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Field(_, _, ()))
+        )),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_method_call_expr() {
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Token::Identifier("foo".to_owned())),
+        Spanned::empty(Token::Basic(BasicToken::Period)),
+        Spanned::empty(Token::Identifier("bar".to_owned())),
+        Spanned::empty(Token::Basic(BasicToken::LParen)),
+        Spanned::empty(Token::Literal(LiteralToken::Integer(4))),
+        Spanned::empty(Token::Basic(BasicToken::RParen)),
+    ]
+    .into_iter()
+    .collect();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::MethodCall(
+        receiver,
+        name,
+        args,
+        (),
+    )) = expr.item
+    {
+        assert_eq!(name.item, "bar".to_owned());
+        assert_eq!(args.len(), 1);
+        assert_eq!(integer(&args[0]), Some(4));
+
+        match receiver.item {
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(_, ())) => { /* */ }
+            _ => panic!(),
+        }
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn parse_asm_expr() {
+    use BasicToken::*;
+    use Token::*;
+
+    // asm { "out ({port}), a", in port = p, clobber "a" }
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Keyword(KeywordToken::Asm)),
+        Spanned::empty(Basic(LBrace)),
+        Spanned::empty(Literal(LiteralToken::String("out ({port}), a".to_owned()))),
+        Spanned::empty(Basic(Comma)),
+        Spanned::empty(Keyword(KeywordToken::In)),
+        Spanned::empty(Identifier("port".to_owned())),
+        Spanned::empty(Basic(Equal)),
+        Spanned::empty(Identifier("p".to_owned())),
+        Spanned::empty(Basic(Comma)),
+        Spanned::empty(Identifier("clobber".to_owned())),
+        Spanned::empty(Literal(LiteralToken::String("a".to_owned()))),
+        Spanned::empty(Basic(RBrace)),
+    ]
+    .into_iter()
+    .collect();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Asm(assembly, ())) =
+        expr.item
+    {
+        assert_eq!(assembly.template.item, "out ({port}), a".to_owned());
+        assert_eq!(assembly.operands.len(), 1);
+        assert_eq!(assembly.operands[0].direction, Direction::In);
+        assert_eq!(assembly.operands[0].name.item, "port".to_owned());
+        assert_eq!(assembly.clobbers.len(), 1);
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn parse_repeat_expr() {
+    use BasicToken::*;
+    use Token::*;
+
+    // [0; 64]
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Basic(LBrack)),
+        Spanned::empty(Literal(LiteralToken::Integer(0))),
+        Spanned::empty(Basic(Semicolon)),
+        Spanned::empty(Literal(LiteralToken::Integer(64))),
+        Spanned::empty(Basic(RBrack)),
+    ]
+    .into_iter()
+    .collect();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Repeat(
+        value,
+        count,
+        (),
+    )) = expr.item
+    {
+        assert_eq!(integer(&value), Some(0));
+        assert_eq!(integer(&count), Some(64));
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn parse_range_index_expr() {
+    use BasicToken::*;
+    use Token::*;
+
+    // buf[2..]
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Identifier("buf".to_owned())),
+        Spanned::empty(Basic(LBrack)),
+        Spanned::empty(Literal(LiteralToken::Integer(2))),
+        Spanned::empty(Basic(Period2)),
+        Spanned::empty(Basic(RBrack)),
+    ]
+    .into_iter()
+    .collect();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    if let ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Index(_, index, ())) =
+        expr.item
+    {
+        match index.item {
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Range(
+                Some(_),
+                None,
+                RangeLimits::HalfOpen,
+                (),
+            )) => { /* */ }
+            _ => panic!(),
+        }
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn parse_for_expr() {
+    use BasicToken::*;
+    use Token::*;
+
+    // for i in 0..=9 {}
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Keyword(KeywordToken::For)),
+        Spanned::empty(Identifier("i".to_owned())),
+        Spanned::empty(Keyword(KeywordToken::In)),
+        Spanned::empty(Literal(LiteralToken::Integer(0))),
+        Spanned::empty(Basic(Period2Equal)),
+        Spanned::empty(Literal(LiteralToken::Integer(9))),
+        Spanned::empty(Basic(LBrace)),
+        Spanned::empty(Basic(RBrace)),
+    ]
+    .into_iter()
+    .collect();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    if let ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::For(
+        None,
+        name,
+        iterable,
+        body,
+        (),
+    )) = expr.item
+    {
+        assert_eq!(name.item, "i".to_owned());
+        assert!(body.is_empty());
+
+        match iterable.item {
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Range(
+                Some(_),
+                Some(_),
+                RangeLimits::Closed,
+                (),
+            )) => { /* */ }
+            _ => panic!(),
+        }
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn parse_unbounded_for_expr() {
+    use BasicToken::*;
+    use Token::*;
+
+    // for i in 0.. { x }
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Keyword(KeywordToken::For)),
+        Spanned::empty(Identifier("i".to_owned())),
+        Spanned::empty(Keyword(KeywordToken::In)),
+        Spanned::empty(Literal(LiteralToken::Integer(0))),
+        Spanned::empty(Basic(Period2)),
+        Spanned::empty(Basic(LBrace)),
+        Spanned::empty(Identifier("x".to_owned())),
+        Spanned::empty(Basic(RBrace)),
+    ]
+    .into_iter()
+    .collect();
+
+    let expr = expression(&mut tokens).unwrap();
+
+    // The block is the body of the loop rather than the end of the range.
+    if let ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::For(
+        None,
+        _,
+        iterable,
+        body,
+        (),
+    )) = expr.item
+    {
+        assert_eq!(body.len(), 1);
+
+        match iterable.item {
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Range(
+                Some(_),
+                None,
+                RangeLimits::HalfOpen,
+                (),
+            )) => { /* */ }
+            _ => panic!(),
+        }
+    } else {
+        panic!()
+    }
+}
+
+#[test]
+fn parse_defer_stmt() {
+    use BasicToken::*;
+    use Token::*;
+
+    // defer close(port);
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Keyword(KeywordToken::Defer)),
+        Spanned::empty(Identifier("close".to_owned())),
+        Spanned::empty(Basic(LParen)),
+        Spanned::empty(Identifier("port".to_owned())),
+        Spanned::empty(Basic(RParen)),
+        Spanned::empty(Basic(Semicolon)),
+    ]
+    .into_iter()
+    .collect();
+
+    match statement(&mut tokens).unwrap() {
+        Statement::Defer(Spanned {
+            item: ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Call(_, _, ())),
+            ..
+        }) => { /* */ }
+        _ => panic!(),
+    }
+
+    assert_eq!(tokens.peek(), Eof);
+}
+
+#[test]
+fn parse_block_tail() {
+    // The last expression of a block is its value only if it has no semicolon.
+    let mut tokens = lex::tokenize("{ let x = 1; foo(x); if x { } x }").unwrap();
+
+    let block = block_expression(&mut tokens).unwrap().item;
+
+    match &block[..] {
+        [Some(Statements::Leading(
+            Statement::Let(_),
+            rest,
+            Some(Spanned {
+                item: ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(_, ())),
+                span,
+            }),
+        ))] => {
+            assert_eq!(*span, Span::new(30, 31));
+            assert_eq!(rest.len(), 2);
+            assert!(matches!(
+                rest[1],
+                Statement::Expression(Spanned {
+                    item: ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::If(
+                        _,
+                        _,
+                        None,
+                        ()
+                    )),
+                    ..
+                })
+            ));
+        }
+        _ => panic!(),
+    }
+
+    let mut tokens = lex::tokenize("{ 1 }").unwrap();
+    let block = block_expression(&mut tokens).unwrap().item;
+    assert!(matches!(
+        &block[..],
+        [Some(Statements::Block(Spanned {
+            item: ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+                Literal::Integer(1),
+                ()
+            )),
+            span: Span { start: 2, stop: 3 },
+        }))]
+    ));
+
+    let mut tokens = lex::tokenize("{ foo(); }").unwrap();
+    let block = block_expression(&mut tokens).unwrap().item;
+    assert!(matches!(
+        &block[..],
+        [Some(Statements::Leading(Statement::Expression(_), _, None))]
+    ));
+
+    let mut tokens = lex::tokenize("{ foo() bar() }").unwrap();
+    let error = block_expression(&mut tokens).err().unwrap();
+    assert!(error.message.starts_with("Expected ;"), "{}", error.message);
+
+    // An expression with a block directly before the closing brace is the value of the block.
+    for source in [
+        "{ if c { 1 } else { 2 } }",
+        "{ when c { _ => 1 } }",
+        "{ { 3 } }",
+        "{ loop { break 5; } }",
+        "{ 'a: while c { } }",
+    ] {
+        let mut tokens = lex::tokenize(source).unwrap();
+        let block = block_expression(&mut tokens).unwrap().item;
+        assert!(
+            matches!(
+                &block[..],
+                [Some(Statements::Block(Spanned {
+                    item: ExpressionKind::ExpressionWithBlock(_),
+                    ..
+                }))]
+            ),
+            "{source}"
+        );
+    }
+
+    // Otherwise it ends its statement at its closing brace, rather than continuing into an
+    // operator following it.
+    let mut tokens = lex::tokenize("{ if c { } *p = 1; }").unwrap();
+    let block = block_expression(&mut tokens).unwrap().item;
+    match &block[..] {
+        [Some(Statements::Leading(Statement::Expression(first), rest, None))] => {
+            assert!(matches!(
+                first.item,
+                ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::If(..))
+            ));
+            assert!(matches!(
+                rest[..],
+                [Statement::Expression(Spanned {
+                    item: ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Binary(
+                        _,
+                        BinaryOperator::Equal,
+                        _,
+                        ()
+                    )),
+                    ..
+                })]
+            ));
+        }
+        _ => panic!(),
+    }
+
+    let mut tokens = lex::tokenize("{ { y = 1; } -1 }").unwrap();
+    let block = block_expression(&mut tokens).unwrap().item;
+    assert!(matches!(
+        &block[..],
+        [Some(Statements::Leading(
+            Statement::Expression(Spanned {
+                item: ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Block(..)),
+                ..
+            }),
+            _,
+            Some(Spanned {
+                item: ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Prefix(
+                    PrefixOperator::ArithmeticNegate,
+                    _,
+                    ()
+                )),
+                ..
+            }),
+        ))]
+    ));
+}
+
+#[test]
+fn parse_case_expr() {
+    let mut tokens = lex::tokenize(
+        "when x {
+            Shape::Circle(r) if r > 0 => 1,
+            Shape::Rect { w, h: 0, .. } => { 2 }
+            Shape::Empty => 3,
+            -1..=9 | _ => 4,
+        }",
+    )
+    .unwrap();
+
+    // `|` does not separate alternatives, so the last arm is expected to fail to parse.
+    assert!(expression(&mut tokens).is_err());
+
+    let mut tokens = lex::tokenize(
+        "when x {
+            Shape::Circle(r) if r > 0 => 1,
+            Shape::Rect { w, h: 0, .. } => { 2 }
+            Shape::Empty => 3,
+            -1..=9 => 4,
+            'a'.. => 5,
+            mut other => 6,
+        }",
+    )
+    .unwrap();
+
+    let expr = expression(&mut tokens).unwrap();
+    assert_eq!(tokens.peek(), Token::Eof);
+
+    let ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Case(_, arms, ())) = expr.item
+    else {
+        panic!()
+    };
+
+    assert_eq!(arms.len(), 6);
+
+    match &arms[0].pattern.item {
+        PatternKind::Tuple(path, elements, ()) => {
+            assert_eq!(path.len(), 2);
+            assert!(matches!(
+                &elements[..],
+                [Spanned {
+                    item: PatternKind::Binding(Mutability::Immutable, _, ()),
+                    ..
+                }]
+            ));
+        }
+        _ => panic!(),
+    }
+    assert!(arms[0].guard.is_some());
+
+    match &arms[1].pattern.item {
+        PatternKind::Struct(_, fields, true, ()) => {
+            assert_eq!(fields.len(), 2);
+            assert!(matches!(
+                fields[0].pattern.item,
+                PatternKind::Binding(_, _, ())
+            ));
+            assert!(matches!(
+                fields[1].pattern.item,
+                PatternKind::Literal(
+                    Constant::Integer {
+                        negative: false,
+                        value: 0
+                    },
+                    ()
+                )
+            ));
+        }
+        _ => panic!(),
+    }
+
+    assert!(matches!(arms[2].pattern.item, PatternKind::Path(_, ())));
+    assert!(matches!(
+        arms[3].pattern.item,
+        PatternKind::Range(
+            Some(Constant::Integer {
+                negative: true,
+                value: 1
+            }),
+            Some(Constant::Integer {
+                negative: false,
+                value: 9
+            }),
+            RangeLimits::Closed,
+            (),
+        )
+    ));
+    assert!(matches!(
+        arms[4].pattern.item,
+        PatternKind::Range(
+            Some(Constant::Character('a')),
+            None,
+            RangeLimits::HalfOpen,
+            ()
+        )
+    ));
+    assert!(matches!(
+        arms[5].pattern.item,
+        PatternKind::Binding(Mutability::Mutable, _, ())
+    ));
+}
 
-//     let expr = expression(&mut tokens).unwrap();
+#[test]
+fn parse_if_is_expr() {
+    // A brace following the pattern's path begins the block rather than a struct pattern.
+    let mut tokens = lex::tokenize("if x is Color::Red { 1 } else if x is Some(_) { 2 }").unwrap();
 
-//     if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Assignment(left, op, right)) = expr.kind {
-//         assert_eq!(op, AssignmentOperator::Equal);
+    let expr = expression(&mut tokens).unwrap();
 
-//         if let ExpressionKind::WithoutBlock(ExpressionWithoutBlock::Assignment(left_inner, op_inner, right_inner)) =
-//     }
-// }
+    match expr.item {
+        ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::IfIs(
+            _,
+            pattern,
+            then,
+            Some(otherwise),
+            (),
+        )) => {
+            assert!(matches!(pattern.item, PatternKind::Path(_, ())));
+            assert!(matches!(
+                then.item,
+                ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::Block(_, ()))
+            ));
+            assert!(matches!(
+                otherwise.item,
+                ExpressionKind::ExpressionWithBlock(ExpressionWithBlock::IfIs(_, _, _, None, ()))
+            ));
+        }
+        _ => panic!(),
+    }
+}
 
 #[test]
 fn parse_let_statement_bare() {
@@ -337,8 +917,8 @@ fn parse_let_statement_bare() {
         Statement::Let(inner) => {
             assert_eq!(inner.name.item, "foo".to_owned());
             assert_eq!(inner.mutability, Mutability::Immutable);
-            assert_eq!(inner.ty, None);
-            assert_eq!(inner.initializer, None);
+            assert!(inner.ty.is_none());
+            assert!(inner.initializer.is_none());
         }
         _ => panic!(),
     }
@@ -365,13 +945,17 @@ fn parse_let_statement_bare_mut() {
         Statement::Let(inner) => {
             assert_eq!(inner.name.item, "foo".to_owned());
             assert_eq!(inner.mutability, Mutability::Mutable);
-            assert_eq!(inner.ty, None);
-            assert_eq!(inner.initializer, None);
+            assert!(inner.ty.is_none());
+            assert!(inner.initializer.is_none());
         }
         _ => panic!(),
     }
 }
 
+fn is_path_type(ty: &Option<crate::model::ast::Type>, name: &str) -> bool {
+    matches!(ty, Some(Spanned { item: TypeKind::Path(path), .. }) if path.len() == 1 && path[0].item == name)
+}
+
 #[test]
 fn parse_let_statement_typed() {
     use BasicToken::*;
@@ -394,14 +978,8 @@ fn parse_let_statement_typed() {
         Statement::Let(inner) => {
             assert_eq!(inner.name.item, "foo".to_owned());
             assert_eq!(inner.mutability, Mutability::Immutable);
-            assert_eq!(
-                inner.ty,
-                Some(Spanned::empty(TypeReprKind::Path(Path::new(
-                    false,
-                    vec![Spanned::empty("Foo".to_owned())],
-                ))))
-            );
-            assert_eq!(inner.initializer, None);
+            assert!(is_path_type(&inner.ty, "Foo"));
+            assert!(inner.initializer.is_none());
         }
         _ => panic!(),
     }
@@ -430,52 +1008,354 @@ fn parse_let_statement_typed_mut() {
         Statement::Let(inner) => {
             assert_eq!(inner.name.item, "foo".to_owned());
             assert_eq!(inner.mutability, Mutability::Mutable);
+            assert!(is_path_type(&inner.ty, "Foo"));
+            assert!(inner.initializer.is_none());
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_let_statement_initialized() {
+    let mut tokens = lex::tokenize("let foo: Foo = 5;").unwrap();
+
+    match statement(&mut tokens).unwrap() {
+        Statement::Let(inner) => {
+            assert_eq!(inner.name.item, "foo".to_owned());
+            assert!(is_path_type(&inner.ty, "Foo"));
+            assert_eq!(inner.initializer.as_ref().and_then(integer), Some(5));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_function_type() {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // fn(u8, &mut Buf) -> bool
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Keyword(Fn)),
+        Spanned::empty(Basic(LParen)),
+        Spanned::empty(Keyword(U8)),
+        Spanned::empty(Basic(Comma)),
+        Spanned::empty(Basic(Ampersand)),
+        Spanned::empty(Keyword(Mut)),
+        Spanned::empty(Identifier("Buf".to_owned())),
+        Spanned::empty(Basic(RParen)),
+        Spanned::empty(Basic(LightRArrow)),
+        Spanned::empty(Keyword(Bool)),
+    ]
+    .into_iter()
+    .collect();
+
+    let ty = type_repr(&mut tokens).unwrap();
+
+    match ty.item {
+        TypeKind::Function(parameters, Some(return_type)) => {
+            assert_eq!(parameters.len(), 2);
+            assert!(matches!(parameters[0].item, TypeKind::U8));
+            assert!(matches!(return_type.item, TypeKind::Bool));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_generic_type() {
+    let mut tokens = lex::tokenize("Result[Option[u8], Error]").unwrap();
+
+    match type_repr(&mut tokens).unwrap().item {
+        TypeKind::Generic(path, arguments) => {
+            assert_eq!(path[0].item, "Result".to_owned());
+            assert_eq!(arguments.len(), 2);
+            assert!(matches!(arguments[0].item, TypeKind::Generic(_, _)));
+            assert!(matches!(arguments[1].item, TypeKind::Path(_)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_item_attributes() {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // @inline @section("boot") fn main() {}
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Basic(At)),
+        Spanned::empty(Identifier("inline".to_owned())),
+        Spanned::empty(Basic(At)),
+        Spanned::empty(Identifier("section".to_owned())),
+        Spanned::empty(Basic(LParen)),
+        Spanned::empty(Literal(LiteralToken::String("boot".to_owned()))),
+        Spanned::empty(Basic(RParen)),
+        Spanned::empty(Keyword(Fn)),
+        Spanned::empty(Identifier("main".to_owned())),
+        Spanned::empty(Basic(LParen)),
+        Spanned::empty(Basic(RParen)),
+        Spanned::empty(Basic(LBrace)),
+        Spanned::empty(Basic(RBrace)),
+    ]
+    .into_iter()
+    .collect();
+
+    let parsed = item(&mut tokens).unwrap();
+
+    assert_eq!(parsed.attributes.len(), 2);
+    assert_eq!(parsed.attributes[0].name.item, "inline".to_owned());
+    assert!(parsed.attributes[0].arguments.is_empty());
+    assert_eq!(parsed.attributes[1].name.item, "section".to_owned());
+    assert_eq!(parsed.attributes[1].arguments.len(), 1);
+
+    match parsed.kind {
+        ItemKind::Function(_) => { /* */ }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_item_visibility() {
+    use BasicToken::*;
+    use KeywordToken::*;
+    use Token::*;
+
+    // pub(geode) struct Point { pub x: u8, y: u8 }
+    let mut tokens: Stream<Token> = vec![
+        Spanned::empty(Keyword(Pub)),
+        Spanned::empty(Basic(LParen)),
+        Spanned::empty(Identifier("geode".to_owned())),
+        Spanned::empty(Basic(RParen)),
+        Spanned::empty(Keyword(Struct)),
+        Spanned::empty(Identifier("Point".to_owned())),
+        Spanned::empty(Basic(LBrace)),
+        Spanned::empty(Keyword(Pub)),
+        Spanned::empty(Identifier("x".to_owned())),
+        Spanned::empty(Basic(Colon)),
+        Spanned::empty(Keyword(U8)),
+        Spanned::empty(Basic(Comma)),
+        Spanned::empty(Identifier("y".to_owned())),
+        Spanned::empty(Basic(Colon)),
+        Spanned::empty(Keyword(U8)),
+        Spanned::empty(Basic(RBrace)),
+    ]
+    .into_iter()
+    .collect();
+
+    let parsed = item(&mut tokens).unwrap();
+
+    assert_eq!(parsed.visibility, Visibility::Geode);
+
+    match parsed.kind {
+        ItemKind::Struct(StructItem { fields, .. }) => {
+            assert_eq!(fields[0].visibility, Visibility::Public);
+            assert_eq!(fields[1].visibility, Visibility::Private);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_mod_items() {
+    let mut tokens =
+        lex::tokenize("mod foo;\nmod bar {\n    mod baz;\n    fn main() {}\n}").unwrap();
+
+    let items = parse::items(&mut tokens).unwrap();
+
+    assert_eq!(items.len(), 2);
+    match &items[0].kind {
+        ItemKind::Mod(ModItem {
+            name,
+            body: ModBody::File(None),
+        }) => assert_eq!(name.item, "foo".to_owned()),
+        _ => panic!(),
+    }
+    match &items[1].kind {
+        ItemKind::Mod(ModItem {
+            body: ModBody::Inline(inner),
+            ..
+        }) => {
+            assert!(matches!(
+                inner[0].kind,
+                ItemKind::Mod(ModItem {
+                    body: ModBody::File(None),
+                    ..
+                })
+            ));
+            assert!(matches!(inner[1].kind, ItemKind::Function(_)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_use_items() {
+    let mut tokens =
+        lex::tokenize("use foo::{bar::gottem, baz as spanish, qux::*};\nuse top;").unwrap();
+
+    let items = parse::items(&mut tokens).unwrap();
+
+    let ItemKind::Use(UseTree {
+        prefix,
+        kind: UseTreeKind::Nested(trees, _),
+    }) = &items[0].kind
+    else {
+        panic!()
+    };
+
+    assert_eq!(prefix.len(), 1);
+    assert_eq!(trees.len(), 3);
+    assert!(
+        matches!(&trees[0], UseTree { prefix, kind: UseTreeKind::Simple(None) } if prefix.len() == 2)
+    );
+    assert!(
+        matches!(&trees[1], UseTree { kind: UseTreeKind::Simple(Some(alias)), .. } if alias.item == "spanish")
+    );
+    assert!(
+        matches!(&trees[2], UseTree { prefix, kind: UseTreeKind::Glob } if prefix[0].item == "qux")
+    );
+
+    assert!(matches!(
+        &items[1].kind,
+        ItemKind::Use(UseTree { prefix, kind: UseTreeKind::Simple(None) }) if prefix.len() == 1
+    ));
+}
+
+#[test]
+fn parse_trait_items() {
+    let mut tokens =
+        lex::tokenize("trait Shape {\n    fn area(&self) -> u32;\n    fn name() -> &str;\n}")
+            .unwrap();
+
+    let ItemKind::Trait(TraitItem { name, functions }) = item(&mut tokens).unwrap().kind else {
+        panic!()
+    };
+
+    assert_eq!(name.item, "Shape".to_owned());
+    assert_eq!(functions.len(), 2);
+    match &functions[0].kind {
+        ItemKind::Function(function) => {
             assert_eq!(
-                inner.ty,
-                Some(Spanned::empty(TypeReprKind::Path(Path::new(
-                    false,
-                    vec![Spanned::empty("Foo".to_owned())],
-                ))))
+                function.receiver.as_ref().map(|receiver| &receiver.item),
+                Some(&Receiver::Reference(Mutability::Immutable))
             );
-            assert_eq!(inner.initializer, None);
+            assert!(function.body.is_none());
         }
         _ => panic!(),
     }
+    assert!(matches!(
+        &functions[1].kind,
+        ItemKind::Function(FunctionItem {
+            receiver: None,
+            body: None,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn parse_receivers() {
+    let receiver = |source: &str| {
+        let mut tokens = lex::tokenize(source).unwrap();
+        let ItemKind::Trait(trait_) = item(&mut tokens).unwrap().kind else {
+            panic!()
+        };
+        match &trait_.functions[0].kind {
+            ItemKind::Function(function) => function.receiver.clone().map(|receiver| receiver.item),
+            _ => panic!(),
+        }
+    };
+
+    assert_eq!(receiver("trait T { fn f(self); }"), Some(Receiver::Value));
+    assert_eq!(
+        receiver("trait T { fn f(&mut self, x: u8); }"),
+        Some(Receiver::Reference(Mutability::Mutable))
+    );
+    assert_eq!(receiver("trait T { fn f(x: u8); }"), None);
+
+    // A receiver taken by value cannot be declared mutable.
+    let mut tokens = lex::tokenize("trait T { fn f(mut self); }").unwrap();
+    assert!(item(&mut tokens).is_err());
 }
 
-// #[test]
-// fn parse_let_statement_initialized() {
-//     use BasicToken::*;
-//     use KeywordToken::*;
-//     use LiteralToken::*;
-//     use Token::*;
-
-//     let mut tokens: Stream<Token> = vec![
-//         Spanned::empty(Keyword(Let)),
-//         Spanned::empty(Identifier("foo".to_owned())),
-//         Spanned::empty(Basic(Equal)),
-//         Spanned::empty(Literal(Integer(5))),
-//         Spanned::empty(Basic(Semicolon)),
-//     ]
-//     .into_iter()
-//     .collect();
-
-//     let stmt = statement(&mut tokens).unwrap();
-
-//     match stmt.item {
-//         StatementKind::Let(inner) => {
-//             assert_eq!(inner.name.item, "foo".to_owned());
-//             assert_eq!(inner.mutability, Mutability::Immutable);
-//             assert_eq!(
-//                 inner.ty,
-//                 Some(Spanned::empty(TypeReprKind::Path(Path::new(
-//                     false,
-//                     Spanned::empty("Foo".to_owned()),
-//                     Vec::new(),
-//                 ))))
-//             );
-//             assert_eq!(inner.initializer, None);
-//         }
-//         _ => assert!(false),
-//     }
-// }
+#[test]
+fn parse_generic_bounds() {
+    let mut tokens =
+        lex::tokenize("fn largest[T: Ord + Copy, U](value: T) -> T { value }").unwrap();
+
+    let ItemKind::Function(function) = item(&mut tokens).unwrap().kind else {
+        panic!()
+    };
+
+    assert_eq!(function.generics.len(), 2);
+    assert_eq!(function.generics[0].name.item, "T".to_owned());
+    let bounds = function.generics[0]
+        .bounds
+        .iter()
+        .map(|bound| bound[0].item.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(bounds, vec!["Ord", "Copy"]);
+    assert!(function.generics[1].bounds.is_empty());
+}
+
+#[test]
+fn parse_impl_items() {
+    let mut tokens = lex::tokenize(
+        "impl Shape for Square {\n    fn area(&self) -> u32 { 4 }\n}\nimpl Square {}",
+    )
+    .unwrap();
+
+    let items = parse::items(&mut tokens).unwrap();
+
+    match &items[0].kind {
+        ItemKind::Impl(ImplItem {
+            trait_: Some(trait_),
+            ty,
+            functions,
+        }) => {
+            assert_eq!(trait_[0].item, "Shape".to_owned());
+            assert!(matches!(&ty.item, TypeKind::Path(path) if path[0].item == "Square"));
+            assert_eq!(functions.len(), 1);
+        }
+        _ => panic!(),
+    }
+    assert!(matches!(
+        &items[1].kind,
+        ItemKind::Impl(ImplItem { trait_: None, .. })
+    ));
+}
+
+#[test]
+fn parse_global_path() {
+    let mut tokens = lex::tokenize("::a::B").unwrap();
+    let ty = type_repr(&mut tokens).unwrap();
+
+    // The leading `::` is kept as an empty segment spanning it.
+    match ty.item {
+        TypeKind::Path(path) => {
+            let names: Vec<_> = path.iter().map(|segment| segment.item.as_str()).collect();
+            assert_eq!(names, vec!["", "a", "B"]);
+            assert_eq!(path[0].span, Span::new(0, 2));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(ty.span, Span::new(0, 6));
+}
+
+#[test]
+fn parse_test_sources() {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../opal_tests");
+
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        let source = std::fs::read_to_string(&path).unwrap();
+
+        let mut tokens = lex::tokenize(&source).unwrap();
+        if let Err(error) = parse::items(&mut tokens) {
+            panic!("{}: {}", path.display(), error.message);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::model::ast::{Syntax, TypeKind};
+use crate::model::base::*;
+use crate::source::FileId;
+use crate::span::{Span, Spanned};
+
+pub const NAME: &str = "core";
+pub const OPTION: &str = "Option";
+pub const RESULT: &str = "Result";
+
+/// The module of items every geode can name without importing them, which name resolution
+/// adds after the geode's own modules:
+///
+/// ```text
+/// pub enum Option[T] { Some(T), None }
+/// pub enum Result[T, E] { Ok(T), Err(E) }
+/// use Option::*;
+/// use Result::*;
+/// ```
+///
+/// Its items are built directly rather than parsed, so they have no spans of their own, and
+/// the module borrows the file of the geode's root module.
+pub fn module(file: FileId) -> Module<Syntax> {
+    Module {
+        name: Spanned::empty(String::from(NAME)),
+        items: vec![
+            enumeration(
+                OPTION,
+                &["T"],
+                vec![("Some", vec!["T"]), ("None", Vec::new())],
+            ),
+            enumeration(
+                RESULT,
+                &["T", "E"],
+                vec![("Ok", vec!["T"]), ("Err", vec!["E"])],
+            ),
+            glob(OPTION),
+            glob(RESULT),
+        ],
+        file,
+    }
+}
+
+fn item(kind: ItemKind<Syntax>, visibility: Visibility) -> Item<Syntax> {
+    Item {
+        kind,
+        attributes: Vec::new(),
+        visibility,
+        span: Span::new(0, 0),
+    }
+}
+
+fn enumeration(name: &str, generics: &[&str], variants: Vec<(&str, Vec<&str>)>) -> Item<Syntax> {
+    let generics = generics
+        .iter()
+        .map(|generic| GenericParameter {
+            name: Spanned::empty((*generic).to_owned()),
+            bounds: Vec::new(),
+            span: Span::new(0, 0),
+        })
+        .collect();
+
+    let variants = variants
+        .into_iter()
+        .map(|(name, fields)| Variant {
+            name: Spanned::empty(name.to_owned()),
+            kind: match fields.is_empty() {
+                true => VariantKind::Unit,
+                false => VariantKind::Tuple(
+                    fields
+                        .into_iter()
+                        .map(|field| {
+                            Spanned::empty(TypeKind::Path(vec![Spanned::empty(field.to_owned())]))
+                        })
+                        .collect(),
+                ),
+            },
+            attributes: Vec::new(),
+            visibility: Visibility::Public,
+            span: Span::new(0, 0),
+        })
+        .collect();
+
+    item(
+        ItemKind::Enum(EnumItem {
+            name: Spanned::empty(name.to_owned()),
+            generics,
+            variants,
+        }),
+        Visibility::Public,
+    )
+}
+
+/// `use name::*`, which brings the variants of an enum into scope.
+fn glob(name: &str) -> Item<Syntax> {
+    item(
+        ItemKind::Use(UseTree {
+            prefix: vec![Spanned::empty(name.to_owned())],
+            kind: UseTreeKind::Glob,
+        }),
+        Visibility::Private,
+    )
+}
@@ -0,0 +1,159 @@
+use crate::error::Error;
+use crate::model::base::Visibility;
+use crate::span::Span;
+
+/// The position of a module within its geode, as the names of the modules leading to it from
+/// the root module, whose path is empty. Inline and out-of-line modules are treated alike.
+pub type ModulePath = Vec<String>;
+
+/// A declaration as the privacy checks see it: its visibility, and the module it belongs to.
+/// For a field, `module` is the module of the struct or variant declaring it.
+#[derive(Debug, Clone, Copy)]
+pub struct Declaration<'a> {
+    pub name: &'a str,
+    pub visibility: Visibility,
+    pub module: &'a [String],
+    pub span: Span,
+}
+
+/// Whether a declaration made in `module` may be named from within `from`. Until geodes can
+/// depend on one another, `pub` and `pub(geode)` grant the same access.
+pub fn accessible(visibility: Visibility, module: &[String], from: &[String]) -> bool {
+    match visibility {
+        Visibility::Public | Visibility::Geode => true,
+        Visibility::Private => from.starts_with(module),
+    }
+}
+
+/// Checks a path that name resolution has resolved segment by segment, pairing each segment's
+/// declaration with the span it is named at. Every segment must be accessible, so a public
+/// item is still out of reach through a private module.
+pub fn check_path(segments: &[(Declaration, Span)], from: &[String]) -> Result<(), Error> {
+    for (declaration, span) in segments {
+        if !accessible(declaration.visibility, declaration.module, from) {
+            return Err(private(
+                format!("`{}` is private", declaration.name),
+                declaration,
+                *span,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks `value.field` on a value of the struct `owner`.
+pub fn check_field(
+    owner: &str,
+    field: &Declaration,
+    from: &[String],
+    span: Span,
+) -> Result<(), Error> {
+    match accessible(field.visibility, field.module, from) {
+        true => Ok(()),
+        false => Err(private(
+            format!("Field `{}` of `{owner}` is private", field.name),
+            field,
+            span,
+        )),
+    }
+}
+
+fn private(message: String, declaration: &Declaration, span: Span) -> Error {
+    Error::with_details(
+        span,
+        message,
+        format!(
+            "It can only be named within {}",
+            describe(declaration.module)
+        ),
+    )
+    .with_note(
+        declaration.span,
+        format!("`{}` is declared here", declaration.name),
+    )
+}
+
+fn describe(module: &[String]) -> String {
+    match module.is_empty() {
+        true => String::from("the root module"),
+        false => format!("module `{}`", module.join("::")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resolve::resolve;
+    use crate::testing::{self, messages, notes, span, span_in};
+
+    #[test]
+    fn private_within_submodules() {
+        let source = "
+            mod net {
+                const PORT: u8 = 80;
+                pub(geode) const HOST: u8 = 1;
+                fn port() -> u8 { PORT }
+                mod tcp { fn port() -> u8 { net::PORT } }
+            }
+            mod fs { fn host() -> u8 { net::HOST } }
+            fn main() { let port = net::PORT; }
+        ";
+
+        // A private item can be named within its module and the modules nested in it.
+        let errors = resolve(testing::parse(source)).err().unwrap();
+
+        assert_eq!(
+            messages(&errors),
+            vec![(
+                "`PORT` is private",
+                Some("It can only be named within module `net`"),
+                span_in(source, "port = net::PORT", "PORT")
+            )]
+        );
+    }
+
+    #[test]
+    fn public_item_in_private_module() {
+        let source = "
+            mod net {
+                mod tcp { pub fn connect() {} }
+                pub fn open() { tcp::connect(); }
+            }
+            fn main() { net::tcp::connect(); }
+        ";
+
+        // `connect` is public, but it is out of reach through the private `tcp`.
+        let errors = resolve(testing::parse(source)).err().unwrap();
+
+        assert_eq!(
+            notes(&errors),
+            vec![(
+                "`tcp` is private",
+                span_in(source, "net::tcp", "tcp"),
+                vec![span(source, "tcp")]
+            )]
+        );
+    }
+
+    #[test]
+    fn fields() {
+        let source = "
+            mod shapes {
+                pub struct Rect { pub width: u8, area: u8 }
+                fn area(r: Rect) -> u8 { r.area }
+            }
+            fn f(r: shapes::Rect) -> u8 { r.width + r.area }
+        ";
+
+        let errors = testing::type_errors(source);
+
+        assert_eq!(
+            notes(&errors),
+            vec![(
+                "Field `area` of `Rect` is private",
+                span_in(source, "r.width + r.area", "area"),
+                vec![span_in(source, "area: u8", "area")]
+            )]
+        );
+    }
+}
@@ -0,0 +1,635 @@
+use crate::decision;
+use crate::error::Error;
+use crate::layout::{EnumLayout, Layouts};
+use crate::model::def::{DefId, Definitions};
+use crate::model::ir::{LabelId, Statement, VariableId};
+use crate::model::types::Type;
+use crate::prelude;
+use crate::span::Span;
+use crate::typeck::{Tables, VariantShape};
+
+/// How `operand?` unwraps its operand, an `Option` or a `Result`. On `Some` or `Ok` it produces
+/// the payload. On `None` or `Err` it returns early from the enclosing function, whose return
+/// type must be the same enum, with the error converted to the function's error type if they
+/// differ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Propagation {
+    pub output: Type,        // The type of `operand?`, the payload of `Some` or `Ok`
+    pub success: DefId,      // `Some` or `Ok`
+    pub residual: DefId,     // `None` or `Err`, the variant that is returned early
+    pub error: Option<Type>, // The payload of `Err`, when the operand is a `Result`
+    // The variant of the function's error type that wraps `error`, along with that type, when
+    // the two differ.
+    pub conversion: Option<(DefId, Type)>,
+}
+
+/// Checks `operand?` within a function returning `return_type`.
+///
+/// An `Option` may only be propagated out of a function returning an `Option`, and a
+/// `Result[T, E]` out of one returning a `Result[U, F]`. The error is returned as it is if `E`
+/// and `F` are the same type. Otherwise `F` must be an enum with exactly one variant holding
+/// just an `E`, such as `Io(E)`, which the error is wrapped in.
+pub fn propagation(
+    operand: &Type,
+    return_type: &Type,
+    tables: &Tables,
+    definitions: &Definitions,
+    span: Span,
+) -> Result<Propagation, Error> {
+    let option = definitions.core(prelude::OPTION);
+    let result = definitions.core(prelude::RESULT);
+
+    let (def, arguments) = match operand {
+        Type::Named(def, _, arguments) if Some(*def) == option || Some(*def) == result => {
+            (*def, arguments)
+        }
+        _ => {
+            return Err(Error::with_details(
+                span,
+                String::from("The `?` operator can only be applied to an `Option` or a `Result`"),
+                format!("Found `{operand}`"),
+            ))
+        }
+    };
+
+    let name = &definitions.get(def).name;
+    let article = match name.starts_with(['A', 'E', 'I', 'O', 'U']) {
+        true => "an",
+        false => "a",
+    };
+    let returned = match return_type {
+        Type::Named(other, _, returned) if *other == def => returned,
+        _ => {
+            return Err(Error::with_details(
+                span,
+                format!(
+                    "The `?` operator on {article} `{name}` can only be used in a function returning `{name}`"
+                ),
+                format!("This function returns `{return_type}`"),
+            ))
+        }
+    };
+
+    let [success, residual] = tables.variants_of(def)[..] else {
+        unreachable!("`Option` and `Result` have two variants each");
+    };
+
+    let mut propagation = Propagation {
+        output: arguments[0].clone(),
+        success,
+        residual,
+        error: None,
+        conversion: None,
+    };
+
+    if Some(def) == result {
+        let (error, expected) = (&arguments[1], &returned[1]);
+
+        // An error type that failed to check was already reported.
+        if error != expected && *error != Type::Never && *expected != Type::Never {
+            let variant = conversion(error, expected, tables, definitions, span)?;
+            propagation.conversion = Some((variant, expected.clone()));
+        }
+
+        propagation.error = Some(error.clone());
+    }
+
+    Ok(propagation)
+}
+
+/// The variant of `expected` that an error of type `error` is wrapped in, which must be the only
+/// one holding just an `error`.
+fn conversion(
+    error: &Type,
+    expected: &Type,
+    tables: &Tables,
+    definitions: &Definitions,
+    span: Span,
+) -> Result<DefId, Error> {
+    let substitution = tables.substitution(expected);
+    let candidates = match expected {
+        Type::Named(def, _, _) => tables
+            .variants_of(*def)
+            .into_iter()
+            .filter(|variant| match &tables.variants[variant].shape {
+                VariantShape::Tuple(types) => {
+                    types.len() == 1 && types[0].substitute(&substitution) == *error
+                }
+                _ => false,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    match candidates[..] {
+        [variant] => Ok(variant),
+        [] => Err(Error::with_details(
+            span,
+            format!("The `?` operator cannot convert the error type `{error}` into `{expected}`"),
+            format!("The function's error type must be `{error}`, or an enum with a variant holding just a `{error}`"),
+        )),
+        _ => {
+            let mut ambiguous = Error::with_details(
+                span,
+                format!("The `?` operator cannot decide how to convert the error type `{error}` into `{expected}`"),
+                format!("`{expected}` has {} variants holding just a `{error}`", candidates.len()),
+            );
+
+            for variant in candidates {
+                ambiguous = ambiguous.with_note(
+                    definitions.get(variant).span,
+                    String::from("The error could be wrapped in this variant"),
+                );
+            }
+
+            Err(ambiguous)
+        }
+    }
+}
+
+/// What lowering `?` needs from the lowering of the function around it.
+pub trait Lower {
+    fn variable(&mut self) -> VariableId;
+    fn label(&mut self) -> LabelId;
+    /// Lowers the deferred expressions of every enclosing block, which run before returning.
+    fn deferred(&mut self) -> Vec<Statement>;
+}
+
+/// Lowers `operand?` to a branch on the variant of the operand, given the variable holding it.
+/// The residual is rebuilt as a value of the function's return type and returned early, after
+/// the deferred expressions of the blocks being left. Otherwise control continues after the
+/// statements, with the payload in the variable returned alongside them.
+pub fn lower(
+    propagation: &Propagation,
+    operand: (VariableId, &Type),
+    return_type: &Type,
+    layouts: &Layouts,
+    lower: &mut impl Lower,
+) -> (Vec<Statement>, VariableId) {
+    let (scrutinee, operand_type) = operand;
+    let layout = enum_layout(layouts, operand_type);
+
+    let success = lower.label();
+    let failure = lower.label();
+    let target = |variant: DefId| match variant == propagation.success {
+        true => success,
+        false => failure,
+    };
+
+    let tag = lower.variable();
+    let mut statements = decision::switch_variant(layout, scrutinee, tag, target, success);
+    statements.push(Statement::Label(failure));
+
+    let mut fields = Vec::new();
+
+    if let Some(error) = &propagation.error {
+        let mut value = lower.variable();
+        let mut size = layouts.of(error).size;
+        statements.push(Statement::Project {
+            target: value,
+            source: scrutinee,
+            offset: payload(layout, propagation.residual),
+            size,
+        });
+
+        if let Some((variant, wrapper)) = &propagation.conversion {
+            let wrapped = lower.variable();
+            let layout = enum_layout(layouts, wrapper);
+            statements.extend(decision::construct(
+                layout,
+                *variant,
+                wrapped,
+                &[(value, size)],
+            ));
+            (value, size) = (wrapped, layout.layout.size);
+        }
+
+        fields.push((value, size));
+    }
+
+    let residual = lower.variable();
+    let returned = enum_layout(layouts, return_type);
+    statements.extend(decision::construct(
+        returned,
+        propagation.residual,
+        residual,
+        &fields,
+    ));
+    statements.extend(lower.deferred());
+    statements.push(Statement::Return(residual));
+
+    let output = lower.variable();
+    statements.push(Statement::Label(success));
+    statements.push(Statement::Project {
+        target: output,
+        source: scrutinee,
+        offset: payload(layout, propagation.success),
+        size: layouts.of(&propagation.output).size,
+    });
+
+    (statements, output)
+}
+
+fn enum_layout<'a>(layouts: &'a Layouts, ty: &Type) -> &'a EnumLayout {
+    match ty {
+        Type::Named(def, _, arguments) => layouts.of_enum(*def, arguments),
+        _ => None,
+    }
+    .expect("`?` is only applied to enums whose layouts were computed")
+}
+
+/// The offset of the only field of a variant's payload.
+fn payload(layout: &EnumLayout, variant: DefId) -> u32 {
+    layout
+        .variants
+        .iter()
+        .find(|layout| layout.variant == variant)
+        .and_then(|layout| layout.offsets.first().copied())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::{self, DataModel};
+    use crate::model::ast::{self, Syntax};
+    use crate::model::base::{
+        EnumItem, Expression, ExpressionKind, ExpressionWithoutBlock, FunctionItem, Geode, Item,
+        ItemKind, Module, Mutability, Parameter, Statements, Variant, VariantKind, Visibility,
+    };
+    use crate::model::typed::Typed;
+    use crate::propagate::*;
+    use crate::testing::{self, check_items, expression, ident, item, messages, ty, variable};
+    use crate::typeck::{self, def_of};
+
+    fn generic(name: &str, arguments: Vec<ast::TypeKind>, start: usize) -> ast::Type {
+        let arguments = arguments
+            .into_iter()
+            .map(|argument| ty(argument, start))
+            .collect();
+
+        ty(
+            ast::TypeKind::Generic(vec![ident(name, start)], arguments),
+            start,
+        )
+    }
+
+    fn named(name: &str) -> ast::TypeKind {
+        ast::TypeKind::Path(vec![ident(name, 0)])
+    }
+
+    fn wrap(variant: &str, inner: Expression<Syntax>) -> Expression<Syntax> {
+        let start = inner.span.start - 1;
+        expression(
+            ExpressionWithoutBlock::Call(Box::new(variable(variant, start)), vec![inner], ()),
+            start,
+        )
+    }
+
+    fn question(operand: Expression<Syntax>) -> Expression<Syntax> {
+        let start = operand.span.start;
+        expression(
+            ExpressionWithoutBlock::ErrorPropagation(Box::new(operand), ()),
+            start,
+        )
+    }
+
+    /// `fn name(x: parameter) -> return_type { tail }`
+    fn function(
+        name: &str,
+        start: usize,
+        parameter: ast::Type,
+        return_type: ast::Type,
+        tail: Expression<Syntax>,
+    ) -> Item<Syntax> {
+        testing::function(
+            name,
+            start,
+            vec![("x", parameter)],
+            Some(return_type),
+            Vec::new(),
+            Some(tail),
+        )
+    }
+
+    /// `enum name { A(u8), B(bool), ... }`, with a variant for each of `fields`.
+    fn enumeration(name: &str, start: usize, fields: Vec<ast::TypeKind>) -> Item<Syntax> {
+        let variants = fields
+            .into_iter()
+            .enumerate()
+            .map(|(index, field)| Variant {
+                name: ident(
+                    &((b'A' + index as u8) as char).to_string(),
+                    start + index + 1,
+                ),
+                kind: VariantKind::Tuple(vec![ty(field, start + index + 1)]),
+                attributes: Vec::new(),
+                visibility: Visibility::Public,
+                span: Span::new(start + index + 1, start + index + 2),
+            })
+            .collect();
+
+        item(ItemKind::Enum(EnumItem {
+            name: ident(name, start),
+            generics: Vec::new(),
+            variants,
+        }))
+    }
+
+    fn core(definitions: &Definitions, name: &str, arguments: Vec<Type>) -> Type {
+        let def = definitions.core(name).unwrap();
+        Type::Named(def, name.to_owned(), arguments)
+    }
+
+    /// The type of the enum declared first in the geode.
+    fn first_enum(geode: &Geode<Typed>) -> Type {
+        match &geode.modules[0].items[0].kind {
+            ItemKind::Enum(enumeration) => Type::Named(
+                def_of(&enumeration.name),
+                enumeration.name.ident.item.clone(),
+                Vec::new(),
+            ),
+            _ => panic!("Expected an enum"),
+        }
+    }
+
+    #[test]
+    fn options() {
+        use ast::TypeKind::*;
+
+        // fn f(x: Option[u8]) -> Option[u8] { Some(x?) }
+        check_items(vec![function(
+            "f",
+            10,
+            generic("Option", vec![U8], 20),
+            generic("Option", vec![U8], 30),
+            wrap("Some", question(variable("x", 41))),
+        )])
+        .unwrap();
+
+        // fn f(x: Option[u8]) -> u8 { x? }
+        // fn g(x: u8) -> Option[u8] { Some(x?) }
+        let errors = check_items(vec![
+            function(
+                "f",
+                10,
+                generic("Option", vec![U8], 20),
+                ty(U8, 30),
+                question(variable("x", 41)),
+            ),
+            function(
+                "g",
+                50,
+                ty(U8, 60),
+                generic("Option", vec![U8], 70),
+                wrap("Some", question(variable("x", 81))),
+            ),
+        ])
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            messages(&errors),
+            vec![
+                (
+                    "The `?` operator on an `Option` can only be used in a function returning `Option`",
+                    Some("This function returns `u8`"),
+                    Span::new(41, 42)
+                ),
+                (
+                    "The `?` operator can only be applied to an `Option` or a `Result`",
+                    Some("Found `u8`"),
+                    Span::new(81, 82)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn results() {
+        let source = "fn f(x: Result[u8, bool]) -> u8 { x? }";
+        let (geode, definitions) = testing::resolve(source);
+
+        let errors = typeck::check(geode, &definitions, &Type::I32)
+            .err()
+            .unwrap();
+
+        let start = source.find("x?").unwrap();
+        assert_eq!(
+            messages(&errors),
+            vec![(
+                "The `?` operator on a `Result` can only be used in a function returning `Result`",
+                Some("This function returns `u8`"),
+                Span::new(start, start + 2)
+            )]
+        );
+    }
+
+    #[test]
+    fn error_conversion() {
+        use ast::TypeKind::*;
+
+        // enum Error { A(u8), B(bool) }
+        // fn f(x: Result[u8, bool]) -> Result[u8, Error] { Ok(x?) }
+        let (geode, tables, definitions) = check_items(vec![
+            enumeration("Error", 90, vec![U8, Bool]),
+            function(
+                "f",
+                10,
+                generic("Result", vec![U8, Bool], 20),
+                generic("Result", vec![U8, named("Error")], 30),
+                wrap("Ok", question(variable("x", 41))),
+            ),
+        ])
+        .unwrap();
+
+        let error = first_enum(&geode);
+        let operand = core(&definitions, prelude::RESULT, vec![Type::U8, Type::Bool]);
+        let returned = core(&definitions, prelude::RESULT, vec![Type::U8, error.clone()]);
+        let propagation = propagation(
+            &operand,
+            &returned,
+            &tables,
+            &definitions,
+            Span::new(41, 42),
+        )
+        .unwrap();
+
+        assert_eq!(propagation.output, Type::U8);
+        assert_eq!(propagation.error, Some(Type::Bool));
+        assert_eq!(
+            propagation
+                .conversion
+                .map(|(variant, ty)| (definitions.get(variant).name.clone(), ty)),
+            Some((String::from("B"), error))
+        );
+
+        // enum Twice { A(bool), B(bool) }
+        // fn f(x: Result[u8, bool]) -> Result[u8, Twice] { Ok(x?) }
+        // fn g(x: Result[u8, u8]) -> Result[u8, bool] { Ok(x?) }
+        let errors = check_items(vec![
+            enumeration("Twice", 90, vec![Bool, Bool]),
+            function(
+                "f",
+                10,
+                generic("Result", vec![U8, Bool], 20),
+                generic("Result", vec![U8, named("Twice")], 30),
+                wrap("Ok", question(variable("x", 41))),
+            ),
+            function(
+                "g",
+                50,
+                generic("Result", vec![U8, U8], 60),
+                generic("Result", vec![U8, Bool], 70),
+                wrap("Ok", question(variable("x", 81))),
+            ),
+        ])
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            messages(&errors),
+            vec![
+                (
+                    "The `?` operator cannot decide how to convert the error type `bool` into `Twice`",
+                    Some("`Twice` has 2 variants holding just a `bool`"),
+                    Span::new(41, 42)
+                ),
+                (
+                    "The `?` operator cannot convert the error type `u8` into `bool`",
+                    Some("The function's error type must be `u8`, or an enum with a variant holding just a `u8`"),
+                    Span::new(81, 82)
+                ),
+            ]
+        );
+        assert_eq!(
+            errors[0]
+                .notes
+                .iter()
+                .map(|note| note.span)
+                .collect::<Vec<_>>(),
+            vec![Span::new(91, 92), Span::new(92, 93)]
+        );
+    }
+
+    struct Builder {
+        next: usize,
+    }
+
+    impl Lower for Builder {
+        fn variable(&mut self) -> VariableId {
+            self.next += 1;
+            self.next
+        }
+
+        fn label(&mut self) -> LabelId {
+            self.next += 1;
+            self.next
+        }
+
+        // Stands in for the deferred expressions, to check that they run before returning.
+        fn deferred(&mut self) -> Vec<Statement> {
+            vec![Statement::Unreachable]
+        }
+    }
+
+    #[test]
+    fn lowering() {
+        use ast::TypeKind::*;
+
+        // enum Error { A(u8), B(bool) }
+        // fn f(x: Result[u8, bool]) -> Result[u8, Error] { Ok(x?) }
+        let (geode, tables, definitions) = check_items(vec![
+            enumeration("Error", 90, vec![U8, Bool]),
+            function(
+                "f",
+                10,
+                generic("Result", vec![U8, Bool], 20),
+                generic("Result", vec![U8, named("Error")], 30),
+                wrap("Ok", question(variable("x", 41))),
+            ),
+        ])
+        .unwrap();
+
+        let layouts = layout::compute(&geode, DataModel::BITS_32).unwrap();
+        let error = first_enum(&geode);
+        let operand = core(&definitions, prelude::RESULT, vec![Type::U8, Type::Bool]);
+        let returned = core(&definitions, prelude::RESULT, vec![Type::U8, error]);
+        let propagation = propagation(
+            &operand,
+            &returned,
+            &tables,
+            &definitions,
+            Span::new(41, 42),
+        )
+        .unwrap();
+
+        let mut builder = Builder { next: 0 };
+        let (statements, output) = lower(
+            &propagation,
+            (0, &operand),
+            &returned,
+            &layouts,
+            &mut builder,
+        );
+
+        // Labels 1 and 2 are success and failure. The tag is read into 3, the error into 4,
+        // the wrapped error into 5 and the residual into 6.
+        assert!(matches!(
+            statements.as_slice(),
+            [
+                Statement::Project {
+                    target: 3,
+                    source: 0,
+                    offset: 0,
+                    size: 1
+                },
+                Statement::Switch {
+                    scrutinee: 3,
+                    default: 1,
+                    ..
+                },
+                Statement::Label(2),
+                Statement::Project {
+                    target: 4,
+                    source: 0,
+                    offset: 1,
+                    size: 1
+                },
+                Statement::InsertImmediate {
+                    target: 5,
+                    offset: 0,
+                    value: 1,
+                    size: 1
+                },
+                Statement::Insert {
+                    target: 5,
+                    offset: 1,
+                    source: 4,
+                    size: 1
+                },
+                Statement::InsertImmediate {
+                    target: 6,
+                    offset: 0,
+                    value: 1,
+                    size: 1
+                },
+                Statement::Insert {
+                    target: 6,
+                    offset: 1,
+                    source: 5,
+                    size: 2
+                },
+                Statement::Unreachable,
+                Statement::Return(6),
+                Statement::Label(1),
+                Statement::Project {
+                    target: 7,
+                    source: 0,
+                    offset: 1,
+                    size: 1
+                },
+            ]
+        ));
+        assert_eq!(output, 7);
+    }
+}
@@ -0,0 +1,1283 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::model::ast::{self, Syntax, TypeKind};
+use crate::model::base::*;
+use crate::model::def::{DefId, DefKind, Definition, Definitions};
+use crate::model::fold::{walk_item, walk_module, walk_pattern, Folder};
+use crate::model::resolved::*;
+use crate::model::visit::{NameContext, PathContext};
+use crate::prelude;
+use crate::privacy;
+use crate::span::{Span, Spanned};
+
+type Ident = Spanned<String>;
+
+/// Types and values are named separately, so that a struct and a function may share a name.
+/// Modules, structs, enums, traits, type aliases and type parameters are types, while
+/// functions, constants, statics and locals are values. Variants are both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Namespace {
+    Type,
+    Value,
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Namespace::Type => write!(f, "type"),
+            Namespace::Value => write!(f, "value"),
+        }
+    }
+}
+
+/// The names declared in or imported into a module, or declared within an enum or trait.
+#[derive(Debug, Default)]
+struct Scope {
+    types: HashMap<String, Binding>,
+    values: HashMap<String, Binding>,
+    globs: Vec<DefId>, // Scopes imported with `use prefix::*`, which any other binding shadows
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    def: DefId,
+    span: Span, // Where the name is bound, which is either its declaration or an import
+}
+
+type ImportTarget = (Vec<(Namespace, DefId)>, Vec<(DefId, Span)>);
+
+/// A `use` tree flattened to a single import.
+struct Import {
+    module: DefId,
+    path: Vec<Ident>,
+    kind: ImportKind,
+}
+
+enum ImportKind {
+    Single(Ident), // The name the import binds, which is the path's last segment unless renamed
+    Glob,
+}
+
+/// The bindings introduced by one block of a function body, or by its signature. Later `let`
+/// statements in the same block replace earlier bindings of the same name, shadowing them.
+#[derive(Debug, Default)]
+struct Rib {
+    values: HashMap<String, (LocalId, Span)>,
+    types: HashMap<String, DefId>,
+}
+
+/// Resolves every name in the geode, producing the `Resolved` phase along with the table of
+/// definitions that its resolutions refer to.
+///
+/// Resolution happens in three steps. Every item is first declared in the scope of its module,
+/// enum or trait, so that items may be used before they are declared. Imports are then
+/// resolved repeatedly until no more of them can be, since one import may depend on another.
+/// Finally the tree is folded into the `Resolved` phase, resolving paths within function
+/// bodies against the ribs of enclosing blocks before the scope of the enclosing module.
+///
+/// Paths start from the current module, or from the root module if the current module has no
+/// binding for their first segment, or else from the core module. The core module is added to
+/// the geode after its own modules, so that `Option` and `Result` can be used anywhere without
+/// importing them. See `prelude`.
+pub fn resolve(mut geode: Geode<Syntax>) -> Result<(Geode<Resolved>, Definitions), Vec<Error>> {
+    let file = geode.modules.first().map_or(0, |root| root.file);
+    geode.modules.push(prelude::module(file));
+
+    let mut definitions = Definitions::new();
+    let root = definitions.define(Definition {
+        kind: DefKind::Mod,
+        name: geode.name.item.clone(),
+        parent: None,
+        visibility: Visibility::Public,
+        span: geode.name.span,
+    });
+    let core = definitions.define(Definition {
+        kind: DefKind::Mod,
+        name: String::from(prelude::NAME),
+        parent: Some(root),
+        visibility: Visibility::Public,
+        span: Span::new(0, 0),
+    });
+    definitions.set_core(core);
+
+    let mut resolver = Resolver {
+        definitions,
+        scopes: HashMap::from([(root, Scope::default()), (core, Scope::default())]),
+        imports: Vec::new(),
+        order: Vec::new(),
+        next: 0,
+        root,
+        core,
+        current: root,
+        item: root,
+        ribs: Vec::new(),
+        locals: 0,
+        modules: geode.modules.iter().map(|_| None).collect(),
+        errors: Vec::new(),
+    };
+
+    // A module file is always numbered after the module declaring it, so it is declared
+    // before its own items are collected.
+    if let Some(first) = resolver.modules.first_mut() {
+        *first = Some(root);
+    }
+    if let Some(last) = resolver.modules.last_mut() {
+        *last = Some(core);
+    }
+    for (id, module) in geode.modules.iter().enumerate() {
+        if let Some(def) = resolver.modules[id] {
+            resolver.collect(&module.items, def);
+        }
+    }
+    resolver.resolve_imports();
+
+    let geode = resolver.fold_geode(geode);
+
+    match resolver.errors.is_empty() {
+        true => Ok((geode, resolver.definitions)),
+        false => Err(resolver.errors),
+    }
+}
+
+struct Resolver {
+    definitions: Definitions,
+    scopes: HashMap<DefId, Scope>,
+    imports: Vec<Import>,
+    // Items, variants and associated functions are declared before the tree is folded, in
+    // the same order that the fold visits them in, so `next` indexes into `order`.
+    order: Vec<DefId>,
+    next: usize,
+    root: DefId,
+    core: DefId,
+    current: DefId, // The module whose items are being folded
+    item: DefId,    // The item declared most recently, which owns the type parameters that follow
+    ribs: Vec<Rib>,
+    locals: usize,
+    modules: Vec<Option<DefId>>, // The definition of each module file, once its `mod` item is collected
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    fn collect(&mut self, items: &[Item<Syntax>], module: DefId) {
+        use Namespace::*;
+
+        for item in items {
+            let visibility = item.visibility;
+
+            match &item.kind {
+                ItemKind::Mod(ModItem { name, body }) => {
+                    let def = self.declare(DefKind::Mod, name, module, visibility, &[Type]);
+                    self.scopes.insert(def, Scope::default());
+
+                    match body {
+                        ModBody::Inline(items) => self.collect(items, def),
+                        ModBody::File(Some(id)) => self.modules[*id] = Some(def),
+                        ModBody::File(None) => { /* Reported when loading the geode */ }
+                    }
+                }
+                ItemKind::Use(tree) => self.collect_imports(tree, &[], module),
+                ItemKind::Function(function) => {
+                    self.declare(DefKind::Fn, &function.name, module, visibility, &[Value]);
+                }
+                ItemKind::TypeAlias(alias) => {
+                    self.declare(DefKind::TypeAlias, &alias.name, module, visibility, &[Type]);
+                }
+                ItemKind::Struct(struct_item) => {
+                    self.declare(
+                        DefKind::Struct,
+                        &struct_item.name,
+                        module,
+                        visibility,
+                        &[Type],
+                    );
+                }
+                ItemKind::Enum(enum_item) => {
+                    let def =
+                        self.declare(DefKind::Enum, &enum_item.name, module, visibility, &[Type]);
+                    self.scopes.insert(def, Scope::default());
+
+                    for variant in &enum_item.variants {
+                        self.declare(
+                            DefKind::Variant,
+                            &variant.name,
+                            def,
+                            variant.visibility,
+                            &[Type, Value],
+                        );
+                    }
+                }
+                ItemKind::Const(const_item) => {
+                    self.declare(
+                        DefKind::Const,
+                        &const_item.name,
+                        module,
+                        visibility,
+                        &[Value],
+                    );
+                }
+                ItemKind::Static(static_item) => {
+                    self.declare(
+                        DefKind::Static,
+                        &static_item.name,
+                        module,
+                        visibility,
+                        &[Value],
+                    );
+                }
+                ItemKind::Trait(trait_item) => {
+                    let def = self.declare(
+                        DefKind::Trait,
+                        &trait_item.name,
+                        module,
+                        visibility,
+                        &[Type],
+                    );
+                    self.scopes.insert(def, Scope::default());
+
+                    // A trait's functions are as visible as the trait.
+                    for function in &trait_item.functions {
+                        if let ItemKind::Function(signature) = &function.kind {
+                            self.declare(
+                                DefKind::AssocFn,
+                                &signature.name,
+                                def,
+                                Visibility::Public,
+                                &[Value],
+                            );
+                        }
+                    }
+                }
+                ItemKind::Impl(impl_item) => {
+                    // Functions within an `impl` are only ever named relative to their type,
+                    // so they are declared without being bound in any scope.
+                    for function in &impl_item.functions {
+                        if let ItemKind::Function(method) = &function.kind {
+                            self.declare(
+                                DefKind::AssocFn,
+                                &method.name,
+                                module,
+                                function.visibility,
+                                &[],
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn declare(
+        &mut self,
+        kind: DefKind,
+        name: &Ident,
+        parent: DefId,
+        visibility: Visibility,
+        namespaces: &[Namespace],
+    ) -> DefId {
+        let def = self.definitions.define(Definition {
+            kind,
+            name: name.item.clone(),
+            parent: Some(parent),
+            visibility,
+            span: name.span,
+        });
+        self.order.push(def);
+
+        for namespace in namespaces {
+            self.bind(
+                parent,
+                *namespace,
+                &name.item,
+                Binding {
+                    def,
+                    span: name.span,
+                },
+            );
+        }
+
+        def
+    }
+
+    fn bind(&mut self, scope: DefId, namespace: Namespace, name: &str, binding: Binding) {
+        let scope = self
+            .scopes
+            .get_mut(&scope)
+            .expect("Names are only bound within modules, enums and traits");
+        let bindings = match namespace {
+            Namespace::Type => &mut scope.types,
+            Namespace::Value => &mut scope.values,
+        };
+
+        match bindings.get(name) {
+            Some(first) => self.errors.push(
+                Error::new(binding.span, format!("`{name}` is defined multiple times"))
+                    .with_note(first.span, format!("`{name}` is first defined here")),
+            ),
+            None => {
+                bindings.insert(name.to_owned(), binding);
+            }
+        }
+    }
+
+    fn collect_imports(&mut self, tree: &UseTree<Syntax>, outer: &[Ident], module: DefId) {
+        let path: Vec<Ident> = outer.iter().chain(&tree.prefix).cloned().collect();
+
+        match &tree.kind {
+            UseTreeKind::Simple(alias) => {
+                let Some(last) = path.last() else {
+                    return;
+                };
+                let binding = alias.clone().unwrap_or_else(|| last.clone());
+
+                self.imports.push(Import {
+                    module,
+                    path,
+                    kind: ImportKind::Single(binding),
+                });
+            }
+            UseTreeKind::Nested(trees, _) => {
+                for tree in trees {
+                    self.collect_imports(tree, &path, module);
+                }
+            }
+            UseTreeKind::Glob => self.imports.push(Import {
+                module,
+                path,
+                kind: ImportKind::Glob,
+            }),
+        }
+    }
+
+    fn resolve_imports(&mut self) {
+        let mut pending = std::mem::take(&mut self.imports);
+
+        loop {
+            let count = pending.len();
+            pending.retain(|import| !self.try_import(import));
+
+            if pending.len() == count {
+                break;
+            }
+        }
+
+        // Whatever is left cannot be resolved, no matter what else is imported.
+        for import in pending {
+            let error = match self.import_target(&import) {
+                Err(error) => error,
+                Ok(_) => Error::new(
+                    import.path.last().map_or(Span::new(0, 0), |last| last.span),
+                    String::from("Unresolved import"),
+                ),
+            };
+            self.errors.push(error);
+        }
+    }
+
+    /// Binds an import if what it names can be found yet, returning whether it could.
+    fn try_import(&mut self, import: &Import) -> bool {
+        let Ok((found, trail)) = self.import_target(import) else {
+            return false;
+        };
+        self.check_access(&trail, import.module);
+
+        match &import.kind {
+            ImportKind::Single(binding) => {
+                for (namespace, def) in found {
+                    self.bind(
+                        import.module,
+                        namespace,
+                        &binding.item,
+                        Binding {
+                            def,
+                            span: binding.span,
+                        },
+                    );
+                }
+            }
+            ImportKind::Glob => {
+                let (_, scope) = found[0];
+                self.scopes
+                    .get_mut(&import.module)
+                    .expect("Imports only appear within modules")
+                    .globs
+                    .push(scope);
+            }
+        }
+
+        true
+    }
+
+    /// What an import names: the definitions in each namespace for a single import, or the
+    /// module or enum that a glob import takes its names from. The definitions named by each
+    /// segment of the path are also returned, for checking privacy.
+    fn import_target(&self, import: &Import) -> Result<ImportTarget, Error> {
+        match &import.kind {
+            ImportKind::Glob => {
+                let trail = self.scope_path(import.module, &import.path)?;
+                let scope = trail.last().map_or(import.module, |(def, _)| *def);
+
+                Ok((vec![(Namespace::Type, scope)], trail))
+            }
+            ImportKind::Single(_) => {
+                let (last, prefix) = import
+                    .path
+                    .split_last()
+                    .expect("Imports name at least one segment");
+                let trail = self.scope_path(import.module, prefix)?;
+                let scope = trail.last().map_or(import.module, |(def, _)| *def);
+
+                let found: Vec<_> = [Namespace::Type, Namespace::Value]
+                    .into_iter()
+                    .filter_map(|namespace| {
+                        let binding = match prefix.is_empty() {
+                            true => self.lookup_start(scope, &last.item, namespace),
+                            false => self.lookup(scope, &last.item, namespace),
+                        };
+                        binding.map(|binding| (namespace, binding.def))
+                    })
+                    .collect();
+
+                match found.first() {
+                    None => Err(Error::new(
+                        last.span,
+                        format!("Cannot find `{}` in {}", last.item, self.describe(scope)),
+                    )),
+                    Some((_, def)) => {
+                        let mut trail = trail;
+                        trail.push((*def, last.span));
+
+                        Ok((found, trail))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Follows a path through modules, enums and traits, returning the definition named by
+    /// each segment along with the segment's span. An empty path names `module` itself.
+    fn scope_path(&self, module: DefId, segments: &[Ident]) -> Result<Vec<(DefId, Span)>, Error> {
+        let mut trail = Vec::new();
+        let mut current = module;
+
+        for (i, segment) in segments.iter().enumerate() {
+            // The empty segment of a leading `::` names the root module itself.
+            if i == 0 && segment.item.is_empty() {
+                current = self.root;
+                trail.push((self.root, segment.span));
+                continue;
+            }
+
+            let binding = match i {
+                0 => self.lookup_start(module, &segment.item, Namespace::Type),
+                _ => self.lookup(current, &segment.item, Namespace::Type),
+            };
+
+            let Some(binding) = binding else {
+                return Err(Error::new(
+                    segment.span,
+                    format!(
+                        "Cannot find `{}` in {}",
+                        segment.item,
+                        self.describe(current)
+                    ),
+                ));
+            };
+
+            match self.definitions.get(binding.def).kind {
+                DefKind::Mod | DefKind::Enum | DefKind::Trait => current = binding.def,
+                _ => {
+                    return Err(Error::new(
+                        segment.span,
+                        format!("Expected `{}` to be a module, enum, or trait", segment.item),
+                    ))
+                }
+            }
+
+            trail.push((binding.def, segment.span));
+        }
+
+        Ok(trail)
+    }
+
+    /// Looks up the first segment of a path, which may be bound in the current module, the
+    /// root module, or the core module.
+    fn lookup_start(&self, module: DefId, name: &str, namespace: Namespace) -> Option<Binding> {
+        self.lookup(module, name, namespace)
+            .or_else(|| self.lookup(self.root, name, namespace))
+            .or_else(|| self.lookup(self.core, name, namespace))
+    }
+
+    fn lookup(&self, scope: DefId, name: &str, namespace: Namespace) -> Option<Binding> {
+        self.lookup_visiting(scope, scope, name, namespace, &mut Vec::new())
+    }
+
+    fn lookup_visiting(
+        &self,
+        scope: DefId,
+        importer: DefId,
+        name: &str,
+        namespace: Namespace,
+        visited: &mut Vec<DefId>,
+    ) -> Option<Binding> {
+        // Globs may import one another, so each scope is only searched once.
+        if visited.contains(&scope) {
+            return None;
+        }
+        visited.push(scope);
+
+        let bound = self.scopes.get(&scope)?;
+        let bindings = match namespace {
+            Namespace::Type => &bound.types,
+            Namespace::Value => &bound.values,
+        };
+
+        // A glob only imports the names that are accessible from the importing module.
+        bindings
+            .get(name)
+            .copied()
+            .filter(|binding| scope == importer || self.is_accessible(binding.def, importer))
+            .or_else(|| {
+                bound
+                    .globs
+                    .iter()
+                    .find_map(|glob| self.lookup_visiting(*glob, scope, name, namespace, visited))
+            })
+    }
+
+    fn is_accessible(&self, def: DefId, from: DefId) -> bool {
+        let definition = self.definitions.get(def);
+        let module = self.declaring_module(def);
+        let from = self.definitions.module_path(self.definitions.module(from));
+
+        privacy::accessible(definition.visibility, &module, &from)
+    }
+
+    /// The path of the module a definition is declared in, which for a variant or associated
+    /// function is the module of its enum or trait.
+    fn declaring_module(&self, def: DefId) -> Vec<String> {
+        match self.definitions.get(def).parent {
+            Some(parent) => self
+                .definitions
+                .module_path(self.definitions.module(parent)),
+            None => Vec::new(),
+        }
+    }
+
+    fn check_access(&mut self, trail: &[(DefId, Span)], from: DefId) {
+        let modules: Vec<_> = trail
+            .iter()
+            .map(|(def, _)| self.declaring_module(*def))
+            .collect();
+        let segments: Vec<_> = trail
+            .iter()
+            .zip(&modules)
+            .map(|((def, span), module)| {
+                let definition = self.definitions.get(*def);
+                let declaration = privacy::Declaration {
+                    name: &definition.name,
+                    visibility: definition.visibility,
+                    module,
+                    span: definition.span,
+                };
+                (declaration, *span)
+            })
+            .collect();
+        let from = self.definitions.module_path(self.definitions.module(from));
+
+        if let Err(error) = privacy::check_path(&segments, &from) {
+            self.errors.push(error);
+        }
+    }
+
+    fn describe(&self, scope: DefId) -> String {
+        match scope == self.root {
+            true => String::from("the root module"),
+            false => format!("`{}`", self.definitions.get(scope).name),
+        }
+    }
+
+    fn next_def(&mut self) -> DefId {
+        let def = self.order[self.next];
+        self.next += 1;
+        def
+    }
+
+    fn resolution(&self, def: DefId) -> Resolution {
+        Resolution::Def(self.definitions.get(def).kind, def)
+    }
+
+    /// Stands in for the resolution of a path that failed to resolve. It is never observed,
+    /// since the error that was reported fails resolution as a whole.
+    fn unresolvable(&self) -> Resolution {
+        Resolution::Def(DefKind::Mod, self.root)
+    }
+
+    fn declared(&self, ident: Ident, def: DefId) -> Name {
+        Name {
+            ident,
+            resolution: Some(self.resolution(def)),
+        }
+    }
+
+    fn unresolved(ident: Ident) -> Name {
+        Name {
+            ident,
+            resolution: None,
+        }
+    }
+
+    /// Binds a local in the innermost rib, shadowing any earlier binding of the same name.
+    fn local(&mut self, ident: Ident) -> Name {
+        let local = LocalId(self.locals);
+        self.locals += 1;
+
+        self.innermost()
+            .values
+            .insert(ident.item.clone(), (local, ident.span));
+
+        Name {
+            ident,
+            resolution: Some(Resolution::Local(local)),
+        }
+    }
+
+    fn innermost(&mut self) -> &mut Rib {
+        self.ribs
+            .last_mut()
+            .expect("Locals and type parameters are only bound within functions")
+    }
+
+    fn check_unbound(&mut self, name: &Ident, what: &str, first: Option<Span>) {
+        if let Some(first) = first {
+            self.errors.push(
+                Error::new(
+                    name.span,
+                    format!("{what} `{}` is bound more than once", name.item),
+                )
+                .with_note(first, format!("`{}` is first bound here", name.item)),
+            );
+        }
+    }
+
+    fn path(&mut self, segments: Vec<Ident>, namespace: Namespace) -> ResolvedPath {
+        let resolution = match self.resolve_path(&segments, namespace) {
+            Ok(resolution) => resolution,
+            Err(error) => {
+                self.errors.push(error);
+                self.unresolvable()
+            }
+        };
+
+        ResolvedPath {
+            segments,
+            resolution,
+        }
+    }
+
+    fn resolve_path(
+        &mut self,
+        segments: &[Ident],
+        namespace: Namespace,
+    ) -> Result<Resolution, Error> {
+        let (last, prefix) = segments
+            .split_last()
+            .expect("Paths have at least one segment");
+
+        if prefix.is_empty() {
+            for rib in self.ribs.iter().rev() {
+                match namespace {
+                    Namespace::Value => {
+                        if let Some((local, _)) = rib.values.get(&last.item) {
+                            return Ok(Resolution::Local(*local));
+                        }
+                    }
+                    Namespace::Type => {
+                        if let Some(def) = rib.types.get(&last.item) {
+                            return Ok(self.resolution(*def));
+                        }
+                    }
+                }
+            }
+
+            return match self.lookup_start(self.current, &last.item, namespace) {
+                Some(binding) => {
+                    self.check_access(&[(binding.def, last.span)], self.current);
+                    Ok(self.resolution(binding.def))
+                }
+                None => Err(Error::new(
+                    last.span,
+                    format!("Cannot find {namespace} `{}` in this scope", last.item),
+                )),
+            };
+        }
+
+        // A single segment naming a type parameter, struct, or type alias is followed by the
+        // name of an associated function, as in `T::new`.
+        if let [first] = prefix {
+            let parameter = self
+                .ribs
+                .iter()
+                .rev()
+                .find_map(|rib| rib.types.get(&first.item));
+            let binding = parameter.copied().or_else(|| {
+                self.lookup_start(self.current, &first.item, Namespace::Type)
+                    .map(|binding| binding.def)
+            });
+
+            if let Some(def) = binding {
+                if let DefKind::TypeParameter | DefKind::Struct | DefKind::TypeAlias =
+                    self.definitions.get(def).kind
+                {
+                    if parameter.is_none() {
+                        self.check_access(&[(def, first.span)], self.current);
+                    }
+                    return Ok(Resolution::Relative(def, last.clone()));
+                }
+            }
+        }
+
+        let mut trail = self.scope_path(self.current, prefix)?;
+        let (scope, _) = *trail.last().expect("The prefix is not empty");
+
+        match self.lookup(scope, &last.item, namespace) {
+            Some(binding) => {
+                trail.push((binding.def, last.span));
+                self.check_access(&trail, self.current);
+                Ok(self.resolution(binding.def))
+            }
+            // Any other name within an enum is one of its associated functions.
+            None if self.definitions.get(scope).kind == DefKind::Enum => {
+                self.check_access(&trail, self.current);
+                Ok(Resolution::Relative(scope, last.clone()))
+            }
+            None => Err(Error::new(
+                last.span,
+                format!(
+                    "Cannot find {namespace} `{}` in {}",
+                    last.item,
+                    self.describe(scope)
+                ),
+            )),
+        }
+    }
+
+    fn use_tree(&mut self, tree: UseTree<Syntax>, outer: &[Ident]) -> UseTree<Resolved> {
+        let UseTree { prefix, kind } = tree;
+        let path: Vec<Ident> = outer.iter().chain(&prefix).cloned().collect();
+
+        // Imports were resolved and any errors reported before the tree is rebuilt, so the
+        // imported definition only needs to be looked up again here.
+        let resolution = match &kind {
+            UseTreeKind::Simple(_) => {
+                let import = Import {
+                    module: self.current,
+                    path: path.clone(),
+                    kind: ImportKind::Single(Spanned::empty(String::new())),
+                };
+                match self.import_target(&import) {
+                    Ok((found, _)) => self.resolution(found[0].1),
+                    Err(_) => self.unresolvable(),
+                }
+            }
+            UseTreeKind::Nested(_, _) | UseTreeKind::Glob => {
+                match self.scope_path(self.current, &path) {
+                    Ok(trail) => {
+                        self.resolution(trail.last().map_or(self.current, |(def, _)| *def))
+                    }
+                    Err(_) => self.unresolvable(),
+                }
+            }
+        };
+
+        let kind = match kind {
+            UseTreeKind::Simple(alias) => UseTreeKind::Simple(alias.map(|alias| Name {
+                ident: alias,
+                resolution: Some(resolution.clone()),
+            })),
+            UseTreeKind::Nested(trees, span) => UseTreeKind::Nested(
+                trees
+                    .into_iter()
+                    .map(|tree| self.use_tree(tree, &path))
+                    .collect(),
+                span,
+            ),
+            UseTreeKind::Glob => UseTreeKind::Glob,
+        };
+
+        UseTree {
+            prefix: ResolvedPath {
+                segments: prefix,
+                resolution,
+            },
+            kind,
+        }
+    }
+
+    fn trait_path(&mut self, path: Vec<Ident>) -> ResolvedPath {
+        let errors = self.errors.len();
+        let resolved = self.path(path, Namespace::Type);
+
+        // Paths that failed to resolve have already been reported.
+        if let Resolution::Def(kind, _) = resolved.resolution {
+            if kind != DefKind::Trait && self.errors.len() == errors {
+                self.errors.push(Error::new(
+                    resolved.span(),
+                    format!(
+                        "Expected a trait, but `{}` is not one",
+                        resolved
+                            .segments
+                            .iter()
+                            .map(|segment| segment.item.as_str())
+                            .collect::<Vec<_>>()
+                            .join("::")
+                    ),
+                ));
+            }
+        }
+
+        resolved
+    }
+
+    fn ty(&mut self, ty: ast::Type) -> Type {
+        let kind = match ty.item {
+            TypeKind::U8 => TypeKind::U8,
+            TypeKind::I8 => TypeKind::I8,
+            TypeKind::U16 => TypeKind::U16,
+            TypeKind::I16 => TypeKind::I16,
+            TypeKind::U32 => TypeKind::U32,
+            TypeKind::I32 => TypeKind::I32,
+            TypeKind::Bool => TypeKind::Bool,
+            TypeKind::Char => TypeKind::Char,
+            TypeKind::Str => TypeKind::Str,
+            TypeKind::Unit => TypeKind::Unit,
+            TypeKind::Array(element, size) => TypeKind::Array(Box::new(self.ty(*element)), size),
+            TypeKind::Slice(element) => TypeKind::Slice(Box::new(self.ty(*element))),
+            TypeKind::Reference(mutability, inner) => {
+                TypeKind::Reference(mutability, Box::new(self.ty(*inner)))
+            }
+            TypeKind::Function(parameters, result) => TypeKind::Function(
+                parameters.into_iter().map(|ty| self.ty(ty)).collect(),
+                result.map(|result| Box::new(self.ty(*result))),
+            ),
+            TypeKind::Parenthesized(inner) => TypeKind::Parenthesized(Box::new(self.ty(*inner))),
+            TypeKind::Path(path) => TypeKind::Path(self.path(path, Namespace::Type)),
+            TypeKind::Generic(path, arguments) => TypeKind::Generic(
+                self.path(path, Namespace::Type),
+                arguments.into_iter().map(|ty| self.ty(ty)).collect(),
+            ),
+        };
+
+        Spanned::new(kind, ty.span)
+    }
+}
+
+impl Folder<Syntax, Resolved> for Resolver {
+    fn fold_name(&mut self, name: Ident, context: NameContext) -> Name {
+        match context {
+            NameContext::Module => self.declared(name, self.current),
+            NameContext::Declaration => {
+                let def = self.next_def();
+                self.item = def;
+                self.declared(name, def)
+            }
+            NameContext::TypeParameter => {
+                let first = self.innermost().types.get(&name.item).copied();
+                let first = first.map(|def| self.definitions.get(def).span);
+                self.check_unbound(&name, "Type parameter", first);
+
+                let def = self.definitions.define(Definition {
+                    kind: DefKind::TypeParameter,
+                    name: name.item.clone(),
+                    parent: Some(self.item),
+                    visibility: Visibility::Private,
+                    span: name.span,
+                });
+                self.innermost().types.insert(name.item.clone(), def);
+
+                self.declared(name, def)
+            }
+            NameContext::Parameter => {
+                let first = self
+                    .innermost()
+                    .values
+                    .get(&name.item)
+                    .map(|(_, span)| *span);
+                self.check_unbound(&name, "Parameter", first);
+
+                self.local(name)
+            }
+            NameContext::Binding => self.local(name),
+            NameContext::Field | NameContext::Method => Self::unresolved(name),
+            NameContext::Import => unreachable!("Use trees are resolved whole by `fold_use_tree`"),
+        }
+    }
+
+    fn fold_path(&mut self, path: Vec<Ident>, context: PathContext) -> ResolvedPath {
+        match context {
+            PathContext::Expression | PathContext::Pattern => self.path(path, Namespace::Value),
+            PathContext::StructPattern => self.path(path, Namespace::Type),
+            PathContext::Trait => self.trait_path(path),
+            PathContext::Import => unreachable!("Use trees are resolved whole by `fold_use_tree`"),
+        }
+    }
+
+    fn fold_type(&mut self, ty: ast::Type) -> Type {
+        self.ty(ty)
+    }
+
+    fn enter_scope(&mut self) {
+        self.ribs.push(Rib::default());
+    }
+
+    fn exit_scope(&mut self) {
+        self.ribs.pop();
+    }
+
+    fn fold_module(&mut self, id: ModuleId, module: Module<Syntax>) -> Module<Resolved> {
+        let def = self.modules[id].expect("Each module file is declared by a `mod` item");
+
+        let outer = std::mem::replace(&mut self.current, def);
+        let module = walk_module(self, id, module);
+        self.current = outer;
+
+        module
+    }
+
+    fn fold_item(&mut self, item: Item<Syntax>) -> Item<Resolved> {
+        match &item.kind {
+            // The items of an inline module are resolved within it. The module's own name is
+            // the next to be declared.
+            ItemKind::Mod(ModItem {
+                body: ModBody::Inline(_),
+                ..
+            }) => {
+                let outer = std::mem::replace(&mut self.current, self.order[self.next]);
+                let item = walk_item(self, item);
+                self.current = outer;
+
+                item
+            }
+            _ => walk_item(self, item),
+        }
+    }
+
+    fn fold_use_tree(&mut self, tree: UseTree<Syntax>) -> UseTree<Resolved> {
+        self.use_tree(tree, &[])
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern<Syntax>) -> Pattern<Resolved> {
+        // Each pattern has a scope of its own, so an earlier binding of the same name in the
+        // innermost scope is within the same pattern.
+        if let PatternKind::Binding(_, name, _) = &pattern.item {
+            let first = self
+                .innermost()
+                .values
+                .get(&name.item)
+                .map(|(_, span)| *span);
+            self.check_unbound(name, "Identifier", first);
+        }
+
+        walk_pattern(self, pattern)
+    }
+
+    fn fold_receiver(&mut self, receiver: Spanned<Receiver>) -> Spanned<Receiver> {
+        self.local(Spanned::new(String::from("self"), receiver.span));
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::visit::Visitor;
+    use crate::resolve::*;
+    use crate::testing::{self, expression, geode, ident, integer, item, let_statement, ty};
+
+    fn path(segments: &[(&str, usize)]) -> Expression<Syntax> {
+        let segments = segments
+            .iter()
+            .map(|(name, start)| ident(name, *start))
+            .collect();
+
+        expression(ExpressionWithoutBlock::Path(segments, ()), 0)
+    }
+
+    fn function(
+        name: &str,
+        start: usize,
+        statements: Vec<Statement<Syntax>>,
+        visibility: Visibility,
+    ) -> Item<Syntax> {
+        Item {
+            visibility,
+            ..testing::function(name, start, Vec::new(), None, statements, None)
+        }
+    }
+
+    fn constant(name: &str, start: usize, visibility: Visibility) -> Item<Syntax> {
+        Item {
+            visibility,
+            ..item(ItemKind::Const(ConstItem {
+                name: ident(name, start),
+                ty: ty(TypeKind::U8, 0),
+                value: integer(0, 0),
+            }))
+        }
+    }
+
+    fn module(
+        name: &str,
+        start: usize,
+        items: Vec<Item<Syntax>>,
+        visibility: Visibility,
+    ) -> Item<Syntax> {
+        Item {
+            visibility,
+            ..item(ItemKind::Mod(ModItem {
+                name: ident(name, start),
+                body: ModBody::Inline(items),
+            }))
+        }
+    }
+
+    fn import(segments: &[(&str, usize)], kind: UseTreeKind<Syntax>) -> Item<Syntax> {
+        item(ItemKind::Use(UseTree {
+            prefix: segments
+                .iter()
+                .map(|(name, start)| ident(name, *start))
+                .collect(),
+            kind,
+        }))
+    }
+
+    /// Collects the resolution of every path used as an expression, in order.
+    #[derive(Default)]
+    struct Paths(Vec<Resolution>);
+
+    impl Visitor<Resolved> for Paths {
+        fn visit_path(&mut self, path: &ResolvedPath, context: PathContext) {
+            if context == PathContext::Expression {
+                self.0.push(path.resolution.clone());
+            }
+        }
+    }
+
+    fn initializers(geode: &Geode<Resolved>) -> Vec<Resolution> {
+        let mut paths = Paths::default();
+        paths.visit_geode(geode);
+        paths.0
+    }
+
+    #[test]
+    fn shadowed_locals() {
+        // fn main() { let x = LIMIT; let x = x; let y = x; }
+        let geode = geode(vec![
+            constant("LIMIT", 1, Visibility::Private),
+            function(
+                "main",
+                2,
+                vec![
+                    let_statement("x", 3, Mutability::Immutable, None, path(&[("LIMIT", 4)])),
+                    let_statement("x", 5, Mutability::Immutable, None, path(&[("x", 6)])),
+                    let_statement("y", 7, Mutability::Immutable, None, path(&[("x", 8)])),
+                ],
+                Visibility::Private,
+            ),
+        ]);
+
+        let (resolved, definitions) = resolve(geode).unwrap();
+        let resolutions = initializers(&resolved);
+
+        assert!(
+            matches!(resolutions[0], Resolution::Def(DefKind::Const, def) if definitions.get(def).name == "LIMIT")
+        );
+        assert_eq!(resolutions[1], Resolution::Local(LocalId(0)));
+        assert_eq!(resolutions[2], Resolution::Local(LocalId(1)));
+    }
+
+    #[test]
+    fn undefined_and_duplicate_names() {
+        // const A: u8 = 0; const A: u8 = 0; fn main() { let x = missing; }
+        let geode = geode(vec![
+            constant("A", 1, Visibility::Private),
+            constant("A", 2, Visibility::Private),
+            function(
+                "main",
+                3,
+                vec![let_statement(
+                    "x",
+                    4,
+                    Mutability::Immutable,
+                    None,
+                    path(&[("missing", 5)]),
+                )],
+                Visibility::Private,
+            ),
+        ]);
+
+        let errors = resolve(geode).err().unwrap();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].span, Span::new(2, 3));
+        assert_eq!(errors[0].notes[0].span, Span::new(1, 2));
+        assert_eq!(errors[1].span, Span::new(5, 6));
+    }
+
+    #[test]
+    fn separate_namespaces() {
+        // struct Point {} fn Point() {}
+        let point = item(ItemKind::Struct(StructItem {
+            name: ident("Point", 1),
+            fields: Vec::new(),
+        }));
+        let geode = geode(vec![
+            point,
+            function("Point", 2, Vec::new(), Visibility::Private),
+        ]);
+
+        assert!(resolve(geode).is_ok());
+    }
+
+    fn nested_modules(c: Visibility) -> Geode<Syntax> {
+        // mod a { pub const B: u8 = 0; mod c { pub const D: u8 = 0; } }
+        // use a::B; use a::c::*;
+        // fn main() { let x = B; let y = D; }
+        geode(vec![
+            module(
+                "a",
+                1,
+                vec![
+                    constant("B", 2, Visibility::Public),
+                    module("c", 3, vec![constant("D", 4, Visibility::Public)], c),
+                ],
+                Visibility::Private,
+            ),
+            import(&[("a", 5), ("B", 6)], UseTreeKind::Simple(None)),
+            import(&[("a", 7), ("c", 8)], UseTreeKind::Glob),
+            function(
+                "main",
+                9,
+                vec![
+                    let_statement("x", 10, Mutability::Immutable, None, path(&[("B", 11)])),
+                    let_statement("y", 12, Mutability::Immutable, None, path(&[("D", 13)])),
+                ],
+                Visibility::Private,
+            ),
+        ])
+    }
+
+    #[test]
+    fn imports_and_globs() {
+        let (resolved, definitions) = resolve(nested_modules(Visibility::Public)).unwrap();
+        let names: Vec<_> = initializers(&resolved)
+            .into_iter()
+            .map(|resolution| match resolution {
+                Resolution::Def(_, def) => definitions.get(def).name.clone(),
+                _ => panic!("Expected imported constants"),
+            })
+            .collect();
+
+        assert_eq!(names, vec!["B", "D"]);
+    }
+
+    #[test]
+    fn glob_through_private_module() {
+        let errors = resolve(nested_modules(Visibility::Private)).err().unwrap();
+
+        // `c` is private to `a`, so the root module cannot import from it.
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::new(8, 9));
+    }
+
+    #[test]
+    fn global_paths() {
+        let source = "
+            const B: u8 = 0;
+            mod a {
+                const C: u8 = 1;
+                fn f() -> u8 { ::B + ::C }
+            }
+        ";
+
+        // `::` names the root module alone, which has a `B` but no `C`.
+        let errors = resolve(testing::parse(source)).err().unwrap();
+
+        let start = source.find("::C").unwrap() + 2;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "Cannot find value `C` in the root module"
+        );
+        assert_eq!(errors[0].span, Span::new(start, start + 1));
+    }
+
+    #[test]
+    fn private_items() {
+        // mod a { const B: u8 = 0; } fn main() { let x = a::B; }
+        let geode = geode(vec![
+            module(
+                "a",
+                1,
+                vec![constant("B", 2, Visibility::Private)],
+                Visibility::Private,
+            ),
+            function(
+                "main",
+                3,
+                vec![let_statement(
+                    "x",
+                    4,
+                    Mutability::Immutable,
+                    None,
+                    path(&[("a", 5), ("B", 6)]),
+                )],
+                Visibility::Private,
+            ),
+        ]);
+
+        let errors = resolve(geode).err().unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::new(6, 7));
+        assert_eq!(errors[0].notes[0].span, Span::new(2, 3));
+    }
+
+    #[test]
+    fn module_files() {
+        // mod a; fn main() { let x = a::B; }, where `a.opal` holds `pub const B: u8 = 0;`
+        let mut geode = geode(vec![
+            item(ItemKind::Mod(ModItem {
+                name: ident("a", 1),
+                body: ModBody::File(Some(1)),
+            })),
+            function(
+                "main",
+                2,
+                vec![let_statement(
+                    "x",
+                    3,
+                    Mutability::Immutable,
+                    None,
+                    path(&[("a", 4), ("B", 5)]),
+                )],
+                Visibility::Private,
+            ),
+        ]);
+        geode.modules.push(Module {
+            name: ident("a", 1),
+            items: vec![constant("B", 6, Visibility::Public)],
+            file: 1,
+        });
+
+        let (resolved, definitions) = resolve(geode).unwrap();
+        let Resolution::Def(DefKind::Const, def) = initializers(&resolved)[0] else {
+            panic!("Expected `a::B` to name a constant");
+        };
+
+        assert_eq!(definitions.get(def).span, Span::new(6, 7));
+        assert!(matches!(
+            resolved.modules[1].name.resolution,
+            Some(Resolution::Def(DefKind::Mod, _))
+        ));
+    }
+}
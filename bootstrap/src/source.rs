@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use crate::span::Span;
+
+pub type FileId = usize;
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+    pub start: usize,
+}
+
+impl SourceFile {
+    /// One past the position of the file's last character, which is where its `Eof` token is.
+    pub fn end(&self) -> usize {
+        self.start + self.text.chars().count()
+    }
+}
+
+/// Every source file in the geode. Each file is given its own range of positions, so that a
+/// `Span` identifies the file it came from without carrying a file id around. Positions count
+/// characters, as the lexer does.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    /// Registers a file, placing it after every file added so far. The position one past the
+    /// end of each file is left unused, so that end-of-file spans stay within their file.
+    pub fn add(&mut self, path: PathBuf, text: String) -> FileId {
+        let start = self.files.last().map_or(0, |last| last.end() + 1);
+
+        self.files.push(SourceFile { path, text, start });
+        self.files.len() - 1
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id]
+    }
+
+    pub fn find(&self, path: &Path) -> Option<FileId> {
+        self.files.iter().position(|file| file.path == path)
+    }
+
+    /// The file containing the start of `span`, with the 1-based line and column it starts at.
+    pub fn locate(&self, span: Span) -> Option<(&SourceFile, usize, usize)> {
+        let file = self
+            .files
+            .iter()
+            .rev()
+            .find(|file| file.start <= span.start && span.start <= file.end())?;
+
+        let offset = span.start - file.start;
+        let preceding: Vec<char> = file.text.chars().take(offset).collect();
+        let line = preceding.iter().filter(|c| **c == '\n').count() + 1;
+        let column = offset
+            - preceding
+                .iter()
+                .rposition(|c| *c == '\n')
+                .map_or(0, |i| i + 1)
+            + 1;
+
+        Some((file, line, column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::*;
+
+    #[test]
+    fn locate_across_files() {
+        let mut sources = SourceMap::new();
+        let main = sources.add(
+            PathBuf::from("main.opal"),
+            "mod foo;\nfn main() {}".to_owned(),
+        );
+        let foo = sources.add(PathBuf::from("foo.opal"), "fn foo() {}".to_owned());
+
+        assert_eq!(sources.file(foo).start, 22);
+
+        let (file, line, column) = sources.locate(Span::new(12, 14)).unwrap();
+        assert_eq!(
+            (file.path.as_path(), line, column),
+            (Path::new("main.opal"), 2, 4)
+        );
+
+        let (file, line, column) = sources.locate(Span::new(25, 28)).unwrap();
+        assert_eq!(
+            (file.path.as_path(), line, column),
+            (Path::new("foo.opal"), 1, 4)
+        );
+
+        assert_eq!(sources.find(Path::new("main.opal")), Some(main));
+    }
+}
@@ -23,6 +23,14 @@ impl<T: EndMarked> Stream<T> {
         self.spanned_peek().item
     }
 
+    /// Peeks `n` elements past the next one, so `peek_nth(0)` is the same as `peek()`.
+    pub fn peek_nth(&self, n: usize) -> T {
+        match self.spans.len().checked_sub(n + 1) {
+            None => T::END,
+            Some(index) => self.spans[index].item.clone(),
+        }
+    }
+
     pub fn peek_span(&self) -> Span {
         self.spanned_peek().span
     }
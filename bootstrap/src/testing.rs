@@ -0,0 +1,261 @@
+use std::path::{Path, PathBuf};
+
+use crate::driver::{self, BuildOptions};
+use crate::error::Error;
+use crate::load::FileSystem;
+use crate::model::ast::{self, Syntax};
+use crate::model::base::*;
+use crate::model::def::Definitions;
+use crate::model::ir;
+use crate::model::resolved::Resolved;
+use crate::model::typed::Typed;
+use crate::model::types::Type;
+use crate::parse;
+use crate::resolve;
+use crate::source::SourceMap;
+use crate::span::{Span, Spanned};
+use crate::typeck::{self, Tables};
+
+// Fixtures shared by the tests of the passes. Those starting from source text panic with the
+// messages of any errors, since a test's source is expected to get through the passes before the
+// one under test.
+
+/// Parses `source` as the only module of a geode named `test`.
+pub fn parse(source: &str) -> Geode<Syntax> {
+    let items = parse::tokenize(source)
+        .and_then(|mut tokens| parse::items(&mut tokens))
+        .unwrap_or_else(|error| panic!("{}", error.message));
+
+    Geode {
+        name: Spanned::empty(String::from("test")),
+        modules: vec![Module {
+            name: Spanned::empty(String::from("test")),
+            items,
+            file: 0,
+        }],
+    }
+}
+
+pub fn resolve(source: &str) -> (Geode<Resolved>, Definitions) {
+    resolve::resolve(parse(source)).unwrap_or_else(|errors| panic!("{}", joined(&errors)))
+}
+
+pub fn typed(source: &str) -> (Geode<Typed>, Tables, Definitions) {
+    let (geode, definitions) = resolve(source);
+    let (geode, tables) = typeck::check(geode, &definitions, &Type::I32)
+        .unwrap_or_else(|errors| panic!("{}", joined(&errors)));
+
+    (geode, tables, definitions)
+}
+
+/// The errors that type checking `source` reports, if any.
+pub fn type_errors(source: &str) -> Vec<Error> {
+    let (geode, definitions) = resolve(source);
+    typeck::check(geode, &definitions, &Type::I32)
+        .err()
+        .unwrap_or_default()
+}
+
+/// Builds `source` as the root module `main.opal`, giving the functions lowered from it.
+pub fn lowered(source: &'static str) -> Vec<ir::Function> {
+    built(source, &BuildOptions::default()).unwrap_or_else(|errors| panic!("{}", joined(&errors)))
+}
+
+/// Builds `source` as the root module `main.opal` with the given options.
+pub fn built(
+    source: &'static str,
+    options: &BuildOptions,
+) -> Result<Vec<ir::Function>, Vec<Error>> {
+    driver::build(
+        Path::new("main.opal"),
+        options,
+        &Single(source),
+        &mut SourceMap::new(),
+        &mut Vec::new(),
+    )
+}
+
+fn joined(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Builders for trees of the `Syntax` phase, for tests that need control over every span. A node
+// built at `start` spans the single character there.
+
+pub fn ident(name: &str, start: usize) -> Spanned<String> {
+    Spanned::new(name.to_owned(), Span::new(start, start + 1))
+}
+
+pub fn ty(kind: ast::TypeKind, start: usize) -> ast::Type {
+    Spanned::new(kind, Span::new(start, start + 1))
+}
+
+pub fn expression(kind: ExpressionWithoutBlock<Syntax>, start: usize) -> Expression<Syntax> {
+    Spanned::new(
+        ExpressionKind::ExpressionWithoutBlock(kind),
+        Span::new(start, start + 1),
+    )
+}
+
+/// A path of a single segment, such as a local variable.
+pub fn variable(name: &str, start: usize) -> Expression<Syntax> {
+    expression(
+        ExpressionWithoutBlock::Path(vec![ident(name, start)], ()),
+        start,
+    )
+}
+
+pub fn integer(value: u32, start: usize) -> Expression<Syntax> {
+    expression(
+        ExpressionWithoutBlock::Literal(Literal::Integer(value), ()),
+        start,
+    )
+}
+
+/// A private item without attributes.
+pub fn item(kind: ItemKind<Syntax>) -> Item<Syntax> {
+    Item {
+        kind,
+        attributes: Vec::new(),
+        visibility: Visibility::Private,
+        span: Span::new(0, 1),
+    }
+}
+
+/// `fn name(parameters) -> return_type { statements tail }`, with the parameters following the
+/// name.
+pub fn function(
+    name: &str,
+    start: usize,
+    parameters: Vec<(&str, ast::Type)>,
+    return_type: Option<ast::Type>,
+    statements: Vec<Statement<Syntax>>,
+    tail: Option<Expression<Syntax>>,
+) -> Item<Syntax> {
+    let parameters = parameters
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, ty))| Parameter {
+            name: ident(name, start + index + 1),
+            mutability: Mutability::Immutable,
+            ty,
+            span: Span::new(start + index + 1, start + index + 2),
+        })
+        .collect();
+
+    let mut statements = statements.into_iter();
+    let body = match (statements.next(), tail) {
+        (None, None) => Vec::new(),
+        (None, Some(tail)) => vec![Some(Statements::Block(tail))],
+        (Some(first), tail) => {
+            vec![Some(Statements::Leading(first, statements.collect(), tail))]
+        }
+    };
+
+    item(ItemKind::Function(FunctionItem {
+        name: ident(name, start),
+        generics: Vec::new(),
+        receiver: None,
+        parameters,
+        return_type,
+        body: Some(body),
+    }))
+}
+
+/// `let [mut] name[: ty] = initializer;`
+pub fn let_statement(
+    name: &str,
+    start: usize,
+    mutability: Mutability,
+    ty: Option<ast::Type>,
+    initializer: Expression<Syntax>,
+) -> Statement<Syntax> {
+    Statement::Let(Let {
+        name: ident(name, start),
+        mutability,
+        ty,
+        initializer: Some(initializer),
+        span: Span::new(start, start + 1),
+    })
+}
+
+/// `items` as the only module of a geode named `test`.
+pub fn geode(items: Vec<Item<Syntax>>) -> Geode<Syntax> {
+    Geode {
+        name: ident("test", 0),
+        modules: vec![Module {
+            name: ident("test", 0),
+            items,
+            file: 0,
+        }],
+    }
+}
+
+/// Type checks `items`, which are expected to resolve.
+pub fn check_items(
+    items: Vec<Item<Syntax>>,
+) -> Result<(Geode<Typed>, Tables, Definitions), Vec<Error>> {
+    let (geode, definitions) =
+        resolve::resolve(geode(items)).unwrap_or_else(|errors| panic!("{}", joined(&errors)));
+    let (geode, tables) = typeck::check(geode, &definitions, &Type::I32)?;
+
+    Ok((geode, tables, definitions))
+}
+
+pub fn messages(errors: &[Error]) -> Vec<(&str, Option<&str>, Span)> {
+    errors
+        .iter()
+        .map(|error| (error.message.as_str(), error.details.as_deref(), error.span))
+        .collect()
+}
+
+/// The span of the first occurrence of `text` in `source`.
+pub fn span(source: &str, text: &str) -> Span {
+    span_in(source, text, text)
+}
+
+/// The span of the first occurrence of `text` within the first occurrence of `context` in
+/// `source`, for text that is not unique on its own.
+pub fn span_in(source: &str, context: &str, text: &str) -> Span {
+    let context = source
+        .find(context)
+        .unwrap_or_else(|| panic!("`{context}` does not occur in the source"));
+    let start = context
+        + source[context..]
+            .find(text)
+            .unwrap_or_else(|| panic!("`{text}` does not occur in its context"));
+
+    Span::new(start, start + text.len())
+}
+
+/// The messages of `errors` along with the spans of their notes.
+pub fn notes(errors: &[Error]) -> Vec<(&str, Span, Vec<Span>)> {
+    errors
+        .iter()
+        .map(|error| {
+            let notes = error.notes.iter().map(|note| note.span).collect();
+            (error.message.as_str(), error.span, notes)
+        })
+        .collect()
+}
+
+/// A file system holding only the root module, `main.opal`.
+pub struct Single(pub &'static str);
+
+impl FileSystem for Single {
+    fn read(&self, path: &Path) -> Option<String> {
+        self.exists(path).then(|| self.0.to_owned())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path == Path::new("main.opal")
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        path.to_owned()
+    }
+}
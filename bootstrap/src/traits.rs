@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::model::base::{Mutability, Receiver};
+use crate::model::def::DefId;
+use crate::model::types::Type;
+use crate::span::Span;
+
+/// The signature of a function declared within a `trait` or `impl` block. Within a trait,
+/// the implementing type is written `Self`, which is represented as a type parameter whose
+/// `DefId` is that of the trait itself.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub name: String,
+    pub receiver: Option<Receiver>,
+    pub parameters: Vec<Type>,
+    pub return_type: Type,
+    pub span: Span,
+}
+
+/// A `trait` item. Each method is paired with the function item declaring it, which calls
+/// through a bound on the trait name until monomorphization dispatches them.
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub def: DefId,
+    pub name: String,
+    pub methods: Vec<(Signature, DefId)>,
+    pub span: Span,
+}
+
+/// An `impl Trait for Type { ... }` block. Each method is paired with the function item that
+/// implements it, which is what calls through the trait are dispatched to.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub trait_: DefId,
+    pub self_ty: Type,
+    pub methods: Vec<(Signature, DefId)>,
+    pub span: Span,
+}
+
+/// Every trait, trait implementation, and generic parameter bound within a geode.
+#[derive(Debug, Clone, Default)]
+pub struct TraitEnvironment {
+    traits: HashMap<DefId, Trait>,
+    impls: Vec<Impl>,
+    bounds: HashMap<DefId, Vec<DefId>>,
+}
+
+impl TraitEnvironment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn declare(&mut self, trait_: Trait) {
+        self.traits.insert(trait_.def, trait_);
+    }
+
+    pub fn bound(&mut self, parameter: DefId, trait_: DefId) {
+        self.bounds.entry(parameter).or_default().push(trait_);
+    }
+
+    pub fn lookup(&self, trait_: DefId) -> &Trait {
+        self.traits
+            .get(&trait_)
+            .expect("Name resolution should only produce DefIds of declared traits")
+    }
+
+    pub fn implement(&mut self, implementation: Impl) -> Result<(), Vec<Error>> {
+        // Coherence: a type may implement a trait at most once, otherwise calls through the
+        // trait would have more than one candidate. Conflicting impls are not registered, so
+        // that the first impl remains the one dispatched to.
+
+        let trait_ = self.lookup(implementation.trait_);
+
+        if let Some(previous) = self.impls.iter().find(|existing| {
+            existing.trait_ == implementation.trait_ && existing.self_ty == implementation.self_ty
+        }) {
+            return Err(vec![Error::new(
+                implementation.span,
+                format!(
+                    "Conflicting implementations of trait `{}` for type `{}`",
+                    trait_.name, implementation.self_ty
+                ),
+            )
+            .with_note(
+                previous.span,
+                String::from("First implementation is here"),
+            )]);
+        }
+
+        let errors = Self::check_methods(trait_, &implementation);
+        self.impls.push(implementation);
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    fn check_methods(trait_: &Trait, implementation: &Impl) -> Vec<Error> {
+        // Every method declared by the trait must be implemented with the same signature,
+        // once `Self` is replaced by the implementing type. Methods the trait does not declare
+        // belong in an inherent `impl` block instead.
+
+        let mut errors = Vec::new();
+        let substitutions = [(trait_.def, implementation.self_ty.clone())];
+
+        for (declared, _) in &trait_.methods {
+            let found = implementation
+                .methods
+                .iter()
+                .find(|(method, _)| method.name == declared.name);
+
+            let method = match found {
+                Some((method, _)) => method,
+                None => {
+                    errors.push(
+                        Error::new(
+                            implementation.span,
+                            format!(
+                                "Implementation of trait `{}` for type `{}` is missing method `{}`",
+                                trait_.name, implementation.self_ty, declared.name
+                            ),
+                        )
+                        .with_note(declared.span, String::from("Method is declared here")),
+                    );
+                    continue;
+                }
+            };
+
+            let expected_parameters = declared
+                .parameters
+                .iter()
+                .map(|ty| ty.substitute(&substitutions))
+                .collect::<Vec<_>>();
+            let expected_return = declared.return_type.substitute(&substitutions);
+
+            if method.receiver != declared.receiver
+                || method.parameters != expected_parameters
+                || method.return_type != expected_return
+            {
+                errors.push(
+                    Error::with_details(
+                        method.span,
+                        format!(
+                            "Method `{}` does not match its declaration in trait `{}`",
+                            method.name, trait_.name
+                        ),
+                        format!(
+                            "Expected `fn({}) -> {}`, found `fn({}) -> {}`",
+                            signature_parameters(&declared.receiver, &expected_parameters),
+                            expected_return,
+                            signature_parameters(&method.receiver, &method.parameters),
+                            method.return_type,
+                        ),
+                    )
+                    .with_note(declared.span, String::from("Method is declared here")),
+                );
+            }
+        }
+
+        for (method, _) in &implementation.methods {
+            if !trait_
+                .methods
+                .iter()
+                .any(|(declared, _)| declared.name == method.name)
+            {
+                errors.push(Error::new(
+                    method.span,
+                    format!(
+                        "Method `{}` is not a member of trait `{}`",
+                        method.name, trait_.name
+                    ),
+                ));
+            }
+        }
+
+        errors
+    }
+
+    pub fn implements(&self, ty: &Type, trait_: DefId) -> bool {
+        match ty {
+            // Within a generic function, a parameter implements exactly the traits it is
+            // bounded by. The bounds are checked again at every instantiation.
+            Type::Parameter(parameter, _) => self
+                .bounds
+                .get(parameter)
+                .is_some_and(|bounds| bounds.contains(&trait_)),
+            _ => self
+                .impls
+                .iter()
+                .any(|imp| imp.trait_ == trait_ && imp.self_ty == *ty),
+        }
+    }
+
+    /// Checks that each generic argument satisfies the bounds of the parameter it instantiates.
+    pub fn check_bounds(&self, arguments: &[(DefId, Type)], span: Span) -> Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+
+        for (parameter, argument) in arguments {
+            for bound in self.bounds.get(parameter).into_iter().flatten() {
+                if !self.implements(argument, *bound) {
+                    errors.push(Error::new(
+                        span,
+                        format!(
+                            "The trait `{}` is not implemented for type `{}`",
+                            self.lookup(*bound).name,
+                            argument
+                        ),
+                    ));
+                }
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Finds the trait method called by `value.name(...)` where `value` has the type of the
+    /// given generic parameter. Only the parameter's bounds are searched. Gives the trait
+    /// along with the method's signature and the function declaring it.
+    pub fn bound_method(
+        &self,
+        parameter: DefId,
+        name: &str,
+        span: Span,
+    ) -> Result<(DefId, &Signature, DefId), Error> {
+        let mut candidates = self
+            .bounds
+            .get(&parameter)
+            .into_iter()
+            .flatten()
+            .map(|bound| self.lookup(*bound))
+            .filter_map(|trait_| {
+                trait_
+                    .methods
+                    .iter()
+                    .find(|(method, _)| method.name == name)
+                    .map(|(method, function)| (trait_.def, method, *function))
+            });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(found), None) => Ok(found),
+            (None, _) => Err(Error::new(
+                span,
+                format!(
+                    "No method named `{name}` is provided by the bounds of this type parameter"
+                ),
+            )),
+            (Some((first, _, _)), Some((second, _, _))) => Err(Error::with_details(
+                span,
+                format!("Method `{name}` is ambiguous"),
+                format!(
+                    "Both `{}` and `{}` declare a method named `{name}`",
+                    self.lookup(first).name,
+                    self.lookup(second).name
+                ),
+            )),
+        }
+    }
+
+    /// Static dispatch: finds the function implementing a trait's method for a concrete type.
+    /// This is only meaningful after monomorphization, once `self_ty` no longer mentions any
+    /// generic parameters.
+    pub fn dispatch(&self, trait_: DefId, name: &str, self_ty: &Type) -> Option<DefId> {
+        self.impls
+            .iter()
+            .find(|imp| imp.trait_ == trait_ && imp.self_ty == *self_ty)
+            .and_then(|imp| imp.methods.iter().find(|(method, _)| method.name == name))
+            .map(|(_, function)| *function)
+    }
+}
+
+fn signature_parameters(receiver: &Option<Receiver>, parameters: &[Type]) -> String {
+    let receiver = receiver.iter().map(|receiver| match receiver {
+        Receiver::Value => String::from("self"),
+        Receiver::Reference(Mutability::Immutable) => String::from("&self"),
+        Receiver::Reference(Mutability::Mutable) => String::from("&mut self"),
+    });
+
+    receiver
+        .chain(parameters.iter().map(|ty| ty.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::traits::*;
+
+    const SHOW: DefId = DefId(0);
+    const SHOW_U8: DefId = DefId(1);
+    const T: DefId = DefId(2);
+    const SHOW_METHOD: DefId = DefId(4);
+
+    fn show() -> Signature {
+        Signature {
+            name: "show".to_owned(),
+            receiver: Some(Receiver::Value),
+            parameters: Vec::new(),
+            return_type: Type::Str,
+            span: Span::new(0, 1),
+        }
+    }
+
+    fn environment() -> TraitEnvironment {
+        let mut env = TraitEnvironment::new();
+        env.declare(Trait {
+            def: SHOW,
+            name: "Show".to_owned(),
+            methods: vec![(show(), SHOW_METHOD)],
+            span: Span::new(0, 1),
+        });
+        env
+    }
+
+    fn show_for(ty: Type, methods: Vec<(Signature, DefId)>) -> Impl {
+        Impl {
+            trait_: SHOW,
+            self_ty: ty,
+            methods,
+            span: Span::new(2, 3),
+        }
+    }
+
+    #[test]
+    fn dispatch_to_impl() {
+        let mut env = environment();
+        env.implement(show_for(Type::U8, vec![(show(), SHOW_U8)]))
+            .unwrap();
+
+        assert!(env.implements(&Type::U8, SHOW));
+        assert_eq!(env.dispatch(SHOW, "show", &Type::U8), Some(SHOW_U8));
+        assert_eq!(env.dispatch(SHOW, "show", &Type::I8), None);
+    }
+
+    #[test]
+    fn conflicting_impls() {
+        let mut env = environment();
+        env.implement(show_for(Type::U8, vec![(show(), SHOW_U8)]))
+            .unwrap();
+
+        let errors = env
+            .implement(show_for(Type::U8, vec![(show(), DefId(3))]))
+            .unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].notes[0].span, Span::new(2, 3));
+        assert_eq!(env.dispatch(SHOW, "show", &Type::U8), Some(SHOW_U8));
+    }
+
+    #[test]
+    fn missing_method() {
+        let mut env = environment();
+        let errors = env.implement(show_for(Type::U8, Vec::new())).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn bounds() {
+        let mut env = environment();
+        env.bound(T, SHOW);
+        env.implement(show_for(Type::U8, vec![(show(), SHOW_U8)]))
+            .unwrap();
+
+        let parameter = Type::Parameter(T, "T".to_owned());
+        assert!(env.implements(&parameter, SHOW));
+        assert!(env.bound_method(T, "show", Span::new(0, 1)).is_ok());
+        assert!(env.check_bounds(&[(T, Type::U8)], Span::new(0, 1)).is_ok());
+        assert!(env
+            .check_bounds(&[(T, Type::Bool)], Span::new(0, 1))
+            .is_err());
+    }
+}
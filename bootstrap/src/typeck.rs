@@ -0,0 +1,3592 @@
+use std::collections::HashMap;
+
+use crate::asm;
+use crate::consteval;
+use crate::error::Error;
+use crate::index;
+use crate::infer::Inference;
+use crate::loops::{self, Frame, LoopKind, LoopStack};
+use crate::method::{self, Method, MethodError, MethodTable};
+use crate::model::ast::TypeKind;
+use crate::model::base::*;
+use crate::model::def::{DefId, DefKind, Definitions};
+use crate::model::resolved::{self, LocalId, Name, Resolution, Resolved, ResolvedPath};
+use crate::model::symbol::Symbol;
+use crate::model::typed::*;
+use crate::model::types::{InferKind, Type};
+use crate::model::visit::{
+    walk_expression, walk_item, walk_pattern_mut, walk_with_block_mut, walk_without_block_mut,
+    Visitor, VisitorMut,
+};
+use crate::privacy;
+use crate::propagate;
+use crate::span::{Span, Spanned};
+use crate::traits::{Impl, Signature, Trait, TraitEnvironment};
+
+/// A field of a struct or of a struct-like variant, as type checking sees it.
+#[derive(Debug, Clone)]
+pub struct FieldType {
+    pub name: String,
+    pub ty: Type,
+    pub visibility: Visibility,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum VariantShape {
+    Unit,
+    Tuple(Vec<Type>), // Constructed by calling the variant like a function
+    Struct(Vec<FieldType>),
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantType {
+    pub enum_: DefId,
+    pub shape: VariantShape,
+    pub span: Span,
+}
+
+/// What type checking learns about the declarations of a geode. Later passes consult these
+/// alongside the typed tree, rather than lowering type annotations again.
+#[derive(Debug)]
+pub struct Tables {
+    pub signatures: HashMap<DefId, Signature>, // Every function, including associated functions
+    pub generics: HashMap<DefId, Vec<DefId>>, // The type parameters of each generic function or enum
+    pub owners: HashMap<DefId, Type>,         // The `Self` type of each associated function
+    pub fields: HashMap<DefId, Vec<FieldType>>, // The fields of each struct
+    pub variants: HashMap<DefId, VariantType>,
+    pub aliases: HashMap<DefId, Type>, // What each type alias expands to
+    pub globals: HashMap<DefId, Symbol>, // Constants and statics
+    pub constants: HashMap<DefId, u32>, // The values of integer constants, for array lengths
+    pub methods: MethodTable<DefId>,
+    pub traits: TraitEnvironment,
+}
+
+impl Tables {
+    fn new() -> Self {
+        Self {
+            signatures: HashMap::new(),
+            generics: HashMap::new(),
+            owners: HashMap::new(),
+            fields: HashMap::new(),
+            variants: HashMap::new(),
+            aliases: HashMap::new(),
+            globals: HashMap::new(),
+            constants: HashMap::new(),
+            methods: MethodTable::new(),
+            traits: TraitEnvironment::new(),
+        }
+    }
+
+    /// The variants of an enum, in declaration order.
+    pub fn variants_of(&self, enum_: DefId) -> Vec<DefId> {
+        let mut variants = self
+            .variants
+            .iter()
+            .filter(|(_, variant)| variant.enum_ == enum_)
+            .map(|(def, _)| *def)
+            .collect::<Vec<_>>();
+
+        variants.sort();
+        variants
+    }
+
+    /// What each type parameter of a generic enum stands for within `ty`, an instance of it.
+    /// Substituting these gives the types of the fields of its variants.
+    pub fn substitution(&self, ty: &Type) -> Vec<(DefId, Type)> {
+        match ty {
+            Type::Named(def, _, arguments) => match self.generics.get(def) {
+                Some(parameters) => parameters.iter().copied().zip(arguments.clone()).collect(),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Checks the types of every expression in the geode, producing the `Typed` phase along with
+/// the tables describing its declarations.
+///
+/// Checking happens in two steps. The signatures of every item are first collected, so that
+/// items may be used before they are declared, and trait impls are registered once every
+/// trait is known. Type aliases are expanded before anything else, since they are transparent:
+/// an alias is the same type as the one it names. Function bodies are then checked, with types flowing from the outside in:
+/// each expression is checked against the type its context expects, if there is one, which is
+/// how integer literals and empty references learn their types. Types that are still unknown
+/// where they are needed, as for `let x;`, are inferred from later uses within the same body,
+/// and integer literals that nothing constrains take the type `default_integer`.
+///
+/// Structs and enums containing themselves by value are rejected, as they would have infinite
+/// size.
+///
+/// An expression that fails to check is given the type `Never`, which is compatible with
+/// every type, so that one mistake is not reported again by every expression containing it.
+pub fn check(
+    geode: Geode<Resolved>,
+    definitions: &Definitions,
+    default_integer: &Type,
+) -> Result<(Geode<Typed>, Tables), Vec<Error>> {
+    let mut tables = Tables::new();
+    let mut errors = Vec::new();
+    tables.aliases = Aliases::expand_all(&geode, definitions, &mut errors);
+
+    let mut collector = Collector {
+        definitions,
+        tables,
+        impls: Vec::new(),
+        errors,
+    };
+
+    collector.visit_geode(&geode);
+
+    // The trait that provided each trait method in the method table, by type and name.
+    let mut provided: Vec<(Type, String, DefId)> = Vec::new();
+
+    for implementation in std::mem::take(&mut collector.impls) {
+        for (signature, function) in &implementation.methods {
+            // Trait methods can be called with method-call syntax too, unless an inherent
+            // method of the same name takes precedence.
+            let method = Method {
+                name: signature.name.clone(),
+                receiver: signature.receiver.clone(),
+                function: *function,
+                span: signature.span,
+            };
+
+            let Err(MethodError::MultiplyDefined(previous)) = collector
+                .tables
+                .methods
+                .insert(implementation.self_ty.clone(), method)
+            else {
+                provided.push((
+                    implementation.self_ty.clone(),
+                    signature.name.clone(),
+                    implementation.trait_,
+                ));
+                continue;
+            };
+
+            // Implementing the same trait twice is reported when the impl is registered.
+            let other = provided
+                .iter()
+                .find(|(ty, name, _)| *ty == implementation.self_ty && *name == signature.name);
+
+            if let Some((_, _, trait_)) =
+                other.filter(|(_, _, trait_)| *trait_ != implementation.trait_)
+            {
+                collector.errors.push(
+                    Error::with_details(
+                        signature.span,
+                        format!(
+                            "Method `{}` for `{}` is provided by more than one trait",
+                            signature.name, implementation.self_ty
+                        ),
+                        format!(
+                            "Calls to `{}` with method-call syntax would be ambiguous",
+                            signature.name
+                        ),
+                    )
+                    .with_note(
+                        previous,
+                        format!(
+                            "`{}` also provides `{}` here",
+                            definitions.get(*trait_).name,
+                            signature.name
+                        ),
+                    ),
+                );
+            }
+        }
+
+        if let Err(errors) = collector.tables.traits.implement(implementation) {
+            collector.errors.extend(errors);
+        }
+    }
+
+    let sizes = Sizes::check(&collector.tables, definitions);
+    collector.errors.extend(sizes);
+
+    let mut checker = Checker {
+        definitions,
+        tables: collector.tables,
+        locals: HashMap::new(),
+        receiver: None,
+        return_type: None,
+        module: def_of(&geode.name),
+        loops: LoopStack::new(),
+        inference: Inference::new(),
+        literals: Vec::new(),
+        default_integer: default_integer.clone(),
+        errors: collector.errors,
+    };
+
+    let geode = checker.geode(geode);
+
+    match checker.errors.is_empty() {
+        true => Ok((geode, checker.tables)),
+        false => Err(checker.errors),
+    }
+}
+
+/// The type denoted by a type annotation. Type aliases are replaced by the types they expand
+/// to, as found in `aliases`.
+pub fn lower(
+    ty: &resolved::Type,
+    definitions: &Definitions,
+    aliases: &HashMap<DefId, Type>,
+) -> Result<Type, Error> {
+    Ok(match &ty.item {
+        TypeKind::U8 => Type::U8,
+        TypeKind::I8 => Type::I8,
+        TypeKind::U16 => Type::U16,
+        TypeKind::I16 => Type::I16,
+        TypeKind::U32 => Type::U32,
+        TypeKind::I32 => Type::I32,
+        TypeKind::Bool => Type::Bool,
+        TypeKind::Char => Type::Char,
+        TypeKind::Str => Type::Str,
+        TypeKind::Unit => Type::Unit,
+        TypeKind::Array(element, size) => {
+            Type::Array(Box::new(lower(element, definitions, aliases)?), *size)
+        }
+        TypeKind::Slice(element) => Type::Slice(Box::new(lower(element, definitions, aliases)?)),
+        TypeKind::Reference(mutability, inner) => Type::Reference(
+            mutability.clone(),
+            Box::new(lower(inner, definitions, aliases)?),
+        ),
+        TypeKind::Function(parameters, return_type) => Type::Function(
+            parameters
+                .iter()
+                .map(|parameter| lower(parameter, definitions, aliases))
+                .collect::<Result<_, _>>()?,
+            Box::new(match return_type {
+                Some(return_type) => lower(return_type, definitions, aliases)?,
+                None => Type::Unit,
+            }),
+        ),
+        TypeKind::Parenthesized(inner) => lower(inner, definitions, aliases)?,
+        TypeKind::Path(path) => named(path, Vec::new(), ty.span, definitions, aliases)?,
+        TypeKind::Generic(path, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| lower(argument, definitions, aliases))
+                .collect::<Result<_, _>>()?;
+
+            named(path, arguments, ty.span, definitions, aliases)?
+        }
+    })
+}
+
+/// The type named by a path, given the type arguments written after it. Generic enums must be
+/// given exactly as many arguments as they have type parameters, and nothing else takes any.
+fn named(
+    path: &ResolvedPath,
+    arguments: Vec<Type>,
+    span: Span,
+    definitions: &Definitions,
+    aliases: &HashMap<DefId, Type>,
+) -> Result<Type, Error> {
+    let def = match path.resolution {
+        Resolution::Def(DefKind::Struct | DefKind::Enum, def) => {
+            let name = definitions.get(def).name.clone();
+            let parameters = definitions.type_parameters(def).len();
+
+            if arguments.len() != parameters {
+                return Err(Error::with_details(
+                    span,
+                    format!("Wrong number of type arguments for `{name}`"),
+                    format!(
+                        "`{name}` takes {parameters} type arguments, but {} were given",
+                        arguments.len()
+                    ),
+                ));
+            }
+
+            return Ok(Type::Named(def, name, arguments));
+        }
+        Resolution::Def(_, def) => def,
+        Resolution::Local(_) | Resolution::Relative(_, _) => {
+            return Err(Error::new(
+                span,
+                format!("Expected a type, found `{}`", path_name(path)),
+            ))
+        }
+    };
+
+    let definition = definitions.get(def);
+
+    if !arguments.is_empty() {
+        return Err(Error::new(
+            span,
+            format!("`{}` does not take type arguments", definition.name),
+        ));
+    }
+
+    match definition.kind {
+        DefKind::TypeParameter => Ok(Type::Parameter(def, definition.name.clone())),
+        // An alias whose expansion failed was already reported where it is declared.
+        DefKind::TypeAlias => Ok(aliases.get(&def).cloned().unwrap_or(Type::Never)),
+        kind => Err(Error::new(
+            span,
+            format!("Expected a type, found {kind} `{}`", definition.name),
+        )),
+    }
+}
+
+fn generics(generics: Vec<GenericParameter<Resolved>>) -> Vec<GenericParameter<Typed>> {
+    generics
+        .into_iter()
+        .map(|generic| GenericParameter {
+            name: generic.name,
+            bounds: generic.bounds,
+            span: generic.span,
+        })
+        .collect()
+}
+
+pub fn def_of(name: &Name) -> DefId {
+    match name.resolution {
+        Some(Resolution::Def(_, def)) => def,
+        _ => unreachable!("Declared items should resolve to their definitions"),
+    }
+}
+
+fn path_name(path: &ResolvedPath) -> String {
+    path.segments
+        .iter()
+        .map(|segment| segment.item.as_str())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn receiver_type(receiver: &Receiver, owner: &Type) -> Type {
+    match receiver {
+        Receiver::Value => owner.clone(),
+        Receiver::Reference(mutability) => {
+            Type::Reference(mutability.clone(), Box::new(owner.clone()))
+        }
+    }
+}
+
+/// The type of a function as a value, with its receiver, if any, as its first parameter.
+pub fn signature_type(signature: &Signature, owner: Option<&Type>) -> Type {
+    let receiver = signature
+        .receiver
+        .iter()
+        .zip(owner)
+        .map(|(receiver, owner)| receiver_type(receiver, owner));
+
+    Type::Function(
+        receiver
+            .chain(signature.parameters.iter().cloned())
+            .collect(),
+        Box::new(signature.return_type.clone()),
+    )
+}
+
+fn is_integer(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::U8 | Type::I8 | Type::U16 | Type::I16 | Type::U32 | Type::I32
+    )
+}
+
+fn is_signed(ty: &Type) -> bool {
+    matches!(ty, Type::I8 | Type::I16 | Type::I32)
+}
+
+/// Whether a value of the given integer type can hold `value`, or `-value` when `negative`.
+fn fits(value: u32, ty: &Type, negative: bool) -> bool {
+    let max = match (ty, negative) {
+        (Type::U8 | Type::U16 | Type::U32, true) => 0,
+        (Type::U8, false) => u8::MAX as u32,
+        (Type::U16, false) => u16::MAX as u32,
+        (Type::U32, false) => u32::MAX,
+        (Type::I8, false) => i8::MAX as u32,
+        (Type::I8, true) => i8::MIN.unsigned_abs() as u32,
+        (Type::I16, false) => i16::MAX as u32,
+        (Type::I16, true) => i16::MIN.unsigned_abs() as u32,
+        (Type::I32, false) => i32::MAX as u32,
+        (Type::I32, true) => i32::MIN.unsigned_abs(),
+        _ => return true,
+    };
+
+    value <= max
+}
+
+/// Whether `ty` mentions any of the given type parameters.
+fn mentions(ty: &Type, parameters: &[DefId]) -> bool {
+    match ty {
+        Type::Parameter(def, _) => parameters.contains(def),
+        Type::Array(element, _)
+        | Type::Slice(element)
+        | Type::Reference(_, element)
+        | Type::Range(_, element) => mentions(element, parameters),
+        Type::Function(arguments, return_type) => {
+            arguments
+                .iter()
+                .any(|argument| mentions(argument, parameters))
+                || mentions(return_type, parameters)
+        }
+        Type::Named(_, _, arguments) => arguments
+            .iter()
+            .any(|argument| mentions(argument, parameters)),
+        _ => false,
+    }
+}
+
+/// Matches a parameter type mentioning generic parameters against the type of its argument,
+/// recording the first type found for each generic parameter.
+pub fn infer(
+    parameter: &Type,
+    argument: &Type,
+    generics: &[DefId],
+    found: &mut Vec<(DefId, Type)>,
+) {
+    match (parameter, argument) {
+        (_, Type::Never) => {}
+        (Type::Parameter(def, _), _)
+            if generics.contains(def) && !found.iter().any(|(param, _)| param == def) =>
+        {
+            found.push((*def, argument.clone()));
+        }
+        (Type::Array(parameter, _), Type::Array(argument, _))
+        | (Type::Slice(parameter), Type::Slice(argument))
+        | (Type::Reference(_, parameter), Type::Reference(_, argument))
+        | (Type::Range(_, parameter), Type::Range(_, argument)) => {
+            infer(parameter, argument, generics, found)
+        }
+        (Type::Function(parameters, parameter), Type::Function(arguments, argument)) => {
+            for (parameter, argument) in parameters.iter().zip(arguments) {
+                infer(parameter, argument, generics, found);
+            }
+
+            infer(parameter, argument, generics, found);
+        }
+        (Type::Named(a, _, parameters), Type::Named(b, _, arguments)) if a == b => {
+            for (parameter, argument) in parameters.iter().zip(arguments) {
+                infer(parameter, argument, generics, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn mismatch(expected: &Type, found: &Type, span: Span) -> Error {
+    Error::with_details(
+        span,
+        String::from("Mismatched types"),
+        format!("Expected `{expected}`, found `{found}`"),
+    )
+}
+
+/// The aliases mentioned anywhere within a type annotation.
+fn mentioned_aliases(ty: &resolved::Type, found: &mut Vec<DefId>) {
+    match &ty.item {
+        TypeKind::Array(inner, _)
+        | TypeKind::Slice(inner)
+        | TypeKind::Reference(_, inner)
+        | TypeKind::Parenthesized(inner) => mentioned_aliases(inner, found),
+        TypeKind::Function(parameters, return_type) => {
+            for parameter in parameters {
+                mentioned_aliases(parameter, found);
+            }
+
+            if let Some(return_type) = return_type {
+                mentioned_aliases(return_type, found);
+            }
+        }
+        TypeKind::Path(path) => {
+            if let Resolution::Def(DefKind::TypeAlias, def) = path.resolution {
+                found.push(def);
+            }
+        }
+        TypeKind::Generic(_, arguments) => {
+            for argument in arguments {
+                mentioned_aliases(argument, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Expands every type alias of the geode, before anything else is lowered. An alias is
+/// expanded after the aliases its annotation mentions, so that lowering it can substitute
+/// them. Aliases that mention themselves, directly or through other aliases, would expand
+/// forever, and are reported and expanded to `Never` instead.
+struct Aliases<'a> {
+    definitions: &'a Definitions,
+    annotations: Vec<(DefId, &'a resolved::Type)>, // In declaration order
+    expanded: HashMap<DefId, Type>,
+    expanding: Vec<DefId>, // The aliases being expanded, innermost last
+    errors: Vec<Error>,
+}
+
+impl<'a> Aliases<'a> {
+    fn expand_all(
+        geode: &'a Geode<Resolved>,
+        definitions: &'a Definitions,
+        errors: &mut Vec<Error>,
+    ) -> HashMap<DefId, Type> {
+        let mut aliases = Aliases {
+            definitions,
+            annotations: Vec::new(),
+            expanded: HashMap::new(),
+            expanding: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        for module in &geode.modules {
+            aliases.collect(&module.items);
+        }
+
+        for index in 0..aliases.annotations.len() {
+            aliases.expand(aliases.annotations[index].0);
+        }
+
+        errors.extend(aliases.errors);
+        aliases.expanded
+    }
+
+    fn collect(&mut self, items: &'a [Item<Resolved>]) {
+        for item in items {
+            match &item.kind {
+                ItemKind::TypeAlias(alias) => {
+                    self.annotations.push((def_of(&alias.name), &alias.ty))
+                }
+                ItemKind::Mod(ModItem {
+                    body: ModBody::Inline(items),
+                    ..
+                }) => self.collect(items),
+                _ => {}
+            }
+        }
+    }
+
+    fn annotation(&self, alias: DefId) -> &'a resolved::Type {
+        match self.annotations.iter().find(|(def, _)| *def == alias) {
+            Some((_, annotation)) => annotation,
+            None => unreachable!("Every alias should have been collected"),
+        }
+    }
+
+    fn expand(&mut self, alias: DefId) {
+        if self.expanded.contains_key(&alias) {
+            return;
+        }
+
+        if let Some(start) = self.expanding.iter().position(|def| *def == alias) {
+            return self.cycle(start);
+        }
+
+        let annotation = self.annotation(alias);
+        let mut mentioned = Vec::new();
+        mentioned_aliases(annotation, &mut mentioned);
+
+        self.expanding.push(alias);
+
+        for inner in mentioned {
+            self.expand(inner);
+        }
+
+        self.expanding.pop();
+
+        // An alias within a cycle was already given its expansion when the cycle was found.
+        if !self.expanded.contains_key(&alias) {
+            let ty = lower(annotation, self.definitions, &self.expanded).unwrap_or_else(|error| {
+                self.errors.push(error);
+                Type::Never
+            });
+
+            self.expanded.insert(alias, ty);
+        }
+    }
+
+    /// Reports the cycle formed by the aliases being expanded from `start` onwards.
+    fn cycle(&mut self, start: usize) {
+        let cycle = self.expanding[start..].to_vec();
+        let first = self.definitions.get(cycle[0]);
+
+        let mut error = Error::with_details(
+            first.span,
+            format!("Type alias `{}` is defined in terms of itself", first.name),
+            String::from(
+                "Aliases are expanded wherever they are used, so their expansion would never end",
+            ),
+        );
+
+        for (index, alias) in cycle.iter().enumerate() {
+            let next = cycle[(index + 1) % cycle.len()];
+            error = error.with_note(
+                self.annotation(*alias).span,
+                format!(
+                    "`{}` refers to `{}` here",
+                    self.definitions.get(*alias).name,
+                    self.definitions.get(next).name
+                ),
+            );
+        }
+
+        self.errors.push(error);
+
+        for alias in cycle {
+            self.expanded.insert(alias, Type::Never);
+        }
+    }
+}
+
+/// The structs and enums contained by value within a type, and so whose size is part of its
+/// own. References, slices, and functions are pointers, whose size does not depend on what
+/// they point to. The type arguments of a generic enum may be stored in its variants, so they
+/// count as contained too.
+fn contained(ty: &Type, found: &mut Vec<DefId>) {
+    match ty {
+        Type::Named(def, _, arguments) => {
+            found.push(*def);
+
+            for argument in arguments {
+                contained(argument, found);
+            }
+        }
+        Type::Array(element, _) => contained(element, found),
+        _ => {}
+    }
+}
+
+/// Reports structs and enums that contain themselves by value, directly or through other
+/// structs and enums, since no finite amount of memory could hold one of their values.
+struct Sizes<'a> {
+    definitions: &'a Definitions,
+    contains: HashMap<DefId, Vec<(DefId, Span)>>, // What each type contains, and where
+    path: Vec<(DefId, Span)>, // The types being visited, each with the field leading onwards
+    done: Vec<DefId>,
+    reported: Vec<DefId>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Sizes<'a> {
+    fn check(tables: &Tables, definitions: &'a Definitions) -> Vec<Error> {
+        let mut contains = HashMap::<DefId, Vec<(DefId, Span)>>::new();
+
+        let mut add = |owner: DefId, fields: Vec<(&Type, Span)>| {
+            for (ty, span) in fields {
+                let mut found = Vec::new();
+                contained(ty, &mut found);
+
+                let edges = contains.entry(owner).or_default();
+                edges.extend(found.into_iter().map(|inner| (inner, span)));
+            }
+        };
+
+        for (structure, fields) in &tables.fields {
+            add(
+                *structure,
+                fields.iter().map(|field| (&field.ty, field.span)).collect(),
+            );
+        }
+
+        let mut variants = tables.variants.iter().collect::<Vec<_>>();
+        variants.sort_by_key(|(def, _)| **def);
+
+        for (_, variant) in variants {
+            let fields = match &variant.shape {
+                VariantShape::Unit => Vec::new(),
+                VariantShape::Tuple(types) => types.iter().map(|ty| (ty, variant.span)).collect(),
+                VariantShape::Struct(fields) => {
+                    fields.iter().map(|field| (&field.ty, field.span)).collect()
+                }
+            };
+
+            add(variant.enum_, fields);
+        }
+
+        // Visiting in declaration order keeps the reports deterministic.
+        let mut types = contains.keys().copied().collect::<Vec<_>>();
+        types.sort();
+
+        let mut sizes = Sizes {
+            definitions,
+            contains,
+            path: Vec::new(),
+            done: Vec::new(),
+            reported: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        for ty in types {
+            sizes.visit(ty);
+        }
+
+        sizes.errors
+    }
+
+    fn visit(&mut self, ty: DefId) {
+        if self.done.contains(&ty) {
+            return;
+        }
+
+        if let Some(start) = self.path.iter().position(|(other, _)| *other == ty) {
+            return self.cycle(start);
+        }
+
+        for (inner, span) in self.contains.get(&ty).cloned().unwrap_or_default() {
+            self.path.push((ty, span));
+            self.visit(inner);
+            self.path.pop();
+        }
+
+        self.done.push(ty);
+    }
+
+    /// Reports the cycle formed by the types being visited from `start` onwards, unless the
+    /// type it starts from was already reported through another of its fields.
+    fn cycle(&mut self, start: usize) {
+        let cycle = &self.path[start..];
+        let (first, _) = cycle[0];
+
+        if self.reported.contains(&first) {
+            return;
+        }
+
+        self.reported.push(first);
+
+        let name = |def: DefId| &self.definitions.get(def).name;
+        let next = cycle.get(1).map_or(first, |(def, _)| *def);
+
+        let mut error = Error::with_details(
+            self.definitions.get(first).span,
+            format!("Recursive type `{}` has infinite size", name(first)),
+            format!(
+                "Store `{}` behind a reference, such as `&{}`, to give it a finite size",
+                name(next),
+                name(next)
+            ),
+        );
+
+        for (index, (ty, span)) in cycle.iter().enumerate() {
+            let inner = cycle.get(index + 1).map_or(first, |(def, _)| *def);
+            error = error.with_note(
+                *span,
+                format!("`{}` contains `{}` here", name(*ty), name(inner)),
+            );
+        }
+
+        self.errors.push(error);
+    }
+}
+
+/// Collects the signatures of every item, before any function body is checked.
+struct Collector<'a> {
+    definitions: &'a Definitions,
+    tables: Tables,
+    impls: Vec<Impl>, // Trait impls, registered once every trait is declared
+    errors: Vec<Error>,
+}
+
+impl Collector<'_> {
+    fn lower(&mut self, ty: &resolved::Type) -> Type {
+        lower(ty, self.definitions, &self.tables.aliases).unwrap_or_else(|error| {
+            self.errors.push(error);
+            Type::Never
+        })
+    }
+
+    fn fields(&mut self, fields: &[Field<Resolved>]) -> Vec<FieldType> {
+        fields
+            .iter()
+            .map(|field| FieldType {
+                name: field.name.ident.item.clone(),
+                ty: self.lower(&field.ty),
+                visibility: field.visibility,
+                span: field.name.ident.span,
+            })
+            .collect()
+    }
+
+    fn function(&mut self, function: &FunctionItem<Resolved>, owner: Option<&Type>) -> Signature {
+        let def = def_of(&function.name);
+        self.generics(def, &function.generics);
+
+        let signature = Signature {
+            name: function.name.ident.item.clone(),
+            receiver: function
+                .receiver
+                .as_ref()
+                .map(|receiver| receiver.item.clone()),
+            parameters: function
+                .parameters
+                .iter()
+                .map(|parameter| self.lower(&parameter.ty))
+                .collect(),
+            return_type: match &function.return_type {
+                Some(return_type) => self.lower(return_type),
+                None => Type::Unit,
+            },
+            span: function.name.ident.span,
+        };
+
+        if let Some(owner) = owner {
+            self.tables.owners.insert(def, owner.clone());
+        }
+
+        self.tables.signatures.insert(def, signature.clone());
+        signature
+    }
+
+    /// Records the type parameters of a generic function or enum, along with their bounds.
+    fn generics(&mut self, item: DefId, generics: &[GenericParameter<Resolved>]) {
+        let parameters = generics
+            .iter()
+            .map(|generic| {
+                let parameter = def_of(&generic.name);
+
+                for bound in &generic.bounds {
+                    if let Resolution::Def(DefKind::Trait, trait_) = bound.resolution {
+                        self.tables.traits.bound(parameter, trait_);
+                    }
+                }
+
+                parameter
+            })
+            .collect::<Vec<_>>();
+
+        if !parameters.is_empty() {
+            self.tables.generics.insert(item, parameters);
+        }
+    }
+
+    fn functions(&mut self, items: &[Item<Resolved>], owner: &Type) -> Vec<(Signature, DefId)> {
+        items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Function(function) => {
+                    Some((self.function(function, Some(owner)), def_of(&function.name)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn constant(&self, path: &ResolvedPath) -> Option<u32> {
+        match path.resolution {
+            Resolution::Def(DefKind::Const, def) => self.tables.constants.get(&def).copied(),
+            _ => None,
+        }
+    }
+}
+
+impl Visitor<Resolved> for Collector<'_> {
+    fn visit_item(&mut self, item: &Item<Resolved>) {
+        match &item.kind {
+            ItemKind::Mod(_) => walk_item(self, item),
+            ItemKind::Use(_) => {}
+            ItemKind::Function(function) => {
+                self.function(function, None);
+            }
+            ItemKind::TypeAlias(_) => {} // Already expanded, before anything could use them
+            ItemKind::Struct(structure) => {
+                let fields = self.fields(&structure.fields);
+                self.tables.fields.insert(def_of(&structure.name), fields);
+            }
+            ItemKind::Enum(enumeration) => {
+                self.generics(def_of(&enumeration.name), &enumeration.generics);
+
+                for variant in &enumeration.variants {
+                    let shape = match &variant.kind {
+                        VariantKind::Unit => VariantShape::Unit,
+                        VariantKind::Tuple(types) => {
+                            VariantShape::Tuple(types.iter().map(|ty| self.lower(ty)).collect())
+                        }
+                        VariantKind::Struct(fields) => VariantShape::Struct(self.fields(fields)),
+                    };
+
+                    self.tables.variants.insert(
+                        def_of(&variant.name),
+                        VariantType {
+                            enum_: def_of(&enumeration.name),
+                            shape,
+                            span: variant.name.ident.span,
+                        },
+                    );
+                }
+            }
+            ItemKind::Const(constant) => {
+                let def = def_of(&constant.name);
+                let ty = self.lower(&constant.ty);
+
+                // Constants are evaluated in declaration order, so an array length may only
+                // name constants declared before it.
+                if is_integer(&ty) {
+                    if let Ok(value) =
+                        consteval::evaluate(&constant.value, &|path| self.constant(path))
+                    {
+                        self.tables.constants.insert(def, value);
+                    }
+                }
+
+                self.tables.globals.insert(def, Symbol::Constant(ty));
+            }
+            ItemKind::Static(statik) => {
+                let ty = self.lower(&statik.ty);
+                self.tables.globals.insert(
+                    def_of(&statik.name),
+                    Symbol::Variable {
+                        ty,
+                        is_assignable: false,
+                    },
+                );
+            }
+            ItemKind::Impl(implementation) => {
+                let self_ty = self.lower(&implementation.ty);
+                let methods = self.functions(&implementation.functions, &self_ty);
+
+                match &implementation.trait_ {
+                    Some(trait_) => match trait_.resolution {
+                        Resolution::Def(DefKind::Trait, def) => self.impls.push(Impl {
+                            trait_: def,
+                            self_ty,
+                            methods,
+                            span: trait_.span(),
+                        }),
+                        _ => {
+                            unreachable!("Name resolution should only accept traits as impl traits")
+                        }
+                    },
+                    None => {
+                        for (signature, function) in methods {
+                            let method = Method {
+                                name: signature.name,
+                                receiver: signature.receiver,
+                                function,
+                                span: signature.span,
+                            };
+
+                            if let Err(MethodError::MultiplyDefined(previous)) =
+                                self.tables.methods.insert(self_ty.clone(), method)
+                            {
+                                self.errors.push(
+                                    Error::new(
+                                        signature.span,
+                                        format!(
+                                            "Duplicate definitions of `{}` for `{self_ty}`",
+                                            self.definitions.get(function).name
+                                        ),
+                                    )
+                                    .with_note(previous, String::from("First definition is here")),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            ItemKind::Trait(trait_) => {
+                let def = def_of(&trait_.name);
+                let self_ty = Type::Parameter(def, String::from("Self"));
+                let methods = self.functions(&trait_.functions, &self_ty);
+
+                self.tables.traits.declare(Trait {
+                    def,
+                    name: trait_.name.ident.item.clone(),
+                    methods,
+                    span: trait_.name.ident.span,
+                });
+            }
+        }
+    }
+}
+
+/// Checks function bodies and the values of constants and statics.
+struct Checker<'a> {
+    definitions: &'a Definitions,
+    tables: Tables,
+    locals: HashMap<LocalId, Symbol>,
+    receiver: Option<Type>, // The type of `self` within the function being checked
+    return_type: Option<Type>, // `None` outside of functions, where `return` is not allowed
+    module: DefId,          // The module being checked, for privacy
+    loops: LoopStack<Option<Spanned<Type>>>,
+    inference: Inference, // The inference variables of the item being checked
+    literals: Vec<(u32, bool, Type, Span)>, // Integer literals, range checked once their types are known
+    default_integer: Type,
+    errors: Vec<Error>,
+}
+
+impl Checker<'_> {
+    fn geode(&mut self, geode: Geode<Resolved>) -> Geode<Typed> {
+        Geode {
+            name: geode.name,
+            modules: geode
+                .modules
+                .into_iter()
+                .map(|module| Module {
+                    name: module.name,
+                    items: self.items(module.items),
+                    file: module.file,
+                })
+                .collect(),
+        }
+    }
+
+    fn items(&mut self, items: Vec<Item<Resolved>>) -> Vec<Item<Typed>> {
+        items.into_iter().map(|item| self.item(item)).collect()
+    }
+
+    fn item(&mut self, item: Item<Resolved>) -> Item<Typed> {
+        let kind = match item.kind {
+            ItemKind::Mod(ModItem { name, body }) => ItemKind::Mod(ModItem {
+                name,
+                body: match body {
+                    ModBody::Inline(items) => ModBody::Inline(self.items(items)),
+                    ModBody::File(id) => ModBody::File(id),
+                },
+            }),
+            ItemKind::Use(tree) => ItemKind::Use(use_tree(tree)),
+            ItemKind::Function(function) => ItemKind::Function(self.function(function)),
+            ItemKind::TypeAlias(TypeAliasItem { name, ty }) => ItemKind::TypeAlias(TypeAliasItem {
+                name,
+                ty: self.declared(&ty),
+            }),
+            ItemKind::Struct(StructItem { name, fields }) => ItemKind::Struct(StructItem {
+                name,
+                fields: self.fields(fields),
+            }),
+            ItemKind::Enum(EnumItem {
+                name,
+                generics: parameters,
+                variants,
+            }) => ItemKind::Enum(EnumItem {
+                name,
+                generics: generics(parameters),
+                variants: variants
+                    .into_iter()
+                    .map(|variant| Variant {
+                        name: variant.name,
+                        kind: match variant.kind {
+                            VariantKind::Unit => VariantKind::Unit,
+                            VariantKind::Tuple(types) => VariantKind::Tuple(
+                                types.iter().map(|ty| self.declared(ty)).collect(),
+                            ),
+                            VariantKind::Struct(fields) => VariantKind::Struct(self.fields(fields)),
+                        },
+                        attributes: self.attributes(variant.attributes),
+                        visibility: variant.visibility,
+                        span: variant.span,
+                    })
+                    .collect(),
+            }),
+            ItemKind::Const(ConstItem { name, ty, value }) => {
+                let ty = self.declared(&ty);
+                self.module = self.definitions.module(def_of(&name));
+                let value = self.check(value, &ty);
+                ItemKind::Const(ConstItem { name, ty, value })
+            }
+            ItemKind::Static(StaticItem { name, ty, value }) => {
+                let ty = self.declared(&ty);
+                self.module = self.definitions.module(def_of(&name));
+                let value = self.check(value, &ty);
+                ItemKind::Static(StaticItem { name, ty, value })
+            }
+            ItemKind::Impl(ImplItem {
+                trait_,
+                ty,
+                functions,
+            }) => ItemKind::Impl(ImplItem {
+                trait_,
+                ty: self.declared(&ty),
+                functions: self.items(functions),
+            }),
+            ItemKind::Trait(TraitItem { name, functions }) => ItemKind::Trait(TraitItem {
+                name,
+                functions: self.items(functions),
+            }),
+        };
+
+        let mut item = Item {
+            kind,
+            attributes: self.attributes(item.attributes),
+            visibility: item.visibility,
+            span: item.span,
+        };
+
+        self.finish(&mut item);
+        item
+    }
+
+    /// Ends inference for an item once it has been checked, replacing the inference variables
+    /// within it by their types. Items nested within it were already finished on their own.
+    fn finish(&mut self, item: &mut Item<Typed>) {
+        for origin in self.inference.finish(&self.default_integer) {
+            self.errors.push(Error::with_details(
+                origin,
+                String::from("Type annotations needed"),
+                String::from("Nothing determines the type of this value"),
+            ));
+        }
+
+        for (value, negative, ty, span) in std::mem::take(&mut self.literals) {
+            let ty = self.inference.resolve(&ty);
+
+            if !fits(value, &ty, negative) {
+                let sign = if negative { "-" } else { "" };
+                self.errors.push(Error::new(
+                    span,
+                    format!("Integer literal `{sign}{value}` is out of range for `{ty}`"),
+                ));
+            }
+        }
+
+        let mut finalize = Finalize(&self.inference);
+
+        match item.kind {
+            ItemKind::Mod(_) | ItemKind::Impl(_) | ItemKind::Trait(_) => {
+                for attribute in &mut item.attributes {
+                    finalize.visit_attribute_mut(attribute);
+                }
+            }
+            _ => {
+                finalize.visit_item_mut(item);
+
+                let mut unsized_values = Unsized {
+                    errors: &mut self.errors,
+                    place: false,
+                };
+                unsized_values.visit_item(item);
+            }
+        }
+
+        self.inference = Inference::new();
+    }
+
+    /// The type of an annotation outside of a function body, whose errors were already
+    /// reported while collecting signatures.
+    fn declared(&self, ty: &resolved::Type) -> Type {
+        lower(ty, self.definitions, &self.tables.aliases).unwrap_or(Type::Never)
+    }
+
+    fn fields(&mut self, fields: Vec<Field<Resolved>>) -> Vec<Field<Typed>> {
+        fields
+            .into_iter()
+            .map(|field| Field {
+                ty: self.declared(&field.ty),
+                name: field.name,
+                attributes: self.attributes(field.attributes),
+                visibility: field.visibility,
+                span: field.span,
+            })
+            .collect()
+    }
+
+    fn attributes(&mut self, attributes: Vec<Attribute<Resolved>>) -> Vec<Attribute<Typed>> {
+        attributes
+            .into_iter()
+            .map(|attribute| Attribute {
+                name: attribute.name,
+                arguments: attribute
+                    .arguments
+                    .into_iter()
+                    .map(|argument| self.expression(argument, None))
+                    .collect(),
+                span: attribute.span,
+            })
+            .collect()
+    }
+
+    fn function(&mut self, function: FunctionItem<Resolved>) -> FunctionItem<Typed> {
+        let def = def_of(&function.name);
+        let signature = self.tables.signatures[&def].clone();
+
+        self.module = self.definitions.module(def);
+        self.receiver = function
+            .receiver
+            .as_ref()
+            .zip(self.tables.owners.get(&def))
+            .map(|(receiver, owner)| receiver_type(&receiver.item, owner));
+        self.return_type = Some(signature.return_type.clone());
+
+        let parameters = function
+            .parameters
+            .into_iter()
+            .zip(&signature.parameters)
+            .map(|(parameter, ty)| {
+                self.bind(
+                    &parameter.name,
+                    ty.clone(),
+                    parameter.mutability == Mutability::Mutable,
+                );
+
+                Parameter {
+                    name: parameter.name,
+                    mutability: parameter.mutability,
+                    ty: ty.clone(),
+                    span: parameter.span,
+                }
+            })
+            .collect();
+
+        let body = function.body.map(|body| {
+            let (body, ty) = self.block(body, Some(&signature.return_type));
+            self.returns(
+                &function.name.ident.item,
+                &signature.return_type,
+                &ty,
+                signature.span,
+            );
+            body
+        });
+
+        self.receiver = None;
+        self.return_type = None;
+
+        FunctionItem {
+            name: function.name,
+            generics: generics(function.generics),
+            receiver: function.receiver,
+            parameters,
+            return_type: function.return_type.map(|_| signature.return_type),
+            body,
+        }
+    }
+
+    /// Checks the type of a function's body against its return type. A body of type `Unit`
+    /// under any other return type reaches its end without a value to return, which is
+    /// reported as such rather than as a plain mismatch.
+    fn returns(&mut self, name: &str, return_type: &Type, body: &Type, span: Span) {
+        let return_type = self.inference.resolve(return_type);
+
+        if *body == Type::Unit && !matches!(return_type, Type::Unit | Type::Never) {
+            self.errors.push(Error::with_details(
+                span,
+                format!("Function `{name}` can reach the end of its body without returning"),
+                format!(
+                    "The function returns `{return_type}`, but its body may finish without a value"
+                ),
+            ));
+        } else {
+            self.require(&return_type, body, span);
+        }
+    }
+
+    fn bind(&mut self, name: &Name, ty: Type, is_assignable: bool) {
+        if let Some(Resolution::Local(local)) = name.resolution {
+            self.locals
+                .insert(local, Symbol::Variable { ty, is_assignable });
+        }
+    }
+
+    fn require(&mut self, expected: &Type, found: &Type, span: Span) {
+        // `Never` is compatible with everything: as a found type because the expression never
+        // produces a value, and as an expected type because an error was already reported.
+        if !self.inference.unify(expected, found) {
+            let expected = self.inference.resolve(expected);
+            let found = self.inference.resolve(found);
+            self.errors.push(mismatch(&expected, &found, span));
+        }
+    }
+
+    /// The type of a value whose type must be known at this point, as when accessing its
+    /// fields. An integer literal whose type is still unknown takes the default integer type,
+    /// since nothing can constrain it afterwards.
+    fn known(&mut self, ty: &Type, span: Span) -> Type {
+        match self.inference.resolve(ty) {
+            ty @ Type::Infer(_, InferKind::Integer) => {
+                let default = self.default_integer.clone();
+                self.inference.unify(&ty, &default);
+                default
+            }
+            Type::Infer(_, InferKind::Any) => {
+                self.errors.push(Error::with_details(
+                    span,
+                    String::from("Type annotations needed"),
+                    String::from("The type of this value must be known at this point"),
+                ));
+                Type::Never
+            }
+            ty => ty,
+        }
+    }
+
+    /// The type of an operand that can only be an integer. An operand whose type is not known
+    /// yet becomes an integer variable.
+    fn integer(&mut self, ty: &Type, span: Span) -> Type {
+        if let Type::Infer(_, InferKind::Any) = self.inference.resolve(ty) {
+            let integer = self.inference.fresh(InferKind::Integer, span);
+            self.inference.unify(ty, &integer);
+        }
+
+        self.inference.resolve(ty)
+    }
+
+    /// The type shared by several expressions whose values are alternatives, like the arms of
+    /// a `when`. The first expression that does not diverge decides the type.
+    fn join(&mut self, types: Vec<Spanned<Type>>) -> Type {
+        let mut decided: Option<Spanned<Type>> = None;
+
+        for ty in types.into_iter().filter(|ty| ty.item != Type::Never) {
+            match &decided {
+                None => decided = Some(ty),
+                Some(first) if self.inference.unify(&first.item, &ty.item) => {}
+                Some(first) => {
+                    let expected = self.inference.resolve(&first.item);
+                    let found = self.inference.resolve(&ty.item);
+                    self.errors.push(
+                        mismatch(&expected, &found, ty.span)
+                            .with_note(first.span, format!("Type `{expected}` is decided here")),
+                    );
+                }
+            }
+        }
+
+        decided.map_or(Type::Never, |ty| ty.item)
+    }
+
+    fn check(&mut self, expression: Expression<Resolved>, expected: &Type) -> Expression<Typed> {
+        let expression = self.expression(expression, Some(expected));
+        self.require(expected, type_of(&expression), expression.span);
+        expression
+    }
+
+    fn boxed(
+        &mut self,
+        expression: Expression<Resolved>,
+        expected: Option<&Type>,
+    ) -> Box<Expression<Typed>> {
+        Box::new(self.expression(expression, expected))
+    }
+
+    fn expression(
+        &mut self,
+        expression: Expression<Resolved>,
+        expected: Option<&Type>,
+    ) -> Expression<Typed> {
+        let span = expression.span;
+
+        let kind = match expression.item {
+            ExpressionKind::ExpressionWithBlock(with_block) => {
+                ExpressionKind::ExpressionWithBlock(self.with_block(with_block, expected, span))
+            }
+            ExpressionKind::ExpressionWithoutBlock(without_block) => {
+                ExpressionKind::ExpressionWithoutBlock(self.without_block(
+                    without_block,
+                    expected,
+                    span,
+                ))
+            }
+        };
+
+        Spanned::new(kind, span)
+    }
+
+    /// Checks a block, whose type is that of its trailing expression. A block without one has
+    /// type `Unit`, unless one of its statements diverges.
+    fn block(
+        &mut self,
+        block: BlockExpression<Resolved>,
+        expected: Option<&Type>,
+    ) -> (BlockExpression<Typed>, Type) {
+        let mut diverges = false;
+        let mut tail = None;
+        let mut checked = Vec::new();
+
+        for statements in block {
+            let statements = statements.map(|statements| match statements {
+                Statements::Leading(first, rest, last) => {
+                    let first = self.statement(first, &mut diverges);
+                    let rest = rest
+                        .into_iter()
+                        .map(|statement| self.statement(statement, &mut diverges))
+                        .collect();
+                    let last = last.map(|last| self.tail(last, expected, &mut tail));
+                    Statements::Leading(first, rest, last)
+                }
+                Statements::Block(last) => Statements::Block(self.tail(last, expected, &mut tail)),
+            });
+
+            checked.push(statements);
+        }
+
+        let ty = match tail {
+            Some(ty) => ty,
+            None if diverges => Type::Never,
+            None => Type::Unit,
+        };
+
+        (checked, ty)
+    }
+
+    fn tail(
+        &mut self,
+        expression: Expression<Resolved>,
+        expected: Option<&Type>,
+        tail: &mut Option<Type>,
+    ) -> Expression<Typed> {
+        let expression = self.expression(expression, expected);
+        *tail = Some(type_of(&expression).clone());
+        expression
+    }
+
+    fn statement(
+        &mut self,
+        statement: Statement<Resolved>,
+        diverges: &mut bool,
+    ) -> Statement<Typed> {
+        match statement {
+            Statement::Empty => Statement::Empty,
+            Statement::Let(Let {
+                name,
+                mutability,
+                ty,
+                initializer,
+                span,
+            }) => {
+                let annotation = ty.map(|ty| {
+                    lower(&ty, self.definitions, &self.tables.aliases).unwrap_or_else(|error| {
+                        self.errors.push(error);
+                        Type::Never
+                    })
+                });
+
+                let initializer = initializer.map(|initializer| match &annotation {
+                    Some(ty) => self.check(initializer, ty),
+                    None => self.expression(initializer, None),
+                });
+
+                let ty = match (annotation, &initializer) {
+                    (Some(ty), _) => ty,
+                    (None, Some(initializer)) => {
+                        let ty = type_of(initializer).clone();
+                        self.inference
+                            .relocate(&ty, initializer.span, name.ident.span);
+                        ty
+                    }
+                    // Initialized later, which is where its type is inferred from.
+                    (None, None) => self.inference.fresh(InferKind::Any, name.ident.span),
+                };
+
+                *diverges |= initializer
+                    .as_ref()
+                    .is_some_and(|initializer| *type_of(initializer) == Type::Never);
+
+                self.bind(&name, ty.clone(), mutability == Mutability::Mutable);
+
+                Statement::Let(Let {
+                    name,
+                    mutability,
+                    ty: Some(ty),
+                    initializer,
+                    span,
+                })
+            }
+            Statement::Expression(expression) => {
+                let expression = self.expression(expression, None);
+                *diverges |= *type_of(&expression) == Type::Never;
+                Statement::Expression(expression)
+            }
+            Statement::Defer(expression) => Statement::Defer(self.expression(expression, None)),
+        }
+    }
+
+    fn with_block(
+        &mut self,
+        expression: ExpressionWithBlock<Resolved>,
+        expected: Option<&Type>,
+        span: Span,
+    ) -> ExpressionWithBlock<Typed> {
+        use ExpressionWithBlock::*;
+
+        match expression {
+            Case(scrutinee, arms, ()) => {
+                let scrutinee = self.boxed(*scrutinee, None);
+                let scrutinee_ty = type_of(&scrutinee).clone();
+                let mut types = Vec::new();
+
+                let arms = arms
+                    .into_iter()
+                    .map(|arm| {
+                        let pattern = self.pattern(arm.pattern, &scrutinee_ty);
+                        let guard = arm.guard.map(|guard| self.check(guard, &Type::Bool));
+                        let expression = self.expression(arm.expression, expected);
+                        types.push(Spanned::new(type_of(&expression).clone(), expression.span));
+
+                        Arm {
+                            pattern,
+                            guard,
+                            expression,
+                        }
+                    })
+                    .collect();
+
+                let ty = self.join(types);
+                Case(scrutinee, arms, ty)
+            }
+            IfIs(scrutinee, pattern, then, otherwise, ()) => {
+                let scrutinee = self.boxed(*scrutinee, None);
+                let pattern = self.pattern(pattern, &type_of(&scrutinee).clone());
+                let then = self.boxed(*then, expected);
+                let otherwise = otherwise.map(|otherwise| self.boxed(*otherwise, expected));
+
+                let ty = self.branches(
+                    Spanned::new(type_of(&then).clone(), then.span),
+                    otherwise.as_deref(),
+                );
+                IfIs(scrutinee, pattern, then, otherwise, ty)
+            }
+            If(condition, then, otherwise, ()) => {
+                let condition = self.boxed_check(*condition, &Type::Bool);
+                let (then, then_ty) = self.block(then, expected);
+                let otherwise = otherwise.map(|otherwise| self.boxed(*otherwise, expected));
+
+                let ty = self.branches(Spanned::new(then_ty, span), otherwise.as_deref());
+                If(condition, then, otherwise, ty)
+            }
+            For(label, name, iterable, body, ()) => {
+                let iterable = self.boxed(*iterable, None);
+                let element = self.element_type(&iterable);
+                self.bind(&name, element, false);
+
+                let (body, _) = self.loop_body(label.clone(), LoopKind::For, body, span);
+                For(label, name, iterable, body, Type::Unit)
+            }
+            While(label, condition, body, ()) => {
+                let condition = self.boxed_check(*condition, &Type::Bool);
+                let (body, frame) = self.loop_body(label.clone(), LoopKind::While, body, span);
+
+                // `while true` without any `break` never finishes, just like a `loop`.
+                let ty = match condition.item {
+                    ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+                        Literal::True,
+                        _,
+                    )) if frame.data.is_none() => Type::Never,
+                    _ => Type::Unit,
+                };
+                While(label, condition, body, ty)
+            }
+            Loop(label, body, ()) => {
+                if let Err(error) = self.loops.enter(label.clone(), LoopKind::Loop, None, span) {
+                    self.errors.push(error);
+                }
+
+                let (body, ty) = self.block(body, Some(&Type::Unit));
+                self.require(&Type::Unit, &ty, span);
+
+                let ty = loops::loop_type(&self.loops.exit());
+                Loop(label, body, ty)
+            }
+            Block(block, ()) => {
+                let (block, ty) = self.block(block, expected);
+                Block(block, ty)
+            }
+        }
+    }
+
+    fn boxed_check(
+        &mut self,
+        expression: Expression<Resolved>,
+        expected: &Type,
+    ) -> Box<Expression<Typed>> {
+        Box::new(self.check(expression, expected))
+    }
+
+    /// The type of an `if` or `if is`. Without an `else`, the `then` branch must be `Unit`.
+    fn branches(&mut self, then: Spanned<Type>, otherwise: Option<&Expression<Typed>>) -> Type {
+        match otherwise {
+            None => {
+                self.require(&Type::Unit, &then.item, then.span);
+                Type::Unit
+            }
+            Some(otherwise) => {
+                let otherwise = Spanned::new(type_of(otherwise).clone(), otherwise.span);
+                self.join(vec![then, otherwise])
+            }
+        }
+    }
+
+    fn loop_body(
+        &mut self,
+        label: Option<Label>,
+        kind: LoopKind,
+        body: BlockExpression<Resolved>,
+        span: Span,
+    ) -> (BlockExpression<Typed>, Frame<Option<Spanned<Type>>>) {
+        if let Err(error) = self.loops.enter(label, kind, None, span) {
+            self.errors.push(error);
+        }
+
+        let (body, ty) = self.block(body, Some(&Type::Unit));
+        self.require(&Type::Unit, &ty, span);
+        (body, self.loops.exit())
+    }
+
+    /// The type bound by `for name in iterable`. Ranges produce their bounds, while arrays and
+    /// slices produce their elements, by reference when iterated through a reference.
+    fn element_type(&mut self, iterable: &Expression<Typed>) -> Type {
+        match self.known(type_of(iterable), iterable.span) {
+            Type::Never => Type::Never,
+            Type::Range(_, bound) => *bound,
+            Type::Array(element, _) | Type::Slice(element) => *element,
+            Type::Reference(mutability, inner) if index::indexable(&inner).is_some() => {
+                let (element, _) = index::indexable(&inner).unwrap();
+                Type::Reference(mutability, Box::new(element.clone()))
+            }
+            ty => {
+                self.errors.push(Error::with_details(
+                    iterable.span,
+                    format!("Cannot iterate over a value of type `{ty}`"),
+                    String::from("Only ranges, arrays, and slices can be iterated over"),
+                ));
+                Type::Never
+            }
+        }
+    }
+
+    fn without_block(
+        &mut self,
+        expression: ExpressionWithoutBlock<Resolved>,
+        expected: Option<&Type>,
+        span: Span,
+    ) -> ExpressionWithoutBlock<Typed> {
+        use ExpressionWithoutBlock::*;
+
+        match expression {
+            ErrorPropagation(inner, ()) => {
+                let inner = self.boxed(*inner, None);
+                let ty = self.propagation(type_of(&inner), span);
+                ErrorPropagation(inner, ty)
+            }
+            Return(value, ()) => {
+                let value = match (self.return_type.clone(), value) {
+                    (Some(return_type), Some(value)) => {
+                        Some(self.boxed_check(*value, &return_type))
+                    }
+                    (Some(return_type), None) => {
+                        self.require(&return_type, &Type::Unit, span);
+                        None
+                    }
+                    (None, value) => {
+                        self.errors.push(Error::new(
+                            span,
+                            String::from("`return` can only be used within a function"),
+                        ));
+                        value.map(|value| self.boxed(*value, None))
+                    }
+                };
+
+                Return(value, Type::Never)
+            }
+            Break(label, value, ()) => {
+                let value = value.map(|value| self.boxed(*value, None));
+                let ty = value.as_ref().map(|value| type_of(value).clone());
+                let inference = &mut self.inference;
+
+                // Unify with the type decided by an earlier `break` first, so that the types
+                // compare equal once resolved.
+                let result = self
+                    .loops
+                    .target(label.as_ref(), "break", span)
+                    .and_then(|frame| {
+                        if let (Some(first), Some(ty)) = (&mut frame.data, &ty) {
+                            inference.unify(&first.item, ty);
+                            first.item = inference.resolve(&first.item);
+                        }
+
+                        let ty = ty.map(|ty| inference.resolve(&ty));
+                        loops::check_break(frame, ty, span)
+                    });
+
+                if let Err(error) = result {
+                    self.errors.push(error);
+                }
+
+                Break(label, value, Type::Never)
+            }
+            Continue(label, ()) => {
+                if let Err(error) = self.loops.target(label.as_ref(), "continue", span) {
+                    self.errors.push(error);
+                }
+
+                Continue(label, Type::Never)
+            }
+            Grouped(inner, ()) => {
+                let inner = self.boxed(*inner, expected);
+                let ty = type_of(&inner).clone();
+                Grouped(inner, ty)
+            }
+            Path(mut path, ()) => {
+                let ty = self.path_type(&path, span);
+
+                // Later passes see an associated function named through its owner as the
+                // function itself, and one of a type parameter as the trait's declaration.
+                if let Resolution::Relative(_, _) = path.resolution {
+                    if let Some(function) = self.callee(&path) {
+                        path.resolution = Resolution::Def(DefKind::AssocFn, function);
+                    }
+                }
+
+                Path(path, ty)
+            }
+            Literal(literal, ()) => {
+                let ty = self.literal_type(&literal, expected, false, span);
+                Literal(literal, ty)
+            }
+            Array(elements, ()) => {
+                let mut element = match expected.map(|ty| self.inference.resolve(ty)) {
+                    Some(Type::Array(element, _)) => Some(*element),
+                    _ => None,
+                };
+
+                let count = elements.len() as u32;
+                let elements = elements
+                    .into_iter()
+                    .map(|expression| match &element {
+                        Some(ty) => {
+                            let ty = ty.clone();
+                            self.check(expression, &ty)
+                        }
+                        None => {
+                            let expression = self.expression(expression, None);
+                            element = Some(type_of(&expression).clone());
+                            expression
+                        }
+                    })
+                    .collect();
+
+                // The element type of an empty array can only be inferred from its uses.
+                let element = element.unwrap_or_else(|| self.inference.fresh(InferKind::Any, span));
+                let ty = Type::Array(Box::new(element), count);
+
+                Array(elements, ty)
+            }
+            Repeat(value, count, ()) => {
+                let element = match expected.map(|ty| self.inference.resolve(ty)) {
+                    Some(Type::Array(element, _)) => Some(*element),
+                    _ => None,
+                };
+
+                let value = self.boxed(*value, element.as_ref());
+                let count = self.boxed_check(*count, &Type::U32);
+
+                let constants = &self.tables.constants;
+                let length =
+                    consteval::evaluate(&count, &|path: &ResolvedPath| match path.resolution {
+                        Resolution::Def(DefKind::Const, def) => constants.get(&def).copied(),
+                        _ => None,
+                    });
+
+                let ty = match length {
+                    Ok(length) => Type::Array(Box::new(type_of(&value).clone()), length),
+                    Err(error) => {
+                        self.errors.push(error);
+                        Type::Never
+                    }
+                };
+
+                Repeat(value, count, ty)
+            }
+            Range(start, end, limits, ()) => {
+                if start.is_none() || end.is_none() {
+                    self.errors.push(Error::with_details(
+                        span,
+                        String::from("Ranges without both a start and an end can only be indices"),
+                        String::from("Other ranges must hold both of their bounds"),
+                    ));
+                }
+
+                let expected = match expected.map(|ty| self.inference.resolve(ty)) {
+                    Some(Type::Range(_, bound)) => Some(*bound),
+                    _ => None,
+                };
+                self.range(start, end, limits, expected.as_ref(), span)
+            }
+            Prefix(operator, operand, ()) => self.prefix(operator, *operand, expected, span),
+            Binary(left, operator, right, ()) => {
+                self.binary(*left, operator, *right, expected, span)
+            }
+            Call(function, arguments, ()) => self.call(*function, arguments, span),
+            MethodCall(receiver, method, arguments, ()) => {
+                self.method_call(*receiver, method, arguments, span)
+            }
+            Field(base, field, ()) => self.field(*base, field),
+            Index(base, index, ()) => {
+                let base = self.boxed(*base, None);
+                let index = match *index {
+                    Spanned {
+                        item: ExpressionKind::ExpressionWithoutBlock(Range(start, end, limits, ())),
+                        span,
+                    } => {
+                        let range = self.range(start, end, limits, Some(&Type::U32), span);
+                        Box::new(Spanned::new(
+                            ExpressionKind::ExpressionWithoutBlock(range),
+                            span,
+                        ))
+                    }
+                    index => self.boxed(index, Some(&Type::U32)),
+                };
+
+                let bounds = match &index.item {
+                    ExpressionKind::ExpressionWithoutBlock(Range(
+                        _,
+                        _,
+                        _,
+                        Type::Range(_, bound),
+                    )) => bound,
+                    _ => type_of(&index),
+                };
+
+                // An index whose type is otherwise unconstrained is a `u32`.
+                if let Type::Infer(_, _) = self.inference.resolve(bounds) {
+                    self.inference.unify(&Type::U32, bounds);
+                }
+
+                match self.inference.resolve(bounds) {
+                    Type::Never | Type::Infer(_, InferKind::Any) => {}
+                    bounds => {
+                        if let Err(error) = index::check_index_type(&bounds, index.span) {
+                            self.errors.push(error);
+                        }
+                    }
+                }
+
+                let ty = match self.known(type_of(&base), base.span) {
+                    Type::Never => Type::Never,
+                    base => index::index_type(&base, &index, span).unwrap_or_else(|error| {
+                        self.errors.push(error);
+                        Type::Never
+                    }),
+                };
+
+                Index(base, index, ty)
+            }
+            Asm(assembly, ()) => {
+                let operands = assembly
+                    .operands
+                    .into_iter()
+                    .map(|operand| Operand {
+                        direction: operand.direction,
+                        name: operand.name,
+                        expression: self.expression(operand.expression, None),
+                        span: operand.span,
+                    })
+                    .collect::<Vec<_>>();
+
+                let types = operands
+                    .iter()
+                    .map(|operand| self.known(type_of(&operand.expression), operand.span))
+                    .collect::<Vec<_>>();
+
+                let assembly = Assembly {
+                    template: assembly.template,
+                    operands,
+                    clobbers: assembly.clobbers,
+                };
+
+                if let Err(errors) = asm::check(&assembly, &types) {
+                    self.errors.extend(errors);
+                }
+
+                Asm(assembly, Type::Unit)
+            }
+            Other(()) => Other(Type::Never),
+        }
+    }
+
+    /// The type of a literal. Integer literals take the integer type expected of them, if any,
+    /// and are otherwise inferred from their uses. Whether they fit in their type is checked
+    /// once it is known.
+    fn literal_type(
+        &mut self,
+        literal: &Literal,
+        expected: Option<&Type>,
+        negative: bool,
+        span: Span,
+    ) -> Type {
+        match literal {
+            Literal::Character(_) => Type::Char,
+            Literal::String(_) => Type::Reference(Mutability::Immutable, Box::new(Type::Str)),
+            Literal::Integer(value) => {
+                let ty = self.inference.fresh(InferKind::Integer, span);
+
+                if let Some(expected) = expected {
+                    if self.inference.is_integer(expected)
+                        || matches!(self.inference.resolve(expected), Type::Infer(_, _))
+                    {
+                        self.inference.unify(expected, &ty);
+                    }
+                }
+
+                self.literals.push((*value, negative, ty.clone(), span));
+                ty
+            }
+            Literal::True | Literal::False => Type::Bool,
+            Literal::Unit => Type::Unit,
+            Literal::Never => Type::Never,
+        }
+    }
+
+    fn path_type(&mut self, path: &ResolvedPath, span: Span) -> Type {
+        match &path.resolution {
+            Resolution::Local(local) => match self.locals.get(local) {
+                Some(Symbol::Variable { ty, .. }) => ty.clone(),
+                // `self` is a keyword, so the only local it can name is the receiver.
+                _ if path_name(path) == "self" => self.receiver.clone().unwrap_or(Type::Never),
+                // The binding failed to check, which was already reported.
+                _ => Type::Never,
+            },
+            Resolution::Def(DefKind::Fn | DefKind::AssocFn, def) => self.function_type(*def),
+            Resolution::Def(DefKind::Const | DefKind::Static, def) => {
+                match self.tables.globals.get(def) {
+                    Some(Symbol::Constant(ty) | Symbol::Variable { ty, .. }) => ty.clone(),
+                    _ => Type::Never,
+                }
+            }
+            Resolution::Def(DefKind::Variant, def) => {
+                let variant = self.tables.variants[def].clone();
+                let (enum_, substitution) = self.instantiate(variant.enum_, span);
+
+                match &variant.shape {
+                    VariantShape::Unit => enum_,
+                    VariantShape::Tuple(types) => Type::Function(
+                        types
+                            .iter()
+                            .map(|ty| ty.substitute(&substitution))
+                            .collect(),
+                        Box::new(enum_),
+                    ),
+                    VariantShape::Struct(_) => {
+                        self.errors.push(Error::new(
+                            span,
+                            format!(
+                                "Expected a value, found struct variant `{}`",
+                                path_name(path)
+                            ),
+                        ));
+                        Type::Never
+                    }
+                }
+            }
+            Resolution::Def(kind, _) => {
+                self.errors.push(Error::new(
+                    span,
+                    format!("Expected a value, found {kind} `{}`", path_name(path)),
+                ));
+                Type::Never
+            }
+            Resolution::Relative(owner, name) => self.relative(*owner, name),
+        }
+    }
+
+    /// The type of `operand?`, where the operand has type `operand`. See `propagate`.
+    fn propagation(&mut self, operand: &Type, span: Span) -> Type {
+        let Some(return_type) = self.return_type.clone() else {
+            self.errors.push(Error::new(
+                span,
+                String::from("The `?` operator can only be used within a function"),
+            ));
+            return Type::Never;
+        };
+
+        let operand = self.known(operand, span);
+
+        // An error type that is still unknown, as for `Err(x)?` where nothing else constrains
+        // it, is taken to be the function's own.
+        if let (Type::Named(a, _, operand), Type::Named(b, _, returned)) = (&operand, &return_type)
+        {
+            if a == b && operand.len() == 2 && returned.len() == 2 {
+                if let Type::Infer(_, InferKind::Any) = self.inference.resolve(&operand[1]) {
+                    self.inference.unify(&operand[1], &returned[1]);
+                }
+            }
+        }
+
+        let operand = self.inference.resolve(&operand);
+        let propagation =
+            propagate::propagation(&operand, &return_type, &self.tables, self.definitions, span);
+
+        match propagation {
+            Ok(propagation) => propagation.output,
+            Err(error) => {
+                self.errors.push(error);
+                Type::Never
+            }
+        }
+    }
+
+    fn function_type(&self, def: DefId) -> Type {
+        signature_type(&self.tables.signatures[&def], self.tables.owners.get(&def))
+    }
+
+    /// The type named by the owner of a relative path, such as `Point` in `Point::new`.
+    fn owner_type(&self, owner: DefId) -> Option<Type> {
+        let definition = self.definitions.get(owner);
+
+        match definition.kind {
+            DefKind::TypeParameter => Some(Type::Parameter(owner, definition.name.clone())),
+            DefKind::Struct | DefKind::Enum => Some(self.named_type(owner)),
+            DefKind::TypeAlias => self.tables.aliases.get(&owner).cloned(),
+            _ => None,
+        }
+    }
+
+    /// The type of `Type::name`, an associated function of a type or of a type parameter's
+    /// bounds.
+    fn relative(&mut self, owner: DefId, name: &Spanned<String>) -> Type {
+        match self.owner_type(owner) {
+            Some(Type::Parameter(parameter, parameter_name)) => {
+                match self
+                    .tables
+                    .traits
+                    .bound_method(parameter, &name.item, name.span)
+                {
+                    Ok((trait_, signature, _)) => {
+                        // Within the trait, `Self` is the trait's own type parameter.
+                        let self_ty = Type::Parameter(trait_, String::from("Self"));
+                        let owner = Type::Parameter(parameter, parameter_name);
+                        signature_type(signature, Some(&self_ty)).substitute(&[(trait_, owner)])
+                    }
+                    Err(error) => {
+                        self.errors.push(error);
+                        Type::Never
+                    }
+                }
+            }
+            // An alias whose expansion failed was already reported.
+            Some(Type::Never) => Type::Never,
+            Some(owner) => match self.tables.methods.associated(&owner, &name.item) {
+                Some(method) => self.function_type(method.function),
+                None => {
+                    self.errors.push(Error::new(
+                        name.span,
+                        format!(
+                            "No associated function named `{}` found for type `{owner}`",
+                            name.item
+                        ),
+                    ));
+                    Type::Never
+                }
+            },
+            None => {
+                let definition = self.definitions.get(owner);
+                self.errors.push(Error::new(
+                    name.span,
+                    format!(
+                        "Associated functions of {} `{}` are not supported yet",
+                        definition.kind, definition.name
+                    ),
+                ));
+                Type::Never
+            }
+        }
+    }
+
+    fn prefix(
+        &mut self,
+        operator: PrefixOperator,
+        operand: Expression<Resolved>,
+        expected: Option<&Type>,
+        span: Span,
+    ) -> ExpressionWithoutBlock<Typed> {
+        let (operand, ty) = match &operator {
+            PrefixOperator::Borrow | PrefixOperator::MutableBorrow => {
+                let mutability = match operator {
+                    PrefixOperator::MutableBorrow => Mutability::Mutable,
+                    _ => Mutability::Immutable,
+                };
+
+                let inner = match expected.map(|ty| self.inference.resolve(ty)) {
+                    Some(Type::Reference(_, inner)) => Some(*inner),
+                    _ => None,
+                };
+
+                let operand = self.boxed(operand, inner.as_ref());
+                let ty = Type::Reference(mutability, Box::new(type_of(&operand).clone()));
+                (operand, ty)
+            }
+            PrefixOperator::DeReference => {
+                let operand = self.boxed(operand, None);
+
+                let ty = match self.known(type_of(&operand), operand.span) {
+                    Type::Reference(_, inner) => *inner,
+                    Type::Never => Type::Never,
+                    ty => {
+                        self.errors.push(Error::new(
+                            span,
+                            format!("Cannot dereference a value of type `{ty}`"),
+                        ));
+                        Type::Never
+                    }
+                };
+
+                (operand, ty)
+            }
+            PrefixOperator::ArithmeticNegate => {
+                // The literal is checked as negative here, so that `-128` fits in an `i8`.
+                let operand = match operand.item {
+                    ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Literal(
+                        literal @ Literal::Integer(_),
+                        (),
+                    )) => {
+                        let ty = self.literal_type(&literal, expected, true, operand.span);
+                        Box::new(Spanned::new(
+                            ExpressionKind::ExpressionWithoutBlock(
+                                ExpressionWithoutBlock::Literal(literal, ty),
+                            ),
+                            operand.span,
+                        ))
+                    }
+                    item => self.boxed(Spanned::new(item, operand.span), expected),
+                };
+
+                let ty = match self.integer(type_of(&operand), span) {
+                    ty if is_signed(&ty) => ty,
+                    ty @ (Type::Infer(_, _) | Type::Never) => ty,
+                    ty => {
+                        self.errors.push(Error::new(
+                            span,
+                            format!("Cannot negate a value of type `{ty}`"),
+                        ));
+                        Type::Never
+                    }
+                };
+
+                (operand, ty)
+            }
+            PrefixOperator::LogicalNegate => {
+                let operand = self.boxed(operand, expected);
+
+                let ty = match self.inference.resolve(type_of(&operand)) {
+                    ty if is_integer(&ty) => ty,
+                    ty @ (Type::Bool | Type::Infer(_, _) | Type::Never) => ty,
+                    ty => {
+                        self.errors.push(Error::new(
+                            span,
+                            format!("Cannot apply `!` to a value of type `{ty}`"),
+                        ));
+                        Type::Never
+                    }
+                };
+
+                (operand, ty)
+            }
+        };
+
+        ExpressionWithoutBlock::Prefix(operator, operand, ty)
+    }
+
+    fn binary(
+        &mut self,
+        left: Expression<Resolved>,
+        operator: BinaryOperator,
+        right: Expression<Resolved>,
+        expected: Option<&Type>,
+        span: Span,
+    ) -> ExpressionWithoutBlock<Typed> {
+        use BinaryOperator::*;
+
+        let (left, right, ty) = match operator {
+            LazyAnd | LazyOr => (
+                self.boxed_check(left, &Type::Bool),
+                self.boxed_check(right, &Type::Bool),
+                Type::Bool,
+            ),
+            Eq | Ne | Gt | Lt | Ge | Le => {
+                let left = self.boxed(left, None);
+                let ty = type_of(&left).clone();
+                let right = self.boxed_check(right, &ty);
+                let ty = self.inference.resolve(&ty);
+
+                let comparable = match operator {
+                    Eq | Ne => {
+                        is_integer(&ty) || matches!(ty, Type::Bool | Type::Char | Type::Unit)
+                    }
+                    _ => is_integer(&ty) || ty == Type::Char,
+                };
+
+                if !comparable && !matches!(ty, Type::Infer(_, _) | Type::Never) {
+                    self.errors.push(Error::new(
+                        span,
+                        format!("Cannot compare values of type `{ty}`"),
+                    ));
+                }
+
+                (left, right, Type::Bool)
+            }
+            Equal => {
+                let left = self.boxed(left, None);
+                self.require_place(&left);
+                let ty = type_of(&left).clone();
+                (left, self.boxed_check(right, &ty), Type::Unit)
+            }
+            _ => {
+                let assignment = matches!(
+                    operator,
+                    PlusEqual
+                        | MinusEqual
+                        | TimesEqual
+                        | DivideEqual
+                        | AndEqual
+                        | OrEqual
+                        | LShiftEqual
+                        | RShiftEqual
+                );
+
+                let hint = match assignment {
+                    true => None,
+                    false => expected.filter(|ty| self.inference.is_integer(ty)),
+                };
+
+                let left = self.boxed(left, hint);
+                let ty = type_of(&left).clone();
+
+                if assignment {
+                    self.require_place(&left);
+                }
+
+                // The shift amount need not have the same type as the value being shifted.
+                let right = match operator {
+                    LShift | RShift | LShiftEqual | RShiftEqual => {
+                        let right = self.boxed(right, Some(&Type::U32));
+                        let amount = self.inference.resolve(type_of(&right));
+
+                        if !self.inference.is_integer(&amount) && amount != Type::Never {
+                            self.errors.push(Error::new(
+                                right.span,
+                                format!("Shift amounts must be integers, but found `{amount}`"),
+                            ));
+                        }
+
+                        right
+                    }
+                    _ => self.boxed_check(right, &ty),
+                };
+
+                let valid = match operator {
+                    And | Or | AndEqual | OrEqual => {
+                        let ty = self.inference.resolve(&ty);
+                        is_integer(&ty) || matches!(ty, Type::Bool | Type::Infer(_, _))
+                    }
+                    _ => {
+                        let ty = self.integer(&ty, span);
+                        self.inference.is_integer(&ty)
+                    }
+                };
+
+                if !valid && ty != Type::Never {
+                    self.errors.push(Error::new(
+                        span,
+                        format!(
+                            "Cannot apply `{}` to a value of type `{ty}`",
+                            symbol(&operator)
+                        ),
+                    ));
+                }
+
+                let ty = match assignment {
+                    true => Type::Unit,
+                    false => ty,
+                };
+
+                (left, right, ty)
+            }
+        };
+
+        ExpressionWithoutBlock::Binary(left, operator, right, ty)
+    }
+
+    fn require_place(&mut self, expression: &Expression<Typed>) {
+        if !asm::is_place(expression) {
+            self.errors.push(Error::with_details(
+                expression.span,
+                String::from("Cannot assign to this expression"),
+                String::from(
+                    "Only variables, fields, indexed elements, and dereferenced references can be assigned to",
+                ),
+            ));
+        }
+    }
+
+    fn call(
+        &mut self,
+        function: Expression<Resolved>,
+        arguments: Vec<Expression<Resolved>>,
+        span: Span,
+    ) -> ExpressionWithoutBlock<Typed> {
+        let function = self.boxed(function, None);
+
+        let generics = match &function.item {
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(path, _)) => {
+                self.callee(path)
+            }
+            _ => None,
+        }
+        .and_then(|callee| self.tables.generics.get(&callee).cloned())
+        .unwrap_or_default();
+
+        let (arguments, ty) = match self.known(type_of(&function), function.span) {
+            Type::Function(parameters, return_type) => {
+                self.arguments(&parameters, &return_type, &generics, arguments, span)
+            }
+            ty => {
+                if ty != Type::Never {
+                    self.errors.push(Error::new(
+                        function.span,
+                        format!("Expected a function, found `{ty}`"),
+                    ));
+                }
+
+                let arguments = arguments
+                    .into_iter()
+                    .map(|argument| self.expression(argument, None))
+                    .collect();
+                (arguments, Type::Never)
+            }
+        };
+
+        ExpressionWithoutBlock::Call(function, arguments, ty)
+    }
+
+    /// The function a path names, if it names one directly. The associated functions of a
+    /// type parameter are those its bounds declare.
+    fn callee(&self, path: &ResolvedPath) -> Option<DefId> {
+        match &path.resolution {
+            Resolution::Def(DefKind::Fn | DefKind::AssocFn, def) => Some(*def),
+            Resolution::Relative(owner, name) => match self.owner_type(*owner)? {
+                Type::Parameter(parameter, _) => self
+                    .tables
+                    .traits
+                    .bound_method(parameter, &name.item, name.span)
+                    .ok()
+                    .map(|(_, _, function)| function),
+                owner => self
+                    .tables
+                    .methods
+                    .associated(&owner, &name.item)
+                    .map(|method| method.function),
+            },
+            _ => None,
+        }
+    }
+
+    /// Checks the arguments of a call to a function with the given parameter types, returning
+    /// them along with the type of the call. The function's generic parameters are inferred
+    /// from the arguments passed for parameters mentioning them.
+    fn arguments(
+        &mut self,
+        parameters: &[Type],
+        return_type: &Type,
+        generics: &[DefId],
+        arguments: Vec<Expression<Resolved>>,
+        span: Span,
+    ) -> (Vec<Expression<Typed>>, Type) {
+        if parameters.len() != arguments.len() {
+            self.errors.push(Error::with_details(
+                span,
+                String::from("Wrong number of arguments"),
+                format!(
+                    "Expected {} arguments, found {}",
+                    parameters.len(),
+                    arguments.len()
+                ),
+            ));
+        }
+
+        let mut found = Vec::new();
+
+        let arguments = arguments
+            .into_iter()
+            .enumerate()
+            .map(|(index, argument)| match parameters.get(index) {
+                Some(parameter) if !mentions(parameter, generics) => {
+                    self.check(argument, parameter)
+                }
+                Some(parameter) => {
+                    let argument = self.expression(argument, None);
+                    let ty = self.inference.resolve(type_of(&argument));
+                    infer(parameter, &ty, generics, &mut found);
+                    argument
+                }
+                None => self.expression(argument, None),
+            })
+            .collect::<Vec<_>>();
+
+        if generics.is_empty() {
+            return (arguments, return_type.clone());
+        }
+
+        for generic in generics {
+            if !found.iter().any(|(parameter, _)| parameter == generic) {
+                self.errors.push(Error::new(
+                    span,
+                    format!(
+                        "Cannot infer the type of `{}` for this call",
+                        self.definitions.get(*generic).name
+                    ),
+                ));
+                found.push((*generic, Type::Never));
+            }
+        }
+
+        for (parameter, argument) in parameters.iter().zip(&arguments) {
+            if mentions(parameter, generics) {
+                self.require(
+                    &parameter.substitute(&found),
+                    type_of(argument),
+                    argument.span,
+                );
+            }
+        }
+
+        // Bounds can only be checked against known types.
+        let found = found
+            .into_iter()
+            .map(|(generic, ty)| (generic, self.known(&ty, span)))
+            .collect::<Vec<_>>();
+
+        if let Err(errors) = self.tables.traits.check_bounds(&found, span) {
+            self.errors.extend(errors);
+        }
+
+        (arguments, return_type.substitute(&found))
+    }
+
+    /// Checks `receiver.method(arguments)`, desugaring it to a plain call of the method's
+    /// function with the receiver, adjusted to what the method takes, as its first argument.
+    /// A method of a type parameter's bound calls the trait's declaration of it, which
+    /// monomorphization dispatches.
+    fn method_call(
+        &mut self,
+        receiver: Expression<Resolved>,
+        method: Name,
+        arguments: Vec<Expression<Resolved>>,
+        span: Span,
+    ) -> ExpressionWithoutBlock<Typed> {
+        let receiver = self.boxed(receiver, None);
+        let receiver_ty = self.known(type_of(&receiver), receiver.span);
+        let name = &method.ident.item;
+
+        let mut ty = &receiver_ty;
+        let mut derefs = 0;
+        while let Type::Reference(_, inner) = ty {
+            ty = inner;
+            derefs += 1;
+        }
+
+        let found = match ty {
+            Type::Never => Ok(None),
+            Type::Parameter(parameter, _) => self
+                .tables
+                .traits
+                .bound_method(*parameter, name, method.ident.span)
+                .and_then(|(trait_, signature, function)| {
+                    let adjustment =
+                        method::adjust(&receiver_ty, derefs, &signature.receiver, signature.span)
+                            .map_err(|error| self.method_error(error, &receiver_ty, &method))?;
+
+                    // Within the trait, `Self` is the trait's own type parameter.
+                    let self_ty = Type::Parameter(trait_, String::from("Self"));
+                    let arguments = [(trait_, ty.clone())];
+                    let function_ty =
+                        signature_type(signature, Some(&self_ty)).substitute(&arguments);
+
+                    Ok(Some((function, function_ty, adjustment)))
+                }),
+            _ => match self.tables.methods.lookup(&receiver_ty, name) {
+                Ok((found, adjustment)) => Ok(Some((
+                    found.function,
+                    self.function_type(found.function),
+                    adjustment,
+                ))),
+                Err(error) => Err(self.method_error(error, &receiver_ty, &method)),
+            },
+        };
+
+        let (function, function_ty, adjustment) = match found {
+            Ok(Some(found)) => found,
+            Ok(None) | Err(_) => {
+                if let Err(error) = found {
+                    self.errors.push(error);
+                }
+
+                // There is no call to make, and the arguments are still checked on their own.
+                for argument in arguments {
+                    self.expression(argument, None);
+                }
+                return ExpressionWithoutBlock::Grouped(receiver, Type::Never);
+            }
+        };
+
+        let Type::Function(parameters, return_type) = &function_ty else {
+            unreachable!("Methods have function types");
+        };
+        let generics = self.tables.generics.get(&function).cloned();
+        let (arguments, ty) = self.arguments(
+            &parameters[1..],
+            return_type,
+            &generics.unwrap_or_default(),
+            arguments,
+            span,
+        );
+
+        let path = ResolvedPath {
+            segments: vec![method.ident.clone()],
+            resolution: Resolution::Def(DefKind::AssocFn, function),
+        };
+        let function = Spanned::new(
+            ExpressionKind::ExpressionWithoutBlock(ExpressionWithoutBlock::Path(path, function_ty)),
+            method.ident.span,
+        );
+
+        // Each dereference and borrow applied to the receiver has the type of the one before
+        // it, dereferenced or borrowed.
+        let mut adjusted = receiver_ty;
+        let prefix = |operator: &PrefixOperator| {
+            adjusted = match (operator, &adjusted) {
+                (PrefixOperator::DeReference, Type::Reference(_, inner)) => (**inner).clone(),
+                (PrefixOperator::Borrow, _) => {
+                    Type::Reference(Mutability::Immutable, Box::new(adjusted.clone()))
+                }
+                (PrefixOperator::MutableBorrow, _) => {
+                    Type::Reference(Mutability::Mutable, Box::new(adjusted.clone()))
+                }
+                _ => unreachable!("Receivers are only dereferenced and borrowed"),
+            };
+            adjusted.clone()
+        };
+
+        method::desugar(function, *receiver, &adjustment, arguments, ty, prefix)
+    }
+
+    fn method_error(&self, error: MethodError, receiver: &Type, method: &Name) -> Error {
+        let name = &method.ident.item;
+        let span = method.ident.span;
+
+        match error {
+            MethodError::Undefined => Error::new(
+                span,
+                format!("No method named `{name}` found for type `{receiver}`"),
+            ),
+            MethodError::MultiplyDefined(previous) => Error::new(
+                span,
+                format!("Multiple methods named `{name}` found for type `{receiver}`"),
+            )
+            .with_note(previous, String::from("One of them is defined here")),
+            MethodError::NotAMethod(declaration) => Error::with_details(
+                span,
+                format!("`{name}` is an associated function, not a method"),
+                format!("It takes no `self`, so it must be called as `Type::{name}(...)`"),
+            )
+            .with_note(declaration, String::from("Declared here")),
+            MethodError::ImmutableReceiver(declaration) => Error::with_details(
+                span,
+                format!("Cannot call `{name}` through a shared reference"),
+                format!("`{name}` takes `&mut self`, but the receiver has type `{receiver}`"),
+            )
+            .with_note(declaration, String::from("Declared here")),
+        }
+    }
+
+    /// Checks a range, whose bounds have the type `expected` if it is an integer type. Either
+    /// bound may be left out, which only an index allows.
+    fn range(
+        &mut self,
+        start: Option<Box<Expression<Resolved>>>,
+        end: Option<Box<Expression<Resolved>>>,
+        limits: RangeLimits,
+        expected: Option<&Type>,
+        span: Span,
+    ) -> ExpressionWithoutBlock<Typed> {
+        let expected = expected.filter(|ty| self.inference.is_integer(ty));
+        let start = start.map(|start| self.boxed(*start, expected));
+        let end = end.map(|end| match &start {
+            Some(start) => {
+                let ty = type_of(start).clone();
+                self.boxed_check(*end, &ty)
+            }
+            None => self.boxed(*end, expected),
+        });
+
+        let ty = start
+            .iter()
+            .chain(&end)
+            .map(|bound| self.inference.resolve(type_of(bound)))
+            .find(|ty| *ty != Type::Never)
+            .unwrap_or(Type::Never);
+
+        // Only integers can be range bounds, so a bound of unknown type must be one.
+        let ty = self.integer(&ty, span);
+
+        if !self.inference.is_integer(&ty) && ty != Type::Never {
+            self.errors.push(Error::new(
+                span,
+                format!("Range bounds must be integers, but found `{ty}`"),
+            ));
+        }
+
+        ExpressionWithoutBlock::Range(start, end, limits, Type::Range(limits, Box::new(ty)))
+    }
+
+    /// The type of `base.name`, looking through any references to the struct. Arrays and
+    /// slices have the builtin `.len` instead of fields, and ranges `.start` and `.end`.
+    fn field(&mut self, base: Expression<Resolved>, field: Name) -> ExpressionWithoutBlock<Typed> {
+        let base = self.boxed(base, None);
+        let name = &field.ident.item;
+
+        let base_ty = self.known(type_of(&base), base.span);
+        let mut owner = &base_ty;
+        while let Type::Reference(_, inner) = owner {
+            owner = inner;
+        }
+
+        let declared = match owner {
+            Type::Named(def, _, _) => self
+                .tables
+                .fields
+                .get(def)
+                .and_then(|fields| fields.iter().find(|field| field.name == *name))
+                .map(|declared| (*def, declared.clone())),
+            _ => None,
+        };
+
+        let ty = match (owner, declared) {
+            (Type::Never, _) => Type::Never,
+            (owner, Some((def, declared))) => {
+                let module = self.definitions.module_path(self.definitions.module(def));
+                let from = self.definitions.module_path(self.module);
+                let declaration = privacy::Declaration {
+                    name,
+                    visibility: declared.visibility,
+                    module: &module,
+                    span: declared.span,
+                };
+
+                if let Err(error) =
+                    privacy::check_field(&owner.to_string(), &declaration, &from, field.ident.span)
+                {
+                    self.errors.push(error);
+                }
+
+                declared.ty
+            }
+            (owner, None) => match index::builtin_field_type(owner, name) {
+                Some(ty) => ty,
+                None => {
+                    self.errors.push(Error::new(
+                        field.ident.span,
+                        format!("No field named `{name}` on type `{owner}`"),
+                    ));
+                    Type::Never
+                }
+            },
+        };
+
+        ExpressionWithoutBlock::Field(base, field, ty)
+    }
+
+    /// Checks a pattern matching values of the `expected` type, giving each of its bindings
+    /// the type of the part of the value it binds.
+    fn pattern(&mut self, pattern: Pattern<Resolved>, expected: &Type) -> Pattern<Typed> {
+        use PatternKind::*;
+
+        let span = pattern.span;
+        let kind = match pattern.item {
+            Wildcard(()) => Wildcard(expected.clone()),
+            Binding(mutability, name, ()) => {
+                self.bind(&name, expected.clone(), mutability == Mutability::Mutable);
+                Binding(mutability, name, expected.clone())
+            }
+            Literal(constant, ()) => {
+                let ty = self.constant_type(&constant, expected, span);
+                self.require(expected, &ty, span);
+                Literal(constant, expected.clone())
+            }
+            Range(start, end, limits, ()) => {
+                for bound in start.iter().chain(&end) {
+                    if let Constant::Bool(_) = bound {
+                        self.errors.push(Error::new(
+                            span,
+                            String::from(
+                                "Range patterns are only allowed for integers and characters",
+                            ),
+                        ));
+                    }
+
+                    let ty = self.constant_type(bound, expected, span);
+                    self.require(expected, &ty, span);
+                }
+
+                Range(start, end, limits, expected.clone())
+            }
+            Path(path, ()) => {
+                let ty = match &path.resolution {
+                    Resolution::Def(DefKind::Variant, def) => {
+                        let variant = &self.tables.variants[def];
+                        let (enum_, _) = self.expected_instance(variant.enum_, expected);
+
+                        if !matches!(variant.shape, VariantShape::Unit) {
+                            self.errors.push(Error::with_details(
+                                path.span(),
+                                format!("Expected a unit variant, found `{}`", path_name(&path)),
+                                String::from("Match its fields too, or ignore them with `..`"),
+                            ));
+                        }
+
+                        enum_
+                    }
+                    Resolution::Def(DefKind::Const, def) => match self.tables.globals.get(def) {
+                        Some(Symbol::Constant(ty)) => ty.clone(),
+                        _ => Type::Never,
+                    },
+                    _ => {
+                        self.errors.push(Error::new(
+                            path.span(),
+                            format!(
+                                "Expected a unit variant or constant, found `{}`",
+                                path_name(&path)
+                            ),
+                        ));
+                        Type::Never
+                    }
+                };
+
+                self.require(expected, &ty, span);
+                Path(path, expected.clone())
+            }
+            Tuple(path, elements, ()) => {
+                let variant = match &path.resolution {
+                    Resolution::Def(DefKind::Variant, def) => {
+                        Some(self.tables.variants[def].clone())
+                    }
+                    _ => None,
+                };
+
+                let (ty, types) = match variant {
+                    Some(VariantType {
+                        enum_,
+                        shape: VariantShape::Tuple(types),
+                        ..
+                    }) => {
+                        if types.len() != elements.len() {
+                            self.errors.push(Error::with_details(
+                                span,
+                                String::from("Wrong number of fields"),
+                                format!(
+                                    "`{}` has {} fields, but the pattern has {}",
+                                    path_name(&path),
+                                    types.len(),
+                                    elements.len()
+                                ),
+                            ));
+                        }
+
+                        let (ty, substitution) = self.expected_instance(enum_, expected);
+                        let types = types.iter().map(|ty| ty.substitute(&substitution));
+                        (ty, types.collect())
+                    }
+                    _ => {
+                        self.errors.push(Error::new(
+                            path.span(),
+                            format!("Expected a tuple variant, found `{}`", path_name(&path)),
+                        ));
+                        (Type::Never, Vec::new())
+                    }
+                };
+
+                self.require(expected, &ty, span);
+
+                let elements = elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, element)| {
+                        let ty = types.get(index).cloned().unwrap_or(Type::Never);
+                        self.pattern(element, &ty)
+                    })
+                    .collect();
+
+                Tuple(path, elements, expected.clone())
+            }
+            Struct(path, fields, rest, ()) => {
+                let (owner, declared) = match &path.resolution {
+                    Resolution::Def(DefKind::Struct, def) => {
+                        (Some(*def), self.tables.fields.get(def).cloned())
+                    }
+                    Resolution::Def(DefKind::Variant, def) => {
+                        let variant = &self.tables.variants[def];
+                        let declared = match &variant.shape {
+                            VariantShape::Struct(fields) => Some(fields.clone()),
+                            _ => None,
+                        };
+
+                        (Some(variant.enum_), declared)
+                    }
+                    _ => (None, None),
+                };
+
+                let (ty, declared) = match (owner, declared) {
+                    (Some(owner), Some(declared)) => {
+                        let (ty, substitution) = self.expected_instance(owner, expected);
+                        let declared = declared
+                            .into_iter()
+                            .map(|field| FieldType {
+                                ty: field.ty.substitute(&substitution),
+                                ..field
+                            })
+                            .collect();
+
+                        (ty, declared)
+                    }
+                    _ => {
+                        self.errors.push(Error::new(
+                            path.span(),
+                            format!(
+                                "Expected a struct or struct-like variant, found `{}`",
+                                path_name(&path)
+                            ),
+                        ));
+                        (Type::Never, Vec::new())
+                    }
+                };
+
+                self.require(expected, &ty, span);
+
+                let module = owner
+                    .map(|owner| self.definitions.module_path(self.definitions.module(owner)))
+                    .unwrap_or_default();
+                let from = self.definitions.module_path(self.module);
+                let mut matched: Vec<&Spanned<String>> = Vec::new();
+                let mut typed = Vec::new();
+
+                for field in &fields {
+                    let name = &field.name.ident;
+
+                    if let Some(previous) =
+                        matched.iter().find(|previous| previous.item == name.item)
+                    {
+                        self.errors.push(
+                            Error::new(
+                                name.span,
+                                format!("Field `{}` is matched more than once", name.item),
+                            )
+                            .with_note(previous.span, String::from("First matched here")),
+                        );
+                    }
+                    matched.push(name);
+
+                    match declared.iter().find(|declared| declared.name == name.item) {
+                        Some(declared) => {
+                            let declaration = privacy::Declaration {
+                                name: &name.item,
+                                visibility: declared.visibility,
+                                module: &module,
+                                span: declared.span,
+                            };
+
+                            if let Err(error) = privacy::check_field(
+                                &ty.to_string(),
+                                &declaration,
+                                &from,
+                                name.span,
+                            ) {
+                                self.errors.push(error);
+                            }
+
+                            typed.push(declared.ty.clone());
+                        }
+                        None => {
+                            if !declared.is_empty() {
+                                self.errors.push(Error::new(
+                                    name.span,
+                                    format!("No field named `{}` on type `{ty}`", name.item),
+                                ));
+                            }
+                            typed.push(Type::Never);
+                        }
+                    }
+                }
+
+                let missing = declared
+                    .iter()
+                    .filter(|declared| !matched.iter().any(|name| name.item == declared.name))
+                    .map(|declared| format!("`{}`", declared.name))
+                    .collect::<Vec<_>>();
+
+                if !rest && !missing.is_empty() {
+                    self.errors.push(Error::with_details(
+                        span,
+                        format!("Pattern does not mention {}", missing.join(", ")),
+                        String::from("Ignore the remaining fields with `..`"),
+                    ));
+                }
+
+                let fields = fields
+                    .into_iter()
+                    .zip(typed)
+                    .map(|(field, ty)| FieldPattern {
+                        pattern: self.pattern(field.pattern, &ty),
+                        name: field.name,
+                    })
+                    .collect();
+
+                Struct(path, fields, rest, expected.clone())
+            }
+        };
+
+        Spanned::new(kind, span)
+    }
+
+    fn constant_type(&mut self, constant: &Constant, expected: &Type, span: Span) -> Type {
+        match constant {
+            Constant::Integer { negative, value } => {
+                self.literal_type(&Literal::Integer(*value), Some(expected), *negative, span)
+            }
+            Constant::Character(_) => Type::Char,
+            Constant::Bool(_) => Type::Bool,
+        }
+    }
+
+    /// The type of a struct or enum, ignoring any type parameters it has.
+    fn named_type(&self, def: DefId) -> Type {
+        Type::Named(def, self.definitions.get(def).name.clone(), Vec::new())
+    }
+
+    /// The type of a value of a generic enum constructed at `span`, whose type arguments are
+    /// inferred from how it is used. Also returns the substitution for the types of its fields.
+    fn instantiate(&mut self, def: DefId, span: Span) -> (Type, Vec<(DefId, Type)>) {
+        let substitution = self
+            .tables
+            .generics
+            .get(&def)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|parameter| (parameter, self.inference.fresh(InferKind::Any, span)))
+            .collect::<Vec<_>>();
+
+        self.instance(def, substitution)
+    }
+
+    /// The type of a struct or enum matched by a pattern expecting `expected`. A generic enum
+    /// takes its type arguments from `expected`, or is left unknown as `Never` if `expected`
+    /// is another type, which is reported as a mismatch.
+    fn expected_instance(&self, def: DefId, expected: &Type) -> (Type, Vec<(DefId, Type)>) {
+        let parameters = self.tables.generics.get(&def).cloned().unwrap_or_default();
+
+        let arguments = match self.inference.resolve(expected) {
+            Type::Named(other, _, arguments)
+                if other == def && arguments.len() == parameters.len() =>
+            {
+                arguments
+            }
+            _ => vec![Type::Never; parameters.len()],
+        };
+
+        self.instance(def, parameters.into_iter().zip(arguments).collect())
+    }
+
+    fn instance(&self, def: DefId, substitution: Vec<(DefId, Type)>) -> (Type, Vec<(DefId, Type)>) {
+        let arguments = substitution.iter().map(|(_, ty)| ty.clone()).collect();
+        let name = self.definitions.get(def).name.clone();
+        (Type::Named(def, name, arguments), substitution)
+    }
+}
+
+/// Reports the values of unsized types, slices and `str`, that are used by value. They may only
+/// be used as places: borrowed, indexed, or with a field or method accessed on them.
+struct Unsized<'a> {
+    errors: &'a mut Vec<Error>,
+    place: bool, // Whether the expression visited next is used as a place
+}
+
+impl Visitor<Typed> for Unsized<'_> {
+    fn visit_item(&mut self, item: &Item<Typed>) {
+        // Nested items were checked when they were finished on their own.
+        if let ItemKind::Function(function) = &item.kind {
+            self.visit_function(function);
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Expression<Typed>) {
+        use ExpressionWithoutBlock::*;
+
+        let place = std::mem::take(&mut self.place);
+        let ty = type_of(expression);
+
+        if !place && matches!(ty, Type::Slice(_) | Type::Str) {
+            self.errors.push(Error::with_details(
+                expression.span,
+                format!("The size of `{ty}` is not known at compile time"),
+                format!("Borrow it instead, as `&{ty}`"),
+            ));
+        }
+
+        let ExpressionKind::ExpressionWithoutBlock(without_block) = &expression.item else {
+            return walk_expression(self, expression);
+        };
+
+        match without_block {
+            Grouped(inner, _) => {
+                self.place = place;
+                self.visit_expression(inner);
+            }
+            Prefix(PrefixOperator::Borrow | PrefixOperator::MutableBorrow, operand, _) => {
+                self.place = true;
+                self.visit_expression(operand);
+            }
+            Index(base, index, _) => {
+                self.place = true;
+                self.visit_expression(base);
+                self.visit_expression(index);
+            }
+            Field(base, _, _) => {
+                self.place = true;
+                self.visit_expression(base);
+            }
+            MethodCall(receiver, _, arguments, _) => {
+                self.place = true;
+                self.visit_expression(receiver);
+
+                for argument in arguments {
+                    self.visit_expression(argument);
+                }
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+/// Replaces the inference variables within a checked item by the types inferred for them.
+struct Finalize<'a>(&'a Inference);
+
+impl VisitorMut<Typed> for Finalize<'_> {
+    fn visit_with_block_mut(&mut self, expression: &mut ExpressionWithBlock<Typed>) {
+        let ty = with_block_type_mut(expression);
+        *ty = self.0.resolve(ty);
+        walk_with_block_mut(self, expression)
+    }
+
+    fn visit_without_block_mut(&mut self, expression: &mut ExpressionWithoutBlock<Typed>) {
+        let ty = without_block_type_mut(expression);
+        *ty = self.0.resolve(ty);
+        walk_without_block_mut(self, expression)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern<Typed>) {
+        let ty = pattern_type_mut(&mut pattern.item);
+        *ty = self.0.resolve(ty);
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        *ty = self.0.resolve(ty);
+    }
+}
+
+fn use_tree(tree: UseTree<Resolved>) -> UseTree<Typed> {
+    UseTree {
+        prefix: tree.prefix,
+        kind: match tree.kind {
+            UseTreeKind::Simple(name) => UseTreeKind::Simple(name),
+            UseTreeKind::Nested(trees, span) => {
+                UseTreeKind::Nested(trees.into_iter().map(use_tree).collect(), span)
+            }
+            UseTreeKind::Glob => UseTreeKind::Glob,
+        },
+    }
+}
+
+fn symbol(operator: &BinaryOperator) -> &'static str {
+    use BinaryOperator::*;
+
+    match operator {
+        Plus => "+",
+        Minus => "-",
+        Times => "*",
+        Divide => "/",
+        And => "&",
+        Or => "|",
+        LShift => "<<",
+        RShift => ">>",
+        Eq => "==",
+        Ne => "!=",
+        Gt => ">",
+        Lt => "<",
+        Ge => ">=",
+        Le => "<=",
+        LazyOr => "||",
+        LazyAnd => "&&",
+        Equal => "=",
+        PlusEqual => "+=",
+        MinusEqual => "-=",
+        TimesEqual => "*=",
+        DivideEqual => "/=",
+        AndEqual => "&=",
+        OrEqual => "|=",
+        LShiftEqual => "<<=",
+        RShiftEqual => ">>=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{self, messages, span, span_in, type_errors};
+    use crate::typeck::*;
+
+    fn typed(source: &str) -> Geode<Typed> {
+        testing::typed(source).0
+    }
+
+    /// The types of the `let` statements leading the body of a function.
+    fn let_types(geode: &Geode<Typed>, index: usize) -> Vec<Type> {
+        let statements = match &geode.modules[0].items[index].kind {
+            ItemKind::Function(FunctionItem {
+                body: Some(body), ..
+            }) => match body.first() {
+                Some(Some(Statements::Leading(first, rest, _))) => {
+                    std::iter::once(first).chain(rest).collect::<Vec<_>>()
+                }
+                _ => Vec::new(),
+            },
+            _ => panic!("Expected a function"),
+        };
+
+        statements
+            .into_iter()
+            .filter_map(|statement| match statement {
+                Statement::Let(Let { ty, .. }) => ty.clone(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn tail_type(geode: &Geode<Typed>, index: usize) -> &Type {
+        match &geode.modules[0].items[index].kind {
+            ItemKind::Function(FunctionItem {
+                body: Some(body), ..
+            }) => match body.last() {
+                Some(Some(Statements::Block(tail) | Statements::Leading(_, _, Some(tail)))) => {
+                    type_of(tail)
+                }
+                _ => panic!("Expected a trailing expression"),
+            },
+            _ => panic!("Expected a function"),
+        }
+    }
+
+    #[test]
+    fn binary_operators() {
+        let source = "fn f(a: u8, b: bool) -> u8 { a + b }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Mismatched types",
+                Some("Expected `u8`, found `bool`"),
+                span_in(source, "a + b", "b")
+            )]
+        );
+
+        let geode = typed("fn f(a: u8) -> bool { a < 3 && a == 2 }");
+
+        assert_eq!(*tail_type(&geode, 0), Type::Bool);
+    }
+
+    #[test]
+    fn integer_literals() {
+        let source = "fn f() -> u8 { 256 }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Integer literal `256` is out of range for `u8`",
+                None,
+                span(source, "256")
+            )]
+        );
+
+        assert!(type_errors("fn f() -> i8 { -128 }").is_empty());
+    }
+
+    #[test]
+    fn return_type() {
+        let source = "fn f() -> bool { let x = 1; x }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Mismatched types",
+                Some("Expected `bool`, found `{integer}`"),
+                span_in(source, "f(", "f")
+            )]
+        );
+
+        // The body diverges, so only the returned value is mismatched.
+        let source = "fn f() -> u8 { return True; }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Mismatched types",
+                Some("Expected `u8`, found `bool`"),
+                span(source, "True")
+            )]
+        );
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = "fn f() -> u8 { let x = 1; }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Function `f` can reach the end of its body without returning",
+                Some("The function returns `u8`, but its body may finish without a value"),
+                span_in(source, "f(", "f")
+            )]
+        );
+
+        let geode = typed(
+            "
+            fn f() -> u8 { while True {}; }
+            fn g(c: bool) -> bool { let x = if c { return False } else { True }; x }
+            ",
+        );
+
+        // The diverging branch leaves the other to decide the type.
+        assert_eq!(let_types(&geode, 1), vec![Type::Bool]);
+    }
+
+    #[test]
+    fn block_like_tails() {
+        let source = "
+            fn a(c: bool) -> u8 { if c { 1 } else { 2 } }
+            fn b(c: u8) -> u8 { when c { 0 => 1, _ => 2 } }
+            fn d() -> u8 { { 3 } }
+            fn e() -> u8 { loop { break 5; } }
+            fn g() -> u8 { while True {} }
+            fn h(c: bool) -> u8 { if c { return 1; } }
+            fn i(c: bool) -> u8 { while c {} }
+        ";
+
+        // Only the bodies whose trailing expression can finish without a value are reported.
+        assert_eq!(
+            type_errors(source)
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Function `h` can reach the end of its body without returning",
+                "Function `i` can reach the end of its body without returning",
+            ]
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let source = "
+            fn g(x: u8) -> u8 { x }
+            fn f() -> u8 { g(1, 2) + g(True) }
+        ";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![
+                (
+                    "Wrong number of arguments",
+                    Some("Expected 1 arguments, found 2"),
+                    span(source, "g(1, 2)")
+                ),
+                (
+                    "Mismatched types",
+                    Some("Expected `u8`, found `bool`"),
+                    span(source, "True")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn generic_calls() {
+        let geode = typed(
+            "
+            fn id[T](x: T) -> T { x }
+            fn f() -> bool { id(True) }
+            ",
+        );
+
+        assert_eq!(*tail_type(&geode, 1), Type::Bool);
+    }
+
+    #[test]
+    fn fields() {
+        let source = "
+            struct P { x: u8 }
+            fn f(p: &P) -> u8 { p.x + p.y }
+        ";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "No field named `y` on type `P`",
+                None,
+                span_in(source, "p.y", "y")
+            )]
+        );
+    }
+
+    #[test]
+    fn assignments_and_indexing() {
+        let source = "fn f(a: [u8; 4]) { let mut i: i8 = 0; i = True; a[i]; i + 1 = i; }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![
+                (
+                    "Mismatched types",
+                    Some("Expected `i8`, found `bool`"),
+                    span(source, "True")
+                ),
+                (
+                    "Arrays and slices must be indexed by an unsigned integer, but found `i8`",
+                    None,
+                    span_in(source, "a[i]", "i")
+                ),
+                (
+                    "Cannot assign to this expression",
+                    Some("Only variables, fields, indexed elements, and dereferenced references can be assigned to"),
+                    span(source, "i + 1")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn deferred_initialization() {
+        let geode = typed("fn f() -> u8 { let x; x = 5; x }");
+
+        assert_eq!(let_types(&geode, 0), vec![Type::U8]);
+        assert_eq!(*tail_type(&geode, 0), Type::U8);
+    }
+
+    #[test]
+    fn annotations_needed() {
+        let source = "fn f() { let foo0; }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Type annotations needed",
+                Some("Nothing determines the type of this value"),
+                span(source, "foo0")
+            )]
+        );
+    }
+
+    #[test]
+    fn annotations_needed_at_bindings() {
+        let source = "fn f() { let e = []; let g = e; }";
+
+        // The unknown element type is reported where an annotation would give it, at the
+        // first binding of the array rather than at the array itself.
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Type annotations needed",
+                Some("Nothing determines the type of this value"),
+                span_in(source, "e =", "e")
+            )]
+        );
+    }
+
+    #[test]
+    fn integer_default() {
+        let (geode, definitions) =
+            testing::resolve("fn f() { let foo1 = 5; let x = 7; let y: u16 = x; }");
+        let (geode, _) = check(geode, &definitions, &Type::U8).ok().unwrap();
+
+        assert_eq!(let_types(&geode, 0), vec![Type::U8, Type::U16, Type::U16]);
+
+        let source = "fn f() { let x = 300; let y: u8 = x; }";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![(
+                "Integer literal `300` is out of range for `u8`",
+                None,
+                span(source, "300")
+            )]
+        );
+    }
+
+    #[test]
+    fn type_aliases() {
+        let (_, tables, _) = testing::typed(
+            "
+            type Byte = u8;
+            type Bytes = [Byte; 2];
+            fn f(b: Bytes) -> u8 { b[0] }
+            ",
+        );
+
+        assert!(tables
+            .aliases
+            .values()
+            .any(|ty| *ty == Type::Array(Box::new(Type::U8), 2)));
+
+        let source = "
+            type A = B;
+            type B = &A;
+        ";
+        let errors = type_errors(source);
+
+        assert_eq!(
+            messages(&errors),
+            vec![(
+                "Type alias `A` is defined in terms of itself",
+                Some("Aliases are expanded wherever they are used, so their expansion would never end"),
+                span(source, "A")
+            )]
+        );
+        assert_eq!(
+            errors[0].notes,
+            vec![
+                Spanned::new(
+                    String::from("`A` refers to `B` here"),
+                    span_in(source, "A = ", "B")
+                ),
+                Spanned::new(String::from("`B` refers to `A` here"), span(source, "&A")),
+            ]
+        );
+    }
+
+    #[test]
+    fn infinite_size() {
+        let source = "
+            struct List { head: u8, tail: Node }
+            struct Node { list: [List; 1] }
+            struct Tree { left: &Tree }
+        ";
+        let errors = type_errors(source);
+
+        assert_eq!(
+            messages(&errors),
+            vec![(
+                "Recursive type `List` has infinite size",
+                Some("Store `Node` behind a reference, such as `&Node`, to give it a finite size"),
+                span(source, "List")
+            )]
+        );
+        assert_eq!(
+            errors[0].notes,
+            vec![
+                Spanned::new(
+                    String::from("`List` contains `Node` here"),
+                    span(source, "tail")
+                ),
+                Spanned::new(
+                    String::from("`Node` contains `List` here"),
+                    span(source, "list")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn generic_enums() {
+        let geode = typed("fn f(x: u8) -> Option[u8] { Some(x) }");
+
+        assert_eq!(tail_type(&geode, 0).to_string(), "Option[u8]");
+
+        let source = "
+            fn f(x: u8) -> Option[bool] { Some(x) }
+            fn g(y: Option) {}
+        ";
+
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![
+                (
+                    "Wrong number of type arguments for `Option`",
+                    Some("`Option` takes 1 type arguments, but 0 were given"),
+                    span_in(source, "y: ", "Option")
+                ),
+                (
+                    "Mismatched types",
+                    Some("Expected `Option[bool]`, found `Option[u8]`"),
+                    span_in(source, "f(", "f")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_fields() {
+        let source = "
+            mod m {
+                pub enum E { C { x: u8 }, pub(geode) D { y: u8 } }
+                pub struct S { x: u8 }
+            }
+            fn f(e: m::E) -> u8 {
+                when e { m::E::C { x } => x, m::E::D { y } => y }
+            }
+            fn g(s: m::S) -> u8 { s.x }
+        ";
+
+        // The fields of a variant are as visible as the variant, unlike those of a struct.
+        let errors = type_errors(source);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Field `x` of `S` is private");
+        assert_eq!(errors[0].span, span_in(source, "s.x", "x"));
+    }
+
+    #[test]
+    fn trait_methods() {
+        let source = "
+            struct P { x: u8 }
+            trait A { fn get(self) -> u8; }
+            trait B { fn get(self) -> u8; }
+            trait C { fn name(self) -> u8; }
+            impl A for P { fn get(self) -> u8 { 1 } }
+            impl B for P { fn get(self) -> u8 { 2 } }
+            impl P { fn name(self) -> u8 { 3 } }
+            impl C for P { fn name(self) -> u8 { 4 } }
+            fn f(p: P) -> u8 { p.name() }
+        ";
+
+        // The inherent `name` takes precedence over the one from `C`, but `get` is ambiguous.
+        let errors = type_errors(source);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "Method `get` for `P` is provided by more than one trait"
+        );
+        assert_eq!(errors[0].notes[0].item, "`A` also provides `get` here");
+    }
+
+    #[test]
+    fn unsized_values() {
+        let source = "
+            fn f(b: [u8; 8], s: &[u8]) -> u32 {
+                let e = b[2..4];
+                let t = *s;
+                let r = &b[2..4];
+                let x = (*s)[0] + r[1];
+                (*s).len + (&*s).len
+            }
+        ";
+
+        // Slices may only be borrowed, indexed, or have their length taken.
+        assert_eq!(
+            messages(&type_errors(source)),
+            vec![
+                (
+                    "The size of `[u8]` is not known at compile time",
+                    Some("Borrow it instead, as `&[u8]`"),
+                    span(source, "b[2..4]")
+                ),
+                (
+                    "The size of `[u8]` is not known at compile time",
+                    Some("Borrow it instead, as `&[u8]`"),
+                    span(source, "*s")
+                ),
+            ]
+        );
+    }
+}
@@ -84,6 +84,7 @@ pub enum Base {
 //     Continue,
 // }
 
+#[allow(dead_code)]
 enum DecimalDigit {
     Zero = 0,
     One = 1,